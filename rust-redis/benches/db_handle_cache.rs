@@ -0,0 +1,93 @@
+//! GET throughput of many concurrent clients, each either taking the
+//! databases read lock to clone its db handle for every command or reusing
+//! the handle it cached, as connections now do. A writer takes the write
+//! lock now and then, the way SWAPDB and FLUSHALL do, and the clients that
+//! lock per command queue behind it.
+//!
+//! Run with `cargo bench --bench db_handle_cache`.
+#![allow(dead_code)]
+#![allow(unused_imports)]
+
+#[path = "../src/clock.rs"]
+mod clock;
+#[path = "../src/db.rs"]
+mod db;
+#[path = "../src/hll.rs"]
+mod hll;
+#[path = "../src/rax.rs"]
+mod rax;
+#[path = "../src/stream.rs"]
+mod stream;
+
+use bytes::Bytes;
+use db::{Db, Entry, Value, sharded};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+const CLIENTS: usize = 64;
+const KEYS: usize = 1024;
+const RUN_FOR: Duration = Duration::from_millis(500);
+
+fn gets_per_sec(databases: &[RwLock<Db>], keys: &[Bytes], cached: bool, busy_writer: bool) -> u64 {
+    let deadline = Instant::now() + RUN_FOR;
+    let done = AtomicBool::new(false);
+    std::thread::scope(|s| {
+        if busy_writer {
+            s.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    let guard = databases[0].write().unwrap();
+                    std::thread::sleep(Duration::from_millis(1));
+                    drop(guard);
+                    std::thread::sleep(Duration::from_millis(4));
+                }
+            });
+        }
+        let clients: Vec<_> = (0..CLIENTS)
+            .map(|c| {
+                s.spawn(move || {
+                    let handle = databases[0].read().unwrap().clone();
+                    let mut ops = 0u64;
+                    while Instant::now() < deadline {
+                        let key = &keys[(c * 31 + ops as usize) % KEYS];
+                        if cached {
+                            assert!(handle.get(key).is_some());
+                        } else {
+                            let db = databases[0].read().unwrap().clone();
+                            assert!(db.get(key).is_some());
+                        }
+                        ops += 1;
+                    }
+                    ops
+                })
+            })
+            .collect();
+        let total: u64 = clients.into_iter().map(|c| c.join().unwrap()).sum();
+        done.store(true, Ordering::Relaxed);
+        (total as f64 / RUN_FOR.as_secs_f64()) as u64
+    })
+}
+
+fn main() {
+    let keys: Vec<Bytes> = (0..KEYS)
+        .map(|i| Bytes::from(format!("key:{}", i)))
+        .collect();
+    let db: Db = Arc::new(sharded(64));
+    for key in &keys {
+        db.insert(
+            key.clone(),
+            Entry::new(Value::String(Bytes::from("v")), None),
+        );
+    }
+    let databases = vec![RwLock::new(db)];
+
+    for busy_writer in [false, true] {
+        println!(
+            "{} GET clients{}: {} ops/s locking per command, {} ops/s with cached handles",
+            CLIENTS,
+            if busy_writer { ", periodic writer" } else { "" },
+            gets_per_sec(&databases, &keys, false, busy_writer),
+            gets_per_sec(&databases, &keys, true, busy_writer)
+        );
+    }
+}
@@ -0,0 +1,79 @@
+//! Readers of a set of hot keys while a slow write holds one of them, the
+//! way a DEL of a huge value or a big SORT STORE does. A reader whose key
+//! shares that key's shard stalls for the whole write, so the more shards,
+//! the fewer readers stall.
+//!
+//! Run with `cargo bench --bench db_shards`.
+#![allow(dead_code)]
+#![allow(unused_imports)]
+
+#[path = "../src/clock.rs"]
+mod clock;
+#[path = "../src/db.rs"]
+mod db;
+#[path = "../src/hll.rs"]
+mod hll;
+#[path = "../src/rax.rs"]
+mod rax;
+#[path = "../src/stream.rs"]
+mod stream;
+
+use bytes::Bytes;
+use db::{Db, Entry, Value, sharded};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const HOT_KEYS: usize = 32;
+const HOLD: Duration = Duration::from_millis(50);
+
+fn stalled_readers(shards: usize) -> usize {
+    let db: Db = Arc::new(sharded(shards));
+    for i in 0..HOT_KEYS {
+        db.insert(
+            Bytes::from(format!("hot:{}", i)),
+            Entry::new(Value::String(Bytes::from("v")), None),
+        );
+    }
+
+    let writing = db.get_mut(b"hot:0".as_slice()).unwrap();
+    let deadline = Instant::now() + HOLD;
+    std::thread::scope(|s| {
+        let readers: Vec<_> = (1..HOT_KEYS)
+            .map(|i| {
+                let db = &db;
+                s.spawn(move || {
+                    let key = Bytes::from(format!("hot:{}", i));
+                    let mut served = 0;
+                    while Instant::now() < deadline {
+                        assert!(db.get(&key).is_some());
+                        if Instant::now() < deadline {
+                            served += 1;
+                        }
+                        // Clients between requests, not a busy loop
+                        std::thread::sleep(Duration::from_millis(1));
+                    }
+                    served
+                })
+            })
+            .collect();
+
+        std::thread::sleep(HOLD + Duration::from_millis(5));
+        drop(writing);
+        readers
+            .into_iter()
+            .map(|r| r.join().unwrap())
+            .filter(|&served| served == 0)
+            .count()
+    })
+}
+
+fn main() {
+    for shards in [2, 16, 64, 256] {
+        println!(
+            "{:>3} shards: {} of {} hot-key readers stalled behind one write",
+            shards,
+            stalled_readers(shards),
+            HOT_KEYS - 1
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{self, BufRead, Write};
@@ -108,6 +109,48 @@ impl User {
         }
     }
 
+    /// SHA-256 hex digests of the user's passwords, as ACL GETUSER reports them.
+    pub fn password_hashes(&self) -> Vec<String> {
+        let mut hashes: Vec<String> = self
+            .passwords
+            .iter()
+            .map(|p| hex::encode(Sha256::digest(p.as_bytes())))
+            .collect();
+        hashes.sort();
+        hashes
+    }
+
+    /// Key patterns as rules, e.g. `~*` or `~user:* ~tmp:*`. Empty when the
+    /// user may access no keys.
+    pub fn key_rules(&self) -> String {
+        if self.all_keys {
+            return "~*".to_string();
+        }
+        self.allowed_key_patterns
+            .iter()
+            .map(|p| format!("~{}", p))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Command permissions as rules, e.g. `+@all -flushdb` or `-@all +get`.
+    pub fn command_rules(&self) -> String {
+        let (base, sign, cmds) = if self.all_commands {
+            ("+@all", '-', &self.disallowed_commands)
+        } else {
+            ("-@all", '+', &self.allowed_commands)
+        };
+        let mut cmds: Vec<&String> = cmds.iter().collect();
+        cmds.sort();
+        let mut s = base.to_string();
+        for cmd in cmds {
+            s.push(' ');
+            s.push(sign);
+            s.push_str(cmd);
+        }
+        s
+    }
+
     pub fn to_string(&self) -> String {
         let mut s = format!("user {}", self.name);
         if self.enabled {
@@ -122,36 +165,13 @@ impl User {
             s.push_str(" nopass");
         }
 
-        if self.all_keys {
-            s.push_str(" ~*");
-        } else if self.allowed_key_patterns.is_empty() {
-            // Maybe explicitly deny all keys? Redis default is no keys access if not specified.
-            // But if we want to represent it:
-            // s.push_str(" resetkeys");
-        } else {
-            for pattern in &self.allowed_key_patterns {
-                s.push_str(&format!(" ~{}", pattern));
-            }
-        }
-
-        if self.all_commands {
-            s.push_str(" +@all");
-            for cmd in &self.disallowed_commands {
-                s.push_str(&format!(" -{}", cmd));
-            }
-        } else {
-            // Default is -@all
-            // But we don't output -@all explicitly unless it's empty?
-            // Redis `ACL SAVE` output is normalized.
-            // If all_commands is false, we list allowed commands.
-            if self.allowed_commands.is_empty() {
-                s.push_str(" -@all");
-            } else {
-                for cmd in &self.allowed_commands {
-                    s.push_str(&format!(" +{}", cmd));
-                }
-            }
+        let keys = self.key_rules();
+        if !keys.is_empty() {
+            s.push(' ');
+            s.push_str(&keys);
         }
+        s.push(' ');
+        s.push_str(&self.command_rules());
         s
     }
 }
@@ -2,36 +2,107 @@ use crate::cmd::process_frame;
 use crate::cmd::scripting::ScriptManager;
 use crate::conf::Config;
 use crate::db::{Db, Value};
-use crate::resp::{Resp, read_frame};
+use crate::resp::{Resp, fmt_int, fmt_usize, read_frame};
 use bytes::Bytes;
 use rand::Rng;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::fs::{File, OpenOptions};
-use tokio::io::{self, AsyncWriteExt, BufWriter};
+use tokio::io::{self, AsyncWrite, AsyncWriteExt, BufWriter};
 use tokio::task::JoinHandle;
 
 enum AofMsg {
     Append(Resp),
     AppendSync(Resp, tokio::sync::oneshot::Sender<()>),
     Flush(tokio::sync::oneshot::Sender<()>),
+    Offset(tokio::sync::oneshot::Sender<u64>),
     Rewrite(
         Arc<Vec<RwLock<Db>>>,
+        Vec<String>,
         tokio::sync::oneshot::Sender<io::Result<()>>,
     ),
 }
 
+/// Counters the AOF task publishes for `INFO persistence` and `WAITAOF`.
+pub struct AofStats {
+    /// File size when it was opened or last rewritten.
+    pub base_size: AtomicU64,
+    /// File size including appends still sitting in the write buffer.
+    pub current_size: AtomicU64,
+    /// Bytes appended since startup. Unlike the file size this never shrinks
+    /// on rewrite, so it is what `WAITAOF` waits on.
+    pub write_offset: AtomicU64,
+    pub last_write_ok: AtomicBool,
+    pub rewrite_in_progress: AtomicBool,
+    pub pending_rewrite: AtomicBool,
+    fsync_offset: tokio::sync::watch::Sender<u64>,
+}
+
+impl AofStats {
+    fn new(size: u64) -> Self {
+        Self {
+            base_size: AtomicU64::new(size),
+            current_size: AtomicU64::new(size),
+            write_offset: AtomicU64::new(0),
+            last_write_ok: AtomicBool::new(true),
+            rewrite_in_progress: AtomicBool::new(false),
+            pending_rewrite: AtomicBool::new(false),
+            fsync_offset: tokio::sync::watch::Sender::new(0),
+        }
+    }
+
+    /// `write_offset` as of the last fsync.
+    pub fn fsync_offset(&self) -> u64 {
+        *self.fsync_offset.borrow()
+    }
+
+    /// Wait until everything up to `offset` has been fsynced.
+    pub async fn wait_fsynced(&self, offset: u64) {
+        let mut rx = self.fsync_offset.subscribe();
+        let _ = rx.wait_for(|synced| *synced >= offset).await;
+    }
+
+    fn record_write(&self, len: u64) {
+        self.current_size.fetch_add(len, Ordering::Relaxed);
+        self.write_offset.fetch_add(len, Ordering::Relaxed);
+    }
+
+    fn mark_fsynced(&self) {
+        self.fsync_offset
+            .send_replace(self.write_offset.load(Ordering::Relaxed));
+    }
+}
+
 /// Cheaply cloneable handle to the background AOF writer task.
 /// Callers send commands through a channel; the task owns the file exclusively.
 #[derive(Clone)]
 pub struct AofWriter {
     sender: tokio::sync::mpsc::Sender<AofMsg>,
     policy: AppendFsync,
+    stats: Arc<AofStats>,
 }
 
 impl AofWriter {
+    pub fn stats(&self) -> &AofStats {
+        &self.stats
+    }
+
+    /// The `write_offset` once every command sent before this call has been
+    /// written, so a caller can wait for its own writes to be fsynced.
+    pub async fn offset(&self) -> u64 {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        if self.sender.send(AofMsg::Offset(tx)).await.is_ok()
+            && let Ok(offset) = rx.await
+        {
+            return offset;
+        }
+        self.stats.write_offset.load(Ordering::Relaxed)
+    }
+
     /// Append a command.  For `appendfsync always` this awaits the disk sync;
     /// for `everysec` / `no` it waits for channel capacity rather than dropping.
     pub async fn append(&self, cmd: &Resp) {
@@ -63,11 +134,17 @@ impl AofWriter {
         }
     }
 
-    /// Trigger an AOF rewrite and wait for it to complete.
-    pub async fn rewrite(&self, databases: Arc<Vec<RwLock<Db>>>) -> io::Result<()> {
+    /// Trigger an AOF rewrite of `databases` and the FUNCTION library
+    /// sources in `functions`, and wait for it to complete.
+    pub async fn rewrite(
+        &self,
+        databases: Arc<Vec<RwLock<Db>>>,
+        functions: Vec<String>,
+    ) -> io::Result<()> {
         let (tx, rx) = tokio::sync::oneshot::channel();
+        self.stats.pending_rewrite.store(true, Ordering::Relaxed);
         self.sender
-            .send(AofMsg::Rewrite(databases, tx))
+            .send(AofMsg::Rewrite(databases, functions, tx))
             .await
             .map_err(|_| io::Error::new(io::ErrorKind::Other, "AOF task died"))?;
         rx.await
@@ -84,6 +161,7 @@ impl AofWriter {
 /// `flush()` call is eliminated for `EverySec` / `No` modes.
 pub fn start_aof_task(aof: Aof) -> AofWriter {
     let policy = aof.policy;
+    let stats = aof.stats.clone();
     let (sender, mut receiver) = tokio::sync::mpsc::channel::<AofMsg>(4096);
     tokio::spawn(async move {
         let mut aof = aof;
@@ -108,20 +186,21 @@ pub fn start_aof_task(aof: Aof) -> AofWriter {
                                 let _ = reply.send(());
                             }
                             Some(AofMsg::Flush(reply)) => {
-                                let _ = aof.writer.flush().await;
-                                let _ = aof.writer.get_mut().sync_data().await;
+                                let _ = aof.fsync().await;
                                 let _ = reply.send(());
                             }
-                            Some(AofMsg::Rewrite(databases, reply)) => {
-                                let _ = reply.send(aof.rewrite(&databases).await);
+                            Some(AofMsg::Offset(reply)) => {
+                                let _ = reply.send(aof.stats.write_offset.load(Ordering::Relaxed));
+                            }
+                            Some(AofMsg::Rewrite(databases, functions, reply)) => {
+                                let _ = reply.send(aof.rewrite(&databases, &functions).await);
                             }
                             None => break,
                         }
                     }
                     _ = ticker.tick() => {
                         // Flush BufWriter → OS, then fsync OS → disk.
-                        let _ = aof.writer.flush().await;
-                        let _ = aof.writer.get_mut().sync_data().await;
+                        let _ = aof.fsync().await;
                     }
                 }
             }
@@ -136,18 +215,52 @@ pub fn start_aof_task(aof: Aof) -> AofWriter {
                         let _ = reply.send(());
                     }
                     AofMsg::Flush(reply) => {
-                        let _ = aof.writer.flush().await;
-                        let _ = aof.writer.get_mut().sync_data().await;
+                        let _ = aof.fsync().await;
                         let _ = reply.send(());
                     }
-                    AofMsg::Rewrite(databases, reply) => {
-                        let _ = reply.send(aof.rewrite(&databases).await);
+                    AofMsg::Offset(reply) => {
+                        let _ = reply.send(aof.stats.write_offset.load(Ordering::Relaxed));
+                    }
+                    AofMsg::Rewrite(databases, functions, reply) => {
+                        let _ = reply.send(aof.rewrite(&databases, &functions).await);
                     }
                 }
             }
         }
     });
-    AofWriter { sender, policy }
+    AofWriter {
+        sender,
+        policy,
+        stats,
+    }
+}
+
+/// Passes writes through to `inner`, counting the bytes it accepts.
+struct CountingWriter<'a, W> {
+    inner: &'a mut W,
+    written: u64,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for CountingWriter<'_, W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let res = Pin::new(&mut *self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = res {
+            self.written += n as u64;
+        }
+        res
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.inner).poll_shutdown(cx)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -162,6 +275,7 @@ pub struct Aof {
     path: String,
     policy: AppendFsync,
     sync_task: Option<JoinHandle<()>>,
+    stats: Arc<AofStats>,
 }
 
 impl Aof {
@@ -172,6 +286,7 @@ impl Aof {
             .append(true)
             .open(path)
             .await?;
+        let size = file.metadata().await?.len();
 
         let sync_task = if policy == AppendFsync::EverySec {
             let file_clone = file.try_clone().await?;
@@ -194,27 +309,51 @@ impl Aof {
             path: path.to_string(),
             policy,
             sync_task,
+            stats: Arc::new(AofStats::new(size)),
         })
     }
 
     /// Append a frame and immediately flush + fsync (used for `Always` and as
     /// the fallback for `Flush` messages).
     pub async fn append(&mut self, frame: &Resp) -> io::Result<()> {
-        write_resp(&mut self.writer, frame).await?;
+        self.append_nobuf(frame).await?;
         self.writer.flush().await?;
 
         if self.policy == AppendFsync::Always {
             self.writer.get_mut().sync_all().await?;
         }
+        // With `appendfsync no` the OS decides when to sync, so data handed
+        // to it counts as durable as it is going to get.
+        if self.policy != AppendFsync::EverySec {
+            self.stats.mark_fsynced();
+        }
 
         Ok(())
     }
 
+    /// Flush the write buffer and fsync the file.
+    async fn fsync(&mut self) -> io::Result<()> {
+        self.writer.flush().await?;
+        self.writer.get_mut().sync_data().await?;
+        self.stats.mark_fsynced();
+        Ok(())
+    }
+
     /// Append a frame **without** flushing the BufWriter.  The caller is
     /// responsible for flushing periodically (e.g., via the 1-second ticker in
     /// `start_aof_task`).  Only suitable for `EverySec` / `No` policies.
     async fn append_nobuf(&mut self, frame: &Resp) -> io::Result<()> {
-        write_resp(&mut self.writer, frame).await
+        let mut counter = CountingWriter {
+            inner: &mut self.writer,
+            written: 0,
+        };
+        let res = write_resp(&mut counter, frame).await;
+        let written = counter.written;
+        self.stats.record_write(written);
+        self.stats
+            .last_write_ok
+            .store(res.is_ok(), Ordering::Relaxed);
+        res
     }
 
     pub async fn load(
@@ -274,7 +413,27 @@ impl Aof {
         Ok(())
     }
 
-    pub async fn rewrite(&mut self, databases: &Arc<Vec<RwLock<Db>>>) -> io::Result<()> {
+    pub async fn rewrite(
+        &mut self,
+        databases: &Arc<Vec<RwLock<Db>>>,
+        functions: &[String],
+    ) -> io::Result<()> {
+        self.stats.pending_rewrite.store(false, Ordering::Relaxed);
+        self.stats
+            .rewrite_in_progress
+            .store(true, Ordering::Relaxed);
+        let res = self.rewrite_file(databases, functions).await;
+        self.stats
+            .rewrite_in_progress
+            .store(false, Ordering::Relaxed);
+        res
+    }
+
+    async fn rewrite_file(
+        &mut self,
+        databases: &Arc<Vec<RwLock<Db>>>,
+        functions: &[String],
+    ) -> io::Result<()> {
         let temp_path = format!("{}.tmp", self.path);
         let file = OpenOptions::new()
             .write(true)
@@ -284,6 +443,17 @@ impl Aof {
             .await?;
         let mut writer = BufWriter::new(file);
 
+        // Libraries first, so functions exist before anything calls them
+        for code in functions {
+            let load_cmd = Resp::Array(Some(vec![
+                Resp::BulkString(Some(Bytes::from("FUNCTION"))),
+                Resp::BulkString(Some(Bytes::from("LOAD"))),
+                Resp::BulkString(Some(Bytes::from("REPLACE"))),
+                Resp::BulkString(Some(Bytes::from(code.clone()))),
+            ]));
+            write_resp(&mut writer, &load_cmd).await?;
+        }
+
         // Iterate over DB and write reconstruction commands
         for (i, db_lock) in databases.iter().enumerate() {
             let db = db_lock.read().unwrap().clone();
@@ -385,7 +555,24 @@ impl Aof {
                             let cmd = Resp::Array(Some(args));
                             write_resp(&mut writer, &cmd).await?;
                         }
-                        None
+
+                        // 3. Restore what replaying the entries can't: the
+                        // last ID past deleted entries and the counters
+                        if s.is_empty() && s.groups.is_empty() {
+                            None
+                        } else {
+                            Some(Resp::Array(Some(vec![
+                                Resp::BulkString(Some(Bytes::from("XSETID"))),
+                                Resp::BulkString(Some(key.clone())),
+                                Resp::BulkString(Some(Bytes::from(s.last_id.to_string()))),
+                                Resp::BulkString(Some(Bytes::from("ENTRIESADDED"))),
+                                Resp::BulkString(Some(Bytes::from(s.entries_added.to_string()))),
+                                Resp::BulkString(Some(Bytes::from("MAXDELETEDID"))),
+                                Resp::BulkString(Some(Bytes::from(
+                                    s.max_deleted_id.to_string(),
+                                ))),
+                            ])))
+                        }
                     }
                     Value::HyperLogLog(hll) => Some(Resp::Array(Some(vec![
                         Resp::BulkString(Some(Bytes::from("SET"))),
@@ -412,6 +599,7 @@ impl Aof {
 
         writer.flush().await?;
         writer.get_mut().sync_all().await?; // Ensure data is safe before rename
+        let size = writer.get_ref().metadata().await?.len();
         drop(writer); // Close file
 
         // Rename temp to real
@@ -446,6 +634,11 @@ impl Aof {
 
         self.writer = BufWriter::new(file);
 
+        // The rewritten file already holds everything appended so far
+        self.stats.base_size.store(size, Ordering::Relaxed);
+        self.stats.current_size.store(size, Ordering::Relaxed);
+        self.stats.mark_fsynced();
+
         Ok(())
     }
 }
@@ -475,13 +668,15 @@ where
                 writer.write_all(b"\r\n").await?;
             }
             Resp::Integer(i) => {
+                let mut buf = [0u8; 20];
                 writer.write_all(b":").await?;
-                writer.write_all(i.to_string().as_bytes()).await?;
+                writer.write_all(fmt_int(*i, &mut buf)).await?;
                 writer.write_all(b"\r\n").await?;
             }
             Resp::BulkString(Some(b)) => {
+                let mut buf = [0u8; 20];
                 writer.write_all(b"$").await?;
-                writer.write_all(b.len().to_string().as_bytes()).await?;
+                writer.write_all(fmt_usize(b.len(), &mut buf)).await?;
                 writer.write_all(b"\r\n").await?;
                 writer.write_all(b).await?;
                 writer.write_all(b"\r\n").await?;
@@ -490,8 +685,9 @@ where
                 writer.write_all(b"$-1\r\n").await?;
             }
             Resp::Array(Some(items)) => {
+                let mut buf = [0u8; 20];
                 writer.write_all(b"*").await?;
-                writer.write_all(items.len().to_string().as_bytes()).await?;
+                writer.write_all(fmt_usize(items.len(), &mut buf)).await?;
                 writer.write_all(b"\r\n").await?;
                 for item in items {
                     write_resp(writer, item).await?;
@@ -500,6 +696,60 @@ where
             Resp::Array(None) => {
                 writer.write_all(b"*-1\r\n").await?;
             }
+            Resp::Verbatim(data) => {
+                let mut buf = [0u8; 20];
+                writer.write_all(b"=").await?;
+                writer
+                    .write_all(fmt_usize(data.len() + 4, &mut buf))
+                    .await?;
+                writer.write_all(b"\r\ntxt:").await?;
+                writer.write_all(data).await?;
+                writer.write_all(b"\r\n").await?;
+            }
+            Resp::Set(items) | Resp::Push(items) => {
+                let mut buf = [0u8; 20];
+                writer
+                    .write_all(if let Resp::Push(_) = resp { b">" } else { b"~" })
+                    .await?;
+                writer.write_all(fmt_usize(items.len(), &mut buf)).await?;
+                writer.write_all(b"\r\n").await?;
+                for item in items {
+                    write_resp(writer, item).await?;
+                }
+            }
+            Resp::Map(pairs) => {
+                let mut buf = [0u8; 20];
+                writer.write_all(b"%").await?;
+                writer.write_all(fmt_usize(pairs.len(), &mut buf)).await?;
+                writer.write_all(b"\r\n").await?;
+                for (key, value) in pairs {
+                    write_resp(writer, key).await?;
+                    write_resp(writer, value).await?;
+                }
+            }
+            Resp::Double(d) => {
+                writer.write_all(b",").await?;
+                writer.write_all(crate::resp::format_double(*d).as_bytes()).await?;
+                writer.write_all(b"\r\n").await?;
+            }
+            Resp::BigNumber(n) => {
+                writer.write_all(b"(").await?;
+                writer.write_all(n).await?;
+                writer.write_all(b"\r\n").await?;
+            }
+            Resp::Boolean(b) => {
+                writer
+                    .write_all(if *b { b"#t\r\n" } else { b"#f\r\n" })
+                    .await?;
+            }
+            Resp::Null => {
+                writer.write_all(b"_\r\n").await?;
+            }
+            Resp::Encoded(chunks) => {
+                for chunk in chunks {
+                    writer.write_all(chunk).await?;
+                }
+            }
             Resp::Multiple(items) => {
                 for item in items {
                     write_resp(writer, item).await?;
@@ -1,4 +1,9 @@
+#[path = "../cluster.rs"]
+mod cluster;
+// The client only encodes requests and decodes replies; the rest of the
+// shared RESP module serves the server
 #[path = "../resp.rs"]
+#[allow(dead_code)]
 mod resp;
 use resp::{Resp, read_frame, write_frame};
 use std::collections::HashMap;
@@ -46,7 +51,7 @@ fn print_resp(r: &Resp) {
         Resp::BulkString(None) => {
             println!("(nil)");
         }
-        Resp::BulkString(Some(b)) => match std::str::from_utf8(b.as_ref()) {
+        Resp::BulkString(Some(b)) | Resp::Verbatim(b) => match std::str::from_utf8(b.as_ref()) {
             Ok(s) => println!("{}", s),
             Err(_) => {
                 let hex = b
@@ -67,46 +72,44 @@ fn print_resp(r: &Resp) {
                 print_resp(it);
             }
         }
-        Resp::Multiple(items) => {
-            for it in items {
+        Resp::Set(items) | Resp::Push(items) => {
+            let kind = if let Resp::Push(_) = r { "push" } else { "set" };
+            println!("({}) {}", kind, items.len());
+            for (i, it) in items.iter().enumerate() {
+                print!("{}) ", i + 1);
                 print_resp(it);
             }
         }
-        Resp::NoReply | Resp::Control(_) => {}
-    }
-}
-
-fn hash_tag(key: &str) -> String {
-    if let Some(start) = key.find('{') {
-        if let Some(end) = key[start + 1..].find('}') {
-            let inner = &key[start + 1..start + 1 + end];
-            if !inner.is_empty() {
-                return inner.to_string();
+        Resp::Map(pairs) => {
+            println!("(map) {}", pairs.len());
+            for (i, (k, v)) in pairs.iter().enumerate() {
+                print!("{}# ", i + 1);
+                print_resp(k);
+                print!("   => ");
+                print_resp(v);
             }
         }
-    }
-    key.to_string()
-}
-
-fn crc16(data: &[u8]) -> u16 {
-    const POLY: u16 = 0x1021;
-    let mut crc: u16 = 0xFFFF;
-    for b in data {
-        crc ^= (*b as u16) << 8;
-        for _ in 0..8 {
-            if (crc & 0x8000) != 0 {
-                crc = (crc << 1) ^ POLY;
-            } else {
-                crc <<= 1;
+        Resp::Double(d) => {
+            println!("(double) {}", resp::format_double(*d));
+        }
+        Resp::BigNumber(n) => {
+            println!("(big number) {}", String::from_utf8_lossy(n));
+        }
+        Resp::Boolean(b) => {
+            println!("({})", b);
+        }
+        Resp::Null => {
+            println!("(nil)");
+        }
+        // Only the server builds these; the client decodes plain frames
+        Resp::Encoded(_) => {}
+        Resp::Multiple(items) => {
+            for it in items {
+                print_resp(it);
             }
         }
+        Resp::NoReply | Resp::Control(_) => {}
     }
-    crc
-}
-
-fn key_slot(key: &str) -> u16 {
-    let tag = hash_tag(key);
-    (crc16(tag.as_bytes()) % 16384) as u16
 }
 
 fn extract_key_from_tokens(tokens: &[String]) -> Option<&str> {
@@ -196,7 +199,7 @@ async fn main() -> io::Result<()> {
         };
 
         let target_addr = if let Some(key) = extract_key_from_tokens(&tokens) {
-            let s = key_slot(key);
+            let s = cluster::key_hash_slot(key.as_bytes());
             slot_map
                 .get(&s)
                 .cloned()
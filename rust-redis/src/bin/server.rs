@@ -1,10 +1,15 @@
 #![allow(unexpected_cfgs)]
 #![allow(unused_imports)]
 #![allow(dead_code)]
+use std::pin::Pin;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
-use tokio::net::TcpListener;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, BufWriter, ReadBuf,
+};
+use tokio::net::{TcpListener, TcpStream};
 use tracing::{error, info, warn};
 #[path = "../acl.rs"]
 pub mod acl;
@@ -99,19 +104,25 @@ async fn run_server(
     // Initialize multiple databases
     let mut dbs = Vec::with_capacity(cfg.databases as usize);
     for _ in 0..cfg.databases {
-        dbs.push(std::sync::RwLock::new(db::Db::default()));
+        dbs.push(std::sync::RwLock::new(Arc::new(db::sharded(cfg.db_shards))));
     }
     let databases = Arc::new(dbs);
 
+    // Create script cache
+    let script_manager = cmd::scripting::create_script_manager(cfg.lua_time_limit);
+
     if !cfg.appendonly {
-        if let Err(e) = rdb::rdb_load(&databases, &cfg) {
-            warn!("Failed to load RDB: {}", e);
+        match rdb::rdb_load(&databases, &cfg) {
+            Ok(functions) => {
+                if let Err(e) = script_manager.restore_libraries(&functions, "FLUSH") {
+                    let msg = String::from_utf8_lossy(&e.as_bytes()).trim().to_string();
+                    warn!("Failed to load functions from RDB: {}", msg);
+                }
+            }
+            Err(e) => warn!("Failed to load RDB: {}", e),
         }
     }
 
-    // Create script cache
-    let script_manager = cmd::scripting::create_script_manager();
-
     // Initialize ACL
     let mut acl_store = acl::Acl::new();
 
@@ -165,15 +176,21 @@ async fn run_server(
     };
     let mut server_ctx = cmd::ServerContext {
         databases: databases,
+        db_epoch: Arc::new(std::sync::atomic::AtomicU64::new(0)),
         acl: acl,
         aof: None, // filled in after AOF load below
         config: Arc::new(cfg.clone()),
         script_manager: script_manager.clone(),
-        blocking_waiters: std::sync::Arc::new(dashmap::DashMap::new()),
-        blocking_zset_waiters: std::sync::Arc::new(dashmap::DashMap::new()),
+        blocking_waiters: Arc::new(db::sharded(cfg.db_shards)),
+        blocking_zset_waiters: Arc::new(db::sharded(cfg.db_shards)),
+        stream_waiters: Arc::new(db::sharded(cfg.db_shards)),
         pubsub: std::sync::Arc::new(cmd::PubSubCtx::new()),
         start_time: std::time::Instant::now(),
-        clients_ctx: std::sync::Arc::new(cmd::ClientCtx::new()),
+        clients_ctx: std::sync::Arc::new(cmd::ClientCtx::new(
+            cfg.maxclients,
+            cfg.timeout,
+            cfg.tcp_keepalive,
+        )),
         repl: std::sync::Arc::new(cmd::ReplicationCtx::new(
             run_id,
             cfg.repl_backlog_size,
@@ -194,6 +211,7 @@ async fn run_server(
             cfg.maxmemory_policy,
             cfg.maxmemory_samples,
             cmd::notify::parse_notify_flags(&cfg.notify_keyspace_events),
+            cfg.encoding_limits,
         )),
         persist: std::sync::Arc::new(cmd::PersistenceCtx::new(
             cfg.rdbcompression,
@@ -207,6 +225,10 @@ async fn run_server(
         )),
         cluster_ctx: std::sync::Arc::new(cmd::ClusterCtx::new(cluster_state.clone())),
     };
+    server_ctx
+        .clients_ctx
+        .proto_max_bulk_len
+        .store(cfg.proto_max_bulk_len, Ordering::Relaxed);
 
     if cfg.cluster_enabled {
         let p = std::path::Path::new(&cfg.dir).join(&cfg.cluster_config_file);
@@ -228,8 +250,18 @@ async fn run_server(
 
     // Background task to clean up expired keys
     cmd::start_expiration_task(server_ctx.clone());
+    cmd::start_client_timeout_task(server_ctx.clone());
     cmd::start_cluster_topology_task(server_ctx.clone());
     cmd::start_cluster_failover_task(server_ctx.clone());
+    cmd::start_stats_sampler_task(server_ctx.clone());
+
+    // SIGTERM/SIGINT run the same shutdown sequence as the SHUTDOWN command.
+    let signal_task = cmd::save::start_shutdown_signal_task(server_ctx.clone());
+    tokio::spawn(async move {
+        if signal_task.await.is_ok() {
+            std::process::exit(0);
+        }
+    });
 
     // Background task for periodic RDB save
     let server_ctx_for_save = server_ctx.clone();
@@ -268,15 +300,82 @@ async fn run_server(
         }
     });
 
+    serve(listener, server_ctx).await;
+}
+
+/// Enable keepalive probes on an accepted socket, mirroring Redis' anetKeepAlive:
+/// first probe after `interval` idle seconds, then every `interval / 3` seconds,
+/// giving up after three unanswered probes.
+fn set_tcp_keepalive(stream: &TcpStream, interval: u64) {
+    let idle = Duration::from_secs(interval);
+    let keepalive = socket2::TcpKeepalive::new().with_time(idle);
+    #[cfg(target_os = "linux")]
+    let keepalive = keepalive
+        .with_interval((idle / 3).max(Duration::from_secs(1)))
+        .with_retries(3);
+    if let Err(e) = socket2::SockRef::from(stream).set_tcp_keepalive(&keepalive) {
+        warn!("failed to enable TCP keepalive: {}", e);
+    }
+}
+
+/// A socket half that adds every byte it moves to an INFO stats counter.
+struct CountedIo<T> {
+    inner: T,
+    counter: Arc<AtomicU64>,
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for CountedIo<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        let res = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let read = buf.filled().len() - before;
+        self.counter.fetch_add(read as u64, Ordering::Relaxed);
+        res
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for CountedIo<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        data: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let res = Pin::new(&mut self.inner).poll_write(cx, data);
+        if let Poll::Ready(Ok(n)) = &res {
+            self.counter.fetch_add(*n as u64, Ordering::Relaxed);
+        }
+        res
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Accept loop: admits connections up to `maxclients` and runs one task set
+/// (reader, writer, command loop) per client.
+async fn serve(listener: TcpListener, server_ctx: cmd::ServerContext) {
     let next_connection_id = Arc::new(AtomicU64::new(1));
 
     loop {
         let (mut socket, addr) = listener.accept().await.unwrap();
         let client_fd = Some(socket.as_raw_fd()); // Capture FD
+        let laddr = socket
+            .local_addr()
+            .map(|a| a.to_string())
+            .unwrap_or_default();
         info!("accepted connection from {}", addr);
 
         let current_clients = server_ctx.clients_ctx.client_count.load(Ordering::Relaxed);
-        if current_clients >= server_ctx.config.maxclients {
+        if current_clients >= server_ctx.clients_ctx.maxclients.load(Ordering::Relaxed) {
             warn!(
                 "max number of clients reached, rejecting connection from {}",
                 addr
@@ -287,7 +386,16 @@ async fn run_server(
             continue;
         }
 
+        let keepalive = server_ctx.clients_ctx.tcp_keepalive.load(Ordering::Relaxed);
+        if keepalive > 0 {
+            set_tcp_keepalive(&socket, keepalive);
+        }
+
         server_ctx.clients_ctx.client_count.fetch_add(1, Ordering::Relaxed);
+        server_ctx
+            .clients_ctx
+            .total_connections_received
+            .fetch_add(1, Ordering::Relaxed);
         let server_ctx_cloned: cmd::ServerContext = server_ctx.clone();
         let connection_id = next_connection_id.fetch_add(1, Ordering::Relaxed);
 
@@ -304,10 +412,15 @@ async fn run_server(
                 let ci = cmd::ClientInfo {
                     id: connection_id,
                     addr: addr.to_string(),
+                    laddr,
                     name: "".to_string(),
                     db: 0,
                     sub: 0,
                     psub: 0,
+                    ssub: 0,
+                    multi: -1,
+                    watch: 0,
+                    resp: 2,
                     flags,
                     cmd: "".to_string(),
                     connect_time: std::time::Instant::now(),
@@ -318,6 +431,14 @@ async fn run_server(
                 server_ctx_cloned.clients_ctx.clients.insert(connection_id, ci);
             }
             let (read_half, write_half) = socket.into_split();
+            let read_half = CountedIo {
+                inner: read_half,
+                counter: server_ctx_cloned.clients_ctx.net_input_bytes.clone(),
+            };
+            let write_half = CountedIo {
+                inner: write_half,
+                counter: server_ctx_cloned.clients_ctx.net_output_bytes.clone(),
+            };
 
             // Writer task
             tokio::spawn(async move {
@@ -395,17 +516,25 @@ async fn run_server(
                 .insert(connection_id, conn_ctx.watched_keys_dirty.clone());
 
             // Reader Task
+            let proto_max_bulk_len = server_ctx_cloned.clients_ctx.proto_max_bulk_len.clone();
             tokio::spawn(async move {
                 let mut reader = BufReader::new(read_half);
                 loop {
-                    match resp::read_frame(&mut reader).await {
+                    let max_bulk_len = proto_max_bulk_len.load(Ordering::Relaxed);
+                    match resp::read_request(&mut reader, max_bulk_len).await {
                         Ok(Some(frame)) => {
-                            if frame_tx.send(frame).await.is_err() {
+                            if frame_tx.send(Ok(frame)).await.is_err() {
                                 break;
                             }
                         }
                         Ok(None) => break, // EOF
-                        Err(_) => break,   // Error
+                        Err(e) => {
+                            // Protocol errors are answered before the connection closes
+                            if resp::ProtocolError::is(&e) {
+                                let _ = frame_tx.send(Err(e)).await;
+                            }
+                            break;
+                        }
                     }
                 }
             });
@@ -414,7 +543,11 @@ async fn run_server(
                 tokio::select! {
                     frame_opt = frame_rx.recv() => {
                         match frame_opt {
-                            Some(frame) => {
+                            Some(Err(e)) => {
+                                let _ = tx.send(Resp::Error(format!("ERR {}", e))).await;
+                                break;
+                            }
+                            Some(Ok(frame)) => {
                                 let cmd_name = match &frame {
                                     resp::Resp::Array(Some(items)) => {
                                         if !items.is_empty() {
@@ -435,44 +568,19 @@ async fn run_server(
                                     &server_ctx_cloned,
                                 ).await;
 
-                                if tx.send(response).await.is_err() {
-                                    break;
-                                }
-
+                                // The write happened even if the client is
+                                // gone by the time its reply is sent
                                 if let Some(cmd) = cmd_to_log {
                                     if let Some(aof) = &server_ctx_cloned.aof {
                                         aof.append(&cmd).await;
                                     }
-                                    let next_off = server_ctx_cloned.repl.repl_offset.fetch_add(1, Ordering::Relaxed) + 1;
-                                    {
-                                        {
-                                            let mut q = server_ctx_cloned.repl.repl_backlog.lock().await;
-                                            q.push_back((next_off, cmd.clone()));
-                                            let max = server_ctx_cloned.repl.repl_backlog_size.load(Ordering::Relaxed);
-                                            while q.len() > max {
-                                                q.pop_front();
-                                            }
-                                        }
-                                    }
-                                    for entry in server_ctx_cloned.repl.replicas.iter() {
-                                        let _ = entry.value().try_send(cmd.clone());
-                                    }
+                                    cmd::replication::propagate(&server_ctx_cloned, cmd).await;
                                 }
-                                if let Some(mut ci) = server_ctx_cloned.clients_ctx.clients.get_mut(&connection_id) {
-                                    let mut flags = String::from("N");
-                                    if conn_ctx.in_multi {
-                                        flags.push('M');
-                                    }
-                                    if !conn_ctx.subscriptions.is_empty() || !conn_ctx.psubscriptions.is_empty() {
-                                        flags.push('P');
-                                    }
-                                    ci.db = conn_ctx.db_index;
-                                    ci.sub = conn_ctx.subscriptions.len();
-                                    ci.psub = conn_ctx.psubscriptions.len();
-                                    ci.flags = flags;
-                                    ci.cmd = cmd_name;
-                                    ci.last_activity = std::time::Instant::now();
+
+                                if tx.send(response).await.is_err() {
+                                    break;
                                 }
+                                cmd::client::update_client_info(&conn_ctx, &server_ctx_cloned, cmd_name);
                             }
                             None => break, // Reader closed
                         }
@@ -483,35 +591,7 @@ async fn run_server(
                 }
             }
 
-            // Cleanup subscriptions on disconnect
-            for channel in conn_ctx.subscriptions.iter() {
-                if let Some(subscribers) = server_ctx_cloned.pubsub.channels.get(channel) {
-                    subscribers.remove(&conn_ctx.id);
-                }
-            }
-            for pattern in conn_ctx.psubscriptions.iter() {
-                if let Some(subscribers) = server_ctx_cloned.pubsub.patterns.get(pattern) {
-                    subscribers.remove(&conn_ctx.id);
-                }
-            }
-            // Cleanup watched keys
-            for (db_idx, keys) in conn_ctx.watched_keys.iter() {
-                for key in keys {
-                    if let Some(mut clients) = server_ctx_cloned
-                        .clients_ctx.watched_clients
-                        .get_mut(&(*db_idx, key.clone()))
-                    {
-                        clients.remove(&conn_ctx.id);
-                    }
-                }
-            }
-            server_ctx_cloned.clients_ctx.client_watched_dirty.remove(&conn_ctx.id);
-            server_ctx_cloned
-                .clients_ctx.client_count
-                .fetch_sub(1, Ordering::Relaxed);
-            server_ctx_cloned.clients_ctx.clients.remove(&conn_ctx.id);
-            server_ctx_cloned.clients_ctx.monitors.remove(&conn_ctx.id);
-            server_ctx_cloned.repl.replicas.remove(&conn_ctx.id);
+            cmd::release_client(&mut conn_ctx, &server_ctx_cloned);
         });
     }
 }
@@ -6,6 +6,42 @@ use std::time::{SystemTime, UNIX_EPOCH};
 
 pub const CLUSTER_SLOTS: usize = 16384;
 
+/// CRC16-CCITT (XMODEM: polynomial 0x1021, initial value 0), the checksum
+/// Redis Cluster hashes keys with.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &b in data {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            if (crc & 0x8000) != 0 {
+                crc = (crc << 1) ^ 0x1021;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+    crc
+}
+
+/// The part of `key` that decides its slot. Only the first `{` counts: if a
+/// `}` follows it with at least one byte in between, just those bytes are
+/// hashed, so `{user1000}.following` and `{user1000}.followers` share a
+/// slot. Otherwise (no braces, or `{}`) the whole key is hashed.
+fn hash_tag(key: &[u8]) -> &[u8] {
+    if let Some(open) = key.iter().position(|&b| b == b'{')
+        && let Some(len) = key[open + 1..].iter().position(|&b| b == b'}')
+        && len > 0
+    {
+        return &key[open + 1..open + 1 + len];
+    }
+    key
+}
+
+/// The cluster slot, in `0..CLUSTER_SLOTS`, that `key` belongs to.
+pub fn key_hash_slot(key: &[u8]) -> u16 {
+    crc16(hash_tag(key)) % CLUSTER_SLOTS as u16
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct NodeId(pub String);
 
@@ -510,12 +546,6 @@ impl ClusterState {
         }
     }
 
-    pub fn key_slot(key: &str) -> u16 {
-        let tag = Self::hash_tag(key);
-        let crc = Self::crc16(tag.as_bytes());
-        (crc % CLUSTER_SLOTS as u16) as u16
-    }
-
     pub fn record_ok(&mut self, id: &NodeId) {
         self.last_ok_ms.insert(id.clone(), Self::now_ms());
     }
@@ -784,42 +814,6 @@ impl ClusterState {
         (covered, covered == 16384)
     }
 
-    fn hash_tag(key: &str) -> String {
-        let b = key.as_bytes();
-        let mut i = 0usize;
-        while i < b.len() {
-            if b[i] == b'{' {
-                let mut j = i + 1;
-                while j < b.len() {
-                    if b[j] == b'}' {
-                        if j > i + 1 {
-                            return String::from_utf8_lossy(&b[i + 1..j]).to_string();
-                        }
-                        break;
-                    }
-                    j += 1;
-                }
-            }
-            i += 1;
-        }
-        key.to_string()
-    }
-
-    fn crc16(data: &[u8]) -> u16 {
-        let mut crc: u16 = 0;
-        for &b in data {
-            crc ^= (b as u16) << 8;
-            for _ in 0..8 {
-                if (crc & 0x8000) != 0 {
-                    crc = (crc << 1) ^ 0x1021;
-                } else {
-                    crc <<= 1;
-                }
-            }
-        }
-        crc
-    }
-
     fn merge_into_ranges(ranges: &mut Vec<SlotRange>, slots: &[u16]) {
         let mut s = slots.to_vec();
         s.sort_unstable();
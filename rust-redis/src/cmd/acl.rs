@@ -1,3 +1,4 @@
+use crate::acl::User;
 use crate::cmd::{AclLogEntry, ConnectionContext, ServerContext};
 use crate::resp::{Resp, as_bytes};
 use bytes::Bytes;
@@ -108,6 +109,21 @@ pub fn acl(items: &[Resp], conn_ctx: &ConnectionContext, server_ctx: &ServerCont
                     Resp::SimpleString(bytes::Bytes::from_static(b"OK"))
                 }
             }
+            "GETUSER" => {
+                if items.len() != 3 {
+                    return Resp::Error(
+                        "ERR wrong number of arguments for 'acl getuser' command".to_string(),
+                    );
+                }
+                let username = match as_bytes(&items[2]) {
+                    Some(b) => String::from_utf8_lossy(b).to_string(),
+                    None => return Resp::Error("ERR invalid username".to_string()),
+                };
+                match server_ctx.acl.load().get_user(&username) {
+                    Some(user) => format_acl_user(&user),
+                    None => Resp::Null,
+                }
+            }
             "SAVE" => {
                 if let Some(acl_file) = &server_ctx.config.aclfile {
                     let acl = server_ctx.acl.load();
@@ -256,6 +272,25 @@ pub fn acl(items: &[Resp], conn_ctx: &ConnectionContext, server_ctx: &ServerCont
     }
 }
 
+/// The ACL GETUSER reply. Channels are not restricted, so every user
+/// reports `&*`, and selectors are not supported, so that list is empty.
+fn format_acl_user(user: &User) -> Resp {
+    let bulk = |s: String| Resp::BulkString(Some(Bytes::from(s)));
+    let mut flags = vec![bulk(if user.enabled { "on" } else { "off" }.to_string())];
+    if user.passwords.is_empty() {
+        flags.push(bulk("nopass".to_string()));
+    }
+    let passwords = user.password_hashes().into_iter().map(bulk).collect();
+    Resp::Map(vec![
+        (bulk("flags".to_string()), Resp::Array(Some(flags))),
+        (bulk("passwords".to_string()), Resp::Array(Some(passwords))),
+        (bulk("commands".to_string()), bulk(user.command_rules())),
+        (bulk("keys".to_string()), bulk(user.key_rules())),
+        (bulk("channels".to_string()), bulk("&*".to_string())),
+        (bulk("selectors".to_string()), Resp::Array(Some(Vec::new()))),
+    ])
+}
+
 fn format_acl_log_entry(entry: &AclLogEntry) -> Resp {
     let mut map = Vec::new();
     map.push(Resp::BulkString(Some(Bytes::from("count"))));
@@ -0,0 +1,383 @@
+//! The `cjson` library scripts get, following lua-cjson as bundled with
+//! Redis: arrays are tables with keys 1..n, `cjson.null` stands for JSON null,
+//! and numbers print with 14 significant digits.
+
+use mlua::prelude::*;
+
+// lua-cjson's default limits
+const MAX_DEPTH: usize = 1000;
+const SPARSE_RATIO: usize = 2;
+const SPARSE_SAFE: usize = 10;
+
+/// Sets the `cjson` global with `encode`, `decode` and `null`.
+pub fn register(lua: &Lua) -> LuaResult<()> {
+    let cjson = lua.create_table()?;
+    cjson.set(
+        "encode",
+        lua.create_function(|_, value: LuaValue| {
+            let mut out = String::new();
+            encode_value(&value, 0, &mut out)?;
+            Ok(out)
+        })?,
+    )?;
+    cjson.set(
+        "decode",
+        lua.create_function(|lua, text: LuaString| {
+            let mut parser = Parser {
+                lua,
+                text: text.as_bytes(),
+                pos: 0,
+            };
+            let value = parser.value(0)?;
+            parser.skip_whitespace();
+            if parser.pos < parser.text.len() {
+                return Err(parser.error("the end"));
+            }
+            Ok(value)
+        })?,
+    )?;
+    cjson.set("null", null())?;
+    lua.globals().set("cjson", cjson)
+}
+
+fn null() -> LuaValue<'static> {
+    LuaValue::LightUserData(LuaLightUserData(std::ptr::null_mut()))
+}
+
+fn unsupported(kind: &str) -> LuaError {
+    LuaError::RuntimeError(format!("Cannot serialise {}: type not supported", kind))
+}
+
+fn encode_number(n: f64, out: &mut String) -> LuaResult<()> {
+    if !n.is_finite() {
+        return Err(LuaError::RuntimeError(
+            "Cannot serialise number: must not be NaN or Infinity".to_string(),
+        ));
+    }
+    if n == n.trunc() && n.abs() < 1e14 {
+        out.push_str(&(n as i64).to_string());
+    } else {
+        out.push_str(&format_g14(n));
+    }
+    Ok(())
+}
+
+/// C's `%.14g`.
+fn format_g14(n: f64) -> String {
+    let sci = format!("{:.13e}", n);
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    if (-4..14).contains(&exp) {
+        let decimals = (13 - exp).max(0) as usize;
+        let fixed = format!("{:.*}", decimals, n);
+        if fixed.contains('.') {
+            fixed
+                .trim_end_matches('0')
+                .trim_end_matches('.')
+                .to_string()
+        } else {
+            fixed
+        }
+    } else {
+        let mantissa = if mantissa.contains('.') {
+            mantissa.trim_end_matches('0').trim_end_matches('.')
+        } else {
+            mantissa
+        };
+        let sign = if exp < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", mantissa, sign, exp.abs())
+    }
+}
+
+fn encode_string(s: &[u8], out: &mut String) {
+    out.push('"');
+    for c in String::from_utf8_lossy(s).chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '/' => out.push_str("\\/"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 || c == '\u{7f}' => {
+                out.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// The length of `table` if it is an array: only positive integer keys,
+/// and not so sparse that the nulls filling the gaps would dominate.
+fn array_len(table: &LuaTable) -> LuaResult<Option<usize>> {
+    let mut max = 0;
+    let mut count = 0;
+    for pair in table.clone().pairs::<LuaValue, LuaValue>() {
+        let (key, _) = pair?;
+        let index = match key {
+            LuaValue::Integer(i) if i >= 1 => i as usize,
+            LuaValue::Number(n) if n >= 1.0 && n == n.trunc() => n as usize,
+            _ => return Ok(None),
+        };
+        max = max.max(index);
+        count += 1;
+    }
+    if max > SPARSE_SAFE && max > count * SPARSE_RATIO {
+        return Err(LuaError::RuntimeError(
+            "Cannot serialise table: excessively sparse array".to_string(),
+        ));
+    }
+    Ok(Some(max))
+}
+
+fn encode_value(value: &LuaValue, depth: usize, out: &mut String) -> LuaResult<()> {
+    match value {
+        LuaValue::Nil => out.push_str("null"),
+        LuaValue::LightUserData(ud) if ud.0.is_null() => out.push_str("null"),
+        LuaValue::Boolean(b) => out.push_str(if *b { "true" } else { "false" }),
+        LuaValue::Integer(i) => encode_number(*i as f64, out)?,
+        LuaValue::Number(n) => encode_number(*n, out)?,
+        LuaValue::String(s) => encode_string(s.as_bytes(), out),
+        LuaValue::Table(table) => {
+            if depth >= MAX_DEPTH {
+                return Err(LuaError::RuntimeError(format!(
+                    "Cannot serialise, excessive nesting ({})",
+                    depth + 1
+                )));
+            }
+            match array_len(table)? {
+                // An empty table encodes as an object, as lua-cjson does
+                Some(len) if len > 0 => {
+                    out.push('[');
+                    for i in 1..=len {
+                        if i > 1 {
+                            out.push(',');
+                        }
+                        encode_value(&table.raw_get::<_, LuaValue>(i)?, depth + 1, out)?;
+                    }
+                    out.push(']');
+                }
+                _ => {
+                    out.push('{');
+                    let mut first = true;
+                    for pair in table.clone().pairs::<LuaValue, LuaValue>() {
+                        let (key, value) = pair?;
+                        if !first {
+                            out.push(',');
+                        }
+                        first = false;
+                        match &key {
+                            LuaValue::String(s) => encode_string(s.as_bytes(), out),
+                            LuaValue::Integer(_) | LuaValue::Number(_) => {
+                                let mut number = String::new();
+                                encode_value(&key, depth + 1, &mut number)?;
+                                encode_string(number.as_bytes(), out);
+                            }
+                            _ => {
+                                return Err(LuaError::RuntimeError(
+                                    "Cannot serialise table: table key must be a number or string"
+                                        .to_string(),
+                                ));
+                            }
+                        }
+                        out.push(':');
+                        encode_value(&value, depth + 1, out)?;
+                    }
+                    out.push('}');
+                }
+            }
+        }
+        LuaValue::Function(_) => return Err(unsupported("function")),
+        LuaValue::Thread(_) => return Err(unsupported("thread")),
+        _ => return Err(unsupported("userdata")),
+    }
+    Ok(())
+}
+
+struct Parser<'lua, 'a> {
+    lua: &'lua Lua,
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl<'lua> Parser<'lua, '_> {
+    fn error(&self, expected: &str) -> LuaError {
+        LuaError::RuntimeError(format!(
+            "Expected {} but found invalid token at character {}",
+            expected,
+            self.pos + 1
+        ))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.text.len() && self.text[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, literal: &[u8]) -> bool {
+        if self.text[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> LuaResult<LuaValue<'lua>> {
+        self.skip_whitespace();
+        if depth >= MAX_DEPTH {
+            return Err(LuaError::RuntimeError(format!(
+                "Found too many nested data structures ({}) at character {}",
+                depth + 1,
+                self.pos + 1
+            )));
+        }
+        match self.text.get(self.pos) {
+            Some(b'{') => self.object(depth),
+            Some(b'[') => self.array(depth),
+            Some(b'"') => Ok(LuaValue::String(self.lua.create_string(self.string()?)?)),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ if self.eat(b"true") => Ok(LuaValue::Boolean(true)),
+            _ if self.eat(b"false") => Ok(LuaValue::Boolean(false)),
+            _ if self.eat(b"null") => Ok(null()),
+            _ => Err(self.error("value")),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> LuaResult<LuaValue<'lua>> {
+        let table = self.lua.create_table()?;
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.eat(b"}") {
+            return Ok(LuaValue::Table(table));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.text.get(self.pos) != Some(&b'"') {
+                return Err(self.error("object key string"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            if !self.eat(b":") {
+                return Err(self.error("colon"));
+            }
+            let value = self.value(depth + 1)?;
+            table.raw_set(self.lua.create_string(key)?, value)?;
+            self.skip_whitespace();
+            if self.eat(b"}") {
+                return Ok(LuaValue::Table(table));
+            }
+            if !self.eat(b",") {
+                return Err(self.error("comma or object end"));
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> LuaResult<LuaValue<'lua>> {
+        let table = self.lua.create_table()?;
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.eat(b"]") {
+            return Ok(LuaValue::Table(table));
+        }
+        let mut index = 1;
+        loop {
+            let value = self.value(depth + 1)?;
+            table.raw_set(index, value)?;
+            index += 1;
+            self.skip_whitespace();
+            if self.eat(b"]") {
+                return Ok(LuaValue::Table(table));
+            }
+            if !self.eat(b",") {
+                return Err(self.error("comma or array end"));
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> LuaResult<u32> {
+        let digits = self
+            .text
+            .get(self.pos..self.pos + 4)
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|d| u32::from_str_radix(d, 16).ok())
+            .ok_or_else(|| self.error("unicode escape"))?;
+        self.pos += 4;
+        Ok(digits)
+    }
+
+    fn string(&mut self) -> LuaResult<Vec<u8>> {
+        self.pos += 1;
+        let mut out = Vec::new();
+        loop {
+            let Some(&c) = self.text.get(self.pos) else {
+                return Err(self.error("string end"));
+            };
+            self.pos += 1;
+            match c {
+                b'"' => return Ok(out),
+                b'\\' => {
+                    let Some(&escape) = self.text.get(self.pos) else {
+                        return Err(self.error("string end"));
+                    };
+                    self.pos += 1;
+                    match escape {
+                        b'"' | b'\\' | b'/' => out.push(escape),
+                        b'b' => out.push(0x08),
+                        b'f' => out.push(0x0c),
+                        b'n' => out.push(b'\n'),
+                        b'r' => out.push(b'\r'),
+                        b't' => out.push(b'\t'),
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            // A high surrogate must be followed by a low one
+                            if (0xd800..0xdc00).contains(&code) {
+                                if !self.eat(b"\\u") {
+                                    return Err(self.error("low surrogate"));
+                                }
+                                let low = self.hex4()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(self.error("low surrogate"));
+                                }
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            let c =
+                                char::from_u32(code).ok_or_else(|| self.error("unicode escape"))?;
+                            out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                        }
+                        _ => return Err(self.error("escape sequence")),
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> LuaResult<LuaValue<'lua>> {
+        let start = self.pos;
+        while self.pos < self.text.len()
+            && matches!(
+                self.text[self.pos],
+                b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'
+            )
+        {
+            self.pos += 1;
+        }
+        let literal = std::str::from_utf8(&self.text[start..self.pos]).unwrap();
+        // Integral literals stay integers so they come back as integer replies
+        if let Ok(i) = literal.parse::<i64>() {
+            return Ok(LuaValue::Integer(i));
+        }
+        match literal.parse::<f64>() {
+            Ok(n) => Ok(LuaValue::Number(n)),
+            Err(_) => {
+                self.pos = start;
+                Err(self.error("value"))
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::cmd::{ConnectionContext, ServerContext};
+use crate::cmd::{ClientInfo, ConnectionContext, ServerContext};
 use crate::resp::Resp;
 use bytes::Bytes;
 
@@ -6,6 +6,85 @@ fn to_bytes<S: AsRef<str>>(s: S) -> Bytes {
     Bytes::from(s.as_ref().to_string())
 }
 
+/// Refreshes the CLIENT LIST entry of a connection from its state after
+/// running `cmd`.
+pub fn update_client_info(conn_ctx: &ConnectionContext, server_ctx: &ServerContext, cmd: String) {
+    let Some(mut ci) = server_ctx.clients_ctx.clients.get_mut(&conn_ctx.id) else {
+        return;
+    };
+    let mut flags = String::from("N");
+    if conn_ctx.in_multi {
+        flags.push('M');
+    }
+    if !conn_ctx.subscriptions.is_empty()
+        || !conn_ctx.psubscriptions.is_empty()
+        || !conn_ctx.ssubscriptions.is_empty()
+    {
+        flags.push('P');
+    }
+    if conn_ctx.client_tracking {
+        flags.push('t');
+    }
+    ci.db = conn_ctx.db_index;
+    ci.sub = conn_ctx.subscriptions.len();
+    ci.psub = conn_ctx.psubscriptions.len();
+    ci.ssub = conn_ctx.ssubscriptions.len();
+    ci.multi = if conn_ctx.in_multi {
+        conn_ctx.multi_queue.len() as i64
+    } else {
+        -1
+    };
+    ci.watch = conn_ctx.watched_keys.values().map(|keys| keys.len()).sum();
+    ci.resp = conn_ctx.protocol;
+    ci.flags = flags;
+    ci.cmd = cmd;
+    ci.last_activity = std::time::Instant::now();
+}
+
+/// One CLIENT LIST / CLIENT INFO line, with the fields in Redis's order.
+fn client_info_line(c: &ClientInfo) -> String {
+    let age = c.connect_time.elapsed().as_secs();
+    let idle = c.last_activity.elapsed().as_secs();
+    let mut fields = Vec::new();
+    fields.push(format!("id={}", c.id));
+    fields.push(format!("addr={}", c.addr));
+    fields.push(format!("laddr={}", c.laddr));
+    fields.push(format!("name={}", c.name));
+    fields.push(format!("age={}", age));
+    fields.push(format!("idle={}", idle));
+    fields.push(format!("flags={}", c.flags));
+    fields.push(format!("db={}", c.db));
+    fields.push(format!("sub={}", c.sub));
+    fields.push(format!("psub={}", c.psub));
+    fields.push(format!("ssub={}", c.ssub));
+    fields.push(format!("multi={}", c.multi));
+    fields.push(format!("watch={}", c.watch));
+    fields.push(format!("cmd={}", c.cmd));
+    fields.push(format!("resp={}", c.resp));
+    fields.join(" ")
+}
+
+/// Names the connection, as CLIENT SETNAME and HELLO SETNAME do. Names are
+/// limited to printable ASCII without spaces so CLIENT LIST stays parseable;
+/// an empty name clears it.
+pub fn set_client_name(
+    name: &[u8],
+    conn_ctx: &mut ConnectionContext,
+    server_ctx: &ServerContext,
+) -> Result<(), Resp> {
+    if name.iter().any(|&b| !(b'!'..=b'~').contains(&b)) {
+        return Err(Resp::Error(
+            "ERR Client names cannot contain spaces, newlines or special characters.".to_string(),
+        ));
+    }
+    let name = String::from_utf8_lossy(name).to_string();
+    if let Some(mut ci) = server_ctx.clients_ctx.clients.get_mut(&conn_ctx.id) {
+        ci.name = name.clone();
+    }
+    conn_ctx.client_name = name;
+    Ok(())
+}
+
 pub fn client(
     items: &[Resp],
     conn_ctx: &mut ConnectionContext,
@@ -23,99 +102,52 @@ pub fn client(
     };
     match sub.as_str() {
         "list" => {
-            let mut lines = Vec::new();
-            for entry in server_ctx.clients_ctx.clients.iter() {
-                let c = entry.value();
-                let age = c.connect_time.elapsed().as_secs();
-                let idle = c.last_activity.elapsed().as_secs();
-                let mut fields = Vec::new();
-                fields.push(format!("id={}", c.id));
-                fields.push(format!("addr={}", c.addr));
-                fields.push(format!("name={}", c.name));
-                fields.push(format!("age={}", age));
-                fields.push(format!("idle={}", idle));
-                fields.push(format!("flags={}", c.flags));
-                fields.push(format!("db={}", c.db));
-                fields.push(format!("sub={}", c.sub));
-                fields.push(format!("psub={}", c.psub));
-                fields.push(format!("cmd={}", c.cmd));
-                lines.push(fields.join(" "));
+            update_client_info(conn_ctx, server_ctx, "client|list".to_string());
+            let lines: Vec<String> = server_ctx
+                .clients_ctx
+                .clients
+                .iter()
+                .map(|entry| client_info_line(entry.value()) + "\n")
+                .collect();
+            (Resp::BulkString(Some(to_bytes(lines.concat()))), None)
+        }
+        "info" => {
+            update_client_info(conn_ctx, server_ctx, "client|info".to_string());
+            match server_ctx.clients_ctx.clients.get(&conn_ctx.id) {
+                Some(c) => (
+                    Resp::BulkString(Some(to_bytes(client_info_line(&c) + "\n"))),
+                    None,
+                ),
+                None => (Resp::BulkString(Some(Bytes::new())), None),
             }
-            let text = lines.join("\n");
-            (Resp::BulkString(Some(to_bytes(text))), None)
         }
         "setname" => {
-            if items.len() < 3 {
+            if items.len() != 3 {
                 return (
                     Resp::Error("ERR wrong number of arguments for 'client setname'".to_string()),
                     None,
                 );
             }
             let new_name = match &items[2] {
-                Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).to_string(),
+                Resp::BulkString(Some(b)) => b,
                 _ => return (Resp::Error("ERR invalid client name".to_string()), None),
             };
-            if new_name.contains(' ') {
-                return (
-                    Resp::Error("ERR Client names cannot contain spaces".to_string()),
-                    None,
-                );
+            match set_client_name(new_name, conn_ctx, server_ctx) {
+                Ok(()) => (Resp::SimpleString(Bytes::from("OK")), None),
+                Err(e) => (e, None),
             }
-            if let Some(mut ci) = server_ctx.clients_ctx.clients.get_mut(&conn_ctx.id) {
-                ci.name = new_name;
-            }
-            (Resp::SimpleString(Bytes::from("OK")), None)
         }
         "getname" => {
-            let name = server_ctx
-                .clients_ctx.clients
-                .get(&conn_ctx.id)
-                .map(|c| c.name.clone())
-                .unwrap_or_default();
-            if name.is_empty() {
+            if conn_ctx.client_name.is_empty() {
                 (Resp::BulkString(None), None)
             } else {
-                (Resp::BulkString(Some(to_bytes(name))), None)
-            }
-        }
-        "kill" => {
-            if items.len() >= 3 {
-                // CLIENT KILL ID <id> or CLIENT KILL ADDR <addr>
-                if let Resp::BulkString(Some(flag)) = &items[2] {
-                    let flag_s = String::from_utf8_lossy(flag).to_uppercase();
-                    if flag_s == "ID" && items.len() >= 4 {
-                        if let Resp::BulkString(Some(idb)) = &items[3] {
-                            if let Ok(id) = String::from_utf8_lossy(idb).parse::<u64>() {
-                                let killed = kill_client_by_id(server_ctx, id);
-                                return (Resp::Integer(if killed { 1 } else { 0 }), None);
-                            }
-                        }
-                        return (Resp::Integer(0), None);
-                    } else if flag_s == "ADDR" && items.len() >= 4 {
-                        if let Resp::BulkString(Some(addrb)) = &items[3] {
-                            let addr = String::from_utf8_lossy(addrb).to_string();
-                            let killed = kill_client_by_addr(server_ctx, &addr);
-                            return (Resp::Integer(if killed { 1 } else { 0 }), None);
-                        }
-                        return (Resp::Integer(0), None);
-                    }
-                }
-                // Legacy form CLIENT KILL <ip:port>
-                if let Resp::BulkString(Some(addrb)) = &items[2] {
-                    let addr = String::from_utf8_lossy(addrb).to_string();
-                    let killed = kill_client_by_addr(server_ctx, &addr);
-                    if killed {
-                        return (Resp::SimpleString(Bytes::from("OK")), None);
-                    } else {
-                        return (Resp::Error("ERR no such client".to_string()), None);
-                    }
-                }
+                (
+                    Resp::BulkString(Some(to_bytes(&conn_ctx.client_name))),
+                    None,
+                )
             }
-            (
-                Resp::Error("ERR wrong number of arguments for 'client kill'".to_string()),
-                None,
-            )
         }
+        "kill" => (client_kill(items, conn_ctx, server_ctx), None),
         "pause" => (Resp::SimpleString(Bytes::from("OK")), None),
         "unpause" => (Resp::SimpleString(Bytes::from("OK")), None),
         "tracking" => {
@@ -130,6 +162,7 @@ pub fn client(
                     } else if arg == "OFF" {
                         conn_ctx.client_tracking = false;
                         conn_ctx.client_caching = false;
+                        crate::cmd::untrack_all_keys(conn_ctx.id, server_ctx);
                         return (Resp::SimpleString(Bytes::from("OK")), None);
                     }
                 }
@@ -149,6 +182,107 @@ pub fn client(
     }
 }
 
+/// CLIENT KILL. The legacy `CLIENT KILL addr` form kills one client and
+/// replies OK; otherwise the arguments are filter/value pairs, every filter
+/// must match, and the reply is the number of clients killed. SKIPME
+/// (default yes) spares the calling connection.
+fn client_kill(items: &[Resp], conn_ctx: &ConnectionContext, server_ctx: &ServerContext) -> Resp {
+    let args: Vec<String> = items[2..]
+        .iter()
+        .filter_map(|item| match item {
+            Resp::BulkString(Some(b)) | Resp::SimpleString(b) => {
+                Some(String::from_utf8_lossy(b).to_string())
+            }
+            _ => None,
+        })
+        .collect();
+    if args.is_empty() || args.len() != items.len() - 2 {
+        return Resp::Error("ERR wrong number of arguments for 'client kill'".to_string());
+    }
+
+    if args.len() == 1 {
+        return if kill_client_by_addr(server_ctx, &args[0]) {
+            Resp::SimpleString(Bytes::from("OK"))
+        } else {
+            Resp::Error("ERR no such client".to_string())
+        };
+    }
+    if !args.len().is_multiple_of(2) {
+        return Resp::Error("ERR syntax error".to_string());
+    }
+
+    let mut id = None;
+    let mut addr = None;
+    let mut laddr = None;
+    let mut client_type = None;
+    let mut max_age = None;
+    let mut skip_me = true;
+    for pair in args.chunks(2) {
+        let value = &pair[1];
+        match pair[0].to_uppercase().as_str() {
+            "ID" => match value.parse::<u64>() {
+                Ok(v) if v > 0 => id = Some(v),
+                _ => return Resp::Error("ERR client-id should be greater than 0".to_string()),
+            },
+            "ADDR" => addr = Some(value.clone()),
+            "LADDR" => laddr = Some(value.clone()),
+            "TYPE" => match value.to_lowercase().as_str() {
+                t @ ("normal" | "master" | "pubsub") => client_type = Some(t.to_string()),
+                "replica" | "slave" => client_type = Some("replica".to_string()),
+                _ => return Resp::Error(format!("ERR Unknown client type '{}'", value)),
+            },
+            "MAXAGE" => match value.parse::<u64>() {
+                Ok(v) => max_age = Some(v),
+                Err(_) => {
+                    return Resp::Error("ERR value is not an integer or out of range".to_string());
+                }
+            },
+            "SKIPME" => match value.to_lowercase().as_str() {
+                "yes" => skip_me = true,
+                "no" => skip_me = false,
+                _ => return Resp::Error("ERR syntax error".to_string()),
+            },
+            _ => return Resp::Error("ERR syntax error".to_string()),
+        }
+    }
+
+    let victims: Vec<u64> = server_ctx
+        .clients_ctx
+        .clients
+        .iter()
+        .filter(|entry| {
+            let c = entry.value();
+            !(skip_me && c.id == conn_ctx.id)
+                && id.is_none_or(|id| c.id == id)
+                && addr.as_ref().is_none_or(|a| &c.addr == a)
+                && laddr.as_ref().is_none_or(|a| &c.laddr == a)
+                && client_type
+                    .as_deref()
+                    .is_none_or(|t| client_type_of(c, server_ctx) == t)
+                && max_age.is_none_or(|age| c.connect_time.elapsed().as_secs() >= age)
+        })
+        .map(|entry| entry.value().id)
+        .collect();
+
+    let killed = victims
+        .into_iter()
+        .filter(|id| kill_client_by_id(server_ctx, *id))
+        .count();
+    Resp::Integer(killed as i64)
+}
+
+/// The CLIENT KILL TYPE a connection belongs to. Our link to a master is
+/// not a client connection, so nothing is ever of type `master`.
+fn client_type_of(c: &ClientInfo, server_ctx: &ServerContext) -> &'static str {
+    if server_ctx.repl.replicas.contains_key(&c.id) {
+        "replica"
+    } else if c.sub + c.psub + c.ssub > 0 {
+        "pubsub"
+    } else {
+        "normal"
+    }
+}
+
 fn kill_client_by_id(server_ctx: &ServerContext, id: u64) -> bool {
     if let Some((_k, ci)) = server_ctx.clients_ctx.clients.remove(&id) {
         if let Some(tx) = ci.shutdown_tx {
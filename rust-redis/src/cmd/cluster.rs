@@ -1,4 +1,4 @@
-use crate::cluster::{NodeId, NodeRole, SlotState};
+use crate::cluster::{NodeId, NodeRole, SlotState, key_hash_slot};
 use crate::cmd::{ConnectionContext, ServerContext};
 use crate::resp::{Resp, as_bytes};
 use bytes::Bytes;
@@ -46,11 +46,10 @@ pub fn cluster(
                 );
             }
             let key = match as_bytes(&items[2]) {
-                Some(b) => String::from_utf8_lossy(&b).to_string(),
+                Some(b) => b,
                 None => return Resp::Error("ERR invalid key".to_string()),
             };
-            let slot = crate::cluster::ClusterState::key_slot(&key) as i64;
-            Resp::Integer(slot)
+            Resp::Integer(key_hash_slot(key) as i64)
         }
         "COUNTKEYSINSLOT" => {
             if items.len() != 3 {
@@ -72,9 +71,7 @@ pub fn cluster(
             let mut cnt = 0i64;
             for e in db.iter() {
                 let k = e.key();
-                let key_str = String::from_utf8_lossy(&k[..]).to_string();
-                let s = crate::cluster::ClusterState::key_slot(&key_str);
-                if s == slot {
+                if key_hash_slot(k) == slot {
                     cnt += 1;
                 }
             }
@@ -110,9 +107,7 @@ pub fn cluster(
                     break;
                 }
                 let k = e.key();
-                let key_str = String::from_utf8_lossy(&k[..]).to_string();
-                let s = crate::cluster::ClusterState::key_slot(&key_str);
-                if s == slot {
+                if key_hash_slot(k) == slot {
                     res.push(Resp::BulkString(Some(Bytes::from(k.clone()))));
                 }
             }
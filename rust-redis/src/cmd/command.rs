@@ -316,6 +316,14 @@ const COMMAND_TABLE: &[CommandInfo] = &[
         last_key: 1,
         step: 1,
     },
+    CommandInfo {
+        name: "lcs",
+        arity: -3,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 2,
+        step: 1,
+    },
     CommandInfo {
         name: "stralgo",
         arity: -2,
@@ -396,6 +404,22 @@ const COMMAND_TABLE: &[CommandInfo] = &[
         last_key: 2,
         step: 1,
     },
+    CommandInfo {
+        name: "lmpop",
+        arity: -4,
+        flags: &["write", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "blmpop",
+        arity: -5,
+        flags: &["write", "noscript", "blocking", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
     CommandInfo {
         name: "lmove",
         arity: 5,
@@ -668,6 +692,14 @@ const COMMAND_TABLE: &[CommandInfo] = &[
         last_key: -1,
         step: 1,
     },
+    CommandInfo {
+        name: "sintercard",
+        arity: -3,
+        flags: &["readonly", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
     CommandInfo {
         name: "sinterstore",
         arity: -3,
@@ -750,7 +782,7 @@ const COMMAND_TABLE: &[CommandInfo] = &[
     },
     CommandInfo {
         name: "zrank",
-        arity: 3,
+        arity: -3,
         flags: &["readonly", "fast"],
         first_key: 1,
         last_key: 1,
@@ -758,7 +790,7 @@ const COMMAND_TABLE: &[CommandInfo] = &[
     },
     CommandInfo {
         name: "zrevrank",
-        arity: 3,
+        arity: -3,
         flags: &["readonly", "fast"],
         first_key: 1,
         last_key: 1,
@@ -796,6 +828,46 @@ const COMMAND_TABLE: &[CommandInfo] = &[
         last_key: 1,
         step: 1,
     },
+    CommandInfo {
+        name: "zrevrangebyscore",
+        arity: -4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "zrevrangebylex",
+        arity: -4,
+        flags: &["readonly"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "zremrangebyrank",
+        arity: 4,
+        flags: &["write"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "zremrangebyscore",
+        arity: 4,
+        flags: &["write"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "zremrangebylex",
+        arity: 4,
+        flags: &["write"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
     CommandInfo {
         name: "zcount",
         arity: 4,
@@ -884,6 +956,14 @@ const COMMAND_TABLE: &[CommandInfo] = &[
         last_key: 0,
         step: 0,
     },
+    CommandInfo {
+        name: "zintercard",
+        arity: -3,
+        flags: &["readonly", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
     CommandInfo {
         name: "zinterstore",
         arity: -4,
@@ -908,14 +988,6 @@ const COMMAND_TABLE: &[CommandInfo] = &[
         last_key: 0,
         step: 0,
     },
-    CommandInfo {
-        name: "sdiffstore",
-        arity: -3,
-        flags: &["write", "denyoom"],
-        first_key: 1,
-        last_key: -1,
-        step: 1,
-    },
     CommandInfo {
         name: "pfadd",
         arity: -3,
@@ -991,7 +1063,7 @@ const COMMAND_TABLE: &[CommandInfo] = &[
     CommandInfo {
         name: "geosearch",
         arity: -3,
-        flags: &["readonly", "movablekeys"],
+        flags: &["readonly"],
         first_key: 1,
         last_key: 1,
         step: 1,
@@ -999,14 +1071,14 @@ const COMMAND_TABLE: &[CommandInfo] = &[
     CommandInfo {
         name: "geosearchstore",
         arity: -4,
-        flags: &["write", "denyoom", "movablekeys"],
+        flags: &["write", "denyoom"],
         first_key: 1,
         last_key: 2,
         step: 1,
     },
     CommandInfo {
         name: "expire",
-        arity: 3,
+        arity: -3,
         flags: &["write", "fast"],
         first_key: 1,
         last_key: 1,
@@ -1014,7 +1086,7 @@ const COMMAND_TABLE: &[CommandInfo] = &[
     },
     CommandInfo {
         name: "pexpire",
-        arity: 3,
+        arity: -3,
         flags: &["write", "fast"],
         first_key: 1,
         last_key: 1,
@@ -1022,7 +1094,7 @@ const COMMAND_TABLE: &[CommandInfo] = &[
     },
     CommandInfo {
         name: "expireat",
-        arity: 3,
+        arity: -3,
         flags: &["write", "fast"],
         first_key: 1,
         last_key: 1,
@@ -1030,7 +1102,7 @@ const COMMAND_TABLE: &[CommandInfo] = &[
     },
     CommandInfo {
         name: "pexpireat",
-        arity: 3,
+        arity: -3,
         flags: &["write", "fast"],
         first_key: 1,
         last_key: 1,
@@ -1076,6 +1148,14 @@ const COMMAND_TABLE: &[CommandInfo] = &[
         last_key: 0,
         step: 0,
     },
+    CommandInfo {
+        name: "randomkey",
+        arity: 1,
+        flags: &["readonly", "random"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
     CommandInfo {
         name: "keys",
         arity: 2,
@@ -1100,6 +1180,14 @@ const COMMAND_TABLE: &[CommandInfo] = &[
         last_key: 0,
         step: 0,
     },
+    CommandInfo {
+        name: "debug",
+        arity: -2,
+        flags: &["admin", "noscript", "loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
     CommandInfo {
         name: "bgsave",
         arity: -1,
@@ -1172,6 +1260,30 @@ const COMMAND_TABLE: &[CommandInfo] = &[
         last_key: 0,
         step: 0,
     },
+    CommandInfo {
+        name: "function",
+        arity: -2,
+        flags: &["noscript"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "fcall",
+        arity: -3,
+        flags: &["noscript", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "fcall_ro",
+        arity: -3,
+        flags: &["noscript", "readonly", "movablekeys"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
     CommandInfo {
         name: "xadd",
         arity: -5,
@@ -1212,6 +1324,22 @@ const COMMAND_TABLE: &[CommandInfo] = &[
         last_key: 1,
         step: 1,
     },
+    CommandInfo {
+        name: "xdelex",
+        arity: -5,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "xackdel",
+        arity: -6,
+        flags: &["write", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
     CommandInfo {
         name: "xtrim",
         arity: -4,
@@ -1220,10 +1348,18 @@ const COMMAND_TABLE: &[CommandInfo] = &[
         last_key: 1,
         step: 1,
     },
+    CommandInfo {
+        name: "xsetid",
+        arity: -3,
+        flags: &["write", "denyoom", "fast"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
     CommandInfo {
         name: "xread",
         arity: -4,
-        flags: &["readonly", "blocking"],
+        flags: &["readonly", "blocking", "movablekeys"],
         first_key: 1,
         last_key: 1,
         step: 1,
@@ -1239,7 +1375,7 @@ const COMMAND_TABLE: &[CommandInfo] = &[
     CommandInfo {
         name: "xreadgroup",
         arity: -7,
-        flags: &["write", "blocking"],
+        flags: &["write", "blocking", "movablekeys"],
         first_key: 1,
         last_key: 1,
         step: 1,
@@ -1300,6 +1436,30 @@ const COMMAND_TABLE: &[CommandInfo] = &[
         last_key: 0,
         step: 0,
     },
+    CommandInfo {
+        name: "ssubscribe",
+        arity: -2,
+        flags: &["pubsub", "noscript", "loading", "stale"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "sunsubscribe",
+        arity: -1,
+        flags: &["pubsub", "noscript", "loading", "stale"],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+    },
+    CommandInfo {
+        name: "spublish",
+        arity: 3,
+        flags: &["pubsub", "fast", "loading", "stale"],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+    },
     CommandInfo {
         name: "pubsub",
         arity: -2,
@@ -1463,7 +1623,7 @@ const COMMAND_TABLE: &[CommandInfo] = &[
     CommandInfo {
         name: "sort",
         arity: -2,
-        flags: &["write", "denyoom"],
+        flags: &["write", "denyoom", "movablekeys"],
         first_key: 1,
         last_key: 1,
         step: 1,
@@ -1532,6 +1692,14 @@ const COMMAND_TABLE: &[CommandInfo] = &[
         last_key: 0,
         step: 0,
     },
+    CommandInfo {
+        name: "slaveof",
+        arity: 3,
+        flags: &["admin", "noscript", "loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
     CommandInfo {
         name: "time",
         arity: 1,
@@ -1548,6 +1716,54 @@ const COMMAND_TABLE: &[CommandInfo] = &[
         last_key: 0,
         step: 0,
     },
+    CommandInfo {
+        name: "psync",
+        arity: -3,
+        flags: &["admin", "noscript"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "replconf",
+        arity: -1,
+        flags: &["admin", "noscript", "loading", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "wait",
+        arity: 3,
+        flags: &["noscript"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "waitaof",
+        arity: 4,
+        flags: &["noscript"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "cluster",
+        arity: -2,
+        flags: &["admin", "random", "stale"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
+    CommandInfo {
+        name: "asking",
+        arity: 1,
+        flags: &["fast"],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+    },
 ];
 
 pub fn command(items: &[Resp]) -> Resp {
@@ -38,8 +38,10 @@ async fn config_get(items: &[Resp], ctx: &ServerContext) -> Resp {
 
     // Helper to add pair
     let mut add_pair = |k: &str, v: &str| {
-        response.push(Resp::BulkString(Some(Bytes::from(k.to_string()))));
-        response.push(Resp::BulkString(Some(Bytes::from(v.to_string()))));
+        response.push((
+            Resp::BulkString(Some(Bytes::from(k.to_string()))),
+            Resp::BulkString(Some(Bytes::from(v.to_string()))),
+        ));
     };
 
     let cfg = &ctx.config;
@@ -52,6 +54,7 @@ async fn config_get(items: &[Resp], ctx: &ServerContext) -> Resp {
 
     let slowlog_threshold = ctx.slowlog.threshold_us.load(Ordering::Relaxed);
     let slowlog_max_len = ctx.slowlog.max_len.load(Ordering::Relaxed);
+    let lua_time_limit = ctx.script_manager.time_limit_ms.load(Ordering::Relaxed);
     let maxmemory = ctx.mem.maxmemory.load(Ordering::Relaxed);
     let maxmemory_policy = *ctx.mem.maxmemory_policy.read().unwrap();
     let maxmemory_samples = ctx.mem.maxmemory_samples.load(Ordering::Relaxed);
@@ -68,6 +71,11 @@ async fn config_get(items: &[Resp], ctx: &ServerContext) -> Resp {
     let repl_diskless_sync = ctx.repl.repl_diskless_sync.load(Ordering::Relaxed);
     let repl_diskless_sync_delay = ctx.repl.repl_diskless_sync_delay.load(Ordering::Relaxed);
     let replica_read_only = ctx.repl.replica_read_only.load(Ordering::Relaxed);
+    let maxclients = ctx.clients_ctx.maxclients.load(Ordering::Relaxed);
+    let timeout = ctx.clients_ctx.timeout.load(Ordering::Relaxed);
+    let tcp_keepalive = ctx.clients_ctx.tcp_keepalive.load(Ordering::Relaxed);
+    let proto_max_bulk_len = ctx.clients_ctx.proto_max_bulk_len.load(Ordering::Relaxed);
+    let encoding_limits = *ctx.mem.encoding_limits.read().unwrap();
     let save_params = ctx.persist.save_params.read().unwrap();
     let save_str = save_params
         .iter()
@@ -90,8 +98,15 @@ async fn config_get(items: &[Resp], ctx: &ServerContext) -> Resp {
         ("bind", cfg.bind.clone()),
         ("port", cfg.port.to_string()),
         ("databases", cfg.databases.to_string()),
+        ("db-shards", cfg.db_shards.to_string()),
+        ("maxclients", maxclients.to_string()),
+        ("timeout", timeout.to_string()),
+        ("tcp-keepalive", tcp_keepalive.to_string()),
+        ("proto-max-bulk-len", proto_max_bulk_len.to_string()),
         ("slowlog-log-slower-than", slowlog_threshold.to_string()),
         ("slowlog-max-len", slowlog_max_len.to_string()),
+        ("lua-time-limit", lua_time_limit.to_string()),
+        ("busy-reply-threshold", lua_time_limit.to_string()),
         ("maxmemory", maxmemory.to_string()),
         ("maxmemory-policy", maxmemory_policy.as_str().to_string()),
         ("maxmemory-samples", maxmemory_samples.to_string()),
@@ -149,6 +164,17 @@ async fn config_get(items: &[Resp], ctx: &ServerContext) -> Resp {
             },
         ),
     ];
+    let configs = configs
+        .into_iter()
+        .chain(
+            crate::conf::EncodingLimits::NAMES
+                .iter()
+                .map(|name| (*name, encoding_limits.get(name).unwrap().to_string())),
+        )
+        .chain([(
+            "list-max-listpack-size",
+            encoding_limits.list_max_listpack_size.to_string(),
+        )]);
 
     if param_lower == "*" {
         for (k, v) in configs {
@@ -162,7 +188,7 @@ async fn config_get(items: &[Resp], ctx: &ServerContext) -> Resp {
         }
     }
 
-    Resp::Array(Some(response))
+    Resp::Map(response)
 }
 
 async fn config_set(items: &[Resp], ctx: &ServerContext) -> Resp {
@@ -207,6 +233,13 @@ async fn config_set(items: &[Resp], ctx: &ServerContext) -> Resp {
                 Err(_) => Resp::Error("ERR value is not an integer or out of range".to_string()),
             }
         }
+        "lua-time-limit" | "busy-reply-threshold" => match value.parse::<u64>() {
+            Ok(v) => {
+                ctx.script_manager.time_limit_ms.store(v, Ordering::Relaxed);
+                Resp::SimpleString(Bytes::from("OK"))
+            }
+            Err(_) => Resp::Error("ERR value is not an integer or out of range".to_string()),
+        },
         "maxmemory" => {
             let s = value.to_lowercase();
             let (num, unit) = if s.ends_with("gb") {
@@ -285,12 +318,12 @@ async fn config_set(items: &[Resp], ctx: &ServerContext) -> Resp {
             *params = new_params;
             Resp::SimpleString(Bytes::from("OK"))
         }
-        "repl-backlog-size" => match value.parse::<usize>() {
-            Ok(v) => {
-                ctx.repl.repl_backlog_size.store(v, Ordering::Relaxed);
+        "repl-backlog-size" => match crate::conf::parse_memory(&value) {
+            Some(v) => {
+                ctx.repl.repl_backlog_size.store(v as usize, Ordering::Relaxed);
                 Resp::SimpleString(Bytes::from("OK"))
             }
-            Err(_) => Resp::Error("ERR value is not an integer or out of range".to_string()),
+            None => Resp::Error("ERR value is not an integer or out of range".to_string()),
         },
         "repl-ping-replica-period" => match value.parse::<u64>() {
             Ok(v) if v > 0 => {
@@ -337,6 +370,59 @@ async fn config_set(items: &[Resp], ctx: &ServerContext) -> Resp {
                 .store(value.eq_ignore_ascii_case("yes"), Ordering::Relaxed);
             Resp::SimpleString(Bytes::from("OK"))
         }
+        "maxclients" => match value.parse::<u64>() {
+            Ok(v) if v > 0 => {
+                ctx.clients_ctx.maxclients.store(v, Ordering::Relaxed);
+                Resp::SimpleString(Bytes::from("OK"))
+            }
+            _ => Resp::Error("ERR value is not an integer or out of range".to_string()),
+        },
+        "timeout" => match value.parse::<u64>() {
+            Ok(v) => {
+                ctx.clients_ctx.timeout.store(v, Ordering::Relaxed);
+                Resp::SimpleString(Bytes::from("OK"))
+            }
+            Err(_) => Resp::Error("ERR value is not an integer or out of range".to_string()),
+        },
+        "tcp-keepalive" => match value.parse::<u64>() {
+            Ok(v) => {
+                ctx.clients_ctx.tcp_keepalive.store(v, Ordering::Relaxed);
+                Resp::SimpleString(Bytes::from("OK"))
+            }
+            Err(_) => Resp::Error("ERR value is not an integer or out of range".to_string()),
+        },
+        "proto-max-bulk-len" => match crate::conf::parse_memory(&value) {
+            Some(v) if v >= crate::conf::MIN_PROTO_MAX_BULK_LEN => {
+                ctx.clients_ctx
+                    .proto_max_bulk_len
+                    .store(v, Ordering::Relaxed);
+                Resp::SimpleString(Bytes::from("OK"))
+            }
+            _ => Resp::Error("ERR value is not an integer or out of range".to_string()),
+        },
+        name if crate::conf::EncodingLimits::NAMES.contains(&name) => {
+            match value.parse::<usize>() {
+                Ok(v) => {
+                    let mut limits = ctx.mem.encoding_limits.write().unwrap();
+                    *limits.get_mut(name).unwrap() = v;
+                    Resp::SimpleString(Bytes::from("OK"))
+                }
+                Err(_) => Resp::Error("ERR value is not an integer or out of range".to_string()),
+            }
+        }
+        "list-max-listpack-size" => match crate::conf::EncodingLimits::parse_list_size(&value) {
+            Some(v) => {
+                let mut limits = ctx.mem.encoding_limits.write().unwrap();
+                limits.list_max_listpack_size = v;
+                Resp::SimpleString(Bytes::from("OK"))
+            }
+            None => Resp::Error("ERR value is not an integer or out of range".to_string()),
+        },
+        // The keyspace array and its maps are sized once at startup
+        "databases" | "db-shards" => Resp::Error(format!(
+            "ERR CONFIG SET failed (possibly related to argument '{}') - can't set '{}' at runtime",
+            param_lower, param_lower
+        )),
         _ => Resp::Error("ERR Unsupported CONFIG parameter".to_string()),
     }
 }
@@ -358,6 +444,8 @@ async fn config_rewrite(_items: &[Resp], ctx: &ServerContext) -> Resp {
         append_cfg("bind", &cfg.bind);
         // databases
         append_cfg("databases", &cfg.databases.to_string());
+        // db-shards
+        append_cfg("db-shards", &cfg.db_shards.to_string());
         // maxmemory
         append_cfg(
             "maxmemory",
@@ -474,9 +562,37 @@ async fn config_rewrite(_items: &[Resp], ctx: &ServerContext) -> Resp {
             "slowlog-max-len",
             &ctx.slowlog.max_len.load(Ordering::Relaxed).to_string(),
         );
+        append_cfg(
+            "lua-time-limit",
+            &ctx.script_manager.time_limit_ms.load(Ordering::Relaxed).to_string(),
+        );
 
         // maxclients
-        append_cfg("maxclients", &cfg.maxclients.to_string());
+        append_cfg(
+            "maxclients",
+            &ctx.clients_ctx.maxclients.load(Ordering::Relaxed).to_string(),
+        );
+        append_cfg(
+            "timeout",
+            &ctx.clients_ctx.timeout.load(Ordering::Relaxed).to_string(),
+        );
+        append_cfg(
+            "tcp-keepalive",
+            &ctx.clients_ctx.tcp_keepalive.load(Ordering::Relaxed).to_string(),
+        );
+        append_cfg(
+            "proto-max-bulk-len",
+            &ctx.clients_ctx.proto_max_bulk_len.load(Ordering::Relaxed).to_string(),
+        );
+
+        let encoding_limits = *ctx.mem.encoding_limits.read().unwrap();
+        for name in crate::conf::EncodingLimits::NAMES {
+            append_cfg(name, &encoding_limits.get(name).unwrap().to_string());
+        }
+        append_cfg(
+            "list-max-listpack-size",
+            &encoding_limits.list_max_listpack_size.to_string(),
+        );
 
         // Write to file
         match std::fs::write(config_file, content) {
@@ -0,0 +1,168 @@
+use crate::cmd::ServerContext;
+use crate::db::Db;
+use crate::rdb;
+use crate::resp::{Resp, as_bytes};
+use bytes::Bytes;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, RwLock};
+use tokio::task::block_in_place;
+
+pub fn debug(items: &[Resp], db: &Db, ctx: &ServerContext) -> Resp {
+    if items.len() < 2 {
+        return Resp::Error("ERR wrong number of arguments for 'DEBUG' command".to_string());
+    }
+
+    let subcommand = match &items[1] {
+        Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).to_uppercase(),
+        Resp::SimpleString(s) => String::from_utf8_lossy(s).to_uppercase(),
+        _ => return Resp::Error("ERR syntax error".to_string()),
+    };
+
+    match subcommand.as_str() {
+        "OBJECT" => debug_object(&items[2..], db, ctx),
+        "RELOAD" => debug_reload(&items[2..], ctx),
+        "SET-ACTIVE-EXPIRE" => debug_set_active_expire(&items[2..], ctx),
+        _ => Resp::Error(format!(
+            "ERR unknown subcommand '{}'. Try OBJECT, RELOAD or SET-ACTIVE-EXPIRE.",
+            subcommand
+        )),
+    }
+}
+
+/// Low-level details of a key's value. `serializedlength` is the size of its
+/// RDB encoding, as DUMP would produce it, so stream consumer groups and
+/// their PELs count towards it.
+fn debug_object(args: &[Resp], db: &Db, ctx: &ServerContext) -> Resp {
+    let key = match args {
+        [key] => match as_bytes(key) {
+            Some(key) => key,
+            None => return Resp::Error("ERR syntax error".to_string()),
+        },
+        _ => {
+            return Resp::Error(
+                "ERR wrong number of arguments for 'DEBUG OBJECT' command".to_string(),
+            );
+        }
+    };
+    let entry = match db.get(key) {
+        Some(entry) if !entry.is_expired() => entry,
+        _ => return Resp::Error("ERR no such key".to_string()),
+    };
+
+    let limits = *ctx.mem.encoding_limits.read().unwrap();
+    let mut serialized = Vec::new();
+    if let Err(e) =
+        rdb::RdbEncoder::new(&mut serialized, false, false).dump_value(&entry.value, &limits)
+    {
+        return Resp::Error(format!("ERR {}", e));
+    }
+    Resp::SimpleString(Bytes::from(format!(
+        "Value at:{:p} refcount:1 encoding:{} serializedlength:{} lru:{} lru_seconds_idle:{}",
+        &entry.value,
+        crate::cmd::key::object_encoding(&entry.value, &limits),
+        serialized.len(),
+        entry.lru & 0xFF_FFFF,
+        crate::clock::now_secs().saturating_sub(entry.lru)
+    )))
+}
+
+/// Turns the background expire cycle off (0) or back on (1), leaving
+/// expired keys to be removed only when accessed.
+fn debug_set_active_expire(args: &[Resp], ctx: &ServerContext) -> Resp {
+    let enabled = match args {
+        [arg] => match as_bytes(arg) {
+            Some(b"0") => false,
+            Some(b"1") => true,
+            _ => return Resp::Error("ERR value is out of range, must be 0 or 1".to_string()),
+        },
+        _ => {
+            return Resp::Error(
+                "ERR wrong number of arguments for 'DEBUG SET-ACTIVE-EXPIRE' command".to_string(),
+            );
+        }
+    };
+    ctx.mem
+        .active_expire_enabled
+        .store(enabled, Ordering::Relaxed);
+    Resp::SimpleString(Bytes::from("OK"))
+}
+
+/// Saves the dataset to the RDB file and loads it back, so it is rebuilt
+/// exactly as it would be after a restart. NOSAVE skips the save and loads
+/// whatever is on disk; NOFLUSH keeps the current keys and loads on top of
+/// them. Every database stays write-locked throughout, so no client observes
+/// a partially loaded keyspace.
+fn debug_reload(options: &[Resp], ctx: &ServerContext) -> Resp {
+    let mut nosave = false;
+    let mut noflush = false;
+    for opt in options {
+        match as_bytes(opt) {
+            Some(o) if o.eq_ignore_ascii_case(b"NOSAVE") => nosave = true,
+            Some(o) if o.eq_ignore_ascii_case(b"NOFLUSH") => noflush = true,
+            _ => {
+                return Resp::Error(
+                    "ERR DEBUG RELOAD only supports the NOFLUSH and NOSAVE options.".to_string(),
+                );
+            }
+        }
+    }
+
+    // The locks are std ones held across disk I/O, so the worker thread
+    // hands its other tasks off rather than stalling them
+    block_in_place(|| reload_locked(nosave, noflush, ctx))
+}
+
+fn reload_locked(nosave: bool, noflush: bool, ctx: &ServerContext) -> Resp {
+    let mut guards: Vec<_> = ctx.databases.iter().map(|db| db.write().unwrap()).collect();
+
+    if !nosave {
+        // The rdb helpers lock the databases they are given, so they work on
+        // views of the guarded maps rather than on ctx.databases
+        let snapshot: Arc<Vec<RwLock<Db>>> = Arc::new(
+            guards
+                .iter()
+                .map(|db| RwLock::new((**db).clone()))
+                .collect(),
+        );
+        if let Err(e) = rdb::rdb_save(&snapshot, ctx.script_manager.library_codes(), &ctx.config) {
+            return Resp::Error(format!("ERR Error trying to save the DB: {}", e));
+        }
+    } else if !Path::new(&ctx.config.dbfilename).exists() {
+        // rdb_load treats a missing file as an empty dataset, which would
+        // silently wipe the keyspace here
+        return Resp::Error(
+            "ERR Error trying to load the RDB dump: No such file or directory".to_string(),
+        );
+    }
+
+    // Load into fresh maps so a failure leaves the dataset untouched
+    let loaded: Arc<Vec<RwLock<Db>>> = Arc::new(
+        guards
+            .iter()
+            .map(|_| RwLock::new(Arc::new(crate::db::sharded(ctx.config.db_shards))))
+            .collect(),
+    );
+    let functions = match rdb::rdb_load(&loaded, &ctx.config) {
+        Ok(functions) => functions,
+        Err(e) => return Resp::Error(format!("ERR Error trying to load the RDB dump: {}", e)),
+    };
+    let policy = if noflush { "REPLACE" } else { "FLUSH" };
+    if let Err(e) = ctx.script_manager.restore_libraries(&functions, policy) {
+        return e;
+    }
+    for (guard, db) in guards.iter_mut().zip(loaded.iter()) {
+        let db = db.read().unwrap();
+        if noflush {
+            for entry in db.iter() {
+                guard.insert(entry.key().clone(), entry.value().clone());
+            }
+        } else {
+            **guard = db.clone();
+        }
+    }
+    if !noflush {
+        ctx.db_epoch.fetch_add(1, Ordering::Release);
+    }
+    Resp::SimpleString(Bytes::from("OK"))
+}
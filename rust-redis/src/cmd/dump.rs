@@ -1,11 +1,14 @@
+use crate::cmd::ServerContext;
+use crate::cmd::expiry::{expiry_from_abs_ms, expiry_from_relative_ms, parse_expire_arg};
 use crate::db::{Db, Entry};
 use crate::rdb::{RdbEncoder, RdbLoader};
 use crate::resp::{Resp, as_bytes};
 use std::io::Cursor;
 
-const RDB_VERSION: u16 = 9;
+// Listpack-encoded hashes and sorted sets (RDB types 16/17) need RDB 10.
+const RDB_VERSION: u16 = 10;
 
-pub fn dump(items: &[Resp], db: &Db) -> Resp {
+pub fn dump(items: &[Resp], db: &Db, server_ctx: &ServerContext) -> Resp {
     if items.len() != 2 {
         return Resp::Error("ERR wrong number of arguments for 'dump' command".to_string());
     }
@@ -20,10 +23,11 @@ pub fn dump(items: &[Resp], db: &Db) -> Resp {
         None => return Resp::BulkString(None),
     };
 
+    let limits = *server_ctx.mem.encoding_limits.read().unwrap();
     let mut buf = Vec::new();
     {
         let mut encoder = RdbEncoder::new(&mut buf, false, true);
-        if let Err(_) = encoder.dump_value(&entry.value) {
+        if encoder.dump_value(&entry.value, &limits).is_err() {
             return Resp::Error("ERR failed to dump value".to_string());
         }
         // Write RDB version (u16)
@@ -47,17 +51,12 @@ pub fn restore(items: &[Resp], db: &Db) -> Resp {
         None => return Resp::Error("ERR invalid key".to_string()),
     };
 
-    let ttl_ms = match as_bytes(&items[2]) {
-        Some(b) => {
-            let s = String::from_utf8_lossy(&b);
-            match s.parse::<u64>() {
-                Ok(v) => v,
-                Err(_) => {
-                    return Resp::Error("ERR value is not an integer or out of range".to_string());
-                }
-            }
+    let ttl_ms = match parse_expire_arg(&items[2]) {
+        Ok(v) if v < 0 => {
+            return Resp::Error("ERR Invalid TTL value, must be >= 0".to_string());
         }
-        None => return Resp::Error("ERR invalid ttl".to_string()),
+        Ok(v) => v,
+        Err(e) => return e,
     };
 
     let serialized = match as_bytes(&items[3]) {
@@ -148,16 +147,21 @@ pub fn restore(items: &[Resp], db: &Db) -> Resp {
         return Resp::Error("ERR DUMP payload version or checksum are wrong".to_string());
     }
 
-    // Calculate expire_at
+    // Calculate expire_at; a ttl of 0 means the key never expires
     let expire_at = if ttl_ms > 0 {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
-        if absttl {
-            Some(ttl_ms)
+        let at = if absttl {
+            expiry_from_abs_ms(ttl_ms, "restore")
         } else {
-            Some(now + ttl_ms)
+            expiry_from_relative_ms(ttl_ms, "restore")
+        };
+        match at {
+            // Already in the past: the restored key would be expired on arrival
+            Ok(at) if at <= crate::clock::now_ms() as i64 => {
+                db.remove(key.as_slice());
+                return Resp::SimpleString(bytes::Bytes::from_static(b"OK"));
+            }
+            Ok(at) => Some(at as u64),
+            Err(e) => return e,
         }
     } else {
         None
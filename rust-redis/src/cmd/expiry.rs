@@ -0,0 +1,44 @@
+//! Absolute expiry times for the commands that set a TTL.
+//!
+//! Every helper returns unix milliseconds computed with checked arithmetic,
+//! so a TTL too large to represent is rejected with the same
+//! `invalid expire time in '<cmd>' command` error instead of wrapping. The
+//! result may lie in the past (EXPIRE with a negative TTL); callers decide
+//! whether that deletes the key or is an error.
+
+use crate::resp::{Resp, as_bytes};
+
+pub fn invalid_expire_time(cmd: &str) -> Resp {
+    Resp::Error(format!("ERR invalid expire time in '{}' command", cmd))
+}
+
+/// Parses a TTL or timestamp argument as a signed integer.
+pub fn parse_expire_arg(arg: &Resp) -> Result<i64, Resp> {
+    as_bytes(arg)
+        .and_then(|b| std::str::from_utf8(b).ok())
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or(Resp::StaticError(
+            "ERR value is not an integer or out of range",
+        ))
+}
+
+pub fn expiry_from_relative_secs(secs: i64, cmd: &str) -> Result<i64, Resp> {
+    let ms = secs
+        .checked_mul(1000)
+        .ok_or_else(|| invalid_expire_time(cmd))?;
+    expiry_from_relative_ms(ms, cmd)
+}
+
+pub fn expiry_from_relative_ms(ms: i64, cmd: &str) -> Result<i64, Resp> {
+    ms.checked_add(crate::clock::now_ms() as i64)
+        .ok_or_else(|| invalid_expire_time(cmd))
+}
+
+pub fn expiry_from_abs_secs(secs: i64, cmd: &str) -> Result<i64, Resp> {
+    secs.checked_mul(1000)
+        .ok_or_else(|| invalid_expire_time(cmd))
+}
+
+pub fn expiry_from_abs_ms(ms: i64, _cmd: &str) -> Result<i64, Resp> {
+    Ok(ms)
+}
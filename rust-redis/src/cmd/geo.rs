@@ -122,29 +122,29 @@ pub fn geodist(items: &[Resp], db: &Db) -> Resp {
         1.0
     };
 
-    if let Some(entry) = db.get(&key) {
-        if let Value::ZSet(zset) = &entry.value {
-            let score1 = zset.members.get(&member1);
-            let score2 = zset.members.get(&member2);
-
-            if let (Some(s1), Some(s2)) = (score1, score2) {
-                let hash1 = crate::geo::GeoHashBits {
-                    bits: *s1 as u64,
-                    step: 26,
-                };
-                let (lat1, lon1) = geohash_decode(hash1);
+    if let Some(entry) = db.get(&key)
+        && let Value::ZSet(zset) = &entry.value
+    {
+        let score1 = zset.members.get(&member1);
+        let score2 = zset.members.get(&member2);
+
+        if let (Some(s1), Some(s2)) = (score1, score2) {
+            let hash1 = crate::geo::GeoHashBits {
+                bits: *s1 as u64,
+                step: 26,
+            };
+            let (lat1, lon1) = geohash_decode(hash1);
 
-                let hash2 = crate::geo::GeoHashBits {
-                    bits: *s2 as u64,
-                    step: 26,
-                };
-                let (lat2, lon2) = geohash_decode(hash2);
+            let hash2 = crate::geo::GeoHashBits {
+                bits: *s2 as u64,
+                step: 26,
+            };
+            let (lat2, lon2) = geohash_decode(hash2);
 
-                let dist = calc_dist(lat1, lon1, lat2, lon2);
-                let converted = dist / unit_scale;
+            let dist = calc_dist(lat1, lon1, lat2, lon2);
+            let converted = dist / unit_scale;
 
-                return Resp::BulkString(Some(Bytes::from(converted.to_string())));
-            }
+            return Resp::BulkString(Some(Bytes::from(converted.to_string())));
         }
     }
 
@@ -359,6 +359,10 @@ fn geosearch_generic(
         }
     }
 
+    // Release the source before writing the destination: both may live in
+    // the same shard, and holding the read guard across the insert deadlocks
+    drop(entry);
+
     if let Some(dest_key) = opts.store {
         let mut dest_zset = SortedSet::new();
         let count = points.len() as i64;
@@ -1,6 +1,7 @@
-use crate::cmd::key::match_pattern;
+use crate::cmd::key::{match_pattern, scan_batch};
+use crate::cmd::{ConnectionContext, reply_encoder};
 use crate::db::{Db, Entry, Value};
-use crate::resp::Resp;
+use crate::resp::{ReplyKind, Resp, format_double};
 use bytes::Bytes;
 use rand::seq::IndexedRandom;
 use rand::seq::IteratorRandom;
@@ -254,7 +255,7 @@ pub fn hincrbyfloat(items: &[Resp], db: &Db) -> Resp {
                 Ok(s) => match s.parse::<f64>() {
                     Ok(old_f) => {
                         if old_f.is_nan() || old_f.is_infinite() {
-                            return Resp::Error("ERR value is not a valid float".to_string());
+                            return Resp::Error("ERR hash value is not a float".to_string());
                         }
                         old_f + increment
                     }
@@ -270,7 +271,7 @@ pub fn hincrbyfloat(items: &[Resp], db: &Db) -> Resp {
             return Resp::Error("ERR increment would produce NaN or Infinity".to_string());
         }
 
-        let val_str = new_val.to_string();
+        let val_str = format_double(new_val);
         let val_bytes = Bytes::from(val_str);
         map.insert(field, val_bytes.clone());
         Resp::BulkString(Some(val_bytes))
@@ -314,7 +315,7 @@ pub fn hget(items: &[Resp], db: &Db) -> Resp {
     }
 }
 
-pub fn hgetall(items: &[Resp], db: &Db) -> Resp {
+pub fn hgetall(items: &[Resp], conn_ctx: &ConnectionContext, db: &Db) -> Resp {
     if items.len() != 2 {
         return Resp::Error("ERR wrong number of arguments for 'HGETALL'".to_string());
     }
@@ -328,23 +329,32 @@ pub fn hgetall(items: &[Resp], db: &Db) -> Resp {
         if entry.is_expired() {
             drop(entry);
             db.remove(&key);
-            return Resp::Array(Some(Vec::new()));
+            return Resp::Map(Vec::new());
         }
         match &entry.value {
             Value::Hash(map) => {
-                let mut res = Vec::new();
+                if let Some(mut out) = reply_encoder(conn_ctx, ReplyKind::Map, map.len()) {
+                    for (k, v) in map {
+                        out.bulk(k);
+                        out.bulk(v);
+                    }
+                    return out.finish();
+                }
+                let mut res = Vec::with_capacity(map.len());
                 for (k, v) in map {
-                    res.push(Resp::BulkString(Some(k.clone())));
-                    res.push(Resp::BulkString(Some(v.clone())));
+                    res.push((
+                        Resp::BulkString(Some(k.clone())),
+                        Resp::BulkString(Some(v.clone())),
+                    ));
                 }
-                Resp::Array(Some(res))
+                Resp::Map(res)
             }
             _ => Resp::Error(
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
             ),
         }
     } else {
-        Resp::Array(Some(Vec::new()))
+        Resp::Map(Vec::new())
     }
 }
 
@@ -555,43 +565,17 @@ pub fn hscan(items: &[Resp], db: &Db) -> Resp {
 
         match &entry.value {
             Value::Hash(map) => {
-                let keys: Vec<&Bytes> = map.keys().collect();
+                let (batch, next_cursor) = scan_batch(map.keys(), cursor, count);
 
                 let mut res = Vec::new();
-                let mut next_cursor = 0;
-
-                if !keys.is_empty() {
-                    let start = if cursor as usize >= keys.len() {
-                        0
-                    } else {
-                        cursor as usize
-                    };
-
-                    let mut current = start;
-                    let mut added = 0;
-
-                    while added < count && current < keys.len() {
-                        let key_bytes = keys[current];
-
-                        let include = if let Some(pattern) = &match_pattern_str {
-                            match_pattern(pattern.as_bytes(), key_bytes)
-                        } else {
-                            true
-                        };
-
-                        if include {
-                            if let Some(val) = map.get(key_bytes) {
-                                res.push(Resp::BulkString(Some(key_bytes.clone())));
-                                res.push(Resp::BulkString(Some(val.clone())));
-                                added += 1;
-                            }
-                        }
-                        current += 1;
-                    }
-
-                    if current < keys.len() {
-                        next_cursor = current as u64;
+                for field in batch {
+                    if let Some(pattern) = &match_pattern_str
+                        && !match_pattern(pattern.as_bytes(), field)
+                    {
+                        continue;
                     }
+                    res.push(Resp::BulkString(Some(field.clone())));
+                    res.push(Resp::BulkString(Some(map[field].clone())));
                 }
 
                 Resp::Array(Some(vec![
@@ -3,7 +3,7 @@ use crate::resp::{Resp, as_bytes};
 use bytes::Bytes;
 
 pub fn hello(items: &[Resp], conn_ctx: &mut ConnectionContext, server_ctx: &ServerContext) -> Resp {
-    let mut version = 2;
+    let mut version = conn_ctx.protocol as i64;
 
     if items.len() > 1 {
         let ver_str = match as_bytes(&items[1]) {
@@ -19,6 +19,9 @@ pub fn hello(items: &[Resp], conn_ctx: &mut ConnectionContext, server_ctx: &Serv
             Err(_) => return Resp::Error("ERR syntax error".to_string()),
         }
 
+        // Options are only applied once they all parse and AUTH succeeds
+        let mut auth = None;
+        let mut setname = None;
         let mut i = 2;
         while i < items.len() {
             let arg = match as_bytes(&items[i]) {
@@ -39,14 +42,7 @@ pub fn hello(items: &[Resp], conn_ctx: &mut ConnectionContext, server_ctx: &Serv
                         Some(b) => String::from_utf8_lossy(b).to_string(),
                         None => return Resp::Error("ERR syntax error".to_string()),
                     };
-
-                    let acl = server_ctx.acl.load();
-                    if let Some(_user) = acl.authenticate(&username, &password) {
-                        conn_ctx.authenticated = true;
-                        conn_ctx.current_username = username;
-                    } else {
-                        return Resp::Error("WRONGPASS invalid username-password pair".to_string());
-                    }
+                    auth = Some((username, password));
                     i += 3;
                 }
                 "SETNAME" => {
@@ -54,44 +50,44 @@ pub fn hello(items: &[Resp], conn_ctx: &mut ConnectionContext, server_ctx: &Serv
                         return Resp::Error("ERR syntax error".to_string());
                     }
                     let name = match as_bytes(&items[i + 1]) {
-                        Some(b) => String::from_utf8_lossy(b).to_string(),
+                        Some(b) => b,
                         None => return Resp::Error("ERR syntax error".to_string()),
                     };
-                    if name.chars().any(char::is_whitespace) {
-                        return Resp::Error("ERR Client names cannot contain spaces, newlines or special characters.".to_string());
-                    }
-
-                    if let Some(mut ci) = server_ctx.clients_ctx.clients.get_mut(&conn_ctx.id) {
-                        ci.name = name;
-                    }
+                    setname = Some(name);
                     i += 2;
                 }
                 _ => return Resp::Error("ERR syntax error".to_string()),
             }
         }
-    }
-
-    let mut info = Vec::new();
-    info.push(Resp::BulkString(Some(Bytes::from("server"))));
-    info.push(Resp::BulkString(Some(Bytes::from("redis"))));
-
-    info.push(Resp::BulkString(Some(Bytes::from("version"))));
-    info.push(Resp::BulkString(Some(Bytes::from("6.2.5"))));
 
-    info.push(Resp::BulkString(Some(Bytes::from("proto"))));
-    info.push(Resp::Integer(version));
-
-    info.push(Resp::BulkString(Some(Bytes::from("id"))));
-    info.push(Resp::Integer(conn_ctx.id as i64));
-
-    info.push(Resp::BulkString(Some(Bytes::from("mode"))));
-    info.push(Resp::BulkString(Some(Bytes::from("standalone"))));
-
-    info.push(Resp::BulkString(Some(Bytes::from("role"))));
-    info.push(Resp::BulkString(Some(Bytes::from("master"))));
-
-    info.push(Resp::BulkString(Some(Bytes::from("modules"))));
-    info.push(Resp::Array(Some(Vec::new())));
+        if let Some((username, password)) = auth {
+            let acl = server_ctx.acl.load();
+            if acl.authenticate(&username, &password).is_none() {
+                return Resp::Error("WRONGPASS invalid username-password pair".to_string());
+            }
+            conn_ctx.authenticated = true;
+            conn_ctx.current_username = username;
+        }
+        if let Some(name) = setname
+            && let Err(e) = crate::cmd::client::set_client_name(name, conn_ctx, server_ctx)
+        {
+            return e;
+        }
+        conn_ctx.protocol = version as u8;
+    }
 
-    Resp::Array(Some(info))
+    let role = match *server_ctx.repl.replication_role.read().unwrap() {
+        crate::cmd::ReplicationRole::Master => "master",
+        crate::cmd::ReplicationRole::Slave => "replica",
+    };
+    let field = |name: &'static str| Resp::BulkString(Some(Bytes::from(name)));
+    Resp::Map(vec![
+        (field("server"), field("redis")),
+        (field("version"), field("6.2.5")),
+        (field("proto"), Resp::Integer(version)),
+        (field("id"), Resp::Integer(conn_ctx.id as i64)),
+        (field("mode"), field("standalone")),
+        (field("role"), field(role)),
+        (field("modules"), Resp::Array(Some(Vec::new()))),
+    ])
 }
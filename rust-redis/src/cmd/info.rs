@@ -1,4 +1,5 @@
 use crate::cmd::ServerContext;
+use crate::cmd::replication::connected_replicas;
 use crate::resp::Resp;
 use bytes::Bytes;
 use std::sync::atomic::Ordering;
@@ -34,6 +35,20 @@ pub fn info(items: &[Resp], ctx: &ServerContext) -> Resp {
         info.push_str(&get_memory_info(ctx));
     }
 
+    if section == "default" || section == "all" || section == "persistence" {
+        if !info.is_empty() {
+            info.push_str("\r\n");
+        }
+        info.push_str(&get_persistence_info(ctx));
+    }
+
+    if section == "default" || section == "all" || section == "stats" {
+        if !info.is_empty() {
+            info.push_str("\r\n");
+        }
+        info.push_str(&get_stats_info(ctx));
+    }
+
     if section == "default" || section == "all" || section == "replication" {
         if !info.is_empty() {
             info.push_str("\r\n");
@@ -41,6 +56,13 @@ pub fn info(items: &[Resp], ctx: &ServerContext) -> Resp {
         info.push_str(&get_replication_info(ctx));
     }
 
+    if section == "default" || section == "all" || section == "cpu" {
+        if !info.is_empty() {
+            info.push_str("\r\n");
+        }
+        info.push_str(&get_cpu_info());
+    }
+
     if section == "default" || section == "all" || section == "keyspace" {
         if !info.is_empty() {
             info.push_str("\r\n");
@@ -55,7 +77,7 @@ pub fn info(items: &[Resp], ctx: &ServerContext) -> Resp {
         info.push_str(&get_cluster_info(ctx));
     }
 
-    Resp::BulkString(Some(Bytes::from(info)))
+    Resp::Verbatim(Bytes::from(info))
 }
 
 pub fn role(_items: &[Resp], ctx: &ServerContext) -> Resp {
@@ -64,8 +86,20 @@ pub fn role(_items: &[Resp], ctx: &ServerContext) -> Resp {
         crate::cmd::ReplicationRole::Master => {
             let mut role_info = Vec::new();
             role_info.push(Resp::BulkString(Some(Bytes::from("master"))));
-            role_info.push(Resp::Integer(0));
-            role_info.push(Resp::Array(Some(Vec::new())));
+            role_info.push(Resp::Integer(
+                ctx.repl.repl_offset.load(Ordering::Relaxed) as i64
+            ));
+            let replicas = connected_replicas(ctx)
+                .into_iter()
+                .map(|r| {
+                    Resp::Array(Some(vec![
+                        Resp::BulkString(Some(Bytes::from(r.ip))),
+                        Resp::BulkString(Some(Bytes::from(r.port.to_string()))),
+                        Resp::BulkString(Some(Bytes::from(r.offset.to_string()))),
+                    ]))
+                })
+                .collect();
+            role_info.push(Resp::Array(Some(replicas)));
             Resp::Array(Some(role_info))
         }
         crate::cmd::ReplicationRole::Slave => {
@@ -80,21 +114,38 @@ pub fn role(_items: &[Resp], ctx: &ServerContext) -> Resp {
             role_info.push(Resp::BulkString(Some(Bytes::from("slave"))));
             role_info.push(Resp::BulkString(Some(Bytes::from(mh))));
             role_info.push(Resp::Integer(mp));
-            role_info.push(Resp::BulkString(Some(Bytes::from("connect"))));
-            role_info.push(Resp::Integer(0));
+            let state = if ctx.repl.master_link_established.load(Ordering::Relaxed) {
+                "connected"
+            } else {
+                "connect"
+            };
+            role_info.push(Resp::BulkString(Some(Bytes::from(state))));
+            role_info.push(Resp::Integer(
+                ctx.repl.repl_offset.load(Ordering::Relaxed) as i64,
+            ));
             Resp::Array(Some(role_info))
         }
     }
 }
 
-fn get_server_info(_ctx: &ServerContext) -> String {
+fn get_server_info(ctx: &ServerContext) -> String {
     let mut s = String::new();
     s.push_str("# Server\r\n");
     s.push_str("redis_version:6.2.5\r\n");
     s.push_str(&format!("os:{}\r\n", std::env::consts::OS));
     s.push_str(&format!("process_id:{}\r\n", std::process::id()));
-    s.push_str(&format!("tcp_port:{}\r\n", _ctx.config.port));
-    if let Some(config_file) = &_ctx.config.config_file {
+    s.push_str(&format!("run_id:{}\r\n", ctx.repl.run_id.read().unwrap()));
+    s.push_str(&format!("tcp_port:{}\r\n", ctx.config.port));
+    let uptime = ctx.start_time.elapsed().as_secs();
+    s.push_str(&format!("uptime_in_seconds:{}\r\n", uptime));
+    s.push_str(&format!("uptime_in_days:{}\r\n", uptime / 86400));
+    // Commands run on the connection tasks; there are no separate I/O threads
+    s.push_str("io_threads_active:0\r\n");
+    let executable = std::env::current_exe()
+        .map(|p| p.display().to_string())
+        .unwrap_or_default();
+    s.push_str(&format!("executable:{}\r\n", executable));
+    if let Some(config_file) = &ctx.config.config_file {
         s.push_str(&format!("config_file:{}\r\n", config_file));
     } else {
         s.push_str("config_file:\r\n");
@@ -102,6 +153,28 @@ fn get_server_info(_ctx: &ServerContext) -> String {
     s
 }
 
+fn get_cpu_info() -> String {
+    let mut s = String::new();
+    s.push_str("# CPU\r\n");
+    let (sys, user) = process_cpu_times();
+    s.push_str(&format!("used_cpu_sys:{:.6}\r\n", sys));
+    s.push_str(&format!("used_cpu_user:{:.6}\r\n", user));
+    s
+}
+
+/// System and user CPU seconds consumed by this process so far.
+fn process_cpu_times() -> (f64, f64) {
+    let secs = |tv: libc::timeval| tv.tv_sec as f64 + tv.tv_usec as f64 / 1_000_000.0;
+    unsafe {
+        let mut usage: libc::rusage = std::mem::zeroed();
+        if libc::getrusage(libc::RUSAGE_SELF, &mut usage) == 0 {
+            (secs(usage.ru_stime), secs(usage.ru_utime))
+        } else {
+            (0.0, 0.0)
+        }
+    }
+}
+
 fn get_clients_info(ctx: &ServerContext) -> String {
     let mut s = String::new();
     s.push_str("# Clients\r\n");
@@ -111,7 +184,32 @@ fn get_clients_info(ctx: &ServerContext) -> String {
     let blocked = ctx.clients_ctx.blocked_client_count.load(Ordering::Relaxed);
     s.push_str(&format!("blocked_clients:{}\r\n", blocked));
 
-    s.push_str(&format!("maxclients:{}\r\n", ctx.config.maxclients));
+    let mut tracking = 0;
+    let mut pubsub = 0;
+    for entry in ctx.clients_ctx.clients.iter() {
+        let c = entry.value();
+        if c.flags.contains('t') {
+            tracking += 1;
+        }
+        if c.sub > 0 || c.psub > 0 {
+            pubsub += 1;
+        }
+    }
+    s.push_str(&format!("tracking_clients:{}\r\n", tracking));
+    s.push_str(&format!("pubsub_clients:{}\r\n", pubsub));
+
+    // A client may watch several keys; count each watcher once.
+    let mut watching = std::collections::HashSet::new();
+    for entry in ctx.clients_ctx.watched_clients.iter() {
+        watching.extend(entry.value().iter().copied());
+    }
+    s.push_str(&format!("watching_clients:{}\r\n", watching.len()));
+
+    let in_timeout_table = ctx.clients_ctx.clients_in_timeout_table.load(Ordering::Relaxed);
+    s.push_str(&format!("clients_in_timeout_table:{}\r\n", in_timeout_table));
+
+    let maxclients = ctx.clients_ctx.maxclients.load(Ordering::Relaxed);
+    s.push_str(&format!("maxclients:{}\r\n", maxclients));
     s
 }
 
@@ -199,6 +297,107 @@ fn get_memory_usage() -> (u64, u64) {
     (current_rss, current_rss)
 }
 
+fn get_persistence_info(ctx: &ServerContext) -> String {
+    let persist = &ctx.persist;
+    let mut s = String::new();
+    s.push_str("# Persistence\r\n");
+    s.push_str("loading:0\r\n");
+    s.push_str(&format!(
+        "rdb_changes_since_last_save:{}\r\n",
+        persist.dirty.load(Ordering::Relaxed)
+    ));
+    s.push_str(&format!(
+        "rdb_bgsave_in_progress:{}\r\n",
+        (persist.rdb_child_pid.load(Ordering::Relaxed) != -1) as u8
+    ));
+    s.push_str(&format!(
+        "rdb_last_save_time:{}\r\n",
+        persist.last_save_time.load(Ordering::Relaxed)
+    ));
+    let bgsave_status = if persist.last_bgsave_ok.load(Ordering::Relaxed) {
+        "ok"
+    } else {
+        "err"
+    };
+    s.push_str(&format!("rdb_last_bgsave_status:{}\r\n", bgsave_status));
+    s.push_str(&format!("aof_enabled:{}\r\n", ctx.aof.is_some() as u8));
+    let Some(aof) = &ctx.aof else {
+        s.push_str("aof_rewrite_in_progress:0\r\n");
+        s.push_str("aof_last_write_status:ok\r\n");
+        return s;
+    };
+    let stats = aof.stats();
+    s.push_str(&format!(
+        "aof_rewrite_in_progress:{}\r\n",
+        stats.rewrite_in_progress.load(Ordering::Relaxed) as u8
+    ));
+    let write_status = if stats.last_write_ok.load(Ordering::Relaxed) {
+        "ok"
+    } else {
+        "err"
+    };
+    s.push_str(&format!("aof_last_write_status:{}\r\n", write_status));
+    s.push_str(&format!(
+        "aof_current_size:{}\r\n",
+        stats.current_size.load(Ordering::Relaxed)
+    ));
+    s.push_str(&format!(
+        "aof_base_size:{}\r\n",
+        stats.base_size.load(Ordering::Relaxed)
+    ));
+    s.push_str(&format!(
+        "aof_pending_rewrite:{}\r\n",
+        stats.pending_rewrite.load(Ordering::Relaxed) as u8
+    ));
+    s.push_str(&format!(
+        "aof_last_fsync_offset:{}\r\n",
+        stats.fsync_offset()
+    ));
+    s
+}
+
+fn get_stats_info(ctx: &ServerContext) -> String {
+    let mut s = String::new();
+    s.push_str("# Stats\r\n");
+    let clients = &ctx.clients_ctx;
+    let (ops_per_sec, input_kbps, output_kbps) = {
+        let m = clients.inst_metrics.lock().unwrap();
+        (
+            m.ops.value(),
+            m.net_input.value() as f64 / 1024.0,
+            m.net_output.value() as f64 / 1024.0,
+        )
+    };
+    s.push_str(&format!(
+        "total_connections_received:{}\r\n",
+        clients.total_connections_received.load(Ordering::Relaxed)
+    ));
+    s.push_str(&format!(
+        "total_commands_processed:{}\r\n",
+        clients.total_commands_processed.load(Ordering::Relaxed)
+    ));
+    s.push_str(&format!("instantaneous_ops_per_sec:{}\r\n", ops_per_sec));
+    s.push_str(&format!(
+        "total_net_input_bytes:{}\r\n",
+        clients.net_input_bytes.load(Ordering::Relaxed)
+    ));
+    s.push_str(&format!(
+        "total_net_output_bytes:{}\r\n",
+        clients.net_output_bytes.load(Ordering::Relaxed)
+    ));
+    s.push_str(&format!("instantaneous_input_kbps:{:.2}\r\n", input_kbps));
+    s.push_str(&format!("instantaneous_output_kbps:{:.2}\r\n", output_kbps));
+    s.push_str(&format!(
+        "keyspace_hits:{}\r\n",
+        ctx.mem.keyspace_hits.load(Ordering::Relaxed)
+    ));
+    s.push_str(&format!(
+        "keyspace_misses:{}\r\n",
+        ctx.mem.keyspace_misses.load(Ordering::Relaxed)
+    ));
+    s
+}
+
 fn get_replication_info(ctx: &ServerContext) -> String {
     let mut s = String::new();
     s.push_str("# Replication\r\n");
@@ -206,58 +405,19 @@ fn get_replication_info(ctx: &ServerContext) -> String {
     match role {
         crate::cmd::ReplicationRole::Master => {
             s.push_str("role:master\r\n");
-            let connected_slaves = ctx.repl.replicas.len();
-            s.push_str(&format!("connected_slaves:{}\r\n", connected_slaves));
-            let now = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs() as u64;
-            let mut idx = 0;
-            for entry in ctx.repl.replicas.iter() {
-                let id = *entry.key();
-                let addr = if let Some(ci) = ctx.clients_ctx.clients.get(&id) {
-                    ci.addr.clone()
-                } else {
-                    String::from("unknown:0")
-                };
-                let mut ip = String::from("unknown");
-                let mut port: u16 = 0;
-                if let Some((host, port_s)) = addr.rsplit_once(':') {
-                    ip = host.to_string();
-                    port = port_s.parse::<u16>().unwrap_or(0);
-                }
-                if let Some(p) = ctx.repl.replica_listening_port.get(&id) {
-                    port = *p.value();
-                }
-                let offset = if let Some(v) = ctx.repl.replica_ack.get(&id) {
-                    *v.value()
-                } else {
-                    0
-                };
-                let ack_time = if let Some(t) = ctx.repl.replica_ack_time.get(&id) {
-                    *t.value()
-                } else {
-                    now
-                };
-                let lag = now.saturating_sub(ack_time);
+            let replicas = connected_replicas(ctx);
+            s.push_str(&format!("connected_slaves:{}\r\n", replicas.len()));
+            for (idx, r) in replicas.iter().enumerate() {
                 s.push_str(&format!(
                     "slave{}:ip={},port={},state=online,offset={},lag={}\r\n",
-                    idx, ip, port, offset, lag
+                    idx, r.ip, r.port, r.offset, r.lag
                 ));
-                idx += 1;
             }
-            let master_offset = ctx.repl.repl_offset.load(Ordering::Relaxed);
             let repl_backlog_size = ctx.repl.repl_backlog_size.load(Ordering::Relaxed) as u64;
             let (first_offset, histlen) = {
                 // Use try_lock: INFO is best-effort; skip if briefly contended.
-                if let Ok(q) = ctx.repl.repl_backlog.try_lock() {
-                    if let Some((off, _)) = q.front() {
-                        let first = *off;
-                        let hist = master_offset.saturating_sub(first).saturating_add(1);
-                        (first, hist)
-                    } else {
-                        (0, 0)
-                    }
+                if let Ok(backlog) = ctx.repl.repl_backlog.try_lock() {
+                    (backlog.first_offset().unwrap_or(0), backlog.histlen())
                 } else {
                     (0, 0)
                 }
@@ -296,14 +456,19 @@ fn get_replication_info(ctx: &ServerContext) -> String {
             let mp = ctx.repl.master_port.read().unwrap().unwrap_or(0);
             s.push_str(&format!("master_host:{}\r\n", mh));
             s.push_str(&format!("master_port:{}\r\n", mp));
-            let status = if ctx.repl.master_link_established.load(Ordering::Relaxed) {
-                "up"
-            } else {
-                "down"
-            };
+            let link_up = ctx.repl.master_link_established.load(Ordering::Relaxed);
+            let syncing = !link_up
+                && ctx
+                    .repl
+                    .master_link
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .is_some_and(|link| !link.is_finished());
+            let status = if link_up { "up" } else { "down" };
             s.push_str(&format!("master_link_status:{}\r\n", status));
             s.push_str("master_last_io_seconds_ago:0\r\n");
-            s.push_str("master_sync_in_progress:0\r\n");
+            s.push_str(&format!("master_sync_in_progress:{}\r\n", syncing as u8));
             s.push_str("slave_read_only:1\r\n");
             let offset = ctx.repl.repl_offset.load(Ordering::Relaxed);
             s.push_str(&format!("slave_repl_offset:{}\r\n", offset));
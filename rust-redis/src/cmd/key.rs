@@ -1,14 +1,25 @@
+use crate::cmd::expiry::{
+    expiry_from_abs_ms, expiry_from_abs_secs, expiry_from_relative_ms, expiry_from_relative_secs,
+    parse_expire_arg,
+};
+use crate::conf::EncodingLimits;
 use crate::db::{Db, Entry, Value};
 use crate::resp::Resp;
 use bytes::Bytes;
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
 
-pub fn del(items: &[Resp], db: &Db) -> Resp {
+/// Removes the given keys, returning the reply together with the keys that
+/// actually existed so only those fire `del` events and invalidate watchers.
+pub fn del(items: &[Resp], db: &Db) -> (Resp, Vec<Bytes>) {
     if items.len() < 2 {
-        return Resp::StaticError("ERR wrong number of arguments for 'DEL'");
+        return (
+            Resp::StaticError("ERR wrong number of arguments for 'DEL'"),
+            Vec::new(),
+        );
     }
 
-    let mut deleted = 0;
+    let mut removed = Vec::new();
     for item in &items[1..] {
         let key = match item {
             Resp::BulkString(Some(b)) => b,
@@ -23,20 +34,24 @@ pub fn del(items: &[Resp], db: &Db) -> Resp {
             } else {
                 drop(entry);
                 if db.remove(key).is_some() {
-                    deleted += 1;
+                    removed.push(key.clone());
                 }
             }
         }
     }
-    Resp::Integer(deleted)
+    (Resp::Integer(removed.len() as i64), removed)
 }
 
-pub fn unlink(items: &[Resp], db: &Db) -> Resp {
+/// Like [`del`], but hands large values off to a background task to drop.
+pub fn unlink(items: &[Resp], db: &Db) -> (Resp, Vec<Bytes>) {
     if items.len() < 2 {
-        return Resp::StaticError("ERR wrong number of arguments for 'UNLINK'");
+        return (
+            Resp::StaticError("ERR wrong number of arguments for 'UNLINK'"),
+            Vec::new(),
+        );
     }
 
-    let mut deleted = 0;
+    let mut removed = Vec::new();
     for item in &items[1..] {
         let key = match item {
             Resp::BulkString(Some(b)) => b,
@@ -51,7 +66,7 @@ pub fn unlink(items: &[Resp], db: &Db) -> Resp {
             } else {
                 drop(entry);
                 if let Some((_, val)) = db.remove(key) {
-                    deleted += 1;
+                    removed.push(key.clone());
                     // In Redis, UNLINK is non-blocking. Here we can simulate it by
                     // dropping the value in a separate thread if it's potentially large.
                     // For now, DashMap's remove is fast, but we'll explicitly drop val
@@ -63,157 +78,145 @@ pub fn unlink(items: &[Resp], db: &Db) -> Resp {
             }
         }
     }
-    Resp::Integer(deleted)
+    (Resp::Integer(removed.len() as i64), removed)
 }
 
-pub fn expire(items: &[Resp], db: &Db) -> Resp {
-    if items.len() != 3 {
-        return Resp::StaticError("ERR wrong number of arguments for 'EXPIRE'");
+/// The NX / XX / GT / LT options of EXPIRE and friends. XX may be combined
+/// with GT or LT; NX stands alone.
+#[derive(Default)]
+struct ExpireCondition {
+    nx: bool,
+    xx: bool,
+    gt: bool,
+    lt: bool,
+}
+
+impl ExpireCondition {
+    fn parse(options: &[Resp]) -> Result<Self, Resp> {
+        let mut cond = ExpireCondition::default();
+        for opt in options {
+            let opt = match opt {
+                Resp::BulkString(Some(b)) => b,
+                Resp::SimpleString(s) => s,
+                _ => return Err(Resp::StaticError("ERR syntax error")),
+            };
+            match opt.to_ascii_uppercase().as_slice() {
+                b"NX" => cond.nx = true,
+                b"XX" => cond.xx = true,
+                b"GT" => cond.gt = true,
+                b"LT" => cond.lt = true,
+                _ => {
+                    return Err(Resp::Error(format!(
+                        "ERR Unsupported option {}",
+                        String::from_utf8_lossy(opt)
+                    )));
+                }
+            }
+        }
+        if cond.nx && (cond.xx || cond.gt || cond.lt) {
+            return Err(Resp::StaticError(
+                "ERR NX and XX, GT or LT options at the same time are not compatible",
+            ));
+        }
+        if cond.gt && cond.lt {
+            return Err(Resp::StaticError(
+                "ERR GT and LT options at the same time are not compatible",
+            ));
+        }
+        Ok(cond)
     }
-    let key = match &items[1] {
-        Resp::BulkString(Some(b)) => b.clone(),
-        Resp::SimpleString(s) => s.clone(),
-        _ => return Resp::StaticError("ERR invalid key"),
-    };
-    let seconds_bytes = match &items[2] {
-        Resp::BulkString(Some(b)) => b,
-        Resp::SimpleString(s) => s,
-        _ => return Resp::StaticError("ERR invalid seconds"),
-    };
-    let seconds_str = match std::str::from_utf8(seconds_bytes) {
-        Ok(s) => s,
-        Err(_) => return Resp::StaticError("ERR value is not an integer or out of range"),
-    };
-    let seconds: u64 = match seconds_str.parse() {
-        Ok(s) => s,
-        Err(_) => return Resp::StaticError("ERR value is not an integer or out of range"),
-    };
 
-    if let Some(mut entry) = db.get_mut(&key) {
-        if entry.is_expired() {
-            drop(entry);
-            db.remove(&key);
-            Resp::Integer(0)
-        } else {
-            let new_entry = Entry::new(entry.value.clone(), Some(seconds * 1000));
-            entry.expires_at = new_entry.expires_at;
-            Resp::Integer(1)
+    /// Whether a key whose deadline is `current` (None for no TTL) may be
+    /// given `new`. As in Redis, a key without a TTL counts as expiring
+    /// never: GT leaves it alone and LT always applies.
+    fn allows(&self, current: Option<u64>, new: u64) -> bool {
+        if (self.nx && current.is_some()) || (self.xx && current.is_none()) {
+            return false;
+        }
+        match current {
+            Some(current) => (!self.gt || new > current) && (!self.lt || new < current),
+            None => !self.gt,
         }
-    } else {
-        Resp::Integer(0)
     }
 }
 
-pub fn pexpire(items: &[Resp], db: &Db) -> Resp {
-    if items.len() != 3 {
-        return Resp::StaticError("ERR wrong number of arguments for 'PEXPIRE'");
-    }
-    let key = match &items[1] {
-        Resp::BulkString(Some(b)) => b.clone(),
-        Resp::SimpleString(s) => s.clone(),
-        _ => return Resp::StaticError("ERR invalid key"),
-    };
-    let ms_bytes = match &items[2] {
-        Resp::BulkString(Some(b)) => b,
-        Resp::SimpleString(s) => s,
-        _ => return Resp::StaticError("ERR invalid milliseconds"),
-    };
-    let ms_str = match std::str::from_utf8(ms_bytes) {
-        Ok(s) => s,
-        Err(_) => return Resp::StaticError("ERR value is not an integer or out of range"),
-    };
-    let ms: u64 = match ms_str.parse() {
-        Ok(s) => s,
-        Err(_) => return Resp::StaticError("ERR value is not an integer or out of range"),
+/// Sets the absolute `deadline_ms` on `key` if `options` allow it, replying
+/// 1 when it was set and 0 when the key is missing or the condition failed.
+/// A deadline already in the past deletes the key, as in Redis.
+fn set_expiry(key: &Bytes, deadline_ms: i64, options: &[Resp], db: &Db) -> Resp {
+    let cond = match ExpireCondition::parse(options) {
+        Ok(cond) => cond,
+        Err(e) => return e,
     };
 
-    if let Some(mut entry) = db.get_mut(&key) {
+    if let Some(mut entry) = db.get_mut(key) {
         if entry.is_expired() {
             drop(entry);
-            db.remove(&key);
+            db.remove(key);
             Resp::Integer(0)
-        } else {
-            let new_entry = Entry::new(entry.value.clone(), Some(ms));
-            entry.expires_at = new_entry.expires_at;
+        } else if cond.allows(entry.expires_at, deadline_ms.max(0) as u64) {
+            if deadline_ms <= crate::clock::now_ms() as i64 {
+                drop(entry);
+                db.remove(key);
+            } else {
+                entry.expires_at = Some(deadline_ms as u64);
+            }
             Resp::Integer(1)
+        } else {
+            Resp::Integer(0)
         }
     } else {
         Resp::Integer(0)
     }
 }
 
-pub fn expireat(items: &[Resp], db: &Db) -> Resp {
-    if items.len() != 3 {
-        return Resp::StaticError("ERR wrong number of arguments for 'EXPIREAT'");
+pub fn expire(items: &[Resp], db: &Db) -> Resp {
+    if items.len() < 3 {
+        return Resp::StaticError("ERR wrong number of arguments for 'EXPIRE'");
     }
-    let key = match &items[1] {
-        Resp::BulkString(Some(b)) => b.clone(),
-        Resp::SimpleString(s) => s.clone(),
-        _ => return Resp::StaticError("ERR invalid key"),
-    };
-    let timestamp_bytes = match &items[2] {
-        Resp::BulkString(Some(b)) => b,
-        Resp::SimpleString(s) => s,
-        _ => return Resp::StaticError("ERR invalid timestamp"),
-    };
-    let timestamp_str = match std::str::from_utf8(timestamp_bytes) {
-        Ok(s) => s,
-        Err(_) => return Resp::StaticError("ERR value is not an integer or out of range"),
-    };
-    let timestamp: u64 = match timestamp_str.parse() {
-        Ok(s) => s,
-        Err(_) => return Resp::StaticError("ERR value is not an integer or out of range"),
-    };
+    expire_generic(items, db, "expire", expiry_from_relative_secs)
+}
 
-    if let Some(mut entry) = db.get_mut(&key) {
-        if entry.is_expired() {
-            drop(entry);
-            db.remove(&key);
-            Resp::Integer(0)
-        } else {
-            entry.expires_at = Some(timestamp * 1000);
-            Resp::Integer(1)
-        }
-    } else {
-        Resp::Integer(0)
+pub fn pexpire(items: &[Resp], db: &Db) -> Resp {
+    if items.len() < 3 {
+        return Resp::StaticError("ERR wrong number of arguments for 'PEXPIRE'");
+    }
+    expire_generic(items, db, "pexpire", expiry_from_relative_ms)
+}
+
+pub fn expireat(items: &[Resp], db: &Db) -> Resp {
+    if items.len() < 3 {
+        return Resp::StaticError("ERR wrong number of arguments for 'EXPIREAT'");
     }
+    expire_generic(items, db, "expireat", expiry_from_abs_secs)
 }
 
 pub fn pexpireat(items: &[Resp], db: &Db) -> Resp {
-    if items.len() != 3 {
+    if items.len() < 3 {
         return Resp::StaticError("ERR wrong number of arguments for 'PEXPIREAT'");
     }
+    expire_generic(items, db, "pexpireat", expiry_from_abs_ms)
+}
+
+/// Sets the expiry of `items[1]` from the time in `items[2]`, subject to the
+/// NX/XX/GT/LT options that follow it.
+fn expire_generic(
+    items: &[Resp],
+    db: &Db,
+    cmd: &str,
+    to_abs_ms: fn(i64, &str) -> Result<i64, Resp>,
+) -> Resp {
     let key = match &items[1] {
         Resp::BulkString(Some(b)) => b.clone(),
         Resp::SimpleString(s) => s.clone(),
         _ => return Resp::StaticError("ERR invalid key"),
     };
-    let timestamp_bytes = match &items[2] {
-        Resp::BulkString(Some(b)) => b,
-        Resp::SimpleString(s) => s,
-        _ => return Resp::StaticError("ERR invalid timestamp"),
-    };
-    let timestamp_str = match std::str::from_utf8(timestamp_bytes) {
-        Ok(s) => s,
-        Err(_) => return Resp::StaticError("ERR value is not an integer or out of range"),
-    };
-    let timestamp: u64 = match timestamp_str.parse() {
-        Ok(s) => s,
-        Err(_) => return Resp::StaticError("ERR value is not an integer or out of range"),
+    let when = match parse_expire_arg(&items[2]).and_then(|v| to_abs_ms(v, cmd)) {
+        Ok(when) => when,
+        Err(e) => return e,
     };
 
-    if let Some(mut entry) = db.get_mut(&key) {
-        if entry.is_expired() {
-            drop(entry);
-            db.remove(&key);
-            Resp::Integer(0)
-        } else {
-            entry.expires_at = Some(timestamp);
-            Resp::Integer(1)
-        }
-    } else {
-        Resp::Integer(0)
-    }
+    set_expiry(&key, when, &items[3..], db)
 }
 
 pub fn ttl(items: &[Resp], db: &Db) -> Resp {
@@ -321,7 +324,9 @@ pub fn exists(items: &[Resp], db: &Db) -> Resp {
     Resp::Integer(count)
 }
 
-pub fn touch(items: &[Resp], db: &Db) -> Resp {
+/// Refreshes the access metadata (LRU clock and LFU counter) of each live
+/// key without reading its value, so it counts as recently used for eviction.
+pub fn touch(items: &[Resp], db: &Db, server_ctx: &ServerContext) -> Resp {
     if items.len() < 2 {
         return Resp::StaticError("ERR wrong number of arguments for 'TOUCH'");
     }
@@ -334,14 +339,23 @@ pub fn touch(items: &[Resp], db: &Db) -> Resp {
             _ => continue,
         };
 
-        if let Some(entry) = db.get(key) {
-            if !entry.is_expired() {
-                count += 1;
-                // TODO: Update LRU/LFU access time when implemented in Entry
-            } else {
+        let hit = match db.get_mut(key) {
+            Some(mut entry) if !entry.is_expired() => {
+                entry.touch();
+                true
+            }
+            Some(entry) => {
                 drop(entry);
                 db.remove(key);
+                false
             }
+            None => false,
+        };
+        if hit {
+            count += 1;
+            server_ctx.mem.keyspace_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            server_ctx.mem.keyspace_misses.fetch_add(1, Ordering::Relaxed);
         }
     }
     Resp::Integer(count)
@@ -379,25 +393,57 @@ pub fn type_(items: &[Resp], db: &Db) -> Resp {
     }
 }
 
-pub fn flushdb(items: &[Resp], db: &Db) -> Resp {
-    if items.len() > 2 {
-        // Redis 6.2 supports FLUSHDB [ASYNC|SYNC]
+/// Validates the optional `ASYNC|SYNC` flag of FLUSHDB/FLUSHALL. Both modes
+/// clear in place.
+fn parse_flush_mode(items: &[Resp]) -> Result<(), Resp> {
+    match items.len() {
+        1 => Ok(()),
+        2 => match as_bytes(&items[1]) {
+            Some(b) if b.eq_ignore_ascii_case(b"ASYNC") || b.eq_ignore_ascii_case(b"SYNC") => {
+                Ok(())
+            }
+            _ => Err(Resp::StaticError("ERR syntax error")),
+        },
+        _ => Err(Resp::StaticError("ERR syntax error")),
     }
+}
 
+/// Flushes are bulk operations: the dirty counter grows by the number of
+/// keys removed, and no per-key keyspace events are fired.
+fn record_flush(removed: usize, conn_ctx: &mut ConnectionContext) {
+    conn_ctx.dirty_changes = Some(removed as u64);
+    conn_ctx.modified_keys = Some(Vec::new());
+}
+
+pub fn flushdb(items: &[Resp], db: &Db, conn_ctx: &mut ConnectionContext) -> Resp {
+    if let Err(e) = parse_flush_mode(items) {
+        return e;
+    }
+
+    let removed = db.len();
     db.clear();
+    record_flush(removed, conn_ctx);
     Resp::SimpleString(Bytes::from("OK"))
 }
 
 use std::sync::RwLock;
 
-pub fn flushall(items: &[Resp], databases: &Arc<Vec<RwLock<Db>>>) -> Resp {
-    if items.len() > 2 {
-        // Just warning or handling if needed. For now simple clear.
+pub fn flushall(
+    items: &[Resp],
+    databases: &Arc<Vec<RwLock<Db>>>,
+    conn_ctx: &mut ConnectionContext,
+) -> Resp {
+    if let Err(e) = parse_flush_mode(items) {
+        return e;
     }
 
+    let mut removed = 0;
     for db_lock in databases.iter() {
-        db_lock.read().unwrap().clear();
+        let db = db_lock.read().unwrap();
+        removed += db.len();
+        db.clear();
     }
+    record_flush(removed, conn_ctx);
     Resp::SimpleString(Bytes::from("OK"))
 }
 
@@ -408,6 +454,26 @@ pub fn dbsize(items: &[Resp], db: &Db) -> Resp {
     Resp::Integer(db.len() as i64)
 }
 
+/// Picks a key uniformly from the key set, so a single huge collection is no
+/// more likely to be returned than any small key. An expired pick is deleted
+/// and another drawn, so the reply is nil only once the keyspace is empty.
+pub fn randomkey(items: &[Resp], db: &Db) -> Resp {
+    if items.len() != 1 {
+        return Resp::StaticError("ERR wrong number of arguments for 'RANDOMKEY'");
+    }
+    loop {
+        let Some((key, expired)) =
+            crate::db::random_entry(db, |key, entry| (key.clone(), entry.is_expired()))
+        else {
+            return Resp::BulkString(None);
+        };
+        if !expired {
+            return Resp::BulkString(Some(key));
+        }
+        db.remove_if(&key, |_, entry| entry.is_expired());
+    }
+}
+
 pub fn copy(items: &[Resp], conn_ctx: &mut ConnectionContext, server_ctx: &ServerContext) -> Resp {
     if items.len() < 3 {
         return Resp::StaticError("ERR wrong number of arguments for 'COPY' command");
@@ -465,31 +531,121 @@ pub fn copy(items: &[Resp], conn_ctx: &mut ConnectionContext, server_ctx: &Serve
         .clone();
     let dst_db = server_ctx.databases[db_idx].read().unwrap().clone();
 
-    if let Some(entry) = src_db.get(&source) {
-        if entry.is_expired() {
+    // Clone the entry out and release the shard guard before touching the
+    // destination: both keys may live in the same map (and shard).
+    let entry = match src_db.get(&source) {
+        Some(entry) if entry.is_expired() => {
             drop(entry);
             src_db.remove(&source);
             return Resp::Integer(0);
         }
+        Some(entry) => entry.clone(),
+        None => return Resp::Integer(0),
+    };
 
-        if !replace {
-            if let Some(dst_entry) = dst_db.get(&destination) {
-                if !dst_entry.is_expired() {
-                    return Resp::Integer(0);
-                }
-                drop(dst_entry);
-                dst_db.remove(&destination);
-            }
+    if !replace && let Some(dst_entry) = dst_db.get(&destination) {
+        if !dst_entry.is_expired() {
+            return Resp::Integer(0);
         }
+        drop(dst_entry);
+        dst_db.remove(&destination);
+    }
 
-        dst_db.insert(destination, entry.clone());
-        Resp::Integer(1)
-    } else {
-        Resp::Integer(0)
+    dst_db.insert(destination, entry);
+    Resp::Integer(1)
+}
+
+// Longest string Redis stores inline with its object header (embstr).
+const MAX_EMBSTR_LEN: usize = 44;
+
+/// Whether `member` is the canonical spelling of an i64: no leading zeros,
+/// sign or whitespace beyond what the integer itself prints as.
+fn is_canonical_int(member: &[u8]) -> bool {
+    std::str::from_utf8(member)
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .is_some_and(|v| v.to_string().as_bytes() == member)
+}
+
+fn fits_listpack<'a>(
+    len: usize,
+    max_entries: usize,
+    max_value: usize,
+    mut elements: impl Iterator<Item = &'a Bytes>,
+) -> bool {
+    len <= max_entries && elements.all(|e| e.len() <= max_value)
+}
+
+/// The encoding Redis would pick for `value` under `limits`. Values are
+/// always stored in their full form here, so this is derived from the
+/// contents rather than tracked, which keeps it accurate however the value
+/// was built or loaded, and lets it follow CONFIG SET immediately.
+pub fn object_encoding(value: &Value, limits: &EncodingLimits) -> &'static str {
+    match value {
+        Value::String(s) => {
+            if is_canonical_int(s) {
+                "int"
+            } else if s.len() <= MAX_EMBSTR_LEN {
+                "embstr"
+            } else {
+                "raw"
+            }
+        }
+        Value::List(list) => {
+            let fits = if limits.list_max_listpack_size >= 0 {
+                list.len() as i64 <= limits.list_max_listpack_size
+            } else {
+                // Each entry costs its bytes plus a couple of header bytes
+                let max_bytes = 4096 << (-limits.list_max_listpack_size - 1);
+                list.iter().map(|e| e.len() + 2).sum::<usize>() <= max_bytes
+            };
+            if fits { "listpack" } else { "quicklist" }
+        }
+        Value::Set(set) => {
+            if set.len() <= limits.set_max_intset_entries && set.iter().all(|m| is_canonical_int(m))
+            {
+                "intset"
+            } else if fits_listpack(
+                set.len(),
+                limits.set_max_listpack_entries,
+                limits.set_max_listpack_value,
+                set.iter(),
+            ) {
+                "listpack"
+            } else {
+                "hashtable"
+            }
+        }
+        Value::ZSet(zset) => {
+            if fits_listpack(
+                zset.members.len(),
+                limits.zset_max_listpack_entries,
+                limits.zset_max_listpack_value,
+                zset.members.keys(),
+            ) {
+                "listpack"
+            } else {
+                "skiplist"
+            }
+        }
+        Value::Hash(hash) => {
+            if fits_listpack(
+                hash.len(),
+                limits.hash_max_listpack_entries,
+                limits.hash_max_listpack_value,
+                hash.iter().flat_map(|(f, v)| [f, v]),
+            ) {
+                "listpack"
+            } else {
+                "hashtable"
+            }
+        }
+        Value::Stream(_) => "stream",
+        Value::HyperLogLog(_) => "raw",
     }
 }
 
-pub fn object(items: &[Resp], db: &Db) -> Resp {
+pub fn object(items: &[Resp], db: &Db, server_ctx: &ServerContext) -> Resp {
     if items.len() < 3 {
         return Resp::StaticError("ERR wrong number of arguments for 'OBJECT' command");
     }
@@ -515,16 +671,8 @@ pub fn object(items: &[Resp], db: &Db) -> Resp {
 
         match subcommand.as_str() {
             "ENCODING" => {
-                let enc = match &entry.value {
-                    Value::String(_) => "raw",
-                    Value::List(_) => "quicklist",
-                    Value::Set(_) => "hashtable",
-                    Value::ZSet(_) => "skiplist",
-                    Value::Hash(_) => "hashtable",
-                    Value::Stream(_) => "stream",
-                    Value::HyperLogLog(_) => "raw",
-                };
-                Resp::BulkString(Some(Bytes::from(enc)))
+                let limits = *server_ctx.mem.encoding_limits.read().unwrap();
+                Resp::BulkString(Some(Bytes::from(object_encoding(&entry.value, &limits))))
             }
             "IDLETIME" => {
                 let idle = crate::clock::now_secs().saturating_sub(entry.lru);
@@ -826,15 +974,53 @@ pub fn swapdb(items: &[Resp], server_ctx: &ServerContext) -> Resp {
         return Resp::SimpleString(Bytes::from("OK"));
     }
 
-    // Swap the databases in the map
-    let mut db1 = server_ctx.databases[idx1].write().unwrap();
-    let mut db2 = server_ctx.databases[idx2].write().unwrap();
+    // Always lock the lower index first, so SWAPDB a b racing SWAPDB b a
+    // can't deadlock. Holding both write locks makes the swap atomic: any
+    // command resolves its db either before or after, never in between.
+    let (lo, hi) = (idx1.min(idx2), idx1.max(idx2));
+    let mut db_lo = server_ctx.databases[lo].write().unwrap();
+    let mut db_hi = server_ctx.databases[hi].write().unwrap();
 
-    std::mem::swap(&mut *db1, &mut *db2);
+    std::mem::swap(&mut *db_lo, &mut *db_hi);
+    server_ctx.db_epoch.fetch_add(1, Ordering::Release);
 
     Resp::SimpleString(Bytes::from("OK"))
 }
 
+/// Position of `item` in SCAN order (64-bit FNV-1a of its bytes). Cursors
+/// name a position in this order rather than an index into a snapshot, so
+/// deleting elements between calls cannot move an unvisited one behind the
+/// cursor.
+fn scan_position(item: &[u8]) -> u64 {
+    item.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Selects the batch of a SCAN-family iteration starting at `cursor`: up to
+/// `count` items (more only to keep equal positions together) at or past
+/// the cursor, plus the cursor for the next call, 0 once the walk is done.
+/// Anything present for the whole walk is returned at least once.
+pub(crate) fn scan_batch<'a>(
+    items: impl Iterator<Item = &'a Bytes>,
+    cursor: u64,
+    count: usize,
+) -> (Vec<&'a Bytes>, u64) {
+    let mut pending: Vec<(u64, &Bytes)> = items
+        .map(|item| (scan_position(item), item))
+        .filter(|(pos, _)| *pos >= cursor)
+        .collect();
+    pending.sort_unstable();
+
+    let mut end = count.max(1).min(pending.len());
+    while end < pending.len() && pending[end].0 == pending[end - 1].0 {
+        end += 1;
+    }
+    let next_cursor = pending.get(end).map_or(0, |(pos, _)| *pos);
+    let batch = pending[..end].iter().map(|(_, item)| *item).collect();
+    (batch, next_cursor)
+}
+
 pub fn scan(items: &[Resp], db: &Db) -> Resp {
     if items.len() < 2 {
         return Resp::StaticError("ERR wrong number of arguments for 'SCAN'");
@@ -849,7 +1035,7 @@ pub fn scan(items: &[Resp], db: &Db) -> Resp {
         Ok(s) => s,
         Err(_) => return Resp::StaticError("ERR invalid cursor"),
     };
-    let cursor: usize = match cursor_str.parse() {
+    let cursor: u64 = match cursor_str.parse() {
         Ok(i) => i,
         Err(_) => return Resp::StaticError("ERR invalid cursor"),
     };
@@ -924,22 +1110,11 @@ pub fn scan(items: &[Resp], db: &Db) -> Resp {
         }
     }
 
-    let mut all_keys: Vec<bytes::Bytes> = db.iter().map(|r| r.key().clone()).collect();
-    all_keys.sort();
-
-    let total_len = all_keys.len();
-    if cursor >= total_len {
-        return Resp::Array(Some(vec![
-            Resp::BulkString(Some(Bytes::from("0"))),
-            Resp::Array(Some(vec![])),
-        ]));
-    }
-
-    let end = std::cmp::min(cursor + count, total_len);
-    let next_cursor = if end == total_len { 0 } else { end };
+    let all_keys: Vec<bytes::Bytes> = db.iter().map(|r| r.key().clone()).collect();
+    let (batch, next_cursor) = scan_batch(all_keys.iter(), cursor, count);
 
     let mut result_keys = Vec::new();
-    for key in &all_keys[cursor..end] {
+    for key in batch {
         if let Some(pattern) = match_pattern_str {
             if !match_pattern(pattern, key) {
                 continue;
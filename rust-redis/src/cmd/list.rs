@@ -7,114 +7,101 @@ use tokio::time::timeout;
 
 use std::sync::atomic::Ordering;
 
-pub fn lpush(
-    items: &[Resp],
+/// Hands `val` to the oldest waiter blocked on `key`, returning false when
+/// nobody takes it and it belongs in the list.
+///
+/// A waiter is served at most one element. Its channel has a single slot, so
+/// `Full` means it already got an element through another key (or the same
+/// key given twice) and is no longer waiting, and `Closed` means it timed out
+/// or went away. Both are dropped and the next waiter is tried, so a live,
+/// unserved waiter is never skipped.
+fn hand_off_to_waiter(server_ctx: &ServerContext, db_index: usize, key: &[u8], val: &[u8]) -> bool {
+    let map_key = (db_index, key.to_vec());
+    loop {
+        // Pop under the shard lock but send outside it
+        let sender = match server_ctx.blocking_waiters.get_mut(&map_key) {
+            Some(mut waiters) => waiters.pop_front(),
+            None => None,
+        };
+        let Some(sender) = sender else {
+            return false;
+        };
+        if sender.try_send((key.to_vec(), val.to_vec())).is_ok() {
+            return true;
+        }
+    }
+}
+
+/// An element a pusher handed to a blocked pop that had stopped waiting.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Reclaimed {
+    /// The waiter still serves it.
+    Serve(Vec<u8>, Vec<u8>),
+    /// It went back onto its list, replayed by the push given.
+    Requeued(bytes::Bytes, Resp),
+}
+
+/// Closes a blocked pop's channel once it stops waiting without a reply. A
+/// pusher may have handed over an element between the timeout firing and
+/// the close; it is returned so the caller still serves it, unless the
+/// client is going away. Then it goes to the next waiter, or back to the
+/// `from` end of its list with the push to propagate, since the pusher left
+/// it out of its own log.
+///
+/// The pusher checked the key's type, so if it now holds another type the
+/// list was deleted or overwritten since. The element would have gone with
+/// it had it reached the list, so it is dropped and nothing is propagated.
+pub(crate) fn reclaim_hand_off(
+    rx: &mut tokio::sync::mpsc::Receiver<(Vec<u8>, Vec<u8>)>,
+    serve: bool,
     db: &Db,
-    conn_ctx: &ConnectionContext,
+    db_index: usize,
     server_ctx: &ServerContext,
-) -> Resp {
-    if items.len() < 3 {
-        return Resp::Error("ERR wrong number of arguments for 'LPUSH'".to_string());
+    from: PopDirection,
+) -> Option<Reclaimed> {
+    rx.close();
+    let (key, val) = rx.try_recv().ok()?;
+    if serve {
+        return Some(Reclaimed::Serve(key, val));
     }
-    let key = match &items[1] {
-        Resp::BulkString(Some(b)) => b.clone(),
-        Resp::SimpleString(s) => s.clone(),
-        _ => return Resp::Error("ERR invalid key".to_string()),
+    if hand_off_to_waiter(server_ctx, db_index, &key, &val) {
+        return None;
+    }
+    let (key, val) = (bytes::Bytes::from(key), bytes::Bytes::from(val));
+    let mut entry = db
+        .entry(key.clone())
+        .or_insert_with(|| Entry::new(Value::List(VecDeque::new()), None));
+    if entry.is_expired() {
+        *entry = Entry::new(Value::List(VecDeque::new()), None);
+    }
+    let Value::List(list) = &mut entry.value else {
+        return None;
     };
-
-    let mut count = 0;
-    for i in 2..items.len() {
-        let val = match &items[i] {
-            Resp::BulkString(Some(b)) => b.clone(),
-            Resp::SimpleString(s) => s.clone(),
-            _ => return Resp::Error("ERR invalid value".to_string()),
-        };
-
-        // Check for blocking waiters
-        let mut handled = false;
-        let map_key = (conn_ctx.db_index, key.to_vec());
-
-        // We need to loop because the first waiter might be dead (dropped receiver)
-        loop {
-            // Scope the lock
-            let mut sender_opt = None;
-            if let Some(mut waiters) = server_ctx.blocking_waiters.get_mut(&map_key) {
-                if let Some(sender) = waiters.pop_front() {
-                    sender_opt = Some(sender);
-                }
-            }
-
-            if let Some(sender) = sender_opt {
-                // Try to send to the waiter
-                // We send (key, value)
-                // Use try_send for synchronous sending
-                match sender.try_send((key.to_vec(), val.to_vec())) {
-                    Ok(_) => {
-                        handled = true;
-                        break;
-                    }
-                    Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => {
-                        // Channel full, receiver not ready? Should not happen with size 1 if receiver is waiting.
-                        // But if it happens, we treat it as not handled by this waiter?
-                        // Or we can't block. So we assume this waiter is busy and try next?
-                        // But strictly BLPOP waiters should be ready.
-                        // If full, maybe another push filled it?
-                        // If so, this waiter is effectively "served" by another push.
-                        // So we should try next waiter.
-                        continue;
-                    }
-                    Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => {
-                        // Receiver dropped, try next waiter
-                        continue;
-                    }
-                }
-            } else {
-                // No more waiters
-                break;
-            }
+    let push = match from {
+        PopDirection::Left => {
+            list.push_front(val.clone());
+            "LPUSH"
         }
-
-        if !handled {
-            let mut entry = db
-                .entry(key.clone())
-                .or_insert_with(|| Entry::new(Value::List(VecDeque::new()), None));
-
-            if entry.is_expired() {
-                entry.value = Value::List(VecDeque::new());
-                entry.expires_at = None;
-            }
-
-            if let Value::List(list) = &mut entry.value {
-                list.push_front(val);
-                count = list.len();
-            } else {
-                return Resp::Error(
-                    "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-                );
-            }
-        } else {
-            // Value was sent to a waiter, so list length might not increase?
-            // Redis says: "The command returns the length of the list after the push operations."
-            // If a value is delivered to a waiter, it is effectively pushed and then popped.
-            // So the length is the current length.
-            // But if the list was empty and we sent to waiter, length is 0?
-            // Redis docs: "RPUSH mylist a b c" -> returns 3.
-            // If "BLPOP mylist 0" is waiting.
-            // "RPUSH mylist a" -> returns 1? Or 0?
-            // Redis `LPUSH` returns the length of the list *after* the push.
-            // If `BLPOP` consumes it, the list is empty (len 0).
-            // Let's verify standard Redis behavior if possible.
-            // Assuming 0 if consumed.
-            if let Some(entry) = db.get(&key) {
-                if let Value::List(list) = &entry.value {
-                    count = list.len();
-                }
-            } else {
-                count = 0;
-            }
+        PopDirection::Right => {
+            list.push_back(val.clone());
+            "RPUSH"
         }
-    }
-    Resp::Integer(count as i64)
+    };
+    let log = Resp::Array(Some(vec![
+        Resp::BulkString(Some(bytes::Bytes::from_static(push.as_bytes()))),
+        Resp::BulkString(Some(key.clone())),
+        Resp::BulkString(Some(val)),
+    ]));
+    Some(Reclaimed::Requeued(key, log))
+}
+
+pub fn lpush(
+    items: &[Resp],
+    db: &Db,
+    conn_ctx: &ConnectionContext,
+    server_ctx: &ServerContext,
+) -> (Resp, Option<Resp>) {
+    push_generic(items, true, "LPUSH", db, conn_ctx, server_ctx)
 }
 
 pub fn rpush(
@@ -122,124 +109,116 @@ pub fn rpush(
     db: &Db,
     conn_ctx: &ConnectionContext,
     server_ctx: &ServerContext,
-) -> Resp {
+) -> (Resp, Option<Resp>) {
+    push_generic(items, false, "RPUSH", db, conn_ctx, server_ctx)
+}
+
+/// Shared body of LPUSH and RPUSH. Each value goes to a blocked client if
+/// one is waiting on the key, and onto the list otherwise. The reply is the
+/// length of the list once every value is placed: values handed to waiters
+/// pass through without ever being counted. Likewise only the values that
+/// reached the list are propagated; the waiters log what they took from it.
+fn push_generic(
+    items: &[Resp],
+    front: bool,
+    name: &str,
+    db: &Db,
+    conn_ctx: &ConnectionContext,
+    server_ctx: &ServerContext,
+) -> (Resp, Option<Resp>) {
     if items.len() < 3 {
-        return Resp::Error("ERR wrong number of arguments for 'RPUSH'".to_string());
+        return (
+            Resp::Error(format!("ERR wrong number of arguments for '{}'", name)),
+            None,
+        );
     }
     let key = match &items[1] {
         Resp::BulkString(Some(b)) => b.clone(),
         Resp::SimpleString(s) => s.clone(),
-        _ => return Resp::Error("ERR invalid key".to_string()),
+        _ => return (Resp::Error("ERR invalid key".to_string()), None),
     };
+    let mut values = Vec::with_capacity(items.len() - 2);
+    for item in &items[2..] {
+        match item {
+            Resp::BulkString(Some(b)) | Resp::SimpleString(b) => values.push(b.clone()),
+            _ => return (Resp::Error("ERR invalid value".to_string()), None),
+        }
+    }
 
-    let mut count = 0;
-    for i in 2..items.len() {
-        let val = match &items[i] {
-            Resp::BulkString(Some(b)) => b.clone(),
-            Resp::SimpleString(s) => s.clone(),
-            _ => return Resp::Error("ERR invalid value".to_string()),
-        };
-
-        // Check for blocking waiters
-        let mut handled = false;
-        let map_key = (conn_ctx.db_index, key.to_vec());
-
-        // We need to loop because the first waiter might be dead (dropped receiver)
-        loop {
-            // Scope the lock
-            let mut sender_opt = None;
-            if let Some(mut waiters) = server_ctx.blocking_waiters.get_mut(&map_key) {
-                if let Some(sender) = waiters.pop_front() {
-                    sender_opt = Some(sender);
-                }
-            }
+    // Check the type before any value is handed to a waiter
+    if let Some(entry) = db.get(&key)
+        && !entry.is_expired()
+        && !matches!(entry.value, Value::List(_))
+    {
+        return (
+            Resp::Error(
+                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+            ),
+            None,
+        );
+    }
 
-            if let Some(sender) = sender_opt {
-                // Try to send to the waiter
-                // We send (key, value)
-                // Use try_send for synchronous sending
-                match sender.try_send((key.to_vec(), val.to_vec())) {
-                    Ok(_) => {
-                        handled = true;
-                        break;
-                    }
-                    Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => {
-                        continue;
-                    }
-                    Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => {
-                        continue;
-                    }
-                }
-            } else {
-                // No more waiters
-                break;
-            }
+    let mut len = None;
+    let mut log = vec![items[0].clone(), items[1].clone()];
+    for val in values {
+        if hand_off_to_waiter(server_ctx, conn_ctx.db_index, &key, &val) {
+            continue;
         }
+        log.push(Resp::BulkString(Some(val.clone())));
 
-        if !handled {
-            let mut entry = db
-                .entry(key.clone())
-                .or_insert_with(|| Entry::new(Value::List(VecDeque::new()), None));
+        let mut entry = db
+            .entry(key.clone())
+            .or_insert_with(|| Entry::new(Value::List(VecDeque::new()), None));
 
-            if entry.is_expired() {
-                entry.value = Value::List(VecDeque::new());
-                entry.expires_at = None;
-            }
+        if entry.is_expired() {
+            entry.value = Value::List(VecDeque::new());
+            entry.expires_at = None;
+        }
 
-            if let Value::List(list) = &mut entry.value {
-                list.push_back(val);
-                count = list.len();
+        if let Value::List(list) = &mut entry.value {
+            if front {
+                list.push_front(val);
             } else {
-                return Resp::Error(
-                    "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-                );
+                list.push_back(val);
             }
+            len = Some(list.len());
         } else {
-            if let Some(entry) = db.get(&key) {
-                if let Value::List(list) = &entry.value {
-                    count = list.len();
-                }
-            } else {
-                count = 0;
-            }
+            return (
+                Resp::Error(
+                    "WRONGTYPE Operation against a key holding the wrong kind of value"
+                        .to_string(),
+                ),
+                None,
+            );
         }
     }
-    Resp::Integer(count as i64)
+
+    // Every value went to a waiter, so the list is as it was
+    let len = len.unwrap_or_else(|| match db.get(&key) {
+        Some(entry) if !entry.is_expired() => match &entry.value {
+            Value::List(list) => list.len(),
+            _ => 0,
+        },
+        _ => 0,
+    });
+    let log = (log.len() > 2).then(|| Resp::Array(Some(log)));
+    (Resp::Integer(len as i64), log)
 }
 
 pub fn lpop(items: &[Resp], db: &Db) -> Resp {
-    if items.len() != 2 {
-        return Resp::Error("ERR wrong number of arguments for 'LPOP'".to_string());
-    }
-    let key = match &items[1] {
-        Resp::BulkString(Some(b)) => b.clone(),
-        Resp::SimpleString(s) => s.clone(),
-        _ => return Resp::Error("ERR invalid key".to_string()),
-    };
-
-    if let Some(mut entry) = db.get_mut(&key) {
-        if entry.is_expired() {
-            drop(entry);
-            db.remove(&key);
-            return Resp::BulkString(None);
-        }
-        match &mut entry.value {
-            Value::List(list) => match list.pop_front() {
-                Some(v) => Resp::BulkString(Some(v)),
-                None => Resp::BulkString(None),
-            },
-            _ => Resp::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            ),
-        }
-    } else {
-        Resp::BulkString(None)
-    }
+    pop_generic(items, db, PopDirection::Left, "LPOP")
 }
 
 pub fn rpop(items: &[Resp], db: &Db) -> Resp {
-    if items.len() != 2 {
-        return Resp::Error("ERR wrong number of arguments for 'RPOP'".to_string());
+    pop_generic(items, db, PopDirection::Right, "RPOP")
+}
+
+/// `LPOP`/`RPOP key [count]`. Without a count the reply is a single bulk
+/// string; with one it is an array of up to `count` elements, or a nil array
+/// when the key does not exist.
+fn pop_generic(items: &[Resp], db: &Db, direction: PopDirection, name: &str) -> Resp {
+    if items.len() != 2 && items.len() != 3 {
+        return Resp::Error(format!("ERR wrong number of arguments for '{}'", name));
     }
     let key = match &items[1] {
         Resp::BulkString(Some(b)) => b.clone(),
@@ -247,23 +226,50 @@ pub fn rpop(items: &[Resp], db: &Db) -> Resp {
         _ => return Resp::Error("ERR invalid key".to_string()),
     };
 
-    if let Some(mut entry) = db.get_mut(&key) {
-        if entry.is_expired() {
-            drop(entry);
-            db.remove(&key);
-            return Resp::BulkString(None);
+    let count = match items.get(2) {
+        None => None,
+        Some(Resp::BulkString(Some(b))) | Some(Resp::SimpleString(b)) => {
+            match String::from_utf8_lossy(b).parse::<i64>() {
+                Ok(n) if n >= 0 => Some(n as usize),
+                Ok(_) => {
+                    return Resp::Error("ERR value is out of range, must be positive".to_string());
+                }
+                Err(_) => {
+                    return Resp::Error("ERR value is not an integer or out of range".to_string());
+                }
+            }
         }
-        match &mut entry.value {
-            Value::List(list) => match list.pop_back() {
-                Some(v) => Resp::BulkString(Some(v)),
-                None => Resp::BulkString(None),
-            },
-            _ => Resp::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            ),
+        Some(_) => {
+            return Resp::Error("ERR value is not an integer or out of range".to_string());
         }
-    } else {
-        Resp::BulkString(None)
+    };
+
+    // A zero count still reports a missing key or a type mismatch
+    if count == Some(0) {
+        return match db.get(&key) {
+            Some(entry) if !entry.is_expired() => match &entry.value {
+                Value::List(_) => Resp::Array(Some(Vec::new())),
+                _ => Resp::Error(
+                    "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+                ),
+            },
+            _ => Resp::Array(None),
+        };
+    }
+
+    match pop_many(db, &key, direction, count.unwrap_or(1)) {
+        Ok(Some(values)) => match count {
+            Some(_) => Resp::Array(Some(
+                values
+                    .into_iter()
+                    .map(|v| Resp::BulkString(Some(v)))
+                    .collect(),
+            )),
+            None => Resp::BulkString(values.into_iter().next()),
+        },
+        Ok(None) if count.is_some() => Resp::Array(None),
+        Ok(None) => Resp::BulkString(None),
+        Err(e) => e,
     }
 }
 
@@ -363,67 +369,58 @@ pub fn lrange(items: &[Resp], db: &Db) -> Resp {
 }
 
 #[derive(Copy, Clone)]
-enum PopDirection {
+pub(crate) enum PopDirection {
     Left,
     Right,
 }
 
-async fn blocking_pop_generic(
-    items: &[Resp],
-    db: &Db,
-    conn_ctx: &ConnectionContext,
-    server_ctx: &ServerContext,
-    direction: PopDirection,
-) -> Resp {
-    if items.len() < 3 {
-        let cmd = match direction {
-            PopDirection::Left => "BLPOP",
-            PopDirection::Right => "BRPOP",
-        };
-        return Resp::Error(format!("ERR wrong number of arguments for '{}'", cmd));
-    }
-
-    let timeout_arg = match &items[items.len() - 1] {
-        Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).parse::<f64>(),
-        Resp::SimpleString(s) => String::from_utf8_lossy(s).parse::<f64>(),
-        _ => return Resp::Error("ERR timeout is not a float or out of range".to_string()),
-    };
+/// What a blocking pop served: the reply, and the pop to propagate for the
+/// elements it took from the list itself. Elements handed over by a pusher
+/// never reach the list (nor the pusher's log), so they are not part of it.
+struct Served {
+    key: bytes::Bytes,
+    reply: Resp,
+    log: Option<Resp>,
+}
 
-    let timeout_secs = match timeout_arg {
-        Ok(v) => v,
-        Err(_) => return Resp::Error("ERR timeout is not a float or out of range".to_string()),
+/// `LPOP`/`RPOP key count`, replaying `count` pops from one end of a list.
+fn pop_log(key: &bytes::Bytes, direction: PopDirection, count: usize) -> Resp {
+    let name = match direction {
+        PopDirection::Left => "LPOP",
+        PopDirection::Right => "RPOP",
     };
+    Resp::Array(Some(vec![
+        Resp::BulkString(Some(bytes::Bytes::from_static(name.as_bytes()))),
+        Resp::BulkString(Some(key.clone())),
+        Resp::BulkString(Some(bytes::Bytes::from(count.to_string()))),
+    ]))
+}
 
-    let mut keys = Vec::new();
+/// Parses a blocking command's timeout in seconds.
+fn parse_timeout(arg: &Resp) -> Result<f64, Resp> {
+    crate::resp::as_bytes(arg)
+        .and_then(|b| String::from_utf8_lossy(b).parse::<f64>().ok())
+        .ok_or_else(|| Resp::Error("ERR timeout is not a float or out of range".to_string()))
+}
 
+/// Runs a blocking pop over `keys`. `pop` is first tried on each key with
+/// no element in hand; if every list is empty, the client waits until a
+/// pusher hands it an element, the timeout passes or the server shuts down,
+/// and `pop` is called once more with the element it was handed. `Ok(None)`
+/// means nothing was served.
+async fn blocking_pop(
+    keys: &[bytes::Bytes],
+    timeout_secs: f64,
+    direction: PopDirection,
+    db: &Db,
+    conn_ctx: &ConnectionContext,
+    server_ctx: &ServerContext,
+    mut pop: impl FnMut(&bytes::Bytes, Option<bytes::Bytes>) -> Result<Option<Served>, Resp>,
+) -> Result<Option<Served>, Resp> {
     // 1. Try to serve from existing lists immediately
-    for i in 1..items.len() - 1 {
-        let key = match &items[i] {
-            Resp::BulkString(Some(b)) => b.clone(),
-            Resp::SimpleString(s) => s.clone(),
-            _ => continue,
-        };
-        keys.push(key.clone());
-
-        if let Some(mut entry) = db.get_mut(&key) {
-            if entry.is_expired() {
-                drop(entry);
-                db.remove(&key);
-                continue;
-            }
-            if let Value::List(list) = &mut entry.value {
-                let val_opt = match direction {
-                    PopDirection::Left => list.pop_front(),
-                    PopDirection::Right => list.pop_back(),
-                };
-                if let Some(val) = val_opt {
-                    // Found item, return immediately
-                    return Resp::Array(Some(vec![
-                        Resp::BulkString(Some(key)),
-                        Resp::BulkString(Some(val)),
-                    ]));
-                }
-            }
+    for key in keys {
+        if let Some(served) = pop(key, None)? {
+            return Ok(Some(served));
         }
     }
 
@@ -431,7 +428,7 @@ async fn blocking_pop_generic(
     let (tx, mut rx) = tokio::sync::mpsc::channel::<(Vec<u8>, Vec<u8>)>(1);
 
     // Register waiter for all keys
-    for key in &keys {
+    for key in keys {
         let map_key = (conn_ctx.db_index, key.to_vec());
         let mut queue = server_ctx
             .blocking_waiters
@@ -441,9 +438,7 @@ async fn blocking_pop_generic(
     }
 
     // Wait
-    server_ctx
-        .clients_ctx.blocked_client_count
-        .fetch_add(1, Ordering::Relaxed);
+    server_ctx.clients_ctx.block_client(conn_ctx.id, timeout_secs > 0.0);
 
     let (_shutdown_tx, mut shutdown_rx) = if let Some(rx) = &conn_ctx.shutdown {
         (None, rx.clone())
@@ -455,38 +450,103 @@ async fn blocking_pop_generic(
     let result = if timeout_secs > 0.0 {
         let duration = Duration::from_secs_f64(timeout_secs);
         tokio::select! {
-            res = timeout(duration, rx.recv()) => {
-                match res {
-                    Ok(Some((key, val))) => Some((key, val)),
-                    Ok(None) => None,
-                    Err(_) => None, // Timeout
-                }
-            }
-            _ = shutdown_rx.changed() => {
-                None
-            }
+            res = timeout(duration, rx.recv()) => res.ok().flatten(),
+            _ = shutdown_rx.changed() => None,
         }
     } else {
         // Infinite wait
         tokio::select! {
-            res = rx.recv() => {
-                res
-            }
-            _ = shutdown_rx.changed() => {
-                None
-            }
+            res = rx.recv() => res,
+            _ = shutdown_rx.changed() => None,
         }
     };
-    server_ctx
-        .clients_ctx.blocked_client_count
-        .fetch_sub(1, Ordering::Relaxed);
+    let result = match result {
+        Some(handed) => Some(Reclaimed::Serve(handed.0, handed.1)),
+        None => {
+            let serve = !*shutdown_rx.borrow();
+            reclaim_hand_off(&mut rx, serve, db, conn_ctx.db_index, server_ctx, direction)
+        }
+    };
+    server_ctx.clients_ctx.unblock_client(conn_ctx.id, timeout_secs > 0.0);
 
     match result {
-        Some((key, val)) => Resp::Array(Some(vec![
-            Resp::BulkString(Some(bytes::Bytes::from(key))),
-            Resp::BulkString(Some(bytes::Bytes::from(val))),
-        ])),
-        None => Resp::BulkString(None), // Timeout
+        Some(Reclaimed::Serve(key, val)) => {
+            pop(&bytes::Bytes::from(key), Some(bytes::Bytes::from(val)))
+        }
+        // The client is going away, so the reply is never written
+        Some(Reclaimed::Requeued(key, log)) => Ok(Some(Served {
+            key,
+            reply: Resp::Null,
+            log: Some(log),
+        })),
+        None => Ok(None),
+    }
+}
+
+/// Shared body of BLPOP and BRPOP.
+async fn blocking_pop_generic(
+    items: &[Resp],
+    db: &Db,
+    conn_ctx: &ConnectionContext,
+    server_ctx: &ServerContext,
+    direction: PopDirection,
+) -> (Resp, Option<Resp>) {
+    if items.len() < 3 {
+        let cmd = match direction {
+            PopDirection::Left => "BLPOP",
+            PopDirection::Right => "BRPOP",
+        };
+        return (
+            Resp::Error(format!("ERR wrong number of arguments for '{}'", cmd)),
+            None,
+        );
+    }
+
+    let timeout_secs = match parse_timeout(&items[items.len() - 1]) {
+        Ok(v) => v,
+        Err(e) => return (e, None),
+    };
+
+    let keys: Vec<bytes::Bytes> = items[1..items.len() - 1]
+        .iter()
+        .filter_map(|item| match item {
+            Resp::BulkString(Some(b)) | Resp::SimpleString(b) => Some(b.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let served = blocking_pop(
+        &keys,
+        timeout_secs,
+        direction,
+        db,
+        conn_ctx,
+        server_ctx,
+        |key, handed| {
+            let (val, log) = match handed {
+                Some(val) => (val, None),
+                // A key of another type is passed over rather than an error
+                None => match pop_many(db, key, direction, 1) {
+                    Ok(Some(mut values)) => (values.remove(0), Some(pop_log(key, direction, 1))),
+                    _ => return Ok(None),
+                },
+            };
+            Ok(Some(Served {
+                key: key.clone(),
+                reply: Resp::Array(Some(vec![
+                    Resp::BulkString(Some(key.clone())),
+                    Resp::BulkString(Some(val)),
+                ])),
+                log,
+            }))
+        },
+    )
+    .await;
+
+    match served {
+        Ok(Some(served)) => (served.reply, served.log),
+        Ok(None) => (Resp::BulkString(None), None), // Timeout
+        Err(e) => (e, None),
     }
 }
 
@@ -495,7 +555,7 @@ pub async fn blpop(
     db: &Db,
     conn_ctx: &ConnectionContext,
     server_ctx: &ServerContext,
-) -> Resp {
+) -> (Resp, Option<Resp>) {
     blocking_pop_generic(items, db, conn_ctx, server_ctx, PopDirection::Left).await
 }
 
@@ -504,7 +564,7 @@ pub async fn brpop(
     db: &Db,
     conn_ctx: &ConnectionContext,
     server_ctx: &ServerContext,
-) -> Resp {
+) -> (Resp, Option<Resp>) {
     blocking_pop_generic(items, db, conn_ctx, server_ctx, PopDirection::Right).await
 }
 
@@ -633,6 +693,10 @@ fn lmove_execute(
                             };
                             if let Some(v) = v {
                                 val = Some(v);
+                                if list.is_empty() {
+                                    drop(entry);
+                                    db.remove(&src);
+                                }
                             } else {
                                 return Ok(None);
                             }
@@ -746,91 +810,319 @@ pub async fn blmove(
     db: &Db,
     conn_ctx: &ConnectionContext,
     server_ctx: &ServerContext,
-) -> Resp {
+) -> (Resp, Option<Resp>) {
     if items.len() != 6 {
-        return Resp::Error("ERR wrong number of arguments for 'BLMOVE'".to_string());
+        return (
+            Resp::Error("ERR wrong number of arguments for 'BLMOVE'".to_string()),
+            None,
+        );
     }
 
     let src_key = match &items[1] {
         Resp::BulkString(Some(b)) => b.clone(),
         Resp::SimpleString(s) => s.clone(),
-        _ => return Resp::Error("ERR invalid key".to_string()),
+        _ => return (Resp::Error("ERR invalid key".to_string()), None),
     };
 
     let dst_key = match &items[2] {
         Resp::BulkString(Some(b)) => b.clone(),
         Resp::SimpleString(s) => s.clone(),
-        _ => return Resp::Error("ERR invalid key".to_string()),
+        _ => return (Resp::Error("ERR invalid key".to_string()), None),
     };
 
     let where_from = match parse_direction(&items[3]) {
         Ok(d) => d,
-        Err(e) => return e,
+        Err(e) => return (e, None),
     };
 
     let where_to = match parse_direction(&items[4]) {
         Ok(d) => d,
-        Err(e) => return e,
+        Err(e) => return (e, None),
     };
 
-    let timeout_arg = match &items[5] {
-        Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).parse::<f64>(),
-        Resp::SimpleString(s) => String::from_utf8_lossy(s).parse::<f64>(),
-        _ => return Resp::Error("ERR timeout is not a float or out of range".to_string()),
+    let timeout_secs = match parse_timeout(&items[5]) {
+        Ok(v) => v,
+        Err(e) => return (e, None),
     };
 
-    let timeout_secs = match timeout_arg {
-        Ok(v) => v,
-        Err(_) => return Resp::Error("ERR timeout is not a float or out of range".to_string()),
+    let served = blocking_pop(
+        std::slice::from_ref(&src_key),
+        timeout_secs,
+        where_from,
+        db,
+        conn_ctx,
+        server_ctx,
+        |key, handed| match handed {
+            // Moved within the dataset: replays as the equivalent LMOVE
+            None => Ok(lmove_execute(db, key, &dst_key, where_from, where_to)?.map(|v| {
+                let mut log = items[..5].to_vec();
+                log[0] = Resp::BulkString(Some(bytes::Bytes::from_static(b"LMOVE")));
+                Served {
+                    key: key.clone(),
+                    reply: Resp::BulkString(Some(v)),
+                    log: Some(Resp::Array(Some(log))),
+                }
+            })),
+            // A handed-over element never reached the source list, so only
+            // its arrival at the destination is propagated
+            Some(v) => {
+                blmove_push_to_dest(db, &dst_key, where_to, v.clone())?;
+                let push = match where_to {
+                    PopDirection::Left => "LPUSH",
+                    PopDirection::Right => "RPUSH",
+                };
+                Ok(Some(Served {
+                    key: key.clone(),
+                    reply: Resp::BulkString(Some(v.clone())),
+                    log: Some(Resp::Array(Some(vec![
+                        Resp::BulkString(Some(bytes::Bytes::from_static(push.as_bytes()))),
+                        Resp::BulkString(Some(dst_key.clone())),
+                        Resp::BulkString(Some(v)),
+                    ]))),
+                }))
+            }
+        },
+    )
+    .await;
+
+    match served {
+        Ok(Some(served)) => (served.reply, served.log),
+        Ok(None) => (Resp::BulkString(None), None),
+        Err(e) => (e, None),
+    }
+}
+
+/// Parses the `numkeys key [key ...] LEFT|RIGHT [COUNT count]` tail shared by
+/// LMPOP and BLMPOP, starting at the numkeys argument.
+fn parse_mpop_args(
+    items: &[Resp],
+    numkeys_idx: usize,
+) -> Result<(Vec<bytes::Bytes>, PopDirection, usize), Resp> {
+    let numkeys = match items.get(numkeys_idx) {
+        Some(Resp::BulkString(Some(b))) | Some(Resp::SimpleString(b)) => {
+            String::from_utf8_lossy(b).parse::<usize>().unwrap_or(0)
+        }
+        _ => 0,
     };
+    if numkeys == 0 {
+        return Err(Resp::Error(
+            "ERR numkeys should be greater than 0".to_string(),
+        ));
+    }
 
-    match lmove_execute(db, &src_key, &dst_key, where_from, where_to) {
-        Ok(Some(v)) => return Resp::BulkString(Some(v)),
-        Ok(None) => {}
-        Err(e) => return e,
+    let where_idx = numkeys_idx + 1 + numkeys;
+    if where_idx >= items.len() {
+        return Err(Resp::Error("ERR syntax error".to_string()));
     }
 
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<(Vec<u8>, Vec<u8>)>(1);
+    let mut keys = Vec::with_capacity(numkeys);
+    for item in &items[numkeys_idx + 1..where_idx] {
+        match item {
+            Resp::BulkString(Some(b)) => keys.push(b.clone()),
+            Resp::SimpleString(s) => keys.push(s.clone()),
+            _ => return Err(Resp::Error("ERR invalid key".to_string())),
+        }
+    }
 
-    let map_key = (conn_ctx.db_index, src_key.to_vec());
-    let mut queue = server_ctx
-        .blocking_waiters
-        .entry(map_key)
-        .or_insert_with(VecDeque::new);
-    queue.push_back(tx);
+    let direction = parse_direction(&items[where_idx])?;
 
-    server_ctx
-        .clients_ctx.blocked_client_count
-        .fetch_add(1, Ordering::Relaxed);
-    let result = if timeout_secs > 0.0 {
-        let duration = Duration::from_secs_f64(timeout_secs);
-        match timeout(duration, rx.recv()).await {
-            Ok(Some((_key, val))) => Some(val),
-            Ok(None) => None,
-            Err(_) => None,
+    let mut count = None;
+    let mut i = where_idx + 1;
+    while i < items.len() {
+        let opt = match &items[i] {
+            Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).to_ascii_uppercase(),
+            Resp::SimpleString(s) => String::from_utf8_lossy(s).to_ascii_uppercase(),
+            _ => return Err(Resp::Error("ERR syntax error".to_string())),
+        };
+        if opt != "COUNT" || count.is_some() || i + 1 >= items.len() {
+            return Err(Resp::Error("ERR syntax error".to_string()));
         }
-    } else {
-        match rx.recv().await {
-            Some((_key, val)) => Some(val),
-            None => None,
+        let n = match &items[i + 1] {
+            Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).parse::<usize>().unwrap_or(0),
+            Resp::SimpleString(s) => String::from_utf8_lossy(s).parse::<usize>().unwrap_or(0),
+            _ => 0,
+        };
+        if n == 0 {
+            return Err(Resp::Error(
+                "ERR count should be greater than 0".to_string(),
+            ));
         }
+        count = Some(n);
+        i += 2;
+    }
+
+    Ok((keys, direction, count.unwrap_or(1)))
+}
+
+/// Pops up to `count` elements from one side of the list at `key`. Returns
+/// `Ok(None)` when the key is missing (or expired), and removes the key once
+/// the list is emptied.
+fn pop_many(
+    db: &Db,
+    key: &bytes::Bytes,
+    direction: PopDirection,
+    count: usize,
+) -> Result<Option<Vec<bytes::Bytes>>, Resp> {
+    let Some(mut entry) = db.get_mut(key) else {
+        return Ok(None);
+    };
+    if entry.is_expired() {
+        drop(entry);
+        db.remove(key);
+        return Ok(None);
+    }
+    let Value::List(list) = &mut entry.value else {
+        return Err(Resp::Error(
+            "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+        ));
     };
-    server_ctx
-        .clients_ctx.blocked_client_count
-        .fetch_sub(1, Ordering::Relaxed);
 
-    match result {
-        Some(v) => {
-            let value = bytes::Bytes::from(v);
-            match blmove_push_to_dest(db, &dst_key, where_to, value.clone()) {
-                Ok(()) => Resp::BulkString(Some(value)),
-                Err(e) => e,
+    let mut popped = Vec::with_capacity(count.min(list.len()));
+    while popped.len() < count {
+        let val = match direction {
+            PopDirection::Left => list.pop_front(),
+            PopDirection::Right => list.pop_back(),
+        };
+        match val {
+            Some(v) => popped.push(v),
+            None => break,
+        }
+    }
+    if list.is_empty() {
+        drop(entry);
+        db.remove(key);
+    }
+    if popped.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(popped))
+}
+
+fn mpop_reply(key: bytes::Bytes, values: Vec<bytes::Bytes>) -> Resp {
+    Resp::Array(Some(vec![
+        Resp::BulkString(Some(key)),
+        Resp::Array(Some(
+            values
+                .into_iter()
+                .map(|v| Resp::BulkString(Some(v)))
+                .collect(),
+        )),
+    ]))
+}
+
+/// Pops from the first non-empty list among `keys`, recording the popped key
+/// so only it is treated as modified.
+fn mpop_first_non_empty(
+    db: &Db,
+    keys: &[bytes::Bytes],
+    direction: PopDirection,
+    count: usize,
+    conn_ctx: &mut ConnectionContext,
+) -> Result<Option<Resp>, Resp> {
+    for key in keys {
+        if let Some(values) = pop_many(db, key, direction, count)? {
+            conn_ctx.modified_keys = Some(vec![key.clone()]);
+            return Ok(Some(mpop_reply(key.clone(), values)));
+        }
+    }
+    Ok(None)
+}
+
+pub fn lmpop(items: &[Resp], db: &Db, conn_ctx: &mut ConnectionContext) -> Resp {
+    if items.len() < 4 {
+        return Resp::Error("ERR wrong number of arguments for 'LMPOP'".to_string());
+    }
+
+    let (keys, direction, count) = match parse_mpop_args(items, 1) {
+        Ok(parsed) => parsed,
+        Err(e) => return e,
+    };
+
+    match mpop_first_non_empty(db, &keys, direction, count, conn_ctx) {
+        Ok(Some(reply)) => reply,
+        Ok(None) => {
+            conn_ctx.modified_keys = Some(Vec::new());
+            Resp::Array(None)
+        }
+        Err(e) => e,
+    }
+}
+
+pub async fn blmpop(
+    items: &[Resp],
+    db: &Db,
+    conn_ctx: &mut ConnectionContext,
+    server_ctx: &ServerContext,
+) -> (Resp, Option<Resp>) {
+    if items.len() < 5 {
+        return (
+            Resp::Error("ERR wrong number of arguments for 'BLMPOP'".to_string()),
+            None,
+        );
+    }
+
+    let timeout_secs = match parse_timeout(&items[1]) {
+        Ok(v) if v >= 0.0 => v,
+        _ => {
+            return (
+                Resp::Error("ERR timeout is not a float or out of range".to_string()),
+                None,
+            );
+        }
+    };
+
+    let (keys, direction, count) = match parse_mpop_args(items, 2) {
+        Ok(parsed) => parsed,
+        Err(e) => return (e, None),
+    };
+
+    let served = blocking_pop(
+        &keys,
+        timeout_secs,
+        direction,
+        db,
+        conn_ctx,
+        server_ctx,
+        |key, handed| {
+            // A pusher hands over a single element; the rest of COUNT comes
+            // from the list, and only that part is propagated
+            let mut values: Vec<bytes::Bytes> = handed.into_iter().collect();
+            let from_list = match pop_many(db, key, direction, count - values.len()) {
+                Ok(Some(more)) => more,
+                Ok(None) => Vec::new(),
+                Err(e) if values.is_empty() => return Err(e),
+                Err(_) => Vec::new(),
+            };
+            let log = (!from_list.is_empty()).then(|| pop_log(key, direction, from_list.len()));
+            values.extend(from_list);
+            if values.is_empty() {
+                return Ok(None);
             }
+            Ok(Some(Served {
+                key: key.clone(),
+                reply: mpop_reply(key.clone(), values),
+                log,
+            }))
+        },
+    )
+    .await;
+
+    match served {
+        Ok(Some(served)) => {
+            conn_ctx.modified_keys = Some(vec![served.key]);
+            (served.reply, served.log)
         }
-        None => Resp::BulkString(None),
+        Ok(None) => {
+            conn_ctx.modified_keys = Some(Vec::new());
+            (Resp::Array(None), None)
+        }
+        Err(e) => (e, None),
     }
 }
 
+/// Inserts next to the first occurrence of the pivot. Finding the pivot is a
+/// linear scan, and inserting into the middle of the `VecDeque` shifts the
+/// shorter side, so the cost is O(n) on huge lists.
 pub fn linsert(items: &[Resp], db: &Db) -> Resp {
     if items.len() != 5 {
         return Resp::Error("ERR wrong number of arguments for 'LINSERT'".to_string());
@@ -887,15 +1179,7 @@ pub fn linsert(items: &[Resp], db: &Db) -> Resp {
 
         match &mut entry.value {
             Value::List(list) => {
-                let mut index = None;
-                for (i, val) in list.iter().enumerate() {
-                    if val == &pivot {
-                        index = Some(i);
-                        break;
-                    }
-                }
-
-                if let Some(idx) = index {
+                if let Some(idx) = list.iter().position(|val| val == &pivot) {
                     let insert_at = if before { idx } else { idx + 1 };
                     list.insert(insert_at, element);
                     Resp::Integer(list.len() as i64)
@@ -912,6 +1196,9 @@ pub fn linsert(items: &[Resp], db: &Db) -> Resp {
     }
 }
 
+/// Removes matches of `element`. With a non-zero count the scan starts from
+/// the head (or tail, when negative) and stops as soon as `count` matches are
+/// gone; a count of 0 has to visit the whole list.
 pub fn lrem(items: &[Resp], db: &Db) -> Resp {
     if items.len() != 4 {
         return Resp::Error("ERR wrong number of arguments for 'LREM'".to_string());
@@ -1183,6 +1470,8 @@ pub fn rpushx(items: &[Resp], db: &Db) -> Resp {
     }
 }
 
+/// Scanning is O(n) in the worst case; `MAXLEN` caps the number of elements
+/// compared, and the scan also ends once enough matches are collected.
 pub fn lpos(items: &[Resp], db: &Db) -> Resp {
     if items.len() < 3 {
         return Resp::Error("ERR wrong number of arguments for 'LPOS'".to_string());
@@ -1,6 +1,7 @@
 use crate::cmd::{ServerContext, as_bytes};
 use crate::db::{Db, Value};
 use crate::resp::Resp;
+use crate::stream::PendingEntry;
 use bytes::Bytes;
 use memory_stats::memory_stats;
 use std::sync::atomic::Ordering;
@@ -67,7 +68,7 @@ async fn memory_usage(items: &[Resp], db: &Db) -> Resp {
     }
 }
 
-fn estimate_value_size(val: &Value) -> usize {
+pub fn estimate_value_size(val: &Value) -> usize {
     match val {
         Value::String(b) => b.len(),
         Value::List(l) => {
@@ -89,6 +90,21 @@ fn estimate_value_size(val: &Value) -> usize {
             let count = s.len();
             let mut size = 256; // Base overhead
             size += count * 128; // Estimate 128 bytes per entry (ID + some fields)
+            // Consumer groups can outweigh the entries: every pending entry is
+            // held in the group PEL and again in its consumer's pending set
+            for group in s.groups.values() {
+                size += group.name.len() + 96;
+                size += group
+                    .pel
+                    .values()
+                    .map(|p| std::mem::size_of::<PendingEntry>() + p.owner.len() + 16)
+                    .sum::<usize>();
+                size += group
+                    .consumers
+                    .values()
+                    .map(|c| c.name.len() + 80 + c.pending_ids.len() * 24)
+                    .sum::<usize>();
+            }
             size
         }
         Value::HyperLogLog(_) => 12 * 1024, // HLL is typically 12KB in Redis
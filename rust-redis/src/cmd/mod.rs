@@ -17,12 +17,15 @@ use tracing::error;
 pub mod acl;
 pub mod asking;
 pub mod bitmap;
+pub mod cjson;
 pub mod client;
 pub mod cluster;
 pub mod command;
 pub mod config;
+pub mod debug;
 pub mod dump;
 pub mod evict;
+pub mod expiry;
 pub mod geo;
 pub mod hash;
 pub mod hello;
@@ -71,6 +74,8 @@ impl SlowLogCtx {
 pub struct PubSubCtx {
     pub channels: Arc<DashMap<String, DashMap<u64, tokio::sync::mpsc::Sender<Resp>>>>,
     pub patterns: Arc<DashMap<String, DashMap<u64, tokio::sync::mpsc::Sender<Resp>>>>,
+    /// Sharded channels (SSUBSCRIBE/SPUBLISH), kept apart from `channels`.
+    pub shard_channels: Arc<DashMap<String, DashMap<u64, tokio::sync::mpsc::Sender<Resp>>>>,
 }
 
 impl PubSubCtx {
@@ -78,6 +83,7 @@ impl PubSubCtx {
         Self {
             channels: Arc::new(DashMap::new()),
             patterns: Arc::new(DashMap::new()),
+            shard_channels: Arc::new(DashMap::new()),
         }
     }
 }
@@ -99,15 +105,68 @@ pub struct LatencyEvent {
     pub duration: u64,
 }
 
-fn unwatch_all_keys(conn_ctx: &mut ConnectionContext, server_ctx: &ServerContext) {
-    for (db_idx, keys) in conn_ctx.watched_keys.iter() {
+/// Drop every WATCH registration of a client. `conn_ctx.watched_keys` is the
+/// per-client reverse index of `watched_clients`, so this only touches the
+/// keys the client watched; entries left without watchers are removed.
+pub fn unwatch_all_keys(conn_ctx: &mut ConnectionContext, server_ctx: &ServerContext) {
+    for (db_idx, keys) in conn_ctx.watched_keys.drain() {
         for key in keys {
-            if let Some(mut clients) = server_ctx.clients_ctx.watched_clients.get_mut(&(*db_idx, key.clone())) {
-                clients.remove(&conn_ctx.id);
-            }
+            server_ctx
+                .clients_ctx
+                .watched_clients
+                .remove_if_mut(&(db_idx, key), |_, clients| {
+                    clients.remove(&conn_ctx.id);
+                    clients.is_empty()
+                });
         }
     }
-    conn_ctx.watched_keys.clear();
+}
+
+/// An encoder for a reply of `len` elements (pairs, for a map), when it is
+/// large enough to be encoded as the collection is walked. Scripts convert
+/// replies into Lua values, so they always get them built as `Resp`s.
+pub fn reply_encoder(
+    conn_ctx: &ConnectionContext,
+    kind: crate::resp::ReplyKind,
+    len: usize,
+) -> Option<crate::resp::ReplyEncoder> {
+    let elements = if kind == crate::resp::ReplyKind::Map {
+        len * 2
+    } else {
+        len
+    };
+    (!conn_ctx.is_lua && elements >= crate::resp::STREAM_REPLY_MIN_ELEMENTS)
+        .then(|| crate::resp::ReplyEncoder::new(conn_ctx.protocol, kind, len))
+}
+
+/// Release everything a connection registered in the shared maps. Driven by
+/// the client's own reverse indexes, so the cost is proportional to what this
+/// client subscribed to, watched or tracked rather than to the total number
+/// of keys registered by all clients.
+pub fn release_client(conn_ctx: &mut ConnectionContext, server_ctx: &ServerContext) {
+    for channel in conn_ctx.subscriptions.drain() {
+        if let Some(subscribers) = server_ctx.pubsub.channels.get(&channel) {
+            subscribers.remove(&conn_ctx.id);
+        }
+    }
+    for pattern in conn_ctx.psubscriptions.drain() {
+        if let Some(subscribers) = server_ctx.pubsub.patterns.get(&pattern) {
+            subscribers.remove(&conn_ctx.id);
+        }
+    }
+    for channel in conn_ctx.ssubscriptions.drain() {
+        if let Some(subscribers) = server_ctx.pubsub.shard_channels.get(&channel) {
+            subscribers.remove(&conn_ctx.id);
+        }
+    }
+    unwatch_all_keys(conn_ctx, server_ctx);
+    untrack_all_keys(conn_ctx.id, server_ctx);
+
+    server_ctx.clients_ctx.client_watched_dirty.remove(&conn_ctx.id);
+    server_ctx.clients_ctx.client_count.fetch_sub(1, Ordering::Relaxed);
+    server_ctx.clients_ctx.clients.remove(&conn_ctx.id);
+    server_ctx.clients_ctx.monitors.remove(&conn_ctx.id);
+    replication::unregister_replica(conn_ctx.id, server_ctx);
 }
 
 fn touch_watched_key(key: &[u8], db_idx: usize, server_ctx: &ServerContext) {
@@ -147,6 +206,47 @@ fn touch_watched_key(key: &[u8], db_idx: usize, server_ctx: &ServerContext) {
         // Redis 6.0 tracking usually removes keys after invalidation (except BCAST mode)
         // For simplicity we remove them here.
         server_ctx.clients_ctx.tracking_clients.remove(&map_key);
+        for client_id in ids.iter() {
+            server_ctx
+                .clients_ctx
+                .client_tracked_keys
+                .remove_if_mut(client_id, |_, keys| {
+                    keys.remove(&map_key);
+                    keys.is_empty()
+                });
+        }
+    }
+}
+
+fn track_key(client_id: u64, db_idx: usize, key: &[u8], server_ctx: &ServerContext) {
+    let map_key = (db_idx, key.to_vec());
+    server_ctx
+        .clients_ctx
+        .tracking_clients
+        .entry(map_key.clone())
+        .or_default()
+        .insert(client_id);
+    server_ctx
+        .clients_ctx
+        .client_tracked_keys
+        .entry(client_id)
+        .or_default()
+        .insert(map_key);
+}
+
+/// Drop every tracking registration of a client, walking only the keys it
+/// tracks via `client_tracked_keys` instead of the whole tracking table.
+pub fn untrack_all_keys(client_id: u64, server_ctx: &ServerContext) {
+    if let Some((_, keys)) = server_ctx.clients_ctx.client_tracked_keys.remove(&client_id) {
+        for map_key in keys {
+            server_ctx
+                .clients_ctx
+                .tracking_clients
+                .remove_if_mut(&map_key, |_, clients| {
+                    clients.remove(&client_id);
+                    clients.is_empty()
+                });
+        }
     }
 }
 
@@ -200,11 +300,14 @@ pub struct ConnectionContext {
     pub db_index: usize,
     pub authenticated: bool,
     pub current_username: String,
+    /// Set by CLIENT SETNAME / HELLO SETNAME; empty when unnamed.
+    pub client_name: String,
     pub in_multi: bool,
     pub multi_queue: Vec<Vec<Resp>>,
     pub msg_sender: Option<tokio::sync::mpsc::Sender<Resp>>,
     pub subscriptions: HashSet<String>,
     pub psubscriptions: HashSet<String>,
+    pub ssubscriptions: HashSet<String>,
     pub shutdown: Option<tokio::sync::watch::Receiver<bool>>,
     pub is_lua: bool,
     pub watched_keys: HashMap<usize, HashSet<Vec<u8>>>,
@@ -217,6 +320,16 @@ pub struct ConnectionContext {
     pub is_replica: bool,
     pub replication_state: Arc<std::sync::Mutex<ReplicationState>>,
     pub asking: bool, // ASKING for cluster slot migration
+    pub protocol: u8, // RESP version negotiated via HELLO (2 or 3)
+    /// Keys a write command actually modified, when it reports them itself.
+    /// Overrides the argument keys for watch invalidation and keyspace events.
+    pub modified_keys: Option<Vec<bytes::Bytes>>,
+    /// Number of changes a write command actually made, when its reply does not
+    /// reflect that (e.g. ZADD without CH). Overrides the reply-derived dirty count.
+    pub dirty_changes: Option<u64>,
+    /// The selected db's handle, kept between commands with the index and
+    /// `ServerContext::db_epoch` it was read at.
+    pub db_cache: Option<(usize, u64, Db)>,
 }
 
 impl ConnectionContext {
@@ -232,11 +345,13 @@ impl ConnectionContext {
             db_index: 0,
             authenticated: false,
             current_username: "default".to_string(),
+            client_name: String::new(),
             in_multi: false,
             multi_queue: Vec::new(),
             msg_sender,
             subscriptions: HashSet::new(),
             psubscriptions: HashSet::new(),
+            ssubscriptions: HashSet::new(),
             shutdown,
             is_lua: false,
             watched_keys: HashMap::new(),
@@ -249,6 +364,10 @@ impl ConnectionContext {
             is_replica: false,
             replication_state: Arc::new(std::sync::Mutex::new(ReplicationState::Normal)),
             asking: false,
+            protocol: 2,
+            modified_keys: None,
+            dirty_changes: None,
+            db_cache: None,
         }
     }
 }
@@ -257,10 +376,19 @@ impl ConnectionContext {
 pub struct ClientInfo {
     pub id: u64,
     pub addr: String,
+    /// Local address of the connection (the listener it was accepted on).
+    pub laddr: String,
     pub name: String,
     pub db: usize,
     pub sub: usize,
     pub psub: usize,
+    pub ssub: usize,
+    /// Commands queued in MULTI, or -1 outside a transaction.
+    pub multi: i64,
+    /// Number of keys the client WATCHes.
+    pub watch: usize,
+    /// RESP version negotiated with HELLO.
+    pub resp: u8,
     pub flags: String,
     pub cmd: String,
     pub connect_time: std::time::Instant,
@@ -274,13 +402,24 @@ pub struct NodeConn {
     pub writer: tokio::sync::Mutex<tokio::io::BufWriter<tokio::net::tcp::OwnedWriteHalf>>,
 }
 
+/// Clients blocked in XREAD/XREADGROUP on each (db, key), woken by XADD.
+pub type StreamWaiters = DashMap<(usize, Vec<u8>), Vec<tokio::sync::mpsc::Sender<()>>>;
+
+/// The (db, key) pairs each tracking client has read, by client id.
+pub type ClientTrackedKeys = DashMap<u64, HashSet<(usize, Vec<u8>)>>;
+
 #[derive(Clone)]
 pub struct ServerContext {
     pub databases: Arc<Vec<RwLock<Db>>>,
+    /// Bumped whenever a db index is pointed at a different map (SWAPDB,
+    /// DEBUG RELOAD), so connections drop their cached handles.
+    pub db_epoch: Arc<std::sync::atomic::AtomicU64>,
     pub acl: Arc<ArcSwap<Acl>>,
     pub aof: Option<AofWriter>,
     pub config: Arc<Config>,
     pub script_manager: Arc<ScriptManager>,
+    /// Blocked list pops per (db, key), oldest first. Each holds a one-slot
+    /// channel and is served at most one element; see `list::hand_off_to_waiter`.
     pub blocking_waiters:
         Arc<DashMap<(usize, Vec<u8>), VecDeque<tokio::sync::mpsc::Sender<(Vec<u8>, Vec<u8>)>>>>,
     pub blocking_zset_waiters: Arc<
@@ -289,6 +428,7 @@ pub struct ServerContext {
             VecDeque<(tokio::sync::mpsc::Sender<(Vec<u8>, Vec<u8>, f64)>, bool)>,
         >,
     >,
+    pub stream_waiters: Arc<StreamWaiters>,
     pub pubsub: Arc<PubSubCtx>,
     pub repl: Arc<ReplicationCtx>,
     pub start_time: std::time::Instant,
@@ -324,39 +464,126 @@ impl ClusterCtx {
 #[derive(Clone)]
 pub struct ClientCtx {
     pub client_count: Arc<std::sync::atomic::AtomicU64>,
+    pub maxclients: Arc<std::sync::atomic::AtomicU64>,
+    pub timeout: Arc<std::sync::atomic::AtomicU64>,
+    pub tcp_keepalive: Arc<std::sync::atomic::AtomicU64>,
+    /// Largest bulk string accepted in a client request (proto-max-bulk-len).
+    pub proto_max_bulk_len: Arc<std::sync::atomic::AtomicU64>,
     pub blocked_client_count: Arc<std::sync::atomic::AtomicU64>,
+    pub clients_in_timeout_table: Arc<std::sync::atomic::AtomicU64>,
     pub clients: Arc<DashMap<u64, ClientInfo>>,
     pub monitors: Arc<DashMap<u64, tokio::sync::mpsc::Sender<Resp>>>,
     pub watched_clients: Arc<DashMap<(usize, Vec<u8>), HashSet<u64>>>,
     pub client_watched_dirty: Arc<DashMap<u64, Arc<std::sync::atomic::AtomicBool>>>,
     pub tracking_clients: Arc<DashMap<(usize, Vec<u8>), HashSet<u64>>>,
+    /// Reverse index of `tracking_clients`.
+    pub client_tracked_keys: Arc<ClientTrackedKeys>,
     pub acl_log: Arc<RwLock<VecDeque<AclLogEntry>>>,
     pub latency_events: Arc<DashMap<String, VecDeque<LatencyEvent>>>,
+    /// Traffic totals for INFO stats; the net counters are fed by the socket halves.
+    pub total_connections_received: Arc<std::sync::atomic::AtomicU64>,
+    pub total_commands_processed: Arc<std::sync::atomic::AtomicU64>,
+    pub net_input_bytes: Arc<std::sync::atomic::AtomicU64>,
+    pub net_output_bytes: Arc<std::sync::atomic::AtomicU64>,
+    pub inst_metrics: Arc<std::sync::Mutex<InstantaneousMetrics>>,
 }
 
 impl ClientCtx {
-    pub fn new() -> Self {
+    pub fn new(maxclients: u64, timeout: u64, tcp_keepalive: u64) -> Self {
         Self {
             client_count: Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            maxclients: Arc::new(std::sync::atomic::AtomicU64::new(maxclients)),
+            timeout: Arc::new(std::sync::atomic::AtomicU64::new(timeout)),
+            tcp_keepalive: Arc::new(std::sync::atomic::AtomicU64::new(tcp_keepalive)),
+            proto_max_bulk_len: Arc::new(std::sync::atomic::AtomicU64::new(
+                crate::conf::DEFAULT_PROTO_MAX_BULK_LEN,
+            )),
             blocked_client_count: Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            clients_in_timeout_table: Arc::new(std::sync::atomic::AtomicU64::new(0)),
             clients: Arc::new(DashMap::new()),
             monitors: Arc::new(DashMap::new()),
             watched_clients: Arc::new(DashMap::new()),
             client_watched_dirty: Arc::new(DashMap::new()),
             tracking_clients: Arc::new(DashMap::new()),
+            client_tracked_keys: Arc::new(DashMap::new()),
             acl_log: Arc::new(RwLock::new(VecDeque::new())),
             latency_events: Arc::new(DashMap::new()),
+            total_connections_received: Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            total_commands_processed: Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            net_input_bytes: Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            net_output_bytes: Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            inst_metrics: Arc::new(std::sync::Mutex::new(InstantaneousMetrics::default())),
+        }
+    }
+
+    /// Account for a client entering a blocking command. Clients blocking with
+    /// a timeout are also counted in the timeout table, as in Redis. The `b`
+    /// flag exempts the client from idle-timeout reaping while it waits.
+    pub fn block_client(&self, client_id: u64, with_timeout: bool) {
+        self.blocked_client_count.fetch_add(1, Ordering::Relaxed);
+        if with_timeout {
+            self.clients_in_timeout_table.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(mut ci) = self.clients.get_mut(&client_id) {
+            ci.flags.push('b');
+        }
+    }
+
+    pub fn unblock_client(&self, client_id: u64, with_timeout: bool) {
+        self.blocked_client_count.fetch_sub(1, Ordering::Relaxed);
+        if with_timeout {
+            self.clients_in_timeout_table.fetch_sub(1, Ordering::Relaxed);
+        }
+        if let Some(mut ci) = self.clients.get_mut(&client_id) {
+            ci.flags.retain(|f| f != 'b');
+            ci.last_activity = std::time::Instant::now();
+        }
+    }
+}
+
+/// Samples kept per instantaneous metric, as in Redis' STATS_METRIC_SAMPLES.
+pub const STATS_METRIC_SAMPLES: usize = 16;
+
+/// Per-second rate of a monotonic counter, averaged over the last
+/// `STATS_METRIC_SAMPLES` samples.
+#[derive(Debug, Default)]
+pub struct InstantaneousMetric {
+    samples: [u64; STATS_METRIC_SAMPLES],
+    idx: usize,
+    last_sample: Option<(std::time::Instant, u64)>,
+}
+
+impl InstantaneousMetric {
+    pub fn track(&mut self, current: u64, now: std::time::Instant) {
+        if let Some((at, prev)) = self.last_sample {
+            let elapsed_ms = now.duration_since(at).as_millis() as u64;
+            if let Some(rate) = (current.saturating_sub(prev) * 1000).checked_div(elapsed_ms) {
+                self.samples[self.idx] = rate;
+                self.idx = (self.idx + 1) % STATS_METRIC_SAMPLES;
+            }
         }
+        self.last_sample = Some((now, current));
+    }
+
+    pub fn value(&self) -> u64 {
+        self.samples.iter().sum::<u64>() / STATS_METRIC_SAMPLES as u64
     }
 }
 
+#[derive(Debug, Default)]
+pub struct InstantaneousMetrics {
+    pub ops: InstantaneousMetric,
+    pub net_input: InstantaneousMetric,
+    pub net_output: InstantaneousMetric,
+}
+
 #[derive(Clone)]
 pub struct ReplicationCtx {
     pub run_id: Arc<RwLock<String>>,
     pub replid2: Arc<RwLock<String>>,
     pub second_repl_offset: Arc<std::sync::atomic::AtomicI64>,
     pub replicas: Arc<DashMap<u64, tokio::sync::mpsc::Sender<Resp>>>,
-    pub repl_backlog: Arc<Mutex<VecDeque<(u64, Resp)>>>,
+    pub repl_backlog: Arc<Mutex<replication::ReplBacklog>>,
     pub repl_backlog_size: Arc<std::sync::atomic::AtomicUsize>,
     pub repl_ping_replica_period: Arc<std::sync::atomic::AtomicU64>,
     pub repl_timeout: Arc<std::sync::atomic::AtomicU64>,
@@ -374,6 +601,9 @@ pub struct ReplicationCtx {
     pub master_port: Arc<RwLock<Option<u16>>>,
     pub repl_waiters: Arc<std::sync::Mutex<VecDeque<WaitContext>>>,
     pub master_link_established: Arc<std::sync::atomic::AtomicBool>,
+    /// The task syncing from the master set by REPLICAOF, aborted when the
+    /// instance is pointed at another master or promoted.
+    pub master_link: Arc<std::sync::Mutex<Option<tokio::task::AbortHandle>>>,
 }
 
 impl ReplicationCtx {
@@ -395,7 +625,7 @@ impl ReplicationCtx {
             )),
             second_repl_offset: Arc::new(std::sync::atomic::AtomicI64::new(-1)),
             replicas: Arc::new(DashMap::new()),
-            repl_backlog: Arc::new(Mutex::new(VecDeque::new())),
+            repl_backlog: Arc::new(Mutex::new(replication::ReplBacklog::default())),
             repl_backlog_size: Arc::new(std::sync::atomic::AtomicUsize::new(repl_backlog_size)),
             repl_ping_replica_period: Arc::new(std::sync::atomic::AtomicU64::new(
                 repl_ping_replica_period,
@@ -419,6 +649,7 @@ impl ReplicationCtx {
             master_port: Arc::new(RwLock::new(None)),
             repl_waiters: Arc::new(std::sync::Mutex::new(VecDeque::new())),
             master_link_established: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            master_link: Arc::new(std::sync::Mutex::new(None)),
         }
     }
 }
@@ -430,6 +661,13 @@ pub struct MemoryCtx {
     pub maxmemory_samples: Arc<std::sync::atomic::AtomicUsize>,
     pub mem_peak_rss: Arc<std::sync::atomic::AtomicU64>,
     pub notify_keyspace_events: Arc<std::sync::atomic::AtomicU32>,
+    /// Compact-encoding thresholds, adjustable with CONFIG SET.
+    pub encoding_limits: Arc<RwLock<crate::conf::EncodingLimits>>,
+    /// Key lookups that found / did not find a live key (INFO stats).
+    pub keyspace_hits: Arc<std::sync::atomic::AtomicU64>,
+    pub keyspace_misses: Arc<std::sync::atomic::AtomicU64>,
+    /// Whether the background expire cycle runs (DEBUG SET-ACTIVE-EXPIRE).
+    pub active_expire_enabled: Arc<std::sync::atomic::AtomicBool>,
 }
 
 impl MemoryCtx {
@@ -438,6 +676,7 @@ impl MemoryCtx {
         maxmemory_policy: crate::conf::EvictionPolicy,
         maxmemory_samples: usize,
         notify_keyspace_events: u32,
+        encoding_limits: crate::conf::EncodingLimits,
     ) -> Self {
         Self {
             maxmemory: Arc::new(std::sync::atomic::AtomicU64::new(maxmemory)),
@@ -447,6 +686,10 @@ impl MemoryCtx {
             notify_keyspace_events: Arc::new(std::sync::atomic::AtomicU32::new(
                 notify_keyspace_events,
             )),
+            encoding_limits: Arc::new(RwLock::new(encoding_limits)),
+            keyspace_hits: Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            keyspace_misses: Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            active_expire_enabled: Arc::new(std::sync::atomic::AtomicBool::new(true)),
         }
     }
 }
@@ -523,6 +766,7 @@ pub(crate) enum Command {
     DecrBy,
     Append,
     StrAlgo,
+    Lcs,
     StrLen,
     Lpush,
     Lpushx,
@@ -533,6 +777,8 @@ pub(crate) enum Command {
     Blpop,
     Brpop,
     Blmove,
+    Lmpop,
+    Blmpop,
     Lmove,
     Llen,
     Lindex,
@@ -568,6 +814,7 @@ pub(crate) enum Command {
     SMismember,
     SMove,
     SInter,
+    SInterCard,
     SInterStore,
     SUnion,
     SUnionStore,
@@ -585,6 +832,11 @@ pub(crate) enum Command {
     ZRevRange,
     Zrangebyscore,
     Zrangebylex,
+    ZRevRangeByScore,
+    ZRevRangeByLex,
+    ZRemRangeByRank,
+    ZRemRangeByScore,
+    ZRemRangeByLex,
     Zcount,
     Zlexcount,
     Zpopmin,
@@ -596,6 +848,7 @@ pub(crate) enum Command {
     Zunion,
     Zunionstore,
     Zinter,
+    Zintercard,
     Zinterstore,
     Zdiff,
     Zdiffstore,
@@ -628,13 +881,16 @@ pub(crate) enum Command {
     FlushDb,
     FlushAll,
     Dbsize,
+    RandomKey,
     Keys,
     Scan,
     Save,
     Bgsave,
     LastSave,
+    Debug,
     Role,
     ReplicaOf,
+    SlaveOf,
     Psync,
     ReplConf,
     Time,
@@ -649,6 +905,9 @@ pub(crate) enum Command {
     Eval,
     EvalSha,
     Script,
+    Function,
+    Fcall,
+    FcallRo,
     Select,
     Auth,
     Acl,
@@ -658,10 +917,13 @@ pub(crate) enum Command {
     Xrevrange,
     Xdel,
     Xtrim,
+    Xsetid,
     Xread,
     Xgroup,
     Xreadgroup,
     Xack,
+    XAckDel,
+    XDelEx,
     Xinfo,
     Xpending,
     Xclaim,
@@ -679,6 +941,9 @@ pub(crate) enum Command {
     Publish,
     Psubscribe,
     Punsubscribe,
+    Ssubscribe,
+    Sunsubscribe,
+    Spublish,
     PubSub,
     Client,
     Monitor,
@@ -694,6 +959,7 @@ pub(crate) enum Command {
     Hello,
     Reset,
     Wait,
+    WaitAof,
     Cluster,
     Asking,
     Unknown,
@@ -771,6 +1037,11 @@ pub(crate) fn get_command_keys<'a>(cmd: Command, items: &'a [Resp]) -> Vec<&'a [
         | Command::ZRevRange
         | Command::Zrangebyscore
         | Command::Zrangebylex
+        | Command::ZRevRangeByScore
+        | Command::ZRevRangeByLex
+        | Command::ZRemRangeByRank
+        | Command::ZRemRangeByScore
+        | Command::ZRemRangeByLex
         | Command::Zcount
         | Command::Zlexcount
         | Command::Zpopmin
@@ -804,7 +1075,10 @@ pub(crate) fn get_command_keys<'a>(cmd: Command, items: &'a [Resp]) -> Vec<&'a [
         | Command::Xrange
         | Command::Xrevrange
         | Command::Xdel
+        | Command::XDelEx
+        | Command::XAckDel
         | Command::Xtrim
+        | Command::Xsetid
         | Command::Xinfo
         | Command::Xpending
         | Command::Xclaim
@@ -832,7 +1106,7 @@ pub(crate) fn get_command_keys<'a>(cmd: Command, items: &'a [Resp]) -> Vec<&'a [
                 }
             }
         }
-        Command::Rename | Command::RenameNx | Command::SMove | Command::Copy => {
+        Command::Rename | Command::RenameNx | Command::SMove | Command::Copy | Command::Lcs => {
             if items.len() > 2 {
                 if let Some(key) = as_bytes(&items[1]) {
                     keys.push(key);
@@ -879,7 +1153,7 @@ pub(crate) fn get_command_keys<'a>(cmd: Command, items: &'a [Resp]) -> Vec<&'a [
                 }
             }
         }
-        Command::Eval | Command::EvalSha => {
+        Command::Eval | Command::EvalSha | Command::Fcall | Command::FcallRo => {
             if items.len() > 2 {
                 if let Some(numkeys_bytes) = as_bytes(&items[2]) {
                     if let Ok(numkeys_str) = std::str::from_utf8(&numkeys_bytes) {
@@ -896,6 +1170,20 @@ pub(crate) fn get_command_keys<'a>(cmd: Command, items: &'a [Resp]) -> Vec<&'a [
                 }
             }
         }
+        Command::Lmpop | Command::Blmpop => {
+            // LMPOP numkeys key [key ...] / BLMPOP timeout numkeys key [key ...]
+            let numkeys_idx = if cmd == Command::Lmpop { 1 } else { 2 };
+            if let Some(numkeys_bytes) = items.get(numkeys_idx).and_then(as_bytes)
+                && let Ok(numkeys_str) = std::str::from_utf8(numkeys_bytes)
+                && let Ok(numkeys) = numkeys_str.parse::<usize>()
+            {
+                for i in 0..numkeys {
+                    if let Some(key) = items.get(numkeys_idx + 1 + i).and_then(as_bytes) {
+                        keys.push(key);
+                    }
+                }
+            }
+        }
         Command::Blmove | Command::Lmove => {
             if items.len() > 2 {
                 if let Some(key) = as_bytes(&items[1]) {
@@ -943,7 +1231,7 @@ pub(crate) fn get_command_keys<'a>(cmd: Command, items: &'a [Resp]) -> Vec<&'a [
                 }
             }
         }
-        Command::Zinter => {
+        Command::Zinter | Command::Zintercard | Command::SInterCard => {
             if items.len() > 1 {
                 if let Some(numkeys_bytes) = as_bytes(&items[1]) {
                     if let Ok(numkeys_str) = std::str::from_utf8(&numkeys_bytes) {
@@ -1211,9 +1499,15 @@ pub async fn process_frame(
                         }
                     }
 
+                    conn_ctx.modified_keys = None;
+                    conn_ctx.dirty_changes = None;
                     let start = std::time::Instant::now();
                     let (res, log) = dispatch_command(cmd_name, &items, conn_ctx, server_ctx).await;
                     let elapsed_us = start.elapsed().as_micros() as i64;
+                    server_ctx
+                        .clients_ctx
+                        .total_commands_processed
+                        .fetch_add(1, Ordering::Relaxed);
 
                     // Record latency
                     if elapsed_us > 1000 {
@@ -1226,11 +1520,7 @@ pub async fn process_frame(
                     if conn_ctx.client_tracking && conn_ctx.client_caching && !is_write {
                         let keys = get_command_keys(cmd_name, &items);
                         for key in keys {
-                            server_ctx
-                                .clients_ctx.tracking_clients
-                                .entry((conn_ctx.db_index, key.to_vec()))
-                                .or_insert_with(HashSet::new)
-                                .insert(conn_ctx.id);
+                            track_key(conn_ctx.id, conn_ctx.db_index, key, server_ctx);
                         }
                     }
 
@@ -1241,15 +1531,25 @@ pub async fn process_frame(
                     let is_error = matches!(res, Resp::Error(_) | Resp::StaticError(_));
                     if !is_queued && !is_error && is_write {
                         // Increment dirty counter
-                        let changes = match &res {
-                            Resp::Integer(n) if *n > 0 => *n as u64,
-                            _ => 1,
+                        let changes = match conn_ctx.dirty_changes.take() {
+                            Some(n) => n,
+                            None => match &res {
+                                Resp::Integer(n) if *n > 0 => *n as u64,
+                                _ => 1,
+                            },
                         };
                         server_ctx.persist.dirty.fetch_add(changes, Ordering::Relaxed);
 
-                        let keys = get_command_keys(cmd_name, &items);
+                        let modified = conn_ctx.modified_keys.take();
+                        let keys = match &modified {
+                            Some(keys) => keys.iter().map(|k| k.as_ref()).collect(),
+                            None => get_command_keys(cmd_name, &items),
+                        };
                         // Hoist event/flags out of the per-key loop
-                        let event = String::from_utf8_lossy(cmd_raw).to_lowercase();
+                        let event = match cmd_name {
+                            Command::Unlink | Command::GetDel => String::from("del"),
+                            _ => String::from_utf8_lossy(cmd_raw).to_lowercase(),
+                        };
                         let notify_flags = notify::get_notify_flags_for_command(cmd_name);
                         for key in keys {
                             touch_watched_key(key, conn_ctx.db_index, server_ctx);
@@ -1280,11 +1580,12 @@ pub async fn process_frame(
                                 _ => {}
                             }
                         }
+                        // CLIENT SETNAME updates ClientInfo, so this is the name as of now
                         let (client_addr, client_name) =
                             if let Some(ci) = server_ctx.clients_ctx.clients.get(&conn_ctx.id) {
                                 (ci.addr.clone(), ci.name.clone())
                             } else {
-                                (String::from("unknown"), String::new())
+                                (String::from("unknown"), conn_ctx.client_name.clone())
                             };
                         let id = server_ctx.slowlog.next_id.fetch_add(1, Ordering::Relaxed);
                         let entry = SlowLogEntry {
@@ -1324,58 +1625,6 @@ pub async fn process_frame(
                 if is_write_cmd(cmd_name) && !conn_ctx.in_multi {
                         match cmd_name {
                             Command::Multi | Command::Exec | Command::Discard => None,
-                            Command::Blpop => match &res {
-                                Resp::Array(Some(arr)) if arr.len() >= 2 => {
-                                    let key_bytes = match &arr[0] {
-                                        Resp::BulkString(Some(k)) => k.clone(),
-                                        Resp::SimpleString(k) => k.clone(),
-                                        _ => bytes::Bytes::new(),
-                                    };
-                                    if !key_bytes.is_empty() {
-                                        Some(Resp::Array(Some(vec![
-                                            Resp::BulkString(Some(bytes::Bytes::from_static(
-                                                b"LPOP",
-                                            ))),
-                                            Resp::BulkString(Some(key_bytes)),
-                                        ])))
-                                    } else {
-                                        None
-                                    }
-                                }
-                                _ => None,
-                            },
-                            Command::Brpop => match &res {
-                                Resp::Array(Some(arr)) if arr.len() >= 2 => {
-                                    let key_bytes = match &arr[0] {
-                                        Resp::BulkString(Some(k)) => k.clone(),
-                                        Resp::SimpleString(k) => k.clone(),
-                                        _ => bytes::Bytes::new(),
-                                    };
-                                    if !key_bytes.is_empty() {
-                                        Some(Resp::Array(Some(vec![
-                                            Resp::BulkString(Some(bytes::Bytes::from_static(
-                                                b"RPOP",
-                                            ))),
-                                            Resp::BulkString(Some(key_bytes)),
-                                        ])))
-                                    } else {
-                                        None
-                                    }
-                                }
-                                _ => None,
-                            },
-                            Command::Blmove => {
-                                // Rewrite to LMOVE with the same arguments
-                                if !items.is_empty() {
-                                    let mut new_items = items.clone();
-                                    // Replace command name
-                                    new_items[0] =
-                                        Resp::BulkString(Some(bytes::Bytes::from_static(b"LMOVE")));
-                                    Some(Resp::Array(Some(new_items)))
-                                } else {
-                                    None
-                                }
-                            }
                             Command::Bzpopmin => {
                                 // Rewrite to ZPOPMIN key
                                 match &res {
@@ -1423,7 +1672,18 @@ pub async fn process_frame(
                                 }
                             }
                             _ => {
-                                if matches!(cmd_name, Command::Xreadgroup) {
+                                // These supply their own log entry only when they change data
+                                if matches!(
+                                    cmd_name,
+                                    Command::Xreadgroup
+                                        | Command::GetEx
+                                        | Command::Lpush
+                                        | Command::Rpush
+                                        | Command::Blpop
+                                        | Command::Brpop
+                                        | Command::Blmove
+                                        | Command::Blmpop
+                                ) {
                                     None
                                 } else {
                                     Some(Resp::Array(Some(items.clone())))
@@ -1443,7 +1703,8 @@ pub async fn process_frame(
         None
     };
 
-    (res, cmd_to_log)
+    // Commands reply in RESP3 shapes; RESP2 clients get them downgraded here
+    (res.into_protocol(conn_ctx.protocol), cmd_to_log)
 }
 
 fn check_access(
@@ -1482,9 +1743,7 @@ fn check_access(
             if !keys.is_empty() {
                 let mut slots = Vec::new();
                 for k in &keys {
-                    let ks = String::from_utf8_lossy(k).to_string();
-                    let s = crate::cluster::ClusterState::key_slot(&ks) as usize;
-                    slots.push(s);
+                    slots.push(crate::cluster::key_hash_slot(k) as usize);
                 }
                 let first = slots[0];
                 for s in &slots {
@@ -1558,6 +1817,54 @@ async fn dispatch_command(
     conn_ctx: &mut ConnectionContext,
     server_ctx: &ServerContext,
 ) -> (Resp, Option<Resp>) {
+    if conn_ctx.is_lua {
+        match cmd {
+            Command::Multi
+            | Command::Exec
+            | Command::Discard
+            | Command::Watch
+            | Command::Unwatch
+            | Command::Subscribe
+            | Command::Unsubscribe
+            | Command::Psubscribe
+            | Command::Punsubscribe
+            | Command::Ssubscribe
+            | Command::Sunsubscribe
+            | Command::Monitor
+            | Command::Eval
+            | Command::EvalSha
+            | Command::Function
+            | Command::Fcall
+            | Command::FcallRo => {
+                return (
+                    Resp::StaticError("ERR This Redis command is not allowed from script"),
+                    None,
+                );
+            }
+            _ => {}
+        }
+    } else if server_ctx.script_manager.is_busy() {
+        let script_kill = matches!(cmd, Command::Script)
+            && items
+                .get(1)
+                .and_then(as_bytes)
+                .is_some_and(|sub| sub.eq_ignore_ascii_case(b"KILL"));
+        // A saving SHUTDOWN would persist whatever the script wrote so far
+        let shutdown_nosave = matches!(cmd, Command::Shutdown)
+            && items[1..]
+                .iter()
+                .filter_map(as_bytes)
+                .any(|arg| arg.eq_ignore_ascii_case(b"NOSAVE"));
+        if !script_kill && !shutdown_nosave {
+            return (
+                Resp::StaticError(
+                    "BUSY Redis is busy running a script. You can only call SCRIPT KILL or SHUTDOWN NOSAVE.",
+                ),
+                None,
+            );
+        }
+    }
+
     if conn_ctx.in_multi {
         match cmd {
             Command::Multi => {
@@ -1574,9 +1881,19 @@ async fn dispatch_command(
         }
     }
 
-    if !conn_ctx.subscriptions.is_empty() {
+    if !conn_ctx.subscriptions.is_empty()
+        || !conn_ctx.psubscriptions.is_empty()
+        || !conn_ctx.ssubscriptions.is_empty()
+    {
         match cmd {
-            Command::Subscribe | Command::Unsubscribe | Command::Ping | Command::Reset => {}
+            Command::Subscribe
+            | Command::Unsubscribe
+            | Command::Psubscribe
+            | Command::Punsubscribe
+            | Command::Ssubscribe
+            | Command::Sunsubscribe
+            | Command::Ping
+            | Command::Reset => {}
             _ => {
                 return (
                     Resp::StaticError(
@@ -1588,12 +1905,30 @@ async fn dispatch_command(
         }
     }
 
+    // Reuse the connection's handle on its db unless SELECT, SWAPDB or
+    // DEBUG RELOAD changed which map that is. Moving it out and back keeps
+    // the common path off both the databases lock and the map's refcount.
     let db_idx = conn_ctx.db_index;
-    let db = {
-        let db_lock = server_ctx.databases[db_idx].read().unwrap();
-        db_lock.clone()
+    let epoch = server_ctx.db_epoch.load(Ordering::Acquire);
+    let db = match conn_ctx.db_cache.take() {
+        Some((idx, cached_epoch, db)) if idx == db_idx && cached_epoch == epoch => db,
+        _ => server_ctx.databases[db_idx].read().unwrap().clone(),
     };
     conn_ctx.asking = false;
+    let result = execute_command(cmd, items, &db, conn_ctx, server_ctx).await;
+    conn_ctx.db_cache = Some((db_idx, epoch, db));
+    result
+}
+
+/// Runs a command against `db`, the handle of the connection's selected db
+/// when the command started.
+async fn execute_command(
+    cmd: Command,
+    items: &[Resp],
+    db: &Db,
+    conn_ctx: &mut ConnectionContext,
+    server_ctx: &ServerContext,
+) -> (Resp, Option<Resp>) {
     match cmd {
         Command::Multi => {
             if items.len() != 1 {
@@ -1702,140 +2037,180 @@ async fn dispatch_command(
         }
         Command::Hello => (hello::hello(items, conn_ctx, &server_ctx), None),
         Command::Reset => (reset::reset(conn_ctx, server_ctx), None),
-        Command::Set => (string::set(items, &db), None),
-        Command::SetNx => (string::setnx(items, &db), None),
-        Command::SetEx => (string::setex(items, &db), None),
-        Command::PSetEx => (string::psetex(items, &db), None),
-        Command::GetSet => (string::getset(items, &db), None),
-        Command::GetDel => (string::getdel(items, &db), None),
-        Command::GetEx => (string::getex(items, &db), None),
-        Command::GetRange => (string::getrange(items, &db), None),
-        Command::Mset => (string::mset(items, &db), None),
-        Command::MsetNx => (string::msetnx(items, &db), None),
-        Command::SetRange => (string::setrange(items, &db), None),
-        Command::Del => (key::del(items, &db), None),
-        Command::Unlink => (key::unlink(items, &db), None),
-        Command::Get => (string::get(items, &db), None),
-        Command::Mget => (string::mget(items, &db), None),
-        Command::Incr => (string::incr(items, &db), None),
-        Command::Decr => (string::decr(items, &db), None),
-        Command::IncrBy => (string::incrby(items, &db), None),
-        Command::IncrByFloat => (string::incrbyfloat(items, &db), None),
-        Command::DecrBy => (string::decrby(items, &db), None),
-        Command::Append => (string::append(items, &db), None),
-        Command::StrLen => (string::strlen(items, &db), None),
-        Command::StrAlgo => (string::stralgo(items, &db), None),
-        Command::Lpush => (list::lpush(items, &db, conn_ctx, server_ctx), None),
-        Command::Lpushx => (list::lpushx(items, &db), None),
-        Command::Rpush => (list::rpush(items, &db, conn_ctx, server_ctx), None),
-        Command::Rpushx => (list::rpushx(items, &db), None),
-        Command::Lpop => (list::lpop(items, &db), None),
-        Command::Rpop => (list::rpop(items, &db), None),
-        Command::Blpop => (list::blpop(items, &db, conn_ctx, server_ctx).await, None),
-        Command::Brpop => (list::brpop(items, &db, conn_ctx, server_ctx).await, None),
-        Command::Blmove => (list::blmove(items, &db, conn_ctx, server_ctx).await, None),
-        Command::Lmove => (list::lmove(items, &db), None),
-        Command::Linsert => (list::linsert(items, &db), None),
-        Command::Lrem => (list::lrem(items, &db), None),
-        Command::Lpos => (list::lpos(items, &db), None),
-        Command::Ltrim => (list::ltrim(items, &db), None),
-        Command::Lindex => (list::lindex(items, &db), None),
-        Command::Llen => (list::llen(items, &db), None),
-        Command::Lrange => (list::lrange(items, &db), None),
-        Command::Hset => (hash::hset(items, &db), None),
-        Command::HsetNx => (hash::hsetnx(items, &db), None),
-        Command::HincrBy => (hash::hincrby(items, &db), None),
-        Command::HincrByFloat => (hash::hincrbyfloat(items, &db), None),
-        Command::Hget => (hash::hget(items, &db), None),
-        Command::Hgetall => (hash::hgetall(items, &db), None),
-        Command::Hmset => (hash::hmset(items, &db), None),
-        Command::Hmget => (hash::hmget(items, &db), None),
-        Command::Hdel => (hash::hdel(items, &db), None),
-        Command::HExists => (hash::hexists(items, &db), None),
-        Command::Hlen => (hash::hlen(items, &db), None),
-        Command::Hkeys => (hash::hkeys(items, &db), None),
-        Command::Hvals => (hash::hvals(items, &db), None),
-        Command::HstrLen => (hash::hstrlen(items, &db), None),
-        Command::HRandField => (hash::hrandfield(items, &db), None),
-        Command::HScan => (hash::hscan(items, &db), None),
-        Command::Sadd => (set::sadd(items, &db), None),
-        Command::Srem => (set::srem(items, &db), None),
-        Command::Sismember => (set::sismember(items, &db), None),
-        Command::SMismember => (set::smismember(items, &db), None),
-        Command::Smembers => (set::smembers(items, &db), None),
-        Command::Scard => (set::scard(items, &db), None),
-        Command::SPop => (set::spop(items, &db), None),
-        Command::SRandMember => (set::srandmember(items, &db), None),
-        Command::SScan => (set::sscan(items, &db), None),
-        Command::SMove => (set::smove(items, &db), None),
-        Command::SInter => (set::sinter(items, &db), None),
-        Command::SInterStore => (set::sinterstore(items, &db), None),
-        Command::SUnion => (set::sunion(items, &db), None),
-        Command::SUnionStore => (set::sunionstore(items, &db), None),
-        Command::SDiff => (set::sdiff(items, &db), None),
-        Command::SDiffStore => (set::sdiffstore(items, &db), None),
-        Command::Zadd => (zset::zadd(items, conn_ctx, server_ctx), None),
-        Command::ZIncrBy => (zset::zincrby(items, &db), None),
-        Command::Zrem => (zset::zrem(items, &db), None),
-        Command::Zscore => (zset::zscore(items, &db), None),
-        Command::Zmscore => (zset::zmscore(items, &db), None),
-        Command::Zcard => (zset::zcard(items, &db), None),
-        Command::Zrank => (zset::zrank(items, &db), None),
-        Command::ZRevRank => (zset::zrevrank(items, &db), None),
-        Command::Zrange => (zset::zrange(items, &db), None),
-        Command::ZRevRange => (zset::zrevrange(items, &db), None),
-        Command::Zrangebyscore => (zset::zrangebyscore(items, &db), None),
-        Command::Zrangebylex => (zset::zrangebylex(items, &db), None),
-        Command::Zcount => (zset::zcount(items, &db), None),
-        Command::Zlexcount => (zset::zlexcount(items, &db), None),
-        Command::Zpopmin => (zset::zpopmin(items, &db), None),
+        Command::Set => (string::set(items, db), None),
+        Command::SetNx => (string::setnx(items, db), None),
+        Command::SetEx => (string::setex(items, db), None),
+        Command::PSetEx => (string::psetex(items, db), None),
+        Command::GetSet => (string::getset(items, db), None),
+        Command::GetDel => {
+            let (res, removed) = string::getdel(items, db);
+            conn_ctx.modified_keys = Some(removed.into_iter().collect());
+            (res, None)
+        }
+        Command::GetEx => {
+            let (res, log) = string::getex(items, db);
+            if log.is_none() {
+                conn_ctx.dirty_changes = Some(0);
+                conn_ctx.modified_keys = Some(Vec::new());
+            }
+            (res, log)
+        }
+        Command::GetRange => (string::getrange(items, db), None),
+        Command::Mset => (string::mset(items, db), None),
+        Command::MsetNx => (string::msetnx(items, db), None),
+        Command::SetRange => (string::setrange(items, db), None),
+        Command::Del => {
+            let (res, removed) = key::del(items, db);
+            conn_ctx.modified_keys = Some(removed);
+            (res, None)
+        }
+        Command::Unlink => {
+            let (res, removed) = key::unlink(items, db);
+            conn_ctx.modified_keys = Some(removed);
+            (res, None)
+        }
+        Command::Get => (string::get(items, db), None),
+        Command::Mget => (string::mget(items, db), None),
+        Command::Incr => (string::incr(items, db), None),
+        Command::Decr => (string::decr(items, db), None),
+        Command::IncrBy => (string::incrby(items, db), None),
+        Command::IncrByFloat => (string::incrbyfloat(items, db), None),
+        Command::DecrBy => (string::decrby(items, db), None),
+        Command::Append => (string::append(items, db), None),
+        Command::StrLen => (string::strlen(items, db), None),
+        Command::StrAlgo => (string::stralgo(items, db), None),
+        Command::Lcs => (string::lcs(items, db), None),
+        Command::Lpush => list::lpush(items, db, conn_ctx, server_ctx),
+        Command::Lpushx => (list::lpushx(items, db), None),
+        Command::Rpush => list::rpush(items, db, conn_ctx, server_ctx),
+        Command::Rpushx => (list::rpushx(items, db), None),
+        Command::Lpop => (list::lpop(items, db), None),
+        Command::Rpop => (list::rpop(items, db), None),
+        Command::Blpop => list::blpop(items, db, conn_ctx, server_ctx).await,
+        Command::Brpop => list::brpop(items, db, conn_ctx, server_ctx).await,
+        Command::Blmove => list::blmove(items, db, conn_ctx, server_ctx).await,
+        Command::Lmpop => (list::lmpop(items, db, conn_ctx), None),
+        Command::Blmpop => list::blmpop(items, db, conn_ctx, server_ctx).await,
+        Command::Lmove => (list::lmove(items, db), None),
+        Command::Linsert => (list::linsert(items, db), None),
+        Command::Lrem => (list::lrem(items, db), None),
+        Command::Lpos => (list::lpos(items, db), None),
+        Command::Ltrim => (list::ltrim(items, db), None),
+        Command::Lindex => (list::lindex(items, db), None),
+        Command::Llen => (list::llen(items, db), None),
+        Command::Lrange => (list::lrange(items, db), None),
+        Command::Hset => (hash::hset(items, db), None),
+        Command::HsetNx => (hash::hsetnx(items, db), None),
+        Command::HincrBy => (hash::hincrby(items, db), None),
+        Command::HincrByFloat => (hash::hincrbyfloat(items, db), None),
+        Command::Hget => (hash::hget(items, db), None),
+        Command::Hgetall => (hash::hgetall(items, conn_ctx, db), None),
+        Command::Hmset => (hash::hmset(items, db), None),
+        Command::Hmget => (hash::hmget(items, db), None),
+        Command::Hdel => (hash::hdel(items, db), None),
+        Command::HExists => (hash::hexists(items, db), None),
+        Command::Hlen => (hash::hlen(items, db), None),
+        Command::Hkeys => (hash::hkeys(items, db), None),
+        Command::Hvals => (hash::hvals(items, db), None),
+        Command::HstrLen => (hash::hstrlen(items, db), None),
+        Command::HRandField => (hash::hrandfield(items, db), None),
+        Command::HScan => (hash::hscan(items, db), None),
+        Command::Sadd => (set::sadd(items, db), None),
+        Command::Srem => (set::srem(items, db), None),
+        Command::Sismember => (set::sismember(items, db), None),
+        Command::SMismember => (set::smismember(items, db), None),
+        Command::Smembers => (set::smembers(items, conn_ctx, db), None),
+        Command::Scard => (set::scard(items, db), None),
+        Command::SPop => (set::spop(items, db), None),
+        Command::SRandMember => (set::srandmember(items, db), None),
+        Command::SScan => (set::sscan(items, db), None),
+        Command::SMove => (set::smove(items, db), None),
+        Command::SInter => (set::sinter(items, db), None),
+        Command::SInterCard => (set::sintercard(items, db), None),
+        Command::SInterStore => (set::sinterstore(items, db), None),
+        Command::SUnion => (set::sunion(items, db), None),
+        Command::SUnionStore => (set::sunionstore(items, db), None),
+        Command::SDiff => (set::sdiff(items, db), None),
+        Command::SDiffStore => (set::sdiffstore(items, db), None),
+        Command::Zadd => {
+            let (res, changed) = zset::zadd(items, conn_ctx, server_ctx);
+            conn_ctx.dirty_changes = Some(changed as u64);
+            if changed == 0 {
+                conn_ctx.modified_keys = Some(Vec::new());
+            }
+            (res, None)
+        }
+        Command::ZIncrBy => (zset::zincrby(items, db), None),
+        Command::Zrem => (zset::zrem(items, db), None),
+        Command::Zscore => (zset::zscore(items, db), None),
+        Command::Zmscore => (zset::zmscore(items, db), None),
+        Command::Zcard => (zset::zcard(items, db), None),
+        Command::Zrank => (zset::zrank(items, db), None),
+        Command::ZRevRank => (zset::zrevrank(items, db), None),
+        Command::Zrange => (zset::zrange(items, conn_ctx, db), None),
+        Command::ZRevRange => (zset::zrevrange(items, db), None),
+        Command::Zrangebyscore => (zset::zrangebyscore(items, conn_ctx, db), None),
+        Command::Zrangebylex => (zset::zrangebylex(items, conn_ctx, db), None),
+        Command::ZRevRangeByScore => (zset::zrevrangebyscore(items, conn_ctx, db), None),
+        Command::ZRevRangeByLex => (zset::zrevrangebylex(items, conn_ctx, db), None),
+        Command::ZRemRangeByRank => (zset::zremrangebyrank(items, db), None),
+        Command::ZRemRangeByScore => (zset::zremrangebyscore(items, db), None),
+        Command::ZRemRangeByLex => (zset::zremrangebylex(items, db), None),
+        Command::Zcount => (zset::zcount(items, db), None),
+        Command::Zlexcount => (zset::zlexcount(items, db), None),
+        Command::Zpopmin => (zset::zpopmin(items, db), None),
         Command::Bzpopmin => (zset::bzpopmin(items, conn_ctx, server_ctx).await, None),
-        Command::Zpopmax => (zset::zpopmax(items, &db), None),
+        Command::Zpopmax => (zset::zpopmax(items, db), None),
         Command::Bzpopmax => (zset::bzpopmax(items, conn_ctx, server_ctx).await, None),
-        Command::ZScan => (zset::zscan(items, &db), None),
-        Command::ZRandMember => (zset::zrandmember(items, &db), None),
-        Command::Zunion => (zset::zunion(items, &db), None),
-        Command::Zunionstore => (zset::zunionstore(items, &db), None),
-        Command::Zinter => (zset::zinter(items, &db), None),
-        Command::Zinterstore => (zset::zinterstore(items, &db), None),
-        Command::Zdiff => (zset::zdiff(items, &db), None),
-        Command::Zdiffstore => (zset::zdiffstore(items, &db), None),
-        Command::Pfadd => (hll::pfadd(items, &db), None),
-        Command::Pfcount => (hll::pfcount(items, &db), None),
-        Command::Pfmerge => (hll::pfmerge(items, &db), None),
-        Command::GeoAdd => (geo::geoadd(items, &db), None),
-        Command::GeoDist => (geo::geodist(items, &db), None),
-        Command::GeoHash => (geo::geohash(items, &db), None),
-        Command::GeoPos => (geo::geopos(items, &db), None),
-        Command::GeoRadius => (geo::georadius(items, &db), None),
-        Command::GeoRadiusByMember => (geo::georadiusbymember(items, &db), None),
-        Command::GeoSearch => (geo::geosearch(items, &db), None),
-        Command::GeoSearchStore => (geo::geosearchstore(items, &db), None),
-        Command::Expire => (key::expire(items, &db), None),
-        Command::PExpire => (key::pexpire(items, &db), None),
-        Command::ExpireAt => (key::expireat(items, &db), None),
-        Command::PExpireAt => (key::pexpireat(items, &db), None),
-        Command::Ttl => (key::ttl(items, &db), None),
-        Command::PTtl => (key::pttl(items, &db), None),
-        Command::Exists => (key::exists(items, &db), None),
-        Command::Type => (key::type_(items, &db), None),
-        Command::Rename => (key::rename(items, &db), None),
-        Command::RenameNx => (key::renamenx(items, &db), None),
-        Command::Persist => (key::persist(items, &db), None),
+        Command::ZScan => (zset::zscan(items, db), None),
+        Command::ZRandMember => (zset::zrandmember(items, db), None),
+        Command::Zunion => (zset::zunion(items, db), None),
+        Command::Zunionstore => (zset::zunionstore(items, db), None),
+        Command::Zinter => (zset::zinter(items, db), None),
+        Command::Zintercard => (zset::zintercard(items, db), None),
+        Command::Zinterstore => (zset::zinterstore(items, db), None),
+        Command::Zdiff => (zset::zdiff(items, db), None),
+        Command::Zdiffstore => (zset::zdiffstore(items, db), None),
+        Command::Pfadd => (hll::pfadd(items, db), None),
+        Command::Pfcount => (hll::pfcount(items, db), None),
+        Command::Pfmerge => (hll::pfmerge(items, db), None),
+        Command::GeoAdd => (geo::geoadd(items, db), None),
+        Command::GeoDist => (geo::geodist(items, db), None),
+        Command::GeoHash => (geo::geohash(items, db), None),
+        Command::GeoPos => (geo::geopos(items, db), None),
+        Command::GeoRadius => (geo::georadius(items, db), None),
+        Command::GeoRadiusByMember => (geo::georadiusbymember(items, db), None),
+        Command::GeoSearch => (geo::geosearch(items, db), None),
+        Command::GeoSearchStore => (geo::geosearchstore(items, db), None),
+        Command::Expire => (key::expire(items, db), None),
+        Command::PExpire => (key::pexpire(items, db), None),
+        Command::ExpireAt => (key::expireat(items, db), None),
+        Command::PExpireAt => (key::pexpireat(items, db), None),
+        Command::Ttl => (key::ttl(items, db), None),
+        Command::PTtl => (key::pttl(items, db), None),
+        Command::Exists => (key::exists(items, db), None),
+        Command::Type => (key::type_(items, db), None),
+        Command::Rename => (key::rename(items, db), None),
+        Command::RenameNx => (key::renamenx(items, db), None),
+        Command::Persist => (key::persist(items, db), None),
         Command::Copy => (key::copy(items, conn_ctx, server_ctx), None),
-        Command::Object => (key::object(items, &db), None),
+        Command::Object => (key::object(items, db, server_ctx), None),
         Command::Move => (key::move_(items, conn_ctx, server_ctx), None),
         Command::SwapDb => (key::swapdb(items, server_ctx), None),
-        Command::FlushDb => (key::flushdb(items, &db), None),
-        Command::FlushAll => (key::flushall(items, &server_ctx.databases), None),
-        Command::Dbsize => (key::dbsize(items, &db), None),
-        Command::Keys => (key::keys(items, &db), None),
-        Command::Scan => (key::scan(items, &db), None),
+        Command::FlushDb => (key::flushdb(items, db, conn_ctx), None),
+        Command::FlushAll => (key::flushall(items, &server_ctx.databases, conn_ctx), None),
+        Command::Dbsize => (key::dbsize(items, db), None),
+        Command::RandomKey => (key::randomkey(items, db), None),
+        Command::Keys => (key::keys(items, db), None),
+        Command::Scan => (key::scan(items, db), None),
         Command::Save => (save::save(items, server_ctx), None),
         Command::Bgsave => (save::bgsave(items, server_ctx), None),
         Command::LastSave => (save::lastsave(items, server_ctx), None),
+        Command::Debug => (debug::debug(items, db, server_ctx), None),
         Command::Role => (info::role(items, server_ctx), None),
-        Command::ReplicaOf => (replication::replicaof(items, server_ctx), None),
+        Command::ReplicaOf | Command::SlaveOf => {
+            (replication::replicaof(items, server_ctx), None)
+        }
         Command::Psync => (replication::psync(items, conn_ctx, server_ctx).await, None),
         Command::ReplConf => (replication::replconf(items, conn_ctx, server_ctx), None),
         Command::Time => {
@@ -1852,11 +2227,22 @@ async fn dispatch_command(
             (Resp::Array(Some(res)), None)
         }
         Command::Shutdown => {
-            // Flush AOF before exiting so no buffered commands are lost.
-            if let Some(aof) = &server_ctx.aof {
-                aof.flush().await;
+            let mut save_flag = None;
+            for arg in items.iter().skip(1) {
+                match as_bytes(arg).map(|b| String::from_utf8_lossy(b).to_uppercase()) {
+                    Some(a) if a == "NOSAVE" => save_flag = Some(false),
+                    Some(a) if a == "SAVE" => save_flag = Some(true),
+                    Some(a) if a == "NOW" || a == "FORCE" => {}
+                    _ => return (Resp::StaticError("ERR syntax error"), None),
+                }
+            }
+            match save::prepare_shutdown(server_ctx, save_flag).await {
+                Ok(()) => std::process::exit(0),
+                Err(_) => (
+                    Resp::StaticError("ERR Errors trying to SHUTDOWN. Check logs."),
+                    None,
+                ),
             }
-            std::process::exit(0);
         }
         Command::Command => (command::command(items), None),
         Command::Config => (config::config(items, server_ctx).await, None),
@@ -1871,10 +2257,13 @@ async fn dispatch_command(
             }
         }
         Command::Info => (info::info(items, server_ctx), None),
-        Command::Memory => (memory::memory(items, &db, server_ctx).await, None),
+        Command::Memory => (memory::memory(items, db, server_ctx).await, None),
         Command::Eval => scripting::eval(items, conn_ctx, server_ctx).await,
         Command::EvalSha => scripting::evalsha(items, conn_ctx, server_ctx).await,
         Command::Script => (scripting::script(items, &server_ctx.script_manager), None),
+        Command::Function => scripting::function(items, &server_ctx.script_manager).await,
+        Command::Fcall => scripting::fcall(items, false, conn_ctx, server_ctx).await,
+        Command::FcallRo => scripting::fcall(items, true, conn_ctx, server_ctx).await,
         Command::Select => {
             if items.len() != 2 {
                 (
@@ -1917,26 +2306,43 @@ async fn dispatch_command(
                 }
             }
         }
-        Command::Xadd => stream::xadd(items, &db),
-        Command::Xlen => (stream::xlen(items, &db), None),
-        Command::Xrange => (stream::xrange(items, &db), None),
-        Command::Xrevrange => (stream::xrevrange(items, &db), None),
-        Command::Xdel => stream::xdel(items, &db),
-        Command::Xtrim => stream::xtrim(items, &db),
+        Command::Xadd => stream::xadd(items, db, conn_ctx, server_ctx),
+        Command::Xlen => (stream::xlen(items, db), None),
+        Command::Xrange => (stream::xrange(items, db), None),
+        Command::Xrevrange => (stream::xrevrange(items, db), None),
+        Command::Xdel => stream::xdel(items, db),
+        Command::Xtrim => stream::xtrim(items, db),
+        Command::Xsetid => stream::xsetid(items, db),
         Command::Xread => (stream::xread_cmd(items, conn_ctx, server_ctx).await, None),
-        Command::Xgroup => stream::xgroup(items, &db),
+        Command::Xgroup => stream::xgroup(items, db),
         Command::Xreadgroup => stream::xreadgroup_cmd(items, conn_ctx, server_ctx).await,
-        Command::Xack => stream::xack(items, &db),
-        Command::Xinfo => (stream::xinfo(items, &db), None),
-        Command::Xpending => (stream::xpending(items, &db), None),
-        Command::Xclaim => stream::xclaim(items, &db),
-        Command::Xautoclaim => stream::xautoclaim(items, &db),
-        Command::SetBit => (bitmap::setbit(items, &db), None),
-        Command::GetBit => (bitmap::getbit(items, &db), None),
-        Command::BitCount => (bitmap::bitcount(items, &db), None),
-        Command::BitOp => bitmap::bitop(items, &db),
-        Command::BitPos => (bitmap::bitpos(items, &db), None),
-        Command::BitField => bitmap::bitfield(items, &db),
+        Command::Xack => stream::xack(items, db),
+        Command::XAckDel => {
+            let (res, log, changed) = stream::xackdel(items, db);
+            conn_ctx.dirty_changes = Some(changed as u64);
+            if changed == 0 {
+                conn_ctx.modified_keys = Some(Vec::new());
+            }
+            (res, log)
+        }
+        Command::XDelEx => {
+            let (res, log, changed) = stream::xdelex(items, db);
+            conn_ctx.dirty_changes = Some(changed as u64);
+            if changed == 0 {
+                conn_ctx.modified_keys = Some(Vec::new());
+            }
+            (res, log)
+        }
+        Command::Xinfo => (stream::xinfo(items, db), None),
+        Command::Xpending => (stream::xpending(items, db), None),
+        Command::Xclaim => stream::xclaim(items, db),
+        Command::Xautoclaim => stream::xautoclaim(items, db),
+        Command::SetBit => (bitmap::setbit(items, db), None),
+        Command::GetBit => (bitmap::getbit(items, db), None),
+        Command::BitCount => (bitmap::bitcount(items, db), None),
+        Command::BitOp => bitmap::bitop(items, db),
+        Command::BitPos => (bitmap::bitpos(items, db), None),
+        Command::BitField => bitmap::bitfield(items, db),
         Command::Publish => (pubsub::publish(items, conn_ctx, server_ctx).await, None),
         Command::Subscribe => (pubsub::subscribe(items, conn_ctx, server_ctx).await, None),
         Command::Unsubscribe => (pubsub::unsubscribe(items, conn_ctx, server_ctx).await, None),
@@ -1945,6 +2351,12 @@ async fn dispatch_command(
             pubsub::punsubscribe(items, conn_ctx, server_ctx).await,
             None,
         ),
+        Command::Ssubscribe => (pubsub::ssubscribe(items, conn_ctx, server_ctx).await, None),
+        Command::Sunsubscribe => (
+            pubsub::sunsubscribe(items, conn_ctx, server_ctx).await,
+            None,
+        ),
+        Command::Spublish => (pubsub::spublish(items, conn_ctx, server_ctx).await, None),
         Command::PubSub => (
             pubsub::pubsub_command(items, conn_ctx, server_ctx).await,
             None,
@@ -1953,21 +2365,23 @@ async fn dispatch_command(
         Command::Monitor => monitor::monitor(conn_ctx, server_ctx),
         Command::Slowlog => slowlog::slowlog(items, server_ctx).await,
         Command::Latency => (latency::latency(items, server_ctx), None),
-        Command::Dump => (dump::dump(items, &db), None),
-        Command::Restore => (dump::restore(items, &db), None),
-        Command::Touch => (key::touch(items, &db), None),
-        Command::Sort => (sort::sort(items, &db), None),
-        Command::SortRo => (sort::sort_ro(items, &db), None),
+        Command::Dump => (dump::dump(items, db, server_ctx), None),
+        Command::Restore => (dump::restore(items, db), None),
+        Command::Touch => (key::touch(items, db, server_ctx), None),
+        Command::Sort => (sort::sort(items, db), None),
+        Command::SortRo => (sort::sort_ro(items, db), None),
         Command::Watch => (watch(items, conn_ctx, server_ctx), None),
         Command::Unwatch => (unwatch(conn_ctx, server_ctx), None),
         Command::Wait => (replication::wait(items, conn_ctx, server_ctx).await, None),
+        Command::WaitAof => (replication::waitaof(items, server_ctx).await, None),
         Command::Asking => (asking::asking(items, conn_ctx), None),
         Command::BgRewriteAof => {
             if let Some(aof) = &server_ctx.aof {
                 let aof = aof.clone();
                 let databases = server_ctx.databases.clone();
+                let functions = server_ctx.script_manager.library_codes();
                 tokio::spawn(async move {
-                    if let Err(e) = aof.rewrite(databases).await {
+                    if let Err(e) = aof.rewrite(databases, functions).await {
                         error!("Background AOF rewrite failed: {}", e);
                     }
                 });
@@ -1990,9 +2404,10 @@ async fn dispatch_command(
     // (asking::asking(items, conn_ctx), None)
 }
 
-pub(crate) fn command_name(raw: &[u8]) -> Command {
+/// Every command name the server recognizes, uppercased, with its variant.
+pub(crate) fn command_map() -> &'static HashMap<String, Command> {
     static COMMAND_MAP: OnceLock<HashMap<String, Command>> = OnceLock::new();
-    let map = COMMAND_MAP.get_or_init(|| {
+    COMMAND_MAP.get_or_init(|| {
         let mut m = HashMap::new();
         m.insert("PING".to_string(), Command::Ping);
         m.insert("SET".to_string(), Command::Set);
@@ -2017,6 +2432,7 @@ pub(crate) fn command_name(raw: &[u8]) -> Command {
         m.insert("DECRBY".to_string(), Command::DecrBy);
         m.insert("APPEND".to_string(), Command::Append);
         m.insert("STRALGO".to_string(), Command::StrAlgo);
+        m.insert("LCS".to_string(), Command::Lcs);
         m.insert("STRLEN".to_string(), Command::StrLen);
         m.insert("LPUSH".to_string(), Command::Lpush);
         m.insert("LPUSHX".to_string(), Command::Lpushx);
@@ -2027,6 +2443,8 @@ pub(crate) fn command_name(raw: &[u8]) -> Command {
         m.insert("BLPOP".to_string(), Command::Blpop);
         m.insert("BRPOP".to_string(), Command::Brpop);
         m.insert("BLMOVE".to_string(), Command::Blmove);
+        m.insert("LMPOP".to_string(), Command::Lmpop);
+        m.insert("BLMPOP".to_string(), Command::Blmpop);
         m.insert("LMOVE".to_string(), Command::Lmove);
         m.insert("LINSERT".to_string(), Command::Linsert);
         m.insert("LREM".to_string(), Command::Lrem);
@@ -2062,6 +2480,7 @@ pub(crate) fn command_name(raw: &[u8]) -> Command {
         m.insert("SSCAN".to_string(), Command::SScan);
         m.insert("SMOVE".to_string(), Command::SMove);
         m.insert("SINTER".to_string(), Command::SInter);
+        m.insert("SINTERCARD".to_string(), Command::SInterCard);
         m.insert("SINTERSTORE".to_string(), Command::SInterStore);
         m.insert("SUNION".to_string(), Command::SUnion);
         m.insert("SUNIONSTORE".to_string(), Command::SUnionStore);
@@ -2079,6 +2498,11 @@ pub(crate) fn command_name(raw: &[u8]) -> Command {
         m.insert("ZREVRANGE".to_string(), Command::ZRevRange);
         m.insert("ZRANGEBYSCORE".to_string(), Command::Zrangebyscore);
         m.insert("ZRANGEBYLEX".to_string(), Command::Zrangebylex);
+        m.insert("ZREVRANGEBYSCORE".to_string(), Command::ZRevRangeByScore);
+        m.insert("ZREVRANGEBYLEX".to_string(), Command::ZRevRangeByLex);
+        m.insert("ZREMRANGEBYRANK".to_string(), Command::ZRemRangeByRank);
+        m.insert("ZREMRANGEBYSCORE".to_string(), Command::ZRemRangeByScore);
+        m.insert("ZREMRANGEBYLEX".to_string(), Command::ZRemRangeByLex);
         m.insert("ZCOUNT".to_string(), Command::Zcount);
         m.insert("ZLEXCOUNT".to_string(), Command::Zlexcount);
         m.insert("ZPOPMIN".to_string(), Command::Zpopmin);
@@ -2090,10 +2514,10 @@ pub(crate) fn command_name(raw: &[u8]) -> Command {
         m.insert("ZUNION".to_string(), Command::Zunion);
         m.insert("ZUNIONSTORE".to_string(), Command::Zunionstore);
         m.insert("ZINTER".to_string(), Command::Zinter);
+        m.insert("ZINTERCARD".to_string(), Command::Zintercard);
         m.insert("ZINTERSTORE".to_string(), Command::Zinterstore);
         m.insert("ZDIFF".to_string(), Command::Zdiff);
         m.insert("ZDIFFSTORE".to_string(), Command::Zdiffstore);
-        m.insert("SDIFFSTORE".to_string(), Command::SDiffStore);
         m.insert("PFADD".to_string(), Command::Pfadd);
         m.insert("PFCOUNT".to_string(), Command::Pfcount);
         m.insert("PFMERGE".to_string(), Command::Pfmerge);
@@ -2123,13 +2547,16 @@ pub(crate) fn command_name(raw: &[u8]) -> Command {
         m.insert("FLUSHDB".to_string(), Command::FlushDb);
         m.insert("FLUSHALL".to_string(), Command::FlushAll);
         m.insert("DBSIZE".to_string(), Command::Dbsize);
+        m.insert("RANDOMKEY".to_string(), Command::RandomKey);
         m.insert("KEYS".to_string(), Command::Keys);
         m.insert("SCAN".to_string(), Command::Scan);
         m.insert("SAVE".to_string(), Command::Save);
         m.insert("BGSAVE".to_string(), Command::Bgsave);
         m.insert("LASTSAVE".to_string(), Command::LastSave);
+        m.insert("DEBUG".to_string(), Command::Debug);
         m.insert("ROLE".to_string(), Command::Role);
         m.insert("REPLICAOF".to_string(), Command::ReplicaOf);
+        m.insert("SLAVEOF".to_string(), Command::SlaveOf);
         m.insert("PSYNC".to_string(), Command::Psync);
         m.insert("REPLCONF".to_string(), Command::ReplConf);
         m.insert("TIME".to_string(), Command::Time);
@@ -2140,6 +2567,9 @@ pub(crate) fn command_name(raw: &[u8]) -> Command {
         m.insert("EVAL".to_string(), Command::Eval);
         m.insert("EVALSHA".to_string(), Command::EvalSha);
         m.insert("SCRIPT".to_string(), Command::Script);
+        m.insert("FUNCTION".to_string(), Command::Function);
+        m.insert("FCALL".to_string(), Command::Fcall);
+        m.insert("FCALL_RO".to_string(), Command::FcallRo);
         m.insert("SELECT".to_string(), Command::Select);
         m.insert("AUTH".to_string(), Command::Auth);
         m.insert("ACL".to_string(), Command::Acl);
@@ -2149,10 +2579,13 @@ pub(crate) fn command_name(raw: &[u8]) -> Command {
         m.insert("XREVRANGE".to_string(), Command::Xrevrange);
         m.insert("XDEL".to_string(), Command::Xdel);
         m.insert("XTRIM".to_string(), Command::Xtrim);
+        m.insert("XSETID".to_string(), Command::Xsetid);
         m.insert("XREAD".to_string(), Command::Xread);
         m.insert("XGROUP".to_string(), Command::Xgroup);
         m.insert("XREADGROUP".to_string(), Command::Xreadgroup);
         m.insert("XACK".to_string(), Command::Xack);
+        m.insert("XACKDEL".to_string(), Command::XAckDel);
+        m.insert("XDELEX".to_string(), Command::XDelEx);
         m.insert("XINFO".to_string(), Command::Xinfo);
         m.insert("XPENDING".to_string(), Command::Xpending);
         m.insert("XCLAIM".to_string(), Command::Xclaim);
@@ -2174,6 +2607,9 @@ pub(crate) fn command_name(raw: &[u8]) -> Command {
         m.insert("PUBLISH".to_string(), Command::Publish);
         m.insert("PSUBSCRIBE".to_string(), Command::Psubscribe);
         m.insert("PUNSUBSCRIBE".to_string(), Command::Punsubscribe);
+        m.insert("SSUBSCRIBE".to_string(), Command::Ssubscribe);
+        m.insert("SUNSUBSCRIBE".to_string(), Command::Sunsubscribe);
+        m.insert("SPUBLISH".to_string(), Command::Spublish);
         m.insert("PUBSUB".to_string(), Command::PubSub);
         m.insert("CLIENT".to_string(), Command::Client);
         m.insert("MONITOR".to_string(), Command::Monitor);
@@ -2189,10 +2625,15 @@ pub(crate) fn command_name(raw: &[u8]) -> Command {
         m.insert("HELLO".to_string(), Command::Hello);
         m.insert("RESET".to_string(), Command::Reset);
         m.insert("WAIT".to_string(), Command::Wait);
+        m.insert("WAITAOF".to_string(), Command::WaitAof);
         m.insert("CLUSTER".to_string(), Command::Cluster);
         m.insert("ASKING".to_string(), Command::Asking);
         m
-    });
+    })
+}
+
+pub(crate) fn command_name(raw: &[u8]) -> Command {
+    let map = command_map();
 
     // Stack-allocate the uppercase form to avoid heap allocation on the hot path.
     // Command names are short ASCII strings; 32 bytes is enough for the longest one.
@@ -2256,6 +2697,8 @@ pub(crate) fn is_write_cmd(cmd: Command) -> bool {
             | Command::Brpop
             | Command::Blmove
             | Command::Lmove
+            | Command::Lmpop
+            | Command::Blmpop
             | Command::Linsert
             | Command::Lrem
             | Command::Ltrim
@@ -2275,6 +2718,9 @@ pub(crate) fn is_write_cmd(cmd: Command) -> bool {
             | Command::Zadd
             | Command::ZIncrBy
             | Command::Zrem
+            | Command::ZRemRangeByRank
+            | Command::ZRemRangeByScore
+            | Command::ZRemRangeByLex
             | Command::Zpopmin
             | Command::Bzpopmin
             | Command::Zpopmax
@@ -2291,9 +2737,12 @@ pub(crate) fn is_write_cmd(cmd: Command) -> bool {
             | Command::Xadd
             | Command::Xdel
             | Command::Xtrim
+            | Command::Xsetid
             | Command::Xgroup
             | Command::Xreadgroup
             | Command::Xack
+            | Command::XAckDel
+            | Command::XDelEx
             | Command::Xclaim
             | Command::Xautoclaim
             | Command::SetBit
@@ -2305,140 +2754,158 @@ pub(crate) fn is_write_cmd(cmd: Command) -> bool {
 }
 
 pub fn start_expiration_task(ctx: ServerContext) {
-    let ctx_clone = ctx.clone();
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(100));
-        // Redis-style active expiration constants.
-        // Each tick: sample SAMPLE_SIZE keys that carry a TTL; if more than
-        // EXPIRE_RATIO_THRESHOLD fraction are expired, repeat – up to
-        // MAX_ROUNDS times.  Total keys examined per tick is at most
-        // SAMPLE_POOL * MAX_ROUNDS = 2 000, regardless of DB size.
-        const SAMPLE_SIZE: usize = 20;
-        const SAMPLE_POOL: usize = SAMPLE_SIZE * 5; // IteratorRandom reservoir bound
-        const MAX_ROUNDS: usize = 10;
-        const EXPIRE_THRESHOLD_NUM: usize = 5; // >25% of 20 = >5 expired
-
         loop {
             interval.tick().await;
-
-            // Check master role
-            let is_master = {
-                if let Ok(role) = ctx_clone.repl.replication_role.read() {
-                    *role == ReplicationRole::Master
-                } else {
-                    false
-                }
-            };
-
-            if !is_master {
+            if !ctx.mem.active_expire_enabled.load(Ordering::Relaxed) {
                 continue;
             }
+            active_expire_cycle(&ctx).await;
+        }
+    });
+}
 
-            for (db_idx, db_lock) in ctx_clone.databases.iter().enumerate() {
-                // Collect expired keys using bounded random sampling.
-                // ThreadRng is !Send so it must be created and dropped within a
-                // scope that contains no .await points.
-                let expired_keys: Vec<bytes::Bytes> = {
-                    use rand::seq::IteratorRandom;
-                    let mut rng = rand::rng();
-                    let mut all_expired: Vec<bytes::Bytes> = Vec::new();
-                    if let Ok(db) = db_lock.read() {
-                        'rounds: for _ in 0..MAX_ROUNDS {
-                            // Sample up to SAMPLE_SIZE keys that have a TTL set.
-                            // `take(SAMPLE_POOL)` caps the iterator walk to O(SAMPLE_POOL).
-                            let sample: Vec<bytes::Bytes> = db
-                                .iter()
-                                .filter(|e| e.value().expires_at.is_some())
-                                .take(SAMPLE_POOL)
-                                .choose_multiple(&mut rng, SAMPLE_SIZE)
-                                .into_iter()
-                                .map(|e| e.key().clone())
-                                .collect();
-
-                            if sample.is_empty() {
-                                break 'rounds;
-                            }
-
-                            let mut round_expired: Vec<bytes::Bytes> = Vec::new();
-                            for key in &sample {
-                                if db.get(key).map_or(false, |e| e.is_expired()) {
-                                    db.remove(key);
-                                    round_expired.push(key.clone());
-                                }
-                            }
+/// One pass of active expiration over every database: removes a bounded
+/// random sample of expired volatile keys, fires their `expired` events,
+/// dirties WATCHers and tracking clients, and propagates a DEL for each.
+/// Replicas leave expiry to the DELs their master sends.
+pub async fn active_expire_cycle(ctx: &ServerContext) {
+    // Redis-style active expiration constants.
+    // Each tick: sample SAMPLE_SIZE keys that carry a TTL; if more than
+    // EXPIRE_RATIO_THRESHOLD fraction are expired, repeat – up to
+    // MAX_ROUNDS times.  Total keys examined per tick is at most
+    // SAMPLE_POOL * MAX_ROUNDS = 2 000, regardless of DB size.
+    const SAMPLE_SIZE: usize = 20;
+    const SAMPLE_POOL: usize = SAMPLE_SIZE * 5; // keys walked per sample
+    const MAX_ROUNDS: usize = 10;
+    const EXPIRE_THRESHOLD_NUM: usize = 5; // >25% of 20 = >5 expired
+
+    // Check master role
+    let is_master = {
+        if let Ok(role) = ctx.repl.replication_role.read() {
+            *role == ReplicationRole::Master
+        } else {
+            false
+        }
+    };
 
-                            let expired_count = round_expired.len();
-                            all_expired.extend(round_expired);
+    if !is_master {
+        return;
+    }
 
-                            // Stop early if expired ratio ≤ 25 %.
-                            if expired_count <= EXPIRE_THRESHOLD_NUM {
-                                break 'rounds;
-                            }
+    for (db_idx, db_lock) in ctx.databases.iter().enumerate() {
+        // Collect expired keys using bounded random sampling.
+        let expired_keys: Vec<bytes::Bytes> = {
+            let mut all_expired: Vec<bytes::Bytes> = Vec::new();
+            if let Ok(db) = db_lock.read() {
+                'rounds: for _ in 0..MAX_ROUNDS {
+                    // Sample up to SAMPLE_SIZE keys that have a TTL set,
+                    // walking at most SAMPLE_POOL keys from a random spot
+                    // so every volatile key gets its turn.
+                    let mut sample: Vec<bytes::Bytes> = Vec::with_capacity(SAMPLE_SIZE);
+                    crate::db::sample_from_random_position(&db, SAMPLE_POOL, |key, entry| {
+                        if entry.expires_at.is_some() {
+                            sample.push(key.clone());
                         }
-                    }
-                    all_expired
-                };
-
-                if !expired_keys.is_empty() {
-                    let select_cmd = Resp::Array(Some(vec![
-                        Resp::BulkString(Some(bytes::Bytes::from("SELECT"))),
-                        Resp::BulkString(Some(bytes::Bytes::from(db_idx.to_string()))),
-                    ]));
+                        sample.len() < SAMPLE_SIZE
+                    });
 
-                    // 1. Append SELECT to AOF
-                    if let Some(aof) = &ctx_clone.aof {
-                        aof.append(&select_cmd).await;
+                    if sample.is_empty() {
+                        break 'rounds;
                     }
 
-                    // 2. Propagate SELECT to Replicas
-                    let next_off = ctx_clone.repl.repl_offset.fetch_add(1, Ordering::Relaxed) + 1;
-                    {
-                        let mut q = ctx_clone.repl.repl_backlog.lock().await;
-                        q.push_back((next_off, select_cmd.clone()));
-                        let max = ctx_clone.repl.repl_backlog_size.load(Ordering::Relaxed);
-                        while q.len() > max {
-                            q.pop_front();
+                    let mut round_expired: Vec<bytes::Bytes> = Vec::new();
+                    for key in &sample {
+                        if db.remove_if(key, |_, e| e.is_expired()).is_some() {
+                            round_expired.push(key.clone());
                         }
                     }
-                    for entry in ctx_clone.repl.replicas.iter() {
-                        let _ = entry.value().try_send(select_cmd.clone());
+
+                    let expired_count = round_expired.len();
+                    all_expired.extend(round_expired);
+
+                    // Stop early if expired ratio ≤ 25 %.
+                    if expired_count <= EXPIRE_THRESHOLD_NUM {
+                        break 'rounds;
                     }
                 }
+            }
+            all_expired
+        };
+
+        if !expired_keys.is_empty() {
+            let select_cmd = Resp::Array(Some(vec![
+                Resp::BulkString(Some(bytes::Bytes::from("SELECT"))),
+                Resp::BulkString(Some(bytes::Bytes::from(db_idx.to_string()))),
+            ]));
+
+            // 1. Append SELECT to AOF
+            if let Some(aof) = &ctx.aof {
+                aof.append(&select_cmd).await;
+            }
 
-                for key in expired_keys {
-                    notify::notify_keyspace_event(
-                        &ctx_clone,
-                        notify::NOTIFY_EXPIRED,
-                        "expired",
-                        &key,
-                        db_idx,
-                    )
-                    .await;
+            // 2. Propagate SELECT to Replicas
+            replication::propagate(ctx, select_cmd).await;
+        }
 
-                    // Propagate DEL command
-                    let del_cmd = Resp::Array(Some(vec![
-                        Resp::BulkString(Some(bytes::Bytes::from("DEL"))),
-                        Resp::BulkString(Some(key.clone())),
-                    ]));
+        for key in expired_keys {
+            touch_watched_key(&key, db_idx, ctx);
+            notify::notify_keyspace_event(ctx, notify::NOTIFY_EXPIRED, "expired", &key, db_idx)
+                .await;
 
-                    // 1. Append to AOF
-                    if let Some(aof) = &ctx_clone.aof {
-                        aof.append(&del_cmd).await;
-                    }
+            // Propagate DEL command
+            let del_cmd = Resp::Array(Some(vec![
+                Resp::BulkString(Some(bytes::Bytes::from("DEL"))),
+                Resp::BulkString(Some(key.clone())),
+            ]));
 
-                    // 2. Propagate to Replicas
-                    let next_off = ctx_clone.repl.repl_offset.fetch_add(1, Ordering::Relaxed) + 1;
-                    {
-                        let mut q = ctx_clone.repl.repl_backlog.lock().await;
-                        q.push_back((next_off, del_cmd.clone()));
-                        let max = ctx_clone.repl.repl_backlog_size.load(Ordering::Relaxed);
-                        while q.len() > max {
-                            q.pop_front();
-                        }
-                    }
+            // 1. Append to AOF
+            if let Some(aof) = &ctx.aof {
+                aof.append(&del_cmd).await;
+            }
+
+            // 2. Propagate to Replicas
+            replication::propagate(ctx, del_cmd).await;
+        }
+    }
+}
+
+/// Close clients idle for longer than `timeout` seconds (0 disables it).
+/// Blocked, pub/sub and replica connections are exempt, as in Redis.
+pub fn start_client_timeout_task(ctx: ServerContext) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
+        loop {
+            interval.tick().await;
 
-                    for entry in ctx_clone.repl.replicas.iter() {
-                        let _ = entry.value().try_send(del_cmd.clone());
+            let timeout = ctx.clients_ctx.timeout.load(Ordering::Relaxed);
+            if timeout == 0 {
+                continue;
+            }
+            let max_idle = std::time::Duration::from_secs(timeout);
+
+            let idle: Vec<u64> = ctx
+                .clients_ctx
+                .clients
+                .iter()
+                .filter(|e| {
+                    let c = e.value();
+                    !c.flags.contains('b')
+                        && c.sub == 0
+                        && c.psub == 0
+                        && c.ssub == 0
+                        && !ctx.repl.replicas.contains_key(&c.id)
+                        && c.last_activity.elapsed() > max_idle
+                })
+                .map(|e| *e.key())
+                .collect();
+
+            for id in idle {
+                if let Some(ci) = ctx.clients_ctx.clients.get(&id) {
+                    tracing::info!("Closing idle client id={} addr={}", id, ci.addr);
+                    if let Some(tx) = &ci.shutdown_tx {
+                        let _ = tx.send(true);
                     }
                 }
             }
@@ -2446,6 +2913,27 @@ pub fn start_expiration_task(ctx: ServerContext) {
     });
 }
 
+/// Sample the traffic counters every 100ms, feeding the instantaneous rates
+/// reported by INFO stats (a 1.6s sliding window, as in Redis).
+pub fn start_stats_sampler_task(ctx: ServerContext) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(100));
+        loop {
+            interval.tick().await;
+            let now = std::time::Instant::now();
+            let clients = &ctx.clients_ctx;
+            let ops = clients.total_commands_processed.load(Ordering::Relaxed);
+            let input = clients.net_input_bytes.load(Ordering::Relaxed);
+            let output = clients.net_output_bytes.load(Ordering::Relaxed);
+
+            let mut m = clients.inst_metrics.lock().unwrap();
+            m.ops.track(ops, now);
+            m.net_input.track(input, now);
+            m.net_output.track(output, now);
+        }
+    });
+}
+
 fn resp_bulk(s: &str) -> Resp {
     Resp::BulkString(Some(bytes::Bytes::from(s.to_string())))
 }
@@ -39,6 +39,8 @@ pub fn get_notify_flags_for_command(cmd: Command) -> u32 {
         | Command::IncrByFloat
         | Command::DecrBy => NOTIFY_STRING,
         Command::Del
+        | Command::Unlink
+        | Command::GetDel
         | Command::Expire
         | Command::PExpire
         | Command::ExpireAt
@@ -59,7 +61,9 @@ pub fn get_notify_flags_for_command(cmd: Command) -> u32 {
         | Command::Lrem
         | Command::Ltrim
         | Command::Lmove
-        | Command::Blmove => NOTIFY_LIST,
+        | Command::Blmove
+        | Command::Lmpop
+        | Command::Blmpop => NOTIFY_LIST,
         Command::Sadd
         | Command::Srem
         | Command::SMove
@@ -75,6 +79,9 @@ pub fn get_notify_flags_for_command(cmd: Command) -> u32 {
         Command::Zadd
         | Command::ZIncrBy
         | Command::Zrem
+        | Command::ZRemRangeByRank
+        | Command::ZRemRangeByScore
+        | Command::ZRemRangeByLex
         | Command::Zpopmin
         | Command::Zpopmax
         | Command::Zunionstore
@@ -83,8 +90,11 @@ pub fn get_notify_flags_for_command(cmd: Command) -> u32 {
         Command::Xadd
         | Command::Xdel
         | Command::Xtrim
+        | Command::Xsetid
         | Command::Xgroup
         | Command::Xack
+        | Command::XAckDel
+        | Command::XDelEx
         | Command::Xclaim => NOTIFY_STREAM,
         _ => 0,
     }
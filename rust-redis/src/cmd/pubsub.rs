@@ -4,6 +4,8 @@ use bytes::Bytes;
 use dashmap::DashMap;
 use glob::Pattern;
 
+type Subscribers = DashMap<String, DashMap<u64, tokio::sync::mpsc::Sender<Resp>>>;
+
 pub async fn subscribe(
     args: &[Resp],
     conn_ctx: &mut ConnectionContext,
@@ -296,6 +298,157 @@ pub async fn publish(
     Resp::Integer(count)
 }
 
+pub async fn ssubscribe(
+    args: &[Resp],
+    conn_ctx: &mut ConnectionContext,
+    server_ctx: &ServerContext,
+) -> Resp {
+    if args.len() < 2 {
+        return Resp::Error("ERR wrong number of arguments for 'ssubscribe' command".to_string());
+    }
+
+    let len = args.len();
+    let mut last_resp = Resp::Error("Internal error".to_string());
+
+    for (i, arg) in args.iter().enumerate().skip(1) {
+        let channel_name = match arg {
+            Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).to_string(),
+            Resp::SimpleString(b) => String::from_utf8_lossy(b).to_string(),
+            _ => continue,
+        };
+
+        if conn_ctx.ssubscriptions.insert(channel_name.clone()) {
+            let channel_map = server_ctx
+                .pubsub
+                .shard_channels
+                .entry(channel_name.clone())
+                .or_default();
+
+            if let Some(sender) = &conn_ctx.msg_sender {
+                channel_map.insert(conn_ctx.id, sender.clone());
+            }
+        }
+
+        // Shard channels are counted apart from channels and patterns
+        let resp = Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("ssubscribe"))),
+            Resp::BulkString(Some(Bytes::from(channel_name))),
+            Resp::Integer(conn_ctx.ssubscriptions.len() as i64),
+        ]));
+
+        if i < len - 1 {
+            if let Some(sender) = &conn_ctx.msg_sender {
+                let _ = sender.send(resp).await;
+            }
+        } else {
+            last_resp = resp;
+        }
+    }
+
+    last_resp
+}
+
+pub async fn sunsubscribe(
+    args: &[Resp],
+    conn_ctx: &mut ConnectionContext,
+    server_ctx: &ServerContext,
+) -> Resp {
+    let channels_to_unsubscribe: Vec<String> = if args.len() <= 1 {
+        conn_ctx.ssubscriptions.iter().cloned().collect()
+    } else {
+        args.iter()
+            .skip(1)
+            .filter_map(|arg| match arg {
+                Resp::BulkString(Some(b)) => Some(String::from_utf8_lossy(b).to_string()),
+                Resp::SimpleString(b) => Some(String::from_utf8_lossy(b).to_string()),
+                _ => None,
+            })
+            .collect()
+    };
+
+    if channels_to_unsubscribe.is_empty() {
+        return Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("sunsubscribe"))),
+            Resp::BulkString(None),
+            Resp::Integer(conn_ctx.ssubscriptions.len() as i64),
+        ]));
+    }
+
+    let len = channels_to_unsubscribe.len();
+    let mut last_resp = Resp::Error("Internal error".to_string());
+
+    for (i, channel_name) in channels_to_unsubscribe.into_iter().enumerate() {
+        conn_ctx.ssubscriptions.remove(&channel_name);
+
+        if let Some(subscribers) = server_ctx.pubsub.shard_channels.get(&channel_name) {
+            subscribers.remove(&conn_ctx.id);
+        }
+
+        let resp = Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("sunsubscribe"))),
+            Resp::BulkString(Some(Bytes::from(channel_name))),
+            Resp::Integer(conn_ctx.ssubscriptions.len() as i64),
+        ]));
+
+        if i < len - 1 {
+            if let Some(sender) = &conn_ctx.msg_sender {
+                let _ = sender.send(resp).await;
+            }
+        } else {
+            last_resp = resp;
+        }
+    }
+
+    last_resp
+}
+
+/// SPUBLISH reaches only SSUBSCRIBE subscribers of the same channel; plain
+/// channel and pattern subscribers never see shard messages.
+pub async fn spublish(
+    args: &[Resp],
+    _conn_ctx: &mut ConnectionContext,
+    server_ctx: &ServerContext,
+) -> Resp {
+    if args.len() != 3 {
+        return Resp::Error("ERR wrong number of arguments for 'spublish' command".to_string());
+    }
+
+    let channel_name = match &args[1] {
+        Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).to_string(),
+        Resp::SimpleString(b) => String::from_utf8_lossy(b).to_string(),
+        _ => return Resp::Error("Invalid channel name".to_string()),
+    };
+
+    let message_bytes = match &args[2] {
+        Resp::BulkString(Some(b)) => b.clone(),
+        Resp::SimpleString(b) => b.clone(),
+        Resp::Integer(i) => Bytes::from(i.to_string()),
+        _ => return Resp::Error("Invalid message".to_string()),
+    };
+
+    let mut senders = Vec::new();
+    if let Some(subscribers) = server_ctx.pubsub.shard_channels.get(&channel_name) {
+        for sub in subscribers.iter() {
+            senders.push(sub.value().clone());
+        }
+    }
+
+    let msg_frame = Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("smessage"))),
+        Resp::BulkString(Some(Bytes::from(channel_name))),
+        Resp::BulkString(Some(message_bytes)),
+    ]));
+
+    let mut count = 0;
+    for sender in senders {
+        if sender.send(msg_frame.clone()).await.is_ok() {
+            count += 1;
+        }
+    }
+
+    Resp::Integer(count)
+}
+
 pub async fn pubsub_command(
     args: &[Resp],
     _conn_ctx: &mut ConnectionContext,
@@ -312,60 +465,10 @@ pub async fn pubsub_command(
     };
 
     match subcmd.as_str() {
-        "CHANNELS" => {
-            let pattern = if args.len() > 2 {
-                match &args[2] {
-                    Resp::BulkString(Some(b)) => Some(String::from_utf8_lossy(b).to_string()),
-                    Resp::SimpleString(b) => Some(String::from_utf8_lossy(b).to_string()),
-                    _ => None,
-                }
-            } else {
-                None
-            };
-
-            let mut channels = Vec::new();
-            for item in server_ctx.pubsub.channels.iter() {
-                let channel = item.key();
-                // Only list active channels (with at least one subscriber)
-                if item.value().is_empty() {
-                    continue;
-                }
-
-                if let Some(p) = &pattern {
-                    if let Ok(pat) = Pattern::new(p) {
-                        if pat.matches(channel) {
-                            channels.push(Resp::BulkString(Some(Bytes::from(channel.clone()))));
-                        }
-                    } else if p == channel {
-                        // Fallback for invalid patterns: treat as literal match
-                        channels.push(Resp::BulkString(Some(Bytes::from(channel.clone()))));
-                    }
-                } else {
-                    channels.push(Resp::BulkString(Some(Bytes::from(channel.clone()))));
-                }
-            }
-            Resp::Array(Some(channels))
-        }
-        "NUMSUB" => {
-            let mut result = Vec::new();
-            for arg in args.iter().skip(2) {
-                let channel = match arg {
-                    Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).to_string(),
-                    Resp::SimpleString(b) => String::from_utf8_lossy(b).to_string(),
-                    _ => continue,
-                };
-
-                let count = if let Some(subs) = server_ctx.pubsub.channels.get(&channel) {
-                    subs.len() as i64
-                } else {
-                    0
-                };
-
-                result.push(Resp::BulkString(Some(Bytes::from(channel))));
-                result.push(Resp::Integer(count));
-            }
-            Resp::Array(Some(result))
-        }
+        "CHANNELS" => active_channels(&server_ctx.pubsub.channels, args),
+        "NUMSUB" => numsub(&server_ctx.pubsub.channels, args),
+        "SHARDCHANNELS" => active_channels(&server_ctx.pubsub.shard_channels, args),
+        "SHARDNUMSUB" => numsub(&server_ctx.pubsub.shard_channels, args),
         "NUMPAT" => {
             let count = server_ctx.pubsub.patterns.len() as i64;
             Resp::Integer(count)
@@ -373,3 +476,63 @@ pub async fn pubsub_command(
         _ => Resp::Error("ERR unknown subcommand".to_string()),
     }
 }
+
+/// Replies to PUBSUB CHANNELS / SHARDCHANNELS `[pattern]`: the channels in
+/// `channels` with at least one subscriber, optionally filtered by a glob.
+fn active_channels(channels: &Subscribers, args: &[Resp]) -> Resp {
+    let pattern = if args.len() > 2 {
+        match &args[2] {
+            Resp::BulkString(Some(b)) => Some(String::from_utf8_lossy(b).to_string()),
+            Resp::SimpleString(b) => Some(String::from_utf8_lossy(b).to_string()),
+            _ => None,
+        }
+    } else {
+        None
+    };
+
+    let mut result = Vec::new();
+    for item in channels.iter() {
+        let channel = item.key();
+        // Only list active channels (with at least one subscriber)
+        if item.value().is_empty() {
+            continue;
+        }
+
+        if let Some(p) = &pattern {
+            if let Ok(pat) = Pattern::new(p) {
+                if pat.matches(channel) {
+                    result.push(Resp::BulkString(Some(Bytes::from(channel.clone()))));
+                }
+            } else if p == channel {
+                // Fallback for invalid patterns: treat as literal match
+                result.push(Resp::BulkString(Some(Bytes::from(channel.clone()))));
+            }
+        } else {
+            result.push(Resp::BulkString(Some(Bytes::from(channel.clone()))));
+        }
+    }
+    Resp::Array(Some(result))
+}
+
+/// Replies to PUBSUB NUMSUB / SHARDNUMSUB: each named channel followed by
+/// its subscriber count in `channels`.
+fn numsub(channels: &Subscribers, args: &[Resp]) -> Resp {
+    let mut result = Vec::new();
+    for arg in args.iter().skip(2) {
+        let channel = match arg {
+            Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).to_string(),
+            Resp::SimpleString(b) => String::from_utf8_lossy(b).to_string(),
+            _ => continue,
+        };
+
+        let count = if let Some(subs) = channels.get(&channel) {
+            subs.len() as i64
+        } else {
+            0
+        };
+
+        result.push(Resp::BulkString(Some(Bytes::from(channel))));
+        result.push(Resp::Integer(count));
+    }
+    Resp::Array(Some(result))
+}
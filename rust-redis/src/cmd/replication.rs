@@ -1,10 +1,11 @@
 use crate::cmd::{ConnectionContext, ServerContext, WaitContext};
 use crate::rdb::{RdbEncoder, RdbLoader};
-use crate::resp::{Resp, read_frame, write_frame};
+use crate::resp::{Resp, as_bytes, read_frame, write_frame};
 use bytes::Bytes;
+use std::collections::VecDeque;
 use std::io::Cursor;
 use std::sync::Arc;
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::io::{AsyncWriteExt, BufReader, BufWriter};
 use tokio::net::TcpStream;
@@ -13,6 +14,177 @@ use tracing::{error, info};
 
 use rand::Rng;
 
+/// A connected replica as reported by `INFO replication` and `ROLE`.
+pub struct ReplicaInfo {
+    pub ip: String,
+    pub port: u16,
+    pub offset: u64,
+    pub lag: u64,
+}
+
+/// Collects every replica that completed PSYNC, ordered by connection id so
+/// the `slaveN` indices stay stable between calls.
+pub fn connected_replicas(ctx: &ServerContext) -> Vec<ReplicaInfo> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let mut ids: Vec<u64> = ctx.repl.replicas.iter().map(|r| *r.key()).collect();
+    ids.sort_unstable();
+
+    ids.into_iter()
+        .map(|id| {
+            let addr = match ctx.clients_ctx.clients.get(&id) {
+                Some(ci) => ci.addr.clone(),
+                None => String::from("unknown:0"),
+            };
+            let (ip, mut port) = match addr.rsplit_once(':') {
+                Some((host, port_s)) => (host.to_string(), port_s.parse::<u16>().unwrap_or(0)),
+                None => (addr.clone(), 0),
+            };
+            // The port the replica listens on is more useful than its ephemeral one
+            if let Some(p) = ctx.repl.replica_listening_port.get(&id) {
+                port = *p.value();
+            }
+            let offset = ctx
+                .repl
+                .replica_ack
+                .get(&id)
+                .map(|v| *v.value())
+                .unwrap_or(0);
+            let ack_time = ctx
+                .repl
+                .replica_ack_time
+                .get(&id)
+                .map(|t| *t.value())
+                .unwrap_or(now);
+            ReplicaInfo {
+                ip,
+                port,
+                offset,
+                lag: now.saturating_sub(ack_time),
+            }
+        })
+        .collect()
+}
+
+/// The writes a reconnecting replica may have missed, each stored with the
+/// byte offset its encoding ends at and trimmed to `repl-backlog-size`
+/// bytes.
+#[derive(Default)]
+pub struct ReplBacklog {
+    entries: VecDeque<(u64, Resp, usize)>,
+    histlen: usize,
+}
+
+impl ReplBacklog {
+    /// Appends the `len`-byte write ending at `offset`, dropping the oldest
+    /// entries until the backlog holds at most `max_bytes`.
+    pub fn push(&mut self, offset: u64, frame: Resp, len: usize, max_bytes: usize) {
+        self.entries.push_back((offset, frame, len));
+        self.histlen += len;
+        while self.histlen > max_bytes
+            && let Some((_, _, len)) = self.entries.pop_front()
+        {
+            self.histlen -= len;
+        }
+    }
+
+    /// Offset of the oldest byte still held.
+    pub fn first_offset(&self) -> Option<u64> {
+        self.entries
+            .front()
+            .map(|(end, _, len)| end - *len as u64 + 1)
+    }
+
+    /// Bytes of encoded commands currently held.
+    pub fn histlen(&self) -> usize {
+        self.histlen
+    }
+
+    /// Every write after byte `offset`, or `None` when no held write starts
+    /// right after it: it was trimmed, or `offset` falls inside a write.
+    /// `current` is the latest offset, which needs nothing resent.
+    pub fn since(&self, offset: u64, current: u64) -> Option<Vec<Resp>> {
+        if offset == current {
+            return Some(Vec::new());
+        }
+        let first = self
+            .entries
+            .iter()
+            .position(|(end, _, len)| end - *len as u64 == offset)?;
+        Some(
+            self.entries
+                .iter()
+                .skip(first)
+                .map(|(_, frame, _)| frame.clone())
+                .collect(),
+        )
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.histlen = 0;
+    }
+}
+
+/// Appends a write to the replication stream and returns the offset it
+/// brings the stream to. The offset advances by the write's encoded length,
+/// and it is taken, recorded in the backlog and sent to every replica under
+/// the backlog lock, so backlog order follows offset order and a replica
+/// that PSYNC registers gets each write once. A replica whose channel is
+/// full is disconnected rather than skipped; it resyncs when it reconnects.
+pub async fn propagate(ctx: &ServerContext, frame: Resp) -> u64 {
+    let max = ctx.repl.repl_backlog_size.load(Ordering::Relaxed);
+    let mut backlog = ctx.repl.repl_backlog.lock().await;
+    let len = frame.as_bytes().len();
+    let offset = ctx.repl.repl_offset.fetch_add(len as u64, Ordering::Relaxed) + len as u64;
+    let lagging: Vec<u64> = ctx
+        .repl
+        .replicas
+        .iter()
+        .filter(|replica| replica.value().try_send(frame.clone()).is_err())
+        .map(|replica| *replica.key())
+        .collect();
+    for id in lagging {
+        disconnect_replica(id, ctx);
+    }
+    backlog.push(offset, frame, len, max);
+    offset
+}
+
+/// Unregisters a replica that can no longer take the stream and closes its
+/// connection.
+fn disconnect_replica(id: u64, ctx: &ServerContext) {
+    unregister_replica(id, ctx);
+    if let Some(ci) = ctx.clients_ctx.clients.get(&id) {
+        info!("Disconnecting replica id={} addr={}: stream fell behind", id, ci.addr);
+        if let Some(tx) = &ci.shutdown_tx {
+            let _ = tx.send(true);
+        }
+    }
+}
+
+/// Drops every registry entry of a disconnected replica, so it no longer
+/// counts towards `connected_slaves`, `WAIT` or `min-replicas-to-write`.
+pub fn unregister_replica(id: u64, ctx: &ServerContext) {
+    ctx.repl.replicas.remove(&id);
+    ctx.repl.replica_ack.remove(&id);
+    ctx.repl.replica_ack_time.remove(&id);
+    ctx.repl.replica_listening_port.remove(&id);
+}
+
+/// Aborts the task replicating from the current master, if there is one.
+fn stop_master_link(ctx: &ServerContext) {
+    if let Some(link) = ctx.repl.master_link.lock().unwrap().take() {
+        link.abort();
+    }
+    ctx.repl
+        .master_link_established
+        .store(false, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// REPLICAOF (and its alias SLAVEOF) `host port` | `NO ONE`.
 pub fn replicaof(items: &[Resp], ctx: &ServerContext) -> Resp {
     if items.len() != 3 {
         return Resp::Error("ERR wrong number of arguments for 'replicaof' command".to_string());
@@ -29,6 +201,7 @@ pub fn replicaof(items: &[Resp], ctx: &ServerContext) -> Resp {
     };
 
     if host.eq_ignore_ascii_case("NO") && port_s.eq_ignore_ascii_case("ONE") {
+        stop_master_link(ctx);
         if let Ok(mut role) = ctx.repl.replication_role.write() {
             *role = crate::cmd::ReplicationRole::Master;
         }
@@ -65,6 +238,23 @@ pub fn replicaof(items: &[Resp], ctx: &ServerContext) -> Resp {
         Err(_) => return Resp::Error("ERR value is not an integer or out of range".to_string()),
     };
 
+    let already_linked = ctx
+        .repl
+        .master_link
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|link| !link.is_finished());
+    if already_linked
+        && ctx.repl.master_host.read().unwrap().as_deref() == Some(host.as_str())
+        && *ctx.repl.master_port.read().unwrap() == Some(port)
+    {
+        return Resp::SimpleString(Bytes::from_static(
+            b"OK Already connected to specified master",
+        ));
+    }
+    stop_master_link(ctx);
+
     if let Ok(mut role) = ctx.repl.replication_role.write() {
         *role = crate::cmd::ReplicationRole::Slave;
     }
@@ -76,15 +266,21 @@ pub fn replicaof(items: &[Resp], ctx: &ServerContext) -> Resp {
     }
 
     let ctx_cloned = ctx.clone();
-    tokio::spawn(async move {
-        if let Err(e) = replication_worker(&ctx_cloned, &host, port).await {
-            error!("Replication worker exited with error: {}", e);
+    // Reconnects until REPLICAOF aborts the link; each attempt asks to
+    // continue from the last offset applied
+    let link = tokio::spawn(async move {
+        loop {
+            if let Err(e) = replication_worker(&ctx_cloned, &host, port).await {
+                error!("Replication worker exited with error: {}", e);
+            }
+            ctx_cloned
+                .repl.master_link_established
+                .store(false, std::sync::atomic::Ordering::Relaxed);
+            info!("Master link down, reconnecting in 1 second");
+            time::sleep(Duration::from_secs(1)).await;
         }
-        ctx_cloned
-            .repl.master_link_established
-            .store(false, std::sync::atomic::Ordering::Relaxed);
-        info!("Replication worker stopped, master link status set to down");
     });
+    *ctx.repl.master_link.lock().unwrap() = Some(link.abort_handle());
 
     Resp::SimpleString(Bytes::from_static(b"OK"))
 }
@@ -100,15 +296,19 @@ async fn replication_worker(
     let mut reader = BufReader::new(read_half);
     let mut writer = BufWriter::new(write_half);
 
+    // After a sync the replid is the master's and the offset the last write
+    // applied, so a reconnect can continue where the link dropped
+    let replid = ctx.repl.run_id.read().unwrap().clone();
+    let offset = ctx.repl.repl_offset.load(Ordering::Relaxed);
     let req = Resp::Array(Some(vec![
         Resp::BulkString(Some(Bytes::from_static(b"PSYNC"))),
-        Resp::BulkString(Some(Bytes::from("?".to_string()))),
-        Resp::BulkString(Some(Bytes::from("-1".to_string()))),
+        Resp::BulkString(Some(Bytes::from(replid.clone()))),
+        Resp::BulkString(Some(Bytes::from(offset.to_string()))),
     ]));
     write_frame(&mut writer, &req).await?;
     writer.flush().await?;
 
-    let last_off = Arc::new(AtomicU64::new(0));
+    let last_off = Arc::new(AtomicU64::new(offset));
     let first_resp = read_frame(&mut reader).await?.ok_or("EOF during PSYNC")?;
 
     match first_resp {
@@ -121,11 +321,12 @@ async fn replication_worker(
 
             match parts[0] {
                 "FULLRESYNC" => {
-                    if parts.len() >= 3 {
-                        if let Ok(off) = parts[2].parse::<u64>() {
-                            last_off.store(off, std::sync::atomic::Ordering::Relaxed);
-                        }
+                    if parts.len() < 3 {
+                        return Err("invalid FULLRESYNC response".into());
                     }
+                    let off = parts[2]
+                        .parse::<u64>()
+                        .map_err(|_| "invalid FULLRESYNC offset")?;
 
                     // Read RDB
                     let rdb_resp = read_frame(&mut reader)
@@ -141,10 +342,36 @@ async fn replication_worker(
                     }
                     let mut loader = RdbLoader::new(Cursor::new(rdb_data.as_ref()));
                     loader.load(&ctx.databases)?;
+                    if let Err(e) = ctx
+                        .script_manager
+                        .restore_libraries(loader.functions(), "FLUSH")
+                    {
+                        let msg = String::from_utf8_lossy(&e.as_bytes()).trim().to_string();
+                        return Err(format!("failed to load functions from RDB: {}", msg).into());
+                    }
+
+                    // Offsets now follow the master's, so whatever this
+                    // instance kept for its own replicas no longer lines up
+                    ctx.repl.repl_backlog.lock().await.clear();
+                    *ctx.repl.run_id.write().unwrap() = parts[1].to_string();
+                    last_off.store(off, Ordering::Relaxed);
+                    ctx.repl.repl_offset.store(off, Ordering::Relaxed);
                 }
                 "CONTINUE" => {
-                    // CONTINUE [replid]
-                    // Do nothing, just proceed to command loop
+                    // CONTINUE [replid]: the stream resumes right after the
+                    // requested offset. A new replid means the master was
+                    // promoted; the old one stays valid for our replicas up
+                    // to here, as on a promotion.
+                    if let Some(new_replid) = parts.get(1)
+                        && *new_replid != replid
+                    {
+                        let mut run_id = ctx.repl.run_id.write().unwrap();
+                        *ctx.repl.replid2.write().unwrap() = run_id.clone();
+                        ctx.repl
+                            .second_repl_offset
+                            .store(offset as i64 + 1, Ordering::Relaxed);
+                        *run_id = new_replid.to_string();
+                    }
                 }
                 _ => return Err(format!("unknown PSYNC response: {}", parts[0]).into()),
             }
@@ -290,30 +517,14 @@ async fn replication_worker(
                         }
                     }
 
-                    // Propagate to sub-replicas (Cascading replication)
+                    // The master counts the bytes of the writes it
+                    // propagates, not its REPLCONF requests; passing the same
+                    // writes on to our own replicas advances our offset by
+                    // the same amount (cascading replication)
                     if let Some(prop_frame) = frame_for_prop {
-                        // Send the frame to all replicas.
-                        for replica in ctx.repl.replicas.iter() {
-                            let _ = replica.value().try_send(prop_frame.clone());
-                        }
-
-                        // Also write to backlog
-                        {
-                            let mut backlog = ctx.repl.repl_backlog.lock().await;
-                            while backlog.len() > 10000 {
-                                backlog.pop_front();
-                            }
-                            let current = last_off.load(std::sync::atomic::Ordering::Relaxed);
-                            backlog.push_back((current, prop_frame));
-                        }
+                        let off = propagate(ctx, prop_frame).await;
+                        last_off.store(off, Ordering::Relaxed);
                     }
-
-                    last_off.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    // Also update global offset for INFO command correctness
-                    ctx.repl.repl_offset.store(
-                        last_off.load(std::sync::atomic::Ordering::Relaxed),
-                        std::sync::atomic::Ordering::Relaxed,
-                    );
                 }
             }
             None => break,
@@ -429,19 +640,8 @@ pub async fn psync(items: &[Resp], conn_ctx: &mut ConnectionContext, ctx: &Serve
         _ => -1,
     };
 
-    if let Some(sender) = &conn_ctx.msg_sender {
-        ctx.repl.replicas.insert(conn_ctx.id, sender.clone());
-        conn_ctx.is_replica = true;
-        if let Ok(mut state) = conn_ctx.replication_state.lock() {
-            *state = crate::cmd::ReplicationState::TransferringRdb;
-        }
-        ctx.persist.rdb_sync_client_id
-            .store(conn_ctx.id, std::sync::atomic::Ordering::Relaxed);
-    }
-
     let runid = ctx.repl.run_id.read().unwrap().clone();
     let replid2 = ctx.repl.replid2.read().unwrap().clone();
-    let current_off = ctx.repl.repl_offset.load(std::sync::atomic::Ordering::Relaxed) as i64;
     let second_off = ctx
         .repl.second_repl_offset
         .load(std::sync::atomic::Ordering::Relaxed);
@@ -454,30 +654,43 @@ pub async fn psync(items: &[Resp], conn_ctx: &mut ConnectionContext, ctx: &Serve
         false
     };
 
-    if can_try_partial && req_off >= 0 {
-        let mut to_send: Vec<Resp> = Vec::new();
-        let earliest_off = {
-            let q = ctx.repl.repl_backlog.lock().await;
-            q.front().map(|(o, _)| *o as i64).unwrap_or(current_off)
-        };
-        if req_off >= earliest_off {
-            {
-                let q = ctx.repl.repl_backlog.lock().await;
-                for (off, frame) in q.iter() {
-                    if (*off as i64) > req_off {
-                        to_send.push(frame.clone());
-                    }
-                }
+    if can_try_partial
+        && req_off >= 0
+        && let Some(sender) = &conn_ctx.msg_sender
+    {
+        // Holding the backlog lock until the replica is registered keeps
+        // writes from slipping between the backlog and the live stream. The
+        // offset only advances under that lock.
+        let backlog = ctx.repl.repl_backlog.lock().await;
+        let current_off = ctx.repl.repl_offset.load(Ordering::Relaxed);
+        if let Some(missed) = backlog.since(req_off as u64, current_off) {
+            // `+CONTINUE` and the backlog go straight to the writer, ahead of
+            // anything propagated once the replica is registered
+            let header = Resp::SimpleString(Bytes::from(format!("CONTINUE {}", runid)));
+            if sender.send(header).await.is_err() {
+                return Resp::NoReply;
             }
-            if let Some(sender) = &conn_ctx.msg_sender {
-                for f in to_send.into_iter() {
-                    let _ = sender.try_send(f);
+            for frame in missed {
+                if sender.send(frame).await.is_err() {
+                    return Resp::NoReply;
                 }
             }
-            return Resp::SimpleString(Bytes::from(format!("CONTINUE {}", runid)));
+            ctx.repl.replicas.insert(conn_ctx.id, sender.clone());
+            conn_ctx.is_replica = true;
+            return Resp::NoReply;
         }
     }
 
+    if let Some(sender) = &conn_ctx.msg_sender {
+        ctx.repl.replicas.insert(conn_ctx.id, sender.clone());
+        conn_ctx.is_replica = true;
+        if let Ok(mut state) = conn_ctx.replication_state.lock() {
+            *state = crate::cmd::ReplicationState::TransferringRdb;
+        }
+        ctx.persist.rdb_sync_client_id
+            .store(conn_ctx.id, std::sync::atomic::Ordering::Relaxed);
+    }
+
     // Delay start if configured (repl-diskless-sync + delay)
     if ctx
         .repl.repl_diskless_sync
@@ -491,6 +704,7 @@ pub async fn psync(items: &[Resp], conn_ctx: &mut ConnectionContext, ctx: &Serve
         }
     }
 
+    let current_off = ctx.repl.repl_offset.load(Ordering::Relaxed);
     let header = Resp::SimpleString(Bytes::from(format!("FULLRESYNC {} {}", runid, current_off)));
     let compression = ctx
         .persist.rdbcompression
@@ -500,9 +714,10 @@ pub async fn psync(items: &[Resp], conn_ctx: &mut ConnectionContext, ctx: &Serve
     // Generate RDB snapshot in a blocking thread to avoid blocking the async runtime.
     // The entire snapshot is buffered in memory so the length prefix can be sent atomically.
     let databases_clone = ctx.databases.clone();
+    let functions = ctx.script_manager.library_codes();
     let rdb_data = tokio::task::spawn_blocking(move || {
         let mut buf: Vec<u8> = Vec::new();
-        let mut enc = RdbEncoder::new(&mut buf, compression, checksum);
+        let mut enc = RdbEncoder::new(&mut buf, compression, checksum).with_functions(functions);
         let _ = enc.save(&databases_clone);
         buf
     })
@@ -513,24 +728,33 @@ pub async fn psync(items: &[Resp], conn_ctx: &mut ConnectionContext, ctx: &Serve
 }
 
 pub async fn wait(items: &[Resp], _conn_ctx: &mut ConnectionContext, ctx: &ServerContext) -> Resp {
-    if items.len() < 3 {
+    if items.len() != 3 {
         return Resp::Error("ERR wrong number of arguments for 'wait' command".to_string());
     }
 
-    let num_replicas: usize = match &items[1] {
-        Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).parse().unwrap_or(0),
-        Resp::SimpleString(s) => String::from_utf8_lossy(s).parse().unwrap_or(0),
-        Resp::Integer(i) => *i as usize,
-        _ => return Resp::Error("ERR invalid numreplicas".to_string()),
+    let parse = |item: &Resp| {
+        as_bytes(item)
+            .and_then(|b| std::str::from_utf8(b).ok())
+            .and_then(|s| s.parse::<i64>().ok())
     };
-
-    let timeout: u64 = match &items[2] {
-        Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).parse().unwrap_or(0),
-        Resp::SimpleString(s) => String::from_utf8_lossy(s).parse().unwrap_or(0),
-        Resp::Integer(i) => *i as u64,
-        _ => return Resp::Error("ERR invalid timeout".to_string()),
+    let num_replicas = match parse(&items[1]) {
+        Some(n) => n.max(0) as usize,
+        None => return Resp::Error("ERR value is not an integer or out of range".to_string()),
+    };
+    let timeout = match parse(&items[2]) {
+        Some(t) if t < 0 => return Resp::Error("ERR timeout is negative".to_string()),
+        Some(t) => t as u64,
+        None => {
+            return Resp::Error("ERR timeout is not an integer or out of range".to_string());
+        }
     };
 
+    // Nothing can acknowledge, so don't make the client sit out the timeout
+    // (or block forever with timeout 0)
+    if ctx.repl.replicas.is_empty() {
+        return Resp::Integer(0);
+    }
+
     let current_offset = ctx.repl.repl_offset.load(std::sync::atomic::Ordering::Relaxed) as u64;
 
     // Check currently acknowledged replicas
@@ -590,3 +814,70 @@ pub async fn wait(items: &[Resp], _conn_ctx: &mut ConnectionContext, ctx: &Serve
         }
     }
 }
+
+/// WAITAOF numlocal numreplicas timeout
+///
+/// Blocks until the writes issued so far are fsynced to the local AOF.
+/// Replicas do not report their own fsync offset, so none are ever counted
+/// and asking for any does not block.
+pub async fn waitaof(items: &[Resp], ctx: &ServerContext) -> Resp {
+    if items.len() != 4 {
+        return Resp::Error("ERR wrong number of arguments for 'waitaof' command".to_string());
+    }
+
+    let parse = |item: &Resp| -> Option<i64> {
+        match item {
+            Resp::BulkString(Some(b)) => std::str::from_utf8(b).ok()?.parse().ok(),
+            Resp::Integer(i) => Some(*i),
+            _ => None,
+        }
+    };
+    let (Some(num_local), Some(num_replicas), Some(timeout)) =
+        (parse(&items[1]), parse(&items[2]), parse(&items[3]))
+    else {
+        return Resp::Error("ERR value is not an integer or out of range".to_string());
+    };
+    if num_local < 0 || num_replicas < 0 {
+        return Resp::Error("ERR value is out of range, must be positive".to_string());
+    }
+    if timeout < 0 {
+        return Resp::Error("ERR timeout is negative".to_string());
+    }
+
+    if *ctx.repl.replication_role.read().unwrap() == crate::cmd::ReplicationRole::Slave {
+        return Resp::Error(
+            "ERR WAITAOF cannot be used with replica instances. Please also note that writes to replicas are just local and are not propagated.".to_string(),
+        );
+    }
+    if num_local > 0 && ctx.aof.is_none() {
+        return Resp::Error(
+            "ERR WAITAOF cannot be used when numlocal is set but appendonly is disabled."
+                .to_string(),
+        );
+    }
+
+    let target = match &ctx.aof {
+        Some(aof) => Some((aof, aof.offset().await)),
+        None => None,
+    };
+    let satisfied = async {
+        if num_local > 0
+            && let Some((aof, offset)) = &target
+        {
+            aof.stats().wait_fsynced(*offset).await;
+        }
+    };
+    if timeout > 0 {
+        let _ = time::timeout(Duration::from_millis(timeout as u64), satisfied).await;
+    } else {
+        satisfied.await;
+    }
+
+    let local_acked = target
+        .map(|(aof, offset)| aof.stats().fsync_offset() >= offset)
+        .unwrap_or(false);
+    Resp::Array(Some(vec![
+        Resp::Integer(local_acked as i64),
+        Resp::Integer(0),
+    ]))
+}
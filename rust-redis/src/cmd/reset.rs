@@ -16,14 +16,7 @@ pub fn reset(conn_ctx: &mut ConnectionContext, server_ctx: &ServerContext) -> Re
     conn_ctx.multi_queue.clear();
 
     // 4. Reset Watch state
-    for (db_idx, keys) in conn_ctx.watched_keys.iter() {
-        for key in keys {
-            if let Some(mut clients) = server_ctx.clients_ctx.watched_clients.get_mut(&(*db_idx, key.clone())) {
-                clients.remove(&conn_ctx.id);
-            }
-        }
-    }
-    conn_ctx.watched_keys.clear();
+    crate::cmd::unwatch_all_keys(conn_ctx, server_ctx);
     conn_ctx.watched_keys_dirty.store(false, Ordering::SeqCst);
 
     // 5. Reset PubSub state (silent unsubscribe)
@@ -41,10 +34,27 @@ pub fn reset(conn_ctx: &mut ConnectionContext, server_ctx: &ServerContext) -> Re
     }
     conn_ctx.psubscriptions.clear();
 
-    // 6. Reset Client Name
+    for channel in &conn_ctx.ssubscriptions {
+        if let Some(subscribers) = server_ctx.pubsub.shard_channels.get(channel) {
+            subscribers.remove(&conn_ctx.id);
+        }
+    }
+    conn_ctx.ssubscriptions.clear();
+
+    // 6. Reset client side caching
+    conn_ctx.client_tracking = false;
+    conn_ctx.client_caching = true;
+    conn_ctx.client_redir_id = -1;
+    conn_ctx.client_tracking_broken = false;
+    crate::cmd::untrack_all_keys(conn_ctx.id, server_ctx);
+
+    // 7. Reset Client Name
     if let Some(mut client_info) = server_ctx.clients_ctx.clients.get_mut(&conn_ctx.id) {
         client_info.name = String::new();
     }
 
+    // 8. Back to RESP2
+    conn_ctx.protocol = 2;
+
     Resp::SimpleString(Bytes::from("RESET"))
 }
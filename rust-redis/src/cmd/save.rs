@@ -9,7 +9,11 @@ use std::sync::atomic::Ordering;
 pub fn save(_items: &[Resp], ctx: &ServerContext) -> Resp {
     // Snapshot dirty before the blocking save so we don't discard concurrent writes.
     let dirty_before = ctx.persist.dirty.load(Ordering::Relaxed);
-    match rdb::rdb_save(&ctx.databases, &ctx.config) {
+    match rdb::rdb_save(
+        &ctx.databases,
+        ctx.script_manager.library_codes(),
+        &ctx.config,
+    ) {
         Ok(_) => {
             ctx.persist.last_bgsave_ok.store(true, Ordering::Relaxed);
             ctx.persist.dirty.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
@@ -32,6 +36,7 @@ pub fn bgsave(_items: &[Resp], ctx: &ServerContext) -> Resp {
     }
 
     let databases_clone = ctx.databases.clone();
+    let functions = ctx.script_manager.library_codes();
     let config_clone = ctx.config.clone();
     let last_bgsave_ok = ctx.persist.last_bgsave_ok.clone();
     let rdb_child_pid = ctx.persist.rdb_child_pid.clone();
@@ -46,7 +51,7 @@ pub fn bgsave(_items: &[Resp], ctx: &ServerContext) -> Resp {
     ctx.persist.rdb_child_pid.store(1, Ordering::Relaxed);
 
     std::thread::spawn(move || {
-        match rdb::rdb_save(&databases_clone, &config_clone) {
+        match rdb::rdb_save(&databases_clone, functions, &config_clone) {
             Ok(_) => {
                 last_bgsave_ok.store(true, Ordering::Relaxed);
                 // Subtract only what was dirty at save-start time; preserve any
@@ -77,3 +82,63 @@ pub fn lastsave(_items: &[Resp], ctx: &ServerContext) -> Resp {
     let last_save = ctx.persist.last_save_time.load(Ordering::Relaxed);
     Resp::Integer(last_save)
 }
+
+/// Orderly shutdown sequence shared by `SHUTDOWN` and the SIGTERM/SIGINT handler.
+///
+/// `save` mirrors the SHUTDOWN modifiers: `Some(true)` is SAVE, `Some(false)` is
+/// NOSAVE and `None` saves only when save points are configured. On success the
+/// AOF has been flushed and every client has been told to disconnect; the caller
+/// is expected to exit the process.
+pub async fn prepare_shutdown(ctx: &ServerContext, save: Option<bool>) -> Result<(), String> {
+    let should_save =
+        save.unwrap_or_else(|| !ctx.persist.save_params.read().unwrap().is_empty());
+    if should_save {
+        info!("Saving the final RDB snapshot before exiting.");
+        if let Resp::Error(e) = self::save(&[], ctx) {
+            error!("Error trying to save the DB, can't exit: {}", e);
+            return Err(e);
+        }
+        info!("DB saved on disk");
+    }
+
+    // Flush AOF before exiting so no buffered commands are lost.
+    if let Some(aof) = &ctx.aof {
+        aof.flush().await;
+    }
+
+    for entry in ctx.clients_ctx.clients.iter() {
+        if let Some(tx) = &entry.value().shutdown_tx {
+            let _ = tx.send(true);
+        }
+    }
+    info!("Redis is now ready to exit, bye bye...");
+    Ok(())
+}
+
+/// Install SIGTERM/SIGINT handlers and run [`prepare_shutdown`] when one fires.
+///
+/// The handlers are registered before this returns, so a signal sent right
+/// afterwards is never missed. The returned task completes once the shutdown
+/// sequence succeeded; a failed final save keeps the server running, like Redis.
+pub fn start_shutdown_signal_task(ctx: ServerContext) -> tokio::task::JoinHandle<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut sigterm = signal(SignalKind::terminate()).expect("failed to install SIGTERM handler");
+    let mut sigint = signal(SignalKind::interrupt()).expect("failed to install SIGINT handler");
+    tokio::spawn(async move {
+        loop {
+            let name = tokio::select! {
+                _ = sigterm.recv() => "SIGTERM",
+                _ = sigint.recv() => "SIGINT",
+            };
+            info!("Received {} scheduling shutdown...", name);
+            if prepare_shutdown(&ctx, None).await.is_ok() {
+                return;
+            }
+            error!(
+                "{} received but errors trying to shut down the server, check the logs for more information",
+                name
+            );
+        }
+    })
+}
@@ -8,18 +8,158 @@ use bytes::Bytes;
 use dashmap::DashMap;
 use mlua::prelude::*;
 use sha1::{Digest, Sha1};
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use tokio::runtime::Handle;
 use tokio::task::block_in_place;
 
 pub struct ScriptManager {
     /// SHA1 → script source cache, shared across all connections.
     pub cache: DashMap<String, String>,
+    /// Libraries loaded with FUNCTION LOAD, by library name.
+    pub libraries: RwLock<BTreeMap<String, Library>>,
+    /// `lua-time-limit` in milliseconds. A script running longer makes other
+    /// clients get BUSY until it ends or SCRIPT KILL stops it.
+    pub time_limit_ms: AtomicU64,
+    /// Scripts in progress, by the id of the client running them.
+    pub running: DashMap<u64, RunningScript>,
 }
 
-pub fn create_script_manager() -> Arc<ScriptManager> {
+pub struct RunningScript {
+    started: Instant,
+    /// Checked by the VM's instruction hook; set by SCRIPT KILL.
+    killed: Arc<AtomicBool>,
+    /// Set once the script runs a write command, which makes it unkillable.
+    wrote: bool,
+}
+
+impl ScriptManager {
+    /// Whether a script has run past `lua-time-limit`.
+    pub fn is_busy(&self) -> bool {
+        if self.running.is_empty() {
+            return false;
+        }
+        let limit = self.time_limit();
+        self.running
+            .iter()
+            .any(|script| script.started.elapsed() >= limit)
+    }
+
+    fn time_limit(&self) -> Duration {
+        Duration::from_millis(self.time_limit_ms.load(Ordering::Relaxed))
+    }
+
+    /// Sources of the loaded libraries, in name order, for RDB saves and AOF
+    /// rewrites.
+    pub fn library_codes(&self) -> Vec<String> {
+        self.libraries
+            .read()
+            .unwrap()
+            .values()
+            .map(|lib| lib.code.clone())
+            .collect()
+    }
+
+    /// Loads library sources under a FUNCTION RESTORE policy: APPEND fails
+    /// on a library that exists, REPLACE overwrites it and FLUSH drops the
+    /// loaded libraries first. Nothing changes unless every source loads.
+    pub fn restore_libraries(&self, codes: &[String], policy: &str) -> Result<(), Resp> {
+        let mut loaded = Vec::with_capacity(codes.len());
+        for code in codes {
+            loaded.push(load_library(code)?);
+        }
+
+        // Apply to a copy, so a conflict leaves the loaded libraries untouched
+        let mut libraries = self.libraries.write().unwrap();
+        let mut restored = BTreeMap::new();
+        if policy != "FLUSH" {
+            for (name, lib) in libraries.iter() {
+                let copy = Library {
+                    code: lib.code.clone(),
+                    functions: lib.functions.clone(),
+                };
+                restored.insert(name.clone(), copy);
+            }
+        }
+        for (name, lib) in loaded {
+            if policy == "APPEND" && restored.contains_key(&name) {
+                return Err(Resp::Error(format!("ERR Library {} already exists", name)));
+            }
+            check_function_names(&restored, &name, &lib)?;
+            restored.insert(name, lib);
+        }
+        *libraries = restored;
+        Ok(())
+    }
+}
+
+/// A function library: its source and the functions it registers.
+pub struct Library {
+    pub code: String,
+    /// Function names with their flags, sorted by name.
+    pub functions: Vec<(String, Vec<String>)>,
+}
+
+impl Library {
+    fn flags(&self, function: &str) -> Option<&[String]> {
+        self.functions
+            .iter()
+            .find(|(name, _)| name == function)
+            .map(|(_, flags)| flags.as_slice())
+    }
+}
+
+/// What `eval_script` runs: an EVAL body, or a function from a library.
+#[derive(Clone, Copy)]
+enum ScriptBody<'a> {
+    Eval(&'a str),
+    Function {
+        code: &'a str,
+        name: &'a str,
+        read_only: bool,
+    },
+}
+
+/// An error from `redis.register_function`, reported to the client as is.
+#[derive(Debug)]
+struct RegisterError(&'static str);
+
+impl std::fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for RegisterError {}
+
+const FUNCTION_FLAGS: [&str; 5] = [
+    "no-writes",
+    "allow-oom",
+    "allow-stale",
+    "no-cluster",
+    "allow-cross-slot-keys",
+];
+
+// Registry key of the table `redis.register_function` fills in
+const FUNCTIONS_REGISTRY: &str = "functions";
+
+// Instructions between checks for SCRIPT KILL
+const KILL_CHECK_INTERVAL: u32 = 10_000;
+
+// redis.log levels, as exposed to scripts
+const LOG_DEBUG: i64 = 0;
+const LOG_VERBOSE: i64 = 1;
+const LOG_NOTICE: i64 = 2;
+const LOG_WARNING: i64 = 3;
+
+pub fn create_script_manager(lua_time_limit: u64) -> Arc<ScriptManager> {
     Arc::new(ScriptManager {
         cache: DashMap::new(),
+        libraries: RwLock::new(BTreeMap::new()),
+        time_limit_ms: AtomicU64::new(lua_time_limit),
+        running: DashMap::new(),
     })
 }
 
@@ -29,7 +169,10 @@ pub fn calc_sha1(script: &str) -> String {
     hex::encode(hasher.finalize())
 }
 
-fn resp_to_lua<'lua>(lua: &'lua Lua, resp: &Resp) -> LuaResult<LuaValue<'lua>> {
+/// Converts a command reply into a Lua value. `protocol` is the version
+/// selected with `redis.setresp`; under RESP3 the reply types RESP2 cannot
+/// express keep their shape instead of collapsing into strings and arrays.
+fn resp_to_lua<'lua>(lua: &'lua Lua, resp: &Resp, protocol: u8) -> LuaResult<LuaValue<'lua>> {
     match resp {
         Resp::SimpleString(s) => Ok(LuaValue::String(lua.create_string(s)?)),
         Resp::Error(e) => {
@@ -43,25 +186,89 @@ fn resp_to_lua<'lua>(lua: &'lua Lua, resp: &Resp) -> LuaResult<LuaValue<'lua>> {
             Ok(LuaValue::Table(table))
         }
         Resp::Integer(i) => Ok(LuaValue::Integer(*i)),
-        Resp::BulkString(Some(b)) => Ok(LuaValue::String(lua.create_string(b)?)),
-        Resp::BulkString(None) => Ok(LuaValue::Boolean(false)),
-        Resp::Array(Some(arr)) => {
+        Resp::Verbatim(b) if protocol == 3 => {
+            let inner = lua.create_table()?;
+            inner.set("format", "txt")?;
+            inner.set("string", lua.create_string(b)?)?;
+            let table = lua.create_table()?;
+            table.set("verbatim_string", inner)?;
+            Ok(LuaValue::Table(table))
+        }
+        Resp::BulkString(Some(b)) | Resp::Verbatim(b) => {
+            Ok(LuaValue::String(lua.create_string(b)?))
+        }
+        Resp::BulkString(None) | Resp::Array(None) | Resp::Null if protocol == 3 => {
+            Ok(LuaValue::Nil)
+        }
+        Resp::BulkString(None) | Resp::Null => Ok(LuaValue::Boolean(false)),
+        Resp::Set(arr) if protocol == 3 => {
+            let members = lua.create_table()?;
+            for item in arr {
+                members.set(resp_to_lua(lua, item, protocol)?, true)?;
+            }
+            let table = lua.create_table()?;
+            table.set("set", members)?;
+            Ok(LuaValue::Table(table))
+        }
+        Resp::Array(Some(arr)) | Resp::Set(arr) | Resp::Push(arr) => {
             let table = lua.create_table()?;
             for (i, item) in arr.iter().enumerate() {
-                table.set(i + 1, resp_to_lua(lua, item)?)?;
+                table.set(i + 1, resp_to_lua(lua, item, protocol)?)?;
             }
             Ok(LuaValue::Table(table))
         }
         Resp::Array(None) => Ok(LuaValue::Boolean(false)),
+        Resp::Map(pairs) if protocol == 3 => {
+            let entries = lua.create_table()?;
+            for (key, value) in pairs {
+                entries.set(
+                    resp_to_lua(lua, key, protocol)?,
+                    resp_to_lua(lua, value, protocol)?,
+                )?;
+            }
+            let table = lua.create_table()?;
+            table.set("map", entries)?;
+            Ok(LuaValue::Table(table))
+        }
+        Resp::Map(pairs) => {
+            let table = lua.create_table()?;
+            for (i, (key, value)) in pairs.iter().enumerate() {
+                table.set(2 * i + 1, resp_to_lua(lua, key, protocol)?)?;
+                table.set(2 * i + 2, resp_to_lua(lua, value, protocol)?)?;
+            }
+            Ok(LuaValue::Table(table))
+        }
+        Resp::Double(d) if protocol == 3 => {
+            let table = lua.create_table()?;
+            table.set("double", *d)?;
+            Ok(LuaValue::Table(table))
+        }
+        Resp::Double(d) => Ok(LuaValue::String(
+            lua.create_string(crate::resp::format_double(*d))?,
+        )),
+        Resp::BigNumber(n) if protocol == 3 => {
+            let table = lua.create_table()?;
+            table.set("big_number", lua.create_string(n)?)?;
+            Ok(LuaValue::Table(table))
+        }
+        Resp::BigNumber(n) => Ok(LuaValue::String(lua.create_string(n)?)),
+        Resp::Boolean(b) if protocol == 3 => Ok(LuaValue::Boolean(*b)),
+        Resp::Boolean(b) => Ok(LuaValue::Integer(*b as i64)),
         Resp::Multiple(_) => Err(LuaError::external("Resp::Multiple not supported in Lua")),
+        // Never produced for scripts, see `reply_encoder`
+        Resp::Encoded(_) => Err(LuaError::external("encoded reply not supported in Lua")),
         Resp::NoReply | Resp::Control(_) => Ok(LuaValue::Boolean(false)),
     }
 }
 
+/// Converts a script's return value into a reply. `map`/`double`/`big_number`
+/// tables become the RESP3 types; RESP2 clients get them downgraded along
+/// with every other reply.
 fn lua_to_resp(value: LuaValue) -> Resp {
     match value {
         LuaValue::String(s) => Resp::BulkString(Some(Bytes::from(s.as_bytes().to_vec()))),
         LuaValue::Integer(i) => Resp::Integer(i),
+        // Lua numbers are truncated towards zero
         LuaValue::Number(n) => Resp::Integer(n as i64),
         LuaValue::Boolean(b) => {
             if b {
@@ -77,14 +284,26 @@ fn lua_to_resp(value: LuaValue) -> Resp {
             if let Ok(ok) = t.get::<_, String>("ok") {
                 return Resp::SimpleString(Bytes::from(ok));
             }
+            if let Ok(LuaValue::Table(map)) = t.get::<_, LuaValue>("map") {
+                let mut pairs = Vec::new();
+                for (key, value) in map.pairs::<LuaValue, LuaValue>().flatten() {
+                    pairs.push((lua_to_resp(key), lua_to_resp(value)));
+                }
+                return Resp::Map(pairs);
+            }
+            if let Ok(d) = t.get::<_, f64>("double") {
+                return Resp::Double(d);
+            }
+            if let Ok(LuaValue::String(n)) = t.get::<_, LuaValue>("big_number") {
+                return Resp::BigNumber(Bytes::from(n.as_bytes().to_vec()));
+            }
 
-            let len = t.len().unwrap_or(0) as usize;
-            let mut items = Vec::with_capacity(len);
-            for i in 1..=len {
-                if let Ok(val) = t.get::<_, LuaValue>(i) {
-                    items.push(lua_to_resp(val));
-                } else {
-                    items.push(Resp::BulkString(None));
+            // Array part only, up to the first nil
+            let mut items = Vec::new();
+            for i in 1.. {
+                match t.raw_get::<_, LuaValue>(i) {
+                    Ok(LuaValue::Nil) | Err(_) => break,
+                    Ok(val) => items.push(lua_to_resp(val)),
                 }
             }
             Resp::Array(Some(items))
@@ -98,6 +317,7 @@ async fn redis_call_handler<'lua>(
     lua: &'lua Lua,
     args: LuaMultiValue<'lua>,
     raise_error: bool,
+    read_only: bool,
     server_ctx: &ServerContext,
     conn_ctx: &ConnectionContext,
 ) -> LuaResult<LuaValue<'lua>> {
@@ -133,8 +353,30 @@ async fn redis_call_handler<'lua>(
     local_conn_ctx.authenticated = conn_ctx.authenticated;
     local_conn_ctx.current_username = conn_ctx.current_username.clone();
     local_conn_ctx.is_lua = true;
+    local_conn_ctx.protocol = conn_ctx.protocol;
 
-    let (res, _) = super::process_frame(frame, &mut local_conn_ctx, server_ctx).await;
+    let is_write = match &frame {
+        Resp::Array(Some(items)) => items
+            .first()
+            .and_then(crate::resp::as_bytes)
+            .is_some_and(|name| super::is_write_cmd(super::command_name(name))),
+        _ => false,
+    };
+    let res = if read_only && is_write {
+        Resp::StaticError("ERR Write commands are not allowed from read-only scripts.")
+    } else {
+        if let Some(mut script) = server_ctx
+            .script_manager
+            .running
+            .get_mut(&conn_ctx.id)
+            .filter(|_| is_write)
+        {
+            script.wrote = true;
+        }
+        super::process_frame(frame, &mut local_conn_ctx, server_ctx)
+            .await
+            .0
+    };
 
     if raise_error {
         match &res {
@@ -144,11 +386,148 @@ async fn redis_call_handler<'lua>(
         }
     }
 
-    resp_to_lua(lua, &res)
+    resp_to_lua(lua, &res, conn_ctx.protocol)
+}
+
+/// Builds `redis.register_function`, which records each function in the
+/// `FUNCTIONS_REGISTRY` table as `{callback = ..., flags = {...}}`.
+fn register_function_api(lua: &Lua) -> LuaResult<LuaFunction<'_>> {
+    lua.set_named_registry_value(FUNCTIONS_REGISTRY, lua.create_table()?)?;
+    lua.create_function(|lua, args: LuaMultiValue| {
+        let fail = |msg| Err(LuaError::external(RegisterError(msg)));
+        let args = args.into_vec();
+        let (name, callback, flags) = match args.as_slice() {
+            [name, callback] => (name.clone(), callback.clone(), None),
+            [LuaValue::Table(spec)] => (
+                spec.get::<_, LuaValue>("function_name")?,
+                spec.get::<_, LuaValue>("callback")?,
+                spec.get::<_, Option<LuaTable>>("flags")?,
+            ),
+            _ => return fail("ERR wrong number of arguments to redis.register_function"),
+        };
+        let LuaValue::String(name) = name else {
+            return fail("ERR function_name argument given to redis.register_function must be a string");
+        };
+        let LuaValue::Function(callback) = callback else {
+            return fail("ERR callback argument given to redis.register_function must be a function");
+        };
+        let name = name.to_str()?;
+        if !valid_name(name) {
+            return fail(
+                "ERR Function names can only contain letters, numbers, or underscores(_) and must be at least one character long",
+            );
+        }
+        let flags = match flags {
+            Some(flags) => flags.sequence_values::<String>().collect::<LuaResult<Vec<_>>>()?,
+            None => Vec::new(),
+        };
+        if flags.iter().any(|f| !FUNCTION_FLAGS.contains(&f.as_str())) {
+            return fail("ERR Unknown flag given");
+        }
+
+        let registry: LuaTable = lua.named_registry_value(FUNCTIONS_REGISTRY)?;
+        if registry.contains_key(name)? {
+            return fail("ERR Function already exists in the library");
+        }
+        let entry = lua.create_table()?;
+        entry.set("callback", callback)?;
+        entry.set("flags", flags)?;
+        registry.set(name, entry)
+    })
+}
+
+/// Library and function names are non-empty runs of `[A-Za-z0-9_]`.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// The `RegisterError` a Lua error was raised with, if any, looking through
+/// the callback traceback mlua wraps it in.
+fn register_error(e: &LuaError) -> Option<&'static str> {
+    match e {
+        LuaError::CallbackError { cause, .. } => register_error(cause),
+        LuaError::ExternalError(inner) => inner.downcast_ref::<RegisterError>().map(|r| r.0),
+        _ => None,
+    }
+}
+
+/// Splits the `#!lua name=<lib>` line off library source, returning the
+/// library name and the code with the line blanked (keeping line numbers).
+fn parse_library_header(code: &str) -> Result<(String, String), Resp> {
+    let Some(header) = code.strip_prefix("#!") else {
+        return Err(Resp::StaticError("ERR Missing library metadata"));
+    };
+    let (header, body) = header.split_once('\n').unwrap_or((header, ""));
+    let mut parts = header.split_whitespace();
+    let engine = parts.next().unwrap_or("");
+    if !engine.eq_ignore_ascii_case("lua") {
+        return Err(Resp::Error(format!("ERR Engine '{}' not found", engine)));
+    }
+    let mut name = None;
+    for part in parts {
+        match part.strip_prefix("name=") {
+            Some(n) => name = Some(n.to_string()),
+            None => {
+                return Err(Resp::Error(format!(
+                    "ERR Invalid metadata value given: {}",
+                    part
+                )));
+            }
+        }
+    }
+    let Some(name) = name else {
+        return Err(Resp::StaticError("ERR Library name was not given"));
+    };
+    if !valid_name(&name) {
+        return Err(Resp::StaticError(
+            "ERR Library names can only contain letters, numbers, or underscores(_) and must be at least one character long",
+        ));
+    }
+    Ok((name, format!("\n{}", body)))
+}
+
+/// Runs library source in a scratch VM where only `redis.register_function`
+/// is available, and collects what it registers.
+fn load_library(code: &str) -> Result<(String, Library), Resp> {
+    let (name, body) = parse_library_header(code)?;
+    let lua = Lua::new();
+    let registered = (|| -> LuaResult<Vec<(String, Vec<String>)>> {
+        let redis_table = lua.create_table()?;
+        redis_table.set("register_function", register_function_api(&lua)?)?;
+        lua.globals().set("redis", redis_table)?;
+        lua.load(body.as_str()).exec()?;
+        let registry: LuaTable = lua.named_registry_value(FUNCTIONS_REGISTRY)?;
+        let mut functions = Vec::new();
+        for pair in registry.pairs::<String, LuaTable>() {
+            let (function, entry) = pair?;
+            functions.push((function, entry.get::<_, Vec<String>>("flags")?));
+        }
+        functions.sort();
+        Ok(functions)
+    })();
+    let functions = match registered {
+        Ok(functions) => functions,
+        Err(e) => {
+            return Err(match register_error(&e) {
+                Some(msg) => Resp::StaticError(msg),
+                None => Resp::Error(format!("ERR Error registering functions: {}", e)),
+            });
+        }
+    };
+    if functions.is_empty() {
+        return Err(Resp::StaticError("ERR No functions registered"));
+    }
+    Ok((
+        name,
+        Library {
+            code: code.to_string(),
+            functions,
+        },
+    ))
 }
 
 async fn eval_script(
-    script: &str,
+    body: ScriptBody<'_>,
     items: &[Resp],
     keys_start: usize,
     keys_end: usize,
@@ -172,64 +551,247 @@ async fn eval_script(
         })
         .collect();
 
-    block_in_place(move || {
+    // Flag the caller for the duration of the script; cleared below on every
+    // outcome, since script errors surface as a reply rather than an early return.
+    conn_ctx.is_lua = true;
+    let script_conn_ctx = conn_ctx.clone();
+
+    let killed = Arc::new(AtomicBool::new(false));
+    server_ctx.script_manager.running.insert(
+        conn_ctx.id,
+        RunningScript {
+            started: Instant::now(),
+            killed: killed.clone(),
+            wrote: false,
+        },
+    );
+    let hook_killed = killed.clone();
+
+    let res = block_in_place(move || {
         // Each EVAL call gets its own Lua VM — no global lock, no serialization.
         // block_in_place + Handle::block_on is the correct mlua pattern for
         // running non-Send Lua futures inside a multi-thread Tokio runtime.
         let lua = Lua::new();
+        let read_only = matches!(
+            body,
+            ScriptBody::Function {
+                read_only: true,
+                ..
+            }
+        );
+
+        let lua_keys = lua.create_table().unwrap();
+        for (i, k) in keys.iter().enumerate() {
+            lua_keys.set(i + 1, k.as_str()).unwrap();
+        }
+        let lua_args = lua.create_table().unwrap();
+        for (i, a) in args.iter().enumerate() {
+            lua_args.set(i + 1, a.as_str()).unwrap();
+        }
 
         {
             let globals = lua.globals();
-            let lua_keys = lua.create_table().unwrap();
-            for (i, k) in keys.iter().enumerate() {
-                lua_keys.set(i + 1, k.as_str()).unwrap();
+            // Functions get KEYS and ARGV as arguments instead
+            if let ScriptBody::Eval(_) = body {
+                globals.set("KEYS", lua_keys.clone()).unwrap();
+                globals.set("ARGV", lua_args.clone()).unwrap();
             }
-            globals.set("KEYS", lua_keys).unwrap();
 
-            let lua_args = lua.create_table().unwrap();
-            for (i, a) in args.iter().enumerate() {
-                lua_args.set(i + 1, a.as_str()).unwrap();
-            }
-            globals.set("ARGV", lua_args).unwrap();
+            // Scripts start in RESP2 whatever the caller negotiated
+            let resp_version = Arc::new(AtomicU8::new(2));
 
             let server_ctx_clone = server_ctx.clone();
-            let conn_ctx_clone = conn_ctx.clone();
+            let conn_ctx_clone = script_conn_ctx.clone();
+            let resp_version_clone = resp_version.clone();
 
             let redis_call = lua
                 .create_async_function(move |lua, args| {
                     let server_ctx = server_ctx_clone.clone();
-                    let conn_ctx = conn_ctx_clone.clone();
-                    async move { redis_call_handler(lua, args, true, &server_ctx, &conn_ctx).await }
+                    let mut conn_ctx = conn_ctx_clone.clone();
+                    conn_ctx.protocol = resp_version_clone.load(Ordering::Relaxed);
+                    async move {
+                        redis_call_handler(lua, args, true, read_only, &server_ctx, &conn_ctx).await
+                    }
                 })
                 .unwrap();
 
             let server_ctx_clone = server_ctx.clone();
-            let conn_ctx_clone = conn_ctx.clone();
+            let conn_ctx_clone = script_conn_ctx.clone();
+            let resp_version_clone = resp_version.clone();
 
             let redis_pcall = lua
                 .create_async_function(move |lua, args| {
                     let server_ctx = server_ctx_clone.clone();
-                    let conn_ctx = conn_ctx_clone.clone();
+                    let mut conn_ctx = conn_ctx_clone.clone();
+                    conn_ctx.protocol = resp_version_clone.load(Ordering::Relaxed);
                     async move {
-                        redis_call_handler(lua, args, false, &server_ctx, &conn_ctx).await
+                        redis_call_handler(lua, args, false, read_only, &server_ctx, &conn_ctx)
+                            .await
+                    }
+                })
+                .unwrap();
+
+            let redis_setresp = lua
+                .create_function(move |_, version: i64| {
+                    if version != 2 && version != 3 {
+                        return Err(LuaError::external("RESP version must be 2 or 3."));
+                    }
+                    resp_version.store(version as u8, Ordering::Relaxed);
+                    Ok(())
+                })
+                .unwrap();
+
+            let redis_sha1hex = lua
+                .create_function(|_, s: LuaString| Ok(hex::encode(Sha1::digest(s.as_bytes()))))
+                .unwrap();
+
+            // Build the {ok = ...} / {err = ...} tables redis.call replies convert from
+            let redis_status_reply = lua
+                .create_function(|lua, status: LuaString| {
+                    let table = lua.create_table()?;
+                    table.set("ok", status)?;
+                    Ok(table)
+                })
+                .unwrap();
+            let redis_error_reply = lua
+                .create_function(|lua, error: LuaString| {
+                    let table = lua.create_table()?;
+                    table.set("err", error)?;
+                    Ok(table)
+                })
+                .unwrap();
+
+            let redis_log = lua
+                .create_function(|_, (level, parts): (i64, mlua::Variadic<LuaString>)| {
+                    if parts.is_empty() {
+                        return Err(LuaError::external(
+                            "redis.log() requires two arguments or more.",
+                        ));
+                    }
+                    let msg = parts
+                        .iter()
+                        .map(|p| String::from_utf8_lossy(p.as_bytes()).into_owned())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    match level {
+                        LOG_DEBUG | LOG_VERBOSE => tracing::debug!("{}", msg),
+                        LOG_NOTICE => tracing::info!("{}", msg),
+                        LOG_WARNING => tracing::warn!("{}", msg),
+                        _ => return Err(LuaError::external("Invalid debug level.")),
                     }
+                    Ok(())
                 })
                 .unwrap();
 
             let redis_table = lua.create_table().unwrap();
             redis_table.set("call", redis_call).unwrap();
             redis_table.set("pcall", redis_pcall).unwrap();
+            redis_table.set("setresp", redis_setresp).unwrap();
+            redis_table.set("sha1hex", redis_sha1hex).unwrap();
+            redis_table.set("status_reply", redis_status_reply).unwrap();
+            redis_table.set("error_reply", redis_error_reply).unwrap();
+            redis_table.set("log", redis_log).unwrap();
+            redis_table.set("LOG_DEBUG", LOG_DEBUG).unwrap();
+            redis_table.set("LOG_VERBOSE", LOG_VERBOSE).unwrap();
+            redis_table.set("LOG_NOTICE", LOG_NOTICE).unwrap();
+            redis_table.set("LOG_WARNING", LOG_WARNING).unwrap();
+            if let ScriptBody::Function { .. } = body {
+                redis_table
+                    .set("register_function", register_function_api(&lua).unwrap())
+                    .unwrap();
+            }
 
             globals.set("redis", redis_table).unwrap();
+            super::cjson::register(&lua).unwrap();
         }
 
-        Handle::current().block_on(async move {
-            match lua.load(script).eval_async::<LuaValue>().await {
+        Handle::current().block_on(async {
+            let result = match body {
+                ScriptBody::Eval(script) => match lua.load(script).into_function() {
+                    Ok(func) => call_killable(&lua, func, (), hook_killed).await,
+                    Err(e) => Err(e),
+                },
+                ScriptBody::Function { code, name, .. } => {
+                    call_function(&lua, code, name, lua_keys, lua_args, hook_killed).await
+                }
+            };
+            match result {
                 Ok(val) => lua_to_resp(val),
                 Err(e) => Resp::Error(format!("ERR error running script: {}", e)),
             }
         })
-    })
+    });
+
+    server_ctx.script_manager.running.remove(&conn_ctx.id);
+    conn_ctx.is_lua = false;
+    if killed.load(Ordering::Relaxed) {
+        return Resp::StaticError("BUSY Script killed by user with SCRIPT KILL.");
+    }
+    res
+}
+
+/// Runs `func` in its own coroutine, which fails with an error once `killed`
+/// is set. The hook goes on the coroutine itself: mlua creates coroutines from
+/// an internal thread that would not inherit one set on the VM.
+async fn call_killable<'lua>(
+    lua: &'lua Lua,
+    func: LuaFunction<'lua>,
+    args: impl IntoLuaMulti<'lua>,
+    killed: Arc<AtomicBool>,
+) -> LuaResult<LuaValue<'lua>> {
+    let thread = lua.create_thread(func)?;
+    thread.set_hook(
+        LuaHookTriggers::new().every_nth_instruction(KILL_CHECK_INTERVAL),
+        move |_, _| {
+            if killed.load(Ordering::Relaxed) {
+                return Err(LuaError::external("Script killed by user with SCRIPT KILL"));
+            }
+            Ok(())
+        },
+    );
+    thread.into_async(args).await
+}
+
+/// Loads a library into `lua` and calls its function `name` with the KEYS
+/// and ARGV tables.
+async fn call_function<'lua>(
+    lua: &'lua Lua,
+    code: &str,
+    name: &str,
+    keys: LuaTable<'lua>,
+    args: LuaTable<'lua>,
+    killed: Arc<AtomicBool>,
+) -> LuaResult<LuaValue<'lua>> {
+    let (_, body) =
+        parse_library_header(code).map_err(|_| LuaError::external("invalid library metadata"))?;
+    lua.load(body.as_str()).exec()?;
+    let registry: LuaTable = lua.named_registry_value(FUNCTIONS_REGISTRY)?;
+    let entry: LuaTable = registry.get(name)?;
+    let callback: LuaFunction = entry.get("callback")?;
+    call_killable(lua, callback, (keys, args), killed).await
+}
+
+/// Validates the `numkeys` argument of EVAL/EVALSHA against the number of
+/// trailing arguments, before any KEYS/ARGV tables are built.
+fn parse_numkeys(items: &[Resp]) -> Result<usize, Resp> {
+    let numkeys = match &items[2] {
+        Resp::BulkString(Some(b)) => std::str::from_utf8(b)
+            .ok()
+            .and_then(|s| s.parse::<i64>().ok())
+            .ok_or(Resp::StaticError(
+                "ERR value is not an integer or out of range",
+            ))?,
+        _ => return Err(Resp::StaticError("ERR invalid numkeys")),
+    };
+    if numkeys < 0 {
+        return Err(Resp::StaticError("ERR Number of keys can't be negative"));
+    }
+    if numkeys as usize > items.len() - 3 {
+        return Err(Resp::StaticError(
+            "ERR Number of keys can't be greater than number of args",
+        ));
+    }
+    Ok(numkeys as usize)
 }
 
 pub async fn eval(
@@ -249,27 +811,24 @@ pub async fn eval(
         _ => return (Resp::Error("ERR invalid script".to_string()), None),
     };
 
-    let numkeys = match &items[2] {
-        Resp::BulkString(Some(b)) => std::str::from_utf8(b)
-            .unwrap_or("0")
-            .parse::<usize>()
-            .unwrap_or(0),
-        _ => return (Resp::Error("ERR invalid numkeys".to_string()), None),
+    let numkeys = match parse_numkeys(items) {
+        Ok(n) => n,
+        Err(e) => return (e, None),
     };
 
     let keys_start = 3;
     let keys_end = keys_start + numkeys;
-    if items.len() < keys_end {
-        return (
-            Resp::Error("ERR wrong number of arguments for 'eval' command".to_string()),
-            None,
-        );
-    }
 
     let args_start = keys_end;
 
     let res = eval_script(
-        script, items, keys_start, keys_end, args_start, conn_ctx, server_ctx,
+        ScriptBody::Eval(script),
+        items,
+        keys_start,
+        keys_end,
+        args_start,
+        conn_ctx,
+        server_ctx,
     )
     .await;
     (res, None)
@@ -288,11 +847,11 @@ pub async fn evalsha(
     }
 
     let sha1 = match &items[1] {
-        Resp::BulkString(Some(b)) => std::str::from_utf8(b).unwrap_or(""),
+        Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).to_lowercase(),
         _ => return (Resp::Error("ERR invalid sha1".to_string()), None),
     };
 
-    let script = if let Some(s) = server_ctx.script_manager.cache.get(sha1) {
+    let script = if let Some(s) = server_ctx.script_manager.cache.get(&sha1) {
         s.clone()
     } else {
         return (
@@ -301,27 +860,24 @@ pub async fn evalsha(
         );
     };
 
-    let numkeys = match &items[2] {
-        Resp::BulkString(Some(b)) => std::str::from_utf8(b)
-            .unwrap_or("0")
-            .parse::<usize>()
-            .unwrap_or(0),
-        _ => return (Resp::Error("ERR invalid numkeys".to_string()), None),
+    let numkeys = match parse_numkeys(items) {
+        Ok(n) => n,
+        Err(e) => return (e, None),
     };
 
     let keys_start = 3;
     let keys_end = keys_start + numkeys;
-    if items.len() < keys_end {
-        return (
-            Resp::Error("ERR wrong number of arguments for 'evalsha' command".to_string()),
-            None,
-        );
-    }
 
     let args_start = keys_end;
 
     let res = eval_script(
-        &script, items, keys_start, keys_end, args_start, conn_ctx, server_ctx,
+        ScriptBody::Eval(&script),
+        items,
+        keys_start,
+        keys_end,
+        args_start,
+        conn_ctx,
+        server_ctx,
     )
     .await;
     (res, None)
@@ -355,6 +911,12 @@ pub fn script(items: &[Resp], script_manager: &Arc<ScriptManager>) -> Resp {
                 _ => return Resp::Error("ERR script must be a string".to_string()),
             };
 
+            // Compile only, so a broken script is rejected before it is cached
+            let lua = Lua::new();
+            if let Err(e) = lua.load(script_content).into_function() {
+                return Resp::Error(format!("ERR Error compiling script (new function): {}", e));
+            }
+
             let sha = calc_sha1(script_content);
             script_manager
                 .cache
@@ -369,11 +931,12 @@ pub fn script(items: &[Resp], script_manager: &Arc<ScriptManager>) -> Resp {
             }
             let mut results = Vec::new();
             for item in &items[2..] {
+                // Cached digests are lowercase hex
                 let sha = match item {
-                    Resp::BulkString(Some(b)) => std::str::from_utf8(b).unwrap_or(""),
-                    _ => "",
+                    Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).to_lowercase(),
+                    _ => String::new(),
                 };
-                if script_manager.cache.contains_key(sha) {
+                if script_manager.cache.contains_key(&sha) {
                     results.push(Resp::Integer(1));
                 } else {
                     results.push(Resp::Integer(0));
@@ -382,9 +945,330 @@ pub fn script(items: &[Resp], script_manager: &Arc<ScriptManager>) -> Resp {
             Resp::Array(Some(results))
         }
         "FLUSH" => {
+            // The cache is a plain map, so ASYNC and SYNC both clear it in place
+            match items.len() {
+                2 => {}
+                3 => match &items[2] {
+                    Resp::BulkString(Some(b))
+                        if b.eq_ignore_ascii_case(b"ASYNC") || b.eq_ignore_ascii_case(b"SYNC") => {}
+                    _ => return Resp::Error("ERR syntax error".to_string()),
+                },
+                _ => return Resp::Error("ERR syntax error".to_string()),
+            }
             script_manager.cache.clear();
             Resp::SimpleString(Bytes::from("OK"))
         }
+        "KILL" => {
+            if items.len() != 2 {
+                return Resp::Error(
+                    "ERR wrong number of arguments for 'script|kill' command".to_string(),
+                );
+            }
+            // Only scripts past the limit make the server busy; the others
+            // are left to finish on their own
+            let limit = script_manager.time_limit();
+            let busy: Vec<(Arc<AtomicBool>, bool)> = script_manager
+                .running
+                .iter()
+                .filter(|script| script.started.elapsed() >= limit)
+                .map(|script| (script.killed.clone(), script.wrote))
+                .collect();
+            if busy.is_empty() {
+                return Resp::StaticError("NOTBUSY No scripts in execution right now.");
+            }
+            if busy.iter().any(|(_, wrote)| *wrote) {
+                return Resp::StaticError(
+                    "UNKILLABLE Sorry the script already executed write commands against the dataset. You can either wait the script termination or kill the server in a hard way using the SHUTDOWN NOSAVE command.",
+                );
+            }
+            for (killed, _) in busy {
+                killed.store(true, Ordering::Relaxed);
+            }
+            Resp::SimpleString(Bytes::from("OK"))
+        }
         _ => Resp::Error("ERR unknown subcommand".to_string()),
     }
 }
+
+/// FCALL / FCALL_RO function numkeys [key ...] [arg ...]. FCALL_RO only runs
+/// functions flagged `no-writes`.
+pub async fn fcall(
+    items: &[Resp],
+    read_only: bool,
+    conn_ctx: &mut ConnectionContext,
+    server_ctx: &ServerContext,
+) -> (Resp, Option<Resp>) {
+    if items.len() < 3 {
+        let name = if read_only { "fcall_ro" } else { "fcall" };
+        return (
+            Resp::Error(format!(
+                "ERR wrong number of arguments for '{}' command",
+                name
+            )),
+            None,
+        );
+    }
+
+    let name = match &items[1] {
+        Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).to_string(),
+        _ => return (Resp::StaticError("ERR Function not found"), None),
+    };
+    let found = server_ctx
+        .script_manager
+        .libraries
+        .read()
+        .unwrap()
+        .values()
+        .find_map(|lib| {
+            lib.flags(&name)
+                .map(|flags| (lib.code.clone(), flags.iter().any(|f| f == "no-writes")))
+        });
+    let Some((code, no_writes)) = found else {
+        return (Resp::StaticError("ERR Function not found"), None);
+    };
+    if read_only && !no_writes {
+        return (
+            Resp::StaticError("ERR Can not execute a script with write flag using *_ro command."),
+            None,
+        );
+    }
+
+    let numkeys = match parse_numkeys(items) {
+        Ok(n) => n,
+        Err(e) => return (e, None),
+    };
+
+    let keys_start = 3;
+    let keys_end = keys_start + numkeys;
+
+    let body = ScriptBody::Function {
+        code: &code,
+        name: &name,
+        read_only: no_writes,
+    };
+    let res = eval_script(
+        body, items, keys_start, keys_end, keys_end, conn_ctx, server_ctx,
+    )
+    .await;
+    (res, None)
+}
+
+/// Fails if `lib` registers a function another library already has.
+fn check_function_names(
+    libraries: &BTreeMap<String, Library>,
+    lib_name: &str,
+    lib: &Library,
+) -> Result<(), Resp> {
+    for (other_name, other) in libraries {
+        if other_name == lib_name {
+            continue;
+        }
+        if let Some((function, _)) = lib
+            .functions
+            .iter()
+            .find(|(function, _)| other.flags(function).is_some())
+        {
+            return Err(Resp::Error(format!(
+                "ERR Function {} already exists",
+                function
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn library_reply(name: &str, lib: &Library, with_code: bool) -> Resp {
+    let bulk = |s: &str| Resp::BulkString(Some(Bytes::from(s.to_string())));
+    let functions = lib
+        .functions
+        .iter()
+        .map(|(function, flags)| {
+            Resp::Map(vec![
+                (bulk("name"), bulk(function)),
+                (bulk("description"), Resp::Null),
+                (
+                    bulk("flags"),
+                    Resp::Set(flags.iter().map(|f| bulk(f)).collect()),
+                ),
+            ])
+        })
+        .collect();
+    let mut fields = vec![
+        (bulk("library_name"), bulk(name)),
+        (bulk("engine"), bulk("LUA")),
+        (bulk("functions"), Resp::Array(Some(functions))),
+    ];
+    if with_code {
+        fields.push((bulk("library_code"), bulk(&lib.code)));
+    }
+    Resp::Map(fields)
+}
+
+/// FUNCTION LOAD/DELETE/LIST/DUMP/RESTORE/FLUSH. The subcommands that change
+/// the loaded libraries are logged, so the AOF and replicas load them too.
+pub async fn function(items: &[Resp], script_manager: &Arc<ScriptManager>) -> (Resp, Option<Resp>) {
+    if items.len() < 2 {
+        return (
+            Resp::Error("ERR wrong number of arguments for 'function' command".to_string()),
+            None,
+        );
+    }
+    let args: Vec<&[u8]> = items[1..]
+        .iter()
+        .map(|item| crate::resp::as_bytes(item).unwrap_or(b""))
+        .collect();
+    let subcommand = String::from_utf8_lossy(args[0]).to_uppercase();
+    let log = Some(Resp::Array(Some(items.to_vec())));
+
+    match subcommand.as_str() {
+        "LOAD" => {
+            let (replace, code) = match args.as_slice() {
+                [_, code] => (false, code),
+                [_, opt, code] if opt.eq_ignore_ascii_case(b"REPLACE") => (true, code),
+                [_, _, _] => return (Resp::StaticError("ERR Unknown option given"), None),
+                _ => {
+                    return (
+                        Resp::Error(
+                            "ERR wrong number of arguments for 'function|load' command".to_string(),
+                        ),
+                        None,
+                    );
+                }
+            };
+            let (name, lib) = match load_library(&String::from_utf8_lossy(code)) {
+                Ok(loaded) => loaded,
+                Err(e) => return (e, None),
+            };
+            let mut libraries = script_manager.libraries.write().unwrap();
+            if !replace && libraries.contains_key(&name) {
+                return (
+                    Resp::Error(format!("ERR Library '{}' already exists", name)),
+                    None,
+                );
+            }
+            if let Err(e) = check_function_names(&libraries, &name, &lib) {
+                return (e, None);
+            }
+            libraries.insert(name.clone(), lib);
+            (Resp::BulkString(Some(Bytes::from(name))), log)
+        }
+        "DELETE" => {
+            if args.len() != 2 {
+                return (
+                    Resp::Error(
+                        "ERR wrong number of arguments for 'function|delete' command".to_string(),
+                    ),
+                    None,
+                );
+            }
+            let name = String::from_utf8_lossy(args[1]).to_string();
+            match script_manager.libraries.write().unwrap().remove(&name) {
+                Some(_) => (Resp::SimpleString(Bytes::from("OK")), log),
+                None => (Resp::StaticError("ERR Library not found"), None),
+            }
+        }
+        "LIST" => {
+            let mut with_code = false;
+            let mut pattern: Option<&[u8]> = None;
+            let mut i = 1;
+            while i < args.len() {
+                if args[i].eq_ignore_ascii_case(b"WITHCODE") {
+                    with_code = true;
+                } else if args[i].eq_ignore_ascii_case(b"LIBRARYNAME") && i + 1 < args.len() {
+                    i += 1;
+                    pattern = Some(args[i]);
+                } else {
+                    return (
+                        Resp::Error(format!(
+                            "ERR Unknown argument {}",
+                            String::from_utf8_lossy(args[i])
+                        )),
+                        None,
+                    );
+                }
+                i += 1;
+            }
+            let libraries = script_manager.libraries.read().unwrap();
+            let reply = libraries
+                .iter()
+                .filter(|(name, _)| {
+                    pattern.is_none_or(|p| super::key::match_pattern(p, name.as_bytes()))
+                })
+                .map(|(name, lib)| library_reply(name, lib, with_code))
+                .collect();
+            (Resp::Array(Some(reply)), None)
+        }
+        "DUMP" => {
+            // The payload is a RESP array of the library sources
+            let codes = script_manager
+                .libraries
+                .read()
+                .unwrap()
+                .values()
+                .map(|lib| Resp::BulkString(Some(Bytes::from(lib.code.clone()))))
+                .collect();
+            let payload = Resp::Array(Some(codes)).as_bytes();
+            (Resp::BulkString(Some(Bytes::from(payload))), None)
+        }
+        "RESTORE" => {
+            let (payload, policy) = match args.as_slice() {
+                [_, payload] => (payload, b"APPEND".as_slice()),
+                [_, payload, policy] => (payload, *policy),
+                _ => {
+                    return (
+                        Resp::Error(
+                            "ERR wrong number of arguments for 'function|restore' command"
+                                .to_string(),
+                        ),
+                        None,
+                    );
+                }
+            };
+            let policy = String::from_utf8_lossy(policy).to_uppercase();
+            if !["APPEND", "REPLACE", "FLUSH"].contains(&policy.as_str()) {
+                return (
+                    Resp::StaticError(
+                        "ERR Wrong restore policy given, value should be either FLUSH, APPEND or REPLACE.",
+                    ),
+                    None,
+                );
+            }
+            let mut reader = tokio::io::BufReader::new(*payload);
+            let codes = match crate::resp::read_frame(&mut reader).await {
+                Ok(Some(Resp::Array(Some(codes)))) => codes,
+                _ => {
+                    return (
+                        Resp::StaticError("ERR payload version or checksum are wrong"),
+                        None,
+                    );
+                }
+            };
+            let mut sources = Vec::with_capacity(codes.len());
+            for code in &codes {
+                let Some(code) = crate::resp::as_bytes(code) else {
+                    return (
+                        Resp::StaticError("ERR payload version or checksum are wrong"),
+                        None,
+                    );
+                };
+                sources.push(String::from_utf8_lossy(code).into_owned());
+            }
+            if let Err(e) = script_manager.restore_libraries(&sources, &policy) {
+                return (e, None);
+            }
+            (Resp::SimpleString(Bytes::from("OK")), log)
+        }
+        "FLUSH" => {
+            match args.as_slice() {
+                [_] => {}
+                [_, mode]
+                    if mode.eq_ignore_ascii_case(b"ASYNC")
+                        || mode.eq_ignore_ascii_case(b"SYNC") => {}
+                _ => return (Resp::StaticError("ERR syntax error"), None),
+            }
+            script_manager.libraries.write().unwrap().clear();
+            (Resp::SimpleString(Bytes::from("OK")), log)
+        }
+        _ => (Resp::Error("ERR unknown subcommand".to_string()), None),
+    }
+}
@@ -1,6 +1,7 @@
-use crate::cmd::key::match_pattern;
+use crate::cmd::key::{match_pattern, scan_batch};
+use crate::cmd::{ConnectionContext, reply_encoder};
 use crate::db::{Db, Entry, Value};
-use crate::resp::Resp;
+use crate::resp::{ReplyKind, Resp, as_bytes};
 use bytes::Bytes;
 use rand::seq::{IndexedRandom, IteratorRandom};
 use std::collections::HashSet;
@@ -70,6 +71,10 @@ pub fn srem(items: &[Resp], db: &Db) -> Resp {
                         count += 1;
                     }
                 }
+                if set.is_empty() {
+                    drop(entry);
+                    db.remove(&key);
+                }
                 Resp::Integer(count)
             }
             _ => Resp::Error(
@@ -162,7 +167,9 @@ pub fn smismember(items: &[Resp], db: &Db) -> Resp {
     }
 }
 
-fn compute_sintersection(keys: &[Bytes], db: &Db) -> Result<HashSet<Bytes>, Resp> {
+/// Intersects the sets at `keys`, starting from the smallest. A non-zero
+/// `limit` stops the last pass once that many members have matched.
+fn compute_sintersection(keys: &[Bytes], limit: usize, db: &Db) -> Result<HashSet<Bytes>, Resp> {
     // Pass 1: Check existence, type, and find cardinalities
     let mut key_sizes: Vec<(usize, usize)> = Vec::with_capacity(keys.len());
 
@@ -211,10 +218,18 @@ fn compute_sintersection(keys: &[Bytes], db: &Db) -> Result<HashSet<Bytes>, Resp
                 return Ok(HashSet::new());
             }
             match &entry.value {
+                Value::Set(set) if key_sizes.len() == 1 && limit > 0 => {
+                    result_members = set.iter().take(limit).cloned().collect();
+                }
                 Value::Set(set) => {
                     result_members = set.clone();
                 }
-                _ => return Err(Resp::Error("WRONGTYPE".to_string())),
+                _ => {
+                    return Err(Resp::Error(
+                        "WRONGTYPE Operation against a key holding the wrong kind of value"
+                            .to_string(),
+                    ));
+                }
             }
         } else {
             return Ok(HashSet::new());
@@ -222,20 +237,33 @@ fn compute_sintersection(keys: &[Bytes], db: &Db) -> Result<HashSet<Bytes>, Resp
     }
 
     // Intersect with others
-    for (idx, _) in key_sizes.iter().skip(1) {
+    let last = key_sizes.len() - 1;
+    for (pos, (idx, _)) in key_sizes.iter().enumerate().skip(1) {
         let key = &keys[*idx];
         if let Some(entry) = db.get(key) {
             if entry.is_expired() {
                 return Ok(HashSet::new());
             }
             match &entry.value {
+                Value::Set(set) if pos == last && limit > 0 => {
+                    result_members = result_members
+                        .into_iter()
+                        .filter(|m| set.contains(m))
+                        .take(limit)
+                        .collect();
+                }
                 Value::Set(set) => {
                     result_members.retain(|m| set.contains(m));
                     if result_members.is_empty() {
                         return Ok(HashSet::new());
                     }
                 }
-                _ => return Err(Resp::Error("WRONGTYPE".to_string())),
+                _ => {
+                    return Err(Resp::Error(
+                        "WRONGTYPE Operation against a key holding the wrong kind of value"
+                            .to_string(),
+                    ));
+                }
             }
         } else {
             return Ok(HashSet::new());
@@ -245,6 +273,88 @@ fn compute_sintersection(keys: &[Bytes], db: &Db) -> Result<HashSet<Bytes>, Resp
     Ok(result_members)
 }
 
+/// Parses `numkeys key [key ...] [LIMIT limit]`, shared by SINTERCARD and
+/// ZINTERCARD. A limit of 0 means no limit.
+pub fn parse_intercard_args(items: &[Resp]) -> Result<(Vec<Bytes>, usize), Resp> {
+    let numkeys = match items.get(1).and_then(as_bytes) {
+        Some(b) => std::str::from_utf8(b)
+            .ok()
+            .and_then(|s| s.parse::<i64>().ok())
+            .filter(|n| *n > 0)
+            .ok_or(Resp::StaticError("ERR numkeys should be greater than 0"))?
+            as usize,
+        None => return Err(Resp::StaticError("ERR numkeys should be greater than 0")),
+    };
+    if numkeys > items.len() - 2 {
+        return Err(Resp::StaticError(
+            "ERR Number of keys can't be greater than number of args",
+        ));
+    }
+
+    let mut keys = Vec::with_capacity(numkeys);
+    for item in &items[2..2 + numkeys] {
+        match as_bytes(item) {
+            Some(b) => keys.push(Bytes::copy_from_slice(b)),
+            None => return Err(Resp::StaticError("ERR invalid key")),
+        }
+    }
+
+    let mut limit = 0;
+    let mut idx = 2 + numkeys;
+    while idx < items.len() {
+        let arg = as_bytes(&items[idx]).unwrap_or_default();
+        if arg.eq_ignore_ascii_case(b"LIMIT") && idx + 1 < items.len() {
+            limit = as_bytes(&items[idx + 1])
+                .and_then(|b| std::str::from_utf8(b).ok())
+                .and_then(|s| s.parse::<i64>().ok())
+                .filter(|n| *n >= 0)
+                .ok_or(Resp::StaticError("ERR LIMIT can't be negative"))?
+                as usize;
+            idx += 2;
+        } else {
+            return Err(Resp::StaticError("ERR syntax error"));
+        }
+    }
+
+    Ok((keys, limit))
+}
+
+pub fn sintercard(items: &[Resp], db: &Db) -> Resp {
+    if items.len() < 3 {
+        return Resp::Error("ERR wrong number of arguments for 'SINTERCARD'".to_string());
+    }
+    let (keys, limit) = match parse_intercard_args(items) {
+        Ok(parsed) => parsed,
+        Err(e) => return e,
+    };
+    match compute_sintersection(&keys, limit, db) {
+        Ok(members) => Resp::Integer(members.len() as i64),
+        Err(e) => e,
+    }
+}
+
+/// Writes the result of a *STORE command, deleting `destination` instead of
+/// leaving an empty set behind. Returns the stored cardinality.
+fn store_set(destination: Bytes, members: HashSet<Bytes>, db: &Db) -> Resp {
+    let count = members.len() as i64;
+    if members.is_empty() {
+        db.remove(&destination);
+    } else {
+        db.insert(destination, Entry::new(Value::Set(members), None));
+    }
+    Resp::Integer(count)
+}
+
+/// Replies with the members of a computed set as a RESP3 set.
+fn members_reply(members: HashSet<Bytes>) -> Resp {
+    Resp::Set(
+        members
+            .into_iter()
+            .map(|m| Resp::BulkString(Some(m)))
+            .collect(),
+    )
+}
+
 pub fn sinter(items: &[Resp], db: &Db) -> Resp {
     if items.len() < 2 {
         return Resp::Error("ERR wrong number of arguments for 'SINTER'".to_string());
@@ -259,14 +369,8 @@ pub fn sinter(items: &[Resp], db: &Db) -> Resp {
         }
     }
 
-    match compute_sintersection(&keys, db) {
-        Ok(members) => {
-            let mut resp_array = Vec::new();
-            for m in members {
-                resp_array.push(Resp::BulkString(Some(m)));
-            }
-            Resp::Array(Some(resp_array))
-        }
+    match compute_sintersection(&keys, 0, db) {
+        Ok(members) => members_reply(members),
         Err(e) => e,
     }
 }
@@ -291,12 +395,8 @@ pub fn sinterstore(items: &[Resp], db: &Db) -> Resp {
         }
     }
 
-    match compute_sintersection(&keys, db) {
-        Ok(members) => {
-            let count = members.len() as i64;
-            db.insert(destination, Entry::new(Value::Set(members), None));
-            Resp::Integer(count)
-        }
+    match compute_sintersection(&keys, 0, db) {
+        Ok(members) => store_set(destination, members, db),
         Err(e) => e,
     }
 }
@@ -343,13 +443,7 @@ pub fn sunion(items: &[Resp], db: &Db) -> Resp {
     }
 
     match compute_sunion(&keys, db) {
-        Ok(members) => {
-            let mut resp_array = Vec::new();
-            for m in members {
-                resp_array.push(Resp::BulkString(Some(m)));
-            }
-            Resp::Array(Some(resp_array))
-        }
+        Ok(members) => members_reply(members),
         Err(e) => e,
     }
 }
@@ -375,16 +469,24 @@ pub fn sunionstore(items: &[Resp], db: &Db) -> Resp {
     }
 
     match compute_sunion(&keys, db) {
-        Ok(members) => {
-            let count = members.len() as i64;
-            db.insert(destination, Entry::new(Value::Set(members), None));
-            Resp::Integer(count)
-        }
+        Ok(members) => store_set(destination, members, db),
         Err(e) => e,
     }
 }
 
 fn compute_sdiff(keys: &[Bytes], db: &Db) -> Result<HashSet<Bytes>, Resp> {
+    // Every key must be a set, even when an earlier one already empties the result
+    for key in keys {
+        if let Some(entry) = db.get(key)
+            && !entry.is_expired()
+            && !matches!(entry.value, Value::Set(_))
+        {
+            return Err(Resp::Error(
+                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+            ));
+        }
+    }
+
     let first_key = &keys[0];
     let mut result_members: HashSet<Bytes>;
 
@@ -448,13 +550,7 @@ pub fn sdiff(items: &[Resp], db: &Db) -> Resp {
     }
 
     match compute_sdiff(&keys, db) {
-        Ok(members) => {
-            let mut resp_array = Vec::new();
-            for m in members {
-                resp_array.push(Resp::BulkString(Some(m)));
-            }
-            Resp::Array(Some(resp_array))
-        }
+        Ok(members) => members_reply(members),
         Err(e) => e,
     }
 }
@@ -480,16 +576,12 @@ pub fn sdiffstore(items: &[Resp], db: &Db) -> Resp {
     }
 
     match compute_sdiff(&keys, db) {
-        Ok(members) => {
-            let count = members.len() as i64;
-            db.insert(destination, Entry::new(Value::Set(members), None));
-            Resp::Integer(count)
-        }
+        Ok(members) => store_set(destination, members, db),
         Err(e) => e,
     }
 }
 
-pub fn smembers(items: &[Resp], db: &Db) -> Resp {
+pub fn smembers(items: &[Resp], conn_ctx: &ConnectionContext, db: &Db) -> Resp {
     if items.len() != 2 {
         return Resp::Error("ERR wrong number of arguments for 'SMEMBERS'".to_string());
     }
@@ -503,22 +595,28 @@ pub fn smembers(items: &[Resp], db: &Db) -> Resp {
         if entry.is_expired() {
             drop(entry);
             db.remove(&key);
-            return Resp::Array(Some(vec![]));
+            return Resp::Set(vec![]);
         }
         match &entry.value {
             Value::Set(set) => {
+                if let Some(mut out) = reply_encoder(conn_ctx, ReplyKind::Set, set.len()) {
+                    for member in set {
+                        out.bulk(member);
+                    }
+                    return out.finish();
+                }
                 let mut result = Vec::with_capacity(set.len());
                 for member in set {
                     result.push(Resp::BulkString(Some(member.clone())));
                 }
-                Resp::Array(Some(result))
+                Resp::Set(result)
             }
             _ => Resp::Error(
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
             ),
         }
     } else {
-        Resp::Array(Some(vec![]))
+        Resp::Set(vec![])
     }
 }
 
@@ -560,11 +658,11 @@ pub fn sscan(items: &[Resp], db: &Db) -> Resp {
     };
 
     let cursor = match &items[2] {
-        Resp::BulkString(Some(b)) => match String::from_utf8_lossy(b).parse::<usize>() {
+        Resp::BulkString(Some(b)) => match String::from_utf8_lossy(b).parse::<u64>() {
             Ok(n) => n,
             Err(_) => return Resp::Error("ERR invalid cursor".to_string()),
         },
-        Resp::SimpleString(s) => match String::from_utf8_lossy(s).parse::<usize>() {
+        Resp::SimpleString(s) => match String::from_utf8_lossy(s).parse::<u64>() {
             Ok(n) => n,
             Err(_) => return Resp::Error("ERR invalid cursor".to_string()),
         },
@@ -630,22 +728,10 @@ pub fn sscan(items: &[Resp], db: &Db) -> Resp {
         }
 
         if let Value::Set(set) = &entry.value {
-            let mut all_members: Vec<bytes::Bytes> = set.iter().cloned().collect();
-            all_members.sort();
-
-            let total_len = all_members.len();
-            if cursor >= total_len {
-                return Resp::Array(Some(vec![
-                    Resp::BulkString(Some(Bytes::from("0"))),
-                    Resp::Array(Some(vec![])),
-                ]));
-            }
-
-            let end = std::cmp::min(cursor + count, total_len);
-            let next_cursor = if end == total_len { 0 } else { end };
+            let (batch, next_cursor) = scan_batch(set.iter(), cursor, count);
 
             let mut result_entries = Vec::new();
-            for member in &all_members[cursor..end] {
+            for member in batch {
                 if let Some(pattern) = &match_pattern_str {
                     if !match_pattern(pattern.as_bytes(), member) {
                         continue;
@@ -729,47 +815,46 @@ pub fn spop(items: &[Resp], db: &Db) -> Resp {
 
             let mut rng = rand::rng();
 
-            match count {
-                None => {
-                    if let Some(member) = set.iter().choose(&mut rng).cloned() {
+            let res = match count {
+                None => match set.iter().choose(&mut rng).cloned() {
+                    Some(member) => {
                         set.remove(&member);
-                        return Resp::BulkString(Some(member));
-                    } else {
-                        return Resp::BulkString(None);
+                        Resp::BulkString(Some(member))
                     }
-                }
+                    None => Resp::BulkString(None),
+                },
                 Some(c) => {
-                    if c == 0 {
-                        return Resp::Array(Some(Vec::new()));
-                    }
-
-                    let count_val = c as usize;
                     let members: Vec<_> = set
                         .iter()
-                        .choose_multiple(&mut rng, count_val)
+                        .choose_multiple(&mut rng, c as usize)
                         .into_iter()
                         .cloned()
                         .collect();
 
-                    let mut result = Vec::new();
+                    let mut result = Vec::with_capacity(members.len());
                     for member in members {
                         set.remove(&member);
                         result.push(Resp::BulkString(Some(member)));
                     }
-                    return Resp::Array(Some(result));
+                    Resp::Array(Some(result))
                 }
+            };
+
+            // Popping the last member deletes the key
+            if set.is_empty() {
+                drop(entry);
+                db.remove(&key);
             }
+            res
         } else {
-            return Resp::Error(
+            Resp::Error(
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            );
+            )
         }
+    } else if count.is_some() {
+        Resp::Array(Some(Vec::new()))
     } else {
-        if count.is_some() {
-            return Resp::Array(Some(Vec::new()));
-        } else {
-            return Resp::BulkString(None);
-        }
+        Resp::BulkString(None)
     }
 }
 
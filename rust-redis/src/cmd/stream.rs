@@ -7,7 +7,7 @@ use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::atomic::Ordering;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
-use tokio::time::sleep;
+use tokio::time::timeout;
 
 fn as_bytes(resp: &Resp) -> Option<Bytes> {
     match resp {
@@ -17,7 +17,115 @@ fn as_bytes(resp: &Resp) -> Option<Bytes> {
     }
 }
 
-pub fn xadd(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
+/// Approximate trimming without LIMIT removes at most this many entries per
+/// call, like Redis (100 * stream-node-max-entries).
+const DEFAULT_TRIM_LIMIT: usize = 100 * 100;
+
+enum TrimStrategy {
+    MaxLen(usize),
+    MinId(StreamID),
+}
+
+/// A `MAXLEN|MINID [=|~] threshold [LIMIT count]` clause of XADD/XTRIM.
+struct TrimSpec {
+    strategy: TrimStrategy,
+    /// Most entries one call may remove; `None` is unbounded.
+    limit: Option<usize>,
+}
+
+impl TrimSpec {
+    /// Parses the clause whose MAXLEN/MINID keyword is at `args[*idx]`,
+    /// leaving `idx` just past it.
+    fn parse(args: &[Resp], idx: &mut usize) -> Result<TrimSpec, Resp> {
+        let syntax = || Resp::Error("ERR syntax error".to_string());
+        let word = |i: usize| {
+            args.get(i)
+                .and_then(as_bytes)
+                .map(|b| String::from_utf8_lossy(&b).to_string())
+        };
+
+        let strategy_name = word(*idx).ok_or_else(syntax)?.to_uppercase();
+        *idx += 1;
+
+        let mut approximate = false;
+        match word(*idx).as_deref() {
+            Some("~") => {
+                approximate = true;
+                *idx += 1;
+            }
+            Some("=") => *idx += 1,
+            _ => {}
+        }
+
+        let threshold = word(*idx).ok_or_else(syntax)?;
+        *idx += 1;
+        let strategy = match strategy_name.as_str() {
+            "MAXLEN" => match threshold.parse::<usize>() {
+                Ok(n) => TrimStrategy::MaxLen(n),
+                Err(_) => return Err(Resp::Error("ERR invalid maxlen".to_string())),
+            },
+            "MINID" => match StreamID::from_str(&threshold) {
+                Ok(id) => TrimStrategy::MinId(id),
+                Err(_) => return Err(Resp::Error("ERR invalid minid".to_string())),
+            },
+            _ => return Err(syntax()),
+        };
+
+        let mut limit = None;
+        if word(*idx).is_some_and(|w| w.eq_ignore_ascii_case("LIMIT")) {
+            let count = word(*idx + 1).ok_or_else(syntax)?;
+            *idx += 2;
+            match count.parse::<usize>() {
+                Ok(l) => limit = Some(l),
+                Err(_) => return Err(Resp::Error("ERR invalid limit".to_string())),
+            }
+            if !approximate {
+                return Err(Resp::Error(
+                    "ERR syntax error, LIMIT cannot be used without the special ~ option"
+                        .to_string(),
+                ));
+            }
+        }
+
+        // Exact trimming is never bounded; LIMIT 0 lifts the approximate bound
+        let limit = match (approximate, limit) {
+            (false, _) | (true, Some(0)) => None,
+            (true, Some(l)) => Some(l),
+            (true, None) => Some(DEFAULT_TRIM_LIMIT),
+        };
+        Ok(TrimSpec { strategy, limit })
+    }
+
+    fn apply(&self, stream: &mut Stream) -> usize {
+        match self.strategy {
+            TrimStrategy::MaxLen(maxlen) => stream.trim_maxlen(maxlen, self.limit),
+            TrimStrategy::MinId(minid) => stream.trim_minid(minid, self.limit),
+        }
+    }
+
+    /// The exact clause matching what `apply` left in `stream`, to propagate
+    /// in place of an approximate or LIMITed one: replaying it removes the
+    /// same entries whatever the replica's trimming bounds are.
+    fn resolved_args(&self, stream: &Stream) -> Vec<Resp> {
+        let (name, threshold) = match self.strategy {
+            TrimStrategy::MaxLen(_) => ("MAXLEN", stream.len().to_string()),
+            // Everything below the oldest survivor went; with no survivor
+            // the requested threshold already covers every entry
+            TrimStrategy::MinId(minid) => ("MINID", stream.first_id().unwrap_or(minid).to_string()),
+        };
+        [name, "=", threshold.as_str()]
+            .iter()
+            .map(|a| Resp::BulkString(Some(Bytes::from(a.to_string()))))
+            .collect()
+    }
+}
+
+pub fn xadd(
+    args: &[Resp],
+    db: &Db,
+    conn_ctx: &ConnectionContext,
+    server_ctx: &ServerContext,
+) -> (Resp, Option<Resp>) {
     if args.len() < 5 {
         return (
             Resp::Error("ERR wrong number of arguments for 'xadd' command".to_string()),
@@ -32,20 +140,24 @@ pub fn xadd(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
 
     let mut arg_idx = 2;
     let mut nomkstream = false;
+    let mut trim = None;
 
-    // Parse NOMKSTREAM
-    if arg_idx < args.len() {
-        if let Some(b) = as_bytes(&args[arg_idx]) {
-            if String::from_utf8_lossy(&b).to_uppercase() == "NOMKSTREAM" {
-                nomkstream = true;
-                arg_idx += 1;
+    // Options come before the ID, in any order
+    while let Some(b) = args.get(arg_idx).and_then(as_bytes) {
+        let opt = String::from_utf8_lossy(&b).to_uppercase();
+        if opt == "NOMKSTREAM" {
+            nomkstream = true;
+            arg_idx += 1;
+        } else if opt == "MAXLEN" || opt == "MINID" {
+            match TrimSpec::parse(args, &mut arg_idx) {
+                Ok(t) => trim = Some(t),
+                Err(e) => return (e, None),
             }
+        } else {
+            break;
         }
     }
 
-    // Note: MAXLEN/MINID are not requested but often appear here.
-    // For now we only handle NOMKSTREAM and then the ID.
-
     if arg_idx >= args.len() {
         return (
             Resp::Error("ERR wrong number of arguments for 'xadd' command".to_string()),
@@ -53,6 +165,7 @@ pub fn xadd(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
         );
     }
 
+    let id_idx = arg_idx;
     let id_str = match as_bytes(&args[arg_idx]) {
         Some(b) => String::from_utf8_lossy(&b).to_string(),
         None => return (Resp::Error("ERR invalid ID".to_string()), None),
@@ -106,26 +219,25 @@ pub fn xadd(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
 
     let id = if id_str == "*" {
         let last_id = stream.last_id;
-        if last_id.ms == u64::MAX && last_id.seq == u64::MAX {
-            return (
-                Resp::Error("ERR The stream has exhausted the last possible ID".to_string()),
-                None,
-            );
-        }
-
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_millis() as u64;
 
-        if now < last_id.ms {
-            let ms = last_id.ms;
-            let seq = last_id.seq + 1;
-            StreamID::new(ms, seq)
-        } else if now == last_id.ms {
-            StreamID::new(now, last_id.seq + 1)
-        } else {
+        if now > last_id.ms {
             StreamID::new(now, 0)
+        } else {
+            match last_id.next() {
+                Some(id) => id,
+                None => {
+                    return (
+                        Resp::Error(
+                            "ERR The stream has exhausted the last possible ID".to_string(),
+                        ),
+                        None,
+                    );
+                }
+            }
         }
     } else {
         match StreamID::from_str(&id_str) {
@@ -136,21 +248,27 @@ pub fn xadd(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
 
     match stream.insert(id, entry_fields) {
         Ok(new_id) => {
+            let trim_args = trim.as_ref().map(|trim| {
+                trim.apply(&mut stream);
+                trim.resolved_args(&stream)
+            });
             db.insert(
                 key.clone(),
                 crate::db::Entry::new(Value::Stream(stream), None),
             );
+            wake_stream_readers(conn_ctx.db_index, &key, server_ctx);
 
-            // Construct log command
-            let mut log_args = Vec::with_capacity(args.len());
-            log_args.push(Resp::BulkString(Some(Bytes::from("XADD"))));
-            log_args.push(args[1].clone()); // key
-            log_args.push(Resp::BulkString(Some(Bytes::from(new_id.to_string())))); // concrete ID
-
-            // fields
-            for i in 3..args.len() {
-                log_args.push(args[i].clone());
+            // Construct log command: the options with trimming resolved, the
+            // concrete ID, then the fields
+            let mut log_args = args[..2].to_vec();
+            if nomkstream {
+                log_args.push(Resp::BulkString(Some(Bytes::from_static(b"NOMKSTREAM"))));
             }
+            if let Some(trim_args) = trim_args {
+                log_args.extend(trim_args);
+            }
+            log_args.push(Resp::BulkString(Some(Bytes::from(new_id.to_string()))));
+            log_args.extend_from_slice(&args[id_idx + 1..]);
 
             (
                 Resp::BulkString(Some(Bytes::from(new_id.to_string()))),
@@ -184,6 +302,40 @@ pub fn xlen(args: &[Resp], db: &Db) -> Resp {
     Resp::Integer(0)
 }
 
+/// Parses the bounds and `[COUNT count]` of XRANGE / XREVRANGE. A bare `ms`
+/// start means `ms-0` and a bare `ms` end means `ms-<max seq>`, so both cover
+/// the whole millisecond. A negative COUNT is treated as 0, like Redis.
+fn parse_range_args(
+    start: &Resp,
+    end: &Resp,
+    opts: &[Resp],
+) -> Result<(StreamID, StreamID, Option<usize>), Resp> {
+    let start_id = match as_bytes(start) {
+        Some(b) if b.as_ref() == b"-" => StreamID::new(0, 0),
+        Some(b) => StreamID::parse(&String::from_utf8_lossy(&b), 0)
+            .map_err(|_| Resp::Error("ERR invalid start ID".to_string()))?,
+        None => return Err(Resp::Error("ERR invalid start ID".to_string())),
+    };
+    let end_id = match as_bytes(end) {
+        Some(b) if b.as_ref() == b"+" => StreamID::new(u64::MAX, u64::MAX),
+        Some(b) => StreamID::parse(&String::from_utf8_lossy(&b), u64::MAX)
+            .map_err(|_| Resp::Error("ERR invalid end ID".to_string()))?,
+        None => return Err(Resp::Error("ERR invalid end ID".to_string())),
+    };
+
+    let count = match opts {
+        [] => None,
+        [opt, val] if as_bytes(opt).is_some_and(|o| o.eq_ignore_ascii_case(b"COUNT")) => {
+            match as_bytes(val).and_then(|v| String::from_utf8_lossy(&v).parse::<i64>().ok()) {
+                Some(c) => Some(c.max(0) as usize),
+                None => return Err(Resp::Error("ERR invalid count".to_string())),
+            }
+        }
+        _ => return Err(Resp::Error("ERR syntax error".to_string())),
+    };
+    Ok((start_id, end_id, count))
+}
+
 pub fn xrange(args: &[Resp], db: &Db) -> Resp {
     if args.len() < 4 {
         return Resp::Error("ERR wrong number of arguments for 'xrange' command".to_string());
@@ -194,62 +346,13 @@ pub fn xrange(args: &[Resp], db: &Db) -> Resp {
         None => return Resp::Error("ERR invalid key".to_string()),
     };
 
-    let start_str = match as_bytes(&args[2]) {
-        Some(b) => String::from_utf8_lossy(&b).to_string(),
-        None => return Resp::Error("ERR invalid start ID".to_string()),
-    };
-
-    let end_str = match as_bytes(&args[3]) {
-        Some(b) => String::from_utf8_lossy(&b).to_string(),
-        None => return Resp::Error("ERR invalid end ID".to_string()),
+    let (start_id, end_id, count) = match parse_range_args(&args[2], &args[3], &args[4..]) {
+        Ok(parsed) => parsed,
+        Err(e) => return e,
     };
 
-    // Parse options: [COUNT count]
-    let mut count = None;
-    if args.len() > 4 {
-        if args.len() == 6 {
-            let opt = match as_bytes(&args[4]) {
-                Some(b) => String::from_utf8_lossy(&b).to_string().to_uppercase(),
-                None => return Resp::Error("ERR syntax error".to_string()),
-            };
-            if opt == "COUNT" {
-                if let Some(val) = as_bytes(&args[5]) {
-                    if let Ok(c) = String::from_utf8_lossy(&val).parse::<usize>() {
-                        count = Some(c);
-                    } else {
-                        return Resp::Error("ERR invalid count".to_string());
-                    }
-                } else {
-                    return Resp::Error("ERR invalid count".to_string());
-                }
-            } else {
-                return Resp::Error("ERR syntax error".to_string());
-            }
-        } else {
-            return Resp::Error("ERR syntax error".to_string());
-        }
-    }
-
     if let Some(entry) = db.get(&key) {
         if let Value::Stream(stream) = &entry.value {
-            let start_id = if start_str == "-" {
-                StreamID::new(0, 0)
-            } else {
-                match StreamID::from_str(&start_str) {
-                    Ok(id) => id,
-                    Err(_) => return Resp::Error("ERR invalid start ID".to_string()),
-                }
-            };
-
-            let end_id = if end_str == "+" {
-                StreamID::new(u64::MAX, u64::MAX)
-            } else {
-                match StreamID::from_str(&end_str) {
-                    Ok(id) => id,
-                    Err(_) => return Resp::Error("ERR invalid end ID".to_string()),
-                }
-            };
-
             let entries = stream.range(&start_id, &end_id);
             let mut arr = Vec::new();
             let take_count = count.unwrap_or(entries.len());
@@ -289,70 +392,13 @@ pub fn xrevrange(args: &[Resp], db: &Db) -> Resp {
         None => return Resp::Error("ERR invalid key".to_string()),
     };
 
-    let end_str = match as_bytes(&args[2]) {
-        Some(b) => String::from_utf8_lossy(&b).to_string(),
-        None => return Resp::Error("ERR invalid end ID".to_string()),
-    };
-
-    let start_str = match as_bytes(&args[3]) {
-        Some(b) => String::from_utf8_lossy(&b).to_string(),
-        None => return Resp::Error("ERR invalid start ID".to_string()),
+    let (start_id, end_id, count) = match parse_range_args(&args[3], &args[2], &args[4..]) {
+        Ok(parsed) => parsed,
+        Err(e) => return e,
     };
 
-    // Parse options: [COUNT count]
-    let mut count = None;
-    if args.len() > 4 {
-        if args.len() == 6 {
-            let opt = match as_bytes(&args[4]) {
-                Some(b) => String::from_utf8_lossy(&b).to_string().to_uppercase(),
-                None => return Resp::Error("ERR syntax error".to_string()),
-            };
-            if opt == "COUNT" {
-                if let Some(val) = as_bytes(&args[5]) {
-                    if let Ok(c) = String::from_utf8_lossy(&val).parse::<usize>() {
-                        count = Some(c);
-                    } else {
-                        return Resp::Error("ERR invalid count".to_string());
-                    }
-                } else {
-                    return Resp::Error("ERR invalid count".to_string());
-                }
-            } else {
-                return Resp::Error("ERR syntax error".to_string());
-            }
-        } else {
-            return Resp::Error("ERR syntax error".to_string());
-        }
-    }
-
     if let Some(entry) = db.get(&key) {
         if let Value::Stream(stream) = &entry.value {
-            let start_id = if start_str == "-" {
-                StreamID::new(0, 0)
-            } else {
-                match StreamID::from_str(&start_str) {
-                    Ok(id) => id,
-                    Err(_) => return Resp::Error("ERR invalid start ID".to_string()),
-                }
-            };
-
-            let end_id = if end_str == "+" {
-                StreamID::new(u64::MAX, u64::MAX)
-            } else {
-                match StreamID::from_str(&end_str) {
-                    Ok(id) => id,
-                    Err(_) => return Resp::Error("ERR invalid end ID".to_string()),
-                }
-            };
-
-            // rev_range expects (start, end) where start <= end usually, but rev_range implementation
-            // in Stream might handle (end, start) or expects min, max.
-            // Redis XREVRANGE end start [COUNT] -> from higher ID to lower ID.
-            // My Stream::rev_range implementation takes (start, end) as (min, max) and iterates backwards?
-            // Let's check Stream::rev_range implementation.
-            // It calls rax.rev_range(&start_bytes, &end_bytes).
-            // Usually range queries take (min, max).
-
             let entries = stream.rev_range(&start_id, &end_id);
             let mut arr = Vec::new();
             let take_count = count.unwrap_or(entries.len());
@@ -642,9 +688,9 @@ pub fn xgroup(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
                 let id = if id_str == "$" {
                     stream.last_id
                 } else {
-                    match StreamID::from_str(&id_str) {
-                        Ok(id) => id,
-                        Err(_) => return (Resp::Error("ERR invalid stream ID".to_string()), None),
+                    match parse_id_default_seq(&args[4]) {
+                        Some(id) => id,
+                        None => return (Resp::Error("ERR invalid stream ID".to_string()), None),
                     }
                 };
 
@@ -697,21 +743,21 @@ pub fn xgroup(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
         if let Some(mut entry) = db.get_mut(&key) {
             if let Value::Stream(stream) = &mut entry.value {
                 if stream.groups.remove(&group_name).is_some() {
-                    return (Resp::Integer(1), None);
+                    (Resp::Integer(1), None)
                 } else {
-                    return (Resp::Integer(0), None);
+                    (Resp::Integer(0), None)
                 }
             } else {
-                return (
+                (
                     Resp::Error(
                         "WRONGTYPE Operation against a key holding the wrong kind of value"
                             .to_string(),
                     ),
                     None,
-                );
+                )
             }
         } else {
-            return (Resp::Integer(0), None);
+            (Resp::Integer(0), None)
         }
     } else if subcommand == "CREATECONSUMER" {
         if args.len() < 5 {
@@ -748,24 +794,155 @@ pub fn xgroup(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
                     for arg in args {
                         log_args.push(arg.clone());
                     }
-                    return (Resp::Integer(1), Some(Resp::Array(Some(log_args))));
+                    (Resp::Integer(1), Some(Resp::Array(Some(log_args))))
                 } else {
-                    return (
+                    (
+                        Resp::Error("NOGROUP No such consumer group".to_string()),
+                        None,
+                    )
+                }
+            } else {
+                (
+                    Resp::Error(
+                        "WRONGTYPE Operation against a key holding the wrong kind of value"
+                            .to_string(),
+                    ),
+                    None,
+                )
+            }
+        } else {
+            (Resp::Error("ERR no such key".to_string()), None)
+        }
+    } else if subcommand == "DELCONSUMER" {
+        if args.len() != 5 {
+            return (
+                Resp::Error("ERR wrong number of arguments for 'xgroup' command".to_string()),
+                None,
+            );
+        }
+        let key = match as_bytes(&args[2]) {
+            Some(b) => b,
+            None => return (Resp::Error("ERR invalid key".to_string()), None),
+        };
+        let group_name = match as_bytes(&args[3]) {
+            Some(b) => String::from_utf8_lossy(&b).to_string(),
+            None => return (Resp::Error("ERR invalid group name".to_string()), None),
+        };
+        let consumer_name = match as_bytes(&args[4]) {
+            Some(b) => String::from_utf8_lossy(&b).to_string(),
+            None => return (Resp::Error("ERR invalid consumer name".to_string()), None),
+        };
+
+        if let Some(mut entry) = db.get_mut(&key) {
+            if let Value::Stream(stream) = &mut entry.value {
+                if let Some(group) = stream.groups.get_mut(&group_name) {
+                    let consumer = match group.consumers.remove(&consumer_name) {
+                        Some(c) => c,
+                        None => return (Resp::Integer(0), None),
+                    };
+                    // Its pending entries are dropped, not handed to anyone else
+                    for id in &consumer.pending_ids {
+                        group.pel.remove(id);
+                    }
+
+                    (
+                        Resp::Integer(consumer.pending_ids.len() as i64),
+                        Some(Resp::Array(Some(args.to_vec()))),
+                    )
+                } else {
+                    (
                         Resp::Error("NOGROUP No such consumer group".to_string()),
                         None,
+                    )
+                }
+            } else {
+                (
+                    Resp::Error(
+                        "WRONGTYPE Operation against a key holding the wrong kind of value"
+                            .to_string(),
+                    ),
+                    None,
+                )
+            }
+        } else {
+            (Resp::Error("ERR no such key".to_string()), None)
+        }
+    } else if subcommand == "SETID" {
+        if args.len() != 5 && args.len() != 7 {
+            return (
+                Resp::Error("ERR wrong number of arguments for 'xgroup' command".to_string()),
+                None,
+            );
+        }
+        let key = match as_bytes(&args[2]) {
+            Some(b) => b,
+            None => return (Resp::Error("ERR invalid key".to_string()), None),
+        };
+        let group_name = match as_bytes(&args[3]) {
+            Some(b) => String::from_utf8_lossy(&b).to_string(),
+            None => return (Resp::Error("ERR invalid group name".to_string()), None),
+        };
+        let id_str = match as_bytes(&args[4]) {
+            Some(b) => String::from_utf8_lossy(&b).to_string(),
+            None => return (Resp::Error("ERR invalid ID".to_string()), None),
+        };
+
+        let mut entries_read = None;
+        if args.len() == 7 {
+            match as_bytes(&args[5]) {
+                Some(b) if b.eq_ignore_ascii_case(b"ENTRIESREAD") => {}
+                _ => return (Resp::Error("ERR syntax error".to_string()), None),
+            }
+            match as_bytes(&args[6]).and_then(|b| String::from_utf8_lossy(&b).parse::<u64>().ok()) {
+                Some(n) => entries_read = Some(n),
+                None => {
+                    return (
+                        Resp::Error("ERR value is not an integer or out of range".to_string()),
+                        None,
                     );
                 }
+            }
+        }
+
+        if let Some(mut entry) = db.get_mut(&key) {
+            if let Value::Stream(stream) = &mut entry.value {
+                let id = if id_str == "$" {
+                    stream.last_id
+                } else {
+                    match parse_id_default_seq(&args[4]) {
+                        Some(id) => id,
+                        None => return (Resp::Error("ERR invalid stream ID".to_string()), None),
+                    }
+                };
+
+                if let Some(group) = stream.groups.get_mut(&group_name) {
+                    group.last_id = id;
+                    group.entries_read = entries_read;
+
+                    // Log command with the resolved ID so replay does not depend on `$`
+                    let mut log_args = args.to_vec();
+                    log_args[4] = Resp::BulkString(Some(Bytes::from(id.to_string())));
+                    (
+                        Resp::SimpleString(Bytes::from("OK")),
+                        Some(Resp::Array(Some(log_args))),
+                    )
+                } else {
+                    (
+                        Resp::Error("NOGROUP No such consumer group".to_string()),
+                        None,
+                    )
+                }
             } else {
-                return (
+                (
                     Resp::Error(
                         "WRONGTYPE Operation against a key holding the wrong kind of value"
                             .to_string(),
                     ),
                     None,
-                );
+                )
             }
         } else {
-            return (Resp::Error("ERR no such key".to_string()), None);
+            (Resp::Error("ERR no such key".to_string()), None)
         }
     } else {
         (Resp::Error("ERR unknown subcommand".to_string()), None)
@@ -941,6 +1118,7 @@ pub fn xreadgroup(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
                                 .duration_since(UNIX_EPOCH)
                                 .unwrap()
                                 .as_millis();
+                            consumer.active_time = now;
 
                             for entry in &entries_to_process {
                                 let pe = PendingEntry {
@@ -1053,6 +1231,126 @@ pub fn xreadgroup(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
     }
 }
 
+/// Wakes every reader blocked on `key`. Readers re-register each time they
+/// find nothing new, so all of them are taken off the key here.
+fn wake_stream_readers(db_index: usize, key: &[u8], server_ctx: &ServerContext) {
+    if let Some((_, waiters)) = server_ctx.stream_waiters.remove(&(db_index, key.to_vec())) {
+        for sender in waiters {
+            // A full channel already holds a wakeup; a closed one is a reader
+            // that timed out or was served through another key
+            let _ = sender.try_send(());
+        }
+    }
+}
+
+/// The keys after STREAMS in an XREAD/XREADGROUP call; empty when the
+/// arguments are malformed, which the reader itself reports.
+fn stream_keys(args: &[Resp]) -> Vec<Bytes> {
+    let streams = args
+        .iter()
+        .position(|a| as_bytes(a).is_some_and(|b| b.eq_ignore_ascii_case(b"STREAMS")));
+    match streams {
+        Some(idx) if (args.len() - idx - 1).is_multiple_of(2) => {
+            let num_streams = (args.len() - idx - 1) / 2;
+            args[idx + 1..idx + 1 + num_streams]
+                .iter()
+                .filter_map(as_bytes)
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Pins each `$` ID of a blocking XREAD to the stream's last ID when the call
+/// starts, so entries added while it waits are the ones it returns.
+fn resolve_last_ids(args: &[Resp], db: &Db) -> Vec<Resp> {
+    let mut args = args.to_vec();
+    let keys = stream_keys(&args);
+    let first_id = args.len() - keys.len();
+    for (i, key) in keys.iter().enumerate() {
+        if as_bytes(&args[first_id + i]).as_deref() != Some(b"$".as_slice()) {
+            continue;
+        }
+        let last_id = match db.get(key) {
+            Some(entry) => match &entry.value {
+                Value::Stream(stream) => stream.last_id,
+                _ => continue,
+            },
+            None => StreamID::new(0, 0),
+        };
+        args[first_id + i] = Resp::BulkString(Some(Bytes::from(last_id.to_string())));
+    }
+    args
+}
+
+/// Runs `read` until it returns a reply, waiting between attempts for XADD
+/// to signal one of the streams in `args`. `ms == 0` waits without a
+/// deadline; `None` means the wait timed out or the server is shutting down.
+async fn block_on_streams<T>(
+    args: &[Resp],
+    ms: u64,
+    conn_ctx: &ConnectionContext,
+    server_ctx: &ServerContext,
+    mut read: impl FnMut() -> Option<T>,
+) -> Option<T> {
+    let keys = stream_keys(args);
+    let deadline = (ms > 0).then(|| Instant::now() + Duration::from_millis(ms));
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+    let (_shutdown_tx, mut shutdown_rx) = if let Some(rx) = &conn_ctx.shutdown {
+        (None, rx.clone())
+    } else {
+        let (tx, rx) = tokio::sync::watch::channel(false);
+        (Some(tx), rx)
+    };
+
+    server_ctx.clients_ctx.block_client(conn_ctx.id, ms > 0);
+    let result = loop {
+        // Register before reading, so an XADD between the read and the wait
+        // still wakes us
+        for key in &keys {
+            server_ctx
+                .stream_waiters
+                .entry((conn_ctx.db_index, key.to_vec()))
+                .or_default()
+                .push(tx.clone());
+        }
+        if let Some(out) = read() {
+            break Some(out);
+        }
+
+        let woken = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                tokio::select! {
+                    res = timeout(remaining, rx.recv()) => matches!(res, Ok(Some(()))),
+                    _ = shutdown_rx.changed() => false,
+                }
+            }
+            None => {
+                tokio::select! {
+                    res = rx.recv() => res.is_some(),
+                    _ = shutdown_rx.changed() => false,
+                }
+            }
+        };
+        if !woken {
+            break None;
+        }
+    };
+    // Leave nothing behind on keys that were never written to
+    for key in &keys {
+        server_ctx.stream_waiters.remove_if_mut(
+            &(conn_ctx.db_index, key.to_vec()),
+            |_, waiters| {
+                waiters.retain(|sender| !sender.same_channel(&tx));
+                waiters.is_empty()
+            },
+        );
+    }
+    server_ctx.clients_ctx.unblock_client(conn_ctx.id, ms > 0);
+    result
+}
+
 pub async fn xread_cmd(
     args: &[Resp],
     conn_ctx: &ConnectionContext,
@@ -1093,59 +1391,15 @@ pub async fn xread_cmd(
     match block_ms {
         None => xread(args, &db),
         Some(ms) => {
-            server_ctx
-                .clients_ctx.blocked_client_count
-                .fetch_add(1, Ordering::Relaxed);
-            let (_shutdown_tx, mut shutdown_rx) = if let Some(rx) = &conn_ctx.shutdown {
-                (None, rx.clone())
-            } else {
-                let (tx, rx) = tokio::sync::watch::channel(false);
-                (Some(tx), rx)
-            };
-
-            let result = if ms == 0 {
-                loop {
-                    let resp = xread(args, &db);
-                    match resp {
-                        Resp::BulkString(None) => {
-                            tokio::select! {
-                                _ = sleep(Duration::from_millis(10)) => {}
-                                _ = shutdown_rx.changed() => break Resp::BulkString(None),
-                            }
-                            continue;
-                        }
-                        _ => break resp,
-                    }
-                }
-            } else {
-                let deadline = Instant::now() + Duration::from_millis(ms);
-                loop {
-                    let resp = xread(args, &db);
-                    match resp {
-                        Resp::BulkString(None) => {
-                            let now = Instant::now();
-                            if now >= deadline {
-                                break Resp::BulkString(None);
-                            }
-                            let remaining = deadline - now;
-                            let sleep_dur = if remaining > Duration::from_millis(10) {
-                                Duration::from_millis(10)
-                            } else {
-                                remaining
-                            };
-                            tokio::select! {
-                                _ = sleep(sleep_dur) => {}
-                                _ = shutdown_rx.changed() => break Resp::BulkString(None),
-                            }
-                        }
-                        _ => break resp,
-                    }
+            let args = resolve_last_ids(args, &db);
+            block_on_streams(&args, ms, conn_ctx, server_ctx, || {
+                match xread(&args, &db) {
+                    Resp::BulkString(None) => None,
+                    resp => Some(resp),
                 }
-            };
-            server_ctx
-                .clients_ctx.blocked_client_count
-                .fetch_sub(1, Ordering::Relaxed);
-            result
+            })
+            .await
+            .unwrap_or(Resp::BulkString(None))
         }
     }
 }
@@ -1220,61 +1474,14 @@ pub async fn xreadgroup_cmd(
 
     match block_ms {
         None => xreadgroup(args, &db),
-        Some(ms) => {
-            server_ctx
-                .clients_ctx.blocked_client_count
-                .fetch_add(1, Ordering::Relaxed);
-            let (_shutdown_tx, mut shutdown_rx) = if let Some(rx) = &conn_ctx.shutdown {
-                (None, rx.clone())
-            } else {
-                let (tx, rx) = tokio::sync::watch::channel(false);
-                (Some(tx), rx)
-            };
-
-            let result = if ms == 0 {
-                loop {
-                    let (resp, log) = xreadgroup(args, &db);
-                    match resp {
-                        Resp::BulkString(None) => {
-                            tokio::select! {
-                                _ = sleep(Duration::from_millis(10)) => {}
-                                _ = shutdown_rx.changed() => break (Resp::BulkString(None), None),
-                            }
-                            continue;
-                        }
-                        _ => break (resp, log),
-                    }
-                }
-            } else {
-                let deadline = Instant::now() + Duration::from_millis(ms);
-                loop {
-                    let (resp, log) = xreadgroup(args, &db);
-                    match resp {
-                        Resp::BulkString(None) => {
-                            let now = Instant::now();
-                            if now >= deadline {
-                                break (Resp::BulkString(None), None);
-                            }
-                            let remaining = deadline - now;
-                            let sleep_dur = if remaining > Duration::from_millis(10) {
-                                Duration::from_millis(10)
-                            } else {
-                                remaining
-                            };
-                            tokio::select! {
-                                _ = sleep(sleep_dur) => {}
-                                _ = shutdown_rx.changed() => break (Resp::BulkString(None), None),
-                            }
-                        }
-                        _ => break (resp, log),
-                    }
-                }
-            };
-            server_ctx
-                .clients_ctx.blocked_client_count
-                .fetch_sub(1, Ordering::Relaxed);
-            result
-        }
+        Some(ms) => block_on_streams(args, ms, conn_ctx, server_ctx, || {
+            match xreadgroup(args, &db) {
+                (Resp::BulkString(None), _) => None,
+                reply => Some(reply),
+            }
+        })
+        .await
+        .unwrap_or((Resp::BulkString(None), None)),
     }
 }
 
@@ -1340,92 +1547,237 @@ pub fn xack(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
     (Resp::Integer(acked), Some(Resp::Array(Some(log_args))))
 }
 
-pub fn xtrim(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
-    if args.len() < 4 {
+/// How XDELEX/XACKDEL treat consumer group references to a deleted entry.
+#[derive(Clone, Copy, PartialEq)]
+enum RefPolicy {
+    /// Delete the entry, leave PEL references dangling (the default).
+    KeepRef,
+    /// Delete the entry and every PEL reference to it.
+    DelRef,
+    /// Delete only entries every group has read and acknowledged.
+    Acked,
+}
+
+/// Parses `[KEEPREF|DELREF|ACKED] IDS numids id [id ...]` from `args[pos..]`.
+fn parse_ref_policy_ids(args: &[Resp], mut pos: usize) -> Result<(RefPolicy, Vec<StreamID>), Resp> {
+    let mut policy = RefPolicy::KeepRef;
+    if let Some(opt) = args.get(pos).and_then(as_bytes) {
+        let parsed = match opt.to_ascii_uppercase().as_slice() {
+            b"KEEPREF" => Some(RefPolicy::KeepRef),
+            b"DELREF" => Some(RefPolicy::DelRef),
+            b"ACKED" => Some(RefPolicy::Acked),
+            _ => None,
+        };
+        if let Some(p) = parsed {
+            policy = p;
+            pos += 1;
+        }
+    }
+
+    match args.get(pos).and_then(as_bytes) {
+        Some(b) if b.eq_ignore_ascii_case(b"IDS") => pos += 1,
+        _ => return Err(Resp::Error("ERR syntax error".to_string())),
+    }
+
+    let numids = args
+        .get(pos)
+        .and_then(as_bytes)
+        .and_then(|b| String::from_utf8_lossy(&b).parse::<i64>().ok());
+    let numids = match numids {
+        Some(n) if n > 0 => n as usize,
+        _ => {
+            return Err(Resp::Error(
+                "ERR Number of IDs must be a positive integer".to_string(),
+            ));
+        }
+    };
+    pos += 1;
+    if args.len() - pos != numids {
+        return Err(Resp::Error(
+            "ERR The `numids` parameter must match the number of arguments".to_string(),
+        ));
+    }
+
+    let mut ids = Vec::with_capacity(numids);
+    for arg in &args[pos..] {
+        let id = as_bytes(arg).and_then(|b| StreamID::from_str(&String::from_utf8_lossy(&b)).ok());
+        match id {
+            Some(id) => ids.push(id),
+            None => return Err(Resp::Error("ERR invalid stream ID".to_string())),
+        }
+    }
+    Ok((policy, ids))
+}
+
+/// Deletes `id` under `policy`. Returns false when ACKED keeps the entry
+/// because a group still references it.
+fn delete_with_policy(stream: &mut Stream, id: &StreamID, policy: RefPolicy) -> bool {
+    match policy {
+        RefPolicy::Acked if stream.is_referenced(id) => return false,
+        RefPolicy::DelRef => stream.remove_references(id),
+        _ => {}
+    }
+    stream.remove(id);
+    true
+}
+
+/// Returns the reply, the command to propagate and the number of entries
+/// actually deleted. Per id: -1 not found, 1 deleted, 2 still referenced.
+pub fn xdelex(args: &[Resp], db: &Db) -> (Resp, Option<Resp>, usize) {
+    // XDELEX key [KEEPREF|DELREF|ACKED] IDS numids id [id ...]
+    if args.len() < 5 {
         return (
-            Resp::Error("ERR wrong number of arguments for 'xtrim' command".to_string()),
+            Resp::Error("ERR wrong number of arguments for 'xdelex' command".to_string()),
             None,
+            0,
         );
     }
 
     let key = match as_bytes(&args[1]) {
         Some(b) => b,
-        None => return (Resp::Error("ERR invalid key".to_string()), None),
+        None => return (Resp::Error("ERR invalid key".to_string()), None, 0),
     };
 
-    let mut arg_idx = 2;
-    let strategy = match as_bytes(&args[arg_idx]) {
-        Some(b) => String::from_utf8_lossy(&b).to_string().to_uppercase(),
-        None => return (Resp::Error("ERR syntax error".to_string()), None),
+    let (policy, ids) = match parse_ref_policy_ids(args, 2) {
+        Ok(v) => v,
+        Err(e) => return (e, None, 0),
     };
-    arg_idx += 1;
 
-    let mut _approximate = false;
-    if arg_idx < args.len() {
-        let opt = match as_bytes(&args[arg_idx]) {
-            Some(b) => String::from_utf8_lossy(&b).to_string(),
-            None => return (Resp::Error("ERR syntax error".to_string()), None),
-        };
-        if opt == "~" {
-            _approximate = true;
-            arg_idx += 1;
-        } else if opt == "=" {
-            arg_idx += 1;
+    let mut results = Vec::with_capacity(ids.len());
+    let mut deleted = 0;
+
+    if let Some(mut entry) = db.get_mut(&key) {
+        if let Value::Stream(stream) = &mut entry.value {
+            for id in &ids {
+                let code = if stream.get(id).is_none() {
+                    -1
+                } else if delete_with_policy(stream, id, policy) {
+                    deleted += 1;
+                    1
+                } else {
+                    2
+                };
+                results.push(Resp::Integer(code));
+            }
+        } else {
+            return (
+                Resp::Error(
+                    "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+                ),
+                None,
+                0,
+            );
         }
+    } else {
+        results.resize(ids.len(), Resp::Integer(-1));
     }
 
-    if arg_idx >= args.len() {
-        return (Resp::Error("ERR syntax error".to_string()), None);
+    let log = (deleted > 0).then(|| Resp::Array(Some(args.to_vec())));
+    (Resp::Array(Some(results)), log, deleted)
+}
+
+/// Acknowledges ids in `group`, then deletes them like XDELEX. Returns the
+/// reply, the command to propagate and the number of ids acknowledged.
+/// Per id: -1 not pending in the group, 1 acked and deleted, 2 acked but
+/// still referenced.
+pub fn xackdel(args: &[Resp], db: &Db) -> (Resp, Option<Resp>, usize) {
+    // XACKDEL key group [KEEPREF|DELREF|ACKED] IDS numids id [id ...]
+    if args.len() < 6 {
+        return (
+            Resp::Error("ERR wrong number of arguments for 'xackdel' command".to_string()),
+            None,
+            0,
+        );
     }
 
-    let threshold_str = match as_bytes(&args[arg_idx]) {
+    let key = match as_bytes(&args[1]) {
+        Some(b) => b,
+        None => return (Resp::Error("ERR invalid key".to_string()), None, 0),
+    };
+
+    let group_name = match as_bytes(&args[2]) {
         Some(b) => String::from_utf8_lossy(&b).to_string(),
-        None => return (Resp::Error("ERR syntax error".to_string()), None),
+        None => return (Resp::Error("ERR invalid group name".to_string()), None, 0),
     };
-    arg_idx += 1;
 
-    // Parse LIMIT if present
-    let mut _limit = None;
-    if arg_idx < args.len() {
-        let opt = match as_bytes(&args[arg_idx]) {
-            Some(b) => String::from_utf8_lossy(&b).to_string().to_uppercase(),
-            None => return (Resp::Error("ERR syntax error".to_string()), None),
-        };
-        if opt == "LIMIT" {
-            arg_idx += 1;
-            if arg_idx >= args.len() {
-                return (Resp::Error("ERR syntax error".to_string()), None);
-            }
-            if let Some(val) = as_bytes(&args[arg_idx]) {
-                if let Ok(l) = String::from_utf8_lossy(&val).parse::<usize>() {
-                    _limit = Some(l);
-                } else {
-                    return (Resp::Error("ERR invalid limit".to_string()), None);
+    let (policy, ids) = match parse_ref_policy_ids(args, 3) {
+        Ok(v) => v,
+        Err(e) => return (e, None, 0),
+    };
+
+    let no_group = || {
+        Resp::Error(format!(
+            "NOGROUP No such key '{}' or consumer group '{}'",
+            String::from_utf8_lossy(&key),
+            group_name
+        ))
+    };
+
+    let mut results = vec![Resp::Integer(-1); ids.len()];
+    let mut acked = Vec::new();
+
+    if let Some(mut entry) = db.get_mut(&key) {
+        if let Value::Stream(stream) = &mut entry.value {
+            let Some(group) = stream.groups.get_mut(&group_name) else {
+                return (no_group(), None, 0);
+            };
+            for (i, id) in ids.iter().enumerate() {
+                if let Some(pe) = group.pel.remove(id) {
+                    if let Some(consumer) = group.consumers.get_mut(&pe.owner) {
+                        consumer.pending_ids.remove(id);
+                    }
+                    acked.push(i);
                 }
-            } else {
-                return (Resp::Error("ERR invalid limit".to_string()), None);
             }
+            for &i in &acked {
+                let deleted = delete_with_policy(stream, &ids[i], policy);
+                results[i] = Resp::Integer(if deleted { 1 } else { 2 });
+            }
+        } else {
+            return (
+                Resp::Error(
+                    "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+                ),
+                None,
+                0,
+            );
         }
+    } else {
+        return (no_group(), None, 0);
+    }
+
+    let log = (!acked.is_empty()).then(|| Resp::Array(Some(args.to_vec())));
+    (Resp::Array(Some(results)), log, acked.len())
+}
+
+pub fn xtrim(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
+    if args.len() < 4 {
+        return (
+            Resp::Error("ERR wrong number of arguments for 'xtrim' command".to_string()),
+            None,
+        );
+    }
+
+    let key = match as_bytes(&args[1]) {
+        Some(b) => b,
+        None => return (Resp::Error("ERR invalid key".to_string()), None),
+    };
+
+    let mut arg_idx = 2;
+    let trim = match TrimSpec::parse(args, &mut arg_idx) {
+        Ok(t) => t,
+        Err(e) => return (e, None),
+    };
+    if arg_idx != args.len() {
+        return (Resp::Error("ERR syntax error".to_string()), None);
     }
 
     let removed;
+    let trim_args;
     if let Some(mut entry) = db.get_mut(&key) {
         if let Value::Stream(stream) = &mut entry.value {
-            if strategy == "MAXLEN" {
-                if let Ok(maxlen) = threshold_str.parse::<usize>() {
-                    removed = stream.trim_maxlen(maxlen);
-                } else {
-                    return (Resp::Error("ERR invalid maxlen".to_string()), None);
-                }
-            } else if strategy == "MINID" {
-                if let Ok(minid) = StreamID::from_str(&threshold_str) {
-                    removed = stream.trim_minid(minid);
-                } else {
-                    return (Resp::Error("ERR invalid minid".to_string()), None);
-                }
-            } else {
-                return (Resp::Error("ERR syntax error".to_string()), None);
-            }
+            removed = trim.apply(stream);
+            trim_args = trim.resolved_args(stream);
         } else {
             return (
                 Resp::Error(
@@ -1438,11 +1790,9 @@ pub fn xtrim(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
         return (Resp::Integer(0), None);
     }
 
-    // Log command
-    let mut log_args = Vec::with_capacity(args.len());
-    for arg in args {
-        log_args.push(arg.clone());
-    }
+    // Log command, with trimming resolved
+    let mut log_args = args[..2].to_vec();
+    log_args.extend(trim_args);
 
     (
         Resp::Integer(removed as i64),
@@ -1450,6 +1800,132 @@ pub fn xtrim(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
     )
 }
 
+/// Parses an XGROUP or XSETID ID, where a bare `ms` means `ms-0`.
+fn parse_id_default_seq(arg: &Resp) -> Option<StreamID> {
+    StreamID::from_str(&String::from_utf8_lossy(&as_bytes(arg)?)).ok()
+}
+
+pub fn xsetid(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
+    if args.len() < 3 {
+        return (
+            Resp::Error("ERR wrong number of arguments for 'xsetid' command".to_string()),
+            None,
+        );
+    }
+
+    let key = match as_bytes(&args[1]) {
+        Some(b) => b,
+        None => return (Resp::Error("ERR invalid key".to_string()), None),
+    };
+    let id = match parse_id_default_seq(&args[2]) {
+        Some(id) => id,
+        None => return (Resp::Error("ERR invalid stream ID".to_string()), None),
+    };
+
+    let mut entries_added = None;
+    let mut max_deleted_id = None;
+    let mut arg_idx = 3;
+    while arg_idx < args.len() {
+        let opt = match as_bytes(&args[arg_idx]) {
+            Some(b) => b.to_ascii_uppercase(),
+            None => return (Resp::Error("ERR syntax error".to_string()), None),
+        };
+        let Some(val) = args.get(arg_idx + 1) else {
+            return (Resp::Error("ERR syntax error".to_string()), None);
+        };
+        match opt.as_slice() {
+            b"ENTRIESADDED" => {
+                let n = as_bytes(val).and_then(|b| String::from_utf8_lossy(&b).parse::<u64>().ok());
+                match n {
+                    Some(n) => entries_added = Some(n),
+                    None => {
+                        return (
+                            Resp::Error("ERR entries_added must be positive".to_string()),
+                            None,
+                        );
+                    }
+                }
+            }
+            b"MAXDELETEDID" => match parse_id_default_seq(val) {
+                Some(max_id) => max_deleted_id = Some(max_id),
+                None => return (Resp::Error("ERR invalid stream ID".to_string()), None),
+            },
+            _ => return (Resp::Error("ERR syntax error".to_string()), None),
+        }
+        arg_idx += 2;
+    }
+
+    if let Some(max_id) = max_deleted_id
+        && id < max_id
+    {
+        return (
+            Resp::Error(
+                "ERR The ID specified in XSETID is smaller than the provided max_deleted_entry_id"
+                    .to_string(),
+            ),
+            None,
+        );
+    }
+
+    let mut entry = match db.get_mut(&key) {
+        Some(entry) => entry,
+        None => return (Resp::Error("ERR no such key".to_string()), None),
+    };
+    let stream = match &mut entry.value {
+        Value::Stream(s) => s,
+        _ => {
+            return (
+                Resp::Error(
+                    "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+                ),
+                None,
+            );
+        }
+    };
+
+    if let Some(n) = entries_added
+        && n < stream.len() as u64
+    {
+        return (
+            Resp::Error(
+                "ERR The entries_added specified in XSETID is smaller than the target stream length"
+                    .to_string(),
+            ),
+            None,
+        );
+    }
+    // Only entries still present pin the ID; deleted ones may be skipped over
+    if stream.top_id().is_some_and(|top| id < top) {
+        return (
+            Resp::Error(
+                "ERR The ID specified in XSETID is smaller than the target stream top item"
+                    .to_string(),
+            ),
+            None,
+        );
+    }
+
+    stream.last_id = id;
+    if let Some(n) = entries_added {
+        stream.entries_added = n;
+    }
+    if let Some(max_id) = max_deleted_id {
+        stream.max_deleted_id = max_id;
+    }
+
+    // Propagate the resolved state so replicas and the AOF match exactly
+    let log = Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from_static(b"XSETID"))),
+        Resp::BulkString(Some(key)),
+        Resp::BulkString(Some(Bytes::from(id.to_string()))),
+        Resp::BulkString(Some(Bytes::from_static(b"ENTRIESADDED"))),
+        Resp::BulkString(Some(Bytes::from(stream.entries_added.to_string()))),
+        Resp::BulkString(Some(Bytes::from_static(b"MAXDELETEDID"))),
+        Resp::BulkString(Some(Bytes::from(stream.max_deleted_id.to_string()))),
+    ]));
+    (Resp::SimpleString(Bytes::from_static(b"OK")), Some(log))
+}
+
 pub fn xinfo(args: &[Resp], db: &Db) -> Resp {
     if args.len() < 3 {
         return Resp::Error("ERR wrong number of arguments for 'xinfo' command".to_string());
@@ -1557,11 +2033,18 @@ pub fn xinfo(args: &[Resp], db: &Db) -> Resp {
                                 .unwrap()
                                 .as_millis();
                             let idle = if consumer.seen_time > 0 {
-                                now - consumer.seen_time
+                                now.saturating_sub(consumer.seen_time)
                             } else {
                                 0
                             };
                             c_res.push(Resp::Integer(idle as i64));
+                            c_res.push(Resp::SimpleString(Bytes::from("inactive")));
+                            let inactive = if consumer.active_time > 0 {
+                                now.saturating_sub(consumer.active_time) as i64
+                            } else {
+                                -1
+                            };
+                            c_res.push(Resp::Integer(inactive));
                             res.push(Resp::Array(Some(c_res)));
                         }
                         Resp::Array(Some(res))
@@ -1637,7 +2120,7 @@ pub fn xpending(args: &[Resp], db: &Db) -> Resp {
                         }
                         res.push(Resp::Array(Some(consumers_arr)));
                     }
-                    return Resp::Array(Some(res));
+                    Resp::Array(Some(res))
                 } else {
                     // Detailed form: XPENDING key group [IDLE min-idle-time] start end count [consumer]
                     let mut arg_idx = 3;
@@ -1686,7 +2169,7 @@ pub fn xpending(args: &[Resp], db: &Db) -> Resp {
                     let end_id = if end_str == "+" {
                         StreamID::new(u64::MAX, u64::MAX)
                     } else {
-                        match StreamID::from_str(&end_str) {
+                        match StreamID::parse(&end_str, u64::MAX) {
                             Ok(id) => id,
                             Err(_) => return Resp::Error("ERR invalid end ID".to_string()),
                         }
@@ -1744,29 +2227,29 @@ pub fn xpending(args: &[Resp], db: &Db) -> Resp {
                         entry_arr.push(Resp::Integer(pe.delivery_count as i64));
                         res_arr.push(Resp::Array(Some(entry_arr)));
                     }
-                    return Resp::Array(Some(res_arr));
+                    Resp::Array(Some(res_arr))
                 }
             } else {
-                return Resp::Error(format!(
+                Resp::Error(format!(
                     "NOGROUP No such key '{}' or consumer group '{}' in key '{}'",
                     String::from_utf8_lossy(&key),
                     group_name,
                     String::from_utf8_lossy(&key)
-                ));
+                ))
             }
         } else {
-            return Resp::Error(
+            Resp::Error(
                 "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            );
+            )
         }
     } else {
         // Redis behavior for XPENDING on non-existent key is NOGROUP if group name is provided.
-        return Resp::Error(format!(
+        Resp::Error(format!(
             "NOGROUP No such key '{}' or consumer group '{}' in key '{}'",
             String::from_utf8_lossy(&key),
             group_name,
             String::from_utf8_lossy(&key)
-        ));
+        ))
     }
 }
 
@@ -1955,6 +2438,7 @@ pub fn xclaim(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
                             let consumer = group.consumers.get_mut(&consumer_name).unwrap();
                             consumer.pending_ids.insert(id);
                             consumer.seen_time = now;
+                            consumer.active_time = now;
 
                             // Add to results
                             if justid {
@@ -2129,6 +2613,7 @@ pub fn xautoclaim(args: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
                         let consumer = group.consumers.get_mut(&consumer_name).unwrap();
                         consumer.pending_ids.insert(id);
                         consumer.seen_time = now;
+                        consumer.active_time = now;
 
                         if justid {
                             claimed_entries
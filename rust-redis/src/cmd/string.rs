@@ -1,8 +1,39 @@
+use crate::cmd::expiry::{
+    expiry_from_abs_ms, expiry_from_abs_secs, expiry_from_relative_ms, expiry_from_relative_secs,
+    invalid_expire_time, parse_expire_arg,
+};
 use crate::db::{Db, Entry, Value};
-use crate::resp::{Resp, as_bytes};
+use crate::resp::{Resp, as_bytes, format_double};
 use bytes::Bytes;
+use dashmap::mapref::entry::Entry as MapEntry;
 use std::time::Duration;
 
+fn is_expire_option(opt: &[u8]) -> bool {
+    [&b"EX"[..], b"PX", b"EXAT", b"PXAT"]
+        .iter()
+        .any(|o| opt.eq_ignore_ascii_case(o))
+}
+
+/// Resolves the EX/PX/EXAT/PXAT option of SET and GETEX to an absolute unix
+/// time in ms. As in Redis the value must be positive.
+fn parse_expire_option(opt: &[u8], arg: &Resp, cmd: &Resp) -> Result<u64, Resp> {
+    let cmd = String::from_utf8_lossy(as_bytes(cmd).unwrap_or_default()).to_lowercase();
+    let v = parse_expire_arg(arg)?;
+    if v <= 0 {
+        return Err(invalid_expire_time(&cmd));
+    }
+    let at = if opt.eq_ignore_ascii_case(b"EX") {
+        expiry_from_relative_secs(v, &cmd)?
+    } else if opt.eq_ignore_ascii_case(b"PX") {
+        expiry_from_relative_ms(v, &cmd)?
+    } else if opt.eq_ignore_ascii_case(b"EXAT") {
+        expiry_from_abs_secs(v, &cmd)?
+    } else {
+        expiry_from_abs_ms(v, &cmd)?
+    };
+    Ok(at as u64)
+}
+
 pub fn set(items: &[Resp], db: &Db) -> Resp {
     if items.len() < 3 {
         return Resp::StaticError("ERR wrong number of arguments for 'SET'");
@@ -40,93 +71,18 @@ pub fn set(items: &[Resp], db: &Db) -> Resp {
                 }
                 keepttl = true;
                 expire_flag = true;
-            } else if arg.eq_ignore_ascii_case(b"EX") {
-                if expire_flag {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                if i + 1 >= items.len() {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                if let Some(s) = as_bytes(&items[i + 1]) {
-                    if let Ok(s) = std::str::from_utf8(s) {
-                        if let Ok(v) = s.parse::<u64>() {
-                            expire_at = Some(crate::clock::now_ms() + v * 1000);
-                            expire_flag = true;
-                        } else {
-                            return Resp::StaticError(
-                                "ERR value is not an integer or out of range",
-                            );
-                        }
-                    } else {
-                        return Resp::StaticError("ERR value is not an integer or out of range");
-                    }
-                }
-                i += 1;
-            } else if arg.eq_ignore_ascii_case(b"PX") {
-                if expire_flag {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                if i + 1 >= items.len() {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                if let Some(s) = as_bytes(&items[i + 1]) {
-                    if let Ok(s) = std::str::from_utf8(s) {
-                        if let Ok(v) = s.parse::<u64>() {
-                            expire_at = Some(crate::clock::now_ms() + v);
-                            expire_flag = true;
-                        } else {
-                            return Resp::StaticError(
-                                "ERR value is not an integer or out of range",
-                            );
-                        }
-                    } else {
-                        return Resp::StaticError("ERR value is not an integer or out of range");
-                    }
-                }
-                i += 1;
-            } else if arg.eq_ignore_ascii_case(b"EXAT") {
-                if expire_flag {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                if i + 1 >= items.len() {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                if let Some(s) = as_bytes(&items[i + 1]) {
-                    if let Ok(s) = std::str::from_utf8(s) {
-                        if let Ok(v) = s.parse::<u64>() {
-                            expire_at = Some(v * 1000);
-                            expire_flag = true;
-                        } else {
-                            return Resp::StaticError(
-                                "ERR value is not an integer or out of range",
-                            );
-                        }
-                    } else {
-                        return Resp::StaticError("ERR value is not an integer or out of range");
-                    }
-                }
-                i += 1;
-            } else if arg.eq_ignore_ascii_case(b"PXAT") {
+            } else if is_expire_option(arg) {
                 if expire_flag {
                     return Resp::StaticError("ERR syntax error");
                 }
-                if i + 1 >= items.len() {
+                let Some(ttl) = items.get(i + 1) else {
                     return Resp::StaticError("ERR syntax error");
+                };
+                match parse_expire_option(arg, ttl, &items[0]) {
+                    Ok(at) => expire_at = Some(at),
+                    Err(e) => return e,
                 }
-                if let Some(s) = as_bytes(&items[i + 1]) {
-                    if let Ok(s) = std::str::from_utf8(s) {
-                        if let Ok(v) = s.parse::<u64>() {
-                            expire_at = Some(v);
-                            expire_flag = true;
-                        } else {
-                            return Resp::StaticError(
-                                "ERR value is not an integer or out of range",
-                            );
-                        }
-                    } else {
-                        return Resp::StaticError("ERR value is not an integer or out of range");
-                    }
-                }
+                expire_flag = true;
                 i += 1;
             } else if arg.eq_ignore_ascii_case(b"PERSIST") {
                 if expire_flag {
@@ -141,62 +97,33 @@ pub fn set(items: &[Resp], db: &Db) -> Resp {
         i += 1;
     }
 
-    let mut old_val = None;
-
-    if get {
-        if let Some(entry) = db.get(&key) {
-            if entry.is_expired() {
-                // expired, return nil
-            } else {
-                match &entry.value {
-                    Value::String(s) => old_val = Some(s.clone()),
-                    _ => {
-                        return Resp::StaticError(
-                            "WRONGTYPE Operation against a key holding the wrong kind of value",
-                        );
-                    }
-                }
-            }
-        }
-    }
-
-    if nx {
-        if let Some(entry) = db.get(&key) {
-            if !entry.is_expired() {
-                if get {
-                    return Resp::BulkString(old_val);
-                }
-                return Resp::BulkString(None);
-            }
-        }
-    }
-
-    if xx {
-        if let Some(entry) = db.get(&key) {
-            if entry.is_expired() {
-                if get {
-                    return Resp::BulkString(old_val);
-                }
-                return Resp::BulkString(None);
-            }
-        } else {
-            if get {
-                return Resp::BulkString(old_val);
-            }
-            return Resp::BulkString(None);
+    // Type check, NX/XX condition, KEEPTTL lookup and write share one shard
+    // lock, so GET returns exactly the value being replaced
+    let slot = db.entry(key);
+    let current = match &slot {
+        MapEntry::Occupied(e) if !e.get().is_expired() => Some(e.get()),
+        _ => None,
+    };
+    let old_val = match current.map(|e| &e.value) {
+        Some(Value::String(s)) => Some(s.clone()),
+        Some(_) if get => {
+            return Resp::StaticError(
+                "WRONGTYPE Operation against a key holding the wrong kind of value",
+            );
         }
+        _ => None,
+    };
+    let exists = current.is_some();
+    if keepttl {
+        expire_at = current.and_then(|e| e.expires_at);
     }
 
-    // If KEEPTTL, get existing ttl
-    if keepttl {
-        if let Some(entry) = db.get(&key) {
-            if !entry.is_expired() {
-                expire_at = entry.expires_at;
-            }
-        }
+    // With GET, a skipped write still replies with the current value
+    if (nx && exists) || (xx && !exists) {
+        return Resp::BulkString(if get { old_val } else { None });
     }
 
-    db.insert(key, Entry::new_with_expire(Value::String(val), expire_at));
+    slot.insert(Entry::new_with_expire(Value::String(val), expire_at));
 
     if get {
         Resp::BulkString(old_val)
@@ -462,7 +389,7 @@ pub fn setex(items: &[Resp], db: &Db) -> Resp {
     // SET key val EX seconds
     // args: SETEX key seconds val -> SET key val EX seconds
     let mut new_items = Vec::with_capacity(5);
-    new_items.push(Resp::BulkString(Some(Bytes::from_static(b"SET"))));
+    new_items.push(items[0].clone()); // named in expire time errors
     new_items.push(items[1].clone()); // key
     new_items.push(items[3].clone()); // val
     new_items.push(Resp::BulkString(Some(Bytes::from_static(b"EX"))));
@@ -478,7 +405,7 @@ pub fn psetex(items: &[Resp], db: &Db) -> Resp {
     // SET key val PX milliseconds
     // args: PSETEX key milliseconds val -> SET key val PX milliseconds
     let mut new_items = Vec::with_capacity(5);
-    new_items.push(Resp::BulkString(Some(Bytes::from_static(b"SET"))));
+    new_items.push(items[0].clone()); // named in expire time errors
     new_items.push(items[1].clone()); // key
     new_items.push(items[3].clone()); // val
     new_items.push(Resp::BulkString(Some(Bytes::from_static(b"PX"))));
@@ -528,46 +455,54 @@ pub fn getset(items: &[Resp], db: &Db) -> Resp {
     set(&new_items, db)
 }
 
-pub fn getdel(items: &[Resp], db: &Db) -> Resp {
+pub fn getdel(items: &[Resp], db: &Db) -> (Resp, Option<Bytes>) {
     if items.len() != 2 {
-        return Resp::StaticError("ERR wrong number of arguments for 'GETDEL'");
+        return (
+            Resp::StaticError("ERR wrong number of arguments for 'GETDEL'"),
+            None,
+        );
     }
     let key = match &items[1] {
         Resp::BulkString(Some(b)) => b.clone(),
         Resp::SimpleString(s) => s.clone(),
-        _ => return Resp::StaticError("ERR invalid key"),
+        _ => return (Resp::StaticError("ERR invalid key"), None),
     };
 
+    // Type check and removal happen under the same shard lock
+    let removed = db.remove_if(&key, |_, entry| {
+        !entry.is_expired() && matches!(entry.value, Value::String(_))
+    });
+    if let Some((key, entry)) = removed
+        && let Value::String(s) = entry.value
+    {
+        return (Resp::BulkString(Some(s)), Some(key));
+    }
+
     if let Some(entry) = db.get(&key) {
         if entry.is_expired() {
             drop(entry);
             db.remove(&key);
-            return Resp::BulkString(None);
-        }
-        match &entry.value {
-            Value::String(s) => {
-                let res = Resp::BulkString(Some(s.clone()));
-                drop(entry);
-                db.remove(&key);
-                res
-            }
-            _ => Resp::StaticError(
-                "WRONGTYPE Operation against a key holding the wrong kind of value",
-            ),
+            return (Resp::BulkString(None), None);
         }
-    } else {
-        Resp::BulkString(None)
+        return (
+            Resp::StaticError("WRONGTYPE Operation against a key holding the wrong kind of value"),
+            None,
+        );
     }
+    (Resp::BulkString(None), None)
 }
 
-pub fn getex(items: &[Resp], db: &Db) -> Resp {
+pub fn getex(items: &[Resp], db: &Db) -> (Resp, Option<Resp>) {
     if items.len() < 2 {
-        return Resp::StaticError("ERR wrong number of arguments for 'GETEX'");
+        return (
+            Resp::StaticError("ERR wrong number of arguments for 'GETEX'"),
+            None,
+        );
     }
     let key = match &items[1] {
         Resp::BulkString(Some(b)) => b.clone(),
         Resp::SimpleString(s) => s.clone(),
-        _ => return Resp::StaticError("ERR invalid key"),
+        _ => return (Resp::StaticError("ERR invalid key"), None),
     };
 
     // Parse options
@@ -578,102 +513,27 @@ pub fn getex(items: &[Resp], db: &Db) -> Resp {
     let mut i = 2;
     while i < items.len() {
         if let Some(arg) = as_bytes(&items[i]) {
-            if arg.eq_ignore_ascii_case(b"EX") {
-                if expire_set {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                if i + 1 >= items.len() {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                if let Some(s) = as_bytes(&items[i + 1]) {
-                    if let Ok(s) = std::str::from_utf8(s) {
-                        if let Ok(v) = s.parse::<u64>() {
-                            expire_at = Some(crate::clock::now_ms() + v * 1000);
-                            expire_set = true;
-                        } else {
-                            return Resp::StaticError(
-                                "ERR value is not an integer or out of range",
-                            );
-                        }
-                    } else {
-                        return Resp::StaticError("ERR value is not an integer or out of range");
-                    }
-                }
-                i += 1;
-            } else if arg.eq_ignore_ascii_case(b"PX") {
-                if expire_set {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                if i + 1 >= items.len() {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                if let Some(s) = as_bytes(&items[i + 1]) {
-                    if let Ok(s) = std::str::from_utf8(s) {
-                        if let Ok(v) = s.parse::<u64>() {
-                            expire_at = Some(crate::clock::now_ms() + v);
-                            expire_set = true;
-                        } else {
-                            return Resp::StaticError(
-                                "ERR value is not an integer or out of range",
-                            );
-                        }
-                    } else {
-                        return Resp::StaticError("ERR value is not an integer or out of range");
-                    }
-                }
-                i += 1;
-            } else if arg.eq_ignore_ascii_case(b"EXAT") {
+            if is_expire_option(arg) {
                 if expire_set {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                if i + 1 >= items.len() {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                if let Some(s) = as_bytes(&items[i + 1]) {
-                    if let Ok(s) = std::str::from_utf8(s) {
-                        if let Ok(v) = s.parse::<u64>() {
-                            expire_at = Some(v * 1000);
-                            expire_set = true;
-                        } else {
-                            return Resp::StaticError(
-                                "ERR value is not an integer or out of range",
-                            );
-                        }
-                    } else {
-                        return Resp::StaticError("ERR value is not an integer or out of range");
-                    }
+                    return (Resp::StaticError("ERR syntax error"), None);
                 }
-                i += 1;
-            } else if arg.eq_ignore_ascii_case(b"PXAT") {
-                if expire_set {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                if i + 1 >= items.len() {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                if let Some(s) = as_bytes(&items[i + 1]) {
-                    if let Ok(s) = std::str::from_utf8(s) {
-                        if let Ok(v) = s.parse::<u64>() {
-                            expire_at = Some(v);
-                            expire_set = true;
-                        } else {
-                            return Resp::StaticError(
-                                "ERR value is not an integer or out of range",
-                            );
-                        }
-                    } else {
-                        return Resp::StaticError("ERR value is not an integer or out of range");
-                    }
+                let Some(ttl) = items.get(i + 1) else {
+                    return (Resp::StaticError("ERR syntax error"), None);
+                };
+                match parse_expire_option(arg, ttl, &items[0]) {
+                    Ok(at) => expire_at = Some(at),
+                    Err(e) => return (e, None),
                 }
+                expire_set = true;
                 i += 1;
             } else if arg.eq_ignore_ascii_case(b"PERSIST") {
                 if expire_set {
-                    return Resp::StaticError("ERR syntax error");
+                    return (Resp::StaticError("ERR syntax error"), None);
                 }
                 persist = true;
                 expire_set = true;
             } else {
-                return Resp::StaticError("ERR syntax error");
+                return (Resp::StaticError("ERR syntax error"), None);
             }
         }
         i += 1;
@@ -683,26 +543,41 @@ pub fn getex(items: &[Resp], db: &Db) -> Resp {
         if entry.is_expired() {
             drop(entry);
             db.remove(&key);
-            return Resp::BulkString(None);
+            return (Resp::BulkString(None), None);
         }
         match &entry.value {
             Value::String(s) => {
                 let val = s.clone();
-                if expire_set {
-                    if persist {
-                        entry.expires_at = None;
-                    } else {
-                        entry.expires_at = expire_at;
-                    }
-                }
-                Resp::BulkString(Some(val))
+                // Only a TTL change is propagated, as an absolute PEXPIREAT
+                // or a PERSIST; without options GETEX is a plain read.
+                let log = if persist {
+                    entry.expires_at.take().map(|_| {
+                        Resp::Array(Some(vec![
+                            Resp::BulkString(Some(Bytes::from_static(b"PERSIST"))),
+                            Resp::BulkString(Some(key.clone())),
+                        ]))
+                    })
+                } else if let Some(at) = expire_at {
+                    entry.expires_at = Some(at);
+                    Some(Resp::Array(Some(vec![
+                        Resp::BulkString(Some(Bytes::from_static(b"PEXPIREAT"))),
+                        Resp::BulkString(Some(key.clone())),
+                        Resp::BulkString(Some(Bytes::from(at.to_string()))),
+                    ])))
+                } else {
+                    None
+                };
+                (Resp::BulkString(Some(val)), log)
             }
-            _ => Resp::StaticError(
-                "WRONGTYPE Operation against a key holding the wrong kind of value",
+            _ => (
+                Resp::StaticError(
+                    "WRONGTYPE Operation against a key holding the wrong kind of value",
+                ),
+                None,
             ),
         }
     } else {
-        Resp::BulkString(None)
+        (Resp::BulkString(None), None)
     }
 }
 
@@ -833,7 +708,7 @@ pub fn incrbyfloat(items: &[Resp], db: &Db) -> Resp {
         return Resp::StaticError("ERR increment would produce NaN or Infinity");
     }
 
-    let new_val_str = new_val.to_string();
+    let new_val_str = format_double(new_val);
     db.insert(
         key,
         Entry::new_with_expire(Value::String(Bytes::from(new_val_str.clone())), expire_at),
@@ -1058,6 +933,85 @@ pub fn setrange(items: &[Resp], db: &Db) -> Resp {
     }
 }
 
+/// The LEN / IDX / MINMATCHLEN / WITHMATCHLEN options shared by LCS and
+/// STRALGO LCS.
+#[derive(Default)]
+struct LcsOptions {
+    len: bool,
+    idx: bool,
+    min_match_len: usize,
+    with_match_len: bool,
+}
+
+impl LcsOptions {
+    /// Parses the option at `items[i]`, returning how many arguments it took.
+    fn parse_at(&mut self, items: &[Resp], i: usize) -> Result<usize, Resp> {
+        let arg = as_bytes(&items[i]).ok_or(Resp::StaticError("ERR syntax error"))?;
+        if arg.eq_ignore_ascii_case(b"LEN") {
+            self.len = true;
+        } else if arg.eq_ignore_ascii_case(b"IDX") {
+            self.idx = true;
+        } else if arg.eq_ignore_ascii_case(b"WITHMATCHLEN") {
+            self.with_match_len = true;
+        } else if arg.eq_ignore_ascii_case(b"MINMATCHLEN") || arg.eq_ignore_ascii_case(b"MINLEN") {
+            let len = items
+                .get(i + 1)
+                .ok_or(Resp::StaticError("ERR syntax error"))?;
+            // Redis clamps a negative length to 0
+            self.min_match_len = as_bytes(len)
+                .and_then(|b| std::str::from_utf8(b).ok())
+                .and_then(|s| s.parse::<i64>().ok())
+                .ok_or(Resp::StaticError("ERR minmatchlen is not an integer"))?
+                .max(0) as usize;
+            return Ok(2);
+        } else {
+            return Err(Resp::StaticError("ERR syntax error"));
+        }
+        Ok(1)
+    }
+}
+
+/// The string value of `key` for LCS; a missing key reads as empty.
+fn lcs_operand(db: &Db, key: &[u8]) -> Result<Bytes, Resp> {
+    match db.get(key) {
+        Some(entry) if !entry.is_expired() => match &entry.value {
+            Value::String(b) => Ok(b.clone()),
+            _ => Err(Resp::StaticError(
+                "ERR The specified keys must contain string values",
+            )),
+        },
+        _ => Ok(Bytes::new()),
+    }
+}
+
+/// LCS key1 key2 [LEN] [IDX] [MINMATCHLEN len] [WITHMATCHLEN]
+pub fn lcs(items: &[Resp], db: &Db) -> Resp {
+    if items.len() < 3 {
+        return Resp::StaticError("ERR wrong number of arguments for 'lcs' command");
+    }
+    let mut operands = Vec::with_capacity(2);
+    for item in &items[1..3] {
+        let key = match as_bytes(item) {
+            Some(key) => key,
+            None => return Resp::StaticError("ERR invalid key"),
+        };
+        match lcs_operand(db, key) {
+            Ok(s) => operands.push(s),
+            Err(e) => return e,
+        }
+    }
+
+    let mut opts = LcsOptions::default();
+    let mut i = 3;
+    while i < items.len() {
+        match opts.parse_at(items, i) {
+            Ok(taken) => i += taken,
+            Err(e) => return e,
+        }
+    }
+    lcs_reply(&operands[0], &operands[1], &opts)
+}
+
 pub fn stralgo(items: &[Resp], db: &Db) -> Resp {
     if items.len() < 2 {
         return Resp::StaticError("ERR wrong number of arguments for 'STRALGO'");
@@ -1069,229 +1023,140 @@ pub fn stralgo(items: &[Resp], db: &Db) -> Resp {
         _ => return Resp::StaticError("ERR syntax error"),
     }
 
-    let mut get_len = false;
-    let mut get_idx = false;
-    let mut with_match_len = false;
-    let mut min_match_len = 0;
-
+    let mut opts = LcsOptions::default();
     let mut str_a: Option<Bytes> = None;
     let mut str_b: Option<Bytes> = None;
 
     let mut i = 2;
     while i < items.len() {
-        if let Some(arg) = as_bytes(&items[i]) {
-            if arg.eq_ignore_ascii_case(b"KEYS") {
-                if i + 2 >= items.len() {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                if str_a.is_some() {
-                    return Resp::StaticError("ERR Either use STRINGS or KEYS");
-                }
-                // Fetch keys
-                let key1 = match &items[i + 1] {
-                    Resp::BulkString(Some(b)) => b.clone(),
-                    Resp::SimpleString(s) => s.clone(),
-                    _ => return Resp::StaticError("ERR invalid key"),
-                };
-                let key2 = match &items[i + 2] {
-                    Resp::BulkString(Some(b)) => b.clone(),
-                    Resp::SimpleString(s) => s.clone(),
-                    _ => return Resp::StaticError("ERR invalid key"),
-                };
-
-                let v1 = db.get(&key1).map(|e| e.value.clone());
-                let v2 = db.get(&key2).map(|e| e.value.clone());
-
-                str_a = match v1 {
-                    Some(Value::String(b)) => Some(b),
-                    None => Some(Bytes::new()),
-                    _ => return Resp::StaticError("ERR value is not a string"),
-                };
-
-                str_b = match v2 {
-                    Some(Value::String(b)) => Some(b),
-                    None => Some(Bytes::new()),
-                    _ => return Resp::StaticError("ERR value is not a string"),
-                };
-
-                i += 3;
-            } else if arg.eq_ignore_ascii_case(b"STRINGS") {
-                if i + 2 >= items.len() {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                if str_a.is_some() {
-                    return Resp::StaticError("ERR Either use STRINGS or KEYS");
-                }
-                str_a = match &items[i + 1] {
-                    Resp::BulkString(Some(b)) => Some(b.clone()),
-                    Resp::SimpleString(s) => Some(s.clone()),
-                    _ => return Resp::StaticError("ERR invalid string"),
-                };
-                str_b = match &items[i + 2] {
-                    Resp::BulkString(Some(b)) => Some(b.clone()),
-                    Resp::SimpleString(s) => Some(s.clone()),
-                    _ => return Resp::StaticError("ERR invalid string"),
-                };
-                i += 3;
-            } else if arg.eq_ignore_ascii_case(b"LEN") {
-                get_len = true;
-                i += 1;
-            } else if arg.eq_ignore_ascii_case(b"IDX") {
-                get_idx = true;
-                i += 1;
-            } else if arg.eq_ignore_ascii_case(b"WITHMATCHLEN") {
-                with_match_len = true;
-                i += 1;
-            } else if arg.eq_ignore_ascii_case(b"MINMATCHLEN")
-                || arg.eq_ignore_ascii_case(b"MINLEN")
-            {
-                if i + 1 >= items.len() {
-                    return Resp::StaticError("ERR syntax error");
-                }
-                // Parse len
-                if let Some(s) = as_bytes(&items[i + 1]) {
-                    if let Ok(str_val) = std::str::from_utf8(s) {
-                        if let Ok(v) = str_val.parse::<usize>() {
-                            min_match_len = v;
-                        } else {
-                            return Resp::StaticError("ERR minmatchlen is not an integer");
-                        }
-                    } else {
-                        return Resp::StaticError("ERR minmatchlen is not an integer");
+        let is_keys = as_bytes(&items[i]).is_some_and(|a| a.eq_ignore_ascii_case(b"KEYS"));
+        let is_strings = as_bytes(&items[i]).is_some_and(|a| a.eq_ignore_ascii_case(b"STRINGS"));
+        if is_keys || is_strings {
+            if i + 2 >= items.len() {
+                return Resp::StaticError("ERR syntax error");
+            }
+            if str_a.is_some() {
+                return Resp::StaticError("ERR Either use STRINGS or KEYS");
+            }
+            let (first, second) = match (as_bytes(&items[i + 1]), as_bytes(&items[i + 2])) {
+                (Some(first), Some(second)) => (first, second),
+                _ if is_keys => return Resp::StaticError("ERR invalid key"),
+                _ => return Resp::StaticError("ERR invalid string"),
+            };
+            if is_keys {
+                // The same two keys get_command_keys reports for KEYS
+                match (lcs_operand(db, first), lcs_operand(db, second)) {
+                    (Ok(a), Ok(b)) => {
+                        str_a = Some(a);
+                        str_b = Some(b);
                     }
-                } else {
-                    return Resp::StaticError("ERR minmatchlen is not an integer");
+                    (Err(e), _) | (_, Err(e)) => return e,
                 }
-                i += 2;
             } else {
-                return Resp::StaticError("ERR syntax error");
+                str_a = Some(Bytes::copy_from_slice(first));
+                str_b = Some(Bytes::copy_from_slice(second));
             }
+            i += 3;
         } else {
-            return Resp::StaticError("ERR syntax error");
+            match opts.parse_at(items, i) {
+                Ok(taken) => i += taken,
+                Err(e) => return e,
+            }
         }
     }
 
-    let a = str_a.unwrap_or_else(Bytes::new);
-    let b = str_b.unwrap_or_else(Bytes::new);
+    match (str_a, str_b) {
+        (Some(a), Some(b)) => lcs_reply(&a, &b, &opts),
+        _ => Resp::StaticError(
+            "ERR Please specify two strings: STRINGS or KEYS options are mandatory",
+        ),
+    }
+}
+
+/// Computes the longest common subsequence of `a` and `b` with the classic
+/// DP table and replies with it, its length (LEN) or the matching ranges
+/// (IDX). IDX reports ranges from the end of the strings backwards, as Redis
+/// does, skipping runs shorter than MINMATCHLEN.
+fn lcs_reply(a: &[u8], b: &[u8], opts: &LcsOptions) -> Resp {
+    if opts.len && opts.idx {
+        return Resp::StaticError(
+            "ERR If you want both the length and indexes, please just use IDX.",
+        );
+    }
 
-    // Calculate LCS
     let m = a.len();
     let n = b.len();
 
+    // dp[i * (n + 1) + j] is the LCS length of a[..i] and b[..j]
     let mut dp = vec![0u32; (m + 1) * (n + 1)];
-    // Access: dp[i * (n + 1) + j]
-
     for i in 1..=m {
         for j in 1..=n {
-            if a[i - 1] == b[j - 1] {
-                dp[i * (n + 1) + j] = dp[(i - 1) * (n + 1) + (j - 1)] + 1;
+            dp[i * (n + 1) + j] = if a[i - 1] == b[j - 1] {
+                dp[(i - 1) * (n + 1) + (j - 1)] + 1
             } else {
-                let v1 = dp[(i - 1) * (n + 1) + j];
-                let v2 = dp[i * (n + 1) + (j - 1)];
-                dp[i * (n + 1) + j] = std::cmp::max(v1, v2);
-            }
+                dp[(i - 1) * (n + 1) + j].max(dp[i * (n + 1) + (j - 1)])
+            };
         }
     }
+    let lcs_len = dp[m * (n + 1) + n] as usize;
+    if opts.len {
+        return Resp::Integer(lcs_len as i64);
+    }
 
-    let lcs_len = dp[m * (n + 1) + n];
-
-    if get_idx {
-        // Backtrack for matches
-        let mut matches = Vec::new();
-        let mut i = m;
-        let mut j = n;
-
-        let mut current_match_len = 0;
-        let mut match_end_a = 0;
-        let mut match_end_b = 0;
-
-        while i > 0 && j > 0 {
-            if a[i - 1] == b[j - 1] {
-                if current_match_len == 0 {
-                    match_end_a = i - 1;
-                    match_end_b = j - 1;
-                }
-                current_match_len += 1;
+    // Walk back from the end, collecting the LCS and its contiguous runs
+    let mut lcs = vec![0u8; lcs_len];
+    let mut matches = Vec::new();
+    let mut run: Option<(usize, usize, usize)> = None; // (end in a, end in b, len)
+    let (mut i, mut j, mut k) = (m, n, lcs_len);
+    while i > 0 && j > 0 {
+        if a[i - 1] == b[j - 1] {
+            k -= 1;
+            lcs[k] = a[i - 1];
+            run = match run {
+                Some((ea, eb, len)) => Some((ea, eb, len + 1)),
+                None => Some((i - 1, j - 1, 1)),
+            };
+            i -= 1;
+            j -= 1;
+        } else {
+            if let Some(done) = run.take() {
+                matches.push(done);
+            }
+            if dp[(i - 1) * (n + 1) + j] > dp[i * (n + 1) + (j - 1)] {
                 i -= 1;
-                j -= 1;
             } else {
-                // End of current match block (if any)
-                if current_match_len > 0 {
-                    // Since we go backwards, start is i, end is match_end
-                    // Range is [i, match_end_a] inclusive.
-                    // Length is current_match_len.
-                    if current_match_len >= min_match_len {
-                        matches.push((i, match_end_a, j, match_end_b, current_match_len));
-                    }
-                    current_match_len = 0;
-                }
-
-                let v1 = dp[(i - 1) * (n + 1) + j];
-                let v2 = dp[i * (n + 1) + (j - 1)];
-                if v1 > v2 {
-                    i -= 1;
-                } else {
-                    j -= 1;
-                }
-            }
-        }
-        if current_match_len > 0 {
-            if current_match_len >= min_match_len {
-                matches.push((i, match_end_a, j, match_end_b, current_match_len));
-            }
-        }
-
-        // Matches are collected in reverse order (end to start).
-        matches.reverse();
-
-        // Construct response
-        let mut match_arr = Vec::new();
-        for (sa, ea, sb, eb, len) in matches {
-            let mut item = Vec::new();
-            item.push(Resp::Array(Some(vec![
-                Resp::Integer(sa as i64),
-                Resp::Integer(ea as i64),
-            ])));
-            item.push(Resp::Array(Some(vec![
-                Resp::Integer(sb as i64),
-                Resp::Integer(eb as i64),
-            ])));
-            if with_match_len {
-                item.push(Resp::Integer(len as i64));
+                j -= 1;
             }
-            match_arr.push(Resp::Array(Some(item)));
         }
+    }
+    matches.extend(run);
 
-        let mut map = Vec::new();
-        map.push(Resp::BulkString(Some(Bytes::from("matches"))));
-        map.push(Resp::Array(Some(match_arr)));
-        map.push(Resp::BulkString(Some(Bytes::from("len"))));
-        map.push(Resp::Integer(lcs_len as i64));
+    if !opts.idx {
+        return Resp::BulkString(Some(Bytes::from(lcs)));
+    }
 
-        return Resp::Array(Some(map));
-    } else if get_len {
-        return Resp::Integer(lcs_len as i64);
-    } else {
-        // Return LCS string
-        let mut res = Vec::with_capacity(lcs_len as usize);
-        let mut i = m;
-        let mut j = n;
-        while i > 0 && j > 0 {
-            if a[i - 1] == b[j - 1] {
-                res.push(a[i - 1]);
-                i -= 1;
-                j -= 1;
-            } else {
-                let v1 = dp[(i - 1) * (n + 1) + j];
-                let v2 = dp[i * (n + 1) + (j - 1)];
-                if v1 > v2 {
-                    i -= 1;
-                } else {
-                    j -= 1;
-                }
+    let range = |end: usize, len: usize| {
+        Resp::Array(Some(vec![
+            Resp::Integer((end + 1 - len) as i64),
+            Resp::Integer(end as i64),
+        ]))
+    };
+    let match_arr = matches
+        .into_iter()
+        .filter(|&(_, _, len)| len >= opts.min_match_len)
+        .map(|(ea, eb, len)| {
+            let mut item = vec![range(ea, len), range(eb, len)];
+            if opts.with_match_len {
+                item.push(Resp::Integer(len as i64));
             }
-        }
-        res.reverse();
-        return Resp::BulkString(Some(Bytes::from(res)));
-    }
+            Resp::Array(Some(item))
+        })
+        .collect();
+
+    Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from_static(b"matches"))),
+        Resp::Array(Some(match_arr)),
+        Resp::BulkString(Some(Bytes::from_static(b"len"))),
+        Resp::Integer(lcs_len as i64),
+    ]))
 }
@@ -1,7 +1,8 @@
-use crate::cmd::key::match_pattern;
-use crate::cmd::{ConnectionContext, ServerContext};
+use crate::cmd::key::{match_pattern, scan_batch};
+use crate::cmd::set::parse_intercard_args;
+use crate::cmd::{ConnectionContext, ServerContext, reply_encoder};
 use crate::db::{Db, Entry, SortedSet, TotalOrderF64, Value};
-use crate::resp::Resp;
+use crate::resp::{ReplyKind, Resp, as_bytes, format_double};
 use bytes::Bytes;
 use rand::seq::IteratorRandom;
 use std::collections::VecDeque;
@@ -71,10 +72,10 @@ fn is_in_lex_range(member: &[u8], min: &LexBound, max: &LexBound) -> bool {
         LexBound::Exclusive(b) => member > b.as_ref(),
     };
 
-    if !check_min {
-        return false;
-    }
+    check_min && lex_below_max(member, max)
+}
 
+fn lex_below_max(member: &[u8], max: &LexBound) -> bool {
     match max {
         LexBound::Min => false,
         LexBound::Max => true,
@@ -136,15 +137,29 @@ fn compute_zunion(
     Ok(result_map)
 }
 
+/// Intersects the sorted sets at `keys`, seeding from the first one. Every
+/// key is type-checked before any intersecting happens. A non-zero `limit`
+/// stops the last pass once that many members have matched.
 fn compute_zinter(
     keys: &[Bytes],
     weights: &[f64],
     aggregate: Aggregate,
+    limit: usize,
     db: &Db,
 ) -> Result<std::collections::HashMap<Bytes, f64>, Resp> {
     if keys.is_empty() {
         return Ok(std::collections::HashMap::new());
     }
+    for key in keys {
+        if let Some(entry) = db.get(key)
+            && !entry.is_expired()
+            && !matches!(entry.value, Value::ZSet(_))
+        {
+            return Err(Resp::Error(
+                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+            ));
+        }
+    }
 
     let mut result_map: std::collections::HashMap<Bytes, f64> = std::collections::HashMap::new();
 
@@ -157,7 +172,12 @@ fn compute_zinter(
         }
         match &entry.value {
             Value::ZSet(zset) => {
-                for (member, score) in &zset.members {
+                let take = if keys.len() == 1 && limit > 0 {
+                    limit
+                } else {
+                    usize::MAX
+                };
+                for (member, score) in zset.members.iter().take(take) {
                     result_map.insert(member.clone(), score * first_weight);
                 }
             }
@@ -206,6 +226,9 @@ fn compute_zinter(
                                 }
                             };
                             next_result.insert(member, final_score);
+                            if i == keys.len() - 1 && next_result.len() == limit {
+                                break;
+                            }
                         }
                     }
                     result_map = next_result;
@@ -276,15 +299,103 @@ fn compute_zdiff(keys: &[Bytes], db: &Db) -> Result<Vec<(Bytes, f64)>, Resp> {
     Ok(out)
 }
 
-pub fn zadd(items: &[Resp], conn_ctx: &ConnectionContext, server_ctx: &ServerContext) -> Resp {
-    if items.len() < 4 || items.len() % 2 != 0 {
-        return Resp::Error("ERR wrong number of arguments for 'ZADD'".to_string());
+/// Returns the reply along with the number of members actually added or
+/// re-scored, which drives dirty accounting regardless of `CH`.
+pub fn zadd(
+    items: &[Resp],
+    conn_ctx: &ConnectionContext,
+    server_ctx: &ServerContext,
+) -> (Resp, usize) {
+    if items.len() < 4 {
+        return (
+            Resp::Error("ERR wrong number of arguments for 'ZADD'".to_string()),
+            0,
+        );
     }
     let key = match &items[1] {
         Resp::BulkString(Some(b)) => b.clone(),
         Resp::SimpleString(s) => s.clone(),
-        _ => return Resp::Error("ERR invalid key".to_string()),
-    };
+        _ => return (Resp::Error("ERR invalid key".to_string()), 0),
+    };
+
+    let mut nx = false;
+    let mut xx = false;
+    let mut gt = false;
+    let mut lt = false;
+    let mut ch = false;
+    let mut incr = false;
+    let mut idx = 2;
+    while let Some(arg) = items.get(idx).and_then(as_bytes) {
+        if arg.eq_ignore_ascii_case(b"NX") {
+            nx = true;
+        } else if arg.eq_ignore_ascii_case(b"XX") {
+            xx = true;
+        } else if arg.eq_ignore_ascii_case(b"GT") {
+            gt = true;
+        } else if arg.eq_ignore_ascii_case(b"LT") {
+            lt = true;
+        } else if arg.eq_ignore_ascii_case(b"CH") {
+            ch = true;
+        } else if arg.eq_ignore_ascii_case(b"INCR") {
+            incr = true;
+        } else {
+            break;
+        }
+        idx += 1;
+    }
+
+    if nx && xx {
+        return (
+            Resp::Error("ERR XX and NX options at the same time are not compatible".to_string()),
+            0,
+        );
+    }
+    if (gt && lt) || (nx && (gt || lt)) {
+        return (
+            Resp::Error(
+                "ERR GT, LT, and/or NX options at the same time are not compatible".to_string(),
+            ),
+            0,
+        );
+    }
+
+    let pairs = &items[idx..];
+    if pairs.is_empty() || !pairs.len().is_multiple_of(2) {
+        return (Resp::Error("ERR syntax error".to_string()), 0);
+    }
+
+    // Validate every score before touching the set
+    let mut elements = Vec::with_capacity(pairs.len() / 2);
+    for chunk in pairs.chunks(2) {
+        let score_bytes = match &chunk[0] {
+            Resp::BulkString(Some(b)) => b,
+            Resp::SimpleString(s) => s,
+            _ => return (Resp::Error("ERR invalid score".to_string()), 0),
+        };
+        let score_str = match std::str::from_utf8(score_bytes) {
+            Ok(s) => s,
+            Err(_) => {
+                return (Resp::Error("ERR value is not a valid float".to_string()), 0);
+            }
+        };
+        let score: f64 = match score_str.parse() {
+            Ok(s) if !f64::is_nan(s) => s,
+            _ => return (Resp::Error("ERR value is not a valid float".to_string()), 0),
+        };
+
+        let member = match &chunk[1] {
+            Resp::BulkString(Some(b)) => b.clone(),
+            Resp::SimpleString(s) => s.clone(),
+            _ => return (Resp::Error("ERR invalid member".to_string()), 0),
+        };
+        elements.push((score, member));
+    }
+    if incr && elements.len() != 1 {
+        return (
+            Resp::Error("ERR INCR option supports a single increment-element pair".to_string()),
+            0,
+        );
+    }
 
     let db = {
         let db_lock = server_ctx.databases[conn_ctx.db_index].read().unwrap();
@@ -300,40 +411,46 @@ pub fn zadd(items: &[Resp], conn_ctx: &ConnectionContext, server_ctx: &ServerCon
     }
 
     let mut added_count = 0;
+    let mut updated_count = 0;
+    // With INCR: the member's resulting score, or None if a flag blocked it
+    let mut incr_result = None;
 
     if let Value::ZSet(zset) = &mut entry.value {
-        for chunk in items[2..].chunks(2) {
-            let score_bytes = match &chunk[0] {
-                Resp::BulkString(Some(b)) => b,
-                Resp::SimpleString(s) => s,
-                _ => return Resp::Error("ERR invalid score".to_string()),
-            };
-            let score_str = match std::str::from_utf8(score_bytes) {
-                Ok(s) => s,
-                Err(_) => return Resp::Error("ERR value is not a valid float".to_string()),
+        for (score, member) in elements {
+            let old_score = zset.members.get(&member).copied();
+            let score = match old_score {
+                Some(old_score) if incr => old_score + score,
+                _ => score,
             };
-            let score: f64 = match score_str.parse() {
-                Ok(s) => s,
-                Err(_) => return Resp::Error("ERR value is not a valid float".to_string()),
-            };
-
-            let member = match &chunk[1] {
-                Resp::BulkString(Some(b)) => b.clone(),
-                Resp::SimpleString(s) => s.clone(),
-                _ => return Resp::Error("ERR invalid member".to_string()),
-            };
-
-            if let Some(old_score) = zset.members.get(&member) {
-                if *old_score != score {
-                    zset.scores
-                        .remove(&(TotalOrderF64(*old_score), member.clone()));
-                    zset.members.insert(member.clone(), score);
-                    zset.scores.insert((TotalOrderF64(score), member));
+            if score.is_nan() {
+                // INCR of inf by -inf; INCR takes a single pair, so nothing changed yet
+                if zset.members.is_empty() {
+                    drop(entry);
+                    db.remove(&key);
                 }
-            } else {
+                return (
+                    Resp::Error("ERR resulting score is not a number (NaN)".to_string()),
+                    0,
+                );
+            }
+            if let Some(old_score) = old_score {
+                if nx || (gt && score <= old_score) || (lt && score >= old_score) {
+                    continue;
+                }
+                incr_result = Some(score);
+                if score == old_score {
+                    continue;
+                }
+                zset.scores
+                    .remove(&(TotalOrderF64(old_score), member.clone()));
+                zset.members.insert(member.clone(), score);
+                zset.scores.insert((TotalOrderF64(score), member));
+                updated_count += 1;
+            } else if !xx {
                 zset.members.insert(member.clone(), score);
                 zset.scores.insert((TotalOrderF64(score), member));
                 added_count += 1;
+                incr_result = Some(score);
             }
         }
 
@@ -354,69 +471,54 @@ pub fn zadd(items: &[Resp], conn_ctx: &ConnectionContext, server_ctx: &ServerCon
                     }
                 }
 
-                if let Some((sender, is_min)) = sender_info {
-                    // Pop from ZSet
-                    let popped = if is_min {
-                        // Pop Min
-                        if let Some((score_wrapper, member)) = zset.scores.pop_first() {
-                            let score = score_wrapper.0;
-                            zset.members.remove(&member);
-                            Some((member, score))
-                        } else {
-                            None
-                        }
-                    } else {
-                        // Pop Max
-                        if let Some((score_wrapper, member)) = zset.scores.pop_last() {
-                            let score = score_wrapper.0;
-                            zset.members.remove(&member);
-                            Some((member, score))
-                        } else {
-                            None
-                        }
-                    };
-
-                    if let Some((member, score)) = popped {
-                        match sender.try_send((key.to_vec(), member.to_vec(), score)) {
-                            Ok(_) => {
-                                // Sent successfully
-                            }
-                            Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => {
-                                // Should not happen for size 1, but if so, we dropped the item?
-                                // Wait, if we popped it, we MUST deliver or put it back.
-                                // If we can't deliver, we should put it back?
-                                // Or retry next waiter?
-                                // For simplicity/robustness, if we fail to send, we should try to put it back?
-                                // But `sender` is closed is the main issue.
-                                // If Full, it means the receiver hasn't read yet? But they are blocked waiting.
-                                // Let's assume Full won't happen.
-                                // If Closed, we proceed to next waiter.
-                            }
-                            Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => {
-                                // Receiver gone. We popped the item. We should put it back or give to next waiter?
-                                // We should give to next waiter.
-                                // If no more waiters, put back?
-                                // For now, let's try to serve next waiter with the SAME item.
-                                // But my loop structure pops a NEW item each time.
-                                // This logic is flawed if send fails.
-
-                                // Refined logic:
-                                // 1. Peek waiter.
-                                // 2. Pop item.
-                                // 3. Try send.
-                                // 4. If fail, loop with SAME item?
-                            }
-                        }
-                    }
+                let (sender, is_min) = match sender_info {
+                    Some(info) => info,
+                    None => break,
+                };
+                // Reserve the waiter's slot before popping, so a member is
+                // only taken out of the set once it can be delivered. A
+                // closed or already served waiter is skipped.
+                let permit = match sender.try_reserve() {
+                    Ok(permit) => permit,
+                    Err(_) => continue,
+                };
+                let popped = if is_min {
+                    zset.scores.pop_first()
                 } else {
-                    break;
+                    zset.scores.pop_last()
+                };
+                if let Some((score, member)) = popped {
+                    zset.members.remove(&member);
+                    permit.send((key.to_vec(), member.to_vec(), score.0));
                 }
             }
         }
 
-        Resp::Integer(added_count)
+        // XX on a missing key, or waiters draining the set, leaves nothing behind
+        if zset.members.is_empty() {
+            drop(entry);
+            db.remove(&key);
+        }
+
+        let changed = added_count + updated_count;
+        let reply = if incr {
+            match incr_result {
+                Some(score) => Resp::Double(score),
+                None => Resp::BulkString(None),
+            }
+        } else if ch {
+            Resp::Integer(changed as i64)
+        } else {
+            Resp::Integer(added_count as i64)
+        };
+        (reply, changed)
     } else {
-        Resp::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())
+        (
+            Resp::Error(
+                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+            ),
+            0,
+        )
     }
 }
 
@@ -450,6 +552,10 @@ pub fn zrem(items: &[Resp], db: &Db) -> Resp {
                         count += 1;
                     }
                 }
+                if zset.members.is_empty() {
+                    drop(entry);
+                    db.remove(&key);
+                }
                 Resp::Integer(count)
             }
             _ => Resp::Error(
@@ -485,7 +591,7 @@ pub fn zscore(items: &[Resp], db: &Db) -> Resp {
         match &entry.value {
             Value::ZSet(zset) => {
                 if let Some(score) = zset.members.get(&member) {
-                    Resp::BulkString(Some(bytes::Bytes::from(score.to_string())))
+                    Resp::Double(*score)
                 } else {
                     Resp::BulkString(None)
                 }
@@ -533,7 +639,7 @@ pub fn zmscore(items: &[Resp], db: &Db) -> Resp {
                         }
                     };
                     if let Some(score) = zset.members.get(member) {
-                        results.push(Resp::BulkString(Some(Bytes::from(score.to_string()))));
+                        results.push(Resp::Double(*score));
                     } else {
                         results.push(Resp::BulkString(None));
                     }
@@ -629,7 +735,9 @@ pub fn zcount(items: &[Resp], db: &Db) -> Resp {
                     let lt_max = if max_ex { s < max } else { s <= max };
                     if gt_min && lt_max {
                         count += 1;
-                    } else if s > max {
+                    } else if !lt_max {
+                        // Scores are ordered, so nothing further is in range;
+                        // never true for a +inf max
                         break;
                     }
                 }
@@ -686,6 +794,10 @@ pub fn zlexcount(items: &[Resp], db: &Db) -> Resp {
                 for (_, member) in zset.scores.iter() {
                     if is_in_lex_range(member, &min, &max) {
                         count += 1;
+                    } else if !lex_below_max(member, &max) {
+                        // Lex ranges assume equal scores, which orders the
+                        // members, so nothing further is in range
+                        break;
                     }
                 }
                 Resp::Integer(count)
@@ -699,9 +811,85 @@ pub fn zlexcount(items: &[Resp], db: &Db) -> Resp {
     }
 }
 
-pub fn zrangebyscore(items: &[Resp], db: &Db) -> Resp {
-    if items.len() < 4 {
-        return Resp::Error("ERR wrong number of arguments for 'ZRANGEBYSCORE'".to_string());
+pub fn zrangebyscore(items: &[Resp], conn_ctx: &ConnectionContext, db: &Db) -> Resp {
+    zrange_generic(items, ZRangeBy::Score, false, "ZRANGEBYSCORE", conn_ctx, db)
+}
+
+pub fn zrevrangebyscore(items: &[Resp], conn_ctx: &ConnectionContext, db: &Db) -> Resp {
+    zrange_generic(
+        items,
+        ZRangeBy::Score,
+        true,
+        "ZREVRANGEBYSCORE",
+        conn_ctx,
+        db,
+    )
+}
+
+pub fn zrangebylex(items: &[Resp], conn_ctx: &ConnectionContext, db: &Db) -> Resp {
+    zrange_generic(items, ZRangeBy::Lex, false, "ZRANGEBYLEX", conn_ctx, db)
+}
+
+pub fn zrevrangebylex(items: &[Resp], conn_ctx: &ConnectionContext, db: &Db) -> Resp {
+    zrange_generic(items, ZRangeBy::Lex, true, "ZREVRANGEBYLEX", conn_ctx, db)
+}
+
+/// Clamps a ZRANGE-style `start`/`stop` pair (negative counts from the end)
+/// to inclusive indexes into a set of `len` members, or `None` if empty.
+fn rank_range(start: i64, stop: i64, len: i64) -> Option<(usize, usize)> {
+    let start = if start < 0 {
+        (start + len).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        stop + len
+    } else {
+        stop.min(len - 1)
+    };
+    if start > stop || start >= len {
+        return None;
+    }
+    Some((start as usize, stop as usize))
+}
+
+/// Shared body of the ZREMRANGEBY* commands: the entries `range` selects,
+/// found the way ZRANGE finds them, are removed from both indexes. Returns
+/// the number removed.
+fn zremrange_generic(key: &Bytes, db: &Db, range: ParsedRange) -> Resp {
+    if let Some(mut entry) = db.get_mut(key) {
+        if entry.is_expired() {
+            drop(entry);
+            db.remove(key);
+            return Resp::Integer(0);
+        }
+        match &mut entry.value {
+            Value::ZSet(zset) => {
+                let doomed: Vec<_> = select_range(zset, &range, false, 0, usize::MAX)
+                    .cloned()
+                    .collect();
+                for (score, member) in &doomed {
+                    zset.members.remove(member);
+                    zset.scores.remove(&(*score, member.clone()));
+                }
+                if zset.members.is_empty() {
+                    drop(entry);
+                    db.remove(key);
+                }
+                Resp::Integer(doomed.len() as i64)
+            }
+            _ => Resp::Error(
+                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+            ),
+        }
+    } else {
+        Resp::Integer(0)
+    }
+}
+
+pub fn zremrangebyrank(items: &[Resp], db: &Db) -> Resp {
+    if items.len() != 4 {
+        return Resp::Error("ERR wrong number of arguments for 'ZREMRANGEBYRANK'".to_string());
     }
     let key = match &items[1] {
         Resp::BulkString(Some(b)) => b.clone(),
@@ -709,156 +897,272 @@ pub fn zrangebyscore(items: &[Resp], db: &Db) -> Resp {
         _ => return Resp::Error("ERR invalid key".to_string()),
     };
 
-    let min_str = match std::str::from_utf8(match &items[2] {
+    let mut bounds = [0i64; 2];
+    for (i, bound) in bounds.iter_mut().enumerate() {
+        *bound = match as_bytes(&items[2 + i])
+            .and_then(|b| std::str::from_utf8(b).ok())
+            .and_then(|s| s.parse::<i64>().ok())
+        {
+            Some(v) => v,
+            None => {
+                return Resp::Error("ERR value is not an integer or out of range".to_string());
+            }
+        };
+    }
+    let [start, stop] = bounds;
+
+    zremrange_generic(&key, db, ParsedRange::Rank(start, stop))
+}
+
+pub fn zremrangebyscore(items: &[Resp], db: &Db) -> Resp {
+    if items.len() != 4 {
+        return Resp::Error("ERR wrong number of arguments for 'ZREMRANGEBYSCORE'".to_string());
+    }
+    let key = match &items[1] {
+        Resp::BulkString(Some(b)) => b.clone(),
+        Resp::SimpleString(s) => s.clone(),
+        _ => return Resp::Error("ERR invalid key".to_string()),
+    };
+
+    let mut bounds = [(0.0, false); 2];
+    for (i, bound) in bounds.iter_mut().enumerate() {
+        let s = match as_bytes(&items[2 + i]).and_then(|b| std::str::from_utf8(b).ok()) {
+            Some(s) => s,
+            None => return Resp::Error("ERR min or max is not a float".to_string()),
+        };
+        *bound = match parse_score_bound(s) {
+            Ok(v) => v,
+            Err(e) => return e,
+        };
+    }
+    let [min, max] = bounds;
+
+    zremrange_generic(&key, db, ParsedRange::Score(min, max))
+}
+
+pub fn zremrangebylex(items: &[Resp], db: &Db) -> Resp {
+    if items.len() != 4 {
+        return Resp::Error("ERR wrong number of arguments for 'ZREMRANGEBYLEX'".to_string());
+    }
+    let key = match &items[1] {
+        Resp::BulkString(Some(b)) => b.clone(),
+        Resp::SimpleString(s) => s.clone(),
+        _ => return Resp::Error("ERR invalid key".to_string()),
+    };
+
+    let min_bytes = match &items[2] {
         Resp::BulkString(Some(b)) => b,
         Resp::SimpleString(s) => s,
-        _ => return Resp::Error("ERR min or max is not a float".to_string()),
-    }) {
-        Ok(s) => s,
-        Err(_) => return Resp::Error("ERR min or max is not a float".to_string()),
+        _ => return Resp::Error("ERR min or max not valid string range item".to_string()),
     };
-    let max_str = match std::str::from_utf8(match &items[3] {
+    let max_bytes = match &items[3] {
         Resp::BulkString(Some(b)) => b,
         Resp::SimpleString(s) => s,
-        _ => return Resp::Error("ERR min or max is not a float".to_string()),
-    }) {
-        Ok(s) => s,
-        Err(_) => return Resp::Error("ERR min or max is not a float".to_string()),
+        _ => return Resp::Error("ERR min or max not valid string range item".to_string()),
     };
 
-    let (min, min_ex) = match parse_score_bound(min_str) {
+    let min = match parse_lex_bound(min_bytes) {
         Ok(v) => v,
         Err(e) => return e,
     };
-    let (max, max_ex) = match parse_score_bound(max_str) {
+    let max = match parse_lex_bound(max_bytes) {
         Ok(v) => v,
         Err(e) => return e,
     };
 
-    let mut withscores = false;
-    let mut offset: usize = 0;
-    let mut count: Option<i64> = None;
+    zremrange_generic(&key, db, ParsedRange::Lex(min, max))
+}
 
-    let mut idx = 4;
-    while idx < items.len() {
-        let arg = match &items[idx] {
-            Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).to_uppercase(),
-            Resp::SimpleString(s) => String::from_utf8_lossy(s).to_uppercase(),
-            _ => return Resp::Error("ERR syntax error".to_string()),
-        };
+pub fn zrank(items: &[Resp], db: &Db) -> Resp {
+    zrank_generic(items, false, "ZRANK", db)
+}
 
-        if arg == "WITHSCORES" {
-            withscores = true;
-            idx += 1;
-        } else if arg == "LIMIT" {
-            if idx + 2 >= items.len() {
-                return Resp::Error("ERR syntax error".to_string());
-            }
-            let offset_val = match &items[idx + 1] {
-                Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).parse::<i64>(),
-                Resp::SimpleString(s) => String::from_utf8_lossy(s).parse::<i64>(),
-                _ => return Resp::Error("ERR value is not an integer or out of range".to_string()),
-            };
-            let count_val = match &items[idx + 2] {
-                Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).parse::<i64>(),
-                Resp::SimpleString(s) => String::from_utf8_lossy(s).parse::<i64>(),
-                _ => return Resp::Error("ERR value is not an integer or out of range".to_string()),
-            };
+pub fn zrevrank(items: &[Resp], db: &Db) -> Resp {
+    zrank_generic(items, true, "ZREVRANK", db)
+}
 
-            offset = match offset_val {
-                Ok(v) if v >= 0 => v as usize,
-                _ => return Resp::Error("ERR value is not an integer or out of range".to_string()),
-            };
-            count = match count_val {
-                Ok(v) => Some(v),
-                _ => return Resp::Error("ERR value is not an integer or out of range".to_string()),
-            };
-            idx += 3;
-        } else {
-            return Resp::Error("ERR syntax error".to_string());
-        }
+/// Shared body of ZRANK and ZREVRANK. With the trailing WITHSCORE token the
+/// reply is `[rank, score]`, and a missing member is a nil array instead of a
+/// nil bulk string.
+fn zrank_generic(items: &[Resp], rev: bool, name: &str, db: &Db) -> Resp {
+    if items.len() != 3 && items.len() != 4 {
+        return Resp::Error(format!("ERR wrong number of arguments for '{}'", name));
     }
+    let key = match &items[1] {
+        Resp::BulkString(Some(b)) => b.clone(),
+        Resp::SimpleString(s) => s.clone(),
+        _ => return Resp::Error("ERR invalid key".to_string()),
+    };
+    let member = match &items[2] {
+        Resp::BulkString(Some(b)) => b.clone(),
+        Resp::SimpleString(s) => s.clone(),
+        _ => return Resp::Error("ERR invalid member".to_string()),
+    };
+    let withscore = match items.get(3) {
+        None => false,
+        Some(Resp::BulkString(Some(b))) | Some(Resp::SimpleString(b))
+            if b.eq_ignore_ascii_case(b"WITHSCORE") =>
+        {
+            true
+        }
+        Some(_) => return Resp::Error("ERR syntax error".to_string()),
+    };
+    let nil = if withscore {
+        Resp::Array(None)
+    } else {
+        Resp::BulkString(None)
+    };
 
     if let Some(entry) = db.get(&key) {
         if entry.is_expired() {
             drop(entry);
             db.remove(&key);
-            return Resp::Array(Some(vec![]));
+            return nil;
         }
         match &entry.value {
             Value::ZSet(zset) => {
-                let mut result = Vec::new();
-                let mut current_offset = 0;
-                let mut current_count = 0;
+                let score = match zset.members.get(&member) {
+                    Some(score) => *score,
+                    None => return nil,
+                };
+                // Iterate to find rank
+                let target = (TotalOrderF64(score), member);
+                let rank = if rev {
+                    zset.scores.iter().rev().position(|x| *x == target)
+                } else {
+                    zset.scores.iter().position(|x| *x == target)
+                };
+                match rank {
+                    Some(rank) if withscore => Resp::Array(Some(vec![
+                        Resp::Integer(rank as i64),
+                        Resp::BulkString(Some(Bytes::from(format_double(score)))),
+                    ])),
+                    Some(rank) => Resp::Integer(rank as i64),
+                    // Should not happen if data structures are consistent
+                    None => nil,
+                }
+            }
+            _ => Resp::Error(
+                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+            ),
+        }
+    } else {
+        nil
+    }
+}
 
-                for (score_wrapper, member) in zset.scores.iter() {
-                    let s = score_wrapper.0;
-                    let gt_min = if min_ex { s > min } else { s >= min };
-                    let lt_max = if max_ex { s < max } else { s <= max };
+pub fn zrange(items: &[Resp], conn_ctx: &ConnectionContext, db: &Db) -> Resp {
+    zrange_generic(items, ZRangeBy::Rank, false, "ZRANGE", conn_ctx, db)
+}
 
-                    if gt_min && lt_max {
-                        if current_offset < offset {
-                            current_offset += 1;
-                            continue;
-                        }
-                        if let Some(c) = count {
-                            if c >= 0 && current_count >= c as usize {
-                                break;
-                            }
-                        }
+#[derive(Clone, Copy, PartialEq)]
+enum ZRangeBy {
+    Rank,
+    Score,
+    Lex,
+}
 
-                        result.push(Resp::BulkString(Some(member.clone())));
-                        if withscores {
-                            result.push(Resp::BulkString(Some(Bytes::from(s.to_string()))));
-                        }
-                        current_count += 1;
-                    } else if s > max {
-                        break;
-                    }
+enum ParsedRange {
+    Rank(i64, i64),
+    Score((f64, bool), (f64, bool)),
+    Lex(LexBound, LexBound),
+}
+
+/// Walks the members of `zset` that `range` selects, in score order or
+/// reversed for `rev`. Score ranges stop at the first score past the far
+/// bound; score and lex ranges then skip `offset` and keep at most `limit`.
+/// Shared by the ZRANGE family and the ZREMRANGEBY commands.
+fn select_range<'a>(
+    zset: &'a SortedSet,
+    range: &'a ParsedRange,
+    rev: bool,
+    offset: usize,
+    limit: usize,
+) -> Box<dyn Iterator<Item = &'a (TotalOrderF64, Bytes)> + 'a> {
+    let ordered: Box<dyn Iterator<Item = &(TotalOrderF64, Bytes)>> = if rev {
+        Box::new(zset.scores.iter().rev())
+    } else {
+        Box::new(zset.scores.iter())
+    };
+    match range {
+        ParsedRange::Rank(start, stop) => {
+            let len = zset.scores.len() as i64;
+            match rank_range(*start, *stop, len) {
+                Some((start_idx, stop_idx)) => {
+                    Box::new(ordered.skip(start_idx).take(stop_idx - start_idx + 1))
                 }
-                Resp::Array(Some(result))
+                None => Box::new(std::iter::empty()),
             }
-            _ => Resp::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            ),
         }
-    } else {
-        Resp::Array(Some(vec![]))
+        ParsedRange::Score((min, min_ex), (max, max_ex)) => {
+            let (min, min_ex, max, max_ex) = (*min, *min_ex, *max, *max_ex);
+            let above_min = move |s: f64| if min_ex { s > min } else { s >= min };
+            let below_max = move |s: f64| if max_ex { s < max } else { s <= max };
+            // Scores are ordered, so stop at the first one past the far bound
+            Box::new(
+                ordered
+                    .skip_while(move |(score, _)| {
+                        if rev {
+                            !below_max(score.0)
+                        } else {
+                            !above_min(score.0)
+                        }
+                    })
+                    .take_while(move |(score, _)| {
+                        if rev {
+                            above_min(score.0)
+                        } else {
+                            below_max(score.0)
+                        }
+                    })
+                    .skip(offset)
+                    .take(limit),
+            )
+        }
+        ParsedRange::Lex(min, max) => Box::new(
+            ordered
+                .filter(move |(_, member)| is_in_lex_range(member, min, max))
+                .skip(offset)
+                .take(limit),
+        ),
     }
 }
 
-pub fn zrangebylex(items: &[Resp], db: &Db) -> Resp {
+/// Unified `ZRANGE key start stop [BYSCORE|BYLEX] [REV] [LIMIT offset count]
+/// [WITHSCORES]`. Without BYSCORE or BYLEX the range is by index. The legacy
+/// ZRANGEBYSCORE/ZRANGEBYLEX commands and their ZREV variants are this with
+/// `by` and `rev` fixed by the command name, so only ZRANGE itself accepts
+/// BYSCORE, BYLEX and REV. In the REV
+/// score and lex forms the first bound is the high end of the range.
+fn zrange_generic(
+    items: &[Resp],
+    mut by: ZRangeBy,
+    mut rev: bool,
+    name: &str,
+    conn_ctx: &ConnectionContext,
+    db: &Db,
+) -> Resp {
     if items.len() < 4 {
-        return Resp::Error("ERR wrong number of arguments for 'ZRANGEBYLEX'".to_string());
+        return Resp::Error(format!("ERR wrong number of arguments for '{}'", name));
     }
     let key = match &items[1] {
         Resp::BulkString(Some(b)) => b.clone(),
         Resp::SimpleString(s) => s.clone(),
         _ => return Resp::Error("ERR invalid key".to_string()),
     };
-
-    let min_bytes = match &items[2] {
-        Resp::BulkString(Some(b)) => b,
-        Resp::SimpleString(s) => s,
-        _ => return Resp::Error("ERR min or max not valid string range item".to_string()),
-    };
-    let max_bytes = match &items[3] {
-        Resp::BulkString(Some(b)) => b,
-        Resp::SimpleString(s) => s,
-        _ => return Resp::Error("ERR min or max not valid string range item".to_string()),
-    };
-
-    let min = match parse_lex_bound(min_bytes) {
-        Ok(v) => v,
-        Err(e) => return e,
-    };
-    let max = match parse_lex_bound(max_bytes) {
-        Ok(v) => v,
-        Err(e) => return e,
+    let (start, stop) = match (&items[2], &items[3]) {
+        (
+            Resp::BulkString(Some(start)) | Resp::SimpleString(start),
+            Resp::BulkString(Some(stop)) | Resp::SimpleString(stop),
+        ) => (start.clone(), stop.clone()),
+        _ => return Resp::Error("ERR syntax error".to_string()),
     };
 
+    let unified = name == "ZRANGE";
+    let mut withscores = false;
     let mut offset: usize = 0;
     let mut count: Option<i64> = None;
+    let mut has_limit = false;
 
     let mut idx = 4;
     while idx < items.len() {
@@ -868,7 +1172,19 @@ pub fn zrangebylex(items: &[Resp], db: &Db) -> Resp {
             _ => return Resp::Error("ERR syntax error".to_string()),
         };
 
-        if arg == "LIMIT" {
+        if arg == "WITHSCORES" {
+            withscores = true;
+            idx += 1;
+        } else if arg == "BYSCORE" && unified {
+            by = ZRangeBy::Score;
+            idx += 1;
+        } else if arg == "BYLEX" && unified {
+            by = ZRangeBy::Lex;
+            idx += 1;
+        } else if arg == "REV" && unified {
+            rev = true;
+            idx += 1;
+        } else if arg == "LIMIT" {
             if idx + 2 >= items.len() {
                 return Resp::Error("ERR syntax error".to_string());
             }
@@ -891,249 +1207,103 @@ pub fn zrangebylex(items: &[Resp], db: &Db) -> Resp {
                 Ok(v) => Some(v),
                 _ => return Resp::Error("ERR value is not an integer or out of range".to_string()),
             };
+            has_limit = true;
             idx += 3;
         } else {
             return Resp::Error("ERR syntax error".to_string());
         }
     }
 
-    if let Some(entry) = db.get(&key) {
-        if entry.is_expired() {
-            drop(entry);
-            db.remove(&key);
-            return Resp::Array(Some(vec![]));
-        }
-        match &entry.value {
-            Value::ZSet(zset) => {
-                let mut result = Vec::new();
-                let mut current_offset = 0;
-                let mut current_count = 0;
-
-                for (_, member) in zset.scores.iter() {
-                    if is_in_lex_range(member, &min, &max) {
-                        if current_offset < offset {
-                            current_offset += 1;
-                            continue;
-                        }
-                        if let Some(c) = count {
-                            if c >= 0 && current_count >= c as usize {
-                                break;
-                            }
-                        }
-
-                        result.push(Resp::BulkString(Some(member.clone())));
-                        current_count += 1;
-                    }
-                }
-                Resp::Array(Some(result))
-            }
-            _ => Resp::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            ),
-        }
-    } else {
-        Resp::Array(Some(vec![]))
+    if has_limit && by == ZRangeBy::Rank {
+        return Resp::Error(
+            "ERR syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+                .to_string(),
+        );
     }
-}
-
-pub fn zrank(items: &[Resp], db: &Db) -> Resp {
-    if items.len() != 3 {
-        return Resp::Error("ERR wrong number of arguments for 'ZRANK'".to_string());
+    if withscores && by == ZRangeBy::Lex {
+        return Resp::Error(
+            "ERR syntax error, WITHSCORES not supported in combination with BYLEX".to_string(),
+        );
     }
-    let key = match &items[1] {
-        Resp::BulkString(Some(b)) => b.clone(),
-        Resp::SimpleString(s) => s.clone(),
-        _ => return Resp::Error("ERR invalid key".to_string()),
-    };
-    let member = match &items[2] {
-        Resp::BulkString(Some(b)) => b.clone(),
-        Resp::SimpleString(s) => s.clone(),
-        _ => return Resp::Error("ERR invalid member".to_string()),
-    };
 
-    if let Some(entry) = db.get(&key) {
-        if entry.is_expired() {
-            drop(entry);
-            db.remove(&key);
-            return Resp::BulkString(None);
-        }
-        match &entry.value {
-            Value::ZSet(zset) => {
-                if let Some(score) = zset.members.get(&member) {
-                    // Iterate to find rank
-                    let target = (TotalOrderF64(*score), member);
-                    if let Some(rank) = zset.scores.iter().position(|x| *x == target) {
-                        Resp::Integer(rank as i64)
-                    } else {
-                        // Should not happen if data structures are consistent
-                        Resp::BulkString(None)
-                    }
-                } else {
-                    Resp::BulkString(None)
-                }
-            }
-            _ => Resp::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            ),
-        }
+    // In the REV forms the range is given high end first
+    let (low, high) = if rev && by != ZRangeBy::Rank {
+        (stop, start)
     } else {
-        Resp::BulkString(None)
-    }
-}
-
-pub fn zrevrank(items: &[Resp], db: &Db) -> Resp {
-    if items.len() != 3 {
-        return Resp::Error("ERR wrong number of arguments for 'ZREVRANK'".to_string());
-    }
-    let key = match &items[1] {
-        Resp::BulkString(Some(b)) => b.clone(),
-        Resp::SimpleString(s) => s.clone(),
-        _ => return Resp::Error("ERR invalid key".to_string()),
-    };
-    let member = match &items[2] {
-        Resp::BulkString(Some(b)) => b.clone(),
-        Resp::SimpleString(s) => s.clone(),
-        _ => return Resp::Error("ERR invalid member".to_string()),
+        (start, stop)
     };
 
-    if let Some(entry) = db.get(&key) {
-        if entry.is_expired() {
-            drop(entry);
-            db.remove(&key);
-            return Resp::BulkString(None);
-        }
-        match &entry.value {
-            Value::ZSet(zset) => {
-                if let Some(score) = zset.members.get(&member) {
-                    // Iterate to find reverse rank
-                    let target = (TotalOrderF64(*score), member);
-                    if let Some(rank) = zset.scores.iter().rev().position(|x| *x == target) {
-                        Resp::Integer(rank as i64)
-                    } else {
-                        // Should not happen if data structures are consistent
-                        Resp::BulkString(None)
-                    }
-                } else {
-                    Resp::BulkString(None)
+    // Parse the bounds up front so malformed ones are rejected even when the
+    // key does not exist
+    let range = match by {
+        ZRangeBy::Rank => {
+            let parse = |b: &Bytes| std::str::from_utf8(b).ok()?.parse::<i64>().ok();
+            match (parse(&low), parse(&high)) {
+                (Some(start), Some(stop)) => ParsedRange::Rank(start, stop),
+                _ => {
+                    return Resp::Error("ERR value is not an integer or out of range".to_string());
                 }
             }
-            _ => Resp::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            ),
         }
-    } else {
-        Resp::BulkString(None)
-    }
-}
-
-pub fn zrange(items: &[Resp], db: &Db) -> Resp {
-    if items.len() < 4 || items.len() > 5 {
-        return Resp::Error("ERR wrong number of arguments for 'ZRANGE'".to_string());
-    }
-    let key = match &items[1] {
-        Resp::BulkString(Some(b)) => b.clone(),
-        Resp::SimpleString(s) => s.clone(),
-        _ => return Resp::Error("ERR invalid key".to_string()),
-    };
-    let start_str = match std::str::from_utf8(match &items[2] {
-        Resp::BulkString(Some(b)) => b,
-        Resp::SimpleString(s) => s,
-        _ => return Resp::Error("ERR invalid start".to_string()),
-    }) {
-        Ok(s) => s,
-        Err(_) => return Resp::Error("ERR value is not an integer or out of range".to_string()),
-    };
-    let start: i64 = match start_str.parse() {
-        Ok(s) => s,
-        Err(_) => return Resp::Error("ERR value is not an integer or out of range".to_string()),
-    };
-    let stop_str = match std::str::from_utf8(match &items[3] {
-        Resp::BulkString(Some(b)) => b,
-        Resp::SimpleString(s) => s,
-        _ => return Resp::Error("ERR invalid stop".to_string()),
-    }) {
-        Ok(s) => s,
-        Err(_) => return Resp::Error("ERR value is not an integer or out of range".to_string()),
-    };
-    let stop: i64 = match stop_str.parse() {
-        Ok(s) => s,
-        Err(_) => return Resp::Error("ERR value is not an integer or out of range".to_string()),
-    };
-
-    let withscores = if items.len() == 5 {
-        match &items[4] {
-            Resp::BulkString(Some(b)) => {
-                if b.eq_ignore_ascii_case(b"WITHSCORES") {
-                    true
-                } else {
-                    return Resp::Error("ERR syntax error".to_string());
-                }
-            }
-            Resp::SimpleString(s) => {
-                if s.eq_ignore_ascii_case(b"WITHSCORES") {
-                    true
-                } else {
-                    return Resp::Error("ERR syntax error".to_string());
-                }
+        ZRangeBy::Score => {
+            let parse = |b: &Bytes| match std::str::from_utf8(b) {
+                Ok(s) => parse_score_bound(s),
+                Err(_) => Err(Resp::Error("ERR min or max is not a float".to_string())),
+            };
+            match (parse(&low), parse(&high)) {
+                (Ok(min), Ok(max)) => ParsedRange::Score(min, max),
+                (Err(e), _) | (_, Err(e)) => return e,
             }
-            _ => return Resp::Error("ERR syntax error".to_string()),
         }
-    } else {
-        false
+        ZRangeBy::Lex => match (parse_lex_bound(&low), parse_lex_bound(&high)) {
+            (Ok(min), Ok(max)) => ParsedRange::Lex(min, max),
+            (Err(e), _) | (_, Err(e)) => return e,
+        },
     };
 
-    if let Some(entry) = db.get(&key) {
-        if entry.is_expired() {
-            drop(entry);
-            db.remove(&key);
-            return Resp::Array(Some(vec![]));
+    let entry = match db.get(&key) {
+        Some(entry) => entry,
+        None => return Resp::Array(Some(vec![])),
+    };
+    if entry.is_expired() {
+        drop(entry);
+        db.remove(&key);
+        return Resp::Array(Some(vec![]));
+    }
+    let zset = match &entry.value {
+        Value::ZSet(zset) => zset,
+        _ => {
+            return Resp::Error(
+                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+            );
         }
-        match &entry.value {
-            Value::ZSet(zset) => {
-                let len = zset.scores.len() as i64;
-                let mut start_idx = start;
-                let mut stop_idx = stop;
-
-                if start_idx < 0 {
-                    start_idx += len;
-                }
-                if stop_idx < 0 {
-                    stop_idx += len;
-                }
-                if start_idx < 0 {
-                    start_idx = 0;
-                }
-                if stop_idx >= len {
-                    stop_idx = len - 1;
-                }
+    };
 
-                if start_idx > stop_idx || start_idx >= len {
-                    return Resp::Array(Some(vec![]));
-                }
+    // Walks the selected range; huge replies walk it twice, once to learn
+    // the length for the header and once to encode it
+    let limit = count.filter(|c| *c >= 0).map_or(usize::MAX, |c| c as usize);
+    let select = || select_range(zset, &range, rev, offset, limit);
 
-                let mut result = Vec::new();
-                for (score, member) in zset
-                    .scores
-                    .iter()
-                    .skip(start_idx as usize)
-                    .take((stop_idx - start_idx + 1) as usize)
-                {
-                    result.push(Resp::BulkString(Some(member.clone())));
-                    if withscores {
-                        result.push(Resp::BulkString(Some(bytes::Bytes::from(
-                            score.0.to_string(),
-                        ))));
-                    }
-                }
-                Resp::Array(Some(result))
+    let selected = select().count();
+    let elements = if withscores { selected * 2 } else { selected };
+    if let Some(mut out) = reply_encoder(conn_ctx, ReplyKind::Array, elements) {
+        for (score, member) in select() {
+            out.bulk(member);
+            if withscores {
+                out.bulk(&Bytes::from(format_double(score.0)));
             }
-            _ => Resp::Error(
-                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
-            ),
         }
-    } else {
-        Resp::Array(Some(vec![]))
+        return out.finish();
+    }
+
+    let mut result = Vec::with_capacity(elements);
+    for (score, member) in select() {
+        result.push(Resp::BulkString(Some(member.clone())));
+        if withscores {
+            result.push(Resp::BulkString(Some(Bytes::from(format_double(score.0)))));
+        }
     }
+    Resp::Array(Some(result))
 }
 
 pub fn zrevrange(items: &[Resp], db: &Db) -> Resp {
@@ -1201,39 +1371,24 @@ pub fn zrevrange(items: &[Resp], db: &Db) -> Resp {
         match &entry.value {
             Value::ZSet(zset) => {
                 let len = zset.scores.len() as i64;
-                let mut start_idx = start;
-                let mut stop_idx = stop;
-
-                if start_idx < 0 {
-                    start_idx += len;
-                }
-                if stop_idx < 0 {
-                    stop_idx += len;
-                }
-                if start_idx < 0 {
-                    start_idx = 0;
-                }
-                if stop_idx >= len {
-                    stop_idx = len - 1;
-                }
-
-                if start_idx > stop_idx || start_idx >= len {
-                    return Resp::Array(Some(vec![]));
-                }
+                let (start_idx, stop_idx) = match rank_range(start, stop, len) {
+                    Some(range) => range,
+                    None => return Resp::Array(Some(vec![])),
+                };
 
                 let mut result = Vec::new();
                 for (score, member) in zset
                     .scores
                     .iter()
                     .rev()
-                    .skip(start_idx as usize)
-                    .take((stop_idx - start_idx + 1) as usize)
+                    .skip(start_idx)
+                    .take(stop_idx - start_idx + 1)
                 {
                     result.push(Resp::BulkString(Some(member.clone())));
                     if withscores {
-                        result.push(Resp::BulkString(Some(bytes::Bytes::from(
-                            score.0.to_string(),
-                        ))));
+                        result.push(Resp::BulkString(Some(bytes::Bytes::from(format_double(
+                            score.0,
+                        )))));
                     }
                 }
                 Resp::Array(Some(result))
@@ -1291,13 +1446,17 @@ pub fn zpopmin(items: &[Resp], db: &Db) -> Resp {
                         let score = score_wrapper.0;
                         zset.members.remove(&member);
                         result.push(Resp::BulkString(Some(member)));
-                        result.push(Resp::BulkString(Some(bytes::Bytes::from(
-                            score.to_string(),
-                        ))));
+                        result.push(Resp::BulkString(Some(bytes::Bytes::from(format_double(
+                            score,
+                        )))));
                     } else {
                         break;
                     }
                 }
+                if zset.members.is_empty() {
+                    drop(entry);
+                    db.remove(&key);
+                }
                 Resp::Array(Some(result))
             }
             _ => Resp::Error(
@@ -1353,13 +1512,17 @@ pub fn zpopmax(items: &[Resp], db: &Db) -> Resp {
                         let score = score_wrapper.0;
                         zset.members.remove(&member);
                         result.push(Resp::BulkString(Some(member)));
-                        result.push(Resp::BulkString(Some(bytes::Bytes::from(
-                            score.to_string(),
-                        ))));
+                        result.push(Resp::BulkString(Some(bytes::Bytes::from(format_double(
+                            score,
+                        )))));
                     } else {
                         break;
                     }
                 }
+                if zset.members.is_empty() {
+                    drop(entry);
+                    db.remove(&key);
+                }
                 Resp::Array(Some(result))
             }
             _ => Resp::Error(
@@ -1440,11 +1603,15 @@ async fn blocking_zpop_generic(
                 if let Some((score_wrapper, member)) = popped {
                     let score = score_wrapper.0;
                     zset.members.remove(&member);
+                    if zset.members.is_empty() {
+                        drop(entry);
+                        db.remove(&key);
+                    }
 
                     return Resp::Array(Some(vec![
                         Resp::BulkString(Some(key)),
                         Resp::BulkString(Some(member)),
-                        Resp::BulkString(Some(bytes::Bytes::from(score.to_string()))),
+                        Resp::BulkString(Some(bytes::Bytes::from(format_double(score)))),
                     ]));
                 }
             }
@@ -1465,9 +1632,7 @@ async fn blocking_zpop_generic(
     }
 
     // Wait
-    server_ctx
-        .clients_ctx.blocked_client_count
-        .fetch_add(1, Ordering::Relaxed);
+    server_ctx.clients_ctx.block_client(conn_ctx.id, timeout_secs > 0.0);
 
     let (_shutdown_tx, mut shutdown_rx) = if let Some(rx) = &conn_ctx.shutdown {
         (None, rx.clone())
@@ -1501,15 +1666,13 @@ async fn blocking_zpop_generic(
             }
         }
     };
-    server_ctx
-        .clients_ctx.blocked_client_count
-        .fetch_sub(1, Ordering::Relaxed);
+    server_ctx.clients_ctx.unblock_client(conn_ctx.id, timeout_secs > 0.0);
 
     match result {
         Some((key, val, score)) => Resp::Array(Some(vec![
             Resp::BulkString(Some(bytes::Bytes::from(key))),
             Resp::BulkString(Some(bytes::Bytes::from(val))),
-            Resp::BulkString(Some(bytes::Bytes::from(score.to_string()))),
+            Resp::BulkString(Some(bytes::Bytes::from(format_double(score)))),
         ])),
         None => Resp::BulkString(None), // Timeout
     }
@@ -1535,7 +1698,7 @@ pub fn zscan(items: &[Resp], db: &Db) -> Resp {
         Ok(s) => s,
         Err(_) => return Resp::Error("ERR invalid cursor".to_string()),
     };
-    let cursor: usize = match cursor_str.parse() {
+    let cursor: u64 = match cursor_str.parse() {
         Ok(i) => i,
         Err(_) => return Resp::Error("ERR invalid cursor".to_string()),
     };
@@ -1607,22 +1770,10 @@ pub fn zscan(items: &[Resp], db: &Db) -> Resp {
         }
 
         if let Value::ZSet(zset) = &entry.value {
-            let mut all_members: Vec<bytes::Bytes> = zset.members.keys().cloned().collect();
-            all_members.sort();
-
-            let total_len = all_members.len();
-            if cursor >= total_len {
-                return Resp::Array(Some(vec![
-                    Resp::BulkString(Some(Bytes::from("0"))),
-                    Resp::Array(Some(vec![])),
-                ]));
-            }
-
-            let end = std::cmp::min(cursor + count, total_len);
-            let next_cursor = if end == total_len { 0 } else { end };
+            let (batch, next_cursor) = scan_batch(zset.members.keys(), cursor, count);
 
             let mut result_entries = Vec::new();
-            for member in &all_members[cursor..end] {
+            for member in batch {
                 if let Some(pattern) = match_pattern_str {
                     if !match_pattern(pattern, member) {
                         continue;
@@ -1631,7 +1782,7 @@ pub fn zscan(items: &[Resp], db: &Db) -> Resp {
 
                 if let Some(score) = zset.members.get(member) {
                     result_entries.push(Resp::BulkString(Some(member.clone())));
-                    result_entries.push(Resp::BulkString(Some(Bytes::from(score.to_string()))));
+                    result_entries.push(Resp::BulkString(Some(Bytes::from(format_double(*score)))));
                 }
             }
 
@@ -1734,7 +1885,9 @@ pub fn zrandmember(items: &[Resp], db: &Db) -> Resp {
                         for (member, score) in selected {
                             result.push(Resp::BulkString(Some(member.clone())));
                             if withscores {
-                                result.push(Resp::BulkString(Some(Bytes::from(score.to_string()))));
+                                result.push(Resp::BulkString(Some(Bytes::from(format_double(
+                                    *score,
+                                )))));
                             }
                         }
                     } else {
@@ -1746,7 +1899,7 @@ pub fn zrandmember(items: &[Resp], db: &Db) -> Resp {
                                 result.push(Resp::BulkString(Some(member.clone())));
                                 if withscores {
                                     result.push(Resp::BulkString(Some(Bytes::from(
-                                        score.to_string(),
+                                        format_double(*score),
                                     ))));
                                 }
                             }
@@ -1759,7 +1912,7 @@ pub fn zrandmember(items: &[Resp], db: &Db) -> Resp {
                         if withscores {
                             Resp::Array(Some(vec![
                                 Resp::BulkString(Some(member.clone())),
-                                Resp::BulkString(Some(Bytes::from(score.to_string()))),
+                                Resp::BulkString(Some(Bytes::from(format_double(*score)))),
                             ]))
                         } else {
                             Resp::BulkString(Some(member.clone()))
@@ -1840,7 +1993,7 @@ pub fn zincrby(items: &[Resp], db: &Db) -> Resp {
         zset.members.insert(member.clone(), new_score);
         zset.scores.insert((TotalOrderF64(new_score), member));
 
-        Resp::BulkString(Some(Bytes::from(new_score.to_string())))
+        Resp::Double(new_score)
     } else {
         Resp::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())
     }
@@ -1952,7 +2105,7 @@ pub fn zunion(items: &[Resp], db: &Db) -> Resp {
             for (score, member) in scores {
                 res.push(Resp::BulkString(Some(member)));
                 if withscores {
-                    res.push(Resp::BulkString(Some(Bytes::from(score.0.to_string()))));
+                    res.push(Resp::BulkString(Some(Bytes::from(format_double(score.0)))));
                 }
             }
             Resp::Array(Some(res))
@@ -2157,7 +2310,7 @@ pub fn zinter(items: &[Resp], db: &Db) -> Resp {
         }
     }
 
-    match compute_zinter(&keys, &weights, aggregate, db) {
+    match compute_zinter(&keys, &weights, aggregate, 0, db) {
         Ok(result_map) => {
             let mut scores: Vec<(TotalOrderF64, Bytes)> = result_map
                 .into_iter()
@@ -2173,7 +2326,7 @@ pub fn zinter(items: &[Resp], db: &Db) -> Resp {
             for (score, member) in scores {
                 res.push(Resp::BulkString(Some(member)));
                 if withscores {
-                    res.push(Resp::BulkString(Some(Bytes::from(score.0.to_string()))));
+                    res.push(Resp::BulkString(Some(Bytes::from(format_double(score.0)))));
                 }
             }
             Resp::Array(Some(res))
@@ -2182,6 +2335,21 @@ pub fn zinter(items: &[Resp], db: &Db) -> Resp {
     }
 }
 
+pub fn zintercard(items: &[Resp], db: &Db) -> Resp {
+    if items.len() < 3 {
+        return Resp::Error("ERR wrong number of arguments for 'ZINTERCARD'".to_string());
+    }
+    let (keys, limit) = match parse_intercard_args(items) {
+        Ok(parsed) => parsed,
+        Err(e) => return e,
+    };
+    let weights = vec![1.0; keys.len()];
+    match compute_zinter(&keys, &weights, Aggregate::Sum, limit, db) {
+        Ok(members) => Resp::Integer(members.len() as i64),
+        Err(e) => e,
+    }
+}
+
 pub fn zinterstore(items: &[Resp], db: &Db) -> Resp {
     if items.len() < 4 {
         return Resp::Error("ERR wrong number of arguments for 'ZINTERSTORE'".to_string());
@@ -2273,7 +2441,7 @@ pub fn zinterstore(items: &[Resp], db: &Db) -> Resp {
         }
     }
 
-    match compute_zinter(&keys, &weights, aggregate, db) {
+    match compute_zinter(&keys, &weights, aggregate, 0, db) {
         Ok(result_map) => {
             let mut zset = SortedSet::new();
             for (member, score) in result_map {
@@ -2348,7 +2516,7 @@ pub fn zdiff(items: &[Resp], db: &Db) -> Resp {
             for (score, member) in scores {
                 res.push(Resp::BulkString(Some(member)));
                 if withscores {
-                    res.push(Resp::BulkString(Some(Bytes::from(score.0.to_string()))));
+                    res.push(Resp::BulkString(Some(Bytes::from(format_double(score.0)))));
                 }
             }
             Resp::Array(Some(res))
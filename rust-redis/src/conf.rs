@@ -49,6 +49,11 @@ pub struct Config {
     pub bind: String,
     pub port: u16,
     pub databases: usize,
+    /// Lock shards in each db's map and in the blocking-waiter maps
+    /// (`db-shards`). 0, the default, lets DashMap pick four per CPU rounded
+    /// up to a power of two; otherwise a power of two of at least 2. More
+    /// shards make it less likely that two hot keys share a lock.
+    pub db_shards: usize,
     pub logfile: Option<String>,
     pub appendonly: bool,
     pub appendfilename: String,
@@ -60,9 +65,16 @@ pub struct Config {
     pub save_params: Vec<(u64, u64)>,
     pub config_file: Option<String>,
     pub maxclients: u64,
+    pub timeout: u64,
+    pub tcp_keepalive: u64,
     pub slowlog_log_slower_than: i64,
     pub slowlog_max_len: u64,
+    /// Milliseconds a script may run before the server answers BUSY
+    /// (`lua-time-limit`, alias `busy-reply-threshold`).
+    pub lua_time_limit: u64,
     pub maxmemory: u64,
+    pub proto_max_bulk_len: u64,
+    pub encoding_limits: EncodingLimits,
     pub maxmemory_policy: EvictionPolicy,
     pub maxmemory_samples: usize,
     pub notify_keyspace_events: String,
@@ -96,6 +108,7 @@ impl Default for Config {
             bind: "127.0.0.1".to_string(),
             port: 6380,
             databases: 16,
+            db_shards: 0,
             logfile: None,
             appendonly: false,
             appendfilename: "appendonly.aof".to_string(),
@@ -107,16 +120,21 @@ impl Default for Config {
             save_params: vec![(3600, 1), (300, 100), (60, 10000)],
             config_file: None,
             maxclients: 10000,
+            timeout: 0,
+            tcp_keepalive: 300,
             slowlog_log_slower_than: 10000,
             slowlog_max_len: 128,
+            lua_time_limit: 5000,
             maxmemory: 0,
+            proto_max_bulk_len: DEFAULT_PROTO_MAX_BULK_LEN,
+            encoding_limits: EncodingLimits::default(),
             maxmemory_policy: EvictionPolicy::NoEviction,
             maxmemory_samples: 5,
             notify_keyspace_events: String::new(),
             rdbcompression: true,
             rdbchecksum: true,
             stop_writes_on_bgsave_error: true,
-            repl_backlog_size: 1024,
+            repl_backlog_size: 1024 * 1024,
             repl_ping_replica_period: 10,
             repl_timeout: 60,
             replica_read_only: true,
@@ -145,7 +163,79 @@ impl Config {
     }
 }
 
-fn parse_memory(s: &str) -> Option<u64> {
+/// Size limits up to which collections keep a compact encoding
+/// (`set-max-intset-entries`, `*-max-listpack-entries`/`-value`,
+/// `list-max-listpack-size`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodingLimits {
+    pub hash_max_listpack_entries: usize,
+    pub hash_max_listpack_value: usize,
+    pub set_max_intset_entries: usize,
+    pub set_max_listpack_entries: usize,
+    pub set_max_listpack_value: usize,
+    pub zset_max_listpack_entries: usize,
+    pub zset_max_listpack_value: usize,
+    /// Entries per list node when positive; when -1 to -5, a node size of
+    /// 4, 8, 16, 32 or 64 KB.
+    pub list_max_listpack_size: i64,
+}
+
+impl Default for EncodingLimits {
+    fn default() -> Self {
+        Self {
+            hash_max_listpack_entries: 128,
+            hash_max_listpack_value: 64,
+            set_max_intset_entries: 512,
+            set_max_listpack_entries: 128,
+            set_max_listpack_value: 64,
+            zset_max_listpack_entries: 128,
+            zset_max_listpack_value: 64,
+            list_max_listpack_size: -2,
+        }
+    }
+}
+
+impl EncodingLimits {
+    pub const NAMES: [&'static str; 7] = [
+        "hash-max-listpack-entries",
+        "hash-max-listpack-value",
+        "set-max-intset-entries",
+        "set-max-listpack-entries",
+        "set-max-listpack-value",
+        "zset-max-listpack-entries",
+        "zset-max-listpack-value",
+    ];
+
+    /// The limit behind a config parameter name, if it is one of `NAMES`.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut usize> {
+        match name {
+            "hash-max-listpack-entries" => Some(&mut self.hash_max_listpack_entries),
+            "hash-max-listpack-value" => Some(&mut self.hash_max_listpack_value),
+            "set-max-intset-entries" => Some(&mut self.set_max_intset_entries),
+            "set-max-listpack-entries" => Some(&mut self.set_max_listpack_entries),
+            "set-max-listpack-value" => Some(&mut self.set_max_listpack_value),
+            "zset-max-listpack-entries" => Some(&mut self.zset_max_listpack_entries),
+            "zset-max-listpack-value" => Some(&mut self.zset_max_listpack_value),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<usize> {
+        let mut limits = *self;
+        limits.get_mut(name).copied()
+    }
+
+    /// Parses a `list-max-listpack-size` value; sizes below -5 are invalid.
+    pub fn parse_list_size(value: &str) -> Option<i64> {
+        value.parse::<i64>().ok().filter(|v| *v >= -5)
+    }
+}
+
+pub const DEFAULT_PROTO_MAX_BULK_LEN: u64 = 512 * 1024 * 1024;
+/// Smallest proto-max-bulk-len Redis accepts.
+pub const MIN_PROTO_MAX_BULK_LEN: u64 = 1024 * 1024;
+
+pub(crate) fn parse_memory(s: &str) -> Option<u64> {
     let s = s.to_lowercase();
     let (num, unit) = if s.ends_with("gb") {
         (s.trim_end_matches("gb"), 1024 * 1024 * 1024)
@@ -218,6 +308,13 @@ pub fn load_config(path: Option<&str>) -> io::Result<Config> {
                     );
                 }
             }
+            "db-shards" if parts.len() >= 2 => match parts[1].parse::<usize>() {
+                Ok(n) if n == 0 || (n >= 2 && n.is_power_of_two()) => cfg.db_shards = n,
+                _ => warn!(
+                    "invalid db-shards value '{}' (0 or a power of two >= 2), keep previous {}",
+                    parts[1], cfg.db_shards
+                ),
+            },
             "maxclients" if parts.len() >= 2 => {
                 if let Ok(mc) = parts[1].parse::<u64>() {
                     cfg.maxclients = mc;
@@ -228,6 +325,26 @@ pub fn load_config(path: Option<&str>) -> io::Result<Config> {
                     );
                 }
             }
+            "timeout" if parts.len() >= 2 => {
+                if let Ok(t) = parts[1].parse::<u64>() {
+                    cfg.timeout = t;
+                } else {
+                    warn!(
+                        "invalid timeout value '{}', keep previous {}",
+                        parts[1], cfg.timeout
+                    );
+                }
+            }
+            "tcp-keepalive" if parts.len() >= 2 => {
+                if let Ok(ka) = parts[1].parse::<u64>() {
+                    cfg.tcp_keepalive = ka;
+                } else {
+                    warn!(
+                        "invalid tcp-keepalive value '{}', keep previous {}",
+                        parts[1], cfg.tcp_keepalive
+                    );
+                }
+            }
             "slowlog-log-slower-than" if parts.len() >= 2 => {
                 if let Ok(sl) = parts[1].parse::<i64>() {
                     cfg.slowlog_log_slower_than = sl;
@@ -248,6 +365,16 @@ pub fn load_config(path: Option<&str>) -> io::Result<Config> {
                     );
                 }
             }
+            "lua-time-limit" | "busy-reply-threshold" if parts.len() >= 2 => {
+                if let Ok(limit) = parts[1].parse::<u64>() {
+                    cfg.lua_time_limit = limit;
+                } else {
+                    warn!(
+                        "invalid lua-time-limit value '{}', keep previous {}",
+                        parts[1], cfg.lua_time_limit
+                    );
+                }
+            }
             "maxmemory" if parts.len() >= 2 => {
                 if let Some(mm) = parse_memory(parts[1]) {
                     cfg.maxmemory = mm;
@@ -258,9 +385,34 @@ pub fn load_config(path: Option<&str>) -> io::Result<Config> {
                     );
                 }
             }
+            name if parts.len() >= 2 && EncodingLimits::NAMES.contains(&name) => {
+                match parts[1].parse::<usize>() {
+                    Ok(v) => *cfg.encoding_limits.get_mut(name).unwrap() = v,
+                    Err(_) => warn!("invalid {} value '{}', keep previous", name, parts[1]),
+                }
+            }
+            "list-max-listpack-size" if parts.len() >= 2 => {
+                match EncodingLimits::parse_list_size(parts[1]) {
+                    Some(v) => cfg.encoding_limits.list_max_listpack_size = v,
+                    None => warn!(
+                        "invalid list-max-listpack-size value '{}', keep previous",
+                        parts[1]
+                    ),
+                }
+            }
+            "proto-max-bulk-len" if parts.len() >= 2 => {
+                if let Some(v) = parse_memory(parts[1]).filter(|v| *v >= MIN_PROTO_MAX_BULK_LEN) {
+                    cfg.proto_max_bulk_len = v;
+                } else {
+                    warn!(
+                        "invalid proto-max-bulk-len value '{}', keep previous {}",
+                        parts[1], cfg.proto_max_bulk_len
+                    );
+                }
+            }
             "repl-backlog-size" if parts.len() >= 2 => {
-                if let Ok(bs) = parts[1].parse::<usize>() {
-                    cfg.repl_backlog_size = bs;
+                if let Some(bs) = parse_memory(parts[1]) {
+                    cfg.repl_backlog_size = bs as usize;
                 } else {
                     warn!(
                         "invalid repl-backlog-size value '{}', keep previous {}",
@@ -96,3 +96,58 @@ impl Entry {
 }
 
 pub type Db = Arc<DashMap<bytes::Bytes, Entry>>;
+
+/// An empty map with `shards` lock shards, as set by `db-shards`; 0 keeps
+/// DashMap's default.
+pub fn sharded<K: Eq + std::hash::Hash, V>(shards: usize) -> DashMap<K, V> {
+    if shards == 0 {
+        DashMap::new()
+    } else {
+        DashMap::with_shard_amount(shards)
+    }
+}
+
+/// Walks `db` from a random position, wrapping around to its first keys,
+/// calling `visit` on each entry until it returns false or `max_entries`
+/// were visited. Repeated samples spread over the whole keyspace instead of
+/// always starting from its first keys.
+pub fn sample_from_random_position(
+    db: &Db,
+    max_entries: usize,
+    mut visit: impl FnMut(&bytes::Bytes, &Entry) -> bool,
+) {
+    use rand::Rng;
+
+    let len = db.len();
+    if len == 0 {
+        return;
+    }
+    let start = rand::rng().random_range(0..len);
+    for entry in db
+        .iter()
+        .skip(start)
+        .chain(db.iter().take(start))
+        .take(max_entries)
+    {
+        if !visit(entry.key(), entry.value()) {
+            return;
+        }
+    }
+}
+
+/// Calls `f` on an entry picked uniformly at random, or returns None when
+/// `db` is empty. The pick is by position in the key set, so every key is
+/// equally likely whatever its value holds.
+pub fn random_entry<T>(db: &Db, f: impl FnOnce(&bytes::Bytes, &Entry) -> T) -> Option<T> {
+    use rand::Rng;
+
+    let len = db.len();
+    if len == 0 {
+        return None;
+    }
+    let index = rand::rng().random_range(0..len);
+    // Keys deleted since `len` was read can leave fewer than `index`; the
+    // first key stands in then
+    let entry = db.iter().nth(index).or_else(|| db.iter().next())?;
+    Some(f(entry.key(), entry.value()))
+}
@@ -73,6 +73,16 @@ impl<T> Rax<T> {
             .rev_range(start, end, &mut current_key, &mut result);
         result
     }
+
+    /// The value stored under the smallest key, without cloning.
+    pub fn first(&self) -> Option<&T> {
+        self.root.first()
+    }
+
+    /// The value stored under the greatest key, without cloning.
+    pub fn last(&self) -> Option<&T> {
+        self.root.last()
+    }
 }
 
 impl<T> RaxNode<T> {
@@ -209,6 +219,28 @@ impl<T> RaxNode<T> {
         }
     }
 
+    fn first(&self) -> Option<&T> {
+        // A key sorts before every longer key it prefixes
+        if self.is_key {
+            return self.data.as_ref();
+        }
+        self.children.values().find_map(|edge| edge.node.first())
+    }
+
+    fn last(&self) -> Option<&T> {
+        // Removal leaves empty nodes behind, so a branch may hold no key
+        for edge in self.children.values().rev() {
+            if let Some(data) = edge.node.last() {
+                return Some(data);
+            }
+        }
+        if self.is_key {
+            self.data.as_ref()
+        } else {
+            None
+        }
+    }
+
     fn rev_range(
         &self,
         start: &[u8],
@@ -1,5 +1,7 @@
-use crate::conf::Config;
+use crate::cmd::key::object_encoding;
+use crate::conf::{Config, EncodingLimits};
 use crate::db::{Db, Entry, SortedSet, TotalOrderF64, Value};
+use crate::resp::format_double;
 use crate::stream::{Consumer, ConsumerGroup, PendingEntry, Stream, StreamEntry, StreamID};
 use bytes::{Buf, Bytes};
 use dashmap::DashMap;
@@ -10,7 +12,8 @@ use std::sync::{Arc, RwLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 // Constants for RDB format
-const RDB_VERSION: u16 = 9;
+const RDB_VERSION: u16 = 10;
+const RDB_OPCODE_FUNCTION2: u8 = 0xF5;
 const RDB_OPCODE_AUX: u8 = 0xFA;
 const RDB_OPCODE_RESIZEDB: u8 = 0xFB;
 const RDB_OPCODE_EXPIRETIME_MS: u8 = 0xFC;
@@ -23,7 +26,14 @@ const RDB_TYPE_LIST: u8 = 1;
 const RDB_TYPE_SET: u8 = 2;
 const RDB_TYPE_ZSET: u8 = 3;
 const RDB_TYPE_HASH: u8 = 4;
+const RDB_TYPE_SET_INTSET: u8 = 11;
 const RDB_TYPE_STREAM_LISTPACKS: u8 = 15;
+const RDB_TYPE_HASH_LISTPACK: u8 = 16;
+const RDB_TYPE_ZSET_LISTPACK: u8 = 17;
+const RDB_TYPE_STREAM_LISTPACKS_2: u8 = 19;
+
+/// A group's unknown `entries_read`, as Redis writes it.
+const STREAM_ENTRIES_READ_INVALID: u64 = u64::MAX;
 
 // CRC-64/Jones — the same variant Redis uses (poly 0xad93d23594c935a9, reflected).
 static CRC64_TABLE: std::sync::OnceLock<[u64; 256]> = std::sync::OnceLock::new();
@@ -74,6 +84,8 @@ pub struct RdbEncoder<W: Write> {
     crc: Crc64,
     compression: bool,
     checksum: bool,
+    /// Function library sources saved ahead of the keys.
+    functions: Vec<String>,
 }
 
 impl<W: Write> RdbEncoder<W> {
@@ -83,9 +95,16 @@ impl<W: Write> RdbEncoder<W> {
             crc: Crc64::new(),
             compression,
             checksum,
+            functions: Vec::new(),
         }
     }
 
+    /// Saves these FUNCTION library sources along with the keys.
+    pub fn with_functions(mut self, functions: Vec<String>) -> Self {
+        self.functions = functions;
+        self
+    }
+
     pub fn digest(&self) -> u64 {
         self.crc.digest()
     }
@@ -102,7 +121,11 @@ impl<W: Write> RdbEncoder<W> {
         Ok(())
     }
 
-    pub fn dump_value(&mut self, value: &Value) -> io::Result<()> {
+    /// Serializes `value` for DUMP. Small collections use the compact
+    /// representation matching their current encoding under `limits`
+    /// (intset or listpack), as Redis does, and fall back to the full one
+    /// past the thresholds.
+    pub fn dump_value(&mut self, value: &Value, limits: &EncodingLimits) -> io::Result<()> {
         match value {
             Value::String(s) => {
                 self.write_u8(RDB_TYPE_STRING)?;
@@ -115,6 +138,10 @@ impl<W: Write> RdbEncoder<W> {
                     self.write_string(item)?;
                 }
             }
+            Value::Set(s) if object_encoding(value, limits) == "intset" => {
+                self.write_u8(RDB_TYPE_SET_INTSET)?;
+                self.write_string(&encode_intset(s))?;
+            }
             Value::Set(s) => {
                 self.write_u8(RDB_TYPE_SET)?;
                 self.write_len(s.len() as u64)?;
@@ -122,6 +149,15 @@ impl<W: Write> RdbEncoder<W> {
                     self.write_string(item)?;
                 }
             }
+            Value::Hash(h) if object_encoding(value, limits) == "listpack" => {
+                let mut lp = ListpackBuilder::new();
+                for (k, v) in h {
+                    lp.append_string(k);
+                    lp.append_string(v);
+                }
+                self.write_u8(RDB_TYPE_HASH_LISTPACK)?;
+                self.write_string(&lp.finish())?;
+            }
             Value::Hash(h) => {
                 self.write_u8(RDB_TYPE_HASH)?;
                 self.write_len(h.len() as u64)?;
@@ -130,6 +166,15 @@ impl<W: Write> RdbEncoder<W> {
                     self.write_string(v)?;
                 }
             }
+            Value::ZSet(z) if object_encoding(value, limits) == "listpack" => {
+                let mut lp = ListpackBuilder::new();
+                for (score, member) in &z.scores {
+                    lp.append_string(member);
+                    lp.append_string(format_double(score.0).as_bytes());
+                }
+                self.write_u8(RDB_TYPE_ZSET_LISTPACK)?;
+                self.write_string(&lp.finish())?;
+            }
             Value::ZSet(z) => {
                 self.write_u8(RDB_TYPE_ZSET)?;
                 self.write_len(z.scores.len() as u64)?;
@@ -144,7 +189,7 @@ impl<W: Write> RdbEncoder<W> {
                 self.write_string(&hll.registers)?;
             }
             Value::Stream(stream) => {
-                self.write_u8(RDB_TYPE_STREAM_LISTPACKS)?;
+                self.write_u8(RDB_TYPE_STREAM_LISTPACKS_2)?;
                 self.save_stream(stream)?;
             }
         }
@@ -152,7 +197,7 @@ impl<W: Write> RdbEncoder<W> {
     }
 
     fn write_magic(&mut self) -> io::Result<()> {
-        self.writer.write_all(b"REDIS0009")?;
+        self.writer.write_all(format!("REDIS{:04}", RDB_VERSION).as_bytes())?;
         Ok(())
     }
 
@@ -281,12 +326,22 @@ impl<W: Write> RdbEncoder<W> {
         self.write_len(stream.last_id.ms)?;
         self.write_len(stream.last_id.seq)?;
 
+        // First ID, max deleted ID and entries added, which XINFO and XADD
+        // can't rebuild from the entries left
+        let first_id = stream.first_id().unwrap_or(StreamID::new(0, 0));
+        self.write_len(first_id.ms)?;
+        self.write_len(first_id.seq)?;
+        self.write_len(stream.max_deleted_id.ms)?;
+        self.write_len(stream.max_deleted_id.seq)?;
+        self.write_len(stream.entries_added)?;
+
         // 4. Consumer Groups
         self.write_len(stream.groups.len() as u64)?;
         for group in stream.groups.values() {
             self.write_string(group.name.as_bytes())?;
             self.write_len(group.last_id.ms)?;
             self.write_len(group.last_id.seq)?;
+            self.write_len(group.entries_read.unwrap_or(STREAM_ENTRIES_READ_INVALID))?;
 
             // PEL
             self.write_len(group.pel.len() as u64)?;
@@ -334,6 +389,11 @@ impl<W: Write> RdbEncoder<W> {
             .as_secs();
         self.write_aux("ctime", &now.to_string())?;
 
+        for code in std::mem::take(&mut self.functions) {
+            self.write_u8(RDB_OPCODE_FUNCTION2)?;
+            self.write_string(code.as_bytes())?;
+        }
+
         for (i, db_lock) in databases.iter().enumerate() {
             let db = db_lock.read().unwrap();
             if db.is_empty() {
@@ -415,7 +475,7 @@ impl<W: Write> RdbEncoder<W> {
                         self.write_string(&hll.registers)?;
                     }
                     Value::Stream(stream) => {
-                        self.write_u8(RDB_TYPE_STREAM_LISTPACKS)?;
+                        self.write_u8(RDB_TYPE_STREAM_LISTPACKS_2)?;
                         self.write_string(key)?;
                         self.save_stream(stream)?;
                     }
@@ -433,6 +493,59 @@ impl<W: Write> RdbEncoder<W> {
 }
 
 // Listpack Builder for Stream RDB compatibility
+/// Encodes an all-integer set as a Redis intset: the element width and count
+/// as little-endian u32s, then the sorted members at that width.
+fn encode_intset(set: &HashSet<Bytes>) -> Vec<u8> {
+    let mut ints: Vec<i64> = set
+        .iter()
+        .filter_map(|m| std::str::from_utf8(m).ok()?.parse().ok())
+        .collect();
+    ints.sort_unstable();
+
+    let width: u32 = if ints.iter().all(|&v| i16::try_from(v).is_ok()) {
+        2
+    } else if ints.iter().all(|&v| i32::try_from(v).is_ok()) {
+        4
+    } else {
+        8
+    };
+
+    let mut buf = Vec::with_capacity(8 + ints.len() * width as usize);
+    buf.extend_from_slice(&width.to_le_bytes());
+    buf.extend_from_slice(&(ints.len() as u32).to_le_bytes());
+    for v in ints {
+        match width {
+            2 => buf.extend_from_slice(&(v as i16).to_le_bytes()),
+            4 => buf.extend_from_slice(&(v as i32).to_le_bytes()),
+            _ => buf.extend_from_slice(&v.to_le_bytes()),
+        }
+    }
+    buf
+}
+
+fn decode_intset(buf: &[u8]) -> io::Result<HashSet<Bytes>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid intset");
+    if buf.len() < 8 {
+        return Err(invalid());
+    }
+    let width = u32::from_le_bytes(buf[0..4].try_into().unwrap()) as usize;
+    let len = u32::from_le_bytes(buf[4..8].try_into().unwrap()) as usize;
+    if !matches!(width, 2 | 4 | 8) || buf.len() != 8 + width * len {
+        return Err(invalid());
+    }
+
+    let mut set = HashSet::with_capacity(len);
+    for chunk in buf[8..].chunks_exact(width) {
+        let v = match width {
+            2 => i16::from_le_bytes(chunk.try_into().unwrap()) as i64,
+            4 => i32::from_le_bytes(chunk.try_into().unwrap()) as i64,
+            _ => i64::from_le_bytes(chunk.try_into().unwrap()),
+        };
+        set.insert(Bytes::from(v.to_string()));
+    }
+    Ok(set)
+}
+
 struct ListpackBuilder {
     buf: Vec<u8>,
     num_elements: u16,
@@ -694,9 +807,40 @@ impl<'a> ListpackReader<'a> {
     }
 }
 
+/// Reads every element of a listpack, rendering integers in decimal.
+fn listpack_entries(buf: &[u8]) -> io::Result<Vec<Bytes>> {
+    let mut lp = ListpackReader::new(buf);
+
+    // Skip Header (Total Bytes 4, Num Elements 2)
+    lp.read_u32_le()?;
+    lp.read_u16_le()?;
+
+    let mut entries = Vec::new();
+    while let Some(element) = lp.read_element()? {
+        entries.push(match element {
+            LpElement::Int(v) => Bytes::from(v.to_string()),
+            LpElement::String(s) => Bytes::from(s),
+        });
+    }
+    Ok(entries)
+}
+
+/// Reads a listpack of alternating field/value (or member/score) entries.
+fn listpack_pairs(buf: &[u8]) -> io::Result<Vec<Bytes>> {
+    let entries = listpack_entries(buf)?;
+    if entries.len() % 2 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Odd number of listpack entries",
+        ));
+    }
+    Ok(entries)
+}
+
 pub struct RdbLoader<R: Read> {
     reader: R,
     crc: Crc64,
+    functions: Vec<String>,
 }
 
 impl<R: Read> RdbLoader<R> {
@@ -704,9 +848,15 @@ impl<R: Read> RdbLoader<R> {
         RdbLoader {
             reader,
             crc: Crc64::new(),
+            functions: Vec::new(),
         }
     }
 
+    /// FUNCTION library sources found by `load`, for the caller to load.
+    pub fn functions(&self) -> &[String] {
+        &self.functions
+    }
+
     pub fn digest(&self) -> u64 {
         self.crc.digest()
     }
@@ -723,6 +873,21 @@ impl<R: Read> RdbLoader<R> {
         Ok(buf[0])
     }
 
+    /// Reads a length-prefixed payload. The buffer grows with what is
+    /// actually read, so a corrupt length hits EOF instead of allocating it.
+    fn read_bytes(&mut self, len: u64) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.reader.by_ref().take(len).read_to_end(&mut buf)?;
+        if (buf.len() as u64) < len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Unexpected end of RDB file",
+            ));
+        }
+        self.crc.update(&buf);
+        Ok(buf)
+    }
+
     fn read_u32_be(&mut self) -> io::Result<u32> {
         let mut buf = [0u8; 4];
         self.read_exact(&mut buf)?;
@@ -805,8 +970,7 @@ impl<R: Read> RdbLoader<R> {
                     // LZF compressed - SKIP for now
                     let (clen, _) = self.read_len()?;
                     let (_ulen, _) = self.read_len()?;
-                    let mut compressed = vec![0u8; clen as usize];
-                    self.read_exact(&mut compressed)?;
+                    self.read_bytes(clen)?;
                     return Err(io::Error::new(
                         io::ErrorKind::InvalidData,
                         "LZF compression not supported",
@@ -820,14 +984,16 @@ impl<R: Read> RdbLoader<R> {
                 }
             }
         } else {
-            let mut buf = vec![0u8; len as usize];
-            self.read_exact(&mut buf)?;
-            Ok(Bytes::from(buf))
+            Ok(Bytes::from(self.read_bytes(len)?))
         }
     }
 
     pub fn restore_value(&mut self) -> io::Result<Value> {
         let type_code = self.read_u8()?;
+        self.read_object(type_code)
+    }
+
+    fn read_object(&mut self, type_code: u8) -> io::Result<Value> {
         match type_code {
             RDB_TYPE_STRING => {
                 let s = self.read_string()?;
@@ -849,6 +1015,10 @@ impl<R: Read> RdbLoader<R> {
                 }
                 Ok(Value::Set(set))
             }
+            RDB_TYPE_SET_INTSET => {
+                let buf = self.read_string()?;
+                Ok(Value::Set(decode_intset(&buf)?))
+            }
             RDB_TYPE_HASH => {
                 let (len, _) = self.read_len()?;
                 let mut hash = HashMap::new();
@@ -859,6 +1029,15 @@ impl<R: Read> RdbLoader<R> {
                 }
                 Ok(Value::Hash(hash))
             }
+            RDB_TYPE_HASH_LISTPACK => {
+                let buf = self.read_string()?;
+                let entries = listpack_pairs(&buf)?;
+                let mut hash = HashMap::new();
+                for pair in entries.chunks_exact(2) {
+                    hash.insert(pair[0].clone(), pair[1].clone());
+                }
+                Ok(Value::Hash(hash))
+            }
             RDB_TYPE_ZSET => {
                 let (len, _) = self.read_len()?;
                 let mut zset = SortedSet::new();
@@ -872,8 +1051,25 @@ impl<R: Read> RdbLoader<R> {
                 }
                 Ok(Value::ZSet(zset))
             }
-            RDB_TYPE_STREAM_LISTPACKS => {
-                let stream = self.load_stream()?;
+            RDB_TYPE_ZSET_LISTPACK => {
+                let buf = self.read_string()?;
+                let entries = listpack_pairs(&buf)?;
+                let mut zset = SortedSet::new();
+                for pair in entries.chunks_exact(2) {
+                    let score = std::str::from_utf8(&pair[1])
+                        .ok()
+                        .and_then(|s| s.parse::<f64>().ok())
+                        .filter(|score| !score.is_nan())
+                        .ok_or_else(|| {
+                            io::Error::new(io::ErrorKind::InvalidData, "Invalid zset score")
+                        })?;
+                    zset.members.insert(pair[0].clone(), score);
+                    zset.scores.insert((TotalOrderF64(score), pair[0].clone()));
+                }
+                Ok(Value::ZSet(zset))
+            }
+            RDB_TYPE_STREAM_LISTPACKS | RDB_TYPE_STREAM_LISTPACKS_2 => {
+                let stream = self.load_stream(type_code == RDB_TYPE_STREAM_LISTPACKS_2)?;
                 Ok(Value::Stream(stream))
             }
             _ => Err(io::Error::new(
@@ -883,13 +1079,21 @@ impl<R: Read> RdbLoader<R> {
         }
     }
 
-    fn load_stream(&mut self) -> io::Result<Stream> {
+    /// Loads a stream; `v2` streams also carry the counters and group
+    /// entries read that older files leave to be rebuilt.
+    fn load_stream(&mut self, v2: bool) -> io::Result<Stream> {
         let mut stream = Stream::new();
 
         // 1. Listpacks
         let (num_listpacks, _) = self.read_len()?;
         for _ in 0..num_listpacks {
             let master_id_bytes = self.read_string()?;
+            if master_id_bytes.len() != 16 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Invalid stream master ID",
+                ));
+            }
             let lp_bytes = self.read_string()?;
 
             let mut lp = ListpackReader::new(&lp_bytes);
@@ -990,6 +1194,16 @@ impl<R: Read> RdbLoader<R> {
         let seq = self.read_len()?.0;
         stream.last_id = StreamID::new(ms, seq);
 
+        if v2 {
+            // The first ID is recomputed from the entries
+            self.read_len()?;
+            self.read_len()?;
+            let ms = self.read_len()?.0;
+            let seq = self.read_len()?.0;
+            stream.max_deleted_id = StreamID::new(ms, seq);
+            stream.entries_added = self.read_len()?.0;
+        }
+
         // 4. Consumer Groups
         let (num_groups, _) = self.read_len()?;
         for _ in 0..num_groups {
@@ -1002,6 +1216,11 @@ impl<R: Read> RdbLoader<R> {
             let last_id = StreamID::new(ms, seq);
 
             let mut group = ConsumerGroup::new(name.clone(), last_id);
+            if v2 {
+                let entries_read = self.read_len()?.0;
+                group.entries_read =
+                    (entries_read != STREAM_ENTRIES_READ_INVALID).then_some(entries_read);
+            }
 
             // PEL
             let (pel_len, _) = self.read_len()?;
@@ -1034,6 +1253,8 @@ impl<R: Read> RdbLoader<R> {
 
                 let mut consumer = Consumer::new(cname.clone());
                 consumer.seen_time = self.read_u64_le()? as u128;
+                // This RDB type carries no active time; Redis falls back to seen time
+                consumer.active_time = consumer.seen_time;
 
                 // Consumer PEL
                 let (cpel_len, _) = self.read_len()?;
@@ -1092,6 +1313,12 @@ impl<R: Read> RdbLoader<R> {
                     let _key = self.read_string()?;
                     let _val = self.read_string()?;
                 }
+                RDB_OPCODE_FUNCTION2 => {
+                    let code = self.read_string()?;
+                    let code = String::from_utf8(code.to_vec())
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    self.functions.push(code);
+                }
                 RDB_OPCODE_RESIZEDB => {
                     let _db_size = self.read_len()?;
                     let _expires_size = self.read_len()?;
@@ -1109,66 +1336,17 @@ impl<R: Read> RdbLoader<R> {
                     current_db_index = id as usize;
                 }
                 RDB_OPCODE_EOF => {
+                    // Version 5 and later end with an 8 byte checksum; a file
+                    // cut short before it is truncated, not complete
+                    if version >= 5 {
+                        self.read_u64_le()?;
+                    }
                     break;
                 }
                 type_code => {
-                    // It's a value type (0..14)
+                    // It's a value type
                     let key = self.read_string()?;
-                    let val = match type_code {
-                        RDB_TYPE_STRING => {
-                            let s = self.read_string()?;
-                            Value::String(s)
-                        }
-                        RDB_TYPE_LIST => {
-                            let (len, _) = self.read_len()?;
-                            let mut list = VecDeque::new();
-                            for _ in 0..len {
-                                list.push_back(self.read_string()?);
-                            }
-                            Value::List(list)
-                        }
-                        RDB_TYPE_SET => {
-                            let (len, _) = self.read_len()?;
-                            let mut set = HashSet::new();
-                            for _ in 0..len {
-                                set.insert(self.read_string()?);
-                            }
-                            Value::Set(set)
-                        }
-                        RDB_TYPE_HASH => {
-                            let (len, _) = self.read_len()?;
-                            let mut hash = HashMap::new();
-                            for _ in 0..len {
-                                let k = self.read_string()?;
-                                let v = self.read_string()?;
-                                hash.insert(k, v);
-                            }
-                            Value::Hash(hash)
-                        }
-                        RDB_TYPE_ZSET => {
-                            let (len, _) = self.read_len()?;
-                            let mut zset = SortedSet::new();
-                            for _ in 0..len {
-                                let member = self.read_string()?;
-                                let score_bytes = self.read_string()?;
-                                let score_str = String::from_utf8_lossy(&score_bytes);
-                                let score = score_str.parse::<f64>().unwrap_or(0.0);
-                                zset.members.insert(member.clone(), score);
-                                zset.scores.insert((TotalOrderF64(score), member));
-                            }
-                            Value::ZSet(zset)
-                        }
-                        RDB_TYPE_STREAM_LISTPACKS => {
-                            let stream = self.load_stream()?;
-                            Value::Stream(stream)
-                        }
-                        _ => {
-                            return Err(io::Error::new(
-                                io::ErrorKind::InvalidData,
-                                format!("Unknown value type: {}", type_code),
-                            ));
-                        }
-                    };
+                    let val = self.read_object(type_code)?;
 
                     if let Some(db_lock) = databases.get(current_db_index) {
                         // expire_at from RDB is an absolute timestamp in ms,
@@ -1187,14 +1365,21 @@ impl<R: Read> RdbLoader<R> {
     }
 }
 
-pub fn rdb_save(databases: &Arc<Vec<RwLock<Db>>>, conf: &Config) -> io::Result<()> {
+/// Saves `databases` and the FUNCTION library sources in `functions` to
+/// `conf.dbfilename`.
+pub fn rdb_save(
+    databases: &Arc<Vec<RwLock<Db>>>,
+    functions: Vec<String>,
+    conf: &Config,
+) -> io::Result<()> {
     // Write to a temp file first; rename atomically on success so a crash
     // mid-save never leaves a corrupt final RDB file.
     let tmp_path = format!("{}.tmp.{}", conf.dbfilename, std::process::id());
     let result = (|| -> io::Result<()> {
         let file = File::create(&tmp_path)?;
         let writer = BufWriter::new(file);
-        let mut encoder = RdbEncoder::new(writer, conf.rdbcompression, conf.rdbchecksum);
+        let mut encoder = RdbEncoder::new(writer, conf.rdbcompression, conf.rdbchecksum)
+            .with_functions(functions);
         encoder.save(databases)?;
         // Recover the BufWriter to call fsync — ensures data reaches disk
         // before the rename so a crash mid-rename still leaves the old file intact.
@@ -1216,12 +1401,15 @@ pub fn rdb_save(databases: &Arc<Vec<RwLock<Db>>>, conf: &Config) -> io::Result<(
     }
 }
 
-pub fn rdb_load(databases: &Arc<Vec<RwLock<Db>>>, conf: &Config) -> io::Result<()> {
+/// Loads `conf.dbfilename` into `databases`, returning the FUNCTION library
+/// sources it holds.
+pub fn rdb_load(databases: &Arc<Vec<RwLock<Db>>>, conf: &Config) -> io::Result<Vec<String>> {
     if !std::path::Path::new(&conf.dbfilename).exists() {
-        return Ok(());
+        return Ok(Vec::new());
     }
     let file = File::open(&conf.dbfilename)?;
     let reader = BufReader::new(file);
     let mut loader = RdbLoader::new(reader);
-    loader.load(databases)
+    loader.load(databases)?;
+    Ok(loader.functions)
 }
@@ -2,12 +2,11 @@ use bytes::{Bytes, BytesMut};
 use std::future::Future;
 use std::io::{self, ErrorKind};
 use std::pin::Pin;
-use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufWriter};
-use tokio::net::tcp::OwnedWriteHalf;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 /// Format a signed integer into a stack buffer without heap allocation.
 /// Returns the ASCII decimal bytes slice.
-fn fmt_int(n: i64, buf: &mut [u8; 20]) -> &[u8] {
+pub fn fmt_int(n: i64, buf: &mut [u8; 20]) -> &[u8] {
     if n == 0 {
         buf[19] = b'0';
         return &buf[19..];
@@ -32,7 +31,7 @@ fn fmt_int(n: i64, buf: &mut [u8; 20]) -> &[u8] {
 }
 
 /// Format a usize into a stack buffer without heap allocation.
-fn fmt_usize(n: usize, buf: &mut [u8; 20]) -> &[u8] {
+pub fn fmt_usize(n: usize, buf: &mut [u8; 20]) -> &[u8] {
     if n == 0 {
         buf[19] = b'0';
         return &buf[19..];
@@ -58,6 +57,27 @@ pub enum Resp {
     Integer(i64),
     BulkString(Option<Bytes>),
     Array(Option<Vec<Resp>>),
+    /// RESP3 set (`~`); only sent to clients that negotiated protocol 3.
+    Set(Vec<Resp>),
+    /// RESP3 verbatim string (`=`) in `txt` format; protocol 3 clients only.
+    Verbatim(Bytes),
+    /// RESP3 map (`%`) of key/value pairs; a flat key/value array for
+    /// protocol 2 clients.
+    Map(Vec<(Resp, Resp)>),
+    /// RESP3 double (`,`); a bulk string for protocol 2 clients.
+    Double(f64),
+    /// RESP3 big number (`(`), kept as its decimal digits; protocol 3 clients only.
+    BigNumber(Bytes),
+    /// RESP3 boolean (`#t`/`#f`); an integer 1/0 for protocol 2 clients.
+    Boolean(bool),
+    /// RESP3 null (`_`); a nil bulk string for protocol 2 clients.
+    Null,
+    /// RESP3 out-of-band push (`>`); a plain array for protocol 2 clients.
+    Push(Vec<Resp>),
+    /// A reply the command encoded for the connection's protocol while it
+    /// walked its collection (see [`ReplyEncoder`]), kept as the wire chunks
+    /// to write in order.
+    Encoded(Vec<Bytes>),
     #[allow(dead_code)]
     Multiple(Vec<Resp>),
     #[allow(dead_code)]
@@ -139,7 +159,34 @@ where
     }
 }
 
-async fn read_bulk_string<R>(reader: &mut R) -> io::Result<Option<Resp>>
+/// A request the client must be told about before its connection is closed,
+/// carried inside an `InvalidData` `io::Error`.
+#[derive(Debug)]
+pub struct ProtocolError(&'static str);
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Protocol error: {}", self.0)
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl ProtocolError {
+    /// Whether `e` wraps a `ProtocolError`.
+    pub fn is(e: &io::Error) -> bool {
+        e.get_ref().is_some_and(|inner| inner.is::<ProtocolError>())
+    }
+}
+
+fn invalid_bulk_length() -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        ProtocolError("invalid bulk length"),
+    )
+}
+
+async fn read_bulk_string<R>(reader: &mut R, max_bulk_len: u64) -> io::Result<Option<Resp>>
 where
     R: AsyncBufReadExt + AsyncReadExt + Unpin,
 {
@@ -164,6 +211,9 @@ where
         loop {
             let b = reader.read_u8().await?;
             buf.push(b);
+            if buf.len().saturating_sub(d_len) as u64 > max_bulk_len {
+                return Err(invalid_bulk_length());
+            }
             if buf.len() >= d_len {
                 if &buf[buf.len() - d_len..] == delimiter_bytes {
                     buf.truncate(buf.len() - d_len);
@@ -187,6 +237,9 @@ where
             "negative bulk string length",
         ));
     }
+    if len as u64 > max_bulk_len {
+        return Err(invalid_bulk_length());
+    }
     let mut buf = BytesMut::with_capacity(len as usize);
     buf.resize(len as usize, 0);
     reader.read_exact(&mut buf[..]).await?;
@@ -201,7 +254,7 @@ where
     Ok(Some(Resp::BulkString(Some(buf.freeze()))))
 }
 
-async fn read_array<R>(reader: &mut R) -> io::Result<Option<Resp>>
+async fn read_array<R>(reader: &mut R, max_bulk_len: u64) -> io::Result<Option<Resp>>
 where
     R: AsyncBufReadExt + AsyncReadExt + Unpin + Send,
 {
@@ -220,7 +273,7 @@ where
     }
     let mut items = Vec::with_capacity(len as usize);
     for _ in 0..len {
-        let frame = match read_frame(reader).await? {
+        let frame = match read_frame_inner(reader, max_bulk_len).await? {
             Some(f) => f,
             None => return Ok(None),
         };
@@ -229,9 +282,59 @@ where
     Ok(Some(Resp::Array(Some(items))))
 }
 
+async fn read_map<R>(reader: &mut R, max_bulk_len: u64) -> io::Result<Option<Resp>>
+where
+    R: AsyncBufReadExt + AsyncReadExt + Unpin + Send,
+{
+    let len = match read_integer_line(reader).await? {
+        Some(l) => l,
+        None => return Ok(None),
+    };
+    if len < 0 {
+        return Err(io::Error::new(ErrorKind::InvalidData, "invalid map length"));
+    }
+    let mut pairs = Vec::with_capacity(len as usize);
+    for _ in 0..len {
+        let key = match read_frame_inner(reader, max_bulk_len).await? {
+            Some(f) => f,
+            None => return Ok(None),
+        };
+        let value = match read_frame_inner(reader, max_bulk_len).await? {
+            Some(f) => f,
+            None => return Ok(None),
+        };
+        pairs.push((key, value));
+    }
+    Ok(Some(Resp::Map(pairs)))
+}
+
+/// Reads one frame with no bound on bulk string length. Used for replies,
+/// replication streams and AOF replay, which come from trusted peers.
 pub fn read_frame<'a, R>(
     reader: &'a mut R,
 ) -> Pin<Box<dyn Future<Output = io::Result<Option<Resp>>> + Send + 'a>>
+where
+    R: AsyncBufReadExt + AsyncReadExt + Unpin + Send,
+{
+    read_frame_inner(reader, u64::MAX)
+}
+
+/// Reads one client request, rejecting any bulk string longer than
+/// `max_bulk_len` (proto-max-bulk-len) with a protocol error.
+pub fn read_request<'a, R>(
+    reader: &'a mut R,
+    max_bulk_len: u64,
+) -> Pin<Box<dyn Future<Output = io::Result<Option<Resp>>> + Send + 'a>>
+where
+    R: AsyncBufReadExt + AsyncReadExt + Unpin + Send,
+{
+    read_frame_inner(reader, max_bulk_len)
+}
+
+fn read_frame_inner<'a, R>(
+    reader: &'a mut R,
+    max_bulk_len: u64,
+) -> Pin<Box<dyn Future<Output = io::Result<Option<Resp>>> + Send + 'a>>
 where
     R: AsyncBufReadExt + AsyncReadExt + Unpin + Send,
 {
@@ -268,17 +371,71 @@ where
                 };
                 Ok(Some(Resp::Integer(value)))
             }
-            b'$' => read_bulk_string(reader).await,
-            b'*' => read_array(reader).await,
+            b'$' => read_bulk_string(reader, max_bulk_len).await,
+            b'=' => match read_bulk_string(reader, max_bulk_len).await? {
+                Some(Resp::BulkString(Some(data))) if data.len() >= 4 && data[3] == b':' => {
+                    Ok(Some(Resp::Verbatim(data.slice(4..))))
+                }
+                Some(_) => Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "invalid verbatim string",
+                )),
+                None => Ok(None),
+            },
+            b'*' => read_array(reader, max_bulk_len).await,
+            b'~' => match read_array(reader, max_bulk_len).await? {
+                Some(Resp::Array(Some(items))) => Ok(Some(Resp::Set(items))),
+                Some(_) => Err(io::Error::new(ErrorKind::InvalidData, "invalid set length")),
+                None => Ok(None),
+            },
+            b'>' => match read_array(reader, max_bulk_len).await? {
+                Some(Resp::Array(Some(items))) => Ok(Some(Resp::Push(items))),
+                Some(_) => Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "invalid push length",
+                )),
+                None => Ok(None),
+            },
+            b'%' => read_map(reader, max_bulk_len).await,
+            b',' => {
+                let line = match read_line(reader).await? {
+                    Some(l) => l,
+                    None => return Ok(None),
+                };
+                match line.parse::<f64>() {
+                    Ok(d) => Ok(Some(Resp::Double(d))),
+                    Err(_) => Err(io::Error::new(ErrorKind::InvalidData, "invalid double")),
+                }
+            }
+            b'(' => {
+                let line = match read_line(reader).await? {
+                    Some(l) => l,
+                    None => return Ok(None),
+                };
+                Ok(Some(Resp::BigNumber(Bytes::from(line))))
+            }
+            b'#' => match read_line(reader).await?.as_deref() {
+                Some("t") => Ok(Some(Resp::Boolean(true))),
+                Some("f") => Ok(Some(Resp::Boolean(false))),
+                Some(_) => Err(io::Error::new(ErrorKind::InvalidData, "invalid boolean")),
+                None => Ok(None),
+            },
+            b'_' => match read_line(reader).await? {
+                Some(_) => Ok(Some(Resp::Null)),
+                None => Ok(None),
+            },
             _ => Err(io::Error::new(ErrorKind::InvalidData, "unknown RESP type")),
         }
     })
 }
 
-pub fn write_frame<'a>(
-    writer: &'a mut BufWriter<OwnedWriteHalf>,
+pub fn write_frame<'a, W>(
+    writer: &'a mut W,
     frame: &'a Resp,
-) -> Pin<Box<dyn Future<Output = io::Result<()>> + Send + 'a>> {
+) -> Pin<Box<dyn Future<Output = io::Result<()>> + Send + 'a>>
+where
+    W: AsyncWrite + Unpin + Send,
+{
     Box::pin(async move {
         match frame {
             Resp::SimpleString(s) => {
@@ -325,6 +482,64 @@ pub fn write_frame<'a>(
                     write_frame(writer, item).await?;
                 }
             }
+            Resp::Verbatim(data) => {
+                let mut buf = [0u8; 20];
+                writer.write_all(b"=").await?;
+                writer
+                    .write_all(fmt_usize(data.len() + 4, &mut buf))
+                    .await?;
+                writer.write_all(b"\r\ntxt:").await?;
+                writer.write_all(data).await?;
+                writer.write_all(b"\r\n").await?;
+            }
+            Resp::Set(items) | Resp::Push(items) => {
+                let mut buf = [0u8; 20];
+                writer
+                    .write_all(if let Resp::Push(_) = frame {
+                        b">"
+                    } else {
+                        b"~"
+                    })
+                    .await?;
+                writer.write_all(fmt_usize(items.len(), &mut buf)).await?;
+                writer.write_all(b"\r\n").await?;
+                for item in items {
+                    write_frame(writer, item).await?;
+                }
+            }
+            Resp::Map(pairs) => {
+                let mut buf = [0u8; 20];
+                writer.write_all(b"%").await?;
+                writer.write_all(fmt_usize(pairs.len(), &mut buf)).await?;
+                writer.write_all(b"\r\n").await?;
+                for (key, value) in pairs {
+                    write_frame(writer, key).await?;
+                    write_frame(writer, value).await?;
+                }
+            }
+            Resp::Double(d) => {
+                writer.write_all(b",").await?;
+                writer.write_all(format_double(*d).as_bytes()).await?;
+                writer.write_all(b"\r\n").await?;
+            }
+            Resp::BigNumber(n) => {
+                writer.write_all(b"(").await?;
+                writer.write_all(n.as_ref()).await?;
+                writer.write_all(b"\r\n").await?;
+            }
+            Resp::Boolean(b) => {
+                writer
+                    .write_all(if *b { b"#t\r\n" } else { b"#f\r\n" })
+                    .await?;
+            }
+            Resp::Null => {
+                writer.write_all(b"_\r\n").await?;
+            }
+            Resp::Encoded(chunks) => {
+                for chunk in chunks {
+                    writer.write_all(chunk.as_ref()).await?;
+                }
+            }
             Resp::Multiple(items) => {
                 for item in items {
                     write_frame(writer, item).await?;
@@ -336,7 +551,135 @@ pub fn write_frame<'a>(
     })
 }
 
+/// Replies with at least this many elements are encoded by a
+/// [`ReplyEncoder`] instead of first collecting one `Resp` per element.
+pub const STREAM_REPLY_MIN_ELEMENTS: usize = 1024;
+
+/// The aggregate a [`ReplyEncoder`] reply is sent as.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplyKind {
+    Array,
+    /// Alternating keys and values; a flat array for protocol 2 clients.
+    Map,
+    /// A flat array for protocol 2 clients.
+    Set,
+}
+
+/// Encodes a huge reply while the command walks its collection: the header
+/// goes out first with the known length, then each element is written as it
+/// is visited. Nothing is kept per element, so the reply costs its encoded
+/// size and never a second copy of the collection. Framing and small
+/// elements are packed into fixed-size chunks; elements of at least
+/// [`ReplyEncoder::SHARE_MIN_LEN`] bytes become chunks of their own that share
+/// the stored bytes.
+pub struct ReplyEncoder {
+    chunks: Vec<Bytes>,
+    buf: BytesMut,
+}
+
+impl ReplyEncoder {
+    /// Size of the chunks framing and small elements are packed into.
+    pub const CHUNK_SIZE: usize = 64 * 1024;
+    /// Elements at least this long are shared rather than copied.
+    pub const SHARE_MIN_LEN: usize = 4096;
+
+    /// Starts a reply of `len` elements (pairs, for a map) encoded for
+    /// `protocol`.
+    pub fn new(protocol: u8, kind: ReplyKind, len: usize) -> Self {
+        let mut encoder = ReplyEncoder {
+            chunks: Vec::new(),
+            buf: BytesMut::with_capacity(Self::CHUNK_SIZE),
+        };
+        let (tag, len) = match kind {
+            ReplyKind::Map if protocol >= 3 => (b'%', len),
+            ReplyKind::Map => (b'*', len * 2),
+            ReplyKind::Set if protocol >= 3 => (b'~', len),
+            _ => (b'*', len),
+        };
+        encoder.header(tag, len);
+        encoder
+    }
+
+    fn header(&mut self, tag: u8, len: usize) {
+        let mut num = [0u8; 20];
+        self.reserve(23);
+        self.buf.extend_from_slice(&[tag]);
+        self.buf.extend_from_slice(fmt_usize(len, &mut num));
+        self.buf.extend_from_slice(b"\r\n");
+    }
+
+    /// Starts a new chunk when `additional` bytes no longer fit the current
+    /// one, so the buffer never reallocates and copies what it holds.
+    fn reserve(&mut self, additional: usize) {
+        if self.buf.len() + additional > self.buf.capacity() && !self.buf.is_empty() {
+            self.chunks.push(self.buf.split().freeze());
+            self.buf = BytesMut::with_capacity(Self::CHUNK_SIZE.max(additional));
+        }
+    }
+
+    /// Writes the next element as a bulk string.
+    pub fn bulk(&mut self, value: &Bytes) {
+        if value.len() >= Self::SHARE_MIN_LEN {
+            self.header(b'$', value.len());
+            self.chunks.push(self.buf.split().freeze());
+            self.chunks.push(value.clone());
+            self.buf.extend_from_slice(b"\r\n");
+            return;
+        }
+        let mut num = [0u8; 20];
+        self.reserve(value.len() + 25);
+        self.buf.extend_from_slice(b"$");
+        self.buf.extend_from_slice(fmt_usize(value.len(), &mut num));
+        self.buf.extend_from_slice(b"\r\n");
+        self.buf.extend_from_slice(value);
+        self.buf.extend_from_slice(b"\r\n");
+    }
+
+    pub fn finish(mut self) -> Resp {
+        if !self.buf.is_empty() {
+            self.chunks.push(self.buf.freeze());
+        }
+        Resp::Encoded(self.chunks)
+    }
+}
+
 impl Resp {
+    /// The reply as a client speaking `protocol` receives it. Commands build
+    /// the RESP3 shape of their reply; for protocol 2 the RESP3-only types
+    /// are rewritten, recursively, into their RESP2 equivalents, and for
+    /// protocol 3 the RESP2 nil bulk and nil array become the RESP3 null.
+    pub fn into_protocol(self, protocol: u8) -> Resp {
+        match self {
+            Resp::Array(Some(items)) => Resp::Array(Some(convert_all(items, protocol))),
+            Resp::Set(items) if protocol >= 3 => Resp::Set(convert_all(items, protocol)),
+            Resp::Push(items) if protocol >= 3 => Resp::Push(convert_all(items, protocol)),
+            Resp::Set(items) | Resp::Push(items) => Resp::Array(Some(convert_all(items, protocol))),
+            Resp::Map(pairs) if protocol >= 3 => Resp::Map(
+                pairs
+                    .into_iter()
+                    .map(|(key, value)| {
+                        (key.into_protocol(protocol), value.into_protocol(protocol))
+                    })
+                    .collect(),
+            ),
+            Resp::Map(pairs) => {
+                let mut items = Vec::with_capacity(pairs.len() * 2);
+                for (key, value) in pairs {
+                    items.push(key.into_protocol(protocol));
+                    items.push(value.into_protocol(protocol));
+                }
+                Resp::Array(Some(items))
+            }
+            Resp::BulkString(None) | Resp::Array(None) if protocol >= 3 => Resp::Null,
+            other if protocol >= 3 => other,
+            Resp::Verbatim(data) | Resp::BigNumber(data) => Resp::BulkString(Some(data)),
+            Resp::Double(d) => Resp::BulkString(Some(Bytes::from(format_double(d)))),
+            Resp::Boolean(b) => Resp::Integer(b as i64),
+            Resp::Null => Resp::BulkString(None),
+            other => other,
+        }
+    }
+
     #[allow(dead_code)]
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut buf = [0u8; 20];
@@ -393,6 +736,57 @@ impl Resp {
                 }
                 v
             }
+            Resp::Verbatim(data) => {
+                let len_bytes = fmt_usize(data.len() + 4, &mut buf);
+                let mut v = Vec::with_capacity(3 + len_bytes.len() + 4 + data.len() + 2);
+                v.push(b'=');
+                v.extend_from_slice(len_bytes);
+                v.extend_from_slice(b"\r\ntxt:");
+                v.extend_from_slice(data.as_ref());
+                v.extend_from_slice(b"\r\n");
+                v
+            }
+            Resp::Set(items) | Resp::Push(items) => {
+                let len_bytes = fmt_usize(items.len(), &mut buf);
+                let mut v = Vec::with_capacity(3 + len_bytes.len());
+                v.push(if let Resp::Push(_) = self { b'>' } else { b'~' });
+                v.extend_from_slice(len_bytes);
+                v.extend_from_slice(b"\r\n");
+                for item in items {
+                    v.extend_from_slice(&item.as_bytes());
+                }
+                v
+            }
+            Resp::Map(pairs) => {
+                let len_bytes = fmt_usize(pairs.len(), &mut buf);
+                let mut v = Vec::with_capacity(3 + len_bytes.len());
+                v.push(b'%');
+                v.extend_from_slice(len_bytes);
+                v.extend_from_slice(b"\r\n");
+                for (key, value) in pairs {
+                    v.extend_from_slice(&key.as_bytes());
+                    v.extend_from_slice(&value.as_bytes());
+                }
+                v
+            }
+            Resp::Double(d) => {
+                let text = format_double(*d);
+                let mut v = Vec::with_capacity(3 + text.len());
+                v.push(b',');
+                v.extend_from_slice(text.as_bytes());
+                v.extend_from_slice(b"\r\n");
+                v
+            }
+            Resp::BigNumber(n) => {
+                let mut v = Vec::with_capacity(3 + n.len());
+                v.push(b'(');
+                v.extend_from_slice(n.as_ref());
+                v.extend_from_slice(b"\r\n");
+                v
+            }
+            Resp::Boolean(b) => if *b { b"#t\r\n" } else { b"#f\r\n" }.to_vec(),
+            Resp::Null => b"_\r\n".to_vec(),
+            Resp::Encoded(chunks) => chunks.concat(),
             Resp::Multiple(items) => {
                 let mut v = Vec::new();
                 for item in items {
@@ -405,6 +799,21 @@ impl Resp {
     }
 }
 
+/// Converts each element for `protocol`. Arrays of plain bulk strings and
+/// integers, by far the most common large replies, are returned as they are.
+fn convert_all(items: Vec<Resp>, protocol: u8) -> Vec<Resp> {
+    if items
+        .iter()
+        .all(|item| matches!(item, Resp::BulkString(Some(_)) | Resp::Integer(_)))
+    {
+        return items;
+    }
+    items
+        .into_iter()
+        .map(|item| item.into_protocol(protocol))
+        .collect()
+}
+
 #[allow(dead_code)]
 pub fn as_bytes(r: &Resp) -> Option<&[u8]> {
     match r {
@@ -414,3 +823,32 @@ pub fn as_bytes(r: &Resp) -> Option<&[u8]> {
         _ => None,
     }
 }
+
+/// Formats a double the way Redis replies with it: `inf`/`-inf`, no trailing
+/// `.0` on integral values, and the shortest digits that round-trip. Like
+/// `%.17g`, exponents below -4 or at/above 17 switch to `1.5e+20` notation.
+pub fn format_double(v: f64) -> String {
+    if v.is_nan() {
+        return "nan".to_string();
+    }
+    if v.is_infinite() {
+        return if v > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    // Integral scores are the common case: format them on the stack and
+    // allocate only the result. `-0` keeps its sign through the slow path.
+    if v.fract() == 0.0 && v.abs() < 1e17 && !(v == 0.0 && v.is_sign_negative()) {
+        let mut buf = [0u8; 20];
+        return String::from_utf8_lossy(fmt_int(v as i64, &mut buf)).into_owned();
+    }
+    let sci = format!("{:e}", v);
+    let (mantissa, exp) = match sci.split_once('e') {
+        Some((m, e)) => (m, e.parse::<i32>().unwrap_or(0)),
+        None => return sci,
+    };
+    if (-4..17).contains(&exp) {
+        v.to_string()
+    } else {
+        let sign = if exp < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", mantissa, sign, exp.abs())
+    }
+}
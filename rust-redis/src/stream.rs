@@ -22,21 +22,46 @@ impl StreamID {
         bytes[8..16].copy_from_slice(&self.seq.to_be_bytes());
         bytes
     }
+
+    /// The smallest ID greater than this one, or None at `u64::MAX-u64::MAX`.
+    pub fn next(&self) -> Option<StreamID> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(StreamID::new(self.ms, seq)),
+            None => Some(StreamID::new(self.ms.checked_add(1)?, 0)),
+        }
+    }
+
+    /// Parses `ms-seq`, or a bare `ms` with `missing_seq` as its sequence.
+    /// Range queries pass `u64::MAX` for an end bound so `5` covers every
+    /// entry of that millisecond.
+    pub fn parse(s: &str, missing_seq: u64) -> Result<Self, String> {
+        let (ms, seq) = match s.split_once('-') {
+            Some((ms, seq)) => (ms, Some(seq)),
+            None => (s, None),
+        };
+        let ms = parse_id_part(ms)?;
+        let seq = match seq {
+            Some(seq) => parse_id_part(seq)?,
+            None => missing_seq,
+        };
+        Ok(StreamID { ms, seq })
+    }
+}
+
+/// Digits only: `u64::from_str` would also take a leading `+`.
+fn parse_id_part(s: &str) -> Result<u64, String> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("Invalid Stream ID format".to_string());
+    }
+    s.parse::<u64>().map_err(|e| e.to_string())
 }
 
 impl FromStr for StreamID {
     type Err = String;
 
+    /// A bare `ms` means `ms-0`.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<&str> = s.split('-').collect();
-        if parts.len() != 2 {
-            return Err("Invalid Stream ID format".to_string());
-        }
-
-        let ms = parts[0].parse::<u64>().map_err(|e| e.to_string())?;
-        let seq = parts[1].parse::<u64>().map_err(|e| e.to_string())?;
-
-        Ok(StreamID { ms, seq })
+        StreamID::parse(s, 0)
     }
 }
 
@@ -63,7 +88,10 @@ pub struct PendingEntry {
 #[derive(Debug, Clone, PartialEq)]
 pub struct Consumer {
     pub name: String,
+    /// Last interaction of any kind (ms).
     pub seen_time: u128,
+    /// Last read or claim that actually handed entries over (ms); 0 if never.
+    pub active_time: u128,
     pub pending_ids: HashSet<StreamID>,
 }
 
@@ -72,6 +100,7 @@ impl Consumer {
         Consumer {
             name,
             seen_time: 0,
+            active_time: 0,
             pending_ids: HashSet::new(),
         }
     }
@@ -83,6 +112,8 @@ pub struct ConsumerGroup {
     pub last_id: StreamID,
     pub consumers: HashMap<String, Consumer>,
     pub pel: HashMap<StreamID, PendingEntry>,
+    /// Entries the group has read, when known (set by XGROUP SETID ENTRIESREAD).
+    pub entries_read: Option<u64>,
 }
 
 impl ConsumerGroup {
@@ -92,6 +123,7 @@ impl ConsumerGroup {
             last_id,
             consumers: HashMap::new(),
             pel: HashMap::new(),
+            entries_read: None,
         }
     }
 }
@@ -101,6 +133,10 @@ pub struct Stream {
     pub rax: Rax<StreamEntry>,
     pub last_id: StreamID,
     pub groups: HashMap<String, ConsumerGroup>,
+    /// Entries ever added, including ones since deleted or trimmed.
+    pub entries_added: u64,
+    /// Highest ID removed by XDEL or trimming.
+    pub max_deleted_id: StreamID,
 }
 
 impl Default for Stream {
@@ -115,6 +151,8 @@ impl Stream {
             rax: Rax::new(),
             last_id: StreamID::new(0, 0),
             groups: HashMap::new(),
+            entries_added: 0,
+            max_deleted_id: StreamID::new(0, 0),
         }
     }
 
@@ -136,6 +174,7 @@ impl Stream {
 
         self.rax.insert(&id.to_be_bytes(), entry);
         self.last_id = id;
+        self.entries_added += 1;
         Ok(id)
     }
 
@@ -147,12 +186,45 @@ impl Stream {
         self.rax.is_empty()
     }
 
+    /// ID of the oldest entry still present.
+    pub fn first_id(&self) -> Option<StreamID> {
+        self.rax.first().map(|e| e.id)
+    }
+
+    /// ID of the newest entry still present, which may be below `last_id`.
+    pub fn top_id(&self) -> Option<StreamID> {
+        self.rax.last().map(|e| e.id)
+    }
+
     pub fn get(&self, id: &StreamID) -> Option<&StreamEntry> {
         self.rax.get(&id.to_be_bytes())
     }
 
     pub fn remove(&mut self, id: &StreamID) -> Option<StreamEntry> {
-        self.rax.remove(&id.to_be_bytes())
+        let removed = self.rax.remove(&id.to_be_bytes());
+        if removed.is_some() {
+            self.max_deleted_id = self.max_deleted_id.max(*id);
+        }
+        removed
+    }
+
+    /// Whether some consumer group still needs `id`: it is pending in the
+    /// group's PEL, or lies past what the group has been delivered.
+    pub fn is_referenced(&self, id: &StreamID) -> bool {
+        self.groups
+            .values()
+            .any(|g| g.last_id < *id || g.pel.contains_key(id))
+    }
+
+    /// Drops `id` from every group's PEL and from its owning consumer.
+    pub fn remove_references(&mut self, id: &StreamID) {
+        for group in self.groups.values_mut() {
+            if let Some(pe) = group.pel.remove(id)
+                && let Some(consumer) = group.consumers.get_mut(&pe.owner)
+            {
+                consumer.pending_ids.remove(id);
+            }
+        }
     }
 
     pub fn range(&self, start: &StreamID, end: &StreamID) -> Vec<StreamEntry> {
@@ -171,13 +243,14 @@ impl Stream {
         entries.into_iter().map(|(_, entry)| entry).collect()
     }
 
-    pub fn trim_maxlen(&mut self, maxlen: usize) -> usize {
+    /// Removes the oldest entries beyond `maxlen`, at most `limit` of them.
+    pub fn trim_maxlen(&mut self, maxlen: usize, limit: Option<usize>) -> usize {
         let current_len = self.len();
         if current_len <= maxlen {
             return 0;
         }
 
-        let to_remove = current_len - maxlen;
+        let to_remove = (current_len - maxlen).min(limit.unwrap_or(usize::MAX));
         let mut removed = 0;
 
         let entries = self.rax.range(
@@ -185,8 +258,9 @@ impl Stream {
             &StreamID::new(u64::MAX, u64::MAX).to_be_bytes(),
         );
 
-        for (id_bytes, _) in entries.iter().take(to_remove) {
+        for (id_bytes, entry) in entries.iter().take(to_remove) {
             if self.rax.remove(id_bytes).is_some() {
+                self.max_deleted_id = self.max_deleted_id.max(entry.id);
                 removed += 1;
             }
         }
@@ -194,16 +268,18 @@ impl Stream {
         removed
     }
 
-    pub fn trim_minid(&mut self, minid: StreamID) -> usize {
+    /// Removes entries older than `minid`, at most `limit` of them.
+    pub fn trim_minid(&mut self, minid: StreamID, limit: Option<usize>) -> usize {
         let mut removed = 0;
 
         let entries = self
             .rax
             .range(&StreamID::new(0, 0).to_be_bytes(), &minid.to_be_bytes());
 
-        for (id_bytes, entry) in entries {
+        for (id_bytes, entry) in entries.into_iter().take(limit.unwrap_or(usize::MAX)) {
             if entry.id < minid {
                 if self.rax.remove(&id_bytes).is_some() {
+                    self.max_deleted_id = self.max_deleted_id.max(entry.id);
                     removed += 1;
                 }
             }
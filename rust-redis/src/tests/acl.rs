@@ -141,3 +141,75 @@ async fn test_acl_persistence() {
     // Cleanup
     let _ = std::fs::remove_file(&acl_path);
 }
+
+#[tokio::test]
+async fn test_acl_getuser_round_trip() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    let bulk = |s: &str| Resp::BulkString(Some(Bytes::from(s.to_string())));
+
+    let res = run_cmd(
+        vec![
+            "ACL", "SETUSER", "alice", "on", ">secret", "+@all", "-flushdb", "~foo*", "~bar",
+        ],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+
+    // RESP2 flattens the map into alternating field names and values
+    let res = run_cmd(vec!["ACL", "GETUSER", "alice"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            bulk("flags"),
+            Resp::Array(Some(vec![bulk("on")])),
+            bulk("passwords"),
+            Resp::Array(Some(vec![bulk(
+                "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b"
+            )])),
+            bulk("commands"),
+            bulk("+@all -flushdb"),
+            bulk("keys"),
+            bulk("~foo* ~bar"),
+            bulk("channels"),
+            bulk("&*"),
+            bulk("selectors"),
+            Resp::Array(Some(vec![])),
+        ]))
+    );
+
+    // nopass users report the flag and no passwords
+    run_cmd(
+        vec!["ACL", "SETUSER", "bob", "off", "nopass", "+get", "+set"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    run_cmd(vec!["HELLO", "3"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(vec!["ACL", "GETUSER", "bob"], &mut conn_ctx, &server_ctx).await;
+    let Resp::Map(fields) = res else {
+        panic!("expected a map under RESP3, got {:?}", res);
+    };
+    assert_eq!(
+        fields[0],
+        (
+            bulk("flags"),
+            Resp::Array(Some(vec![bulk("off"), bulk("nopass")]))
+        )
+    );
+    assert_eq!(fields[1], (bulk("passwords"), Resp::Array(Some(vec![]))));
+    assert_eq!(fields[2], (bulk("commands"), bulk("-@all +get +set")));
+    assert_eq!(fields[3], (bulk("keys"), bulk("")));
+
+    let res = run_cmd(vec!["ACL", "GETUSER", "nobody"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Null);
+    let res = run_cmd(vec!["ACL", "GETUSER"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR wrong number of arguments for 'acl getuser' command".to_string())
+    );
+}
@@ -89,3 +89,127 @@ async fn test_aof_append_and_load() {
         .await
         .expect("failed to remove temp file");
 }
+
+fn info_field(info: &Resp, name: &str) -> u64 {
+    let text = match info {
+        Resp::BulkString(Some(b)) => String::from_utf8_lossy(b).to_string(),
+        other => panic!("expected INFO text, got {:?}", other),
+    };
+    let prefix = format!("{}:", name);
+    text.lines()
+        .find_map(|line| line.strip_prefix(&prefix))
+        .unwrap_or_else(|| panic!("{} missing from {}", name, text))
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_aof_info_counters_and_waitaof_everysec() {
+    use crate::cmd::process_frame;
+    use crate::tests::helper::{create_connection_context, run_cmd};
+
+    let path = temp_file();
+    let aof = Aof::new(&path, AppendFsync::EverySec)
+        .await
+        .expect("failed to create aof");
+    let mut server_ctx = crate::tests::helper::create_server_context();
+    let mut conn = create_connection_context();
+
+    // Without an AOF only the replica count may be requested
+    let res = run_cmd(vec!["WAITAOF", "1", "0", "0"], &mut conn, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error(
+            "ERR WAITAOF cannot be used when numlocal is set but appendonly is disabled."
+                .to_string()
+        )
+    );
+
+    server_ctx.aof = Some(crate::aof::start_aof_task(aof));
+    // Let the ticker's immediate first fsync pass before writing
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let info = run_cmd(vec!["INFO", "persistence"], &mut conn, &server_ctx).await;
+    assert_eq!(info_field(&info, "aof_enabled"), 1);
+    assert_eq!(info_field(&info, "aof_current_size"), 0);
+    assert_eq!(info_field(&info, "aof_base_size"), 0);
+    assert_eq!(info_field(&info, "aof_pending_rewrite"), 0);
+
+    // Writes are logged the way the connection loop does it
+    for i in 0..3 {
+        let req = Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("SET"))),
+            Resp::BulkString(Some(Bytes::from(format!("k{}", i)))),
+            Resp::BulkString(Some(Bytes::from("v"))),
+        ]));
+        let (_, log) = process_frame(req, &mut conn, &server_ctx).await;
+        server_ctx.aof.as_ref().unwrap().append(&log.unwrap()).await;
+    }
+    let written = server_ctx.aof.as_ref().unwrap().offset().await;
+    let info = run_cmd(vec!["INFO", "persistence"], &mut conn, &server_ctx).await;
+    assert_eq!(info_field(&info, "aof_current_size"), written);
+    assert!(written > 0);
+
+    // The next everysec tick fsyncs the writes and releases WAITAOF
+    let res = run_cmd(vec!["WAITAOF", "1", "0", "3000"], &mut conn, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![Resp::Integer(1), Resp::Integer(0)]))
+    );
+    let info = run_cmd(vec!["INFO", "persistence"], &mut conn, &server_ctx).await;
+    assert_eq!(info_field(&info, "aof_last_fsync_offset"), written);
+    assert_eq!(
+        tokio::fs::metadata(&path).await.unwrap().len(),
+        info_field(&info, "aof_current_size")
+    );
+
+    // No replica reports its fsync offset, so asking for one replies with
+    // the count at once rather than blocking, even without a timeout
+    let res = tokio::time::timeout(
+        std::time::Duration::from_secs(1),
+        run_cmd(vec!["WAITAOF", "1", "1", "0"], &mut conn, &server_ctx),
+    )
+    .await
+    .expect("WAITAOF blocked on replicas");
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![Resp::Integer(1), Resp::Integer(0)]))
+    );
+
+    let _ = tokio::fs::remove_file(&path).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_aof_rewrite_keeps_functions() {
+    use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+
+    let path = temp_file();
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+    let code = "#!lua name=mylib\nredis.register_function('answer', function() return 42 end)";
+    run_cmd(vec!["FUNCTION", "LOAD", code], &mut conn, &server_ctx).await;
+
+    let mut aof = Aof::new(&path, AppendFsync::Always)
+        .await
+        .expect("failed to create aof");
+    aof.rewrite(
+        &server_ctx.databases,
+        &server_ctx.script_manager.library_codes(),
+    )
+    .await
+    .expect("failed to rewrite aof");
+
+    // Replaying the rewritten file alone brings the library back
+    let mut loaded_ctx = create_server_context();
+    Arc::make_mut(&mut loaded_ctx.config).appendfilename = path.clone();
+    Aof::new(&path, AppendFsync::Always)
+        .await
+        .expect("failed to open aof for loading")
+        .load(&loaded_ctx)
+        .await
+        .expect("failed to load aof");
+    let mut conn = create_connection_context();
+    let res = run_cmd(vec!["FCALL", "answer", "0"], &mut conn, &loaded_ctx).await;
+    assert_eq!(res, Resp::Integer(42));
+
+    let _ = std::fs::remove_file(&path);
+}
@@ -29,7 +29,7 @@ async fn test_aof_hang_reproduction() {
     };
 
     let databases = Arc::new(vec![RwLock::new(Db::default())]);
-    let script_manager = scripting::create_script_manager();
+    let script_manager = scripting::create_script_manager(config.lua_time_limit);
 
     // Initialize AOF exactly like server.rs: load first, then hand off to task.
     let aof = Aof::new(&path, config.appendfsync)
@@ -44,24 +44,27 @@ async fn test_aof_hang_reproduction() {
 
     let server_ctx = ServerContext {
         databases: databases.clone(),
+        db_epoch: Arc::new(std::sync::atomic::AtomicU64::new(0)),
         acl: Arc::new(arc_swap::ArcSwap::from_pointee(crate::acl::Acl::new())),
         aof: Some(aof_writer),
         config: Arc::new(config),
         script_manager: script_manager.clone(),
         blocking_waiters: Arc::new(dashmap::DashMap::new()),
         blocking_zset_waiters: Arc::new(dashmap::DashMap::new()),
+        stream_waiters: Arc::new(dashmap::DashMap::new()),
         pubsub: Arc::new(crate::cmd::PubSubCtx::new()),
         repl: Arc::new(crate::cmd::ReplicationCtx::new(
             "test_run_id".to_string(), 1024, 1, 60, true, 0, 10, false, 5,
         )),
         start_time: std::time::Instant::now(),
-        clients_ctx: Arc::new(crate::cmd::ClientCtx::new()),
+        clients_ctx: Arc::new(crate::cmd::ClientCtx::new(10000, 0, 300)),
         slowlog: Arc::new(crate::cmd::SlowLogCtx::new(128, 10_000)),
         mem: Arc::new(crate::cmd::MemoryCtx::new(
             0,
             crate::conf::EvictionPolicy::NoEviction,
             5,
             0,
+            crate::conf::EncodingLimits::default(),
         )),
         persist: Arc::new(crate::cmd::PersistenceCtx::new(
             true, true, true,
@@ -9,10 +9,15 @@ async fn test_client_list_basic() {
     let ci = ClientInfo {
         id: 1,
         addr: "127.0.0.1:6380".to_string(),
+        laddr: "127.0.0.1:6379".to_string(),
         name: "cli".to_string(),
         db: 0,
         sub: 0,
         psub: 0,
+        ssub: 0,
+        multi: -1,
+        watch: 0,
+        resp: 2,
         flags: "N".to_string(),
         cmd: "PING".to_string(),
         connect_time: std::time::Instant::now() - std::time::Duration::from_secs(2),
@@ -53,10 +58,15 @@ async fn test_client_list_multiple() {
     let ci1 = ClientInfo {
         id: 2,
         addr: "10.0.0.1:1234".to_string(),
+        laddr: "127.0.0.1:6379".to_string(),
         name: "".to_string(),
         db: 1,
         sub: 1,
         psub: 0,
+        ssub: 0,
+        multi: -1,
+        watch: 0,
+        resp: 2,
         flags: "NP".to_string(),
         cmd: "SUBSCRIBE".to_string(),
         connect_time: std::time::Instant::now(),
@@ -67,10 +77,15 @@ async fn test_client_list_multiple() {
     let ci2 = ClientInfo {
         id: 3,
         addr: "10.0.0.2:2345".to_string(),
+        laddr: "127.0.0.1:6379".to_string(),
         name: "worker".to_string(),
         db: 0,
         sub: 0,
         psub: 1,
+        ssub: 0,
+        multi: -1,
+        watch: 0,
+        resp: 2,
         flags: "NP".to_string(),
         cmd: "PSUBSCRIBE".to_string(),
         connect_time: std::time::Instant::now(),
@@ -113,10 +128,15 @@ async fn test_client_kill_id() {
     let ci = ClientInfo {
         id: 10,
         addr: "1.2.3.4:5678".to_string(),
+        laddr: "127.0.0.1:6379".to_string(),
         name: "victim".to_string(),
         db: 0,
         sub: 0,
         psub: 0,
+        ssub: 0,
+        multi: -1,
+        watch: 0,
+        resp: 2,
         flags: "N".to_string(),
         cmd: "PING".to_string(),
         connect_time: std::time::Instant::now(),
@@ -149,10 +169,15 @@ async fn test_client_kill_addr() {
     let ci = ClientInfo {
         id: 11,
         addr: "5.6.7.8:9999".to_string(),
+        laddr: "127.0.0.1:6379".to_string(),
         name: "victim_addr".to_string(),
         db: 0,
         sub: 0,
         psub: 0,
+        ssub: 0,
+        multi: -1,
+        watch: 0,
+        resp: 2,
         flags: "N".to_string(),
         cmd: "PING".to_string(),
         connect_time: std::time::Instant::now(),
@@ -185,10 +210,15 @@ async fn test_client_kill_legacy() {
     let ci = ClientInfo {
         id: 12,
         addr: "9.9.9.9:1111".to_string(),
+        laddr: "127.0.0.1:6379".to_string(),
         name: "victim_legacy".to_string(),
         db: 0,
         sub: 0,
         psub: 0,
+        ssub: 0,
+        multi: -1,
+        watch: 0,
+        resp: 2,
         flags: "N".to_string(),
         cmd: "PING".to_string(),
         connect_time: std::time::Instant::now(),
@@ -221,10 +251,15 @@ async fn test_client_setname() {
     let ci = ClientInfo {
         id: 13,
         addr: "127.0.0.1:6379".to_string(),
+        laddr: "127.0.0.1:6379".to_string(),
         name: "old_name".to_string(),
         db: 0,
         sub: 0,
         psub: 0,
+        ssub: 0,
+        multi: -1,
+        watch: 0,
+        resp: 2,
         flags: "N".to_string(),
         cmd: "PING".to_string(),
         connect_time: std::time::Instant::now(),
@@ -259,10 +294,15 @@ async fn test_client_setname_invalid() {
     let ci = ClientInfo {
         id: 14,
         addr: "127.0.0.1:6379".to_string(),
+        laddr: "127.0.0.1:6379".to_string(),
         name: "valid".to_string(),
         db: 0,
         sub: 0,
         psub: 0,
+        ssub: 0,
+        multi: -1,
+        watch: 0,
+        resp: 2,
         flags: "N".to_string(),
         cmd: "PING".to_string(),
         connect_time: std::time::Instant::now(),
@@ -287,3 +327,244 @@ async fn test_client_setname_invalid() {
         _ => panic!("Expected Error response"),
     }
 }
+
+#[tokio::test]
+async fn test_client_setname_round_trip() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let ci = ClientInfo {
+        id: 15,
+        addr: "127.0.0.1:6379".to_string(),
+        laddr: "127.0.0.1:6379".to_string(),
+        name: "".to_string(),
+        db: 0,
+        sub: 0,
+        psub: 0,
+        ssub: 0,
+        multi: -1,
+        watch: 0,
+        resp: 2,
+        flags: "N".to_string(),
+        cmd: "PING".to_string(),
+        connect_time: std::time::Instant::now(),
+        last_activity: std::time::Instant::now(),
+        shutdown_tx: None,
+        msg_sender: None,
+    };
+    server_ctx.clients_ctx.clients.insert(ci.id, ci);
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    conn_ctx.id = 15;
+
+    let res = run_cmd(vec!["CLIENT", "GETNAME"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(None));
+
+    let res = run_cmd(
+        vec!["CLIENT", "SETNAME", "worker-1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let res = run_cmd(vec!["CLIENT", "GETNAME"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("worker-1"))));
+    assert_eq!(conn_ctx.client_name, "worker-1");
+
+    match run_cmd(vec!["CLIENT", "LIST"], &mut conn_ctx, &server_ctx).await {
+        Resp::BulkString(Some(b)) => {
+            let s = String::from_utf8_lossy(&b);
+            assert!(s.contains("id=15 "), "{}", s);
+            assert!(s.contains(" name=worker-1 "), "{}", s);
+        }
+        other => panic!("expected BulkString, got {:?}", other),
+    }
+
+    // Rejected names leave the current one in place
+    for bad in ["has space", "line\nbreak", "tab\t"] {
+        let res = run_cmd(vec!["CLIENT", "SETNAME", bad], &mut conn_ctx, &server_ctx).await;
+        assert_eq!(
+            res,
+            Resp::Error(
+                "ERR Client names cannot contain spaces, newlines or special characters."
+                    .to_string()
+            )
+        );
+    }
+    let res = run_cmd(vec!["CLIENT", "GETNAME"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("worker-1"))));
+
+    // An empty name clears it
+    run_cmd(vec!["CLIENT", "SETNAME", ""], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(vec!["CLIENT", "GETNAME"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(None));
+    assert_eq!(server_ctx.clients_ctx.clients.get(&15).unwrap().name, "");
+}
+
+#[tokio::test]
+async fn test_client_info_describes_caller() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let ci = ClientInfo {
+        id: 16,
+        addr: "127.0.0.1:50000".to_string(),
+        laddr: "127.0.0.1:6379".to_string(),
+        name: "".to_string(),
+        db: 0,
+        sub: 0,
+        psub: 0,
+        ssub: 0,
+        multi: -1,
+        watch: 0,
+        resp: 2,
+        flags: "N".to_string(),
+        cmd: "".to_string(),
+        connect_time: std::time::Instant::now(),
+        last_activity: std::time::Instant::now(),
+        shutdown_tx: None,
+        msg_sender: None,
+    };
+    server_ctx.clients_ctx.clients.insert(ci.id, ci);
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    conn_ctx.id = 16;
+
+    run_cmd(vec!["HELLO", "3"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["WATCH", "a", "b"], &mut conn_ctx, &server_ctx).await;
+    match run_cmd(vec!["CLIENT", "INFO"], &mut conn_ctx, &server_ctx).await {
+        Resp::BulkString(Some(b)) => {
+            let s = String::from_utf8_lossy(&b);
+            assert!(s.starts_with("id=16 addr=127.0.0.1:50000 "), "{}", s);
+            for field in [" age=", " idle=", " multi=-1 ", " watch=2 ", " resp=3"] {
+                assert!(s.contains(field), "{} missing from {}", field, s);
+            }
+            assert!(s.contains(" cmd=client|info "), "{}", s);
+            assert!(s.ends_with('\n') && s.lines().count() == 1, "{}", s);
+        }
+        other => panic!("expected BulkString, got {:?}", other),
+    }
+
+    // Queued commands are counted while the transaction is open
+    run_cmd(vec!["MULTI"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["SET", "a", "1"], &mut conn_ctx, &server_ctx).await;
+    crate::cmd::client::update_client_info(&conn_ctx, &server_ctx, "SET".to_string());
+    let ci = server_ctx.clients_ctx.clients.get(&16).unwrap();
+    assert_eq!(ci.multi, 1);
+    assert_eq!(ci.flags, "NM");
+}
+
+#[tokio::test]
+async fn test_client_kill_filters() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mock_client = |id: u64, addr: &str, sub: usize, age: u64| {
+        let (tx, rx) = tokio::sync::watch::channel(false);
+        let ci = ClientInfo {
+            id,
+            addr: addr.to_string(),
+            laddr: "127.0.0.1:6379".to_string(),
+            name: "".to_string(),
+            db: 0,
+            sub,
+            psub: 0,
+            ssub: 0,
+            multi: -1,
+            watch: 0,
+            resp: 2,
+            flags: "N".to_string(),
+            cmd: "PING".to_string(),
+            connect_time: std::time::Instant::now() - std::time::Duration::from_secs(age),
+            last_activity: std::time::Instant::now(),
+            shutdown_tx: Some(tx),
+            msg_sender: None,
+        };
+        server_ctx.clients_ctx.clients.insert(id, ci);
+        rx
+    };
+    let me = mock_client(20, "10.0.0.20:1000", 0, 0);
+    let other = mock_client(21, "10.0.0.21:1000", 0, 0);
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    conn_ctx.id = 20;
+
+    // SKIPME defaults to yes, so the caller survives a filter matching it
+    let res = run_cmd(
+        vec!["CLIENT", "KILL", "ID", "20"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(0));
+    assert!(!*me.borrow());
+
+    // Only the client with the given ID is closed
+    let res = run_cmd(
+        vec!["CLIENT", "KILL", "ID", "21"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(1));
+    assert!(*other.borrow());
+    assert!(!*me.borrow());
+    assert!(server_ctx.clients_ctx.clients.get(&21).is_none());
+    assert!(server_ctx.clients_ctx.clients.get(&20).is_some());
+
+    // Filters combine: TYPE and MAXAGE must both match
+    let subscriber = mock_client(22, "10.0.0.22:1000", 1, 100);
+    let young_subscriber = mock_client(23, "10.0.0.23:1000", 1, 0);
+    let old_normal = mock_client(24, "10.0.0.24:1000", 0, 100);
+    // A shard channel subscriber is of type pubsub too
+    let shard_subscriber = mock_client(25, "10.0.0.25:1000", 0, 100);
+    server_ctx.clients_ctx.clients.get_mut(&25).unwrap().ssub = 1;
+    let res = run_cmd(
+        vec!["CLIENT", "KILL", "TYPE", "pubsub", "MAXAGE", "50"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(2));
+    assert!(*subscriber.borrow());
+    assert!(*shard_subscriber.borrow());
+    assert!(!*young_subscriber.borrow());
+    assert!(!*old_normal.borrow());
+
+    // LADDR matches every remaining client; SKIPME no includes the caller
+    let res = run_cmd(
+        vec!["CLIENT", "KILL", "LADDR", "127.0.0.1:6379", "SKIPME", "no"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(3));
+    assert!(*me.borrow());
+    assert!(server_ctx.clients_ctx.clients.is_empty());
+
+    // Argument errors
+    let res = run_cmd(
+        vec!["CLIENT", "KILL", "ID", "1", "SKIPME"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Error("ERR syntax error".to_string()));
+    let res = run_cmd(
+        vec!["CLIENT", "KILL", "TYPE", "bogus"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR Unknown client type 'bogus'".to_string())
+    );
+    let res = run_cmd(
+        vec!["CLIENT", "KILL", "ID", "abc"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR client-id should be greater than 0".to_string())
+    );
+}
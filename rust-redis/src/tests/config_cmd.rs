@@ -168,3 +168,134 @@ async fn test_config_replication_params() {
         _ => panic!("expected Array response"),
     }
 }
+
+#[tokio::test]
+async fn test_config_proto_max_bulk_len() {
+    use crate::tests::helper::run_cmd;
+    use std::sync::atomic::Ordering;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let res = run_cmd(
+        vec!["CONFIG", "GET", "proto-max-bulk-len"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("proto-max-bulk-len"))),
+            Resp::BulkString(Some(Bytes::from("536870912"))),
+        ]))
+    );
+
+    // Values below Redis' 1mb floor are refused
+    let res = run_cmd(
+        vec!["CONFIG", "SET", "proto-max-bulk-len", "1000"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert!(matches!(res, Resp::Error(_)));
+
+    let res = run_cmd(
+        vec!["CONFIG", "SET", "proto-max-bulk-len", "1mb"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let limit = server_ctx
+        .clients_ctx
+        .proto_max_bulk_len
+        .load(Ordering::Relaxed);
+    assert_eq!(limit, 1024 * 1024);
+
+    // A bulk of exactly the limit parses, one byte more is a protocol error
+    let request = |len: u64| {
+        let mut buf = format!("*2\r\n$3\r\nSET\r\n${}\r\n", len).into_bytes();
+        buf.extend(std::iter::repeat_n(b'x', len as usize));
+        buf.extend_from_slice(b"\r\n");
+        buf
+    };
+    let ok = request(limit);
+    let mut reader = tokio::io::BufReader::new(ok.as_slice());
+    let frame = crate::resp::read_request(&mut reader, limit).await.unwrap();
+    assert!(matches!(frame, Some(Resp::Array(Some(_)))));
+
+    let oversized = request(limit + 1);
+    let mut reader = tokio::io::BufReader::new(oversized.as_slice());
+    let err = crate::resp::read_request(&mut reader, limit)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(crate::resp::ProtocolError::is(&err));
+    assert_eq!(err.to_string(), "Protocol error: invalid bulk length");
+
+    // Internal streams are not bounded by it
+    let mut reader = tokio::io::BufReader::new(oversized.as_slice());
+    assert!(crate::resp::read_frame(&mut reader).await.is_ok());
+
+    // A client sending an oversized bulk is told why before being dropped
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(crate::serve(listener, server_ctx.clone()));
+    let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+    client
+        .write_all(format!("*2\r\n$3\r\nSET\r\n${}\r\n", limit + 1).as_bytes())
+        .await
+        .unwrap();
+    let mut reply = Vec::new();
+    tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        client.read_to_end(&mut reply),
+    )
+    .await
+    .expect("connection was not closed")
+    .unwrap();
+    assert_eq!(reply, b"-ERR Protocol error: invalid bulk length\r\n");
+}
+
+#[tokio::test]
+async fn test_config_databases() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let res = run_cmd(
+        vec!["CONFIG", "GET", "databases"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("databases"))),
+            Resp::BulkString(Some(Bytes::from("16"))),
+        ]))
+    );
+
+    let res = run_cmd(vec!["SELECT", "15"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let res = run_cmd(vec!["SELECT", "16"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Error("ERR DB index is out of range".to_string()));
+    assert_eq!(conn_ctx.db_index, 15);
+
+    // The database count is fixed at startup
+    match run_cmd(
+        vec!["CONFIG", "SET", "databases", "32"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await
+    {
+        Resp::Error(e) => assert!(e.contains("can't set 'databases' at runtime"), "{}", e),
+        other => panic!("expected error, got {:?}", other),
+    }
+    assert_eq!(server_ctx.databases.len(), 16);
+}
@@ -431,3 +431,57 @@ async fn test_geosearchstore() {
         _ => panic!("Expected BulkString, got {:?}", res),
     }
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_geosearchstore_into_source_key() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    run_cmd(
+        vec![
+            "GEOADD",
+            "Sicily",
+            "13.361389",
+            "38.115556",
+            "Palermo",
+            "15.087269",
+            "37.502669",
+            "Catania",
+        ],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    // The destination shares the source's shard, so the source guard must be
+    // released before the store or the command never returns
+    let ctx = server_ctx.clone();
+    let search = tokio::spawn(async move {
+        let mut conn_ctx = crate::tests::helper::create_connection_context();
+        run_cmd(
+            vec![
+                "GEOSEARCHSTORE",
+                "Sicily",
+                "Sicily",
+                "FROMLONLAT",
+                "15",
+                "37",
+                "BYRADIUS",
+                "100",
+                "km",
+            ],
+            &mut conn_ctx,
+            &ctx,
+        )
+        .await
+    });
+    let res = tokio::time::timeout(std::time::Duration::from_secs(5), search)
+        .await
+        .expect("GEOSEARCHSTORE deadlocked")
+        .unwrap();
+    assert_eq!(res, Resp::Integer(1));
+
+    let res = run_cmd(vec!["ZCARD", "Sicily"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(1));
+}
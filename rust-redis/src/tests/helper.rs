@@ -28,13 +28,13 @@ pub async fn run_cmd(
 }
 
 pub fn create_server_context() -> ServerContext {
+    let config = Config::default();
     let mut dbs = Vec::new();
-    for _ in 0..16 {
+    for _ in 0..config.databases {
         dbs.push(RwLock::new(Db::default()));
     }
     let db = Arc::new(dbs);
-    let config = Config::default();
-    let script_manager = crate::cmd::scripting::create_script_manager();
+    let script_manager = crate::cmd::scripting::create_script_manager(config.lua_time_limit);
     let acl = Arc::new(arc_swap::ArcSwap::from_pointee(crate::acl::Acl::new()));
 
     let mut rng = rand::rng();
@@ -45,6 +45,10 @@ pub fn create_server_context() -> ServerContext {
     let save_params = config.save_params.clone();
     let maxmemory_policy = config.maxmemory_policy;
     let maxmemory_samples = config.maxmemory_samples;
+    let maxclients = config.maxclients;
+    let timeout = config.timeout;
+    let tcp_keepalive = config.tcp_keepalive;
+    let encoding_limits = config.encoding_limits;
     let node_id = crate::cluster::NodeId(run_id.clone());
     let cluster_state = Arc::new(RwLock::new(crate::cluster::ClusterState::new(
         node_id,
@@ -53,20 +57,28 @@ pub fn create_server_context() -> ServerContext {
     )));
     ServerContext {
         databases: db,
+        db_epoch: Arc::new(std::sync::atomic::AtomicU64::new(0)),
         acl: acl,
         aof: None,
         config: Arc::new(config),
         script_manager: script_manager,
         blocking_waiters: Arc::new(DashMap::new()),
         blocking_zset_waiters: Arc::new(DashMap::new()),
+        stream_waiters: Arc::new(DashMap::new()),
         pubsub: Arc::new(crate::cmd::PubSubCtx::new()),
         repl: Arc::new(crate::cmd::ReplicationCtx::new(
             run_id, 1024, 1, 60, true, 0, 10, false, 5,
         )),
         start_time: std::time::Instant::now(),
-        clients_ctx: Arc::new(crate::cmd::ClientCtx::new()),
+        clients_ctx: Arc::new(crate::cmd::ClientCtx::new(maxclients, timeout, tcp_keepalive)),
         slowlog: Arc::new(crate::cmd::SlowLogCtx::new(128, 10_000)),
-        mem: Arc::new(crate::cmd::MemoryCtx::new(0, maxmemory_policy, maxmemory_samples, 0)),
+        mem: Arc::new(crate::cmd::MemoryCtx::new(
+            0,
+            maxmemory_policy,
+            maxmemory_samples,
+            0,
+            encoding_limits,
+        )),
         persist: Arc::new(crate::cmd::PersistenceCtx::new(true, true, true, save_params, 0)),
         cluster_ctx: Arc::new(crate::cmd::ClusterCtx::new(cluster_state)),
     }
@@ -79,14 +91,14 @@ pub fn create_connection_context() -> ConnectionContext {
 }
 
 pub fn create_server_context_with_cluster() -> ServerContext {
+    let mut cfg = Config::default();
+    cfg.cluster_enabled = true;
     let mut dbs = Vec::new();
-    for _ in 0..16 {
+    for _ in 0..cfg.databases {
         dbs.push(RwLock::new(Db::default()));
     }
     let db = Arc::new(dbs);
-    let mut cfg = Config::default();
-    cfg.cluster_enabled = true;
-    let script_manager = crate::cmd::scripting::create_script_manager();
+    let script_manager = crate::cmd::scripting::create_script_manager(cfg.lua_time_limit);
     let acl = Arc::new(arc_swap::ArcSwap::from_pointee(crate::acl::Acl::new()));
 
     let mut rng = rand::rng();
@@ -108,22 +120,34 @@ pub fn create_server_context_with_cluster() -> ServerContext {
     let save_params = cfg.save_params.clone();
     let maxmemory_policy = cfg.maxmemory_policy;
     let maxmemory_samples = cfg.maxmemory_samples;
+    let maxclients = cfg.maxclients;
+    let timeout = cfg.timeout;
+    let tcp_keepalive = cfg.tcp_keepalive;
+    let encoding_limits = cfg.encoding_limits;
     ServerContext {
         databases: db,
+        db_epoch: Arc::new(std::sync::atomic::AtomicU64::new(0)),
         acl: acl,
         aof: None,
         config: Arc::new(cfg),
         script_manager: script_manager,
         blocking_waiters: Arc::new(DashMap::new()),
         blocking_zset_waiters: Arc::new(DashMap::new()),
+        stream_waiters: Arc::new(DashMap::new()),
         pubsub: Arc::new(crate::cmd::PubSubCtx::new()),
         repl: Arc::new(crate::cmd::ReplicationCtx::new(
             run_id, 1024, 1, 60, true, 0, 10, false, 5,
         )),
         start_time: std::time::Instant::now(),
-        clients_ctx: Arc::new(crate::cmd::ClientCtx::new()),
+        clients_ctx: Arc::new(crate::cmd::ClientCtx::new(maxclients, timeout, tcp_keepalive)),
         slowlog: Arc::new(crate::cmd::SlowLogCtx::new(128, 10_000)),
-        mem: Arc::new(crate::cmd::MemoryCtx::new(0, maxmemory_policy, maxmemory_samples, 0)),
+        mem: Arc::new(crate::cmd::MemoryCtx::new(
+            0,
+            maxmemory_policy,
+            maxmemory_samples,
+            0,
+            encoding_limits,
+        )),
         persist: Arc::new(crate::cmd::PersistenceCtx::new(true, true, true, save_params, 0)),
         cluster_ctx: Arc::new(crate::cmd::ClusterCtx::new(cluster_state)),
     }
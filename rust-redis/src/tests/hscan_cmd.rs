@@ -167,3 +167,40 @@ async fn test_hscan_wrong_type() {
         _ => panic!("expected WRONGTYPE error, got: {:?}", res),
     }
 }
+
+#[tokio::test]
+async fn test_hscan_match_applies_to_fields_only() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    // "other" and "extra" have values that match f* but fields that do not
+    for (field, value) in [("f1", "x"), ("f2", "y"), ("other", "f3"), ("extra", "foo")] {
+        run_cmd(vec!["HSET", "h", field, value], &mut conn_ctx, &server_ctx).await;
+    }
+
+    let res = run_cmd(
+        vec!["HSCAN", "h", "0", "MATCH", "f*", "COUNT", "100"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let Resp::Array(Some(reply)) = res else {
+        panic!("expected Array response, got {:?}", res);
+    };
+    assert_eq!(reply[0], Resp::BulkString(Some(Bytes::from("0"))));
+    let Resp::Array(Some(elements)) = &reply[1] else {
+        panic!("expected Array elements");
+    };
+    let mut pairs: Vec<(Resp, Resp)> = elements
+        .chunks(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect();
+    pairs.sort_by_key(|(field, _)| format!("{:?}", field));
+    let bulk = |s: &str| Resp::BulkString(Some(Bytes::from(s.to_string())));
+    assert_eq!(
+        pairs,
+        vec![(bulk("f1"), bulk("x")), (bulk("f2"), bulk("y"))]
+    );
+}
@@ -205,6 +205,11 @@ async fn test_info_clients_with_config() {
     let mut server_ctx = crate::tests::helper::create_server_context();
     let mut config = crate::conf::Config::default();
     config.maxclients = 5000;
+    server_ctx.clients_ctx = Arc::new(crate::cmd::ClientCtx::new(
+        config.maxclients,
+        config.timeout,
+        config.tcp_keepalive,
+    ));
     server_ctx.config = Arc::new(config);
 
     let mut conn_ctx = crate::tests::helper::create_connection_context();
@@ -283,3 +288,194 @@ async fn test_info_blocked_clients() {
         _ => panic!("expected BulkString response"),
     }
 }
+
+#[tokio::test]
+async fn test_info_clients_extended_fields() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    // Block a client with a timeout so it also lands in the timeout table
+    let server_ctx_clone = server_ctx.clone();
+    let handle = tokio::spawn(async move {
+        let mut conn_ctx_blocked = crate::tests::helper::create_connection_context();
+        let req = Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("BLPOP"))),
+            Resp::BulkString(Some(Bytes::from("list_key"))),
+            Resp::BulkString(Some(Bytes::from("5"))),
+        ]));
+        process_frame(req, &mut conn_ctx_blocked, &server_ctx_clone).await;
+    });
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let req = Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("WATCH"))),
+        Resp::BulkString(Some(Bytes::from("watched_key"))),
+    ]));
+    process_frame(req, &mut conn_ctx, &server_ctx).await;
+
+    let req = Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("INFO"))),
+        Resp::BulkString(Some(Bytes::from("CLIENTS"))),
+    ]));
+    let (res, _) = process_frame(req, &mut conn_ctx, &server_ctx).await;
+    match res {
+        Resp::BulkString(Some(info_bytes)) => {
+            let info = String::from_utf8_lossy(&info_bytes);
+            assert!(info.contains("blocked_clients:1"));
+            assert!(info.contains("clients_in_timeout_table:1"));
+            assert!(info.contains("watching_clients:1"));
+            assert!(info.contains("tracking_clients:0"));
+            assert!(info.contains("pubsub_clients:0"));
+        }
+        _ => panic!("expected BulkString response"),
+    }
+
+    let req = Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("LPUSH"))),
+        Resp::BulkString(Some(Bytes::from("list_key"))),
+        Resp::BulkString(Some(Bytes::from("value"))),
+    ]));
+    process_frame(req, &mut conn_ctx, &server_ctx).await;
+    handle.await.unwrap();
+
+    let req = Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("INFO"))),
+        Resp::BulkString(Some(Bytes::from("CLIENTS"))),
+    ]));
+    let (res, _) = process_frame(req, &mut conn_ctx, &server_ctx).await;
+    match res {
+        Resp::BulkString(Some(info_bytes)) => {
+            let info = String::from_utf8_lossy(&info_bytes);
+            assert!(info.contains("blocked_clients:0"));
+            assert!(info.contains("clients_in_timeout_table:0"));
+        }
+        _ => panic!("expected BulkString response"),
+    }
+}
+
+#[tokio::test]
+async fn test_info_resp3_verbatim() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    run_cmd(vec!["HELLO", "3"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(vec!["INFO", "server"], &mut conn_ctx, &server_ctx).await;
+    let text = match &res {
+        Resp::Verbatim(data) => data.clone(),
+        other => panic!("expected Verbatim, got {:?}", other),
+    };
+    assert!(String::from_utf8_lossy(&text).contains("# Server"));
+
+    let encoded = res.as_bytes();
+    let header = format!("={}\r\ntxt:", text.len() + 4);
+    assert!(encoded.starts_with(header.as_bytes()));
+    assert!(encoded.ends_with(b"\r\n"));
+    assert_eq!(encoded.len(), header.len() + text.len() + 2);
+
+    // The frame parses back to the same value
+    let mut reader = tokio::io::BufReader::new(encoded.as_slice());
+    let parsed = crate::resp::read_frame(&mut reader).await.unwrap();
+    assert_eq!(parsed, Some(res));
+
+    // Errors look the same under both protocols
+    let resp3_err = run_cmd(vec!["GET"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["HELLO", "2"], &mut conn_ctx, &server_ctx).await;
+    let resp2_err = run_cmd(vec!["GET"], &mut conn_ctx, &server_ctx).await;
+    assert!(matches!(resp2_err, Resp::Error(_)));
+    assert_eq!(resp3_err, resp2_err);
+
+    let res = run_cmd(vec!["INFO", "server"], &mut conn_ctx, &server_ctx).await;
+    assert!(matches!(res, Resp::BulkString(Some(_))));
+}
+
+#[tokio::test]
+async fn test_info_stats_counters() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let stat = |info: &str, field: &str| -> String {
+        info.lines()
+            .find_map(|l| l.strip_prefix(&format!("{}:", field)))
+            .unwrap_or_else(|| panic!("missing {} in {}", field, info))
+            .to_string()
+    };
+    let info_stats = |res: Resp| match res {
+        Resp::BulkString(Some(b)) => String::from_utf8_lossy(&b).to_string(),
+        other => panic!("expected BulkString, got {:?}", other),
+    };
+
+    let res = run_cmd(vec!["INFO", "stats"], &mut conn_ctx, &server_ctx).await;
+    let before: u64 = stat(&info_stats(res), "total_commands_processed")
+        .parse()
+        .unwrap();
+
+    run_cmd(vec!["SET", "k", "v"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["GET", "k"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["PING"], &mut conn_ctx, &server_ctx).await;
+
+    // The first INFO plus the three commands above
+    let info = info_stats(run_cmd(vec!["INFO", "stats"], &mut conn_ctx, &server_ctx).await);
+    let after: u64 = stat(&info, "total_commands_processed").parse().unwrap();
+    assert_eq!(after, before + 4);
+    assert_eq!(stat(&info, "instantaneous_ops_per_sec"), "0");
+    assert_eq!(stat(&info, "instantaneous_input_kbps"), "0.00");
+    for field in [
+        "total_connections_received",
+        "total_net_input_bytes",
+        "total_net_output_bytes",
+        "instantaneous_output_kbps",
+    ] {
+        stat(&info, field);
+    }
+
+    // A steady 100 ops per 100ms sample averages out to 1000 ops/sec
+    let mut metric = crate::cmd::InstantaneousMetric::default();
+    let start = std::time::Instant::now();
+    for i in 0..=crate::cmd::STATS_METRIC_SAMPLES as u64 {
+        metric.track(i * 100, start + std::time::Duration::from_millis(i * 100));
+    }
+    assert_eq!(metric.value(), 1000);
+}
+
+#[tokio::test]
+async fn test_info_server_uptime_and_cpu() {
+    use crate::tests::helper::run_cmd;
+
+    let mut server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let field = |res: Resp, name: &str| -> String {
+        let info = match res {
+            Resp::BulkString(Some(b)) => String::from_utf8_lossy(&b).to_string(),
+            other => panic!("expected BulkString, got {:?}", other),
+        };
+        info.lines()
+            .find_map(|l| l.strip_prefix(&format!("{}:", name)))
+            .unwrap_or_else(|| panic!("missing {} in {}", name, info))
+            .to_string()
+    };
+
+    let res = run_cmd(vec!["INFO", "server"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(field(res.clone(), "uptime_in_seconds"), "0");
+    assert_eq!(
+        field(res.clone(), "run_id"),
+        *server_ctx.repl.run_id.read().unwrap()
+    );
+    assert_eq!(field(res.clone(), "io_threads_active"), "0");
+    assert!(!field(res, "executable").is_empty());
+
+    // Uptime is measured from start_time
+    server_ctx.start_time -= std::time::Duration::from_secs(5);
+    let res = run_cmd(vec!["INFO", "server"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(field(res.clone(), "uptime_in_seconds"), "5");
+    assert_eq!(field(res, "uptime_in_days"), "0");
+
+    let res = run_cmd(vec!["INFO", "cpu"], &mut conn_ctx, &server_ctx).await;
+    let user: f64 = field(res.clone(), "used_cpu_user").parse().unwrap();
+    let sys: f64 = field(res, "used_cpu_sys").parse().unwrap();
+    assert!(user >= 0.0 && sys >= 0.0);
+}
@@ -320,3 +320,81 @@ async fn test_del() {
         assert!(db.contains_key(&Bytes::from("k3")));
     }
 }
+
+#[tokio::test]
+async fn test_expire_conditions() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    let mut expire = async |args: &[&str]| {
+        let mut cmd = vec!["EXPIRE", "lock"];
+        cmd.extend_from_slice(args);
+        run_cmd(cmd, &mut conn_ctx, &server_ctx).await
+    };
+
+    run_cmd(
+        vec!["SET", "lock", "owner"],
+        &mut crate::tests::helper::create_connection_context(),
+        &server_ctx,
+    )
+    .await;
+
+    // Without a TTL: XX and GT are blocked, NX and LT apply
+    assert_eq!(expire(&["100", "XX"]).await, Resp::Integer(0));
+    assert_eq!(expire(&["100", "GT"]).await, Resp::Integer(0));
+    assert_eq!(expire(&["100", "NX"]).await, Resp::Integer(1));
+    assert_eq!(expire(&["200", "NX"]).await, Resp::Integer(0));
+
+    // Extending never shortens the lock, and the reverse
+    assert_eq!(expire(&["50", "GT"]).await, Resp::Integer(0));
+    assert_eq!(expire(&["300", "gt"]).await, Resp::Integer(1));
+    assert_eq!(expire(&["400", "LT"]).await, Resp::Integer(0));
+    assert_eq!(expire(&["250", "XX", "LT"]).await, Resp::Integer(1));
+    assert_eq!(expire(&["500", "XX"]).await, Resp::Integer(1));
+    match expire(&[]).await {
+        Resp::Error(e) => assert!(e.contains("wrong number"), "{}", e),
+        other => panic!("expected error, got {:?}", other),
+    }
+
+    for bad in [
+        &["10", "NX", "GT"][..],
+        &["10", "NX", "XX"],
+        &["10", "GT", "LT"],
+    ] {
+        match expire(bad).await {
+            Resp::Error(e) => assert!(e.contains("not compatible"), "{}", e),
+            other => panic!("expected error for {:?}, got {:?}", bad, other),
+        }
+    }
+    match expire(&["10", "SOON"]).await {
+        Resp::Error(e) => assert_eq!(e, "ERR Unsupported option SOON"),
+        other => panic!("expected error, got {:?}", other),
+    }
+    drop(expire);
+
+    let res = run_cmd(vec!["TTL", "lock"], &mut conn_ctx, &server_ctx).await;
+    assert!(matches!(res, Resp::Integer(499 | 500)), "{:?}", res);
+
+    // The millisecond and absolute forms take the same options
+    let deadline = crate::clock::now_ms() + 1_000_000;
+    let res = run_cmd(
+        vec!["PEXPIREAT", "lock", &deadline.to_string(), "LT"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(0));
+    let res = run_cmd(
+        vec!["PEXPIRE", "lock", "1000", "LT"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(1));
+    let res = run_cmd(
+        vec!["EXPIREAT", "missing", "1", "NX"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(0));
+}
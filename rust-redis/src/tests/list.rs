@@ -373,3 +373,299 @@ async fn test_lmove_ops() {
         _ => panic!("expected BulkString(None)"),
     }
 }
+
+#[tokio::test]
+async fn test_push_reply_counts_only_listed_values() {
+    use crate::tests::helper::run_cmd;
+    use std::collections::VecDeque;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    // A blocked BLPOP takes the first value; the other two stay in the list
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+    server_ctx
+        .blocking_waiters
+        .insert((0, b"l".to_vec()), VecDeque::from(vec![tx]));
+    let res = run_cmd(
+        vec!["RPUSH", "l", "a", "b", "c"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(2));
+    assert_eq!(rx.try_recv().unwrap(), (b"l".to_vec(), b"a".to_vec()));
+    let res = run_cmd(vec!["LLEN", "l"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(2));
+
+    // When the waiter takes the only value the reply is the untouched length
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+    server_ctx
+        .blocking_waiters
+        .insert((0, b"l".to_vec()), VecDeque::from(vec![tx]));
+    let res = run_cmd(vec!["LPUSH", "l", "d"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(2));
+    assert_eq!(rx.try_recv().unwrap(), (b"l".to_vec(), b"d".to_vec()));
+
+    // A wrong type is rejected before any waiter is served
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+    server_ctx
+        .blocking_waiters
+        .insert((0, b"s".to_vec()), VecDeque::from(vec![tx]));
+    run_cmd(vec!["SET", "s", "v"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(vec!["RPUSH", "s", "x"], &mut conn_ctx, &server_ctx).await;
+    assert!(matches!(res, Resp::Error(ref e) if e.starts_with("WRONGTYPE")));
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_lpop_rpop_count() {
+    use crate::tests::helper::run_cmd;
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    let bulk = |s: &str| Resp::BulkString(Some(Bytes::from(s.to_string())));
+
+    run_cmd(
+        vec!["RPUSH", "l", "a", "b", "c", "d"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    // Without a count the reply stays a single bulk string
+    let res = run_cmd(vec!["LPOP", "l"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, bulk("a"));
+
+    let res = run_cmd(vec!["RPOP", "l", "2"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Array(Some(vec![bulk("d"), bulk("c")])));
+
+    let res = run_cmd(vec!["LPOP", "l", "0"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Array(Some(vec![])));
+
+    // Popping the last element deletes the key
+    let res = run_cmd(vec!["LPOP", "l", "10"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Array(Some(vec![bulk("b")])));
+    let res = run_cmd(vec!["EXISTS", "l"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+
+    let res = run_cmd(vec!["LPOP", "l", "2"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Array(None));
+    let res = run_cmd(vec!["RPOP", "l"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(None));
+
+    let res = run_cmd(vec!["LPOP", "l", "-1"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR value is out of range, must be positive".to_string())
+    );
+
+    run_cmd(vec!["SET", "s", "v"], &mut conn_ctx, &server_ctx).await;
+    match run_cmd(vec!["RPOP", "s", "0"], &mut conn_ctx, &server_ctx).await {
+        Resp::Error(e) => assert!(e.starts_with("WRONGTYPE"), "{}", e),
+        other => panic!("expected WRONGTYPE, got {:?}", other),
+    }
+}
+
+async fn wait_until_blocked(server_ctx: &ServerContext, n: u64) {
+    use std::sync::atomic::Ordering;
+
+    while server_ctx
+        .clients_ctx
+        .blocked_client_count
+        .load(Ordering::Relaxed)
+        < n
+    {
+        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_blpop_burst_delivers_exactly_once() {
+    use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+
+    // Listing the key twice parks two hand-off slots for one client
+    let waiter_ctx = server_ctx.clone();
+    let waiter = tokio::spawn(async move {
+        let mut conn = create_connection_context();
+        run_cmd(vec!["BLPOP", "burst", "burst", "0"], &mut conn, &waiter_ctx).await
+    });
+    wait_until_blocked(&server_ctx, 1).await;
+
+    let values: Vec<String> = (0..200).map(|i| i.to_string()).collect();
+    for v in &values {
+        run_cmd(vec!["RPUSH", "burst", v], &mut conn, &server_ctx).await;
+    }
+
+    assert_eq!(
+        waiter.await.unwrap(),
+        Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("burst"))),
+            Resp::BulkString(Some(Bytes::from("0"))),
+        ]))
+    );
+    let rest = run_cmd(vec!["LRANGE", "burst", "0", "-1"], &mut conn, &server_ctx).await;
+    assert_eq!(
+        rest,
+        Resp::Array(Some(
+            values[1..]
+                .iter()
+                .map(|v| Resp::BulkString(Some(Bytes::from(v.clone()))))
+                .collect()
+        ))
+    );
+}
+
+#[tokio::test]
+async fn test_blocked_pop_reclaims_late_hand_off() {
+    use crate::cmd::list::{PopDirection, Reclaimed, reclaim_hand_off};
+    use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+    use std::collections::VecDeque;
+
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+    let park = |server_ctx: &ServerContext| {
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        server_ctx
+            .blocking_waiters
+            .entry((0, b"late".to_vec()))
+            .or_insert_with(VecDeque::new)
+            .push_back(tx);
+        rx
+    };
+    run_cmd(vec!["RPUSH", "late", "x"], &mut conn, &server_ctx).await;
+
+    // The waiter was handed "a" after it timed out: it still gets served
+    let mut rx = park(&server_ctx);
+    run_cmd(vec!["LPUSH", "late", "a"], &mut conn, &server_ctx).await;
+    let reclaimed = {
+        let db = server_ctx.databases[0].read().unwrap();
+        reclaim_hand_off(&mut rx, true, &db, 0, &server_ctx, PopDirection::Left)
+    };
+    assert_eq!(
+        reclaimed,
+        Some(Reclaimed::Serve(b"late".to_vec(), b"a".to_vec()))
+    );
+
+    // A client going away puts "b" back where it would be popped next
+    let mut rx = park(&server_ctx);
+    run_cmd(vec!["LPUSH", "late", "b"], &mut conn, &server_ctx).await;
+    let reclaimed = {
+        let db = server_ctx.databases[0].read().unwrap();
+        reclaim_hand_off(&mut rx, false, &db, 0, &server_ctx, PopDirection::Left)
+    };
+    // and propagates the push the pusher left out of its own log
+    assert_eq!(
+        reclaimed,
+        Some(Reclaimed::Requeued(
+            Bytes::from("late"),
+            Resp::Array(Some(vec![
+                Resp::BulkString(Some(Bytes::from("LPUSH"))),
+                Resp::BulkString(Some(Bytes::from("late"))),
+                Resp::BulkString(Some(Bytes::from("b"))),
+            ]))
+        ))
+    );
+    let res = run_cmd(vec!["LRANGE", "late", "0", "-1"], &mut conn, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("b"))),
+            Resp::BulkString(Some(Bytes::from("x"))),
+        ]))
+    );
+
+    // Another waiter takes it ahead of the list, logging its own pop
+    let mut rx = park(&server_ctx);
+    run_cmd(vec!["LPUSH", "late", "c"], &mut conn, &server_ctx).await;
+    let mut next = park(&server_ctx);
+    let reclaimed = {
+        let db = server_ctx.databases[0].read().unwrap();
+        reclaim_hand_off(&mut rx, false, &db, 0, &server_ctx, PopDirection::Left)
+    };
+    assert_eq!(reclaimed, None);
+    assert_eq!(next.try_recv().unwrap(), (b"late".to_vec(), b"c".to_vec()));
+
+    // The list was overwritten after the hand-off: "d" goes with it, as it
+    // would have from the list, and there is nothing to propagate
+    let mut rx = park(&server_ctx);
+    run_cmd(vec!["LPUSH", "late", "d"], &mut conn, &server_ctx).await;
+    run_cmd(vec!["SET", "late", "s"], &mut conn, &server_ctx).await;
+    let reclaimed = {
+        let db = server_ctx.databases[0].read().unwrap();
+        reclaim_hand_off(&mut rx, false, &db, 0, &server_ctx, PopDirection::Left)
+    };
+    assert_eq!(reclaimed, None);
+    let res = run_cmd(vec!["GET", "late"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("s"))));
+}
+
+#[tokio::test]
+async fn test_blocking_pops_propagate_what_they_served() {
+    use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+
+    fn frame(args: &[&str]) -> Resp {
+        Resp::Array(Some(
+            args.iter()
+                .map(|a| Resp::BulkString(Some(Bytes::from(a.to_string()))))
+                .collect(),
+        ))
+    }
+
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+
+    let waiter_ctx = server_ctx.clone();
+    let waiter = tokio::spawn(async move {
+        let mut conn = create_connection_context();
+        let req = frame(&["BLMPOP", "0", "1", "mp", "LEFT", "COUNT", "2"]);
+        process_frame(req, &mut conn, &waiter_ctx).await
+    });
+    wait_until_blocked(&server_ctx, 1).await;
+
+    // "a" goes straight to the waiter, which then takes "c" from the list
+    let req = frame(&["LPUSH", "mp", "a", "b", "c"]);
+    let (res, push_log) = process_frame(req, &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(2));
+    assert_eq!(push_log, Some(frame(&["LPUSH", "mp", "b", "c"])));
+
+    let (res, pop_log) = waiter.await.unwrap();
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("mp"))),
+            Resp::Array(Some(vec![
+                Resp::BulkString(Some(Bytes::from("a"))),
+                Resp::BulkString(Some(Bytes::from("c"))),
+            ])),
+        ]))
+    );
+    assert_eq!(pop_log, Some(frame(&["LPOP", "mp", "1"])));
+
+    // Replaying the log reproduces the list the clients observed
+    let replica_ctx = create_server_context();
+    let mut replica = create_connection_context();
+    for entry in [push_log, pop_log].into_iter().flatten() {
+        process_frame(entry, &mut replica, &replica_ctx).await;
+    }
+    let expected = run_cmd(vec!["LRANGE", "mp", "0", "-1"], &mut conn, &server_ctx).await;
+    let replayed = run_cmd(vec!["LRANGE", "mp", "0", "-1"], &mut replica, &replica_ctx).await;
+    assert_eq!(expected, Resp::Array(Some(vec![Resp::BulkString(Some(Bytes::from("b")))])));
+    assert_eq!(replayed, expected);
+
+    // A BLMOVE served by a pusher is propagated as the push to its target
+    let waiter_ctx = server_ctx.clone();
+    let waiter = tokio::spawn(async move {
+        let mut conn = create_connection_context();
+        let req = frame(&["BLMOVE", "src", "dst", "LEFT", "RIGHT", "0"]);
+        process_frame(req, &mut conn, &waiter_ctx).await
+    });
+    wait_until_blocked(&server_ctx, 1).await;
+    let (_, push_log) = process_frame(frame(&["RPUSH", "src", "x"]), &mut conn, &server_ctx).await;
+    assert_eq!(push_log, None);
+    let (res, move_log) = waiter.await.unwrap();
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("x"))));
+    assert_eq!(move_log, Some(frame(&["RPUSH", "dst", "x"])));
+}
@@ -192,3 +192,747 @@ async fn test_lua_isolation_per_call() {
         ),
     }
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_script_forbidden_commands_and_lua_flag() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let eval = |script: &str| {
+        Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("EVAL"))),
+            Resp::BulkString(Some(Bytes::from(script.to_string()))),
+            Resp::BulkString(Some(Bytes::from("0"))),
+        ]))
+    };
+
+    // A script that raises an error must not leave the connection flagged
+    let (res, _) = process_frame(eval("error('boom')"), &mut conn_ctx, &server_ctx).await;
+    assert!(
+        matches!(res, Resp::Error(_)),
+        "expected error, got {:?}",
+        res
+    );
+    assert!(!conn_ctx.is_lua);
+
+    for cmd in ["multi", "exec", "watch", "subscribe"] {
+        let script = format!("return redis.call('{}', 'k')", cmd);
+        let (res, _) = process_frame(eval(&script), &mut conn_ctx, &server_ctx).await;
+        match res {
+            Resp::Error(msg) => assert!(
+                msg.contains("not allowed from script"),
+                "unexpected error for {}: {}",
+                cmd,
+                msg
+            ),
+            _ => panic!("expected error for {}, got {:?}", cmd, res),
+        }
+        assert!(!conn_ctx.is_lua);
+    }
+
+    // The caller is not left inside a transaction
+    assert!(!conn_ctx.in_multi);
+    let req = Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("SET"))),
+        Resp::BulkString(Some(Bytes::from("k"))),
+        Resp::BulkString(Some(Bytes::from("v"))),
+    ]));
+    let (res, _) = process_frame(req, &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_eval_numkeys_bounds() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let res =
+        crate::tests::helper::run_cmd(vec!["EVAL", "return 1", "-1"], &mut conn_ctx, &server_ctx)
+            .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR Number of keys can't be negative".to_string())
+    );
+
+    let res = crate::tests::helper::run_cmd(
+        vec!["EVAL", "return 1", "3", "k1", "k2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR Number of keys can't be greater than number of args".to_string())
+    );
+
+    // numkeys equal to the argument count is fine
+    let res = crate::tests::helper::run_cmd(
+        vec!["EVAL", "return #KEYS", "2", "k1", "k2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(2));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_script_exists_load_and_flush_modes() {
+    use crate::tests::helper::run_cmd;
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let sha = match run_cmd(
+        vec!["SCRIPT", "LOAD", "return 1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await
+    {
+        Resp::BulkString(Some(b)) => String::from_utf8(b.to_vec()).unwrap(),
+        other => panic!("expected sha1, got {:?}", other),
+    };
+    let upper = sha.to_uppercase();
+    let unknown = "0".repeat(40);
+
+    let res = run_cmd(
+        vec![
+            "SCRIPT",
+            "EXISTS",
+            sha.as_str(),
+            unknown.as_str(),
+            upper.as_str(),
+        ],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            Resp::Integer(1),
+            Resp::Integer(0),
+            Resp::Integer(1)
+        ]))
+    );
+
+    // LOAD compiles the script and rejects invalid source
+    match run_cmd(
+        vec!["SCRIPT", "LOAD", "return ("],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await
+    {
+        Resp::Error(e) => assert!(e.starts_with("ERR Error compiling script"), "{}", e),
+        other => panic!("expected compile error, got {:?}", other),
+    }
+
+    let res = run_cmd(vec!["SCRIPT", "FLUSH", "BOGUS"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Error("ERR syntax error".to_string()));
+
+    let res = run_cmd(vec!["SCRIPT", "FLUSH", "async"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let res = run_cmd(
+        vec!["SCRIPT", "EXISTS", sha.as_str()],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Array(Some(vec![Resp::Integer(0)])));
+
+    run_cmd(
+        vec!["SCRIPT", "LOAD", "return 1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(vec!["SCRIPT", "FLUSH", "SYNC"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let res = run_cmd(
+        vec!["SCRIPT", "EXISTS", sha.as_str()],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Array(Some(vec![Resp::Integer(0)])));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_script_helper_functions() {
+    use crate::tests::helper::run_cmd;
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let res = run_cmd(
+        vec!["EVAL", "return redis.sha1hex('')", "0"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::BulkString(Some(Bytes::from(
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709"
+        )))
+    );
+
+    let res = run_cmd(
+        vec![
+            "EVAL",
+            "redis.log(redis.LOG_WARNING, 'from', 'script'); return 1",
+            "0",
+        ],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(1));
+
+    let res = run_cmd(
+        vec!["EVAL", "redis.log(42, 'x')", "0"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert!(matches!(res, Resp::Error(_)), "got {:?}", res);
+
+    // Under RESP3 a set reply reaches Lua as {set = {member = true}}
+    run_cmd(vec!["SADD", "s", "a", "b"], &mut conn_ctx, &server_ctx).await;
+    let script = "local r = redis.call('SUNION', KEYS[1]); \
+                  redis.setresp(3); \
+                  local r3 = redis.call('SUNION', KEYS[1]); \
+                  return {#r, r3.set['a'] and 1 or 0, r3.set['b'] and 1 or 0}";
+    let res = run_cmd(vec!["EVAL", script, "1", "s"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            Resp::Integer(2),
+            Resp::Integer(1),
+            Resp::Integer(1)
+        ]))
+    );
+
+    let res = run_cmd(
+        vec!["EVAL", "redis.setresp(4)", "0"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert!(matches!(res, Resp::Error(_)), "got {:?}", res);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_lua_return_value_conversion() {
+    use crate::tests::helper::run_cmd;
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let cases = vec![
+        (
+            "return {err = 'MYERR custom'}",
+            Resp::Error("MYERR custom".to_string()),
+        ),
+        (
+            "return {ok = 'FINE'}",
+            Resp::SimpleString(Bytes::from("FINE")),
+        ),
+        // The array stops at the first nil
+        (
+            "return {1, 2, nil, 4}",
+            Resp::Array(Some(vec![Resp::Integer(1), Resp::Integer(2)])),
+        ),
+        ("return 3.99", Resp::Integer(3)),
+        ("return -3.99", Resp::Integer(-3)),
+        ("return true", Resp::Integer(1)),
+        ("return false", Resp::BulkString(None)),
+        // RESP2 callers see RESP3-only types in their RESP2 form
+        (
+            "return {double = 1.5}",
+            Resp::BulkString(Some(Bytes::from("1.5"))),
+        ),
+        (
+            "return {map = {a = 1}}",
+            Resp::Array(Some(vec![
+                Resp::BulkString(Some(Bytes::from("a"))),
+                Resp::Integer(1),
+            ])),
+        ),
+    ];
+    for (script, expected) in cases {
+        let res = run_cmd(vec!["EVAL", script, "0"], &mut conn_ctx, &server_ctx).await;
+        assert_eq!(res, expected, "script: {}", script);
+    }
+
+    conn_ctx.protocol = 3;
+    let res = run_cmd(
+        vec!["EVAL", "return {double = 1.5}", "0"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Double(1.5));
+    let res = run_cmd(
+        vec!["EVAL", "return {map = {a = 1}}", "0"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Map(vec![(
+            Resp::BulkString(Some(Bytes::from("a"))),
+            Resp::Integer(1)
+        )])
+    );
+    let res = run_cmd(
+        vec!["EVAL", "return {big_number = '12345678901234567890'}", "0"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::BigNumber(Bytes::from("12345678901234567890")));
+
+    // The RESP3 frames survive a round trip through the wire format
+    let frame = Resp::Array(Some(vec![
+        Resp::Double(1.5),
+        Resp::BigNumber(Bytes::from("12345678901234567890")),
+        Resp::Map(vec![(
+            Resp::BulkString(Some(Bytes::from("a"))),
+            Resp::Integer(1),
+        )]),
+    ]));
+    let encoded = frame.as_bytes();
+    assert!(encoded.starts_with(b"*3\r\n,1.5\r\n(12345678901234567890\r\n%1\r\n"));
+    let mut reader = tokio::io::BufReader::new(encoded.as_slice());
+    let parsed = crate::resp::read_frame(&mut reader).await.unwrap();
+    assert_eq!(parsed, Some(frame));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_function_load_and_fcall() {
+    use crate::tests::helper::run_cmd;
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let code = "#!lua name=mylib\n\
+        redis.register_function('myset', function(keys, args) redis.call('SET', keys[1], args[1]) return args[1] end)\n\
+        redis.register_function{function_name='myget', callback=function(keys, args) return redis.call('GET', keys[1]) end, flags={'no-writes'}}\n\
+        redis.register_function{function_name='sneaky', callback=function(keys, args) return redis.call('DEL', keys[1]) end, flags={'no-writes'}}";
+
+    let res = run_cmd(vec!["FUNCTION", "LOAD", code], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("mylib"))));
+
+    let res = run_cmd(vec!["FUNCTION", "LOAD", code], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR Library 'mylib' already exists".to_string())
+    );
+    let res = run_cmd(
+        vec!["FUNCTION", "LOAD", "REPLACE", code],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("mylib"))));
+
+    // Another library may not reuse a function name
+    let clash = "#!lua name=other\nredis.register_function('myget', function() return 1 end)";
+    let res = run_cmd(vec!["FUNCTION", "LOAD", clash], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR Function myget already exists".to_string())
+    );
+
+    let res = run_cmd(
+        vec!["FCALL", "myset", "1", "fk", "fv"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("fv"))));
+    let res = run_cmd(
+        vec!["FCALL_RO", "myget", "1", "fk"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("fv"))));
+
+    let res = run_cmd(
+        vec!["FCALL_RO", "myset", "1", "fk", "x"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR Can not execute a script with write flag using *_ro command.".to_string())
+    );
+
+    // A no-writes function cannot write even through FCALL
+    match run_cmd(
+        vec!["FCALL", "sneaky", "1", "fk"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await
+    {
+        Resp::Error(e) => assert!(e.contains("Write commands are not allowed"), "{}", e),
+        other => panic!("expected error, got {:?}", other),
+    }
+    let res = run_cmd(vec!["GET", "fk"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("fv"))));
+
+    let res = run_cmd(vec!["FCALL", "missing", "0"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Error("ERR Function not found".to_string()));
+
+    for (bad, msg) in [
+        ("return 1", "ERR Missing library metadata"),
+        ("#!js name=x\nreturn 1", "ERR Engine 'js' not found"),
+        ("#!lua name=empty\nreturn 1", "ERR No functions registered"),
+    ] {
+        let res = run_cmd(vec!["FUNCTION", "LOAD", bad], &mut conn_ctx, &server_ctx).await;
+        assert_eq!(res, Resp::Error(msg.to_string()));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_function_list_dump_restore_and_flush() {
+    use crate::tests::helper::run_cmd;
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let code = "#!lua name=lib1\nredis.register_function('f1', function() return 1 end)";
+    run_cmd(vec!["FUNCTION", "LOAD", code], &mut conn_ctx, &server_ctx).await;
+
+    let bulk = |s: &str| Resp::BulkString(Some(Bytes::from(s.to_string())));
+    let res = run_cmd(
+        vec!["FUNCTION", "LIST", "WITHCODE"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![Resp::Array(Some(vec![
+            bulk("library_name"),
+            bulk("lib1"),
+            bulk("engine"),
+            bulk("LUA"),
+            bulk("functions"),
+            Resp::Array(Some(vec![Resp::Array(Some(vec![
+                bulk("name"),
+                bulk("f1"),
+                bulk("description"),
+                Resp::BulkString(None),
+                bulk("flags"),
+                Resp::Array(Some(vec![])),
+            ]))])),
+            bulk("library_code"),
+            bulk(code),
+        ]))]))
+    );
+    let res = run_cmd(
+        vec!["FUNCTION", "LIST", "LIBRARYNAME", "nomatch*"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Array(Some(vec![])));
+
+    let dump = match run_cmd(vec!["FUNCTION", "DUMP"], &mut conn_ctx, &server_ctx).await {
+        Resp::BulkString(Some(b)) => b,
+        other => panic!("expected payload, got {:?}", other),
+    };
+
+    let res = run_cmd(vec!["FUNCTION", "FLUSH"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let res = run_cmd(vec!["FCALL", "f1", "0"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Error("ERR Function not found".to_string()));
+
+    let payload = String::from_utf8(dump.to_vec()).unwrap();
+    let res = run_cmd(
+        vec!["FUNCTION", "RESTORE", &payload],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let res = run_cmd(vec!["FCALL", "f1", "0"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(1));
+
+    // APPEND refuses an existing library, REPLACE overwrites it
+    let res = run_cmd(
+        vec!["FUNCTION", "RESTORE", &payload],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR Library lib1 already exists".to_string())
+    );
+    let res = run_cmd(
+        vec!["FUNCTION", "RESTORE", &payload, "REPLACE"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let res = run_cmd(
+        vec!["FUNCTION", "RESTORE", "garbage"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR payload version or checksum are wrong".to_string())
+    );
+
+    let res = run_cmd(
+        vec!["FUNCTION", "DELETE", "lib1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let res = run_cmd(
+        vec!["FUNCTION", "DELETE", "lib1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Error("ERR Library not found".to_string()));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_script_kill_stops_busy_script() {
+    use crate::tests::helper::run_cmd;
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    conn_ctx.id = 1;
+
+    let res = run_cmd(vec!["SCRIPT", "KILL"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("NOTBUSY No scripts in execution right now.".to_string())
+    );
+    let res = run_cmd(
+        vec!["CONFIG", "SET", "lua-time-limit", "50"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+
+    let script_ctx = server_ctx.clone();
+    let script = tokio::spawn(async move {
+        let mut script_conn = crate::tests::helper::create_connection_context();
+        script_conn.id = 2;
+        run_cmd(
+            vec!["EVAL", "while true do end", "0"],
+            &mut script_conn,
+            &script_ctx,
+        )
+        .await
+    });
+
+    // Other clients are refused once the script is past the limit
+    let mut busy = false;
+    for _ in 0..100 {
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        if let Resp::Error(e) = run_cmd(vec!["PING"], &mut conn_ctx, &server_ctx).await {
+            assert!(e.starts_with("BUSY "), "{}", e);
+            busy = true;
+            break;
+        }
+    }
+    assert!(busy, "expected BUSY while the script runs");
+
+    // Only SHUTDOWN NOSAVE gets through, not one that would save
+    let res = run_cmd(vec!["SHUTDOWN"], &mut conn_ctx, &server_ctx).await;
+    assert!(matches!(&res, Resp::Error(e) if e.starts_with("BUSY ")), "{:?}", res);
+
+    let res = run_cmd(vec!["SCRIPT", "KILL"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let res = tokio::time::timeout(std::time::Duration::from_secs(5), script)
+        .await
+        .expect("script was not killed")
+        .unwrap();
+    assert_eq!(
+        res,
+        Resp::Error("BUSY Script killed by user with SCRIPT KILL.".to_string())
+    );
+
+    let res = run_cmd(vec!["PING"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("PONG")));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_script_kill_refuses_script_that_wrote() {
+    use crate::tests::helper::run_cmd;
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    conn_ctx.id = 1;
+    run_cmd(
+        vec!["CONFIG", "SET", "lua-time-limit", "50"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    let script_ctx = server_ctx.clone();
+    let script = tokio::spawn(async move {
+        let mut script_conn = crate::tests::helper::create_connection_context();
+        script_conn.id = 2;
+        run_cmd(
+            vec![
+                "EVAL",
+                "redis.call('SET', 'k', 'v') \
+                 local t = redis.call('TIME') local stop = t[1] + 2 \
+                 while tonumber(redis.call('TIME')[1]) < stop do end return 1",
+                "0",
+            ],
+            &mut script_conn,
+            &script_ctx,
+        )
+        .await
+    });
+
+    let mut res = Resp::Null;
+    for _ in 0..100 {
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        res = run_cmd(vec!["SCRIPT", "KILL"], &mut conn_ctx, &server_ctx).await;
+        if matches!(&res, Resp::Error(e) if e.starts_with("UNKILLABLE")) {
+            break;
+        }
+    }
+    assert!(
+        matches!(&res, Resp::Error(e) if e.starts_with("UNKILLABLE ")),
+        "{:?}",
+        res
+    );
+
+    // Other clients stay refused, so the script has to end on its own
+    let res = script.await.unwrap();
+    assert_eq!(res, Resp::Integer(1));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_script_kill_only_targets_scripts_past_the_limit() {
+    use crate::tests::helper::run_cmd;
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    conn_ctx.id = 1;
+    run_cmd(
+        vec!["CONFIG", "SET", "lua-time-limit", "60000"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    // A script that wrote but is still under the limit
+    let script_ctx = server_ctx.clone();
+    let script = tokio::spawn(async move {
+        let mut script_conn = crate::tests::helper::create_connection_context();
+        script_conn.id = 2;
+        run_cmd(
+            vec![
+                "EVAL",
+                "redis.call('SET', 'k', 'v') while redis.call('GET', 'stop') == false do end return 1",
+                "0",
+            ],
+            &mut script_conn,
+            &script_ctx,
+        )
+        .await
+    });
+    for _ in 0..100 {
+        if !server_ctx.script_manager.running.is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert!(!server_ctx.script_manager.running.is_empty());
+
+    // It neither makes the server busy nor gets killed
+    let res = run_cmd(vec!["SCRIPT", "KILL"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("NOTBUSY No scripts in execution right now.".to_string())
+    );
+
+    run_cmd(vec!["SET", "stop", "1"], &mut conn_ctx, &server_ctx).await;
+    let res = script.await.unwrap();
+    assert_eq!(res, Resp::Integer(1));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_cjson_and_reply_helpers() {
+    use crate::tests::helper::run_cmd;
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let bulk = |s: &str| Resp::BulkString(Some(Bytes::from(s.to_string())));
+    for (script, expected) in [
+        ("return cjson.encode({1,2,3})", "[1,2,3]"),
+        ("return cjson.encode({})", "{}"),
+        ("return cjson.encode({a = 'x/y'})", r#"{"a":"x\/y"}"#),
+        (
+            "return cjson.encode({0.1, 1/3, 3.0})",
+            "[0.1,0.33333333333333,3]",
+        ),
+        ("return cjson.encode({true, cjson.null})", "[true,null]"),
+        (
+            "return cjson.encode(cjson.decode('{\"k\":[1,2.5,\"\\\\u00e9\"]}'))",
+            "{\"k\":[1,2.5,\"\u{e9}\"]}",
+        ),
+        (
+            "return redis.sha1hex('')",
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709",
+        ),
+    ] {
+        let res = run_cmd(vec!["EVAL", script, "0"], &mut conn_ctx, &server_ctx).await;
+        assert_eq!(res, bulk(expected), "{}", script);
+    }
+
+    // Decoded integers come back as integer replies
+    let res = run_cmd(
+        vec![
+            "EVAL",
+            "return cjson.decode(ARGV[1]).n",
+            "0",
+            r#"{"n": 42}"#,
+        ],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(42));
+
+    for script in [
+        "return cjson.decode('{\"a\":')",
+        "return cjson.encode({[1] = 1, [100] = 2})",
+        "return cjson.encode(function() end)",
+        "return cjson.encode(0/0)",
+    ] {
+        let res = run_cmd(vec!["EVAL", script, "0"], &mut conn_ctx, &server_ctx).await;
+        assert!(matches!(res, Resp::Error(_)), "{}: {:?}", script, res);
+    }
+
+    let res = run_cmd(
+        vec!["EVAL", "return redis.status_reply('DONE')", "0"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("DONE")));
+    let res = run_cmd(
+        vec!["EVAL", "return redis.error_reply('ERR custom')", "0"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Error("ERR custom".to_string()));
+}
@@ -25,14 +25,22 @@ mod stream_cmd;
 mod stream_persistence;
 mod string;
 mod string_ext;
+mod test_active_expire;
 mod test_bit;
+mod test_client_timeout;
 mod test_cluster_cmd;
+mod test_command;
 mod test_config_rewrite;
 mod test_core_missing;
+mod test_db_handle_cache;
+mod test_db_shards;
+mod test_debug_reload;
 mod test_diskless_sync;
 mod test_dump_restore;
 mod test_echo;
+mod test_empty_key_deletion;
 mod test_eviction;
+mod test_expire_overflow;
 mod test_hello;
 mod test_hexists;
 mod test_hincrby;
@@ -40,22 +48,28 @@ mod test_hsetnx;
 mod test_leader_election;
 mod test_lindex;
 mod test_linsert;
+mod test_lmpop;
 mod test_lpos;
 mod test_lrem;
 mod test_ltrim;
+mod test_maxclients;
 mod test_memory;
 mod test_min_replicas;
 mod test_missing_parts_2;
 mod test_move_swapdb;
 mod test_msetnx;
 mod test_notify;
+mod test_object_encoding;
 mod test_psync2;
 mod test_pushx;
+mod test_randomkey;
 mod test_rdb_config;
+mod test_reply_streaming;
 mod test_replconf_listening_port;
 mod test_replica_readonly;
 mod test_replication;
 mod test_reset;
+mod test_resp3;
 mod test_save_logic;
 mod test_sdiff;
 mod test_sdiffstore;
@@ -64,6 +78,7 @@ mod test_sentinel_failover;
 mod test_sentinel_hello;
 mod test_sentinel_integration;
 mod test_sentinel_logic;
+mod test_shutdown;
 mod test_sinter;
 mod test_sinterstore;
 mod test_smove;
@@ -75,6 +90,7 @@ mod test_sunionstore;
 mod test_touch;
 mod test_unlink;
 mod test_watch;
+mod test_xackdel;
 mod test_xclaim;
 mod test_xinfo;
 mod test_xpending;
@@ -85,6 +101,7 @@ mod test_zinter;
 mod test_zlexcount;
 mod test_zrandmember;
 mod test_zrangeby;
+mod test_zremrangeby;
 mod test_zunion;
 mod zscan_cmd;
 mod zset;
@@ -25,10 +25,15 @@ async fn test_monitor() {
     let client_info = crate::cmd::ClientInfo {
         id: 2,
         addr: "127.0.0.1:12345".to_string(),
+        laddr: "127.0.0.1:6379".to_string(),
         name: "".to_string(),
         db: 0,
         sub: 0,
         psub: 0,
+        ssub: 0,
+        multi: -1,
+        watch: 0,
+        resp: 2,
         flags: "N".to_string(),
         cmd: "".to_string(),
         connect_time: std::time::Instant::now(),
@@ -105,10 +110,15 @@ async fn test_monitor_lua() {
     let client_info = crate::cmd::ClientInfo {
         id: 2,
         addr: "127.0.0.1:12345".to_string(),
+        laddr: "127.0.0.1:6379".to_string(),
         name: "".to_string(),
         db: 0,
         sub: 0,
         psub: 0,
+        ssub: 0,
+        multi: -1,
+        watch: 0,
+        resp: 2,
         flags: "N".to_string(),
         cmd: "".to_string(),
         connect_time: std::time::Instant::now(),
@@ -155,3 +155,100 @@ async fn test_pubsub_channels_filtering() {
         panic!("Unexpected response: {:?}", resp);
     }
 }
+
+#[tokio::test]
+async fn test_sharded_pubsub() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let (stx, mut srx) = mpsc::channel(32);
+    let mut sharded = ConnectionContext::new(1, None, Some(stx), None);
+    let (ptx, mut prx) = mpsc::channel(32);
+    let mut plain = ConnectionContext::new(2, None, Some(ptx), None);
+    let (gtx, mut grx) = mpsc::channel(32);
+    let mut pattern = ConnectionContext::new(3, None, Some(gtx), None);
+    let mut publisher = ConnectionContext::new(4, None, None, None);
+    let bulk = |s: &str| Resp::BulkString(Some(Bytes::from(s.to_string())));
+
+    let res = run_cmd(vec!["SSUBSCRIBE", "news"], &mut sharded, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            bulk("ssubscribe"),
+            bulk("news"),
+            Resp::Integer(1)
+        ]))
+    );
+    run_cmd(vec!["SUBSCRIBE", "news"], &mut plain, &server_ctx).await;
+    run_cmd(vec!["PSUBSCRIBE", "n*"], &mut pattern, &server_ctx).await;
+
+    // SPUBLISH reaches shard subscribers only
+    let res = run_cmd(vec!["SPUBLISH", "news", "hi"], &mut publisher, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(1));
+    assert_eq!(
+        srx.try_recv().unwrap(),
+        Resp::Array(Some(vec![bulk("smessage"), bulk("news"), bulk("hi")]))
+    );
+    assert!(prx.try_recv().is_err());
+    assert!(grx.try_recv().is_err());
+
+    // and PUBLISH never reaches them
+    let res = run_cmd(vec!["PUBLISH", "news", "yo"], &mut publisher, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(2));
+    assert!(srx.try_recv().is_err());
+
+    let res = run_cmd(vec!["PUBSUB", "SHARDCHANNELS"], &mut publisher, &server_ctx).await;
+    assert_eq!(res, Resp::Array(Some(vec![bulk("news")])));
+    let res = run_cmd(
+        vec!["PUBSUB", "SHARDCHANNELS", "x*"],
+        &mut publisher,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Array(Some(vec![])));
+    let res = run_cmd(
+        vec!["PUBSUB", "SHARDNUMSUB", "news", "other"],
+        &mut publisher,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            bulk("news"),
+            Resp::Integer(1),
+            bulk("other"),
+            Resp::Integer(0)
+        ]))
+    );
+
+    // A shard subscriber is in subscribe mode until it leaves
+    let res = run_cmd(vec!["GET", "k"], &mut sharded, &server_ctx).await;
+    assert!(matches!(res, Resp::Error(_)), "{:?}", res);
+    let res = run_cmd(vec!["SUNSUBSCRIBE"], &mut sharded, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            bulk("sunsubscribe"),
+            bulk("news"),
+            Resp::Integer(0)
+        ]))
+    );
+    let res = run_cmd(vec!["GET", "k"], &mut sharded, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(None));
+
+    let res = run_cmd(
+        vec!["SPUBLISH", "news", "again"],
+        &mut publisher,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(0));
+    let res = run_cmd(
+        vec!["PUBSUB", "SHARDNUMSUB", "news"],
+        &mut publisher,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Array(Some(vec![bulk("news"), Resp::Integer(0)])));
+}
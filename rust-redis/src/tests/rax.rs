@@ -103,3 +103,40 @@ fn test_rax_random_keys() {
         assert_eq!(rax.get(key.as_bytes()), Some(&i));
     }
 }
+
+#[test]
+fn test_rax_last_skips_removed_keys() {
+    let mut rax = Rax::new();
+    assert_eq!(rax.last(), None);
+
+    rax.insert(b"apple", 1);
+    rax.insert(b"apply", 2);
+    rax.insert(b"banana", 3);
+    assert_eq!(rax.last(), Some(&3));
+
+    // The emptied branch is left in place and must be skipped
+    rax.remove(b"banana");
+    assert_eq!(rax.last(), Some(&2));
+    rax.remove(b"apply");
+    rax.remove(b"apple");
+    assert_eq!(rax.last(), None);
+}
+
+#[test]
+fn test_rax_first_skips_removed_keys() {
+    let mut rax = Rax::new();
+    assert_eq!(rax.first(), None);
+
+    rax.insert(b"app", 1);
+    rax.insert(b"apple", 2);
+    rax.insert(b"banana", 3);
+    assert_eq!(rax.first(), Some(&1));
+
+    // A prefix key sorts first; once it goes, the emptied node is skipped
+    rax.remove(b"app");
+    assert_eq!(rax.first(), Some(&2));
+    rax.remove(b"apple");
+    assert_eq!(rax.first(), Some(&3));
+    rax.remove(b"banana");
+    assert_eq!(rax.first(), None);
+}
@@ -214,3 +214,38 @@ async fn test_spop() {
         _ => panic!("expected empty Array"),
     }
 }
+
+#[tokio::test]
+async fn test_spop_count_deletes_key() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    run_cmd(vec!["SADD", "set", "a", "b", "c"], &mut conn_ctx, &server_ctx).await;
+
+    // Count larger than the set pops every member once
+    let res = run_cmd(vec!["SPOP", "set", "10"], &mut conn_ctx, &server_ctx).await;
+    match res {
+        Resp::Array(Some(items)) => {
+            let members: HashSet<Bytes> = items
+                .into_iter()
+                .map(|item| match item {
+                    Resp::BulkString(Some(b)) => b,
+                    _ => panic!("expected BulkString"),
+                })
+                .collect();
+            assert_eq!(members.len(), 3);
+        }
+        _ => panic!("expected Array"),
+    }
+    let res = run_cmd(vec!["EXISTS", "set"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+
+    // Popping the last member without a count also removes the key
+    run_cmd(vec!["SADD", "single", "x"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["SPOP", "single"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(vec!["EXISTS", "single"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+
+    let res = run_cmd(vec!["SPOP", "single"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(None));
+}
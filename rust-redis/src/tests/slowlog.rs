@@ -14,10 +14,15 @@ async fn test_slowlog_basic() {
     let client_info = crate::cmd::ClientInfo {
         id: 1,
         addr: "127.0.0.1:9999".to_string(),
+        laddr: "127.0.0.1:6379".to_string(),
         name: "cli".to_string(),
         db: 0,
         sub: 0,
         psub: 0,
+        ssub: 0,
+        multi: -1,
+        watch: 0,
+        resp: 2,
         flags: "N".to_string(),
         cmd: "".to_string(),
         connect_time: std::time::Instant::now(),
@@ -165,3 +170,59 @@ async fn test_slowlog_config() {
     // Verify change
     assert_eq!(server_ctx.slowlog.max_len.load(Ordering::Relaxed), 200);
 }
+
+#[tokio::test]
+async fn test_slowlog_records_name_set_after_connect() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    server_ctx.slowlog.threshold_us.store(0, Ordering::Relaxed);
+    server_ctx.slowlog.max_len.store(10, Ordering::Relaxed);
+
+    let client_info = crate::cmd::ClientInfo {
+        id: 2,
+        addr: "127.0.0.1:9998".to_string(),
+        laddr: "127.0.0.1:6379".to_string(),
+        name: "".to_string(),
+        db: 0,
+        sub: 0,
+        psub: 0,
+        ssub: 0,
+        multi: -1,
+        watch: 0,
+        resp: 2,
+        flags: "N".to_string(),
+        cmd: "".to_string(),
+        connect_time: std::time::Instant::now(),
+        last_activity: std::time::Instant::now(),
+        shutdown_tx: None,
+        msg_sender: None,
+    };
+    server_ctx.clients_ctx.clients.insert(2, client_info);
+    let mut conn = ConnectionContext::new(2, None, None, None);
+
+    let last_entry_name = |res: Resp| match res {
+        Resp::Array(Some(arr)) => match &arr[0] {
+            Resp::Array(Some(entry)) => entry[5].clone(),
+            other => panic!("Expected entry array, got {:?}", other),
+        },
+        other => panic!("Expected array for SLOWLOG GET, got {:?}", other),
+    };
+
+    run_cmd(vec!["SET", "k", "v"], &mut conn, &server_ctx).await;
+    let res = run_cmd(vec!["SLOWLOG", "GET", "1"], &mut conn, &server_ctx).await;
+    assert_eq!(last_entry_name(res), Resp::BulkString(Some(Bytes::new())));
+
+    run_cmd(
+        vec!["CLIENT", "SETNAME", "reporter"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    run_cmd(vec!["GET", "k"], &mut conn, &server_ctx).await;
+    let res = run_cmd(vec!["SLOWLOG", "GET", "1"], &mut conn, &server_ctx).await;
+    assert_eq!(
+        last_entry_name(res),
+        Resp::BulkString(Some(Bytes::from("reporter")))
+    );
+}
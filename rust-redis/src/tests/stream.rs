@@ -57,3 +57,129 @@ fn test_stream_id_to_bytes() {
     assert_eq!(bytes[0..8], 0x1234567890ABCDEFu64.to_be_bytes());
     assert_eq!(bytes[8..16], 0xFEDCBA0987654321u64.to_be_bytes());
 }
+
+#[test]
+fn test_stream_id_parse_rejects_malformed() {
+    for bad in [
+        "",
+        "abc",
+        "1-2-3",
+        "-1",
+        "1--2",
+        "-",
+        "5-",
+        "+5",
+        " 5",
+        "5-abc",
+        "18446744073709551616",
+        "1-18446744073709551616",
+    ] {
+        assert!(
+            bad.parse::<StreamID>().is_err(),
+            "{:?} should not parse",
+            bad
+        );
+    }
+
+    assert_eq!("5".parse::<StreamID>(), Ok(StreamID::new(5, 0)));
+    assert_eq!("5-3".parse::<StreamID>(), Ok(StreamID::new(5, 3)));
+    assert_eq!(
+        StreamID::parse("5", u64::MAX),
+        Ok(StreamID::new(5, u64::MAX))
+    );
+    assert_eq!(
+        "18446744073709551615-18446744073709551615".parse::<StreamID>(),
+        Ok(StreamID::new(u64::MAX, u64::MAX))
+    );
+}
+
+#[test]
+fn test_stream_id_parse_random_input_never_panics() {
+    use rand::Rng;
+
+    let alphabet = b"0123456789--+*$ ax";
+    let mut rng = rand::rng();
+    for _ in 0..20_000 {
+        let len = rng.random_range(0..45);
+        let s: String = (0..len)
+            .map(|_| alphabet[rng.random_range(0..alphabet.len())] as char)
+            .collect();
+        if let Ok(id) = s.parse::<StreamID>() {
+            assert_eq!(id.to_string().parse::<StreamID>(), Ok(id), "{:?}", s);
+        }
+    }
+}
+
+#[test]
+fn test_stream_id_next() {
+    assert_eq!(StreamID::new(5, 1).next(), Some(StreamID::new(5, 2)));
+    assert_eq!(StreamID::new(5, u64::MAX).next(), Some(StreamID::new(6, 0)));
+    assert_eq!(StreamID::new(u64::MAX, u64::MAX).next(), None);
+}
+
+#[tokio::test]
+async fn test_stream_commands_bare_ms_ids() {
+    use crate::resp::Resp;
+    use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+
+    let res = run_cmd(vec!["XADD", "s", "5", "f", "v"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("5-0"))));
+    run_cmd(vec!["XADD", "s", "5-7", "f", "v"], &mut conn, &server_ctx).await;
+    run_cmd(vec!["XADD", "s", "6-0", "f", "v"], &mut conn, &server_ctx).await;
+
+    // A bare end bound covers the whole millisecond
+    match run_cmd(vec!["XRANGE", "s", "5", "5"], &mut conn, &server_ctx).await {
+        Resp::Array(Some(entries)) => assert_eq!(entries.len(), 2),
+        other => panic!("Expected entries, got {:?}", other),
+    }
+    match run_cmd(vec!["XREVRANGE", "s", "5", "-"], &mut conn, &server_ctx).await {
+        Resp::Array(Some(entries)) => assert_eq!(entries.len(), 2),
+        other => panic!("Expected entries, got {:?}", other),
+    }
+
+    for bad in ["abc", "1-2-3", "-1", "18446744073709551616"] {
+        let res = run_cmd(vec!["XADD", "s", bad, "f", "v"], &mut conn, &server_ctx).await;
+        assert_eq!(
+            res,
+            Resp::Error("ERR invalid stream ID".to_string()),
+            "{}",
+            bad
+        );
+        let res = run_cmd(vec!["XRANGE", "s", bad, "+"], &mut conn, &server_ctx).await;
+        assert_eq!(
+            res,
+            Resp::Error("ERR invalid start ID".to_string()),
+            "{}",
+            bad
+        );
+    }
+
+    // Auto IDs carry into the next millisecond instead of overflowing
+    run_cmd(
+        vec![
+            "XADD",
+            "t",
+            "18446744073709551615-18446744073709551614",
+            "f",
+            "v",
+        ],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(vec!["XADD", "t", "*", "f", "v"], &mut conn, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::BulkString(Some(Bytes::from(
+            "18446744073709551615-18446744073709551615"
+        )))
+    );
+    let res = run_cmd(vec!["XADD", "t", "*", "f", "v"], &mut conn, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR The stream has exhausted the last possible ID".to_string())
+    );
+}
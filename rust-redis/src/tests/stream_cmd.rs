@@ -555,3 +555,166 @@ async fn test_xread_block() {
         panic!("Expected Array, got {:?}", resp);
     }
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_xread_block_wakes_on_xadd() {
+    use crate::tests::helper::run_cmd;
+    use std::time::{Duration, Instant};
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    run_cmd(
+        vec!["XADD", "s", "1-1", "f", "old"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    // `$` only sees entries added after the call starts blocking
+    let server_ctx_clone = server_ctx.clone();
+    let handle = tokio::spawn(async move {
+        let mut conn_ctx = crate::tests::helper::create_connection_context();
+        let resp = run_cmd(
+            vec!["XREAD", "BLOCK", "5000", "STREAMS", "s", "$"],
+            &mut conn_ctx,
+            &server_ctx_clone,
+        )
+        .await;
+        (resp, Instant::now())
+    });
+
+    while server_ctx
+        .clients_ctx
+        .blocked_client_count
+        .load(std::sync::atomic::Ordering::Relaxed)
+        == 0
+    {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    // Let a polling reader fall back to sleep before the entry arrives
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let added = Instant::now();
+    run_cmd(
+        vec!["XADD", "s", "2-1", "f", "new"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let (resp, returned) = handle.await.unwrap();
+
+    let latency = returned.duration_since(added);
+    assert!(
+        latency < Duration::from_millis(3),
+        "woke after {:?}",
+        latency
+    );
+    let expected = Resp::Array(Some(vec![Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("s"))),
+        Resp::Array(Some(vec![Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("2-1"))),
+            Resp::Array(Some(vec![
+                Resp::BulkString(Some(Bytes::from("f"))),
+                Resp::BulkString(Some(Bytes::from("new"))),
+            ])),
+        ]))])),
+    ]))]));
+    assert_eq!(resp, expected);
+    assert_eq!(
+        server_ctx
+            .clients_ctx
+            .blocked_client_count
+            .load(std::sync::atomic::Ordering::Relaxed),
+        0
+    );
+
+    // A reader that times out takes its registration with it
+    let resp = run_cmd(
+        vec!["XREAD", "BLOCK", "10", "STREAMS", "s", "other", "$", "$"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(resp, Resp::BulkString(None));
+    assert!(server_ctx.stream_waiters.is_empty());
+}
+
+#[tokio::test]
+async fn test_xrange_partial_ids_and_count_zero() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn = crate::tests::helper::create_connection_context();
+
+    for id in ["4-9", "5-0", "5-1", "5-18446744073709551615", "6-0"] {
+        run_cmd(vec!["XADD", "s", id, "f", "v"], &mut conn, &server_ctx).await;
+    }
+    let ids = |res: Resp| -> Vec<String> {
+        match res {
+            Resp::Array(Some(entries)) => entries
+                .into_iter()
+                .map(|e| match e {
+                    Resp::Array(Some(mut pair)) => match pair.remove(0) {
+                        Resp::BulkString(Some(id)) => String::from_utf8_lossy(&id).to_string(),
+                        other => panic!("Expected id, got {:?}", other),
+                    },
+                    other => panic!("Expected entry, got {:?}", other),
+                })
+                .collect(),
+            other => panic!("Expected entries, got {:?}", other),
+        }
+    };
+    let ms5 = vec!["5-0", "5-1", "5-18446744073709551615"];
+
+    // Start defaults to seq 0, end to the max seq
+    let res = run_cmd(vec!["XRANGE", "s", "5", "5"], &mut conn, &server_ctx).await;
+    assert_eq!(ids(res), ms5);
+    let res = run_cmd(vec!["XRANGE", "s", "5", "+"], &mut conn, &server_ctx).await;
+    assert_eq!(
+        ids(res),
+        vec!["5-0", "5-1", "5-18446744073709551615", "6-0"]
+    );
+    let res = run_cmd(vec!["XRANGE", "s", "-", "5"], &mut conn, &server_ctx).await;
+    assert_eq!(
+        ids(res),
+        vec!["4-9", "5-0", "5-1", "5-18446744073709551615"]
+    );
+    let res = run_cmd(vec!["XREVRANGE", "s", "5", "5"], &mut conn, &server_ctx).await;
+    assert_eq!(ids(res), ms5.iter().rev().copied().collect::<Vec<_>>());
+
+    // COUNT 0 (and a negative COUNT) return nothing
+    for count in ["0", "-3"] {
+        let res = run_cmd(
+            vec!["XRANGE", "s", "-", "+", "COUNT", count],
+            &mut conn,
+            &server_ctx,
+        )
+        .await;
+        assert_eq!(res, Resp::Array(Some(vec![])));
+        let res = run_cmd(
+            vec!["XREVRANGE", "s", "+", "-", "COUNT", count],
+            &mut conn,
+            &server_ctx,
+        )
+        .await;
+        assert_eq!(res, Resp::Array(Some(vec![])));
+    }
+    let res = run_cmd(
+        vec!["XRANGE", "s", "5", "5", "COUNT", "2"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(ids(res), vec!["5-0", "5-1"]);
+
+    // Bounds are validated even when the key does not exist
+    let res = run_cmd(vec!["XRANGE", "nokey", "5-x", "+"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Error("ERR invalid start ID".to_string()));
+    let res = run_cmd(
+        vec!["XRANGE", "s", "-", "+", "COUNT", "x"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Error("ERR invalid count".to_string()));
+}
@@ -38,6 +38,10 @@ mod tests {
         let id1 = StreamID::new(1000, 1);
         let fields1 = vec![(Bytes::from("name"), Bytes::from("alice"))];
         stream.insert(id1, fields1.clone()).unwrap();
+        // Later entries were deleted: replaying the one left can't tell
+        stream.last_id = StreamID::new(2000, 5);
+        stream.entries_added = 7;
+        stream.max_deleted_id = StreamID::new(2000, 5);
 
         let group_name = "mygroup".to_string();
         let group = crate::stream::ConsumerGroup::new(group_name.clone(), StreamID::new(0, 0));
@@ -50,7 +54,7 @@ mod tests {
 
         // 2. Perform AOF Rewrite
         let mut aof = Aof::new(path, AppendFsync::No).await.unwrap();
-        aof.rewrite(&db).await.unwrap();
+        aof.rewrite(&db, &[]).await.unwrap();
 
         // 3. Load AOF into new DB
         let new_db = Arc::new(vec![RwLock::new(Db::default())]);
@@ -79,6 +83,9 @@ mod tests {
                 let retrieved = s.get(&id1).unwrap();
                 assert_eq!(retrieved.fields, fields1);
                 assert!(s.groups.contains_key(&group_name));
+                assert_eq!(s.last_id, StreamID::new(2000, 5));
+                assert_eq!(s.entries_added, 7);
+                assert_eq!(s.max_deleted_id, StreamID::new(2000, 5));
             }
             _ => panic!("Expected Stream"),
         }
@@ -313,9 +320,15 @@ mod tests {
         let fields2 = vec![(Bytes::from("name"), Bytes::from("bob"))];
         stream.insert(id2, fields2.clone()).unwrap();
 
+        // A third entry was added and deleted
+        stream.last_id = StreamID::new(1000, 3);
+        stream.entries_added = 3;
+        stream.max_deleted_id = StreamID::new(1000, 3);
+
         // Add Consumer Group
         let group_name = "mygroup".to_string();
-        let group = crate::stream::ConsumerGroup::new(group_name.clone(), StreamID::new(0, 0));
+        let mut group = crate::stream::ConsumerGroup::new(group_name.clone(), StreamID::new(0, 0));
+        group.entries_read = Some(1);
         stream.groups.insert(group_name.clone(), group);
 
         // Add Consumer and PEL (simulate XREADGROUP)
@@ -371,6 +384,9 @@ mod tests {
             Value::Stream(s) => {
                 assert_eq!(s.len(), 2);
                 assert_eq!(s.groups.len(), 1);
+                assert_eq!(s.last_id, StreamID::new(1000, 3));
+                assert_eq!(s.entries_added, 3);
+                assert_eq!(s.max_deleted_id, StreamID::new(1000, 3));
 
                 // Verify entries
                 let retrieved_entry = s.get(&id1).unwrap();
@@ -381,6 +397,7 @@ mod tests {
 
                 // Verify Group
                 let group = s.groups.get(&group_name).unwrap();
+                assert_eq!(group.entries_read, Some(1));
                 assert_eq!(group.consumers.len(), 1);
                 assert_eq!(group.pel.len(), 1);
 
@@ -542,3 +542,373 @@ async fn test_stralgo_minmatchlen() {
         _ => panic!("expected Array"),
     }
 }
+
+#[tokio::test]
+async fn test_string_ops_on_integer_values() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    run_cmd(vec!["SET", "k", "12345"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(vec!["STRLEN", "k"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(5));
+
+    let res = run_cmd(vec!["GETRANGE", "k", "1", "3"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("234"))));
+    let res = run_cmd(vec!["GETRANGE", "k", "-2", "-1"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("45"))));
+
+    // INCR leaves a decimal string behind that the read commands still see
+    run_cmd(vec!["INCR", "k"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(vec!["STRLEN", "k"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(5));
+    let res = run_cmd(vec!["GETRANGE", "k", "4", "4"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("6"))));
+
+    // SETRANGE overwrites digits in place and the result is a plain string
+    let res = run_cmd(vec!["SETRANGE", "k", "5", "x"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(6));
+    let res = run_cmd(vec!["GET", "k"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("12346x"))));
+    let res = run_cmd(vec!["INCR", "k"], &mut conn_ctx, &server_ctx).await;
+    assert!(matches!(res, Resp::Error(_)), "got {:?}", res);
+}
+
+#[tokio::test]
+async fn test_getdel_wrongtype_and_missing() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    // WRONGTYPE leaves the list in place
+    run_cmd(vec!["RPUSH", "mylist", "a"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(vec!["GETDEL", "mylist"], &mut conn_ctx, &server_ctx).await;
+    match res {
+        Resp::Error(e) => assert!(e.starts_with("WRONGTYPE"), "got {}", e),
+        _ => panic!("expected WRONGTYPE, got {:?}", res),
+    }
+    let res = run_cmd(vec!["LLEN", "mylist"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(1));
+
+    // Missing key -> nil, nothing created
+    let res = run_cmd(vec!["GETDEL", "nokey"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(None));
+    let res = run_cmd(vec!["EXISTS", "nokey"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+}
+
+#[tokio::test]
+async fn test_incrbyfloat_canonical_format() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let res = run_cmd(vec!["INCRBYFLOAT", "f", "3.0"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("3"))));
+    let res = run_cmd(vec!["GET", "f"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("3"))));
+
+    let res = run_cmd(vec!["INCRBYFLOAT", "g", "5.0e3"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("5000"))));
+
+    let res = run_cmd(vec!["INCRBYFLOAT", "g", "0.5"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("5000.5"))));
+
+    let res = run_cmd(
+        vec!["HINCRBYFLOAT", "h", "f", "10.50"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("10.5"))));
+    let res = run_cmd(
+        vec!["HINCRBYFLOAT", "h", "f", "-0.5"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("10"))));
+}
+
+#[test]
+fn test_format_double() {
+    use crate::resp::format_double;
+
+    assert_eq!(format_double(3.0), "3");
+    assert_eq!(format_double(-2.5), "-2.5");
+    assert_eq!(format_double(0.0001), "0.0001");
+    assert_eq!(format_double(0.00001), "1e-05");
+    assert_eq!(format_double(1e16), "10000000000000000");
+    assert_eq!(format_double(1.5e17), "1.5e+17");
+    assert_eq!(format_double(f64::INFINITY), "inf");
+    assert_eq!(format_double(f64::NEG_INFINITY), "-inf");
+
+    // Integral values take the stack-formatted path
+    assert_eq!(format_double(0.0), "0");
+    assert_eq!(format_double(-0.0), "-0");
+    assert_eq!(format_double(-7.0), "-7");
+    assert_eq!(format_double(99999999999999984.0), "99999999999999984");
+    assert_eq!(format_double(1e17), "1e+17");
+}
+
+#[tokio::test]
+async fn test_get_hget_replies_share_stored_bytes() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let value = "x".repeat(4096);
+    run_cmd(vec!["SET", "s", &value], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["HSET", "h", "f", &value], &mut conn_ctx, &server_ctx).await;
+
+    let stored_ptr = |key: &str| {
+        let db = server_ctx.databases[0].read().unwrap();
+        let entry = db.get(key.as_bytes()).unwrap();
+        match &entry.value {
+            crate::db::Value::String(b) => b.as_ptr(),
+            crate::db::Value::Hash(map) => map.get(&Bytes::from("f")).unwrap().as_ptr(),
+            _ => panic!("unexpected type"),
+        }
+    };
+    let string_ptr = stored_ptr("s");
+    let hash_ptr = stored_ptr("h");
+
+    // A tight read loop never copies the payload: every reply points at the
+    // stored buffer
+    for _ in 0..10_000 {
+        match run_cmd(vec!["GET", "s"], &mut conn_ctx, &server_ctx).await {
+            Resp::BulkString(Some(b)) => assert_eq!(b.as_ptr(), string_ptr),
+            other => panic!("expected bulk string, got {:?}", other),
+        }
+        match run_cmd(vec!["HGET", "h", "f"], &mut conn_ctx, &server_ctx).await {
+            Resp::BulkString(Some(b)) => assert_eq!(b.as_ptr(), hash_ptr),
+            other => panic!("expected bulk string, got {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_set_get_keepttl_and_absolute_expiry() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    let bulk = |s: &str| Resp::BulkString(Some(Bytes::from(s.to_string())));
+    let ttl_in = |res: Resp, lo: i64, hi: i64| match res {
+        Resp::Integer(t) => assert!(t >= lo && t <= hi, "ttl {}", t),
+        other => panic!("expected Integer, got {:?}", other),
+    };
+
+    // KEEPTTL retains the expiry; a plain SET clears it
+    run_cmd(
+        vec!["SET", "k", "v1", "EX", "100"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(
+        vec!["SET", "k", "v2", "KEEPTTL", "GET"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("v1"));
+    ttl_in(
+        run_cmd(vec!["TTL", "k"], &mut conn_ctx, &server_ctx).await,
+        99,
+        100,
+    );
+    run_cmd(vec!["SET", "k", "v3"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(vec!["TTL", "k"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(-1));
+
+    // GET reports the current value even when NX/XX skip the write
+    let res = run_cmd(
+        vec!["SET", "k", "v4", "NX", "GET"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("v3"));
+    let res = run_cmd(vec!["GET", "k"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, bulk("v3"));
+    let res = run_cmd(
+        vec!["SET", "new", "v", "NX", "GET"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::BulkString(None));
+    let res = run_cmd(vec!["GET", "new"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, bulk("v"));
+    let res = run_cmd(
+        vec!["SET", "absent", "v", "XX", "GET"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::BulkString(None));
+    let res = run_cmd(vec!["EXISTS", "absent"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+
+    // GET refuses to replace a non-string; a plain SET overwrites it
+    run_cmd(vec!["RPUSH", "l", "a"], &mut conn_ctx, &server_ctx).await;
+    match run_cmd(vec!["SET", "l", "v", "GET"], &mut conn_ctx, &server_ctx).await {
+        Resp::Error(e) => assert!(e.starts_with("WRONGTYPE"), "got {}", e),
+        other => panic!("expected WRONGTYPE, got {:?}", other),
+    }
+    let res = run_cmd(vec!["LLEN", "l"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(1));
+    let res = run_cmd(vec!["SET", "l", "v"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+
+    // Absolute expiries
+    let now_secs = crate::clock::now_ms() / 1000;
+    let at = (now_secs + 100).to_string();
+    run_cmd(
+        vec!["SET", "ex", "v", "EXAT", &at],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    ttl_in(
+        run_cmd(vec!["TTL", "ex"], &mut conn_ctx, &server_ctx).await,
+        98,
+        100,
+    );
+    let past = (crate::clock::now_ms() - 1000).to_string();
+    run_cmd(
+        vec!["SET", "px", "v", "PXAT", &past],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(vec!["GET", "px"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(None));
+
+    let res = run_cmd(
+        vec!["SET", "k", "v", "EX", "10", "KEEPTTL"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Error("ERR syntax error".to_string()));
+}
+
+#[tokio::test]
+async fn test_getex_options_and_propagation() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    let bulk = |s: &str| Resp::BulkString(Some(Bytes::from(s.to_string())));
+    let frame = |args: &[&str]| Resp::Array(Some(args.iter().map(|a| bulk(a)).collect()));
+
+    run_cmd(
+        vec!["SET", "k", "v", "EX", "100"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    // No option: the TTL is untouched and nothing is propagated
+    let (res, log) = process_frame(frame(&["GETEX", "k"]), &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, bulk("v"));
+    assert_eq!(log, None);
+    let res = run_cmd(vec!["TTL", "k"], &mut conn_ctx, &server_ctx).await;
+    assert!(
+        matches!(res, Resp::Integer(t) if t > 0 && t <= 100),
+        "{:?}",
+        res
+    );
+
+    // EX/PX are propagated as the absolute PEXPIREAT they resolved to
+    let before = crate::clock::now_ms();
+    let (res, log) = process_frame(
+        frame(&["GETEX", "k", "PX", "50000"]),
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("v"));
+    let Some(Resp::Array(Some(logged))) = log else {
+        panic!("expected a PEXPIREAT log entry, got {:?}", log);
+    };
+    assert_eq!(logged[..2], [bulk("PEXPIREAT"), bulk("k")]);
+    let at: u64 = match &logged[2] {
+        Resp::BulkString(Some(b)) => std::str::from_utf8(b).unwrap().parse().unwrap(),
+        other => panic!("expected a timestamp, got {:?}", other),
+    };
+    assert!(at >= before + 50000 && at <= crate::clock::now_ms() + 50000);
+
+    // EXAT/PXAT set absolute expiries
+    let exat = (crate::clock::now_ms() / 1000 + 200).to_string();
+    run_cmd(
+        vec!["GETEX", "k", "EXAT", &exat],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(vec!["TTL", "k"], &mut conn_ctx, &server_ctx).await;
+    assert!(
+        matches!(res, Resp::Integer(t) if (198..=200).contains(&t)),
+        "{:?}",
+        res
+    );
+    let pxat = (crate::clock::now_ms() + 300_000).to_string();
+    let (_, log) = process_frame(
+        frame(&["GETEX", "k", "PXAT", &pxat]),
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(log, Some(frame(&["PEXPIREAT", "k", &pxat])));
+    let res = run_cmd(vec!["PTTL", "k"], &mut conn_ctx, &server_ctx).await;
+    assert!(
+        matches!(res, Resp::Integer(t) if t > 299_000 && t <= 300_000),
+        "{:?}",
+        res
+    );
+
+    // PERSIST clears the TTL and returns the value; a second PERSIST is a no-op
+    let (res, log) = process_frame(
+        frame(&["GETEX", "k", "PERSIST"]),
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("v"));
+    assert_eq!(log, Some(frame(&["PERSIST", "k"])));
+    let res = run_cmd(vec!["TTL", "k"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(-1));
+    let (_, log) = process_frame(
+        frame(&["GETEX", "k", "PERSIST"]),
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(log, None);
+
+    // A missing key changes nothing
+    let (res, log) = process_frame(
+        frame(&["GETEX", "nokey", "EX", "10"]),
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::BulkString(None));
+    assert_eq!(log, None);
+
+    // Options are mutually exclusive
+    let res = run_cmd(
+        vec!["GETEX", "k", "EX", "10", "PERSIST"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Error("ERR syntax error".to_string()));
+}
@@ -0,0 +1,229 @@
+use crate::aof::{Aof, AppendFsync, start_aof_task};
+use crate::cmd::{ConnectionContext, ReplicationRole, ServerContext, active_expire_cycle};
+use crate::resp::Resp;
+use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+fn master() -> ServerContext {
+    let server_ctx = create_server_context();
+    *server_ctx.repl.replication_role.write().unwrap() = ReplicationRole::Master;
+    server_ctx
+}
+
+fn in_keyspace(server_ctx: &ServerContext, key: &str) -> bool {
+    server_ctx.databases[0]
+        .read()
+        .unwrap()
+        .contains_key(key.as_bytes())
+}
+
+#[tokio::test]
+async fn test_active_expire_notifies_and_logs_del() {
+    let path = std::env::temp_dir().join(format!("active-expire-{}.aof", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let aof = Aof::new(path.to_str().unwrap(), AppendFsync::Always)
+        .await
+        .unwrap();
+    let mut server_ctx = master();
+    server_ctx.aof = Some(start_aof_task(aof));
+
+    let mut conn = create_connection_context();
+    run_cmd(
+        vec!["CONFIG", "SET", "notify-keyspace-events", "Ex"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    let (tx, mut rx) = mpsc::channel(8);
+    let mut sub = ConnectionContext::new(1, None, Some(tx), None);
+    sub.authenticated = true;
+    run_cmd(
+        vec!["SUBSCRIBE", "__keyevent@0__:expired"],
+        &mut sub,
+        &server_ctx,
+    )
+    .await;
+
+    let mut watcher = create_connection_context();
+    watcher.id = 2;
+    server_ctx
+        .clients_ctx
+        .client_watched_dirty
+        .insert(watcher.id, watcher.watched_keys_dirty.clone());
+    run_cmd(vec!["SET", "k", "v", "PX", "10"], &mut conn, &server_ctx).await;
+    run_cmd(vec!["SET", "kept", "v"], &mut conn, &server_ctx).await;
+    run_cmd(vec!["WATCH", "k"], &mut watcher, &server_ctx).await;
+    tokio::time::sleep(Duration::from_millis(30)).await;
+
+    // Nothing reads the key: only the cycle can remove it
+    assert!(in_keyspace(&server_ctx, "k"));
+    active_expire_cycle(&server_ctx).await;
+    assert!(!in_keyspace(&server_ctx, "k"));
+    assert!(in_keyspace(&server_ctx, "kept"));
+
+    let msg = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        msg,
+        Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("message"))),
+            Resp::BulkString(Some(Bytes::from("__keyevent@0__:expired"))),
+            Resp::BulkString(Some(Bytes::from("k"))),
+        ]))
+    );
+
+    // The WATCHing transaction is aborted
+    run_cmd(vec!["MULTI"], &mut watcher, &server_ctx).await;
+    run_cmd(vec!["SET", "k", "new"], &mut watcher, &server_ctx).await;
+    assert_eq!(
+        run_cmd(vec!["EXEC"], &mut watcher, &server_ctx).await,
+        Resp::Array(None)
+    );
+
+    let mut logged = String::new();
+    for _ in 0..100 {
+        logged = std::fs::read_to_string(&path).unwrap();
+        if logged.contains("DEL") {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(
+        logged.ends_with("*2\r\n$6\r\nSELECT\r\n$1\r\n0\r\n*2\r\n$3\r\nDEL\r\n$1\r\nk\r\n"),
+        "{:?}",
+        logged
+    );
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_debug_set_active_expire() {
+    let server_ctx = master();
+    let mut conn = create_connection_context();
+    crate::cmd::start_expiration_task(server_ctx.clone());
+
+    assert_eq!(
+        run_cmd(
+            vec!["DEBUG", "SET-ACTIVE-EXPIRE", "0"],
+            &mut conn,
+            &server_ctx
+        )
+        .await,
+        Resp::SimpleString(Bytes::from("OK"))
+    );
+    run_cmd(vec!["SET", "k", "v", "PX", "10"], &mut conn, &server_ctx).await;
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert!(in_keyspace(&server_ctx, "k"));
+
+    run_cmd(
+        vec!["DEBUG", "SET-ACTIVE-EXPIRE", "1"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert!(!in_keyspace(&server_ctx, "k"));
+
+    match run_cmd(
+        vec!["DEBUG", "SET-ACTIVE-EXPIRE", "2"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await
+    {
+        Resp::Error(e) => assert!(e.contains("must be 0 or 1"), "{}", e),
+        other => panic!("expected error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_active_expire_reaches_every_volatile_key() {
+    let server_ctx = master();
+    let mut conn = create_connection_context();
+
+    // Far more long-lived volatile keys than one sample walks, so the
+    // expired ones are only found if samples start all over the keyspace
+    for i in 0..500 {
+        run_cmd(
+            vec!["SET", &format!("live:{}", i), "v", "EX", "1000"],
+            &mut conn,
+            &server_ctx,
+        )
+        .await;
+    }
+    for i in 0..20 {
+        run_cmd(
+            vec!["SET", &format!("gone:{}", i), "v", "PX", "1"],
+            &mut conn,
+            &server_ctx,
+        )
+        .await;
+    }
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let remaining = |server_ctx: &ServerContext| {
+        (0..20)
+            .filter(|i| in_keyspace(server_ctx, &format!("gone:{}", i)))
+            .count()
+    };
+    for _ in 0..10_000 {
+        if remaining(&server_ctx) == 0 {
+            break;
+        }
+        active_expire_cycle(&server_ctx).await;
+    }
+    assert_eq!(remaining(&server_ctx), 0);
+    assert_eq!(server_ctx.databases[0].read().unwrap().len(), 500);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_active_expire_spares_keys_set_during_the_cycle() {
+    use crate::db::{Entry, Value};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let server_ctx = master();
+    let done = Arc::new(AtomicBool::new(false));
+    let cycles = {
+        let (server_ctx, done) = (server_ctx.clone(), done.clone());
+        tokio::spawn(async move {
+            while !done.load(Ordering::Relaxed) {
+                active_expire_cycle(&server_ctx).await;
+                tokio::task::yield_now().await;
+            }
+        })
+    };
+
+    // Each round leaves expired keys for the cycle to find and SETs them
+    // again right away; a cycle that checks and deletes in two steps can
+    // delete the new value in between
+    let mut conn = create_connection_context();
+    let db = server_ctx.databases[0].read().unwrap().clone();
+    for round in 0..500 {
+        for i in 0..50 {
+            db.insert(
+                Bytes::from(format!("k:{}", i)),
+                Entry::new_with_expire(Value::String(Bytes::from("old")), Some(0)),
+            );
+        }
+        for i in 0..50 {
+            let key = format!("k:{}", i);
+            run_cmd(vec!["SET", &key, "new"], &mut conn, &server_ctx).await;
+        }
+        for i in 0..50 {
+            assert_eq!(
+                run_cmd(vec!["GET", &format!("k:{}", i)], &mut conn, &server_ctx).await,
+                Resp::BulkString(Some(Bytes::from("new"))),
+                "k:{} lost in round {}",
+                i,
+                round
+            );
+        }
+    }
+    done.store(true, Ordering::Relaxed);
+    cycles.await.unwrap();
+}
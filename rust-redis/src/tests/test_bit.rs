@@ -265,3 +265,40 @@ async fn test_setbit_errors() {
         _ => panic!("Expected error"),
     }
 }
+
+#[tokio::test]
+async fn test_setbit_offset_cap() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    // 2^32 is the first offset past the 512MB cap; it must be rejected before
+    // anything is allocated, so the key never comes into existence.
+    let res = run_cmd(
+        vec!["SETBIT", "big", "4294967296", "1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR bit offset is not an integer or out of range".to_string())
+    );
+    let res = run_cmd(vec!["EXISTS", "big"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+
+    // Negative offsets are out of range too
+    let res = run_cmd(vec!["SETBIT", "big", "-1", "1"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR bit offset is not an integer or out of range".to_string())
+    );
+
+    // Bit values other than 0/1 are rejected without touching the key
+    let res = run_cmd(vec!["SETBIT", "big", "7", "-1"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR bit is not an integer or out of range".to_string())
+    );
+    let res = run_cmd(vec!["EXISTS", "big"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+}
@@ -0,0 +1,69 @@
+use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+use crate::resp::Resp;
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+#[tokio::test]
+async fn test_idle_client_reaped_blocked_client_spared() {
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+
+    let res = run_cmd(vec!["CONFIG", "SET", "timeout", "1"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(crate::serve(listener, server_ctx.clone()));
+    crate::cmd::start_client_timeout_task(server_ctx.clone());
+
+    let mut idle = TcpStream::connect(addr).await.unwrap();
+    idle.write_all(b"*1\r\n$4\r\nPING\r\n").await.unwrap();
+    let mut buf = [0u8; 7];
+    idle.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"+PONG\r\n");
+
+    let mut blocked = TcpStream::connect(addr).await.unwrap();
+    blocked
+        .write_all(b"*3\r\n$5\r\nBLPOP\r\n$5\r\nblist\r\n$1\r\n0\r\n")
+        .await
+        .unwrap();
+
+    let mut shard_sub = TcpStream::connect(addr).await.unwrap();
+    shard_sub
+        .write_all(b"*2\r\n$10\r\nSSUBSCRIBE\r\n$2\r\nch\r\n")
+        .await
+        .unwrap();
+    let expected = b"*3\r\n$10\r\nssubscribe\r\n$2\r\nch\r\n:1\r\n";
+    let mut buf = vec![0u8; expected.len()];
+    shard_sub.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, expected);
+
+    // The idle connection is closed once it exceeds the timeout.
+    let mut rest = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), idle.read_to_end(&mut rest))
+        .await
+        .expect("idle client was not closed")
+        .unwrap();
+    assert!(rest.is_empty());
+
+    // The blocked client survived and still gets its reply.
+    run_cmd(vec!["RPUSH", "blist", "v"], &mut conn_ctx, &server_ctx).await;
+    let mut reply = vec![0u8; 64];
+    let n = tokio::time::timeout(Duration::from_secs(2), blocked.read(&mut reply))
+        .await
+        .expect("blocked client got no reply")
+        .unwrap();
+    assert_eq!(&reply[..n], b"*2\r\n$5\r\nblist\r\n$1\r\nv\r\n");
+
+    // So did the shard channel subscriber.
+    run_cmd(vec!["SPUBLISH", "ch", "hi"], &mut conn_ctx, &server_ctx).await;
+    let expected = b"*3\r\n$8\r\nsmessage\r\n$2\r\nch\r\n$2\r\nhi\r\n";
+    let mut buf = vec![0u8; expected.len()];
+    tokio::time::timeout(Duration::from_secs(2), shard_sub.read_exact(&mut buf))
+        .await
+        .expect("shard channel subscriber got no message")
+        .unwrap();
+    assert_eq!(&buf, expected);
+}
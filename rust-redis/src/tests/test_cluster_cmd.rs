@@ -104,6 +104,44 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_cluster_keyslot_vectors_and_hashtags() {
+        use crate::cluster::{crc16, key_hash_slot};
+
+        // CRC16/XMODEM check value, and slots from the Redis documentation
+        assert_eq!(crc16(b"123456789"), 0x31c3);
+        assert_eq!(key_hash_slot(b"somekey"), 11058);
+        assert_eq!(key_hash_slot(b"foo{hash_tag}"), 2515);
+
+        // A non-empty tag is all that is hashed
+        assert_eq!(
+            key_hash_slot(b"{user1000}.following"),
+            key_hash_slot(b"{user1000}.followers")
+        );
+        assert_eq!(
+            key_hash_slot(b"{user1000}.following"),
+            key_hash_slot(b"user1000")
+        );
+        assert_eq!(key_hash_slot(b"foo{bar}{zap}"), key_hash_slot(b"bar"));
+        assert_eq!(key_hash_slot(b"foo{{bar}}zap"), key_hash_slot(b"{bar"));
+        // An empty tag at the first `{` means the whole key is hashed
+        assert_eq!(
+            key_hash_slot(b"foo{}{bar}"),
+            crc16(b"foo{}{bar}") % crate::cluster::CLUSTER_SLOTS as u16
+        );
+        assert_ne!(key_hash_slot(b"foo{}{bar}"), key_hash_slot(b"bar"));
+
+        let server_ctx = create_server_context_with_cluster();
+        let mut conn_ctx = create_connection_context();
+        let res = run_cmd(
+            vec!["CLUSTER", "KEYSLOT", "{user1000}.followers"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        assert_eq!(res, Resp::Integer(key_hash_slot(b"user1000") as i64));
+    }
+
     #[tokio::test]
     async fn test_cluster_meet_forget_and_setslot() {
         let server_ctx = create_server_context_with_cluster();
@@ -249,7 +287,7 @@ mod tests {
                 None,
             )
             .unwrap();
-            let slot = ClusterState::key_slot("moved_key");
+            let slot = crate::cluster::key_hash_slot(b"moved_key");
             st.add_slots(&remote_id, &[slot]).unwrap();
         }
         let mut conn_ctx = create_connection_context();
@@ -284,7 +322,7 @@ mod tests {
         let server_ctx = create_server_context_with_cluster();
         let mut conn_ctx = create_connection_context();
         let key = "migrate_key";
-        let slot = ClusterState::key_slot(key);
+        let slot = crate::cluster::key_hash_slot(key.as_bytes());
         {
             let mut st = server_ctx.cluster_ctx.state.write().unwrap();
             let remote_id = NodeId("1.1.1.1:7001".to_string());
@@ -313,7 +351,7 @@ mod tests {
         let server_ctx = create_server_context_with_cluster();
         let mut conn_ctx = create_connection_context();
         let key = "import_key";
-        let slot = ClusterState::key_slot(key);
+        let slot = crate::cluster::key_hash_slot(key.as_bytes());
         {
             let mut st = server_ctx.cluster_ctx.state.write().unwrap();
             let from_id = NodeId("2.2.2.2:7002".to_string());
@@ -342,7 +380,7 @@ mod tests {
         let server_ctx = create_server_context_with_cluster();
         let mut conn_ctx = create_connection_context();
         let key = "{42}import";
-        let slot = ClusterState::key_slot(key);
+        let slot = crate::cluster::key_hash_slot(key.as_bytes());
         {
             let mut st = server_ctx.cluster_ctx.state.write().unwrap();
             let from_id = NodeId("3.3.3.3:7003".to_string());
@@ -0,0 +1,95 @@
+use crate::cmd::{Command, command_map};
+use crate::resp::Resp;
+use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+use bytes::Bytes;
+
+#[tokio::test]
+async fn test_command_count_matches_dispatch_table() {
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+
+    // Each recognized name is a distinct command, so this is the number of
+    // Command variants minus Unknown
+    let mut variants: Vec<Command> = Vec::new();
+    for cmd in command_map().values() {
+        assert_ne!(*cmd, Command::Unknown);
+        if !variants.contains(cmd) {
+            variants.push(*cmd);
+        }
+    }
+    assert_eq!(variants.len(), command_map().len());
+
+    let res = run_cmd(vec!["COMMAND", "COUNT"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(command_map().len() as i64));
+
+    // Every recognized command has metadata, and unknown names get nil
+    for name in command_map().keys() {
+        let res = run_cmd(vec!["COMMAND", "INFO", name], &mut conn_ctx, &server_ctx).await;
+        match res {
+            Resp::Array(Some(arr)) => {
+                assert!(matches!(arr[0], Resp::Array(Some(_))), "{}", name)
+            }
+            other => panic!("Expected Array for {}, got {:?}", name, other),
+        }
+    }
+    let res = run_cmd(
+        vec!["COMMAND", "INFO", "unknowncmd"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Array(Some(vec![Resp::BulkString(None)])));
+}
+
+#[tokio::test]
+async fn test_command_info_key_specs() {
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+
+    // (first_key, last_key, step) and whether the flags mark movable keys
+    let cases = [
+        ("mset", 1, -1, 2, false),
+        ("get", 1, 1, 1, false),
+        ("zadd", 1, 1, 1, false),
+        ("georadius", 1, 1, 1, true),
+        ("eval", 0, 0, 0, true),
+        ("zunionstore", 0, 0, 0, true),
+        ("xread", 1, 1, 1, true),
+    ];
+    for (name, first, last, step, movable) in cases {
+        let res = run_cmd(vec!["COMMAND", "INFO", name], &mut conn_ctx, &server_ctx).await;
+        let info = match res {
+            Resp::Array(Some(mut arr)) => match arr.remove(0) {
+                Resp::Array(Some(info)) => info,
+                other => panic!("Expected info for {}, got {:?}", name, other),
+            },
+            other => panic!("Expected Array for {}, got {:?}", name, other),
+        };
+        assert_eq!(info[3], Resp::Integer(first), "{}", name);
+        assert_eq!(info[4], Resp::Integer(last), "{}", name);
+        assert_eq!(info[5], Resp::Integer(step), "{}", name);
+        let flags = match &info[2] {
+            Resp::Array(Some(flags)) => flags,
+            other => panic!("Expected flags for {}, got {:?}", name, other),
+        };
+        let has_movable = flags.contains(&Resp::SimpleString(Bytes::from("movablekeys")));
+        assert_eq!(has_movable, movable, "{}", name);
+    }
+
+    // Movable keys are still resolved through the per-command key extraction
+    let res = run_cmd(
+        vec![
+            "COMMAND", "GETKEYS", "EVAL", "return 1", "2", "k1", "k2", "arg",
+        ],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("k1"))),
+            Resp::BulkString(Some(Bytes::from("k2"))),
+        ]))
+    );
+}
@@ -36,6 +36,72 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_copy_is_deep_clone() {
+        let server_ctx = create_server_context();
+        let mut conn_ctx = create_connection_context();
+
+        // (setup, copy, mutate destination, inspect source, expected source)
+        let cases = vec![
+            (
+                vec!["ZADD", "z", "1", "a"],
+                vec!["ZADD", "z2", "2", "b"],
+                vec!["ZCARD", "z"],
+                Resp::Integer(1),
+            ),
+            (
+                vec!["RPUSH", "l", "a"],
+                vec!["RPUSH", "l2", "b"],
+                vec!["LLEN", "l"],
+                Resp::Integer(1),
+            ),
+            (
+                vec!["HSET", "h", "f", "v"],
+                vec!["HSET", "h2", "g", "w"],
+                vec!["HLEN", "h"],
+                Resp::Integer(1),
+            ),
+            (
+                vec!["SADD", "s", "a"],
+                vec!["SADD", "s2", "b"],
+                vec!["SCARD", "s"],
+                Resp::Integer(1),
+            ),
+            (
+                vec!["XADD", "x", "1-1", "f", "v"],
+                vec!["XADD", "x2", "2-1", "f", "v"],
+                vec!["XLEN", "x"],
+                Resp::Integer(1),
+            ),
+        ];
+
+        for (setup, mutate, inspect, expected) in cases {
+            let src = setup[1];
+            let dst = mutate[1];
+            run_cmd(setup, &mut conn_ctx, &server_ctx).await;
+            let res = run_cmd(vec!["COPY", src, dst], &mut conn_ctx, &server_ctx).await;
+            assert_eq!(res, Resp::Integer(1), "COPY {} {}", src, dst);
+            run_cmd(mutate, &mut conn_ctx, &server_ctx).await;
+            assert_eq!(
+                run_cmd(inspect, &mut conn_ctx, &server_ctx).await,
+                expected,
+                "source {} changed after mutating {}",
+                src,
+                dst
+            );
+        }
+
+        // The zset source keeps its exact contents
+        assert_eq!(
+            run_cmd(vec!["ZSCORE", "z", "b"], &mut conn_ctx, &server_ctx).await,
+            Resp::BulkString(None)
+        );
+        assert_eq!(
+            run_cmd(vec!["ZCARD", "z2"], &mut conn_ctx, &server_ctx).await,
+            Resp::Integer(2)
+        );
+    }
+
     #[tokio::test]
     async fn test_object() {
         let server_ctx = create_server_context();
@@ -44,7 +110,7 @@ mod tests {
         run_cmd(vec!["SET", "k1", "v1"], &mut conn_ctx, &server_ctx).await;
 
         let res = run_cmd(vec!["OBJECT", "ENCODING", "k1"], &mut conn_ctx, &server_ctx).await;
-        assert_eq!(res, Resp::BulkString(Some(Bytes::from("raw"))));
+        assert_eq!(res, Resp::BulkString(Some(Bytes::from("embstr"))));
 
         let res = run_cmd(vec!["OBJECT", "IDLETIME", "k1"], &mut conn_ctx, &server_ctx).await;
         match res {
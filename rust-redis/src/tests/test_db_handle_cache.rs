@@ -0,0 +1,71 @@
+use crate::resp::Resp;
+use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+use bytes::Bytes;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_cached_db_follows_select_and_swapdb() {
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+    let mut other = create_connection_context();
+
+    run_cmd(vec!["SET", "k", "v0"], &mut conn, &server_ctx).await;
+    assert_eq!(
+        run_cmd(vec!["GET", "k"], &mut conn, &server_ctx).await,
+        Resp::BulkString(Some(Bytes::from("v0")))
+    );
+    assert!(matches!(conn.db_cache, Some((0, _, _))));
+
+    // SELECT switches maps even with a handle cached
+    run_cmd(vec!["SELECT", "1"], &mut conn, &server_ctx).await;
+    assert_eq!(
+        run_cmd(vec!["GET", "k"], &mut conn, &server_ctx).await,
+        Resp::BulkString(None)
+    );
+    run_cmd(vec!["SET", "k", "v1"], &mut conn, &server_ctx).await;
+    run_cmd(vec!["SELECT", "0"], &mut conn, &server_ctx).await;
+
+    // A SWAPDB from another connection invalidates the cached handle
+    run_cmd(vec!["SWAPDB", "0", "1"], &mut other, &server_ctx).await;
+    assert_eq!(
+        run_cmd(vec!["GET", "k"], &mut conn, &server_ctx).await,
+        Resp::BulkString(Some(Bytes::from("v1")))
+    );
+}
+
+/// A connection reuses its cached db handle, so its commands go ahead while
+/// another command holds the databases write lock, as SWAPDB does briefly and
+/// DEBUG RELOAD does for a whole save and load. Were the handle cloned under
+/// the read lock per command, the GET would only return once the writer let
+/// go.
+#[tokio::test]
+async fn test_cached_db_handle_skips_the_databases_lock() {
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+    run_cmd(vec!["SET", "k", "v"], &mut conn, &server_ctx).await;
+
+    let released = Arc::new(AtomicBool::new(false));
+    let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+    let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+    let writer = {
+        let (server_ctx, released) = (server_ctx.clone(), released.clone());
+        std::thread::spawn(move || {
+            let guard = server_ctx.databases[0].write().unwrap();
+            locked_tx.send(()).unwrap();
+            // Held until the GET is done, or long enough for a GET waiting on
+            // the lock to show up as one
+            let _ = done_rx.recv_timeout(Duration::from_secs(5));
+            released.store(true, Ordering::SeqCst);
+            drop(guard);
+        })
+    };
+    locked_rx.recv().unwrap();
+
+    let res = run_cmd(vec!["GET", "k"], &mut conn, &server_ctx).await;
+    assert!(!released.load(Ordering::SeqCst), "GET waited for the write lock");
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("v"))));
+    done_tx.send(()).unwrap();
+    writer.join().unwrap();
+}
@@ -0,0 +1,40 @@
+use crate::resp::Resp;
+use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+use bytes::Bytes;
+
+#[tokio::test]
+async fn test_db_shards_config() {
+    let path = std::env::temp_dir().join(format!("db-shards-{}.conf", std::process::id()));
+    let load = |text: &str| {
+        std::fs::write(&path, text).unwrap();
+        crate::conf::load_config(path.to_str()).unwrap().db_shards
+    };
+    assert_eq!(load("port 6380\n"), 0);
+    assert_eq!(load("db-shards 64\n"), 64);
+    // DashMap needs a power of two of at least 2
+    assert_eq!(load("db-shards 64\ndb-shards 48\n"), 64);
+    assert_eq!(load("db-shards 1\n"), 0);
+    assert_eq!(load("db-shards many\n"), 0);
+    let _ = std::fs::remove_file(&path);
+
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+    let res = run_cmd(vec!["CONFIG", "GET", "db-shards"], &mut conn, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("db-shards"))),
+            Resp::BulkString(Some(Bytes::from("0"))),
+        ]))
+    );
+    match run_cmd(
+        vec!["CONFIG", "SET", "db-shards", "128"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await
+    {
+        Resp::Error(e) => assert!(e.contains("can't set 'db-shards' at runtime"), "{}", e),
+        other => panic!("expected error, got {:?}", other),
+    }
+}
@@ -0,0 +1,161 @@
+use crate::cmd::ServerContext;
+use crate::conf::Config;
+use crate::resp::Resp;
+use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+use bytes::Bytes;
+use std::sync::Arc;
+
+fn bulk(s: &str) -> Resp {
+    Resp::BulkString(Some(Bytes::from(s.to_string())))
+}
+
+fn server_with_private_rdb(name: &str) -> (ServerContext, std::path::PathBuf) {
+    let path = std::env::temp_dir().join(format!("{}-{}.rdb", name, std::process::id()));
+    let mut cfg = Config::default();
+    cfg.dbfilename = path.to_string_lossy().into_owned();
+    let mut server_ctx = create_server_context();
+    server_ctx.config = Arc::new(cfg);
+    (server_ctx, path)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_debug_reload_nosave_noflush() {
+    let (server_ctx, rdb_path) = server_with_private_rdb("debug-reload-options");
+    let mut conn_ctx = create_connection_context();
+
+    run_cmd(vec!["SET", "k", "saved"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(vec!["SAVE"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+
+    // NOSAVE reloads the file on disk and drops the changes made since
+    run_cmd(vec!["SET", "k", "changed"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["SET", "extra", "1"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(
+        vec!["DEBUG", "RELOAD", "NOSAVE"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let res = run_cmd(vec!["GET", "k"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, bulk("saved"));
+    let res = run_cmd(vec!["EXISTS", "extra"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+
+    // NOFLUSH keeps keys that are not in the file
+    run_cmd(vec!["SET", "extra", "1"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(
+        vec!["DEBUG", "RELOAD", "NOSAVE", "NOFLUSH"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let res = run_cmd(vec!["GET", "extra"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, bulk("1"));
+    let res = run_cmd(vec!["GET", "k"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, bulk("saved"));
+
+    let res = run_cmd(vec!["DEBUG", "RELOAD", "MERGE"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR DEBUG RELOAD only supports the NOFLUSH and NOSAVE options.".to_string())
+    );
+
+    // Without a file NOSAVE fails rather than loading an empty dataset
+    let _ = std::fs::remove_file(&rdb_path);
+    let res = run_cmd(
+        vec!["DEBUG", "RELOAD", "NOSAVE"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert!(
+        matches!(res, Resp::Error(ref e) if e.starts_with("ERR Error trying to load the RDB dump"))
+    );
+    let res = run_cmd(vec!["GET", "k"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, bulk("saved"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_debug_reload_rejects_corrupt_rdb() {
+    let (server_ctx, rdb_path) = server_with_private_rdb("debug-reload-corrupt");
+    let mut conn_ctx = create_connection_context();
+
+    run_cmd(vec!["SET", "k", "v"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(
+        vec!["XADD", "s", "1-1", "f", "v"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    run_cmd(vec!["SAVE"], &mut conn_ctx, &server_ctx).await;
+    let full = std::fs::read(&rdb_path).unwrap();
+
+    let corrupt: Vec<Vec<u8>> = vec![
+        // Truncated at every length, including inside string and stream payloads
+        (0..full.len())
+            .map(|n| full[..n].to_vec())
+            .collect::<Vec<_>>(),
+        vec![b"NOTREDIS0011".to_vec(), b"REDIS9999\xff".to_vec()],
+        // A string claiming a huge length
+        vec![[b"REDIS0011\x00\x01k\x81".as_slice(), &[0xff; 8]].concat()],
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    for bytes in corrupt {
+        std::fs::write(&rdb_path, &bytes).unwrap();
+        let res = run_cmd(
+            vec!["DEBUG", "RELOAD", "NOSAVE"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        match res {
+            Resp::Error(e) => assert!(
+                e.starts_with("ERR Error trying to load the RDB dump"),
+                "{}",
+                e
+            ),
+            other => panic!("{} bytes loaded: {:?}", bytes.len(), other),
+        }
+    }
+
+    // A failed load leaves the dataset alone
+    let res = run_cmd(vec!["GET", "k"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, bulk("v"));
+    let _ = std::fs::remove_file(&rdb_path);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_debug_reload_keeps_functions() {
+    let (server_ctx, rdb_path) = server_with_private_rdb("debug-reload-functions");
+    let mut conn_ctx = create_connection_context();
+
+    let code = "#!lua name=mylib\nredis.register_function('answer', function() return 42 end)";
+    run_cmd(vec!["FUNCTION", "LOAD", code], &mut conn_ctx, &server_ctx).await;
+
+    // The library goes through the RDB file and is the only one afterwards
+    let res = run_cmd(vec!["DEBUG", "RELOAD"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let res = run_cmd(vec!["FCALL", "answer", "0"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(42));
+
+    // Without NOFLUSH a library loaded since the save is dropped
+    let other = "#!lua name=other\nredis.register_function('other', function() return 1 end)";
+    run_cmd(vec!["FUNCTION", "LOAD", other], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(
+        vec!["DEBUG", "RELOAD", "NOSAVE"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    assert_eq!(
+        server_ctx.script_manager.library_codes(),
+        vec![code.to_string()]
+    );
+
+    let _ = std::fs::remove_file(&rdb_path);
+}
@@ -142,3 +142,259 @@ async fn test_dump_restore() {
     //     _ => panic!("Expected Array from COMMAND, got {:?}", resp),
     // }
 }
+
+#[tokio::test]
+async fn test_restore_ttl_handling() {
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+
+    let _ = run_cmd_bytes(
+        vec![Bytes::from("SET"), Bytes::from("src"), Bytes::from("v")],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let serialized = match run_cmd_bytes(
+        vec![Bytes::from("DUMP"), Bytes::from("src")],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await
+    {
+        Resp::BulkString(Some(bytes)) => bytes,
+        other => panic!("Expected BulkString from DUMP, got {:?}", other),
+    };
+    let restore = |key: &str, ttl: &str, opts: &[&str]| {
+        let mut args = vec![
+            Bytes::from("RESTORE"),
+            Bytes::from(key.to_string()),
+            Bytes::from(ttl.to_string()),
+            serialized.clone(),
+        ];
+        args.extend(opts.iter().map(|o| Bytes::from(o.to_string())));
+        args
+    };
+
+    // ttl 0: no expiration
+    let resp = run_cmd_bytes(restore("k0", "0", &[]), &mut conn_ctx, &server_ctx).await;
+    assert_eq!(resp, Resp::SimpleString(Bytes::from("OK")));
+    let resp = run_cmd_bytes(
+        vec![Bytes::from("PTTL"), Bytes::from("k0")],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(resp, Resp::Integer(-1));
+
+    // Negative ttl is rejected and nothing is written
+    let resp = run_cmd_bytes(restore("kneg", "-1", &[]), &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        resp,
+        Resp::Error("ERR Invalid TTL value, must be >= 0".to_string())
+    );
+    let resp = run_cmd_bytes(
+        vec![Bytes::from("EXISTS"), Bytes::from("kneg")],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(resp, Resp::Integer(0));
+
+    // ABSTTL in the past: accepted, but the key is absent
+    let resp = run_cmd_bytes(
+        restore("kpast", "1000", &["ABSTTL"]),
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(resp, Resp::SimpleString(Bytes::from("OK")));
+    let resp = run_cmd_bytes(
+        vec![Bytes::from("EXISTS"), Bytes::from("kpast")],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(resp, Resp::Integer(0));
+
+    // ... and with REPLACE it removes the existing key
+    let resp = run_cmd_bytes(
+        restore("k0", "1000", &["ABSTTL", "REPLACE"]),
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(resp, Resp::SimpleString(Bytes::from("OK")));
+    let resp = run_cmd_bytes(
+        vec![Bytes::from("EXISTS"), Bytes::from("k0")],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(resp, Resp::Integer(0));
+
+    // ABSTTL in the future becomes the absolute expiry
+    let future = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+        + 100_000;
+    let resp = run_cmd_bytes(
+        restore("kfut", &future.to_string(), &["ABSTTL"]),
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(resp, Resp::SimpleString(Bytes::from("OK")));
+    let resp = run_cmd_bytes(
+        vec![Bytes::from("PTTL"), Bytes::from("kfut")],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    match resp {
+        Resp::Integer(ms) => assert!(ms > 90_000 && ms <= 100_000, "pttl {}", ms),
+        other => panic!("Expected Integer from PTTL, got {:?}", other),
+    }
+}
+
+fn args(parts: &[&str]) -> Vec<Bytes> {
+    parts.iter().map(|p| Bytes::from(p.to_string())).collect()
+}
+
+/// DUMPs `key`, RESTOREs it under `<key>-copy` and returns the payload's RDB
+/// type byte.
+async fn dump_into_copy(
+    key: &str,
+    conn_ctx: &mut ConnectionContext,
+    server_ctx: &ServerContext,
+) -> u8 {
+    let payload = match run_cmd_bytes(args(&["DUMP", key]), conn_ctx, server_ctx).await {
+        Resp::BulkString(Some(b)) => b,
+        other => panic!("Expected BulkString from DUMP, got {:?}", other),
+    };
+    let mut restore = args(&["RESTORE", &format!("{}-copy", key), "0"]);
+    restore.push(payload.clone());
+    let resp = run_cmd_bytes(restore, conn_ctx, server_ctx).await;
+    assert_eq!(resp, Resp::SimpleString(Bytes::from("OK")));
+    payload[0]
+}
+
+#[tokio::test]
+async fn test_dump_uses_encoding_specific_types() {
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+
+    // A small all-integer set is dumped as an intset (RDB type 11)
+    let mut sadd = args(&["SADD", "small"]);
+    sadd.extend((0..10).map(|i| Bytes::from((i * 100_000 - 300_000).to_string())));
+    run_cmd_bytes(sadd, &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        dump_into_copy("small", &mut conn_ctx, &server_ctx).await,
+        11
+    );
+    let resp = run_cmd_bytes(
+        args(&["SINTERCARD", "2", "small", "small-copy"]),
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(resp, Resp::Integer(10));
+
+    // Past set-max-intset-entries it falls back to a plain set (RDB type 2)
+    let mut sadd = args(&["SADD", "large"]);
+    sadd.extend((0..600).map(|i| Bytes::from(i.to_string())));
+    run_cmd_bytes(sadd, &mut conn_ctx, &server_ctx).await;
+    assert_eq!(dump_into_copy("large", &mut conn_ctx, &server_ctx).await, 2);
+    let resp = run_cmd_bytes(
+        args(&["SINTERCARD", "2", "large", "large-copy"]),
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(resp, Resp::Integer(600));
+
+    // Small hashes and sorted sets use their listpack types
+    run_cmd_bytes(args(&["HSET", "h", "f", "v"]), &mut conn_ctx, &server_ctx).await;
+    run_cmd_bytes(args(&["HSET", "h", "n", "7"]), &mut conn_ctx, &server_ctx).await;
+    assert_eq!(dump_into_copy("h", &mut conn_ctx, &server_ctx).await, 16);
+    let resp = run_cmd_bytes(args(&["HGET", "h-copy", "n"]), &mut conn_ctx, &server_ctx).await;
+    assert_eq!(resp, Resp::BulkString(Some(Bytes::from("7"))));
+
+    run_cmd_bytes(
+        args(&["ZADD", "z", "1.5", "a", "-2", "b"]),
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(dump_into_copy("z", &mut conn_ctx, &server_ctx).await, 17);
+    let resp = run_cmd_bytes(args(&["ZSCORE", "z-copy", "a"]), &mut conn_ctx, &server_ctx).await;
+    assert_eq!(resp, Resp::BulkString(Some(Bytes::from("1.5"))));
+}
+
+/// A DUMP payload holding a listpack of short strings as RDB type `rdb_type`.
+/// Decoding fails before the checksum is looked at, so that is left zeroed.
+fn listpack_payload(rdb_type: u8, elements: &[&str]) -> Bytes {
+    let mut lp = vec![0u8; 6];
+    for e in elements {
+        lp.push(0x80 | e.len() as u8);
+        lp.extend_from_slice(e.as_bytes());
+        lp.push(1 + e.len() as u8);
+    }
+    lp.push(0xFF);
+    let total = lp.len() as u32;
+    lp[0..4].copy_from_slice(&total.to_le_bytes());
+    lp[4..6].copy_from_slice(&(elements.len() as u16).to_le_bytes());
+
+    let mut payload = vec![rdb_type, lp.len() as u8];
+    payload.extend_from_slice(&lp);
+    payload.extend_from_slice(&10u16.to_le_bytes());
+    payload.extend_from_slice(&[0; 8]);
+    Bytes::from(payload)
+}
+
+#[tokio::test]
+async fn test_restore_rejects_malformed_listpacks() {
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+
+    let malformed = [
+        ("odd-hash", listpack_payload(16, &["f", "v", "dangling"])),
+        ("odd-zset", listpack_payload(17, &["a", "1", "b"])),
+        ("bad-score", listpack_payload(17, &["a", "1", "b", "high"])),
+        ("nan-score", listpack_payload(17, &["a", "nan"])),
+    ];
+    for (key, payload) in malformed {
+        let mut restore = args(&["RESTORE", key, "0"]);
+        restore.push(payload);
+        let resp = run_cmd_bytes(restore, &mut conn_ctx, &server_ctx).await;
+        assert_eq!(
+            resp,
+            Resp::Error("ERR Bad data format".to_string()),
+            "{}",
+            key
+        );
+    }
+
+    // Scores are dumped the way ZSCORE prints them
+    run_cmd_bytes(
+        args(&["ZADD", "z", "1e20", "big", "3", "three"]),
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let payload = match run_cmd_bytes(args(&["DUMP", "z"]), &mut conn_ctx, &server_ctx).await {
+        Resp::BulkString(Some(b)) => b,
+        other => panic!("Expected BulkString from DUMP, got {:?}", other),
+    };
+    let text = String::from_utf8_lossy(&payload);
+    assert!(text.contains("1e+20"), "{:?}", text);
+    assert!(!text.contains("100000000000000000000"), "{:?}", text);
+    assert_eq!(dump_into_copy("z", &mut conn_ctx, &server_ctx).await, 17);
+    let resp = run_cmd_bytes(
+        args(&["ZSCORE", "z-copy", "big"]),
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(resp, Resp::BulkString(Some(Bytes::from("1e+20"))));
+}
@@ -0,0 +1,86 @@
+use crate::cmd::{ConnectionContext, ServerContext};
+use crate::resp::Resp;
+use crate::tests::helper::run_cmd;
+
+async fn assert_gone(key: &str, conn_ctx: &mut ConnectionContext, server_ctx: &ServerContext) {
+    let res = run_cmd(vec!["EXISTS", key], conn_ctx, server_ctx).await;
+    assert_eq!(res, Resp::Integer(0), "{} should have been deleted", key);
+}
+
+#[tokio::test]
+async fn test_list_pops_delete_empty_key() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    run_cmd(vec!["RPUSH", "l1", "a"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["LPOP", "l1"], &mut conn_ctx, &server_ctx).await;
+    assert_gone("l1", &mut conn_ctx, &server_ctx).await;
+
+    run_cmd(vec!["RPUSH", "l2", "a"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["RPOP", "l2"], &mut conn_ctx, &server_ctx).await;
+    assert_gone("l2", &mut conn_ctx, &server_ctx).await;
+
+    run_cmd(vec!["RPUSH", "l3", "a", "a"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["LREM", "l3", "0", "a"], &mut conn_ctx, &server_ctx).await;
+    assert_gone("l3", &mut conn_ctx, &server_ctx).await;
+
+    run_cmd(vec!["RPUSH", "l4", "a"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(vec!["BLPOP", "l4", "1"], &mut conn_ctx, &server_ctx).await;
+    assert!(matches!(res, Resp::Array(Some(_))), "got {:?}", res);
+    assert_gone("l4", &mut conn_ctx, &server_ctx).await;
+
+    run_cmd(vec!["RPUSH", "l5", "a"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(
+        vec!["LMOVE", "l5", "l6", "LEFT", "RIGHT"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_gone("l5", &mut conn_ctx, &server_ctx).await;
+}
+
+#[tokio::test]
+async fn test_hdel_srem_spop_delete_empty_key() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    run_cmd(vec!["HSET", "h", "f", "v"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["HDEL", "h", "f"], &mut conn_ctx, &server_ctx).await;
+    assert_gone("h", &mut conn_ctx, &server_ctx).await;
+
+    run_cmd(vec!["SADD", "s1", "m"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["SREM", "s1", "m"], &mut conn_ctx, &server_ctx).await;
+    assert_gone("s1", &mut conn_ctx, &server_ctx).await;
+
+    run_cmd(vec!["SADD", "s2", "m"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["SPOP", "s2"], &mut conn_ctx, &server_ctx).await;
+    assert_gone("s2", &mut conn_ctx, &server_ctx).await;
+}
+
+#[tokio::test]
+async fn test_zset_removals_delete_empty_key() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    run_cmd(vec!["ZADD", "z1", "1", "m"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["ZREM", "z1", "m"], &mut conn_ctx, &server_ctx).await;
+    assert_gone("z1", &mut conn_ctx, &server_ctx).await;
+
+    run_cmd(vec!["ZADD", "z2", "1", "m"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["ZPOPMIN", "z2"], &mut conn_ctx, &server_ctx).await;
+    assert_gone("z2", &mut conn_ctx, &server_ctx).await;
+
+    run_cmd(
+        vec!["ZADD", "z3", "1", "m", "2", "n"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    run_cmd(vec!["ZPOPMAX", "z3", "5"], &mut conn_ctx, &server_ctx).await;
+    assert_gone("z3", &mut conn_ctx, &server_ctx).await;
+
+    run_cmd(vec!["ZADD", "z4", "1", "m"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(vec!["BZPOPMIN", "z4", "1"], &mut conn_ctx, &server_ctx).await;
+    assert!(matches!(res, Resp::Array(Some(_))), "got {:?}", res);
+    assert_gone("z4", &mut conn_ctx, &server_ctx).await;
+}
@@ -0,0 +1,69 @@
+use crate::cmd::process_frame;
+use crate::resp::Resp;
+use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+use bytes::Bytes;
+
+const HUGE: &str = "9223372036854775807";
+
+#[tokio::test]
+async fn test_expire_overflow_rejected_uniformly() {
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+
+    run_cmd(vec!["SET", "k", "v"], &mut conn, &server_ctx).await;
+
+    let cases: Vec<(Vec<&str>, &str)> = vec![
+        (vec!["SET", "k", "v", "EX", HUGE], "set"),
+        (vec!["SET", "k", "v", "PX", HUGE], "set"),
+        (vec!["SETEX", "k", HUGE, "v"], "setex"),
+        (vec!["PSETEX", "k", HUGE, "v"], "psetex"),
+        (vec!["GETEX", "k", "EX", HUGE], "getex"),
+        (vec!["GETEX", "k", "PX", HUGE], "getex"),
+        (vec!["EXPIRE", "k", HUGE], "expire"),
+        (vec!["PEXPIRE", "k", HUGE], "pexpire"),
+        (vec!["EXPIREAT", "k", HUGE], "expireat"),
+    ];
+    for (cmd, name) in cases {
+        let res = run_cmd(cmd.clone(), &mut conn, &server_ctx).await;
+        assert_eq!(
+            res,
+            Resp::Error(format!("ERR invalid expire time in '{}' command", name)),
+            "{:?}",
+            cmd
+        );
+    }
+
+    // RESTORE checks the TTL once the payload is known to be valid
+    let payload = match run_cmd(vec!["DUMP", "k"], &mut conn, &server_ctx).await {
+        Resp::BulkString(Some(b)) => b,
+        other => panic!("expected a DUMP payload, got {:?}", other),
+    };
+    let req = Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("RESTORE"))),
+        Resp::BulkString(Some(Bytes::from("r"))),
+        Resp::BulkString(Some(Bytes::from(HUGE))),
+        Resp::BulkString(Some(payload)),
+    ]));
+    let (res, _) = process_frame(req, &mut conn, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR invalid expire time in 'restore' command".to_string())
+    );
+
+    // None of the rejected commands touched the key
+    let res = run_cmd(vec!["TTL", "k"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(-1));
+
+    // A non-positive TTL is invalid for SET-style commands...
+    let res = run_cmd(vec!["SET", "k", "v", "EX", "0"], &mut conn, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR invalid expire time in 'set' command".to_string())
+    );
+
+    // ...but EXPIRE with a TTL in the past deletes the key
+    let res = run_cmd(vec!["EXPIRE", "k", "-1"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(1));
+    let res = run_cmd(vec!["EXISTS", "k"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+}
@@ -64,19 +64,14 @@ async fn test_hello() {
     )
     .await;
     match resp {
-        Resp::Array(Some(info)) => {
-            // Check proto is 3
-            for i in 0..info.len() {
-                if let Resp::BulkString(Some(key)) = &info[i] {
-                    if key == &Bytes::from("proto") {
-                        if let Resp::Integer(val) = &info[i + 1] {
-                            assert_eq!(*val, 3);
-                        }
-                    }
-                }
-            }
+        Resp::Map(info) => {
+            // Under RESP3 the reply is a map with proto 3
+            let proto = info
+                .iter()
+                .find(|(key, _)| key == &Resp::BulkString(Some(Bytes::from("proto"))));
+            assert_eq!(proto.map(|(_, val)| val), Some(&Resp::Integer(3)));
         }
-        _ => panic!("Expected Array, got {:?}", resp),
+        _ => panic!("Expected Map, got {:?}", resp),
     }
 
     // HELLO 2 SETNAME myclient
@@ -139,3 +134,67 @@ async fn test_hello() {
         _ => panic!("Expected Error, got {:?}", resp),
     }
 }
+
+#[tokio::test]
+async fn test_hello_failed_auth_applies_nothing() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+    run_cmd(
+        vec!["ACL", "SETUSER", "alice", "on", ">secret", "+@all"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    // SETNAME comes first but AUTH is checked before anything is applied
+    let resp = run_cmd(
+        vec!["HELLO", "3", "SETNAME", "x", "AUTH", "alice", "badpw"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        resp,
+        Resp::Error("WRONGPASS invalid username-password pair".to_string())
+    );
+    assert_eq!(conn_ctx.protocol, 2);
+    let resp = run_cmd(vec!["CLIENT", "GETNAME"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(resp, Resp::BulkString(None));
+
+    let resp = run_cmd(
+        vec!["HELLO", "3", "SETNAME", "x", "AUTH", "alice", "secret"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert!(matches!(resp, Resp::Map(_)), "{:?}", resp);
+    assert_eq!(conn_ctx.protocol, 3);
+    assert_eq!(conn_ctx.current_username, "alice");
+    let resp = run_cmd(vec!["CLIENT", "GETNAME"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(resp, Resp::BulkString(Some(Bytes::from("x"))));
+}
+
+#[tokio::test]
+async fn test_hello_reports_replication_role() {
+    use crate::cmd::ReplicationRole;
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+    let role_of = |resp: Resp| match resp {
+        Resp::Map(info) => info
+            .into_iter()
+            .find(|(key, _)| key == &Resp::BulkString(Some(Bytes::from("role"))))
+            .map(|(_, val)| val),
+        other => panic!("Expected Map, got {:?}", other),
+    };
+
+    let resp = run_cmd(vec!["HELLO", "3"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(role_of(resp), Some(Resp::BulkString(Some(Bytes::from("master")))));
+
+    *server_ctx.repl.replication_role.write().unwrap() = ReplicationRole::Slave;
+    let resp = run_cmd(vec!["HELLO", "3"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(role_of(resp), Some(Resp::BulkString(Some(Bytes::from("replica")))));
+}
@@ -183,3 +183,84 @@ async fn test_hincrbyfloat() {
         _ => panic!("Expected error about value, got {:?}", res),
     }
 }
+
+#[tokio::test]
+async fn test_hincrby_guards() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    // HINCRBY on a missing key creates both the key and the field
+    let res = run_cmd(
+        vec!["HINCRBY", "newhash", "counter", "7"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(7));
+    let res = run_cmd(
+        vec!["HGET", "newhash", "counter"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("7"))));
+
+    // Overflowing i64 errors and leaves the field untouched
+    run_cmd(
+        vec!["HSET", "newhash", "big", "9223372036854775807"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(
+        vec!["HINCRBY", "newhash", "big", "1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    match res {
+        Resp::Error(e) => assert!(e.contains("overflow"), "got {}", e),
+        _ => panic!("Expected overflow error, got {:?}", res),
+    }
+    let res = run_cmd(vec!["HGET", "newhash", "big"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::BulkString(Some(Bytes::from("9223372036854775807")))
+    );
+
+    // A stored infinity is not a float as far as HINCRBYFLOAT is concerned
+    run_cmd(
+        vec!["HSET", "newhash", "inf", "inf"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(
+        vec!["HINCRBYFLOAT", "newhash", "inf", "1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR hash value is not a float".to_string())
+    );
+
+    // Results that overflow to infinity are rejected
+    run_cmd(
+        vec!["HSET", "newhash", "huge", "1.7e308"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(
+        vec!["HINCRBYFLOAT", "newhash", "huge", "1.7e308"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    match res {
+        Resp::Error(e) => assert!(e.contains("NaN or Infinity"), "got {}", e),
+        _ => panic!("Expected NaN/Infinity error, got {:?}", res),
+    }
+}
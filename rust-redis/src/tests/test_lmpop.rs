@@ -0,0 +1,122 @@
+use crate::cmd::process_frame;
+use crate::resp::Resp;
+use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+use bytes::Bytes;
+
+fn bulk(s: &str) -> Resp {
+    Resp::BulkString(Some(Bytes::from(s.to_string())))
+}
+
+fn mpop_reply(key: &str, values: &[&str]) -> Resp {
+    Resp::Array(Some(vec![
+        bulk(key),
+        Resp::Array(Some(values.iter().map(|v| bulk(v)).collect())),
+    ]))
+}
+
+#[tokio::test]
+async fn test_lmpop() {
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+
+    run_cmd(vec!["RPUSH", "l2", "a", "b", "c"], &mut conn, &server_ctx).await;
+
+    // The first non-empty list is the one popped from
+    let res = run_cmd(
+        vec!["LMPOP", "2", "l1", "l2", "LEFT"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, mpop_reply("l2", &["a"]));
+
+    let res = run_cmd(
+        vec!["LMPOP", "2", "l1", "l2", "RIGHT", "COUNT", "5"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, mpop_reply("l2", &["c", "b"]));
+    let res = run_cmd(vec!["EXISTS", "l2"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+
+    let res = run_cmd(
+        vec!["LMPOP", "2", "l1", "l2", "LEFT"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Array(None));
+
+    // Argument validation
+    let res = run_cmd(vec!["LMPOP", "0", "l1", "LEFT"], &mut conn, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR numkeys should be greater than 0".to_string())
+    );
+    let res = run_cmd(vec!["LMPOP", "3", "l1", "LEFT"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Error("ERR syntax error".to_string()));
+    let res = run_cmd(
+        vec!["LMPOP", "1", "l1", "LEFT", "COUNT", "0"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR count should be greater than 0".to_string())
+    );
+
+    run_cmd(vec!["SET", "str", "v"], &mut conn, &server_ctx).await;
+    match run_cmd(vec!["LMPOP", "1", "str", "LEFT"], &mut conn, &server_ctx).await {
+        Resp::Error(e) => assert!(e.starts_with("WRONGTYPE"), "{}", e),
+        other => panic!("expected WRONGTYPE, got {:?}", other),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_blmpop() {
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+
+    // Served immediately when a list has data
+    run_cmd(vec!["RPUSH", "b2", "x", "y"], &mut conn, &server_ctx).await;
+    let res = run_cmd(
+        vec!["BLMPOP", "0", "2", "b1", "b2", "LEFT", "COUNT", "2"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, mpop_reply("b2", &["x", "y"]));
+
+    // Times out with a nil reply
+    let start = std::time::Instant::now();
+    let res = run_cmd(
+        vec!["BLMPOP", "0.1", "1", "b1", "LEFT"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    assert!(start.elapsed().as_millis() >= 100);
+    assert_eq!(res, Resp::Array(None));
+
+    // Woken by a push. The element never reached the list, so neither the
+    // push nor the pop propagates it
+    let pusher_ctx = server_ctx.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let mut conn = create_connection_context();
+        run_cmd(vec!["RPUSH", "b1", "z"], &mut conn, &pusher_ctx).await;
+    });
+    let req = Resp::Array(Some(vec![
+        bulk("BLMPOP"),
+        bulk("5"),
+        bulk("2"),
+        bulk("b1"),
+        bulk("b2"),
+        bulk("RIGHT"),
+    ]));
+    let (res, log) = process_frame(req, &mut conn, &server_ctx).await;
+    assert_eq!(res, mpop_reply("b1", &["z"]));
+    assert_eq!(log, None);
+}
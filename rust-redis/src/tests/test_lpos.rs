@@ -167,3 +167,59 @@ async fn test_lpos() {
         _ => panic!("Expected WRONGTYPE, got {:?}", res),
     }
 }
+
+#[tokio::test]
+async fn test_lpos_maxlen_bounds_search() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    // A single match in the middle of a long list
+    let mut rpush = vec!["RPUSH".to_string(), "big".to_string()];
+    rpush.extend((0..1000).map(|i| if i == 500 { "needle" } else { "hay" }.to_string()));
+    run_cmd(
+        rpush.iter().map(|s| s.as_str()).collect(),
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    let res = run_cmd(
+        vec!["LPOS", "big", "needle", "MAXLEN", "500"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::BulkString(None));
+    let res = run_cmd(
+        vec!["LPOS", "big", "needle", "MAXLEN", "501"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(500));
+
+    // From the tail the needle is the 500th element compared
+    let res = run_cmd(
+        vec!["LPOS", "big", "needle", "RANK", "-1", "MAXLEN", "499"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::BulkString(None));
+    let res = run_cmd(
+        vec!["LPOS", "big", "needle", "RANK", "-1", "MAXLEN", "500"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(500));
+
+    // MAXLEN 0 means the whole list
+    let res = run_cmd(
+        vec!["LPOS", "big", "needle", "COUNT", "0", "MAXLEN", "0"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Array(Some(vec![Resp::Integer(500)])));
+}
@@ -133,3 +133,43 @@ async fn test_lrem() {
         _ => panic!("Expected Array"),
     }
 }
+
+#[tokio::test]
+async fn test_lrem_count_stops_early() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let mut rpush = vec!["RPUSH".to_string(), "big".to_string()];
+    rpush.extend((0..10_000).map(|_| "x".to_string()));
+    run_cmd(
+        rpush.iter().map(|s| s.as_str()).collect(),
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    // Only the first two matches from the head go, the rest are untouched
+    let res = run_cmd(vec!["LREM", "big", "2", "x"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(2));
+    let res = run_cmd(vec!["LLEN", "big"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(9_998));
+
+    // A negative count works from the tail
+    run_cmd(
+        vec!["RPUSH", "l", "a", "b", "a", "c", "a"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(vec!["LREM", "l", "-2", "a"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(2));
+    let res = run_cmd(vec!["LRANGE", "l", "0", "-1"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("a"))),
+            Resp::BulkString(Some(Bytes::from("b"))),
+            Resp::BulkString(Some(Bytes::from("c"))),
+        ]))
+    );
+}
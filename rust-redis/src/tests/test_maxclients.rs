@@ -0,0 +1,58 @@
+use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+use crate::resp::Resp;
+use bytes::Bytes;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn ping(stream: &mut TcpStream) -> Vec<u8> {
+    stream.write_all(b"*1\r\n$4\r\nPING\r\n").await.unwrap();
+    let mut buf = vec![0u8; 64];
+    let n = stream.read(&mut buf).await.unwrap();
+    buf.truncate(n);
+    buf
+}
+
+#[tokio::test]
+async fn test_maxclients_rejects_extra_connection() {
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+
+    let res = run_cmd(vec!["CONFIG", "SET", "maxclients", "2"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let res = run_cmd(vec!["CONFIG", "GET", "maxclients"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("maxclients"))),
+            Resp::BulkString(Some(Bytes::from("2"))),
+        ]))
+    );
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(crate::serve(listener, server_ctx.clone()));
+
+    let mut c1 = TcpStream::connect(addr).await.unwrap();
+    assert_eq!(ping(&mut c1).await, b"+PONG\r\n");
+    let mut c2 = TcpStream::connect(addr).await.unwrap();
+    assert_eq!(ping(&mut c2).await, b"+PONG\r\n");
+
+    let mut c3 = TcpStream::connect(addr).await.unwrap();
+    let mut buf = Vec::new();
+    tokio::time::timeout(std::time::Duration::from_secs(2), c3.read_to_end(&mut buf))
+        .await
+        .expect("rejected connection was not closed")
+        .unwrap();
+    assert_eq!(buf, b"-ERR max number of clients reached\r\n");
+
+    // Freeing a slot lets a new client in again.
+    drop(c1);
+    for _ in 0..50 {
+        if server_ctx.clients_ctx.client_count.load(std::sync::atomic::Ordering::Relaxed) < 2 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    let mut c4 = TcpStream::connect(addr).await.unwrap();
+    assert_eq!(ping(&mut c4).await, b"+PONG\r\n");
+}
@@ -52,4 +52,97 @@ mod tests {
             _ => panic!("Expected Array, got {:?}", res),
         }
     }
+
+    #[tokio::test]
+    async fn test_stream_usage_counts_consumer_groups() {
+        let server_ctx = create_server_context();
+        let mut conn_ctx = create_connection_context();
+
+        for key in ["plain", "grouped"] {
+            for i in 1..=200 {
+                let id = format!("{}-0", i);
+                run_cmd(
+                    vec!["XADD", key, &id, "field", "value"],
+                    &mut conn_ctx,
+                    &server_ctx,
+                )
+                .await;
+            }
+        }
+        run_cmd(
+            vec!["XGROUP", "CREATE", "grouped", "workers", "0"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        run_cmd(
+            vec![
+                "XREADGROUP",
+                "GROUP",
+                "workers",
+                "worker-1",
+                "STREAMS",
+                "grouped",
+                ">",
+            ],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+
+        let mut usage = async |key: &str| match run_cmd(
+            vec!["MEMORY", "USAGE", key],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await
+        {
+            Resp::Integer(n) => n,
+            other => panic!("Expected Integer, got {:?}", other),
+        };
+        let (plain, grouped) = (usage("plain").await, usage("grouped").await);
+        assert!(
+            grouped > plain * 3 / 2,
+            "{} with a PEL vs {}",
+            grouped,
+            plain
+        );
+
+        let mut serialized_length = async |key: &str| match run_cmd(
+            vec!["DEBUG", "OBJECT", key],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await
+        {
+            Resp::SimpleString(s) => {
+                let s = String::from_utf8(s.to_vec()).unwrap();
+                assert!(s.contains(" encoding:stream "), "{}", s);
+                s.split(' ')
+                    .find_map(|f| f.strip_prefix("serializedlength:"))
+                    .unwrap()
+                    .parse::<usize>()
+                    .unwrap()
+            }
+            other => panic!("Expected SimpleString, got {:?}", other),
+        };
+        let (plain, grouped) = (
+            serialized_length("plain").await,
+            serialized_length("grouped").await,
+        );
+        assert!(
+            grouped > plain * 3 / 2,
+            "{} with a PEL vs {}",
+            grouped,
+            plain
+        );
+
+        let res = run_cmd(
+            vec!["DEBUG", "OBJECT", "missing"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        assert_eq!(res, Resp::Error("ERR no such key".to_string()));
+    }
 }
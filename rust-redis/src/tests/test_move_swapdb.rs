@@ -13,24 +13,27 @@ async fn test_move() {
     }
     let server_ctx = crate::cmd::ServerContext {
         databases: std::sync::Arc::new(dbs),
+        db_epoch: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0)),
         acl: server_ctx.acl.clone(),
         aof: None,
         config: std::sync::Arc::new(cfg),
         script_manager: server_ctx.script_manager.clone(),
         blocking_waiters: std::sync::Arc::new(dashmap::DashMap::new()),
         blocking_zset_waiters: std::sync::Arc::new(dashmap::DashMap::new()),
+        stream_waiters: std::sync::Arc::new(dashmap::DashMap::new()),
         pubsub: std::sync::Arc::new(crate::cmd::PubSubCtx::new()),
         repl: std::sync::Arc::new(crate::cmd::ReplicationCtx::new(
             "test".to_string(), 1024, 1, 60, true, 0, 10, false, 5,
         )),
         start_time: std::time::Instant::now(),
-        clients_ctx: std::sync::Arc::new(crate::cmd::ClientCtx::new()),
+        clients_ctx: std::sync::Arc::new(crate::cmd::ClientCtx::new(10000, 0, 300)),
         slowlog: std::sync::Arc::new(crate::cmd::SlowLogCtx::new(128, 10_000)),
         mem: std::sync::Arc::new(crate::cmd::MemoryCtx::new(
             0,
             crate::conf::EvictionPolicy::NoEviction,
             5,
             0,
+            crate::conf::EncodingLimits::default(),
         )),
         persist: std::sync::Arc::new(crate::cmd::PersistenceCtx::new(
             true, true, true,
@@ -75,24 +78,29 @@ async fn test_swapdb() {
     }
     let server_ctx = crate::cmd::ServerContext {
         databases: std::sync::Arc::new(dbs),
+        db_epoch: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0)),
         acl: std::sync::Arc::new(arc_swap::ArcSwap::from_pointee(crate::acl::Acl::new())),
         aof: None,
         config: std::sync::Arc::new(cfg),
-        script_manager: crate::cmd::scripting::create_script_manager(),
+        script_manager: crate::cmd::scripting::create_script_manager(
+            crate::conf::Config::default().lua_time_limit,
+        ),
         blocking_waiters: std::sync::Arc::new(dashmap::DashMap::new()),
         blocking_zset_waiters: std::sync::Arc::new(dashmap::DashMap::new()),
+        stream_waiters: std::sync::Arc::new(dashmap::DashMap::new()),
         pubsub: std::sync::Arc::new(crate::cmd::PubSubCtx::new()),
         repl: std::sync::Arc::new(crate::cmd::ReplicationCtx::new(
             "test".to_string(), 1024, 1, 60, true, 0, 10, false, 5,
         )),
         start_time: std::time::Instant::now(),
-        clients_ctx: std::sync::Arc::new(crate::cmd::ClientCtx::new()),
+        clients_ctx: std::sync::Arc::new(crate::cmd::ClientCtx::new(10000, 0, 300)),
         slowlog: std::sync::Arc::new(crate::cmd::SlowLogCtx::new(128, 10_000)),
         mem: std::sync::Arc::new(crate::cmd::MemoryCtx::new(
             0,
             crate::conf::EvictionPolicy::NoEviction,
             5,
             0,
+            crate::conf::EncodingLimits::default(),
         )),
         persist: std::sync::Arc::new(crate::cmd::PersistenceCtx::new(
             true, true, true,
@@ -132,3 +140,91 @@ async fn test_swapdb() {
     let res = run_cmd(vec!["GET", "db0_key"], &mut conn, &server_ctx).await;
     assert_eq!(res, Resp::BulkString(Some(Bytes::from("val0"))));
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_swapdb_validation_and_concurrency() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn = crate::tests::helper::create_connection_context();
+
+    for key in ["a", "b", "c"] {
+        run_cmd(vec!["SET", key, "0"], &mut conn, &server_ctx).await;
+    }
+
+    let res = run_cmd(vec!["SWAPDB", "0", "16"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Error("ERR DB index is out of range".to_string()));
+    let res = run_cmd(vec!["SWAPDB", "0", "0"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    assert_eq!(server_ctx.databases[0].read().unwrap().len(), 3);
+
+    // Opposite-order swaps racing each other must not deadlock
+    let mut handles = Vec::new();
+    for i in 0..8 {
+        let ctx = server_ctx.clone();
+        handles.push(tokio::spawn(async move {
+            let mut conn = crate::tests::helper::create_connection_context();
+            let args = if i % 2 == 0 {
+                ["SWAPDB", "0", "1"]
+            } else {
+                ["SWAPDB", "1", "0"]
+            };
+            for _ in 0..100 {
+                run_cmd(args.to_vec(), &mut conn, &ctx).await;
+            }
+        }));
+    }
+    for h in handles {
+        h.await.unwrap();
+    }
+
+    // 800 swaps is an even number, so every key is back in db 0, moved wholesale
+    assert_eq!(server_ctx.databases[0].read().unwrap().len(), 3);
+    assert_eq!(server_ctx.databases[1].read().unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn test_move_collision_same_db_and_ttl() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn = crate::tests::helper::create_connection_context();
+
+    // Missing source key
+    let res = run_cmd(vec!["MOVE", "nokey", "1"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+
+    // Same database
+    run_cmd(vec!["SET", "k", "src"], &mut conn, &server_ctx).await;
+    let res = run_cmd(vec!["MOVE", "k", "0"], &mut conn, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR source and destination objects are the same".to_string())
+    );
+
+    // Destination already holds the key: nothing moves
+    conn.db_index = 1;
+    run_cmd(vec!["SET", "k", "dst"], &mut conn, &server_ctx).await;
+    conn.db_index = 0;
+    let res = run_cmd(vec!["MOVE", "k", "1"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+    let res = run_cmd(vec!["GET", "k"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("src"))));
+    conn.db_index = 1;
+    let res = run_cmd(vec!["GET", "k"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("dst"))));
+
+    // A successful move carries the TTL along
+    conn.db_index = 0;
+    run_cmd(
+        vec!["SET", "ttl_key", "v", "EX", "100"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(vec!["MOVE", "ttl_key", "2"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(1));
+    let res = run_cmd(vec!["EXISTS", "ttl_key"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+    conn.db_index = 2;
+    match run_cmd(vec!["TTL", "ttl_key"], &mut conn, &server_ctx).await {
+        Resp::Integer(ttl) => assert!(ttl > 0 && ttl <= 100, "ttl = {}", ttl),
+        other => panic!("expected integer TTL, got {:?}", other),
+    }
+}
@@ -109,3 +109,242 @@ async fn test_keyevent_notifications() {
         panic!("Unexpected notification format: {:?}", msg);
     }
 }
+
+#[tokio::test]
+async fn test_del_notifies_only_removed_keys() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let req = Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("CONFIG"))),
+        Resp::BulkString(Some(Bytes::from("SET"))),
+        Resp::BulkString(Some(Bytes::from("notify-keyspace-events"))),
+        Resp::BulkString(Some(Bytes::from("Eg"))),
+    ]));
+    let (res, _) = process_frame(req, &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+
+    let (tx, mut rx) = mpsc::channel(32);
+    let mut sub_ctx = ConnectionContext::new(1, None, Some(tx), None);
+    sub_ctx.authenticated = true;
+    let sub_req = Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("SUBSCRIBE"))),
+        Resp::BulkString(Some(Bytes::from("__keyevent@0__:del"))),
+    ]));
+    process_frame(sub_req, &mut sub_ctx, &server_ctx).await;
+
+    for key in ["k1", "k3"] {
+        let set_req = Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("SET"))),
+            Resp::BulkString(Some(Bytes::from(key))),
+            Resp::BulkString(Some(Bytes::from("v"))),
+        ]));
+        process_frame(set_req, &mut conn_ctx, &server_ctx).await;
+    }
+
+    // DEL with one missing key removes and reports only the existing one
+    let del_req = Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("DEL"))),
+        Resp::BulkString(Some(Bytes::from("k1"))),
+        Resp::BulkString(Some(Bytes::from("k2"))),
+    ]));
+    let (del_res, _) = process_frame(del_req, &mut conn_ctx, &server_ctx).await;
+    assert_eq!(del_res, Resp::Integer(1));
+
+    // UNLINK fires the same `del` event
+    let unlink_req = Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("UNLINK"))),
+        Resp::BulkString(Some(Bytes::from("k2"))),
+        Resp::BulkString(Some(Bytes::from("k3"))),
+    ]));
+    let (unlink_res, _) = process_frame(unlink_req, &mut conn_ctx, &server_ctx).await;
+    assert_eq!(unlink_res, Resp::Integer(1));
+
+    let mut received = Vec::new();
+    while let Ok(msg) = rx.try_recv() {
+        if let Resp::Array(Some(items)) = msg {
+            received.push(items[2].clone());
+        }
+    }
+    assert_eq!(
+        received,
+        vec![
+            Resp::BulkString(Some(Bytes::from("k1"))),
+            Resp::BulkString(Some(Bytes::from("k3"))),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_getdel_notifies_only_on_removal() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let req = Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("CONFIG"))),
+        Resp::BulkString(Some(Bytes::from("SET"))),
+        Resp::BulkString(Some(Bytes::from("notify-keyspace-events"))),
+        Resp::BulkString(Some(Bytes::from("Eg"))),
+    ]));
+    let (res, _) = process_frame(req, &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+
+    let (tx, mut rx) = mpsc::channel(32);
+    let mut sub_ctx = ConnectionContext::new(1, None, Some(tx), None);
+    sub_ctx.authenticated = true;
+    let sub_req = Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("SUBSCRIBE"))),
+        Resp::BulkString(Some(Bytes::from("__keyevent@0__:del"))),
+    ]));
+    process_frame(sub_req, &mut sub_ctx, &server_ctx).await;
+
+    let set_req = Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("SET"))),
+        Resp::BulkString(Some(Bytes::from("gd"))),
+        Resp::BulkString(Some(Bytes::from("v"))),
+    ]));
+    process_frame(set_req, &mut conn_ctx, &server_ctx).await;
+
+    // The first GETDEL removes the key, the second finds nothing
+    for _ in 0..2 {
+        let getdel_req = Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("GETDEL"))),
+            Resp::BulkString(Some(Bytes::from("gd"))),
+        ]));
+        process_frame(getdel_req, &mut conn_ctx, &server_ctx).await;
+    }
+
+    let mut received = Vec::new();
+    while let Ok(msg) = rx.try_recv() {
+        if let Resp::Array(Some(items)) = msg {
+            received.push(items[2].clone());
+        }
+    }
+    assert_eq!(received, vec![Resp::BulkString(Some(Bytes::from("gd")))]);
+}
+
+#[tokio::test]
+async fn test_zadd_update_notifies_and_bumps_dirty() {
+    use crate::tests::helper::run_cmd;
+    use std::sync::atomic::Ordering;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    run_cmd(
+        vec!["CONFIG", "SET", "notify-keyspace-events", "Ez"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    let (tx, mut rx) = mpsc::channel(32);
+    let mut sub_ctx = ConnectionContext::new(1, None, Some(tx), None);
+    sub_ctx.authenticated = true;
+    run_cmd(
+        vec!["SUBSCRIBE", "__keyevent@0__:zadd"],
+        &mut sub_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    let res = run_cmd(vec!["ZADD", "z", "1", "m"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(1));
+    assert!(rx.try_recv().is_ok());
+
+    // Re-scoring replies 0 without CH but is still a modification
+    let before = server_ctx.persist.dirty.load(Ordering::Relaxed);
+    let res = run_cmd(vec!["ZADD", "z", "2", "m"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+    assert_eq!(server_ctx.persist.dirty.load(Ordering::Relaxed), before + 1);
+    match rx.try_recv() {
+        Ok(Resp::Array(Some(items))) => {
+            assert_eq!(items[2], Resp::BulkString(Some(Bytes::from("z"))))
+        }
+        other => panic!("Expected zadd event, got {:?}", other),
+    }
+
+    // CH changes only the reply, not the accounting
+    let before = server_ctx.persist.dirty.load(Ordering::Relaxed);
+    let res = run_cmd(
+        vec!["ZADD", "z", "CH", "3", "m", "1", "n"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(2));
+    assert_eq!(server_ctx.persist.dirty.load(Ordering::Relaxed), before + 2);
+    assert!(rx.try_recv().is_ok());
+
+    // Same score again: nothing changed, nothing fired
+    let before = server_ctx.persist.dirty.load(Ordering::Relaxed);
+    let res = run_cmd(vec!["ZADD", "z", "3", "m"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+    assert_eq!(server_ctx.persist.dirty.load(Ordering::Relaxed), before);
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_flushall_is_a_single_bulk_operation() {
+    use crate::tests::helper::run_cmd;
+    use std::sync::atomic::Ordering;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    for i in 0..50 {
+        let key = format!("k{}", i);
+        run_cmd(
+            vec!["SET", key.as_str(), "v", "EX", "100"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+    }
+    conn_ctx.db_index = 1;
+    run_cmd(vec!["SET", "other", "v"], &mut conn_ctx, &server_ctx).await;
+    conn_ctx.db_index = 0;
+
+    run_cmd(
+        vec!["CONFIG", "SET", "notify-keyspace-events", "KEA"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let (tx, mut rx) = mpsc::channel(128);
+    let mut sub_ctx = ConnectionContext::new(1, None, Some(tx), None);
+    sub_ctx.authenticated = true;
+    run_cmd(vec!["PSUBSCRIBE", "__key*__:*"], &mut sub_ctx, &server_ctx).await;
+    while rx.try_recv().is_ok() {}
+
+    let before = server_ctx.persist.dirty.load(Ordering::Relaxed);
+    let req = Resp::Array(Some(vec![Resp::BulkString(Some(Bytes::from("FLUSHALL")))]));
+    let (res, log) = process_frame(req, &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+
+    // Propagated once, as the command itself
+    assert_eq!(
+        log,
+        Some(Resp::Array(Some(vec![Resp::BulkString(Some(
+            Bytes::from("FLUSHALL")
+        ))])))
+    );
+    // Every removed key counts towards the dirty counter
+    assert_eq!(
+        server_ctx.persist.dirty.load(Ordering::Relaxed),
+        before + 51
+    );
+    // No per-key del events
+    assert!(rx.try_recv().is_err());
+
+    for db in server_ctx.databases.iter() {
+        assert_eq!(db.read().unwrap().len(), 0);
+    }
+    let res = run_cmd(vec!["DBSIZE"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+
+    let res = run_cmd(vec!["FLUSHDB", "LAZY"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Error("ERR syntax error".to_string()));
+    let res = run_cmd(vec!["FLUSHDB", "async"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+}
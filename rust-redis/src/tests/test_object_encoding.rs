@@ -0,0 +1,317 @@
+use crate::cmd::ServerContext;
+use crate::conf::Config;
+use crate::resp::Resp;
+use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+use bytes::Bytes;
+use std::sync::Arc;
+
+fn bulk(s: &str) -> Resp {
+    Resp::BulkString(Some(Bytes::from(s.to_string())))
+}
+
+/// A server context whose RDB file is private to the test, so DEBUG RELOAD
+/// does not race other tests over ./dump.rdb.
+fn server_with_private_rdb(name: &str) -> (ServerContext, std::path::PathBuf) {
+    let path = std::env::temp_dir().join(format!("{}-{}.rdb", name, std::process::id()));
+    let mut cfg = Config::default();
+    cfg.dbfilename = path.to_string_lossy().into_owned();
+    let mut server_ctx = create_server_context();
+    server_ctx.config = Arc::new(cfg);
+    (server_ctx, path)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_intset_survives_debug_reload() {
+    let (server_ctx, rdb_path) = server_with_private_rdb("object-encoding-reload");
+    let mut conn_ctx = create_connection_context();
+
+    run_cmd(
+        vec!["SADD", "ints", "1", "2", "3", "-40"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    run_cmd(vec!["SADD", "mixed", "1", "a"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["HSET", "h", "f", "v"], &mut conn_ctx, &server_ctx).await;
+    run_cmd(vec!["ZADD", "z", "1", "m"], &mut conn_ctx, &server_ctx).await;
+
+    let expected = [
+        ("ints", "intset"),
+        ("mixed", "listpack"),
+        ("h", "listpack"),
+        ("z", "listpack"),
+    ];
+    for (key, enc) in expected {
+        let res = run_cmd(vec!["OBJECT", "ENCODING", key], &mut conn_ctx, &server_ctx).await;
+        assert_eq!(res, bulk(enc), "{}", key);
+    }
+
+    let res = run_cmd(vec!["DEBUG", "RELOAD"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    // The reload went through the RDB file on disk
+    assert!(rdb_path.exists());
+
+    for (key, enc) in expected {
+        let res = run_cmd(vec!["OBJECT", "ENCODING", key], &mut conn_ctx, &server_ctx).await;
+        assert_eq!(res, bulk(enc), "{} after reload", key);
+    }
+    let res = run_cmd(vec!["SCARD", "ints"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(4));
+    let _ = std::fs::remove_file(&rdb_path);
+}
+
+#[tokio::test]
+async fn test_encoding_thresholds() {
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+
+    // Non-canonical integers are not intset members
+    run_cmd(vec!["SADD", "s", "1", "01"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(vec!["OBJECT", "ENCODING", "s"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, bulk("listpack"));
+
+    // A value over 64 bytes needs the full encoding
+    let long = "x".repeat(65);
+    run_cmd(
+        vec!["HSET", "h", "f", long.as_str()],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(vec!["OBJECT", "ENCODING", "h"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, bulk("hashtable"));
+    run_cmd(
+        vec!["ZADD", "z", "1", long.as_str()],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(vec!["OBJECT", "ENCODING", "z"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, bulk("skiplist"));
+
+    // So do more than 128 entries (or 512 for an intset)
+    let members: Vec<String> = (0..129).map(|i| format!("m{}", i)).collect();
+    let mut cmd = vec!["SADD", "big"];
+    cmd.extend(members.iter().map(|m| m.as_str()));
+    run_cmd(cmd, &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(
+        vec!["OBJECT", "ENCODING", "big"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("hashtable"));
+
+    let ints: Vec<String> = (0..513).map(|i| i.to_string()).collect();
+    let mut cmd = vec!["SADD", "bigints"];
+    cmd.extend(ints.iter().map(|m| m.as_str()));
+    run_cmd(cmd, &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(
+        vec!["OBJECT", "ENCODING", "bigints"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("hashtable"));
+}
+
+#[tokio::test]
+async fn test_string_encodings() {
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+
+    let long = "x".repeat(45);
+    let cases = [
+        ("123", "int"),
+        ("-42", "int"),
+        ("1234567890123", "int"),
+        ("007", "embstr"),
+        ("+5", "embstr"),
+        ("99999999999999999999", "embstr"),
+        ("hello", "embstr"),
+        (long.as_str(), "raw"),
+    ];
+    for (value, enc) in cases {
+        run_cmd(vec!["SET", "k", value], &mut conn_ctx, &server_ctx).await;
+        let res = run_cmd(vec!["OBJECT", "ENCODING", "k"], &mut conn_ctx, &server_ctx).await;
+        assert_eq!(res, bulk(enc), "{}", value);
+    }
+}
+
+#[tokio::test]
+async fn test_encoding_follows_config_set_limits() {
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+
+    run_cmd(
+        vec!["SADD", "ints", "1", "2", "3"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    run_cmd(vec!["HSET", "h", "f", "value"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(
+        vec!["OBJECT", "ENCODING", "ints"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("intset"));
+
+    let res = run_cmd(
+        vec!["CONFIG", "SET", "set-max-intset-entries", "2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let res = run_cmd(
+        vec!["CONFIG", "GET", "set-max-intset-entries"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![bulk("set-max-intset-entries"), bulk("2")]))
+    );
+    let res = run_cmd(
+        vec!["OBJECT", "ENCODING", "ints"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("listpack"));
+
+    run_cmd(
+        vec!["CONFIG", "SET", "set-max-listpack-entries", "2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(
+        vec!["OBJECT", "ENCODING", "ints"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("hashtable"));
+
+    // Value length limits apply to fields and values alike
+    run_cmd(
+        vec!["CONFIG", "SET", "hash-max-listpack-value", "4"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(vec!["OBJECT", "ENCODING", "h"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, bulk("hashtable"));
+
+    let res = run_cmd(
+        vec!["CONFIG", "SET", "zset-max-listpack-entries", "many"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR value is not an integer or out of range".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_list_encoding() {
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+
+    run_cmd(
+        vec!["RPUSH", "l", "a", "b", "c"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(vec!["OBJECT", "ENCODING", "l"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, bulk("listpack"));
+
+    // The default -2 allows one 8 KB node
+    let big = "x".repeat(9000);
+    run_cmd(
+        vec!["RPUSH", "big", big.as_str()],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(
+        vec!["OBJECT", "ENCODING", "big"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("quicklist"));
+
+    // A positive size counts entries
+    let res = run_cmd(
+        vec!["CONFIG", "SET", "list-max-listpack-size", "2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let res = run_cmd(vec!["OBJECT", "ENCODING", "l"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, bulk("quicklist"));
+    let res = run_cmd(
+        vec!["CONFIG", "GET", "list-max-listpack-size"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![bulk("list-max-listpack-size"), bulk("2")]))
+    );
+
+    let res = run_cmd(
+        vec!["CONFIG", "SET", "list-max-listpack-size", "-6"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert!(matches!(res, Resp::Error(_)), "{:?}", res);
+}
+
+#[tokio::test]
+async fn test_zset_encoding_checks_longest_member() {
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+
+    run_cmd(
+        vec!["ZADD", "short", "1", "a", "2", "b"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(
+        vec!["OBJECT", "ENCODING", "short"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("listpack"));
+
+    // Two members are well under the entry limit, but the second one is
+    // longer than zset-max-listpack-value
+    let long = "y".repeat(100);
+    run_cmd(
+        vec!["ZADD", "long", "1", "a", "2", long.as_str()],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(
+        vec!["OBJECT", "ENCODING", "long"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("skiplist"));
+}
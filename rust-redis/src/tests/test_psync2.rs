@@ -1,3 +1,4 @@
+use crate::cmd::replication::propagate;
 use crate::resp::Resp;
 use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
 use bytes::Bytes;
@@ -13,15 +14,11 @@ async fn test_psync2_transition() {
         let mut run_id = ctx.repl.run_id.write().unwrap();
         *run_id = "1111111111111111111111111111111111111111".to_string();
     }
-    ctx.repl.repl_offset.store(100, Ordering::Relaxed);
-    {
-        // Populate backlog
-        let mut q = ctx.repl.repl_backlog.lock().await;
-        // Add some dummy frames from offset 50 to 100
-        for i in 50..=100 {
-            q.push_back((i, Resp::SimpleString(Bytes::from("PING"))));
-        }
+    // Populate the backlog with 50 writes of 7 bytes each ("+PING\r\n")
+    for _ in 0..50 {
+        propagate(&ctx, Resp::SimpleString(Bytes::from("PING"))).await;
     }
+    assert_eq!(ctx.repl.repl_offset.load(Ordering::Relaxed), 350);
 
     // 2. Promote to Master (Shift ID)
     let res = run_cmd(vec!["REPLICAOF", "NO", "ONE"], &mut conn_ctx, &ctx).await;
@@ -35,26 +32,35 @@ async fn test_psync2_transition() {
 
     assert_ne!(new_id, old_id);
     assert_eq!(replid2, old_id);
-    // repl_offset was 100, so second_off should be 101
-    assert_eq!(second_off, 101);
+    // repl_offset was 350, so second_off should be 351
+    assert_eq!(second_off, 351);
 
     // 4. Simulate a Slave asking for old ID
     // Case A: Valid PSYNC2 request (offset inside backlog and <= second_off)
-    // Offset 80 is in backlog (50..100) and <= 101.
-    let res = run_cmd(vec!["PSYNC", &old_id, "80"], &mut conn_ctx, &ctx).await;
+    // Offset 210 ends the 30th write, leaving 20 to resend.
+    // The CONTINUE line and the backlog are queued on the connection itself
+    let (tx, mut rx) = tokio::sync::mpsc::channel(100);
+    conn_ctx.msg_sender = Some(tx);
+    let res = run_cmd(vec!["PSYNC", &old_id, "210"], &mut conn_ctx, &ctx).await;
+    assert_eq!(res, Resp::NoReply);
 
-    match res {
-        Resp::SimpleString(s) => {
+    match rx.try_recv() {
+        Ok(Resp::SimpleString(s)) => {
             let s_str = String::from_utf8_lossy(&s);
             assert!(s_str.starts_with("CONTINUE"));
             assert!(s_str.contains(&new_id));
         }
-        _ => panic!("Expected SimpleString CONTINUE, got {:?}", res),
+        other => panic!("Expected SimpleString CONTINUE, got {:?}", other),
     }
+    let mut missed = 0;
+    while rx.try_recv().is_ok() {
+        missed += 1;
+    }
+    assert_eq!(missed, 20);
 
     // Case B: Invalid PSYNC2 request (offset > second_off)
-    // Offset 102 is > 101. Should trigger FULLRESYNC.
-    let res = run_cmd(vec!["PSYNC", &old_id, "102"], &mut conn_ctx, &ctx).await;
+    // Offset 352 is > 351. Should trigger FULLRESYNC.
+    let res = run_cmd(vec!["PSYNC", &old_id, "352"], &mut conn_ctx, &ctx).await;
     match res {
         Resp::Multiple(resps) => {
             if let Resp::SimpleString(s) = &resps[0] {
@@ -70,3 +76,118 @@ async fn test_psync2_transition() {
         _ => panic!("Expected Multiple (FULLRESYNC + RDB), got {:?}", res),
     }
 }
+
+fn set_cmd(key: &str) -> Resp {
+    Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("SET"))),
+        Resp::BulkString(Some(Bytes::from(key.to_string()))),
+        Resp::BulkString(Some(Bytes::from("v"))),
+    ]))
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_offsets_count_bytes_in_backlog_order() {
+    let ctx = create_server_context();
+    ctx.repl.repl_backlog_size.store(1024 * 1024, Ordering::Relaxed);
+
+    // Writers race each other; every write's offset must be where it sits
+    // in the backlog
+    let writers: Vec<_> = (0..8)
+        .map(|w| {
+            let ctx = ctx.clone();
+            tokio::spawn(async move {
+                let mut offsets = Vec::new();
+                for i in 0..50 {
+                    let frame = set_cmd(&format!("key:{}:{}", w, i));
+                    offsets.push((propagate(&ctx, frame.clone()).await, frame));
+                }
+                offsets
+            })
+        })
+        .collect();
+    let mut written = Vec::new();
+    for writer in writers {
+        written.extend(writer.await.unwrap());
+    }
+    written.sort_by_key(|(offset, _)| *offset);
+
+    let total: usize = written.iter().map(|(_, f)| f.as_bytes().len()).sum();
+    let current = ctx.repl.repl_offset.load(Ordering::Relaxed);
+    assert_eq!(current, total as u64);
+
+    let mut end = 0;
+    for (offset, frame) in &written {
+        end += frame.as_bytes().len() as u64;
+        assert_eq!(*offset, end);
+    }
+    let backlog = ctx.repl.repl_backlog.lock().await;
+    assert_eq!(backlog.histlen(), total);
+    assert_eq!(backlog.first_offset(), Some(1));
+    let in_backlog = backlog.since(0, current).unwrap();
+    let by_offset: Vec<Resp> = written.into_iter().map(|(_, f)| f).collect();
+    assert_eq!(in_backlog, by_offset);
+    // An offset inside a write is no place to continue from
+    assert!(backlog.since(1, current).is_none());
+}
+
+#[tokio::test]
+async fn test_lagging_replica_is_disconnected_and_continues() {
+    use crate::cmd::ClientInfo;
+
+    let ctx = create_server_context();
+    let replid = ctx.repl.run_id.read().unwrap().clone();
+
+    // A replica whose connection drains nothing: its channel takes one write
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    ctx.clients_ctx.clients.insert(
+        7,
+        ClientInfo {
+            id: 7,
+            addr: "10.0.0.7:6380".to_string(),
+            laddr: "127.0.0.1:6379".to_string(),
+            name: String::new(),
+            db: 0,
+            sub: 0,
+            psub: 0,
+            ssub: 0,
+            multi: -1,
+            watch: 0,
+            resp: 2,
+            flags: "S".to_string(),
+            cmd: "PSYNC".to_string(),
+            connect_time: std::time::Instant::now(),
+            last_activity: std::time::Instant::now(),
+            shutdown_tx: Some(shutdown_tx),
+            msg_sender: Some(tx.clone()),
+        },
+    );
+    ctx.repl.replicas.insert(7, tx);
+
+    let first = propagate(&ctx, set_cmd("a")).await;
+    assert!(ctx.repl.replicas.contains_key(&7));
+    propagate(&ctx, set_cmd("b")).await;
+    propagate(&ctx, set_cmd("c")).await;
+
+    // The write that did not fit drops the replica instead of going missing
+    assert!(!ctx.repl.replicas.contains_key(&7));
+    assert!(*shutdown_rx.borrow());
+    assert_eq!(rx.try_recv().unwrap(), set_cmd("a"));
+    assert!(rx.try_recv().is_err());
+
+    // Reconnecting from the last write it got resends the rest
+    let (tx, mut rx) = tokio::sync::mpsc::channel(100);
+    let mut conn_ctx = create_connection_context();
+    conn_ctx.msg_sender = Some(tx);
+    let res = run_cmd(
+        vec!["PSYNC", &replid, &first.to_string()],
+        &mut conn_ctx,
+        &ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::NoReply);
+    assert!(matches!(rx.try_recv(), Ok(Resp::SimpleString(s)) if s.starts_with(b"CONTINUE")));
+    assert_eq!(rx.try_recv().unwrap(), set_cmd("b"));
+    assert_eq!(rx.try_recv().unwrap(), set_cmd("c"));
+    assert!(rx.try_recv().is_err());
+}
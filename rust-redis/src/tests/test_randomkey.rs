@@ -0,0 +1,91 @@
+use crate::resp::Resp;
+use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+use bytes::Bytes;
+use std::collections::HashMap;
+
+#[tokio::test]
+async fn test_randomkey_empty_and_expired() {
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+
+    let res = run_cmd(vec!["RANDOMKEY"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(None));
+
+    run_cmd(vec!["SET", "gone", "v", "PX", "1"], &mut conn, &server_ctx).await;
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    let res = run_cmd(vec!["RANDOMKEY"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(None));
+    assert_eq!(server_ctx.databases[0].read().unwrap().len(), 0);
+
+    // Expired picks are deleted and drawn again until the live key turns up,
+    // however many of them there are
+    for i in 0..500 {
+        run_cmd(
+            vec!["SET", &format!("gone:{}", i), "v", "PX", "1"],
+            &mut conn,
+            &server_ctx,
+        )
+        .await;
+    }
+    run_cmd(vec!["SET", "live", "v"], &mut conn, &server_ctx).await;
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    let res = run_cmd(vec!["RANDOMKEY"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("live"))));
+
+    let res = run_cmd(vec!["RANDOMKEY", "extra"], &mut conn, &server_ctx).await;
+    assert!(matches!(res, Resp::Error(_)));
+}
+
+#[tokio::test]
+async fn test_randomkey_samples_by_key_not_element() {
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+
+    // One huge list next to a handful of small strings
+    let mut rpush = vec!["RPUSH".to_string(), "biglist".to_string()];
+    rpush.extend((0..10_000).map(|i| i.to_string()));
+    run_cmd(
+        rpush.iter().map(|s| s.as_str()).collect(),
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    let small = ["s1", "s2", "s3", "s4"];
+    for k in small {
+        run_cmd(vec!["SET", k, "v"], &mut conn, &server_ctx).await;
+    }
+
+    let samples = 5000;
+    let mut counts: HashMap<Bytes, usize> = HashMap::new();
+    for _ in 0..samples {
+        match run_cmd(vec!["RANDOMKEY"], &mut conn, &server_ctx).await {
+            Resp::BulkString(Some(k)) => *counts.entry(k).or_default() += 1,
+            other => panic!("expected a key, got {:?}", other),
+        }
+    }
+
+    // Five keys, so each should land near 1000; allow a generous margin
+    assert_eq!(counts.len(), 5);
+    for (key, count) in &counts {
+        assert!(
+            (700..=1300).contains(count),
+            "{:?} returned {} times out of {}",
+            key,
+            count,
+            samples
+        );
+    }
+
+    // SCAN walks keys too, so the big list costs a single slot
+    let res = run_cmd(vec!["SCAN", "0", "COUNT", "100"], &mut conn, &server_ctx).await;
+    match res {
+        Resp::Array(Some(parts)) => {
+            assert_eq!(parts[0], Resp::BulkString(Some(Bytes::from("0"))));
+            match &parts[1] {
+                Resp::Array(Some(keys)) => assert_eq!(keys.len(), 5),
+                other => panic!("expected key array, got {:?}", other),
+            }
+        }
+        other => panic!("expected array, got {:?}", other),
+    }
+}
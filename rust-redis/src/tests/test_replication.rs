@@ -57,6 +57,101 @@ async fn test_replicaof_and_role() {
     }
 }
 
+#[tokio::test]
+async fn test_slaveof_follows_master_until_no_one() {
+    use crate::resp::{read_frame, write_frame};
+    use tokio::io::{AsyncWriteExt, BufReader};
+    use tokio::time::{Duration, sleep, timeout};
+
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    let res = run_cmd(
+        vec!["SLAVEOF", "127.0.0.1", &port],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+
+    // Play the master: accept the PSYNC, continue, then stream a write
+    let (socket, _) = listener.accept().await.unwrap();
+    let (read_half, mut master) = socket.into_split();
+    let mut from_replica = BufReader::new(read_half);
+    let psync = read_frame(&mut from_replica).await.unwrap().unwrap();
+    assert!(format!("{:?}", psync).contains("PSYNC"), "{:?}", psync);
+    master.write_all(b"+CONTINUE\r\n").await.unwrap();
+    let set = |key: &str| {
+        Resp::Array(Some(
+            ["SET", key, "v"]
+                .iter()
+                .map(|s| Resp::BulkString(Some(Bytes::from(s.to_string()))))
+                .collect(),
+        ))
+    };
+    write_frame(&mut master, &set("k1")).await.unwrap();
+
+    let mut synced = false;
+    for _ in 0..100 {
+        if run_cmd(vec!["GET", "k1"], &mut conn_ctx, &server_ctx).await
+            == Resp::BulkString(Some(Bytes::from("v")))
+        {
+            synced = true;
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert!(synced, "replica never applied the master's write");
+
+    let res = run_cmd(vec!["ROLE"], &mut conn_ctx, &server_ctx).await;
+    match res {
+        Resp::Array(Some(arr)) => {
+            assert_eq!(arr[0], Resp::BulkString(Some(Bytes::from("slave"))));
+            assert_eq!(arr[3], Resp::BulkString(Some(Bytes::from("connected"))));
+        }
+        _ => panic!("Expected ROLE array as slave, got {:?}", res),
+    }
+    let res = run_cmd(vec!["INFO", "replication"], &mut conn_ctx, &server_ctx).await;
+    match res {
+        Resp::BulkString(Some(b)) => {
+            assert!(String::from_utf8_lossy(&b).contains("master_link_status:up"))
+        }
+        _ => panic!("Expected INFO bulk string, got {:?}", res),
+    }
+
+    let res = run_cmd(
+        vec!["REPLICAOF", "127.0.0.1", &port],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::SimpleString(Bytes::from("OK Already connected to specified master"))
+    );
+
+    // Promotion drops the link: the master sees the connection close and
+    // its later writes are no longer applied
+    let res = run_cmd(vec!["REPLICAOF", "NO", "ONE"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+    let _ = write_frame(&mut master, &set("k2")).await;
+    let closed = timeout(Duration::from_secs(5), async {
+        while let Ok(Some(_)) = read_frame(&mut from_replica).await {}
+    })
+    .await;
+    assert!(closed.is_ok(), "replica kept the master link open");
+    let res = run_cmd(vec!["GET", "k2"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(None));
+    assert!(
+        !server_ctx
+            .repl
+            .master_link_established
+            .load(std::sync::atomic::Ordering::Relaxed)
+    );
+}
+
 #[tokio::test]
 async fn test_expire_propagation() {
     let ctx = create_server_context();
@@ -132,19 +227,23 @@ async fn test_wait_command() {
         _ => panic!("Expected Integer 0, got {:?}", res),
     }
 
-    // 2. No replicas, WAIT 1 100 -> returns 0 after timeout
-    let start = std::time::Instant::now();
-    let res = run_cmd(vec!["WAIT", "1", "100"], &mut conn_ctx, &ctx).await;
-    let elapsed = start.elapsed().as_millis();
+    // 2. No replicas, WAIT 1 0 -> returns 0 at once instead of blocking forever
+    let res = run_cmd(vec!["WAIT", "1", "0"], &mut conn_ctx, &ctx).await;
     match res {
         Resp::Integer(i) => assert_eq!(i, 0),
         _ => panic!("Expected Integer 0, got {:?}", res),
     }
-    assert!(
-        elapsed >= 100,
-        "Should wait at least 100ms, waited {}ms",
-        elapsed
+
+    // Malformed arguments are rejected rather than read as 0
+    let res = run_cmd(vec!["WAIT", "one", "100"], &mut conn_ctx, &ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR value is not an integer or out of range".to_string())
     );
+    let res = run_cmd(vec!["WAIT", "1", "-1"], &mut conn_ctx, &ctx).await;
+    assert_eq!(res, Resp::Error("ERR timeout is negative".to_string()));
+    let res = run_cmd(vec!["WAIT", "1"], &mut conn_ctx, &ctx).await;
+    assert!(matches!(res, Resp::Error(_)), "{:?}", res);
 
     // 3. Add a replica and simulate ACK
     // Update offset to 10
@@ -201,3 +300,77 @@ async fn test_wait_command() {
         elapsed
     );
 }
+
+#[tokio::test]
+async fn test_replica_registry_in_info_and_role() {
+    let ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+
+    // An in-process replica: announce its port, PSYNC, then acknowledge
+    let (tx, _rx) = tokio::sync::mpsc::channel(100);
+    let mut replica_ctx = create_connection_context();
+    replica_ctx.id = 42;
+    replica_ctx.msg_sender = Some(tx);
+    run_cmd(
+        vec!["REPLCONF", "LISTENING-PORT", "6390"],
+        &mut replica_ctx,
+        &ctx,
+    )
+    .await;
+    run_cmd(vec!["PSYNC", "?", "-1"], &mut replica_ctx, &ctx).await;
+    ctx.repl
+        .repl_offset
+        .store(7, std::sync::atomic::Ordering::Relaxed);
+    run_cmd(vec!["REPLCONF", "ACK", "7"], &mut replica_ctx, &ctx).await;
+
+    let res = run_cmd(vec!["INFO", "replication"], &mut conn_ctx, &ctx).await;
+    match res {
+        Resp::BulkString(Some(b)) => {
+            let info = String::from_utf8_lossy(&b);
+            assert!(info.contains("connected_slaves:1"), "{}", info);
+            let line = info
+                .lines()
+                .find(|l| l.starts_with("slave0:"))
+                .expect("slave0 line");
+            assert!(line.contains("port=6390"), "{}", line);
+            assert!(line.contains("state=online"), "{}", line);
+            assert!(line.contains("offset=7"), "{}", line);
+            assert!(line.contains("lag=0"), "{}", line);
+        }
+        _ => panic!("Expected INFO bulk string, got {:?}", res),
+    }
+
+    let res = run_cmd(vec!["ROLE"], &mut conn_ctx, &ctx).await;
+    match res {
+        Resp::Array(Some(arr)) => {
+            assert_eq!(arr[1], Resp::Integer(7));
+            match &arr[2] {
+                Resp::Array(Some(replicas)) => {
+                    assert_eq!(replicas.len(), 1);
+                    match &replicas[0] {
+                        Resp::Array(Some(r)) => {
+                            assert_eq!(r[1], Resp::BulkString(Some(Bytes::from("6390"))));
+                            assert_eq!(r[2], Resp::BulkString(Some(Bytes::from("7"))));
+                        }
+                        other => panic!("Expected replica entry, got {:?}", other),
+                    }
+                }
+                other => panic!("Expected replica array, got {:?}", other),
+            }
+        }
+        _ => panic!("Expected ROLE array, got {:?}", res),
+    }
+
+    // Disconnecting drops the replica and its stale ack from the registry
+    crate::cmd::release_client(&mut replica_ctx, &ctx);
+    assert!(!ctx.repl.replica_ack.contains_key(&42));
+    let res = run_cmd(vec!["INFO", "replication"], &mut conn_ctx, &ctx).await;
+    match res {
+        Resp::BulkString(Some(b)) => {
+            let info = String::from_utf8_lossy(&b);
+            assert!(info.contains("connected_slaves:0"), "{}", info);
+            assert!(!info.contains("slave0:"), "{}", info);
+        }
+        _ => panic!("Expected INFO bulk string, got {:?}", res),
+    }
+}
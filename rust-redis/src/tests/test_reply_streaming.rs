@@ -0,0 +1,85 @@
+use crate::resp::{Resp, STREAM_REPLY_MIN_ELEMENTS, write_frame};
+use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+use bytes::Bytes;
+
+/// Encodes `reply` the way the connection writer sends it and reads it back.
+async fn round_trip(reply: &Resp) -> Resp {
+    let mut wire: Vec<u8> = Vec::new();
+    write_frame(&mut wire, reply).await.unwrap();
+    let mut reader = tokio::io::BufReader::new(wire.as_slice());
+    crate::resp::read_frame(&mut reader).await.unwrap().unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_streamed_smembers_and_zrange() {
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+
+    let n = STREAM_REPLY_MIN_ELEMENTS;
+    let mut sadd = vec!["SADD".to_string(), "s".to_string()];
+    let mut zadd = vec!["ZADD".to_string(), "z".to_string()];
+    for i in 0..n {
+        sadd.push(format!("m{}", i));
+        zadd.push(i.to_string());
+        zadd.push(format!("m{}", i));
+    }
+    run_cmd(
+        sadd.iter().map(|s| s.as_str()).collect(),
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    run_cmd(
+        zadd.iter().map(|s| s.as_str()).collect(),
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+
+    let reply = run_cmd(vec!["SMEMBERS", "s"], &mut conn, &server_ctx).await;
+    assert!(matches!(reply, Resp::Encoded(_)), "{:?}", reply);
+    match round_trip(&reply).await {
+        Resp::Array(Some(items)) => assert_eq!(items.len(), n),
+        other => panic!("expected an array, got {:?}", other),
+    }
+
+    // Same elements and order as the collected reply would have
+    let reply = run_cmd(
+        vec!["ZRANGE", "z", "0", "-1", "WITHSCORES"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    assert!(matches!(reply, Resp::Encoded(_)), "{:?}", reply);
+    match round_trip(&reply).await {
+        Resp::Array(Some(items)) => {
+            assert_eq!(items.len(), n * 2);
+            assert_eq!(items[0], Resp::BulkString(Some(Bytes::from("m0"))));
+            assert_eq!(items[1], Resp::BulkString(Some(Bytes::from("0"))));
+            assert_eq!(
+                items[n * 2 - 2],
+                Resp::BulkString(Some(Bytes::from(format!("m{}", n - 1))))
+            );
+        }
+        other => panic!("expected an array, got {:?}", other),
+    }
+
+    // Small replies are still built as values
+    let res = run_cmd(vec!["ZRANGE", "z", "0", "1"], &mut conn, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("m0"))),
+            Resp::BulkString(Some(Bytes::from("m1"))),
+        ]))
+    );
+
+    // Scripts need structured replies, so they never get a streamed one
+    let res = run_cmd(
+        vec!["EVAL", "return #redis.call('SMEMBERS', 's')", "0"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(n as i64));
+}
@@ -40,10 +40,15 @@ async fn test_reset_basics() {
         ClientInfo {
             id: conn_ctx.id,
             addr: "127.0.0.1:1234".to_string(),
+            laddr: "127.0.0.1:6379".to_string(),
             name: String::new(),
             db: 0,
             sub: 0,
             psub: 0,
+            ssub: 0,
+            multi: -1,
+            watch: 0,
+            resp: 2,
             flags: String::new(),
             cmd: String::new(),
             connect_time: Instant::now(),
@@ -141,3 +146,73 @@ async fn test_reset_multi() {
     assert!(!conn_ctx.in_multi);
     assert!(conn_ctx.multi_queue.is_empty());
 }
+
+#[tokio::test]
+async fn test_reset_tracking_teardown() {
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+    conn_ctx.id = 42;
+
+    // Register client with a message channel so invalidations are observable
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    server_ctx.clients_ctx.clients.insert(
+        conn_ctx.id,
+        ClientInfo {
+            id: conn_ctx.id,
+            addr: "127.0.0.1:4242".to_string(),
+            laddr: "127.0.0.1:6379".to_string(),
+            name: String::new(),
+            db: 0,
+            sub: 0,
+            psub: 0,
+            ssub: 0,
+            multi: -1,
+            watch: 0,
+            resp: 2,
+            flags: String::new(),
+            cmd: String::new(),
+            connect_time: Instant::now(),
+            last_activity: Instant::now(),
+            shutdown_tx: None,
+            msg_sender: Some(tx),
+        },
+    );
+
+    run_cmd_bytes(
+        vec![Bytes::from("CLIENT"), Bytes::from("TRACKING"), Bytes::from("ON")],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    run_cmd_bytes(vec![Bytes::from("GET"), Bytes::from("k1")], &mut conn_ctx, &server_ctx).await;
+    run_cmd_bytes(vec![Bytes::from("GET"), Bytes::from("k2")], &mut conn_ctx, &server_ctx).await;
+    assert!(server_ctx.clients_ctx.tracking_clients.contains_key(&(0, b"k1".to_vec())));
+    assert_eq!(
+        server_ctx.clients_ctx.client_tracked_keys.get(&conn_ctx.id).unwrap().len(),
+        2
+    );
+
+    let resp = run_cmd_bytes(vec![Bytes::from("RESET")], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(resp, Resp::SimpleString(Bytes::from("RESET")));
+    assert!(!conn_ctx.client_tracking);
+    assert!(!server_ctx.clients_ctx.tracking_clients.contains_key(&(0, b"k1".to_vec())));
+    assert!(!server_ctx.clients_ctx.tracking_clients.contains_key(&(0, b"k2".to_vec())));
+    assert!(!server_ctx.clients_ctx.client_tracked_keys.contains_key(&conn_ctx.id));
+
+    // Writes from another client must not invalidate anything for us anymore
+    let mut writer = create_connection_context();
+    writer.id = 43;
+    run_cmd_bytes(
+        vec![Bytes::from("SET"), Bytes::from("k1"), Bytes::from("v")],
+        &mut writer,
+        &server_ctx,
+    )
+    .await;
+    run_cmd_bytes(
+        vec![Bytes::from("SET"), Bytes::from("k2"), Bytes::from("v")],
+        &mut writer,
+        &server_ctx,
+    )
+    .await;
+    assert!(rx.try_recv().is_err());
+}
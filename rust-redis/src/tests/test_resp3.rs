@@ -0,0 +1,303 @@
+use crate::resp::{Resp, STREAM_REPLY_MIN_ELEMENTS};
+use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
+use bytes::Bytes;
+
+fn bulk(s: &str) -> Resp {
+    Resp::BulkString(Some(Bytes::from(s.to_string())))
+}
+
+async fn decode(raw: &[u8]) -> Resp {
+    let mut reader = tokio::io::BufReader::new(raw);
+    crate::resp::read_frame(&mut reader).await.unwrap().unwrap()
+}
+
+/// Encodes `frame` the way a client speaking `protocol` receives it.
+async fn encode(frame: &Resp, protocol: u8) -> Vec<u8> {
+    let mut wire = Vec::new();
+    crate::resp::write_frame(&mut wire, &frame.clone().into_protocol(protocol))
+        .await
+        .unwrap();
+    wire
+}
+
+/// Encodes `frame` through both writers, checks they agree, and parses it back.
+async fn round_trip(frame: &Resp) -> Resp {
+    let encoded = frame.as_bytes();
+    let mut wire = Vec::new();
+    crate::resp::write_frame(&mut wire, frame).await.unwrap();
+    assert_eq!(wire, encoded, "{:?}", frame);
+    decode(&encoded).await
+}
+
+#[tokio::test]
+async fn test_map_encodes_per_protocol() {
+    let map = Resp::Map(vec![(bulk("a"), Resp::Integer(1)), (bulk("b"), bulk("x"))]);
+
+    let v3 = encode(&map, 3).await;
+    assert_eq!(v3, b"%2\r\n$1\r\na\r\n:1\r\n$1\r\nb\r\n$1\r\nx\r\n");
+    assert_eq!(decode(&v3).await, map);
+
+    let v2 = encode(&map, 2).await;
+    assert_eq!(v2, b"*4\r\n$1\r\na\r\n:1\r\n$1\r\nb\r\n$1\r\nx\r\n");
+    assert_eq!(
+        decode(&v2).await,
+        Resp::Array(Some(vec![
+            bulk("a"),
+            Resp::Integer(1),
+            bulk("b"),
+            bulk("x")
+        ]))
+    );
+}
+
+#[tokio::test]
+async fn test_scalar_types_encode_per_protocol() {
+    let cases: Vec<(Resp, &[u8], &[u8])> = vec![
+        (Resp::Double(1.5), b",1.5\r\n", b"$3\r\n1.5\r\n"),
+        (Resp::Double(f64::INFINITY), b",inf\r\n", b"$3\r\ninf\r\n"),
+        (Resp::Boolean(true), b"#t\r\n", b":1\r\n"),
+        (Resp::Boolean(false), b"#f\r\n", b":0\r\n"),
+        (Resp::Null, b"_\r\n", b"$-1\r\n"),
+        // RESP2 nils become the RESP3 null
+        (Resp::BulkString(None), b"_\r\n", b"$-1\r\n"),
+        (Resp::Array(None), b"_\r\n", b"*-1\r\n"),
+    ];
+    for (frame, v3, v2) in cases {
+        assert_eq!(encode(&frame, 3).await, v3, "{:?}", frame);
+        assert_eq!(encode(&frame, 2).await, v2, "{:?}", frame);
+    }
+
+    // Conversion reaches into nested replies
+    let nested = Resp::Array(Some(vec![
+        Resp::Map(vec![(bulk("k"), Resp::Double(2.0))]),
+        Resp::BulkString(None),
+    ]));
+    assert_eq!(
+        nested.clone().into_protocol(2),
+        Resp::Array(Some(vec![
+            Resp::Array(Some(vec![bulk("k"), bulk("2")])),
+            Resp::BulkString(None),
+        ]))
+    );
+    assert_eq!(
+        nested.into_protocol(3),
+        Resp::Array(Some(vec![
+            Resp::Map(vec![(bulk("k"), Resp::Double(2.0))]),
+            Resp::Null,
+        ]))
+    );
+}
+
+#[tokio::test]
+async fn test_replies_follow_negotiated_protocol() {
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+
+    run_cmd(vec!["HSET", "h", "f", "v"], &mut conn, &server_ctx).await;
+    run_cmd(vec!["ZADD", "z", "2.5", "m"], &mut conn, &server_ctx).await;
+
+    // RESP2 clients see the same replies they always did
+    let res = run_cmd(vec!["HGETALL", "h"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Array(Some(vec![bulk("f"), bulk("v")])));
+    let res = run_cmd(vec!["ZSCORE", "z", "m"], &mut conn, &server_ctx).await;
+    assert_eq!(res, bulk("2.5"));
+    let res = run_cmd(vec!["GET", "missing"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(None));
+    let res = run_cmd(
+        vec!["CONFIG", "GET", "maxmemory-policy"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![bulk("maxmemory-policy"), bulk("noeviction")]))
+    );
+
+    run_cmd(vec!["HELLO", "3"], &mut conn, &server_ctx).await;
+    let res = run_cmd(vec!["HGETALL", "h"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Map(vec![(bulk("f"), bulk("v"))]));
+    let res = run_cmd(vec!["HGETALL", "missing"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Map(Vec::new()));
+    let res = run_cmd(vec!["ZSCORE", "z", "m"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Double(2.5));
+    let res = run_cmd(vec!["ZSCORE", "z", "nope"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Null);
+    let res = run_cmd(vec!["GET", "missing"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Null);
+    let res = run_cmd(
+        vec!["CONFIG", "GET", "maxmemory-policy"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Map(vec![(bulk("maxmemory-policy"), bulk("noeviction"))])
+    );
+
+    // RESET goes back to RESP2
+    run_cmd(vec!["RESET"], &mut conn, &server_ctx).await;
+    let res = run_cmd(vec!["HGETALL", "h"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Array(Some(vec![bulk("f"), bulk("v")])));
+}
+
+#[tokio::test]
+async fn test_config_get_framing_per_protocol() {
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+
+    let mut wire = Vec::new();
+    for protocol in ["2", "3"] {
+        run_cmd(vec!["HELLO", protocol], &mut conn, &server_ctx).await;
+        let reply = run_cmd(vec!["CONFIG", "GET", "maxmemory"], &mut conn, &server_ctx).await;
+        let mut bytes = Vec::new();
+        crate::resp::write_frame(&mut bytes, &reply).await.unwrap();
+        wire.push(bytes);
+    }
+    assert_eq!(wire[0], b"*2\r\n$9\r\nmaxmemory\r\n$1\r\n0\r\n");
+    assert_eq!(wire[1], b"%1\r\n$9\r\nmaxmemory\r\n$1\r\n0\r\n");
+}
+
+#[tokio::test]
+async fn test_streamed_hgetall_is_a_map_under_resp3() {
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+
+    let fields: Vec<String> = (0..STREAM_REPLY_MIN_ELEMENTS)
+        .map(|i| format!("f{}", i))
+        .collect();
+    let mut hmset = vec!["HMSET", "big"];
+    for field in &fields {
+        hmset.push(field);
+        hmset.push("v");
+    }
+    run_cmd(hmset, &mut conn, &server_ctx).await;
+
+    let reply = run_cmd(vec!["HGETALL", "big"], &mut conn, &server_ctx).await;
+    assert!(matches!(reply, Resp::Encoded(_)), "{:?}", reply);
+
+    run_cmd(vec!["HELLO", "3"], &mut conn, &server_ctx).await;
+    let reply = run_cmd(vec!["HGETALL", "big"], &mut conn, &server_ctx).await;
+    let mut wire = Vec::new();
+    crate::resp::write_frame(&mut wire, &reply).await.unwrap();
+    match decode(&wire).await {
+        Resp::Map(pairs) => assert_eq!(pairs.len(), STREAM_REPLY_MIN_ELEMENTS),
+        other => panic!("expected Map, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_set_replies_are_sets_under_resp3() {
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+
+    run_cmd(vec!["SADD", "s1", "a"], &mut conn, &server_ctx).await;
+    run_cmd(vec!["SADD", "s2", "a"], &mut conn, &server_ctx).await;
+    let members: Vec<String> = (0..STREAM_REPLY_MIN_ELEMENTS)
+        .map(|i| format!("m{}", i))
+        .collect();
+    let mut sadd = vec!["SADD", "big"];
+    sadd.extend(members.iter().map(String::as_str));
+    run_cmd(sadd, &mut conn, &server_ctx).await;
+
+    let res = run_cmd(vec!["SMEMBERS", "s1"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Array(Some(vec![bulk("a")])));
+    let res = run_cmd(vec!["SINTER", "s1", "s2"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Array(Some(vec![bulk("a")])));
+    let res = run_cmd(vec!["SMEMBERS", "big"], &mut conn, &server_ctx).await;
+    assert!(matches!(res, Resp::Encoded(_)), "{:?}", res);
+    let res = run_cmd(vec!["INFO", "server"], &mut conn, &server_ctx).await;
+    assert!(matches!(res, Resp::BulkString(Some(_))), "{:?}", res);
+
+    run_cmd(vec!["HELLO", "3"], &mut conn, &server_ctx).await;
+    let res = run_cmd(vec!["SMEMBERS", "s1"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Set(vec![bulk("a")]));
+    let res = run_cmd(vec!["SMEMBERS", "missing"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Set(Vec::new()));
+    let res = run_cmd(vec!["SUNION", "s1", "s2"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Set(vec![bulk("a")]));
+    let res = run_cmd(vec!["INFO", "server"], &mut conn, &server_ctx).await;
+    assert!(matches!(res, Resp::Verbatim(_)), "{:?}", res);
+
+    let reply = run_cmd(vec!["SMEMBERS", "big"], &mut conn, &server_ctx).await;
+    let mut wire = Vec::new();
+    crate::resp::write_frame(&mut wire, &reply).await.unwrap();
+    assert_eq!(wire, reply.as_bytes());
+    match decode(&wire).await {
+        Resp::Set(items) => assert_eq!(items.len(), STREAM_REPLY_MIN_ELEMENTS),
+        other => panic!("expected Set, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_rich_variants_round_trip_in_both_protocols() {
+    let cases = vec![
+        (
+            Resp::Map(vec![(bulk("a"), Resp::Integer(1)), (bulk("b"), bulk("x"))]),
+            Resp::Array(Some(vec![
+                bulk("a"),
+                Resp::Integer(1),
+                bulk("b"),
+                bulk("x"),
+            ])),
+        ),
+        (
+            Resp::Set(vec![bulk("m1"), bulk("m2")]),
+            Resp::Array(Some(vec![bulk("m1"), bulk("m2")])),
+        ),
+        (Resp::Double(1.5), bulk("1.5")),
+        (Resp::Double(-2.0), bulk("-2")),
+        (Resp::Boolean(true), Resp::Integer(1)),
+        (Resp::Boolean(false), Resp::Integer(0)),
+        (
+            Resp::BigNumber(Bytes::from("3492890328409238509324850943850943825024385")),
+            bulk("3492890328409238509324850943850943825024385"),
+        ),
+        (Resp::Verbatim(Bytes::from("some text")), bulk("some text")),
+        (Resp::Null, Resp::BulkString(None)),
+        (
+            Resp::Push(vec![bulk("message"), bulk("ch"), bulk("hi")]),
+            Resp::Array(Some(vec![bulk("message"), bulk("ch"), bulk("hi")])),
+        ),
+    ];
+
+    for (rich, resp2) in cases {
+        assert_eq!(rich.clone().into_protocol(3), rich);
+        assert_eq!(round_trip(&rich).await, rich);
+
+        let downgraded = rich.clone().into_protocol(2);
+        assert_eq!(downgraded, resp2, "{:?}", rich);
+        assert_eq!(round_trip(&downgraded).await, resp2);
+    }
+}
+
+#[tokio::test]
+async fn test_zset_scores_reply_as_doubles() {
+    let server_ctx = create_server_context();
+    let mut conn = create_connection_context();
+
+    let res = run_cmd(
+        vec!["ZADD", "z", "INCR", "1.5", "m"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("1.5"));
+
+    run_cmd(vec!["HELLO", "3"], &mut conn, &server_ctx).await;
+    let res = run_cmd(vec!["ZADD", "z", "INCR", "1", "m"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Double(2.5));
+    let res = run_cmd(vec!["ZINCRBY", "z", "0.5", "m"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Double(3.0));
+    let res = run_cmd(vec!["ZMSCORE", "z", "m", "nope"], &mut conn, &server_ctx).await;
+    assert_eq!(res, Resp::Array(Some(vec![Resp::Double(3.0), Resp::Null])));
+    // A ZADD INCR that NX skips has no score to report
+    let res = run_cmd(
+        vec!["ZADD", "z", "NX", "INCR", "1", "m"],
+        &mut conn,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Null);
+}
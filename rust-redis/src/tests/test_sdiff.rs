@@ -112,3 +112,56 @@ async fn test_sdiff() {
         _ => panic!("Expected Error"),
     }
 }
+
+#[tokio::test]
+async fn test_set_ops_reply_type_follows_protocol() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    run_cmd(
+        vec!["SADD", "s1", "a", "b", "c"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    run_cmd(vec!["SADD", "s2", "b", "d"], &mut conn_ctx, &server_ctx).await;
+
+    let cases: Vec<(Vec<&str>, usize)> = vec![
+        (vec!["SINTER", "s1", "s2"], 1),
+        (vec!["SUNION", "s1", "s2"], 4),
+        (vec!["SDIFF", "s1", "s2"], 2),
+        (vec!["SDIFF", "missing", "s1"], 0),
+    ];
+
+    // RESP2: plain arrays
+    for (cmd, len) in &cases {
+        match run_cmd(cmd.clone(), &mut conn_ctx, &server_ctx).await {
+            Resp::Array(Some(items)) => assert_eq!(items.len(), *len, "{:?}", cmd),
+            other => panic!("Expected Array for {:?}, got {:?}", cmd, other),
+        }
+    }
+
+    // RESP3: set type
+    run_cmd(vec!["HELLO", "3"], &mut conn_ctx, &server_ctx).await;
+    for (cmd, len) in &cases {
+        match run_cmd(cmd.clone(), &mut conn_ctx, &server_ctx).await {
+            Resp::Set(items) => assert_eq!(items.len(), *len, "{:?}", cmd),
+            other => panic!("Expected Set for {:?}, got {:?}", cmd, other),
+        }
+    }
+    assert_eq!(Resp::Set(vec![]).as_bytes(), b"~0\r\n".to_vec());
+
+    // Errors are unaffected by the protocol
+    run_cmd(vec!["SET", "str", "v"], &mut conn_ctx, &server_ctx).await;
+    match run_cmd(vec!["SUNION", "s1", "str"], &mut conn_ctx, &server_ctx).await {
+        Resp::Error(msg) => assert!(msg.starts_with("WRONGTYPE")),
+        other => panic!("Expected WRONGTYPE, got {:?}", other),
+    }
+
+    // RESET goes back to RESP2
+    run_cmd(vec!["RESET"], &mut conn_ctx, &server_ctx).await;
+    match run_cmd(vec!["SINTER", "s1", "s2"], &mut conn_ctx, &server_ctx).await {
+        Resp::Array(Some(items)) => assert_eq!(items.len(), 1),
+        other => panic!("Expected Array, got {:?}", other),
+    }
+}
@@ -88,7 +88,7 @@ async fn test_sdiffstore() {
     }
     let res = run_cmd(vec!["exists", "dest2"], &mut conn_ctx, &server_ctx).await;
     match res {
-        Resp::Integer(n) => assert_eq!(n, 1), // SDIFFSTORE creates empty set key
+        Resp::Integer(n) => assert_eq!(n, 0), // Empty result is not stored
         _ => panic!("Expected integer response"),
     }
 
@@ -117,4 +117,30 @@ async fn test_sdiffstore() {
         Resp::Error(s) => assert!(s.contains("WRONGTYPE")),
         _ => panic!("Expected WRONGTYPE error"),
     }
+
+    // Test 6: WRONGTYPE is reported even when the first key is missing,
+    // and the existing destination is left untouched
+    let res = run_cmd(
+        vec!["sdiffstore", "dest3", "missing", "string_key"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    match res {
+        Resp::Error(s) => assert!(s.contains("WRONGTYPE")),
+        _ => panic!("Expected WRONGTYPE error"),
+    }
+    let res = run_cmd(vec!["scard", "dest3"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(4));
+
+    // Test 7: An empty result deletes a previously populated destination
+    let res = run_cmd(
+        vec!["sdiffstore", "dest3", "s2", "s1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(0));
+    let res = run_cmd(vec!["exists", "dest3"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
 }
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+#[tokio::test]
+async fn test_shutdown_saves_rdb_when_save_points_configured() {
+    let mut server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let dir = std::env::temp_dir().join(format!("rust-redis-shutdown-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let rdb_path = dir.join("dump.rdb");
+    let _ = std::fs::remove_file(&rdb_path);
+
+    let mut cfg = (*server_ctx.config).clone();
+    cfg.dbfilename = rdb_path.to_string_lossy().into_owned();
+    server_ctx.config = Arc::new(cfg);
+    {
+        let mut params = server_ctx.persist.save_params.write().unwrap();
+        params.clear();
+        params.push((3600, 1));
+    }
+
+    crate::tests::helper::run_cmd(vec!["SET", "foo", "bar"], &mut conn_ctx, &server_ctx).await;
+
+    // NOSAVE skips the snapshot even with save points configured
+    crate::cmd::save::prepare_shutdown(&server_ctx, Some(false))
+        .await
+        .unwrap();
+    assert!(!rdb_path.exists());
+
+    // The signal handlers run the sequence with no modifier
+    crate::cmd::save::prepare_shutdown(&server_ctx, None)
+        .await
+        .unwrap();
+
+    assert!(rdb_path.exists());
+    assert_eq!(
+        server_ctx.persist.dirty.load(std::sync::atomic::Ordering::Relaxed),
+        0
+    );
+
+    let databases = Arc::new(vec![std::sync::RwLock::new(crate::db::Db::default())]);
+    crate::rdb::rdb_load(&databases, &server_ctx.config).unwrap();
+    assert!(databases[0].read().unwrap().contains_key(&b"foo"[..]));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -133,3 +133,100 @@ async fn test_sinter() {
         _ => panic!("Expected Error"),
     }
 }
+
+#[tokio::test]
+async fn test_sintercard() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    run_cmd(
+        vec!["SADD", "s1", "a", "b", "c", "d"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    run_cmd(
+        vec!["SADD", "s2", "a", "b", "c", "e"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    let res = run_cmd(
+        vec!["SINTERCARD", "2", "s1", "s2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(3));
+    let res = run_cmd(vec!["SINTERCARD", "1", "s1"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(4));
+
+    // LIMIT stops counting early; 0 means no limit
+    let res = run_cmd(
+        vec!["SINTERCARD", "2", "s1", "s2", "LIMIT", "2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(2));
+    let res = run_cmd(
+        vec!["SINTERCARD", "2", "s1", "s2", "LIMIT", "0"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(3));
+
+    // A missing key empties the intersection
+    let res = run_cmd(
+        vec!["SINTERCARD", "2", "s1", "missing"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(0));
+
+    // Argument and type errors
+    let res = run_cmd(vec!["SINTERCARD", "0", "s1"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR numkeys should be greater than 0".to_string())
+    );
+    let res = run_cmd(
+        vec!["SINTERCARD", "3", "s1", "s2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR Number of keys can't be greater than number of args".to_string())
+    );
+    let res = run_cmd(
+        vec!["SINTERCARD", "1", "s1", "LIMIT", "-1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Error("ERR LIMIT can't be negative".to_string()));
+    let res = run_cmd(
+        vec!["SINTERCARD", "1", "s1", "FOO"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Error("ERR syntax error".to_string()));
+
+    run_cmd(vec!["SET", "str", "v"], &mut conn_ctx, &server_ctx).await;
+    match run_cmd(
+        vec!["SINTERCARD", "2", "missing", "str"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await
+    {
+        Resp::Error(e) => assert!(e.starts_with("WRONGTYPE"), "{}", e),
+        other => panic!("Expected WRONGTYPE, got {:?}", other),
+    }
+}
@@ -121,4 +121,18 @@ async fn test_sinterstore() {
         Resp::Error(msg) => assert!(msg.contains("WRONGTYPE")),
         _ => panic!("Expected Error"),
     }
+    // The failed store leaves the destination as it was
+    let res = run_cmd(vec!["SCARD", "dest"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(1));
+
+    // 6. An empty intersection deletes the destination
+    let res = run_cmd(
+        vec!["SINTERSTORE", "dest", "s1", "missing"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(0));
+    let res = run_cmd(vec!["EXISTS", "dest"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
 }
@@ -107,3 +107,39 @@ async fn test_smove() {
         "Source key should be removed when empty"
     );
 }
+
+#[tokio::test]
+async fn test_smove_edge_cases() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    run_cmd(vec!["SADD", "src", "a", "b"], &mut conn_ctx, &server_ctx).await;
+
+    // Member absent: returns 0 and never creates the destination
+    let res = run_cmd(
+        vec!["SMOVE", "src", "newdest", "zzz"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(0));
+    let res = run_cmd(vec!["EXISTS", "newdest"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+
+    // Same key: no-op that reports membership
+    let res = run_cmd(vec!["SMOVE", "src", "src", "a"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(1));
+    let res = run_cmd(vec!["SMOVE", "src", "src", "zzz"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+    let res = run_cmd(vec!["SCARD", "src"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(2));
+
+    // Moving the last members deletes the source key
+    run_cmd(vec!["SMOVE", "src", "dst", "a"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(vec!["SMOVE", "src", "dst", "b"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(1));
+    let res = run_cmd(vec!["EXISTS", "src"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+    let res = run_cmd(vec!["SCARD", "dst"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(2));
+}
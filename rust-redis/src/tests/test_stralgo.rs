@@ -1,5 +1,6 @@
 use crate::cmd::{ServerContext, process_frame};
 use crate::resp::Resp;
+use crate::tests::helper::run_cmd;
 use bytes::Bytes;
 use tokio;
 
@@ -70,3 +71,125 @@ async fn test_stralgo_lcs_keys() {
         _ => panic!("Expected bulk string 'mytext'"),
     }
 }
+
+fn range(start: i64, end: i64) -> Resp {
+    Resp::Array(Some(vec![Resp::Integer(start), Resp::Integer(end)]))
+}
+
+fn idx_reply(matches: Vec<Vec<Resp>>, len: i64) -> Resp {
+    Resp::Array(Some(vec![
+        Resp::BulkString(Some(Bytes::from("matches"))),
+        Resp::Array(Some(
+            matches.into_iter().map(|m| Resp::Array(Some(m))).collect(),
+        )),
+        Resp::BulkString(Some(Bytes::from("len"))),
+        Resp::Integer(len),
+    ]))
+}
+
+#[tokio::test]
+async fn test_lcs_docs_example() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    run_cmd(
+        vec!["MSET", "key1", "ohmytext", "key2", "mynewtext"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    let res = run_cmd(vec!["LCS", "key1", "key2"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("mytext"))));
+    let res = run_cmd(
+        vec!["LCS", "key1", "key2", "LEN"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(6));
+
+    // Ranges come from the end of the strings backwards
+    let res = run_cmd(
+        vec!["LCS", "key1", "key2", "IDX"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        idx_reply(
+            vec![
+                vec![range(4, 7), range(5, 8)],
+                vec![range(2, 3), range(0, 1)]
+            ],
+            6
+        )
+    );
+    let res = run_cmd(
+        vec![
+            "LCS",
+            "key1",
+            "key2",
+            "IDX",
+            "MINMATCHLEN",
+            "4",
+            "WITHMATCHLEN",
+        ],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        idx_reply(vec![vec![range(4, 7), range(5, 8), Resp::Integer(4)]], 6)
+    );
+
+    // STRALGO reads the same keys from its KEYS segment
+    let res = run_cmd(
+        vec![
+            "STRALGO",
+            "LCS",
+            "KEYS",
+            "key1",
+            "key2",
+            "IDX",
+            "WITHMATCHLEN",
+        ],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        idx_reply(
+            vec![
+                vec![range(4, 7), range(5, 8), Resp::Integer(4)],
+                vec![range(2, 3), range(0, 1), Resp::Integer(2)],
+            ],
+            6
+        )
+    );
+    let items: Vec<Resp> = ["STRALGO", "LCS", "KEYS", "key1", "key2"]
+        .iter()
+        .map(|a| Resp::BulkString(Some(Bytes::from(*a))))
+        .collect();
+    let keys = crate::cmd::get_command_keys(crate::cmd::Command::StrAlgo, &items);
+    assert_eq!(keys, vec![b"key1".as_slice(), b"key2".as_slice()]);
+
+    // Missing keys read as empty strings; other types are rejected
+    let res = run_cmd(vec!["LCS", "key1", "missing"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::new())));
+    run_cmd(vec!["RPUSH", "list", "a"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(vec!["LCS", "key1", "list"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR The specified keys must contain string values".to_string())
+    );
+    let res = run_cmd(
+        vec!["LCS", "key1", "key2", "LEN", "IDX"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert!(matches!(res, Resp::Error(_)), "{:?}", res);
+}
@@ -1,5 +1,6 @@
 #[cfg(test)]
 mod tests {
+    use crate::cmd::process_frame;
     use crate::resp::Resp;
     use crate::tests::helper::{create_connection_context, create_server_context, run_cmd};
     use bytes::Bytes;
@@ -145,4 +146,231 @@ mod tests {
             _ => panic!("Expected Array of keys, got {:?}", res),
         }
     }
+
+    #[tokio::test]
+    async fn test_xsetid() {
+        let server_ctx = create_server_context();
+        let mut conn_ctx = create_connection_context();
+        let err = |msg: &str| Resp::Error(msg.to_string());
+
+        let res = run_cmd(vec!["XSETID", "s", "5-0"], &mut conn_ctx, &server_ctx).await;
+        assert_eq!(res, err("ERR no such key"));
+
+        run_cmd(
+            vec!["XADD", "s", "1-1", "f", "v"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        run_cmd(
+            vec!["XADD", "s", "2-1", "f", "v"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+
+        // The top entry pins the ID until it is deleted
+        let res = run_cmd(vec!["XSETID", "s", "1-5"], &mut conn_ctx, &server_ctx).await;
+        assert_eq!(
+            res,
+            err("ERR The ID specified in XSETID is smaller than the target stream top item")
+        );
+        run_cmd(vec!["XDEL", "s", "2-1"], &mut conn_ctx, &server_ctx).await;
+        let res = run_cmd(vec!["XSETID", "s", "1-5"], &mut conn_ctx, &server_ctx).await;
+        assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+
+        // A bare millisecond value means seq 0; XADD * continues past it
+        let res = run_cmd(
+            vec!["XSETID", "s", "99999999999999", "ENTRIESADDED", "10"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+        let res = run_cmd(vec!["XADD", "s", "*", "f", "v"], &mut conn_ctx, &server_ctx).await;
+        assert_eq!(res, Resp::BulkString(Some(Bytes::from("99999999999999-1"))));
+
+        let res = run_cmd(
+            vec!["XSETID", "s", "99999999999999-5", "ENTRIESADDED", "1"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        assert_eq!(
+            res,
+            err(
+                "ERR The entries_added specified in XSETID is smaller than the target stream length"
+            )
+        );
+        let res = run_cmd(
+            vec![
+                "XSETID",
+                "s",
+                "99999999999999-5",
+                "MAXDELETEDID",
+                "99999999999999-6",
+            ],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        assert_eq!(
+            res,
+            err("ERR The ID specified in XSETID is smaller than the provided max_deleted_entry_id")
+        );
+        let res = run_cmd(vec!["XSETID", "s", "abc"], &mut conn_ctx, &server_ctx).await;
+        assert_eq!(res, err("ERR invalid stream ID"));
+        let res = run_cmd(
+            vec!["XSETID", "s", "1-1", "FOO"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        assert_eq!(res, err("ERR syntax error"));
+
+        // The propagated form always carries the resolved fields
+        let req = Resp::Array(Some(
+            ["XSETID", "s", "99999999999999-7"]
+                .iter()
+                .map(|a| Resp::BulkString(Some(Bytes::from(a.to_string()))))
+                .collect(),
+        ));
+        let (res, log) = process_frame(req, &mut conn_ctx, &server_ctx).await;
+        assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+        let expected: Vec<Resp> = [
+            "XSETID",
+            "s",
+            "99999999999999-7",
+            "ENTRIESADDED",
+            "11",
+            "MAXDELETEDID",
+            "2-1",
+        ]
+        .iter()
+        .map(|a| Resp::BulkString(Some(Bytes::from(a.to_string()))))
+        .collect();
+        assert_eq!(log, Some(Resp::Array(Some(expected))));
+    }
+
+    #[tokio::test]
+    async fn test_xgroup_delconsumer_and_setid() {
+        let server_ctx = create_server_context();
+        let mut conn_ctx = create_connection_context();
+        let frame = |args: &[&str]| {
+            Resp::Array(Some(
+                args.iter()
+                    .map(|a| Resp::BulkString(Some(Bytes::from(a.to_string()))))
+                    .collect(),
+            ))
+        };
+
+        run_cmd(
+            vec!["XADD", "s", "1-1", "f", "v"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        run_cmd(
+            vec!["XADD", "s", "1-2", "f", "v"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        run_cmd(
+            vec!["XGROUP", "CREATE", "s", "g", "0"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        run_cmd(
+            vec!["XREADGROUP", "GROUP", "g", "c1", "STREAMS", "s", ">"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+
+        // DELCONSUMER reports and drops the consumer's pending entries
+        let (res, log) = process_frame(
+            frame(&["XGROUP", "DELCONSUMER", "s", "g", "c1"]),
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        assert_eq!(res, Resp::Integer(2));
+        assert_eq!(log, Some(frame(&["XGROUP", "DELCONSUMER", "s", "g", "c1"])));
+        match run_cmd(vec!["XPENDING", "s", "g"], &mut conn_ctx, &server_ctx).await {
+            Resp::Array(Some(arr)) => assert_eq!(arr[0], Resp::Integer(0)),
+            other => panic!("Expected XPENDING summary, got {:?}", other),
+        }
+        let res = run_cmd(
+            vec!["XGROUP", "DELCONSUMER", "s", "g", "c1"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        assert_eq!(res, Resp::Integer(0));
+        let res = run_cmd(
+            vec!["XGROUP", "DELCONSUMER", "s", "nogroup", "c1"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        assert_eq!(
+            res,
+            Resp::Error("NOGROUP No such consumer group".to_string())
+        );
+
+        // SETID rewinds the group so the entries are delivered again
+        let res = run_cmd(
+            vec!["XGROUP", "SETID", "s", "g", "0", "ENTRIESREAD", "0"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+        match run_cmd(
+            vec!["XREADGROUP", "GROUP", "g", "c2", "STREAMS", "s", ">"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await
+        {
+            Resp::Array(Some(streams)) => match &streams[0] {
+                Resp::Array(Some(stream)) => match &stream[1] {
+                    Resp::Array(Some(entries)) => assert_eq!(entries.len(), 2),
+                    other => panic!("Expected entries, got {:?}", other),
+                },
+                other => panic!("Expected stream reply, got {:?}", other),
+            },
+            other => panic!("Expected XREADGROUP reply, got {:?}", other),
+        }
+
+        // `$` is propagated as the concrete ID
+        let (res, log) = process_frame(
+            frame(&["XGROUP", "SETID", "s", "g", "$"]),
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        assert_eq!(res, Resp::SimpleString(Bytes::from("OK")));
+        assert_eq!(log, Some(frame(&["XGROUP", "SETID", "s", "g", "1-2"])));
+
+        let res = run_cmd(
+            vec!["XGROUP", "SETID", "s", "g", "0", "ENTRIESREAD", "x"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        assert_eq!(
+            res,
+            Resp::Error("ERR value is not an integer or out of range".to_string())
+        );
+        let res = run_cmd(
+            vec!["XGROUP", "SETID", "nokey", "g", "0"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        assert_eq!(res, Resp::Error("ERR no such key".to_string()));
+    }
 }
@@ -124,6 +124,9 @@ async fn test_sunionstore() {
         Resp::Error(msg) => assert!(msg.contains("WRONGTYPE")),
         _ => panic!("Expected Error WRONGTYPE"),
     }
+    // The failed store leaves the destination as it was
+    let res = run_cmd(vec!["SCARD", "dest"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(2));
 
     // 5. Destination overwrite from different type
     run_cmd(vec!["SET", "dest_str", "value"], &mut conn_ctx, &server_ctx).await;
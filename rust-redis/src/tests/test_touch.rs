@@ -116,3 +116,56 @@ async fn test_touch() {
         _ => panic!("Expected Array from COMMAND, got {:?}", resp),
     }
 }
+
+#[tokio::test]
+async fn test_touch_refreshes_access_time() {
+    use crate::tests::helper::run_cmd;
+    use std::sync::atomic::Ordering;
+
+    let server_ctx = create_server_context();
+    let mut conn_ctx = create_connection_context();
+
+    run_cmd(vec!["SET", "k", "v"], &mut conn_ctx, &server_ctx).await;
+    {
+        let db = server_ctx.databases[0].read().unwrap();
+        let mut entry = db.get_mut(b"k".as_slice()).unwrap();
+        entry.lru -= 100;
+    }
+    match run_cmd(vec!["OBJECT", "IDLETIME", "k"], &mut conn_ctx, &server_ctx).await {
+        Resp::Integer(idle) => assert!(idle >= 100, "idle = {}", idle),
+        other => panic!("Expected Integer, got {:?}", other),
+    }
+    let freq_before = match run_cmd(vec!["OBJECT", "FREQ", "k"], &mut conn_ctx, &server_ctx).await {
+        Resp::Integer(f) => f,
+        other => panic!("Expected Integer, got {:?}", other),
+    };
+
+    let dirty_before = server_ctx.persist.dirty.load(Ordering::Relaxed);
+    let resp = run_cmd(vec!["TOUCH", "k", "missing"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(resp, Resp::Integer(1));
+    // TOUCH is a read: nothing is marked dirty
+    assert_eq!(
+        server_ctx.persist.dirty.load(Ordering::Relaxed),
+        dirty_before
+    );
+
+    match run_cmd(vec!["OBJECT", "IDLETIME", "k"], &mut conn_ctx, &server_ctx).await {
+        Resp::Integer(idle) => assert!(idle <= 1, "idle = {}", idle),
+        other => panic!("Expected Integer, got {:?}", other),
+    }
+    match run_cmd(vec!["OBJECT", "FREQ", "k"], &mut conn_ctx, &server_ctx).await {
+        Resp::Integer(f) => assert!(f > freq_before, "freq {} -> {}", freq_before, f),
+        other => panic!("Expected Integer, got {:?}", other),
+    }
+
+    assert_eq!(server_ctx.mem.keyspace_hits.load(Ordering::Relaxed), 1);
+    assert_eq!(server_ctx.mem.keyspace_misses.load(Ordering::Relaxed), 1);
+    match run_cmd(vec!["INFO", "stats"], &mut conn_ctx, &server_ctx).await {
+        Resp::BulkString(Some(b)) => {
+            let info = String::from_utf8_lossy(&b);
+            assert!(info.contains("keyspace_hits:1\r\n"), "{}", info);
+            assert!(info.contains("keyspace_misses:1\r\n"), "{}", info);
+        }
+        other => panic!("Expected BulkString, got {:?}", other),
+    }
+}
@@ -115,3 +115,56 @@ async fn test_watch_triggered_by_exec() {
 
     assert_eq!(res, Resp::Array(None));
 }
+
+#[tokio::test]
+async fn test_disconnect_releases_only_own_registrations() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn1 = crate::tests::helper::create_connection_context();
+    let mut conn2 = crate::tests::helper::create_connection_context();
+    conn1.id = 1;
+    conn2.id = 2;
+    server_ctx
+        .clients_ctx.client_count
+        .store(2, std::sync::atomic::Ordering::Relaxed);
+
+    run_cmd(vec!["CLIENT", "TRACKING", "ON"], &mut conn1, &server_ctx).await;
+    run_cmd(vec!["CLIENT", "TRACKING", "ON"], &mut conn2, &server_ctx).await;
+
+    // Client 1 tracks and watches many keys, client 2 shares a few of them
+    for i in 0..500 {
+        let key = format!("key:{}", i);
+        run_cmd(vec!["GET", &key], &mut conn1, &server_ctx).await;
+        run_cmd(vec!["WATCH", &key], &mut conn1, &server_ctx).await;
+    }
+    for i in 0..10 {
+        let key = format!("key:{}", i);
+        run_cmd(vec!["GET", &key], &mut conn2, &server_ctx).await;
+        run_cmd(vec!["WATCH", &key], &mut conn2, &server_ctx).await;
+    }
+    assert_eq!(server_ctx.clients_ctx.tracking_clients.len(), 500);
+    assert_eq!(server_ctx.clients_ctx.watched_clients.len(), 500);
+
+    crate::cmd::release_client(&mut conn1, &server_ctx);
+
+    // Entries only client 1 registered are gone instead of lingering empty
+    assert_eq!(server_ctx.clients_ctx.tracking_clients.len(), 10);
+    assert_eq!(server_ctx.clients_ctx.watched_clients.len(), 10);
+    assert!(!server_ctx.clients_ctx.client_tracked_keys.contains_key(&1));
+    for i in 0..10 {
+        let map_key = (0, format!("key:{}", i).into_bytes());
+        let tracking = server_ctx.clients_ctx.tracking_clients.get(&map_key).unwrap();
+        assert_eq!(tracking.iter().copied().collect::<Vec<_>>(), vec![2]);
+        let watching = server_ctx.clients_ctx.watched_clients.get(&map_key).unwrap();
+        assert_eq!(watching.iter().copied().collect::<Vec<_>>(), vec![2]);
+    }
+    assert_eq!(
+        server_ctx.clients_ctx.client_tracked_keys.get(&2).unwrap().len(),
+        10
+    );
+    assert_eq!(
+        server_ctx
+            .clients_ctx.client_count
+            .load(std::sync::atomic::Ordering::Relaxed),
+        1
+    );
+}
@@ -0,0 +1,170 @@
+use crate::cmd::{ConnectionContext, ServerContext};
+use crate::resp::Resp;
+use crate::tests::helper::run_cmd;
+
+fn codes(values: &[i64]) -> Resp {
+    Resp::Array(Some(values.iter().map(|v| Resp::Integer(*v)).collect()))
+}
+
+async fn pending_count(
+    group: &str,
+    conn_ctx: &mut ConnectionContext,
+    server_ctx: &ServerContext,
+) -> i64 {
+    match run_cmd(vec!["XPENDING", "s", group], conn_ctx, server_ctx).await {
+        Resp::Array(Some(items)) => match items[0] {
+            Resp::Integer(n) => n,
+            ref other => panic!("expected count, got {:?}", other),
+        },
+        other => panic!("expected XPENDING summary, got {:?}", other),
+    }
+}
+
+async fn setup(conn_ctx: &mut ConnectionContext, server_ctx: &ServerContext) {
+    for id in ["1-1", "1-2", "1-3"] {
+        run_cmd(vec!["XADD", "s", id, "f", "v"], conn_ctx, server_ctx).await;
+    }
+    for group in ["g1", "g2"] {
+        run_cmd(
+            vec!["XGROUP", "CREATE", "s", group, "0-0"],
+            conn_ctx,
+            server_ctx,
+        )
+        .await;
+        run_cmd(
+            vec!["XREADGROUP", "GROUP", group, "c", "STREAMS", "s", ">"],
+            conn_ctx,
+            server_ctx,
+        )
+        .await;
+    }
+}
+
+#[tokio::test]
+async fn test_xackdel_acked_waits_for_all_groups() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    setup(&mut conn_ctx, &server_ctx).await;
+
+    // Pending in both groups: ACKED refuses to delete
+    let res = run_cmd(
+        vec!["XDELEX", "s", "ACKED", "IDS", "1", "1-1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, codes(&[2]));
+
+    // Acked by g1 only: still referenced by g2
+    let res = run_cmd(
+        vec!["XACKDEL", "s", "g1", "ACKED", "IDS", "2", "1-1", "9-9"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, codes(&[2, -1]));
+    assert_eq!(pending_count("g1", &mut conn_ctx, &server_ctx).await, 2);
+
+    // Acked by g2 as well: now fully acknowledged and deleted
+    let res = run_cmd(
+        vec!["XACKDEL", "s", "g2", "ACKED", "IDS", "1", "1-1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, codes(&[1]));
+    let res = run_cmd(vec!["XLEN", "s"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(2));
+
+    // Not yet delivered to the groups counts as referenced
+    run_cmd(
+        vec!["XADD", "s", "2-1", "f", "v"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(
+        vec!["XDELEX", "s", "ACKED", "IDS", "1", "2-1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, codes(&[2]));
+}
+
+#[tokio::test]
+async fn test_xdelex_keepref_and_delref() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    setup(&mut conn_ctx, &server_ctx).await;
+
+    // KEEPREF (the default) leaves the PEL entries behind
+    let res = run_cmd(
+        vec!["XDELEX", "s", "IDS", "2", "1-1", "5-5"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, codes(&[1, -1]));
+    assert_eq!(pending_count("g1", &mut conn_ctx, &server_ctx).await, 3);
+
+    // DELREF clears them from every group
+    let res = run_cmd(
+        vec!["XDELEX", "s", "DELREF", "IDS", "1", "1-2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, codes(&[1]));
+    assert_eq!(pending_count("g1", &mut conn_ctx, &server_ctx).await, 2);
+    assert_eq!(pending_count("g2", &mut conn_ctx, &server_ctx).await, 2);
+
+    // XACKDEL with DELREF drops the other group's reference too
+    let res = run_cmd(
+        vec!["XACKDEL", "s", "g1", "DELREF", "IDS", "1", "1-3"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, codes(&[1]));
+    assert_eq!(pending_count("g2", &mut conn_ctx, &server_ctx).await, 1);
+
+    let res = run_cmd(
+        vec!["XDELEX", "missing", "IDS", "1", "1-1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, codes(&[-1]));
+}
+
+#[tokio::test]
+async fn test_xackdel_errors() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    setup(&mut conn_ctx, &server_ctx).await;
+
+    let cases = vec![
+        vec!["XDELEX", "s", "IDS", "2", "1-1"],
+        vec!["XDELEX", "s", "IDS", "0", "1-1"],
+        vec!["XDELEX", "s", "BOGUS", "IDS", "1", "1-1"],
+        vec!["XDELEX", "s", "IDS", "1", "not-an-id"],
+        vec!["XACKDEL", "s", "nogroup", "IDS", "1", "1-1"],
+        vec!["XACKDEL", "missing", "g1", "IDS", "1", "1-1"],
+    ];
+    for cmd in cases {
+        let res = run_cmd(cmd.clone(), &mut conn_ctx, &server_ctx).await;
+        assert!(matches!(res, Resp::Error(_)), "{:?} -> {:?}", cmd, res);
+    }
+    let res = run_cmd(
+        vec!["XACKDEL", "s", "nogroup", "IDS", "1", "1-1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert!(matches!(res, Resp::Error(ref e) if e.starts_with("NOGROUP")));
+
+    // Nothing was removed along the way
+    let res = run_cmd(vec!["XLEN", "s"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(3));
+}
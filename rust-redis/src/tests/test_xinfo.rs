@@ -87,3 +87,81 @@ async fn test_xinfo() {
     .await;
     assert_eq!(res, Resp::Array(Some(vec![])));
 }
+
+#[tokio::test]
+async fn test_xinfo_consumers_idle_and_inactive() {
+    use crate::cmd::{ConnectionContext, ServerContext};
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    // Returns (idle, inactive) for consumer "c" in group "g"
+    async fn times(conn_ctx: &mut ConnectionContext, server_ctx: &ServerContext) -> (i64, i64) {
+        let res = run_cmd(vec!["XINFO", "CONSUMERS", "s", "g"], conn_ctx, server_ctx).await;
+        let consumers = match res {
+            Resp::Array(Some(c)) => c,
+            other => panic!("expected consumers array, got {:?}", other),
+        };
+        let fields = match &consumers[0] {
+            Resp::Array(Some(f)) => f.clone(),
+            other => panic!("expected consumer fields, got {:?}", other),
+        };
+        let field = |name: &str| {
+            let pos = fields
+                .iter()
+                .position(|f| matches!(f, Resp::SimpleString(s) if s.as_ref() == name.as_bytes()))
+                .unwrap_or_else(|| panic!("missing field {}", name));
+            match fields[pos + 1] {
+                Resp::Integer(n) => n,
+                ref other => panic!("expected integer for {}, got {:?}", name, other),
+            }
+        };
+        (field("idle"), field("inactive"))
+    }
+
+    run_cmd(
+        vec!["XADD", "s", "1-1", "f", "v"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    run_cmd(
+        vec!["XGROUP", "CREATE", "s", "g", "0-0"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    run_cmd(
+        vec!["XGROUP", "CREATECONSUMER", "s", "g", "c"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    // Never read anything yet
+    let (_, inactive) = times(&mut conn_ctx, &server_ctx).await;
+    assert_eq!(inactive, -1);
+
+    // A read that returns data sets both clocks
+    run_cmd(
+        vec!["XREADGROUP", "GROUP", "g", "c", "STREAMS", "s", ">"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    tokio::time::sleep(std::time::Duration::from_millis(60)).await;
+    let (idle, inactive) = times(&mut conn_ctx, &server_ctx).await;
+    assert!(idle >= 50, "idle {}", idle);
+    assert!(inactive >= 50, "inactive {}", inactive);
+
+    // A read with nothing new only counts as being seen
+    run_cmd(
+        vec!["XREADGROUP", "GROUP", "g", "c", "STREAMS", "s", ">"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let (idle, inactive) = times(&mut conn_ctx, &server_ctx).await;
+    assert!(idle < 50, "idle {}", idle);
+    assert!(inactive >= 50, "inactive {}", inactive);
+}
@@ -114,3 +114,163 @@ async fn test_xtrim_minid() {
     let res = run_cmd(vec!["XLEN", "mystream"], &mut conn_ctx, &server_ctx).await;
     assert_eq!(res, Resp::Integer(0));
 }
+
+#[tokio::test]
+async fn test_trim_limit_requires_approximate() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    for i in 1..=20 {
+        let id = format!("{}-0", i);
+        run_cmd(
+            vec!["XADD", "k", id.as_str(), "f", "v"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+    }
+
+    let limit_err = Resp::Error(
+        "ERR syntax error, LIMIT cannot be used without the special ~ option".to_string(),
+    );
+    for cmd in [
+        vec!["XTRIM", "k", "MAXLEN", "=", "5", "LIMIT", "10"],
+        vec!["XTRIM", "k", "MAXLEN", "5", "LIMIT", "10"],
+        vec![
+            "XADD", "k", "MAXLEN", "=", "5", "LIMIT", "10", "*", "f", "v",
+        ],
+    ] {
+        let res = run_cmd(cmd.clone(), &mut conn_ctx, &server_ctx).await;
+        assert_eq!(res, limit_err, "{:?}", cmd);
+    }
+    let res = run_cmd(vec!["XLEN", "k"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(20));
+
+    // Approximate trimming stops after LIMIT entries
+    let res = run_cmd(
+        vec!["XTRIM", "k", "MAXLEN", "~", "5", "LIMIT", "10"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(10));
+
+    // XADD trims after appending
+    run_cmd(
+        vec!["XADD", "k", "MAXLEN", "~", "5", "LIMIT", "2", "*", "f", "v"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(vec!["XLEN", "k"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(9));
+
+    run_cmd(
+        vec!["XADD", "k", "NOMKSTREAM", "MAXLEN", "3", "*", "f", "v"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(vec!["XLEN", "k"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(3));
+
+    // Only 20-0 is below the threshold; the `*` IDs are wall-clock times
+    let res = run_cmd(
+        vec!["XTRIM", "k", "MINID", "=", "100-0"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(1));
+}
+
+#[tokio::test]
+async fn test_trim_propagates_resolved_clause() {
+    use crate::cmd::process_frame;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    let replica_ctx = crate::tests::helper::create_server_context();
+    let mut replica_conn = crate::tests::helper::create_connection_context();
+
+    let frame = |args: &[&str]| {
+        Resp::Array(Some(
+            args.iter()
+                .map(|a| Resp::BulkString(Some(Bytes::from(a.to_string()))))
+                .collect(),
+        ))
+    };
+
+    let mut logged = Vec::new();
+    for i in 1..=10 {
+        let id = format!("{}-0", i);
+        let (_, log) = process_frame(
+            frame(&["XADD", "k", id.as_str(), "f", "v"]),
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        logged.push(log.unwrap());
+    }
+
+    // Approximate and LIMITed trims go out as the exact trim they amounted to
+    for (cmd, res, expected_log) in [
+        (
+            vec![
+                "XADD", "k", "MAXLEN", "~", "3", "LIMIT", "2", "11-0", "f", "v",
+            ],
+            Resp::BulkString(Some(Bytes::from("11-0"))),
+            vec!["XADD", "k", "MAXLEN", "=", "9", "11-0", "f", "v"],
+        ),
+        (
+            vec!["XTRIM", "k", "MINID", "~", "8-0", "LIMIT", "3"],
+            Resp::Integer(3),
+            vec!["XTRIM", "k", "MINID", "=", "6-0"],
+        ),
+        (
+            vec!["XTRIM", "k", "MAXLEN", "4"],
+            Resp::Integer(2),
+            vec!["XTRIM", "k", "MAXLEN", "=", "4"],
+        ),
+        (
+            vec![
+                "XADD",
+                "k",
+                "NOMKSTREAM",
+                "MINID",
+                "100-0",
+                "12-0",
+                "f",
+                "v",
+            ],
+            Resp::BulkString(Some(Bytes::from("12-0"))),
+            vec![
+                "XADD",
+                "k",
+                "NOMKSTREAM",
+                "MINID",
+                "=",
+                "100-0",
+                "12-0",
+                "f",
+                "v",
+            ],
+        ),
+    ] {
+        let (reply, log) = process_frame(frame(&cmd), &mut conn_ctx, &server_ctx).await;
+        assert_eq!(reply, res, "{:?}", cmd);
+        assert_eq!(log, Some(frame(&expected_log)), "{:?}", cmd);
+        logged.push(log.unwrap());
+    }
+
+    // Replaying the log reproduces the stream
+    for log in logged {
+        process_frame(log, &mut replica_conn, &replica_ctx).await;
+    }
+    for cmd in [vec!["XLEN", "k"], vec!["XRANGE", "k", "-", "+"]] {
+        assert_eq!(
+            run_cmd(cmd.clone(), &mut replica_conn, &replica_ctx).await,
+            run_cmd(cmd, &mut conn_ctx, &server_ctx).await
+        );
+    }
+}
@@ -88,3 +88,33 @@ async fn test_zcount() {
     .await;
     assert_eq!(res, Resp::Integer(5));
 }
+
+#[tokio::test]
+async fn test_zcount_infinite_and_exclusive_bounds() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    run_cmd(
+        vec!["ZADD", "z", "-inf", "lo", "1", "a", "2", "b", "+inf", "hi"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    let cases = [
+        ("-inf", "+inf", 4),
+        ("(-inf", "(+inf", 2),
+        ("(1", "+inf", 2),
+        ("-inf", "(1", 1),
+        ("1", "(2", 1),
+        ("(1", "2", 1),
+        ("(2", "(2", 0),
+        ("2", "2", 1),
+        ("(2", "+inf", 1),
+        ("+inf", "+inf", 1),
+    ];
+    for (min, max, expected) in cases {
+        let res = run_cmd(vec!["ZCOUNT", "z", min, max], &mut conn_ctx, &server_ctx).await;
+        assert_eq!(res, Resp::Integer(expected), "ZCOUNT z {} {}", min, max);
+    }
+}
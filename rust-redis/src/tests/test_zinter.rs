@@ -212,3 +212,90 @@ async fn test_zinter() {
         _ => panic!("Expected empty Array, got {:?}", res),
     }
 }
+
+#[tokio::test]
+async fn test_zintercard() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    run_cmd(
+        vec!["ZADD", "z1", "1", "a", "2", "b", "3", "c"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    run_cmd(
+        vec!["ZADD", "z2", "1", "b", "2", "c", "3", "d"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    let res = run_cmd(
+        vec!["ZINTERCARD", "2", "z1", "z2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(2));
+    let res = run_cmd(
+        vec!["ZINTERCARD", "2", "z1", "z2", "LIMIT", "1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(1));
+    let res = run_cmd(
+        vec!["ZINTERCARD", "1", "z1", "LIMIT", "2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(2));
+    let res = run_cmd(
+        vec!["ZINTERCARD", "2", "z1", "missing"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(0));
+
+    let res = run_cmd(vec!["ZINTERCARD", "0", "z1"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR numkeys should be greater than 0".to_string())
+    );
+
+    run_cmd(vec!["SET", "str", "v"], &mut conn_ctx, &server_ctx).await;
+    match run_cmd(
+        vec!["ZINTERCARD", "2", "z1", "str"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await
+    {
+        Resp::Error(e) => assert!(e.starts_with("WRONGTYPE"), "{}", e),
+        other => panic!("Expected WRONGTYPE, got {:?}", other),
+    }
+
+    // The type check covers every key, even after a missing one
+    match run_cmd(
+        vec!["ZINTERCARD", "2", "missing", "str"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await
+    {
+        Resp::Error(e) => assert!(e.starts_with("WRONGTYPE"), "{}", e),
+        other => panic!("Expected WRONGTYPE, got {:?}", other),
+    }
+
+    // LIMIT stops the last pass early, also for a single key
+    let res = run_cmd(
+        vec!["ZINTERCARD", "1", "z1", "LIMIT", "1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(1));
+}
@@ -79,3 +79,32 @@ async fn test_zlexcount() {
     .await;
     assert_eq!(res, Resp::Integer(0));
 }
+
+#[tokio::test]
+async fn test_zlexcount_stops_past_max() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    run_cmd(
+        vec![
+            "ZADD", "z", "0", "a", "0", "b", "0", "c", "0", "d", "0", "e",
+        ],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    let cases = [
+        ("-", "(c", 2),
+        ("-", "[c", 3),
+        ("(a", "(b", 0),
+        ("[e", "+", 1),
+        ("(e", "+", 0),
+        ("-", "-", 0),
+        ("+", "+", 0),
+    ];
+    for (min, max, expected) in cases {
+        let res = run_cmd(vec!["ZLEXCOUNT", "z", min, max], &mut conn_ctx, &server_ctx).await;
+        assert_eq!(res, Resp::Integer(expected), "ZLEXCOUNT z {} {}", min, max);
+    }
+}
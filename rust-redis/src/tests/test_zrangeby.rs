@@ -203,3 +203,238 @@ async fn test_zrangebylex() {
         _ => panic!("Expected Array, got {:?}", res),
     }
 }
+
+#[tokio::test]
+async fn test_limit_negative_count_and_large_offset() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    let members = |names: &[&str]| {
+        Resp::Array(Some(
+            names
+                .iter()
+                .map(|n| Resp::BulkString(Some(Bytes::from(n.to_string()))))
+                .collect(),
+        ))
+    };
+
+    run_cmd(
+        vec![
+            "ZADD", "z", "1", "a", "2", "b", "3", "c", "4", "d", "5", "e",
+        ],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    run_cmd(
+        vec![
+            "ZADD", "lex", "0", "a", "0", "b", "0", "c", "0", "d", "0", "e",
+        ],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    // A negative count takes everything after the offset
+    let cases: [&[&str]; 4] = [
+        &["ZRANGEBYSCORE", "z", "-inf", "+inf", "LIMIT", "2", "-1"],
+        &["ZRANGEBYLEX", "lex", "-", "+", "LIMIT", "2", "-5"],
+        &["ZRANGE", "z", "-inf", "+inf", "BYSCORE", "LIMIT", "2", "-1"],
+        &["ZRANGE", "lex", "-", "+", "BYLEX", "LIMIT", "2", "-1"],
+    ];
+    for cmd in cases {
+        let res = run_cmd(cmd.to_vec(), &mut conn_ctx, &server_ctx).await;
+        assert_eq!(res, members(&["c", "d", "e"]), "{:?}", cmd);
+    }
+    let res = run_cmd(
+        vec!["ZRANGEBYSCORE", "z", "(1", "4", "LIMIT", "1", "-1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, members(&["c", "d"]));
+
+    // An offset past the matching elements yields nothing
+    let cases: [&[&str]; 3] = [
+        &["ZRANGEBYSCORE", "z", "-inf", "+inf", "LIMIT", "100", "5"],
+        &["ZRANGEBYLEX", "lex", "-", "+", "LIMIT", "100", "5"],
+        &[
+            "ZRANGE", "z", "-inf", "+inf", "BYSCORE", "LIMIT", "100", "5",
+        ],
+    ];
+    for cmd in cases {
+        let res = run_cmd(cmd.to_vec(), &mut conn_ctx, &server_ctx).await;
+        assert_eq!(res, members(&[]), "{:?}", cmd);
+    }
+}
+
+#[tokio::test]
+async fn test_zrange_unified_byscore_bylex_rev() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    let bulks = |items: &[&str]| {
+        Resp::Array(Some(
+            items
+                .iter()
+                .map(|s| Resp::BulkString(Some(Bytes::from(s.to_string()))))
+                .collect(),
+        ))
+    };
+
+    run_cmd(
+        vec![
+            "ZADD", "k", "1", "a", "2", "b", "3", "c", "4", "d", "5", "e",
+        ],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    let res = run_cmd(
+        vec!["ZRANGE", "k", "(1", "5", "BYSCORE", "LIMIT", "0", "2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulks(&["b", "c"]));
+    let res = run_cmd(
+        vec!["ZRANGEBYSCORE", "k", "(1", "5", "LIMIT", "0", "2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulks(&["b", "c"]));
+
+    // REV takes the high end first
+    let res = run_cmd(
+        vec![
+            "ZRANGE",
+            "k",
+            "5",
+            "(1",
+            "BYSCORE",
+            "REV",
+            "LIMIT",
+            "0",
+            "2",
+            "WITHSCORES",
+        ],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulks(&["e", "5", "d", "4"]));
+
+    let res = run_cmd(
+        vec!["ZRANGE", "k", "[d", "-", "BYLEX", "REV"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulks(&["d", "c", "b", "a"]));
+
+    // Only ZRANGE itself takes BYSCORE, BYLEX and REV
+    let res = run_cmd(
+        vec!["ZRANGEBYSCORE", "k", "0", "1", "REV"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Error("ERR syntax error".to_string()));
+    let res = run_cmd(
+        vec!["ZRANGEBYLEX", "k", "-", "+", "BYSCORE"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Error("ERR syntax error".to_string()));
+}
+
+#[tokio::test]
+async fn test_zrevrangebyscore_and_zrevrangebylex() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    let bulks = |items: &[&str]| {
+        Resp::Array(Some(
+            items
+                .iter()
+                .map(|s| Resp::BulkString(Some(Bytes::from(s.to_string()))))
+                .collect(),
+        ))
+    };
+
+    run_cmd(
+        vec!["ZADD", "s", "1", "a", "2", "b", "3", "c", "4", "d"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    run_cmd(
+        vec!["ZADD", "l", "0", "a", "0", "b", "0", "c", "0", "d"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    // Highest score first, with the max bound given first
+    let res = run_cmd(
+        vec!["ZREVRANGEBYSCORE", "s", "+inf", "-inf"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulks(&["d", "c", "b", "a"]));
+    let res = run_cmd(
+        vec!["ZREVRANGEBYSCORE", "s", "(4", "2", "WITHSCORES"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulks(&["c", "3", "b", "2"]));
+    let res = run_cmd(
+        vec!["ZREVRANGEBYSCORE", "s", "2", "4"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulks(&[]));
+
+    let res = run_cmd(
+        vec!["ZREVRANGEBYLEX", "l", "[c", "-"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulks(&["c", "b", "a"]));
+    let res = run_cmd(
+        vec!["ZREVRANGEBYLEX", "l", "+", "-", "LIMIT", "1", "2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulks(&["c", "b"]));
+    let res = run_cmd(
+        vec!["ZREVRANGEBYLEX", "l", "-", "+"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulks(&[]));
+
+    let res = run_cmd(
+        vec!["ZREVRANGEBYLEX", "l", "+", "-", "WITHSCORES"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert!(matches!(res, Resp::Error(_)), "{:?}", res);
+    let res = run_cmd(
+        vec!["ZREVRANGEBYLEX", "l", "c", "-"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR min or max not valid string range item".to_string())
+    );
+}
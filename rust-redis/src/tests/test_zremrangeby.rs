@@ -0,0 +1,163 @@
+use crate::resp::Resp;
+use crate::tests::helper::run_cmd;
+
+fn members(res: Resp) -> Vec<String> {
+    match res {
+        Resp::Array(Some(arr)) => arr
+            .into_iter()
+            .map(|r| match r {
+                Resp::BulkString(Some(b)) => String::from_utf8_lossy(&b).to_string(),
+                _ => panic!("Expected BulkString, got {:?}", r),
+            })
+            .collect(),
+        _ => panic!("Expected Array, got {:?}", res),
+    }
+}
+
+#[tokio::test]
+async fn test_zremrangebyrank() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    run_cmd(
+        vec![
+            "ZADD", "z", "1", "a", "2", "b", "3", "c", "4", "d", "5", "e",
+        ],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    let res = run_cmd(
+        vec!["ZREMRANGEBYRANK", "z", "1", "2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(2));
+    let res = run_cmd(vec!["ZRANGE", "z", "0", "-1"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(members(res), vec!["a", "d", "e"]);
+
+    // Negative indexes count from the end
+    let res = run_cmd(
+        vec!["ZREMRANGEBYRANK", "z", "-1", "-1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(1));
+    let res = run_cmd(vec!["ZSCORE", "z", "e"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(None));
+
+    // Out of range removes nothing
+    let res = run_cmd(
+        vec!["ZREMRANGEBYRANK", "z", "5", "10"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(0));
+
+    // Removing everything deletes the key
+    let res = run_cmd(
+        vec!["ZREMRANGEBYRANK", "z", "0", "-1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(2));
+    let res = run_cmd(vec!["EXISTS", "z"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+}
+
+#[tokio::test]
+async fn test_zremrangebyscore() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    run_cmd(
+        vec!["ZADD", "z", "1", "a", "2", "b", "3", "c", "4", "d"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    let res = run_cmd(
+        vec!["ZREMRANGEBYSCORE", "z", "(1", "3"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(2));
+    let res = run_cmd(vec!["ZRANGE", "z", "0", "-1"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(members(res), vec!["a", "d"]);
+
+    let res = run_cmd(
+        vec!["ZREMRANGEBYSCORE", "z", "-inf", "+inf"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(2));
+    let res = run_cmd(vec!["EXISTS", "z"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+
+    let res = run_cmd(
+        vec!["ZREMRANGEBYSCORE", "z", "abc", "1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR min or max is not a float".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_zremrangebylex() {
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    run_cmd(
+        vec![
+            "ZADD", "z", "0", "a", "0", "b", "0", "c", "0", "d", "0", "e",
+        ],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    let res = run_cmd(
+        vec!["ZREMRANGEBYLEX", "z", "[b", "(d"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(2));
+    let res = run_cmd(vec!["ZRANGE", "z", "0", "-1"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(members(res), vec!["a", "d", "e"]);
+
+    // Wrong type
+    run_cmd(vec!["SET", "str", "v"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(
+        vec!["ZREMRANGEBYLEX", "str", "-", "+"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    match res {
+        Resp::Error(e) => assert!(e.starts_with("WRONGTYPE"), "got {}", e),
+        _ => panic!("Expected WRONGTYPE, got {:?}", res),
+    }
+
+    let res = run_cmd(
+        vec!["ZREMRANGEBYLEX", "z", "-", "+"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(3));
+    let res = run_cmd(vec!["EXISTS", "z"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+}
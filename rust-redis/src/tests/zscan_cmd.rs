@@ -183,3 +183,102 @@ async fn test_zscan_count() {
         _ => panic!("expected Array response"),
     }
 }
+
+#[tokio::test]
+async fn test_zscan_full_coverage_with_concurrent_deletes() {
+    use crate::tests::helper::run_cmd;
+    use std::collections::HashSet;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    // Stable members stay for the whole scan; doomed members are removed
+    // a few at a time between ZSCAN calls.
+    let mut zadd = vec!["ZADD".to_string(), "churn".to_string()];
+    for i in 0..200 {
+        zadd.push(i.to_string());
+        zadd.push(format!("stable{}", i));
+        zadd.push(i.to_string());
+        zadd.push(format!("doomed{}", i));
+    }
+    run_cmd(
+        zadd.iter().map(|s| s.as_str()).collect(),
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    let mut seen = HashSet::new();
+    let mut cursor = "0".to_string();
+    let mut next_doomed = 0;
+    loop {
+        let res = run_cmd(
+            vec!["ZSCAN", "churn", &cursor, "COUNT", "7"],
+            &mut conn_ctx,
+            &server_ctx,
+        )
+        .await;
+        let Resp::Array(Some(reply)) = res else {
+            panic!("expected Array response, got {:?}", res);
+        };
+        cursor = match &reply[0] {
+            Resp::BulkString(Some(b)) => String::from_utf8(b.to_vec()).unwrap(),
+            other => panic!("expected cursor, got {:?}", other),
+        };
+        let Resp::Array(Some(elements)) = &reply[1] else {
+            panic!("expected Array elements");
+        };
+        for pair in elements.chunks(2) {
+            if let Resp::BulkString(Some(member)) = &pair[0] {
+                seen.insert(member.clone());
+            }
+        }
+        if cursor == "0" {
+            break;
+        }
+
+        for _ in 0..5 {
+            if next_doomed < 200 {
+                let member = format!("doomed{}", next_doomed);
+                run_cmd(vec!["ZREM", "churn", &member], &mut conn_ctx, &server_ctx).await;
+                next_doomed += 1;
+            }
+        }
+    }
+
+    for i in 0..200 {
+        let member = Bytes::from(format!("stable{}", i));
+        assert!(seen.contains(&member), "ZSCAN skipped {:?}", member);
+    }
+}
+
+#[tokio::test]
+async fn test_zscan_match_applies_to_members_only() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    // "b" has a score that matches 1* but a member that does not
+    run_cmd(
+        vec!["ZADD", "z", "2", "1a", "10", "b"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    let res = run_cmd(
+        vec!["ZSCAN", "z", "0", "MATCH", "1*", "COUNT", "100"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let bulk = |s: &str| Resp::BulkString(Some(Bytes::from(s.to_string())));
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            bulk("0"),
+            Resp::Array(Some(vec![bulk("1a"), bulk("2")])),
+        ]))
+    );
+}
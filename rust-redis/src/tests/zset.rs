@@ -599,3 +599,474 @@ async fn test_zrevrank_ops() {
         _ => panic!("expected BulkString(None)"),
     }
 }
+
+#[tokio::test]
+async fn test_zset_score_formatting() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    let res = run_cmd(
+        vec!["ZADD", "zs", "+inf", "hi", "-inf", "lo", "1.0", "one"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(3));
+
+    let res = run_cmd(vec!["ZSCORE", "zs", "hi"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("inf"))));
+    let res = run_cmd(vec!["ZSCORE", "zs", "lo"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("-inf"))));
+    let res = run_cmd(vec!["ZSCORE", "zs", "one"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("1"))));
+
+    let res = run_cmd(
+        vec!["ZRANGE", "zs", "0", "-1", "WITHSCORES"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![
+            Resp::BulkString(Some(Bytes::from("lo"))),
+            Resp::BulkString(Some(Bytes::from("-inf"))),
+            Resp::BulkString(Some(Bytes::from("one"))),
+            Resp::BulkString(Some(Bytes::from("1"))),
+            Resp::BulkString(Some(Bytes::from("hi"))),
+            Resp::BulkString(Some(Bytes::from("inf"))),
+        ]))
+    );
+
+    let res = run_cmd(
+        vec!["ZINCRBY", "zs", "1.5", "one"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("2.5"))));
+
+    // NaN is never a valid score
+    let res = run_cmd(vec!["ZADD", "zs", "nan", "x"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR value is not a valid float".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_zadd_options() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    // XX never creates the key
+    let res = run_cmd(
+        vec!["ZADD", "z", "XX", "1", "a"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(0));
+    let res = run_cmd(vec!["EXISTS", "z"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+
+    run_cmd(vec!["ZADD", "z", "5", "a"], &mut conn_ctx, &server_ctx).await;
+
+    // NX leaves existing members alone
+    let res = run_cmd(
+        vec!["ZADD", "z", "NX", "1", "a", "2", "b"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(1));
+    let res = run_cmd(vec!["ZSCORE", "z", "a"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("5"))));
+
+    // GT only raises, LT only lowers
+    let res = run_cmd(
+        vec!["ZADD", "z", "GT", "CH", "4", "a"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(0));
+    let res = run_cmd(
+        vec!["ZADD", "z", "LT", "CH", "4", "a"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(1));
+    let res = run_cmd(vec!["ZSCORE", "z", "a"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("4"))));
+
+    let res = run_cmd(
+        vec!["ZADD", "z", "NX", "XX", "1", "a"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR XX and NX options at the same time are not compatible".to_string())
+    );
+    let res = run_cmd(
+        vec!["ZADD", "z", "GT", "LT", "1", "a"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error(
+            "ERR GT, LT, and/or NX options at the same time are not compatible".to_string()
+        )
+    );
+    let res = run_cmd(vec!["ZADD", "z", "1", "a", "2"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Error("ERR syntax error".to_string()));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_zadd_incr() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    let bulk = |s: &str| Resp::BulkString(Some(Bytes::from(s.to_string())));
+
+    // INCR returns the resulting score, creating the member from 0
+    let res = run_cmd(
+        vec!["ZADD", "z", "INCR", "2.5", "a"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("2.5"));
+    let res = run_cmd(
+        vec!["ZADD", "z", "INCR", "1", "a"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("3.5"));
+
+    // A blocked condition replies nil and leaves the score alone
+    let res = run_cmd(
+        vec!["ZADD", "z", "NX", "INCR", "1", "a"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::BulkString(None));
+    let res = run_cmd(
+        vec!["ZADD", "z", "XX", "INCR", "1", "b"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::BulkString(None));
+    let res = run_cmd(
+        vec!["ZADD", "z", "GT", "INCR", "-1", "a"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::BulkString(None));
+    let res = run_cmd(
+        vec!["ZADD", "z", "LT", "INCR", "-1", "a"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("2.5"));
+    let res = run_cmd(vec!["ZSCORE", "z", "b"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(None));
+
+    let res = run_cmd(
+        vec!["ZADD", "z", "INCR", "1", "a", "2", "b"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR INCR option supports a single increment-element pair".to_string())
+    );
+
+    run_cmd(vec!["ZADD", "inf", "inf", "m"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(
+        vec!["ZADD", "inf", "INCR", "-inf", "m"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR resulting score is not a number (NaN)".to_string())
+    );
+    let res = run_cmd(
+        vec!["ZADD", "nan", "INCR", "nan", "m"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR value is not a valid float".to_string())
+    );
+    let res = run_cmd(vec!["EXISTS", "nan"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+
+    // The flagged path still serves blocked BZPOPMIN clients
+    let ctx = server_ctx.clone();
+    let waiter = tokio::spawn(async move {
+        let mut conn_ctx = crate::tests::helper::create_connection_context();
+        run_cmd(vec!["BZPOPMIN", "bz", "5"], &mut conn_ctx, &ctx).await
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let res = run_cmd(
+        vec!["ZADD", "bz", "INCR", "7", "m"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, bulk("7"));
+    let res = waiter.await.unwrap();
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![bulk("bz"), bulk("m"), bulk("7")]))
+    );
+    let res = run_cmd(vec!["EXISTS", "bz"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::Integer(0));
+}
+
+#[tokio::test]
+async fn test_zrange_unified() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    let arr = |v: &[&str]| {
+        Resp::Array(Some(
+            v.iter()
+                .map(|s| Resp::BulkString(Some(Bytes::from(s.to_string()))))
+                .collect(),
+        ))
+    };
+
+    run_cmd(
+        vec!["ZADD", "z", "1", "a", "2", "b", "3", "c", "4", "d"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    // The legacy index form is unchanged, and REV reverses it
+    let res = run_cmd(vec!["ZRANGE", "z", "0", "1"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, arr(&["a", "b"]));
+    let res = run_cmd(
+        vec!["ZRANGE", "z", "0", "1", "REV", "WITHSCORES"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, arr(&["d", "4", "c", "3"]));
+
+    let res = run_cmd(
+        vec!["ZRANGE", "z", "(1", "3", "BYSCORE", "WITHSCORES"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, arr(&["b", "2", "c", "3"]));
+    let res = run_cmd(
+        vec!["ZRANGE", "z", "-inf", "+inf", "BYSCORE", "LIMIT", "1", "2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, arr(&["b", "c"]));
+
+    // With REV the first bound is the high end
+    let res = run_cmd(
+        vec!["ZRANGE", "z", "3", "(1", "BYSCORE", "REV"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, arr(&["c", "b"]));
+    let res = run_cmd(
+        vec!["ZRANGE", "z", "1", "3", "BYSCORE", "REV"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, arr(&[]));
+
+    run_cmd(
+        vec!["ZADD", "lex", "0", "a", "0", "b", "0", "c", "0", "d"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    let res = run_cmd(
+        vec!["ZRANGE", "lex", "[b", "+", "BYLEX", "LIMIT", "0", "2"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, arr(&["b", "c"]));
+    let res = run_cmd(
+        vec!["ZRANGE", "lex", "(c", "-", "BYLEX", "REV"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, arr(&["b", "a"]));
+
+    let res = run_cmd(
+        vec!["ZRANGE", "z", "0", "1", "LIMIT", "0", "1"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error(
+            "ERR syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+                .to_string()
+        )
+    );
+    let res = run_cmd(
+        vec!["ZRANGE", "lex", "-", "+", "BYLEX", "WITHSCORES"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error(
+            "ERR syntax error, WITHSCORES not supported in combination with BYLEX".to_string()
+        )
+    );
+    let res = run_cmd(
+        vec!["ZRANGE", "z", "a", "1", "BYSCORE"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(
+        res,
+        Resp::Error("ERR min or max is not a float".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_zrank_withscore() {
+    use crate::tests::helper::run_cmd;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+    let bulk = |s: &str| Resp::BulkString(Some(Bytes::from(s.to_string())));
+
+    run_cmd(
+        vec!["ZADD", "z", "1", "a", "2.5", "b", "3", "c"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+
+    let res = run_cmd(
+        vec!["ZRANK", "z", "b", "WITHSCORE"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Array(Some(vec![Resp::Integer(1), bulk("2.5")])));
+    let res = run_cmd(
+        vec!["ZREVRANK", "z", "a", "withscore"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Array(Some(vec![Resp::Integer(2), bulk("1")])));
+
+    // A missing member or key is a nil array with WITHSCORE, nil bulk without
+    let res = run_cmd(
+        vec!["ZRANK", "z", "x", "WITHSCORE"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Array(None));
+    let res = run_cmd(
+        vec!["ZREVRANK", "nokey", "a", "WITHSCORE"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Array(None));
+    let res = run_cmd(vec!["ZRANK", "z", "x"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(None));
+
+    let res = run_cmd(
+        vec!["ZRANK", "z", "a", "WITHSCORES"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Error("ERR syntax error".to_string()));
+}
+
+#[tokio::test]
+async fn test_zadd_skips_dead_zset_waiter() {
+    use crate::tests::helper::run_cmd;
+    use std::collections::VecDeque;
+
+    let server_ctx = crate::tests::helper::create_server_context();
+    let mut conn_ctx = crate::tests::helper::create_connection_context();
+
+    // A waiter whose client went away, followed by a live one
+    let (dead_tx, dead_rx) = tokio::sync::mpsc::channel(1);
+    drop(dead_rx);
+    let (live_tx, mut live_rx) = tokio::sync::mpsc::channel(1);
+    server_ctx.blocking_zset_waiters.insert(
+        (0, b"z".to_vec()),
+        VecDeque::from(vec![(dead_tx, true), (live_tx, true)]),
+    );
+
+    let res = run_cmd(
+        vec!["ZADD", "z", "1", "a", "2", "b"],
+        &mut conn_ctx,
+        &server_ctx,
+    )
+    .await;
+    assert_eq!(res, Resp::Integer(2));
+
+    // The dead waiter costs nothing; the live one gets the lowest member
+    let (key, member, score) = live_rx.try_recv().unwrap();
+    assert_eq!(
+        (key.as_slice(), member.as_slice(), score),
+        (&b"z"[..], &b"a"[..], 1.0)
+    );
+    let res = run_cmd(vec!["ZRANGE", "z", "0", "-1"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(
+        res,
+        Resp::Array(Some(vec![Resp::BulkString(Some(Bytes::from("b")))]))
+    );
+
+    // With only a dead waiter the member stays in the set
+    let (dead_tx, dead_rx) = tokio::sync::mpsc::channel(1);
+    drop(dead_rx);
+    server_ctx
+        .blocking_zset_waiters
+        .insert((0, b"z2".to_vec()), VecDeque::from(vec![(dead_tx, false)]));
+    run_cmd(vec!["ZADD", "z2", "5", "m"], &mut conn_ctx, &server_ctx).await;
+    let res = run_cmd(vec!["ZSCORE", "z2", "m"], &mut conn_ctx, &server_ctx).await;
+    assert_eq!(res, Resp::BulkString(Some(Bytes::from("5"))));
+}
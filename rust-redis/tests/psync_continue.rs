@@ -0,0 +1,221 @@
+//! Runs a master and a replica binary with a proxy on the replication link,
+//! drops the link, and checks that the replica resumes with `+CONTINUE`
+//! instead of a second full resync.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn connect(port: u16) -> TcpStream {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        match TcpStream::connect(("127.0.0.1", port)) {
+            Ok(stream) => return stream,
+            Err(_) if Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            Err(e) => panic!("server did not start: {}", e),
+        }
+    }
+}
+
+/// Kills the server when the test ends, even if it panicked.
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn start_server(dir: &Path, name: &str, port: u16) -> Server {
+    let conf_path = dir.join(format!("{}.conf", name));
+    std::fs::write(
+        &conf_path,
+        format!(
+            "bind 127.0.0.1\nport {}\ndbfilename {}\nsave \"\"\n",
+            port,
+            dir.join(format!("{}.rdb", name)).display()
+        ),
+    )
+    .unwrap();
+    let server = Command::new(env!("CARGO_BIN_EXE_server"))
+        .arg(&conf_path)
+        .current_dir(dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    connect(port);
+    Server(server)
+}
+
+/// Sends one command and returns its reply line, or the payload of a bulk
+/// string reply.
+fn request(port: u16, args: &[&str]) -> String {
+    let mut stream = connect(port);
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut req = format!("*{}\r\n", args.len());
+    for arg in args {
+        req.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+    }
+    stream.write_all(req.as_bytes()).unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    if line.starts_with('$') && !line.starts_with("$-1") {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+    }
+    line.trim_end().to_string()
+}
+
+fn wait_for(port: u16, key: &str, value: &str) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while request(port, &["GET", key]) != value {
+        assert!(
+            Instant::now() < deadline,
+            "replica never applied {} = {}",
+            key,
+            value
+        );
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// One proxied replication link and everything the master sent over it.
+struct Link {
+    replica: TcpStream,
+    master: TcpStream,
+    from_master: Arc<Mutex<Vec<u8>>>,
+}
+
+/// Forwards replica connections to the master while `accepting`, so the
+/// test can cut the link and hold the replica off until it is ready.
+struct Proxy {
+    port: u16,
+    accepting: Arc<AtomicBool>,
+    links: Arc<Mutex<Vec<Link>>>,
+}
+
+impl Proxy {
+    fn start(master_port: u16) -> Proxy {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accepting = Arc::new(AtomicBool::new(true));
+        let links = Arc::new(Mutex::new(Vec::new()));
+        let (accepting_c, links_c) = (accepting.clone(), links.clone());
+        std::thread::spawn(move || {
+            for replica in listener.incoming() {
+                let Ok(replica) = replica else { break };
+                if !accepting_c.load(Ordering::SeqCst) {
+                    continue;
+                }
+                let master = TcpStream::connect(("127.0.0.1", master_port)).unwrap();
+                let from_master = Arc::new(Mutex::new(Vec::new()));
+                pipe(replica.try_clone().unwrap(), master.try_clone().unwrap(), None);
+                pipe(
+                    master.try_clone().unwrap(),
+                    replica.try_clone().unwrap(),
+                    Some(from_master.clone()),
+                );
+                links_c.lock().unwrap().push(Link {
+                    replica,
+                    master,
+                    from_master,
+                });
+            }
+        });
+        Proxy {
+            port,
+            accepting,
+            links,
+        }
+    }
+
+    fn drop_links(&self) {
+        for link in self.links.lock().unwrap().iter() {
+            let _ = link.replica.shutdown(Shutdown::Both);
+            let _ = link.master.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn received(&self, link: usize) -> String {
+        let links = self.links.lock().unwrap();
+        String::from_utf8_lossy(&links[link].from_master.lock().unwrap()).to_string()
+    }
+
+    fn link_count(&self) -> usize {
+        self.links.lock().unwrap().len()
+    }
+}
+
+fn pipe(mut from: TcpStream, mut to: TcpStream, log: Option<Arc<Mutex<Vec<u8>>>>) {
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        while let Ok(n) = from.read(&mut buf) {
+            if n == 0 || to.write_all(&buf[..n]).is_err() {
+                break;
+            }
+            if let Some(log) = &log {
+                log.lock().unwrap().extend_from_slice(&buf[..n]);
+            }
+        }
+        let _ = to.shutdown(Shutdown::Both);
+    });
+}
+
+#[test]
+fn test_replica_reconnects_with_continue() {
+    let dir = std::env::temp_dir().join(format!("rust-redis-psync-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let (master_port, replica_port) = (free_port(), free_port());
+    let _master = start_server(&dir, "master", master_port);
+    let _replica = start_server(&dir, "replica", replica_port);
+    let proxy = Proxy::start(master_port);
+
+    assert_eq!(request(master_port, &["SET", "k0", "v0"]), "+OK");
+    let port = proxy.port.to_string();
+    assert_eq!(
+        request(replica_port, &["REPLICAOF", "127.0.0.1", &port]),
+        "+OK"
+    );
+    wait_for(replica_port, "k0", "v0");
+    assert!(proxy.received(0).starts_with("+FULLRESYNC"));
+
+    // Cut the link and write while the replica cannot get back in
+    proxy.accepting.store(false, Ordering::SeqCst);
+    proxy.drop_links();
+    for i in 1..=3 {
+        let (key, value) = (format!("k{}", i), format!("v{}", i));
+        assert_eq!(request(master_port, &["SET", &key, &value]), "+OK");
+    }
+    proxy.accepting.store(true, Ordering::SeqCst);
+
+    for i in 1..=3 {
+        wait_for(replica_port, &format!("k{}", i), &format!("v{}", i));
+    }
+    assert_eq!(proxy.link_count(), 2);
+    let resumed = proxy.received(1);
+    assert!(resumed.starts_with("+CONTINUE"), "{:?}", resumed);
+    assert!(!resumed.contains("FULLRESYNC"), "{:?}", resumed);
+    assert!(!resumed.contains("k0"), "{:?}", resumed);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,89 @@
+//! Runs the server binary and stops it with SIGTERM, checking that the
+//! signal handler saves a snapshot before the process exits.
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn connect(port: u16) -> TcpStream {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        match TcpStream::connect(("127.0.0.1", port)) {
+            Ok(stream) => return stream,
+            Err(_) if Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            Err(e) => panic!("server did not start: {}", e),
+        }
+    }
+}
+
+#[test]
+fn test_sigterm_saves_rdb_before_exit() {
+    let dir = std::env::temp_dir().join(format!("rust-redis-sigterm-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let rdb_path = dir.join("dump.rdb");
+    let conf_path = dir.join("redis.conf");
+    let port = free_port();
+    std::fs::write(
+        &conf_path,
+        format!(
+            "bind 127.0.0.1\nport {}\ndbfilename {}\nsave 3600 1\n",
+            port,
+            rdb_path.display()
+        ),
+    )
+    .unwrap();
+
+    let mut server = Command::new(env!("CARGO_BIN_EXE_server"))
+        .arg(&conf_path)
+        .current_dir(&dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let mut stream = connect(port);
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+        .write_all(b"*3\r\n$3\r\nSET\r\n$3\r\nfoo\r\n$3\r\nbar\r\n")
+        .unwrap();
+    let mut buf = [0u8; 5];
+    stream.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"+OK\r\n");
+    assert!(!rdb_path.exists());
+
+    unsafe {
+        libc::kill(server.id() as libc::pid_t, libc::SIGTERM);
+    }
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let status = loop {
+        if let Some(status) = server.try_wait().unwrap() {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = server.kill();
+            panic!("server did not exit on SIGTERM");
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    assert!(status.success(), "server exited with {}", status);
+    let rdb = std::fs::read(&rdb_path).expect("no snapshot saved on SIGTERM");
+    assert!(rdb.starts_with(b"REDIS"));
+    assert!(rdb.windows(3).any(|w| w == b"foo"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}